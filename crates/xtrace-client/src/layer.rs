@@ -22,6 +22,9 @@
 //! // Events with metric= and value= are auto-pushed:
 //! tracing::info!(metric = "zene_tokens", value = 100, model = "gpt-4");
 //!
+//! // Several metrics with shared labels from one event:
+//! tracing::info!(metric.tokens = 512, metric.cost = 0.02, model = "gpt-4");
+//!
 //! // Trace ID is available anywhere inside a span:
 //! if let Some(tid) = current_trace_id() {
 //!     println!("trace_id = {}", tid);
@@ -33,14 +36,27 @@
 //!     // ...
 //! }
 //! ```
+//!
+//! With [`ExportMode::Otlp`] the span tree itself is exported instead: closed
+//! spans are encoded as OTLP JSON and posted to `/api/public/otel/v1/traces`,
+//! so the server maps them (attributes, nesting, `gen_ai.*` / `langfuse.*`
+//! keys) exactly like spans from OTel-instrumented services.
+//!
+//! ```ignore
+//! let layer = XtraceLayer::with_export_mode(
+//!     client,
+//!     ExportMode::Otlp { service_name: "my-agent".into() },
+//! );
+//! ```
 
 use chrono::Utc;
+use serde_json::{json, Value as JsonValue};
 use std::collections::HashMap;
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::OnceLock;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tracing::field::Field;
 use tracing::field::Visit;
 use tracing_subscriber::layer::Context;
@@ -54,6 +70,10 @@ use crate::MetricPoint;
 /// Default metric name for span duration.
 pub const SPAN_DURATION_METRIC: &str = "span_duration";
 
+/// Numeric fields with this prefix are pushed as metrics named after the rest
+/// of the field name (`metric.tokens = 5` pushes `tokens`).
+pub const METRIC_FIELD_PREFIX: &str = "metric.";
+
 const BATCH_SIZE: usize = 50;
 const FLUSH_INTERVAL_MS: u64 = 500;
 
@@ -101,11 +121,30 @@ pub fn current_trace_id() -> Option<Uuid> {
 // XtraceLayer
 // ---------------------------------------------------------------------------
 
+/// How [`XtraceLayer`] reports spans.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ExportMode {
+    /// Span durations are pushed as the `span_duration` metric (default).
+    #[default]
+    Metrics,
+    /// Closed spans are posted as OTLP JSON to `/api/public/otel/v1/traces`
+    /// and stored as traces and observations. Metric events are still pushed
+    /// as metrics.
+    Otlp {
+        /// `service.name` resource attribute of the exported spans.
+        service_name: String,
+    },
+}
+
 /// A tracing Layer that pushes metrics to xtrace and propagates trace IDs.
 ///
 /// - **Events** with `metric` and `value` fields are pushed as metrics.
 ///   Other string/numeric fields (session_id, task_id, model, etc.) become labels.
-/// - **Span durations** are reported as `span_duration` metric with `span_name` label.
+///   Each numeric `metric.<name>` field is pushed as a separate `<name>` metric
+///   with the same labels and timestamp.
+/// - **Span durations** are reported as `span_duration` metric with `span_name` label,
+///   or, in [`ExportMode::Otlp`], the spans themselves are exported with their
+///   fields as attributes and events attached.
 /// - **Trace IDs** are auto-generated for root spans and inherited by children.
 ///   The `trace_id` is injected into every metric's labels automatically.
 ///   If a span carries an explicit `trace_id` field, that value is used instead.
@@ -115,23 +154,74 @@ pub struct XtraceLayer {
 }
 
 struct XtraceLayerInner {
-    tx: mpsc::SyncSender<MetricPoint>,
+    tx: mpsc::SyncSender<Outgoing>,
     span_records: Mutex<Vec<SpanRecord>>,
+    otlp: bool,
+}
+
+enum Outgoing {
+    Metric(MetricPoint),
+    /// An OTLP JSON span.
+    Span(JsonValue),
 }
 
 struct SpanRecord {
     span_id: u64,
     created_at: Instant,
     name: String,
+    /// Only collected in OTLP mode.
+    otlp: Option<OtlpSpan>,
+}
+
+struct OtlpSpan {
+    trace_id: Uuid,
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    start: SystemTime,
+    attributes: Vec<JsonValue>,
+    events: Vec<JsonValue>,
+    error: Option<String>,
+}
+
+impl OtlpSpan {
+    fn into_json(self, name: String) -> JsonValue {
+        let mut span = json!({
+            "traceId": self.trace_id.simple().to_string(),
+            "spanId": hex(&self.span_id),
+            "name": name,
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(SystemTime::now()),
+            "attributes": self.attributes,
+            "events": self.events,
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = json!(hex(&parent));
+        }
+        if let Some(message) = self.error {
+            span["status"] = json!({ "code": 2, "message": message });
+        }
+        span
+    }
 }
 
 impl XtraceLayer {
     /// Create a new XtraceLayer and spawn the background flusher.
     pub fn new(client: Client) -> Self {
+        Self::with_export_mode(client, ExportMode::Metrics)
+    }
+
+    /// Create a new XtraceLayer reporting spans according to `mode`.
+    pub fn with_export_mode(client: Client, mode: ExportMode) -> Self {
         let (tx, rx) = mpsc::sync_channel(1000);
+        let service_name = match &mode {
+            ExportMode::Metrics => None,
+            ExportMode::Otlp { service_name } => Some(service_name.clone()),
+        };
         let inner = Arc::new(XtraceLayerInner {
             tx,
             span_records: Mutex::new(Vec::new()),
+            otlp: service_name.is_some(),
         });
 
         let client = client.clone();
@@ -140,50 +230,112 @@ impl XtraceLayer {
                 Ok(r) => r,
                 Err(_) => return,
             };
-            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            let mut batch = Batch {
+                metrics: Vec::with_capacity(BATCH_SIZE),
+                spans: Vec::new(),
+            };
             let mut last_flush = Instant::now();
             let flush_interval = std::time::Duration::from_millis(FLUSH_INTERVAL_MS);
 
             loop {
                 match rx.recv_timeout(flush_interval) {
-                    Ok(point) => {
-                        batch.push(point);
-                        if batch.len() >= BATCH_SIZE {
-                            flush_batch(&rt, &client, &mut batch);
+                    Ok(item) => {
+                        match item {
+                            Outgoing::Metric(point) => batch.metrics.push(point),
+                            Outgoing::Span(span) => batch.spans.push(span),
+                        }
+                        // Also flush on time, so a steady trickle below
+                        // BATCH_SIZE is not held back indefinitely.
+                        if batch.len() >= BATCH_SIZE || last_flush.elapsed() >= flush_interval {
+                            batch.flush(&rt, &client, service_name.as_deref());
                             last_flush = Instant::now();
                         }
                     }
                     Err(mpsc::RecvTimeoutError::Timeout) => {
-                        if !batch.is_empty() && last_flush.elapsed() >= flush_interval {
-                            flush_batch(&rt, &client, &mut batch);
+                        if batch.len() > 0 && last_flush.elapsed() >= flush_interval {
+                            batch.flush(&rt, &client, service_name.as_deref());
                             last_flush = Instant::now();
                         }
                     }
                     Err(mpsc::RecvTimeoutError::Disconnected) => break,
                 }
             }
-            if !batch.is_empty() {
-                flush_batch(&rt, &client, &mut batch);
-            }
+            batch.flush(&rt, &client, service_name.as_deref());
         });
 
         Self { inner }
     }
 
-    fn try_send(&self, point: MetricPoint) {
-        let _ = self.inner.tx.try_send(point);
+    pub(crate) fn try_send(&self, point: MetricPoint) {
+        let _ = self.inner.tx.try_send(Outgoing::Metric(point));
     }
 }
 
-fn flush_batch(rt: &tokio::runtime::Runtime, client: &Client, batch: &mut Vec<MetricPoint>) {
-    if batch.is_empty() {
-        return;
+struct Batch {
+    metrics: Vec<MetricPoint>,
+    spans: Vec<JsonValue>,
+}
+
+impl Batch {
+    fn len(&self) -> usize {
+        self.metrics.len().max(self.spans.len())
     }
-    let points = std::mem::take(batch);
-    let client = client.clone();
-    rt.block_on(async move {
-        let _ = client.push_metrics(&points).await;
-    });
+
+    fn flush(&mut self, rt: &tokio::runtime::Runtime, client: &Client, service_name: Option<&str>) {
+        let points = std::mem::take(&mut self.metrics);
+        let spans = std::mem::take(&mut self.spans);
+        let client = client.clone();
+        rt.block_on(async move {
+            if !points.is_empty() {
+                let _ = client.push_metrics(&points).await;
+            }
+            if !spans.is_empty() {
+                let request = otlp_request(service_name.unwrap_or("unknown_service"), spans);
+                let _ = client.export_otlp_traces(&request).await;
+            }
+        });
+    }
+}
+
+/// Wraps spans into an OTLP `ExportTraceServiceRequest`.
+fn otlp_request(service_name: &str, spans: Vec<JsonValue>) -> JsonValue {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    otlp_kv("service.name", json!({ "stringValue": service_name })),
+                    otlp_kv("telemetry.sdk.name", json!({ "stringValue": "xtrace-client" })),
+                    otlp_kv("telemetry.sdk.language", json!({ "stringValue": "rust" })),
+                    otlp_kv("telemetry.sdk.version", json!({ "stringValue": env!("CARGO_PKG_VERSION") })),
+                ]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "xtrace-client", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }]
+        }]
+    })
+}
+
+fn otlp_kv(key: &str, value: JsonValue) -> JsonValue {
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos(t: SystemTime) -> String {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn new_span_id() -> [u8; 8] {
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&Uuid::new_v4().as_bytes()[..8]);
+    id
 }
 
 // ---------------------------------------------------------------------------
@@ -207,19 +359,20 @@ where
         let mut tid_visitor = TraceIdVisitor::default();
         attrs.record(&mut tid_visitor);
 
+        let parent_id = if attrs.is_root() {
+            None
+        } else if let Some(parent) = attrs.parent() {
+            Some(parent.clone())
+        } else {
+            ctx.current_span().id().cloned()
+        };
+
         let trace_id = if let Some(explicit) = tid_visitor.trace_id {
             explicit
         } else {
             // Inherit from parent, or generate a new one for root spans.
-            let parent_id = if attrs.is_root() {
-                None
-            } else if let Some(parent) = attrs.parent() {
-                Some(parent.clone())
-            } else {
-                ctx.current_span().id().cloned()
-            };
-
             let parent_tid = parent_id
+                .as_ref()
                 .and_then(|pid| trace_id_store().lock().ok()?.get(&pid.into_u64()).copied());
 
             parent_tid.unwrap_or_else(Uuid::new_v4)
@@ -231,23 +384,85 @@ where
         }
 
         let mut guard = self.inner.span_records.lock().unwrap();
+        let otlp = self.inner.otlp.then(|| {
+            let parent_span_id = parent_id.and_then(|pid| {
+                let pid = pid.into_u64();
+                guard
+                    .iter()
+                    .find(|r| r.span_id == pid)
+                    .and_then(|r| r.otlp.as_ref())
+                    .filter(|p| p.trace_id == trace_id)
+                    .map(|p| p.span_id)
+            });
+            let mut visitor = AttributeVisitor::default();
+            attrs.record(&mut visitor);
+            let mut attributes = visitor.attributes;
+            attributes.push(otlp_kv(
+                "code.namespace",
+                json!({ "stringValue": attrs.metadata().target() }),
+            ));
+            OtlpSpan {
+                trace_id,
+                span_id: new_span_id(),
+                parent_span_id,
+                start: SystemTime::now(),
+                attributes,
+                events: Vec::new(),
+                error: None,
+            }
+        });
         guard.push(SpanRecord {
             span_id: key,
             created_at: Instant::now(),
             name,
+            otlp,
         });
     }
 
+    fn on_record(
+        &self,
+        id: &tracing::Id,
+        values: &tracing::span::Record<'_>,
+        _ctx: Context<'_, S>,
+    ) {
+        if !self.inner.otlp {
+            return;
+        }
+        let key = id.clone().into_u64();
+        let mut visitor = AttributeVisitor::default();
+        values.record(&mut visitor);
+        let mut guard = self.inner.span_records.lock().unwrap();
+        if let Some(span) = guard
+            .iter_mut()
+            .find(|r| r.span_id == key)
+            .and_then(|r| r.otlp.as_mut())
+        {
+            // Later values replace earlier ones for the same key.
+            for kv in visitor.attributes {
+                span.attributes.retain(|a| a["key"] != kv["key"]);
+                span.attributes.push(kv);
+            }
+        }
+    }
+
     fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        if self.inner.otlp {
+            self.record_span_event(event, &ctx);
+        }
+
         let mut visitor = MetricEventVisitor::default();
         event.record(&mut visitor);
-        if let Some(mut point) = visitor.into_metric_point() {
-            // Auto-inject trace_id from current span context.
-            if let Some(tid) = ctx
-                .current_span()
-                .id()
-                .and_then(|sid| trace_id_store().lock().ok()?.get(&sid.into_u64()).copied())
-            {
+        let points = visitor.into_metric_points();
+        if points.is_empty() {
+            return;
+        }
+        // Auto-inject trace_id from current span context.
+        let tid = ctx
+            .current_span()
+            .id()
+            .and_then(|sid| trace_id_store().lock().ok()?.get(&sid.into_u64()).copied());
+        for mut point in points {
+            if let Some(tid) = tid {
                 point
                     .labels
                     .entry("trace_id".to_string())
@@ -266,16 +481,23 @@ where
             .ok()
             .and_then(|mut s| s.remove(&key));
 
-        let (duration_secs, span_name) = {
+        let rec = {
             let mut guard = self.inner.span_records.lock().unwrap();
             let pos = guard.iter().position(|r| r.span_id == key);
             if let Some(pos) = pos {
-                let rec = guard.remove(pos);
-                (rec.created_at.elapsed().as_secs_f64(), rec.name)
+                guard.remove(pos)
             } else {
                 return;
             }
         };
+        if let Some(span) = rec.otlp {
+            let _ = self
+                .inner
+                .tx
+                .try_send(Outgoing::Span(span.into_json(rec.name)));
+            return;
+        }
+        let (duration_secs, span_name) = (rec.created_at.elapsed().as_secs_f64(), rec.name);
 
         let mut labels = HashMap::new();
         labels.insert("span_name".to_string(), span_name);
@@ -293,10 +515,98 @@ where
     }
 }
 
+impl XtraceLayer {
+    /// Attaches a tracing event to the current span as an OTLP span event.
+    /// `ERROR` events also mark the span status as error.
+    fn record_span_event<S>(&self, event: &tracing::Event<'_>, ctx: &Context<'_, S>)
+    where
+        S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let Some(key) = ctx.current_span().id().map(|id| id.into_u64()) else {
+            return;
+        };
+        let mut visitor = AttributeVisitor::default();
+        event.record(&mut visitor);
+        let meta = event.metadata();
+        let name = visitor
+            .message
+            .clone()
+            .unwrap_or_else(|| meta.name().to_string());
+        let mut attributes = visitor.attributes;
+        attributes.push(otlp_kv(
+            "level",
+            json!({ "stringValue": meta.level().as_str() }),
+        ));
+
+        let mut guard = self.inner.span_records.lock().unwrap();
+        if let Some(span) = guard
+            .iter_mut()
+            .find(|r| r.span_id == key)
+            .and_then(|r| r.otlp.as_mut())
+        {
+            if *meta.level() == tracing::Level::ERROR {
+                span.error = Some(name.clone());
+            }
+            span.events.push(json!({
+                "timeUnixNano": unix_nanos(SystemTime::now()),
+                "name": name,
+                "attributes": attributes,
+            }));
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Visitors
 // ---------------------------------------------------------------------------
 
+/// Collects all fields as OTLP `KeyValue`s; `message` is kept separately.
+#[derive(Default)]
+struct AttributeVisitor {
+    message: Option<String>,
+    attributes: Vec<JsonValue>,
+}
+
+impl AttributeVisitor {
+    fn push(&mut self, field: &Field, value: JsonValue) {
+        self.attributes.push(otlp_kv(field.name(), value));
+    }
+}
+
+impl Visit for AttributeVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        } else {
+            self.push(field, json!({ "stringValue": format!("{:?}", value) }));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.push(field, json!({ "stringValue": value }));
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, json!({ "doubleValue": value }));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, json!({ "boolValue": value }));
+    }
+}
+
 /// Extracts an explicit `trace_id` field from span attributes.
 #[derive(Default)]
 struct TraceIdVisitor {
@@ -319,25 +629,47 @@ impl Visit for TraceIdVisitor {
 struct MetricEventVisitor {
     metric: Option<String>,
     value: Option<f64>,
+    /// `metric.<name>` fields, in field order.
+    named: Vec<(String, f64)>,
     labels: HashMap<String, String>,
 }
 
 impl MetricEventVisitor {
-    fn into_metric_point(self) -> Option<MetricPoint> {
-        let metric = self.metric?;
-        let value = self.value.unwrap_or(0.0);
-        Some(MetricPoint {
-            name: metric,
-            labels: self.labels,
-            value,
-            timestamp: Utc::now(),
-        })
+    fn into_metric_points(self) -> Vec<MetricPoint> {
+        let timestamp = Utc::now();
+        let metric = self.metric.map(|name| (name, self.value.unwrap_or(0.0)));
+        metric
+            .into_iter()
+            .chain(self.named)
+            .map(|(name, value)| MetricPoint {
+                name,
+                labels: self.labels.clone(),
+                value,
+                timestamp,
+            })
+            .collect()
+    }
+
+    /// Records `value` if `field` is a `metric.<name>` field.
+    fn record_named(&mut self, field: &Field, value: f64) -> bool {
+        match field.name().strip_prefix(METRIC_FIELD_PREFIX) {
+            Some(name) if !name.is_empty() => {
+                self.named.push((name.to_string(), value));
+                true
+            }
+            _ => false,
+        }
     }
 }
 
 impl Visit for MetricEventVisitor {
     fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
-        if LABEL_FIELDS.contains(&field.name()) {
+        if field.name().starts_with(METRIC_FIELD_PREFIX) {
+            // `metric.x = %v` / `?v`: accept values that format as a number.
+            if let Ok(v) = format!("{:?}", value).parse::<f64>() {
+                self.record_named(field, v);
+            }
+        } else if LABEL_FIELDS.contains(&field.name()) {
             self.labels
                 .insert(field.name().to_string(), format!("{:?}", value));
         }
@@ -346,6 +678,10 @@ impl Visit for MetricEventVisitor {
     fn record_str(&mut self, field: &Field, value: &str) {
         if field.name() == "metric" {
             self.metric = Some(value.to_string());
+        } else if field.name().starts_with(METRIC_FIELD_PREFIX) {
+            if let Ok(v) = value.trim().parse::<f64>() {
+                self.record_named(field, v);
+            }
         } else if LABEL_FIELDS.contains(&field.name()) {
             self.labels
                 .insert(field.name().to_string(), value.to_string());
@@ -355,7 +691,7 @@ impl Visit for MetricEventVisitor {
     fn record_f64(&mut self, field: &Field, value: f64) {
         if field.name() == "value" {
             self.value = Some(value);
-        } else if LABEL_FIELDS.contains(&field.name()) {
+        } else if !self.record_named(field, value) && LABEL_FIELDS.contains(&field.name()) {
             self.labels
                 .insert(field.name().to_string(), value.to_string());
         }
@@ -364,7 +700,7 @@ impl Visit for MetricEventVisitor {
     fn record_i64(&mut self, field: &Field, value: i64) {
         if field.name() == "value" {
             self.value = Some(value as f64);
-        } else if LABEL_FIELDS.contains(&field.name()) {
+        } else if !self.record_named(field, value as f64) && LABEL_FIELDS.contains(&field.name()) {
             self.labels
                 .insert(field.name().to_string(), value.to_string());
         }
@@ -373,7 +709,7 @@ impl Visit for MetricEventVisitor {
     fn record_u64(&mut self, field: &Field, value: u64) {
         if field.name() == "value" {
             self.value = Some(value as f64);
-        } else if LABEL_FIELDS.contains(&field.name()) {
+        } else if !self.record_named(field, value as f64) && LABEL_FIELDS.contains(&field.name()) {
             self.labels
                 .insert(field.name().to_string(), value.to_string());
         }
@@ -1,16 +1,22 @@
 #[cfg(feature = "tracing")]
 pub mod layer;
+pub mod recorder;
+#[cfg(feature = "tracing")]
+pub mod runtime_metrics;
 #[cfg(feature = "tracing")]
 pub use layer::current_trace_id;
 #[cfg(feature = "tracing")]
-pub use layer::XtraceLayer;
+pub use layer::{ExportMode, XtraceLayer};
+pub use recorder::GenerationRecorder;
 
 use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use url::Url;
 use uuid::Uuid;
 
@@ -21,36 +27,276 @@ pub enum Error {
 
     #[error("http error: {0}")]
     Http(#[from] reqwest::Error),
+
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("request body of {bytes} bytes exceeds the limit of {max} bytes")]
+    RequestTooLarge { bytes: usize, max: usize },
 }
 
+/// Header used to negotiate the server's public API version.
+pub const API_VERSION_HEADER: &str = "x-xtrace-api-version";
+/// Public API version this client's DTOs correspond to.
+pub const API_VERSION: &str = "1";
+
 #[derive(Clone)]
 pub struct Client {
-    base_url: Url,
+    endpoints: Arc<Endpoints>,
     http: reqwest::Client,
+    max_io_bytes: Option<usize>,
+    max_request_bytes: Option<usize>,
 }
 
-impl Client {
-    pub fn new(base_url: &str, bearer_token: &str) -> Result<Self, Error> {
-        let base_url = Url::parse(base_url)?;
+/// Base URLs in priority order (primary first) plus shared failover state.
+struct Endpoints {
+    urls: Vec<Url>,
+    active: AtomicUsize,
+    consecutive_failures: AtomicU32,
+    failover_threshold: u32,
+    recovery_interval: Duration,
+    last_recovery_probe: Mutex<Option<Instant>>,
+}
+
+impl Endpoints {
+    fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed) % self.urls.len()
+    }
+
+    fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Records a failure against `idx`; returns true when the client switched endpoints.
+    fn record_failure(&self, idx: usize) -> bool {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.urls.len() < 2 || failures < self.failover_threshold {
+            return false;
+        }
+        let next = (idx + 1) % self.urls.len();
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        self.active
+            .compare_exchange(idx, next, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+fn is_endpoint_failure(err: &reqwest::Error) -> bool {
+    if err.is_connect() || err.is_timeout() {
+        return true;
+    }
+    matches!(
+        err.status().map(|s| s.as_u16()),
+        Some(502) | Some(503) | Some(504)
+    )
+}
+
+/// Whether the server may have acted on a request that failed with `err`: it
+/// was sent, and the answer does not say it was turned away.
+fn may_have_been_processed(err: &reqwest::Error) -> bool {
+    !err.is_connect() && err.status().map(|s| s.as_u16()) != Some(503)
+}
+
+/// Builds a [`Client`] with fallback endpoints; see [`Client::builder`].
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    base_url: String,
+    bearer_token: String,
+    fallback_urls: Vec<String>,
+    failover_threshold: u32,
+    recovery_interval: Duration,
+    timeout: Duration,
+}
+
+impl ClientBuilder {
+    /// Adds a fallback base URL, tried in insertion order after the primary.
+    pub fn fallback_url(mut self, base_url: &str) -> Self {
+        self.fallback_urls.push(base_url.to_string());
+        self
+    }
+
+    /// Consecutive connection/5xx failures before failing over (default 3).
+    pub fn failover_threshold(mut self, threshold: u32) -> Self {
+        self.failover_threshold = threshold.max(1);
+        self
+    }
+
+    /// How often the primary is health-checked while a fallback is active (default 30s).
+    pub fn recovery_interval(mut self, interval: Duration) -> Self {
+        self.recovery_interval = interval;
+        self
+    }
+
+    /// Timeout of each request (default 30s).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let urls = std::iter::once(&self.base_url)
+            .chain(&self.fallback_urls)
+            .map(|url| Url::parse(url))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let mut headers = HeaderMap::new();
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", bearer_token)).unwrap(),
+            HeaderValue::from_str(&format!("Bearer {}", self.bearer_token)).unwrap(),
         );
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        // Pin response shapes to the API version these DTOs were written against.
+        headers.insert(API_VERSION_HEADER, HeaderValue::from_static(API_VERSION));
 
         let http = reqwest::Client::builder()
             .default_headers(headers)
-            .timeout(Duration::from_secs(30))
+            .timeout(self.timeout)
             .build()?;
 
-        Ok(Self { base_url, http })
+        Ok(Client {
+            endpoints: Arc::new(Endpoints {
+                urls,
+                active: AtomicUsize::new(0),
+                consecutive_failures: AtomicU32::new(0),
+                failover_threshold: self.failover_threshold,
+                recovery_interval: self.recovery_interval,
+                last_recovery_probe: Mutex::new(None),
+            }),
+            http,
+            max_io_bytes: None,
+            max_request_bytes: None,
+        })
+    }
+}
+
+impl Client {
+    pub fn new(base_url: &str, bearer_token: &str) -> Result<Self, Error> {
+        Self::builder(base_url, bearer_token).build()
+    }
+
+    /// A builder for a client with fallback endpoints. Clones of the built
+    /// client share its failover state.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), xtrace_client::Error> {
+    /// use std::time::Duration;
+    ///
+    /// let client = xtrace_client::Client::builder("http://xtrace-a:8742/", "token")
+    ///     .fallback_url("http://xtrace-b:8742/")
+    ///     .failover_threshold(3)
+    ///     .recovery_interval(Duration::from_secs(30))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder(base_url: &str, bearer_token: &str) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.to_string(),
+            bearer_token: bearer_token.to_string(),
+            fallback_urls: Vec::new(),
+            failover_threshold: 3,
+            recovery_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Truncate any trace/observation `input` or `output` whose JSON encoding exceeds
+    /// `max_bytes` before `ingest_batch` sends it. Truncated fields are recorded under
+    /// the `xtrace_truncated` metadata key with their original size.
+    pub fn with_max_io_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_io_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Fail `ingest_batch` with [`Error::RequestTooLarge`] instead of sending a body
+    /// (after I/O truncation) larger than `max_bytes`, which the server would reject
+    /// with `413`. Set it to the server's `XTRACE_MAX_REQUEST_BODY_BYTES` (20 MiB by
+    /// default).
+    pub fn with_max_request_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_request_bytes = Some(max_bytes);
+        self
+    }
+
+    /// The base URL requests are currently sent to.
+    pub fn active_base_url(&self) -> &Url {
+        &self.endpoints.urls[self.endpoints.active()]
+    }
+
+    /// While on a fallback, periodically probe the primary's `/healthz` and switch back once it answers.
+    async fn maybe_recover_primary(&self) {
+        let ep = &self.endpoints;
+        if ep.active() == 0 {
+            return;
+        }
+        {
+            let mut last = match ep.last_recovery_probe.lock() {
+                Ok(g) => g,
+                Err(_) => return,
+            };
+            if last.is_some_and(|t| t.elapsed() < ep.recovery_interval) {
+                return;
+            }
+            *last = Some(Instant::now());
+        }
+        let Ok(url) = ep.urls[0].join("healthz") else {
+            return;
+        };
+        let healthy = self
+            .http
+            .get(url)
+            .timeout(Duration::from_secs(2))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .is_ok();
+        if healthy {
+            ep.active.store(0, Ordering::Relaxed);
+            ep.record_success();
+        }
+    }
+
+    /// Sends a request built against the active endpoint, failing over to the next
+    /// endpoint once the failure threshold is reached. The request is retried there
+    /// when it is idempotent or certainly was not processed (connection refused,
+    /// `503`); a `POST` that timed out is not sent twice.
+    async fn send<F>(&self, path: &str, build: F) -> Result<reqwest::Response, Error>
+    where
+        F: Fn(Url) -> reqwest::RequestBuilder,
+    {
+        self.maybe_recover_primary().await;
+
+        let ep = &self.endpoints;
+        let mut attempts = 0;
+        loop {
+            let idx = ep.active();
+            let url = ep.urls[idx].join(path)?;
+            let request = build(url).build()?;
+            let idempotent = request.method().is_idempotent();
+            let result = self
+                .http
+                .execute(request)
+                .await
+                .and_then(|r| r.error_for_status());
+            match result {
+                Ok(res) => {
+                    ep.record_success();
+                    return Ok(res);
+                }
+                Err(err) if is_endpoint_failure(&err) => {
+                    attempts += 1;
+                    let retry = idempotent || !may_have_been_processed(&err);
+                    if ep.record_failure(idx) && retry && attempts < ep.urls.len() {
+                        continue;
+                    }
+                    return Err(err.into());
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
     }
 
     pub async fn healthz(&self) -> Result<(), Error> {
-        let url = self.base_url.join("healthz")?;
-        self.http.get(url).send().await?.error_for_status()?;
+        self.send("healthz", |url| self.http.get(url)).await?;
         Ok(())
     }
 
@@ -58,172 +304,654 @@ impl Client {
         &self,
         req: &BatchIngestRequest,
     ) -> Result<ApiResponse<JsonValue>, Error> {
-        let url = self.base_url.join("v1/l/batch")?;
+        let truncated = self
+            .max_io_bytes
+            .filter(|max| req.exceeds_io_bytes(*max))
+            .map(|max| {
+                let mut owned = req.clone();
+                owned.truncate_io(max);
+                owned
+            });
+        let body = serde_json::to_vec(truncated.as_ref().unwrap_or(req))?;
+        if let Some(max) = self.max_request_bytes.filter(|max| body.len() > *max) {
+            return Err(Error::RequestTooLarge {
+                bytes: body.len(),
+                max,
+            });
+        }
         let res = self
-            .http
-            .post(url)
-            .json(req)
-            .send()
-            .await?
-            .error_for_status()?;
+            .send("v1/l/batch", |url| self.http.post(url).body(body.clone()))
+            .await?;
         Ok(res.json::<ApiResponse<JsonValue>>().await?)
     }
 
     pub async fn list_traces(&self, q: &TraceListQuery) -> Result<PagedData<TraceListItem>, Error> {
-        let mut url = self.base_url.join("api/public/traces")?;
-        {
-            let mut pairs = url.query_pairs_mut();
-            if let Some(v) = q.page {
-                pairs.append_pair("page", &v.to_string());
-            }
-            if let Some(v) = q.limit {
-                pairs.append_pair("limit", &v.to_string());
-            }
-            if let Some(v) = q.user_id.as_deref() {
-                pairs.append_pair("userId", v);
-            }
-            if let Some(v) = q.name.as_deref() {
-                pairs.append_pair("name", v);
-            }
-            if let Some(v) = q.session_id.as_deref() {
-                pairs.append_pair("sessionId", v);
-            }
-            if let Some(v) = q.from_timestamp.as_ref() {
-                pairs.append_pair("fromTimestamp", &v.to_rfc3339());
-            }
-            if let Some(v) = q.to_timestamp.as_ref() {
-                pairs.append_pair("toTimestamp", &v.to_rfc3339());
-            }
-            if let Some(v) = q.order_by.as_deref() {
-                pairs.append_pair("orderBy", v);
-            }
-            for tag in &q.tags {
-                pairs.append_pair("tags", tag);
-            }
-            if let Some(v) = q.version.as_deref() {
-                pairs.append_pair("version", v);
-            }
-            if let Some(v) = q.release.as_deref() {
-                pairs.append_pair("release", v);
-            }
-            for env in &q.environment {
-                pairs.append_pair("environment", env);
-            }
-            if let Some(v) = q.fields.as_deref() {
-                pairs.append_pair("fields", v);
-            }
-        }
-
-        let res = self.http.get(url).send().await?.error_for_status()?;
+        let res = self
+            .send("api/public/traces", |mut url| {
+                append_trace_list_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
         Ok(res.json::<PagedData<TraceListItem>>().await?)
     }
 
+    /// Number of traces matching `q` (paging fields are ignored), optionally grouped.
+    pub async fn count_traces(
+        &self,
+        q: &TraceListQuery,
+        opts: &CountOptions,
+    ) -> Result<CountResult, Error> {
+        let res = self
+            .send("api/public/traces/count", |mut url| {
+                append_trace_list_query(&mut url, q);
+                append_count_options(&mut url, opts);
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<CountResult>().await?)
+    }
+
     pub async fn get_trace(&self, trace_id: Uuid) -> Result<TraceDetailDto, Error> {
-        let url = self
-            .base_url
-            .join(&format!("api/public/traces/{}", trace_id))?;
-        let res = self.http.get(url).send().await?.error_for_status()?;
+        let path = format!("api/public/traces/{}", trace_id);
+        let res = self.send(&path, |url| self.http.get(url)).await?;
         Ok(res.json::<TraceDetailDto>().await?)
     }
 
+    /// Like [`Client::get_trace`], but only embeds one page of observations.
+    pub async fn get_trace_page(
+        &self,
+        trace_id: Uuid,
+        observations_page: i64,
+        observations_limit: i64,
+    ) -> Result<TraceDetailDto, Error> {
+        let path = format!("api/public/traces/{}", trace_id);
+        let res = self
+            .send(&path, |mut url| {
+                url.query_pairs_mut()
+                    .append_pair("observationsPage", &observations_page.to_string())
+                    .append_pair("observationsLimit", &observations_limit.to_string());
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<TraceDetailDto>().await?)
+    }
+
+    /// Fetch up to 100 traces (the server's default limit, with all
+    /// observations) in one request.
+    /// Ids that were not found are listed in [`TracesById::missing`].
+    pub async fn get_traces(&self, trace_ids: &[Uuid]) -> Result<TracesById, Error> {
+        let body = serde_json::json!({ "traceIds": trace_ids });
+        let res = self
+            .send("api/public/traces/get-many", |url| {
+                self.http.post(url).json(&body)
+            })
+            .await?;
+        Ok(res.json::<TracesById>().await?)
+    }
+
+    /// Mark a trace as finalized. Queued behind batches already sent, so it is
+    /// safe to call right after the last `ingest_batch` for the trace.
+    pub async fn complete_trace(&self, trace_id: Uuid) -> Result<ApiResponse<JsonValue>, Error> {
+        let path = format!("api/public/traces/{}/complete", trace_id);
+        let res = self.send(&path, |url| self.http.post(url)).await?;
+        Ok(res.json::<ApiResponse<JsonValue>>().await?)
+    }
+
+    /// Share a trace (or stop sharing it). Public traces are readable without
+    /// credentials at the returned `sharePath`.
+    pub async fn set_trace_public(&self, trace_id: Uuid, public: bool) -> Result<JsonValue, Error> {
+        self.update_trace(trace_id, &serde_json::json!({ "public": public }))
+            .await
+    }
+
+    /// Partially update a stored trace: `tags` (`add` / `remove`),
+    /// `bookmarked`, `public` and `metadata` (merged by top-level key).
+    pub async fn update_trace(
+        &self,
+        trace_id: Uuid,
+        update: &JsonValue,
+    ) -> Result<JsonValue, Error> {
+        let path = format!("api/public/traces/{}", trace_id);
+        let res = self
+            .send(&path, |url| self.http.patch(url).json(update))
+            .await?;
+        Ok(res.json::<JsonValue>().await?)
+    }
+
+    /// Long-poll until the trace is completed or has received no writes for
+    /// `quiet_period`, or `timeout` (server max 120s) elapses. Errors with 404 if
+    /// the trace never appears.
+    pub async fn wait_for_trace(
+        &self,
+        trace_id: Uuid,
+        timeout: Duration,
+        quiet_period: Duration,
+    ) -> Result<TraceWaitResult, Error> {
+        let path = format!("api/public/traces/{}/wait", trace_id);
+        let res = self
+            .send(&path, |mut url| {
+                url.query_pairs_mut()
+                    .append_pair("timeout", &format!("{}ms", timeout.as_millis()))
+                    .append_pair("quietPeriod", &format!("{}ms", quiet_period.as_millis()));
+                // Outlive the server-side hold instead of the client-wide default.
+                self.http
+                    .get(url)
+                    .timeout(timeout + Duration::from_secs(10))
+            })
+            .await?;
+        Ok(res.json::<TraceWaitResult>().await?)
+    }
+
+    /// Fetch one page of a trace's observations (`page` from 1, `limit` up to 1000).
+    pub async fn list_trace_observations(
+        &self,
+        trace_id: Uuid,
+        page: i64,
+        limit: i64,
+    ) -> Result<PagedData<JsonValue>, Error> {
+        let path = format!("api/public/traces/{}/observations", trace_id);
+        let res = self
+            .send(&path, |mut url| {
+                url.query_pairs_mut()
+                    .append_pair("page", &page.to_string())
+                    .append_pair("limit", &limit.to_string());
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<PagedData<JsonValue>>().await?)
+    }
+
+    /// Fetch a trace's observations nested by parent, with per-node duration
+    /// and cost.
+    pub async fn get_trace_tree(&self, trace_id: Uuid) -> Result<TraceTree, Error> {
+        let path = format!("api/public/traces/{}/tree", trace_id);
+        let res = self.send(&path, |url| self.http.get(url)).await?;
+        Ok(res.json::<TraceTree>().await?)
+    }
+
+    /// List sessions ordered by last activity.
+    pub async fn list_sessions(
+        &self,
+        q: &SessionListQuery,
+    ) -> Result<PagedData<SessionItem>, Error> {
+        let res = self
+            .send("api/public/sessions", |mut url| {
+                append_session_list_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<PagedData<SessionItem>>().await?)
+    }
+
+    /// Get a single session's aggregates and trace ids.
+    pub async fn get_session(&self, session_id: &str) -> Result<SessionDetail, Error> {
+        let res = self
+            .send("api/public/sessions/", |mut url| {
+                if let Ok(mut segments) = url.path_segments_mut() {
+                    segments.pop_if_empty().push(session_id);
+                }
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<SessionDetail>().await?)
+    }
+
+    /// Create (or replace, when `id` is set) a score on a trace or observation.
+    pub async fn create_score(&self, score: &ScoreCreate) -> Result<ScoreCreated, Error> {
+        let res = self
+            .send("api/public/scores", |url| self.http.post(url).json(score))
+            .await?;
+        Ok(res.json::<ScoreCreated>().await?)
+    }
+
+    /// List scores, newest first.
+    pub async fn list_scores(&self, q: &ScoreListQuery) -> Result<PagedData<Score>, Error> {
+        let res = self
+            .send("api/public/scores", |mut url| {
+                append_score_list_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<PagedData<Score>>().await?)
+    }
+
+    pub async fn get_score(&self, score_id: Uuid) -> Result<Score, Error> {
+        let path = format!("api/public/scores/{}", score_id);
+        let res = self.send(&path, |url| self.http.get(url)).await?;
+        Ok(res.json::<Score>().await?)
+    }
+
+    /// List observations across traces, newest first.
+    pub async fn list_observations(
+        &self,
+        q: &ObservationListQuery,
+    ) -> Result<PagedData<JsonValue>, Error> {
+        let res = self
+            .send("api/public/observations", |mut url| {
+                append_observation_list_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<PagedData<JsonValue>>().await?)
+    }
+
+    /// Number of observations matching `q` (paging fields are ignored), optionally grouped.
+    pub async fn count_observations(
+        &self,
+        q: &ObservationListQuery,
+        opts: &CountOptions,
+    ) -> Result<CountResult, Error> {
+        let res = self
+            .send("api/public/observations/count", |mut url| {
+                append_observation_list_query(&mut url, q);
+                append_count_options(&mut url, opts);
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<CountResult>().await?)
+    }
+
+    pub async fn get_observation(&self, observation_id: Uuid) -> Result<JsonValue, Error> {
+        let path = format!("api/public/observations/{}", observation_id);
+        let res = self.send(&path, |url| self.http.get(url)).await?;
+        Ok(res.json::<JsonValue>().await?)
+    }
+
     pub async fn metrics_daily(
         &self,
         q: &MetricsDailyQuery,
     ) -> Result<PagedData<MetricsDailyItem>, Error> {
-        let mut url = self.base_url.join("api/public/metrics/daily")?;
-        {
-            let mut pairs = url.query_pairs_mut();
-            if let Some(v) = q.page {
-                pairs.append_pair("page", &v.to_string());
-            }
-            if let Some(v) = q.limit {
-                pairs.append_pair("limit", &v.to_string());
-            }
-            if let Some(v) = q.trace_name.as_deref() {
-                pairs.append_pair("traceName", v);
-            }
-            if let Some(v) = q.user_id.as_deref() {
-                pairs.append_pair("userId", v);
-            }
-            for tag in &q.tags {
-                pairs.append_pair("tags", tag);
-            }
-            if let Some(v) = q.from_timestamp.as_ref() {
-                pairs.append_pair("fromTimestamp", &v.to_rfc3339());
-            }
-            if let Some(v) = q.to_timestamp.as_ref() {
-                pairs.append_pair("toTimestamp", &v.to_rfc3339());
-            }
-            if let Some(v) = q.version.as_deref() {
-                pairs.append_pair("version", v);
-            }
-            if let Some(v) = q.release.as_deref() {
-                pairs.append_pair("release", v);
-            }
-        }
-
-        let res = self.http.get(url).send().await?.error_for_status()?;
+        let res = self
+            .send("api/public/metrics/daily", |mut url| {
+                append_metrics_daily_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
         Ok(res.json::<PagedData<MetricsDailyItem>>().await?)
     }
 
+    /// Traces, tokens and cost per `userId`, highest cost first.
+    pub async fn user_usage(&self, q: &UsageQuery) -> Result<PagedData<UserUsage>, Error> {
+        let res = self
+            .send("api/public/metrics/users", |mut url| {
+                append_usage_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<PagedData<UserUsage>>().await?)
+    }
+
+    /// Requests, tokens, latency, errors and cost per model, highest cost first.
+    pub async fn model_usage(&self, q: &UsageQuery) -> Result<PagedData<ModelUsage>, Error> {
+        let res = self
+            .send("api/public/metrics/models", |mut url| {
+                append_usage_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<PagedData<ModelUsage>>().await?)
+    }
+
     pub async fn push_metrics(
         &self,
         metrics: &[MetricPoint],
     ) -> Result<ApiResponse<JsonValue>, Error> {
-        let url = self.base_url.join("v1/metrics/batch")?;
         let req = MetricsBatchRequest {
             metrics: metrics.to_vec(),
         };
         let res = self
-            .http
-            .post(url)
-            .json(&req)
-            .send()
-            .await?
-            .error_for_status()?;
+            .send("v1/metrics/batch", |url| self.http.post(url).json(&req))
+            .await?;
         Ok(res.json::<ApiResponse<JsonValue>>().await?)
     }
 
+    /// Post an OTLP/JSON `ExportTraceServiceRequest` to `/api/public/otel/v1/traces`.
+    pub async fn export_otlp_traces(&self, request: &JsonValue) -> Result<(), Error> {
+        self.send("api/public/otel/v1/traces", |url| {
+            self.http.post(url).json(request)
+        })
+        .await?;
+        Ok(())
+    }
+
     /// Query time-series metrics with optional downsampling and aggregation.
     pub async fn query_metrics(
         &self,
         q: &MetricsQueryParams,
     ) -> Result<MetricsQueryResponse, Error> {
-        let mut url = self.base_url.join("api/public/metrics/query")?;
-        {
-            let mut pairs = url.query_pairs_mut();
-            pairs.append_pair("name", &q.name);
-            if let Some(v) = q.from.as_ref() {
-                pairs.append_pair("from", &v.to_rfc3339());
-            }
-            if let Some(v) = q.to.as_ref() {
-                pairs.append_pair("to", &v.to_rfc3339());
-            }
-            if let Some(v) = q.labels.as_ref() {
-                pairs.append_pair("labels", &serde_json::to_string(v).unwrap_or_default());
-            }
-            if let Some(v) = q.step.as_deref() {
-                pairs.append_pair("step", v);
-            }
-            if let Some(v) = q.agg.as_deref() {
-                pairs.append_pair("agg", v);
-            }
-            if let Some(v) = q.group_by.as_deref() {
-                pairs.append_pair("group_by", v);
-            }
-        }
-
-        let res = self.http.get(url).send().await?.error_for_status()?;
+        let res = self
+            .send("api/public/metrics/query", |mut url| {
+                append_metrics_query(&mut url, q);
+                self.http.get(url)
+            })
+            .await?;
         Ok(res.json::<MetricsQueryResponse>().await?)
     }
 
+    /// Run an analytics query (`view`, `dimensions`, `metrics`, `filters`,
+    /// `timeDimension`, `fromTimestamp`, `toTimestamp`, ...) over traces,
+    /// observations or scores. Returns the `data` rows.
+    pub async fn query_analytics(&self, query: &JsonValue) -> Result<Vec<JsonValue>, Error> {
+        #[derive(Deserialize)]
+        struct AnalyticsResponse {
+            data: Vec<JsonValue>,
+        }
+        let res = self
+            .send("api/public/metrics", |mut url| {
+                url.query_pairs_mut()
+                    .append_pair("query", &query.to_string());
+                self.http.get(url)
+            })
+            .await?;
+        Ok(res.json::<AnalyticsResponse>().await?.data)
+    }
+
     /// List all available metric names.
     pub async fn list_metric_names(&self) -> Result<Vec<String>, Error> {
-        let url = self.base_url.join("api/public/metrics/names")?;
-        let res = self.http.get(url).send().await?.error_for_status()?;
+        let res = self
+            .send("api/public/metrics/names", |url| self.http.get(url))
+            .await?;
         let wrapper = res.json::<MetricNamesResponse>().await?;
         Ok(wrapper.data)
     }
+
+    /// Start exporting traces with their observations. `request` has the
+    /// `format` (`jsonl`, `csv`, `parquet` or `openai_chat`), and optionally
+    /// the `filter` (the `GET /traces` filters as JSON fields) and `minScore`
+    /// (`{"name", "value"}`). Returns the running export job.
+    pub async fn start_export(&self, request: &JsonValue) -> Result<JsonValue, Error> {
+        let res = self
+            .send("api/public/exports", |url| {
+                self.http.post(url).json(request)
+            })
+            .await?;
+        Ok(res.json::<JsonValue>().await?)
+    }
+
+    /// Poll an export; `downloadPath` is set once it succeeded.
+    pub async fn get_export(&self, export_id: Uuid) -> Result<JsonValue, Error> {
+        let path = format!("api/public/exports/{}", export_id);
+        let res = self.send(&path, |url| self.http.get(url)).await?;
+        Ok(res.json::<JsonValue>().await?)
+    }
+
+    /// Fetch the file of a succeeded export.
+    pub async fn download_export(&self, export_id: Uuid) -> Result<Vec<u8>, Error> {
+        let path = format!("api/public/exports/{}/download", export_id);
+        let res = self.send(&path, |url| self.http.get(url)).await?;
+        Ok(res.bytes().await?.to_vec())
+    }
+}
+
+fn append_trace_list_query(url: &mut Url, q: &TraceListQuery) {
+    let mut pairs = url.query_pairs_mut();
+    if let Some(v) = q.page {
+        pairs.append_pair("page", &v.to_string());
+    }
+    if let Some(v) = q.limit {
+        pairs.append_pair("limit", &v.to_string());
+    }
+    if let Some(v) = q.user_id.as_deref() {
+        pairs.append_pair("userId", v);
+    }
+    if let Some(v) = q.name.as_deref() {
+        pairs.append_pair("name", v);
+    }
+    if let Some(v) = q.session_id.as_deref() {
+        pairs.append_pair("sessionId", v);
+    }
+    if let Some(v) = q.from_timestamp.as_ref() {
+        pairs.append_pair("fromTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.to_timestamp.as_ref() {
+        pairs.append_pair("toTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.order_by.as_deref() {
+        pairs.append_pair("orderBy", v);
+    }
+    for tag in &q.tags {
+        pairs.append_pair("tags", tag);
+    }
+    if let Some(v) = q.version.as_deref() {
+        pairs.append_pair("version", v);
+    }
+    if let Some(v) = q.release.as_deref() {
+        pairs.append_pair("release", v);
+    }
+    for env in &q.environment {
+        pairs.append_pair("environment", env);
+    }
+    if let Some(v) = q.service_name.as_deref() {
+        pairs.append_pair("serviceName", v);
+    }
+    if let Some(v) = q.service_version.as_deref() {
+        pairs.append_pair("serviceVersion", v);
+    }
+    if let Some(v) = q.sdk_language.as_deref() {
+        pairs.append_pair("sdkLanguage", v);
+    }
+    if let Some(v) = q.host_name.as_deref() {
+        pairs.append_pair("hostName", v);
+    }
+    if let Some(v) = q.provider.as_deref() {
+        pairs.append_pair("provider", v);
+    }
+    if let Some(v) = q.q.as_deref() {
+        pairs.append_pair("q", v);
+    }
+    if let Some(v) = q.min_level.as_deref() {
+        pairs.append_pair("minLevel", v);
+    }
+    if let Some(v) = q.filter.as_ref() {
+        pairs.append_pair("filter", &v.to_string());
+    }
+    if let Some(v) = q.fields {
+        pairs.append_pair("fields", &v.to_string());
+    }
+}
+
+fn append_session_list_query(url: &mut Url, q: &SessionListQuery) {
+    let mut pairs = url.query_pairs_mut();
+    if let Some(v) = q.page {
+        pairs.append_pair("page", &v.to_string());
+    }
+    if let Some(v) = q.limit {
+        pairs.append_pair("limit", &v.to_string());
+    }
+    if let Some(v) = q.from_timestamp.as_ref() {
+        pairs.append_pair("fromTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.to_timestamp.as_ref() {
+        pairs.append_pair("toTimestamp", &v.to_rfc3339());
+    }
+}
+
+fn append_score_list_query(url: &mut Url, q: &ScoreListQuery) {
+    let mut pairs = url.query_pairs_mut();
+    if let Some(v) = q.page {
+        pairs.append_pair("page", &v.to_string());
+    }
+    if let Some(v) = q.limit {
+        pairs.append_pair("limit", &v.to_string());
+    }
+    if let Some(v) = q.trace_id {
+        pairs.append_pair("traceId", &v.to_string());
+    }
+    if let Some(v) = q.observation_id {
+        pairs.append_pair("observationId", &v.to_string());
+    }
+    if let Some(v) = q.name.as_deref() {
+        pairs.append_pair("name", v);
+    }
+    if let Some(v) = q.source.as_deref() {
+        pairs.append_pair("source", v);
+    }
+    if let Some(v) = q.data_type.as_deref() {
+        pairs.append_pair("dataType", v);
+    }
+    if let Some(v) = q.user_id.as_deref() {
+        pairs.append_pair("userId", v);
+    }
+    if let Some(v) = q.config_id.as_deref() {
+        pairs.append_pair("configId", v);
+    }
+    if let Some(v) = q.queue_id.as_deref() {
+        pairs.append_pair("queueId", v);
+    }
+    if let Some(v) = q.environment.as_deref() {
+        pairs.append_pair("environment", v);
+    }
+    if let Some(v) = q.from_timestamp.as_ref() {
+        pairs.append_pair("fromTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.to_timestamp.as_ref() {
+        pairs.append_pair("toTimestamp", &v.to_rfc3339());
+    }
+}
+
+fn append_observation_list_query(url: &mut Url, q: &ObservationListQuery) {
+    let mut pairs = url.query_pairs_mut();
+    if let Some(v) = q.page {
+        pairs.append_pair("page", &v.to_string());
+    }
+    if let Some(v) = q.limit {
+        pairs.append_pair("limit", &v.to_string());
+    }
+    if let Some(v) = q.trace_id {
+        pairs.append_pair("traceId", &v.to_string());
+    }
+    if let Some(v) = q.parent_observation_id {
+        pairs.append_pair("parentObservationId", &v.to_string());
+    }
+    if let Some(v) = q.name.as_deref() {
+        pairs.append_pair("name", v);
+    }
+    if let Some(v) = q.r#type.as_deref() {
+        pairs.append_pair("type", v);
+    }
+    if let Some(v) = q.model.as_deref() {
+        pairs.append_pair("model", v);
+    }
+    if let Some(v) = q.level.as_deref() {
+        pairs.append_pair("level", v);
+    }
+    if let Some(v) = q.environment.as_deref() {
+        pairs.append_pair("environment", v);
+    }
+    if let Some(v) = q.from_start_time.as_ref() {
+        pairs.append_pair("fromStartTime", &v.to_rfc3339());
+    }
+    if let Some(v) = q.to_start_time.as_ref() {
+        pairs.append_pair("toStartTime", &v.to_rfc3339());
+    }
+    if let Some(v) = q.q.as_deref() {
+        pairs.append_pair("q", v);
+    }
+}
+
+/// Replaces the list query's paging with the count options.
+fn append_count_options(url: &mut Url, opts: &CountOptions) {
+    let filters: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != "page" && k != "limit")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    let mut pairs = url.query_pairs_mut();
+    pairs.clear().extend_pairs(filters);
+    if let Some(v) = &opts.group_by {
+        pairs.append_pair("groupBy", v);
+    }
+    if let Some(v) = opts.approximate {
+        pairs.append_pair("approximate", if v { "true" } else { "false" });
+    }
+    if let Some(v) = opts.limit {
+        pairs.append_pair("limit", &v.to_string());
+    }
+}
+
+fn append_metrics_daily_query(url: &mut Url, q: &MetricsDailyQuery) {
+    let mut pairs = url.query_pairs_mut();
+    if let Some(v) = q.page {
+        pairs.append_pair("page", &v.to_string());
+    }
+    if let Some(v) = q.limit {
+        pairs.append_pair("limit", &v.to_string());
+    }
+    if let Some(v) = q.trace_name.as_deref() {
+        pairs.append_pair("traceName", v);
+    }
+    if let Some(v) = q.user_id.as_deref() {
+        pairs.append_pair("userId", v);
+    }
+    for tag in &q.tags {
+        pairs.append_pair("tags", tag);
+    }
+    if let Some(v) = q.from_timestamp.as_ref() {
+        pairs.append_pair("fromTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.to_timestamp.as_ref() {
+        pairs.append_pair("toTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.version.as_deref() {
+        pairs.append_pair("version", v);
+    }
+    if let Some(v) = q.release.as_deref() {
+        pairs.append_pair("release", v);
+    }
+    if let Some(v) = q.timezone.as_deref() {
+        pairs.append_pair("timezone", v);
+    }
+    if let Some(v) = q.group_by.as_deref() {
+        pairs.append_pair("groupBy", v);
+    }
+}
+
+fn append_usage_query(url: &mut Url, q: &UsageQuery) {
+    let mut pairs = url.query_pairs_mut();
+    if let Some(v) = q.page {
+        pairs.append_pair("page", &v.to_string());
+    }
+    if let Some(v) = q.limit {
+        pairs.append_pair("limit", &v.to_string());
+    }
+    if let Some(v) = q.from_timestamp.as_ref() {
+        pairs.append_pair("fromTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.to_timestamp.as_ref() {
+        pairs.append_pair("toTimestamp", &v.to_rfc3339());
+    }
+    if let Some(v) = q.trace_name.as_deref() {
+        pairs.append_pair("traceName", v);
+    }
+    for tag in &q.tags {
+        pairs.append_pair("tags", tag);
+    }
+    if let Some(v) = q.environment.as_deref() {
+        pairs.append_pair("environment", v);
+    }
+}
+
+fn append_metrics_query(url: &mut Url, q: &MetricsQueryParams) {
+    let mut pairs = url.query_pairs_mut();
+    pairs.append_pair("name", &q.name);
+    if let Some(v) = q.from.as_ref() {
+        pairs.append_pair("from", &v.to_rfc3339());
+    }
+    if let Some(v) = q.to.as_ref() {
+        pairs.append_pair("to", &v.to_rfc3339());
+    }
+    if let Some(v) = q.labels.as_ref() {
+        pairs.append_pair("labels", &serde_json::to_string(v).unwrap_or_default());
+    }
+    if let Some(v) = q.step.as_deref() {
+        pairs.append_pair("step", v);
+    }
+    if let Some(v) = q.agg.as_deref() {
+        pairs.append_pair("agg", v);
+    }
+    if let Some(v) = q.group_by.as_deref() {
+        pairs.append_pair("group_by", v);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -330,15 +1058,124 @@ pub struct PagedData<T> {
     pub meta: PageMeta,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct BatchIngestRequest {
     #[serde(default)]
     pub trace: Option<TraceIngest>,
     #[serde(default)]
     pub observations: Vec<ObservationIngest>,
+    /// Idempotency key: the server applies a batch once per key, so a batch
+    /// retried after a timeout does not overwrite later updates.
+    #[serde(default, rename = "eventId", skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+}
+
+/// Metadata key listing which I/O fields were truncated client-side.
+pub const TRUNCATION_METADATA_KEY: &str = "xtrace_truncated";
+const TRUNCATION_SUFFIX: &str = "...[truncated]";
+
+/// Size of `c` inside a JSON string, as serde_json escapes it.
+fn escaped_len(c: char) -> usize {
+    match c {
+        '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+        c if c < ' ' => 6,
+        c => c.len_utf8(),
+    }
+}
+
+/// Size of `v` encoded as JSON.
+fn json_len(v: &JsonValue) -> usize {
+    match v {
+        JsonValue::String(s) => 2 + s.chars().map(escaped_len).sum::<usize>(),
+        other => serde_json::to_vec(other).map(|b| b.len()).unwrap_or(0),
+    }
+}
+
+/// Replaces `field` with a string whose JSON encoding is at most `max_bytes`
+/// bytes (when that fits the suffix); returns the original size.
+fn truncate_field(field: &mut Option<JsonValue>, max_bytes: usize) -> Option<usize> {
+    let v = field.as_ref()?;
+    let original = json_len(v);
+    if original <= max_bytes {
+        return None;
+    }
+    let text = match v {
+        JsonValue::String(s) => s.clone(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    };
+    let budget = max_bytes.saturating_sub(2 + TRUNCATION_SUFFIX.len());
+    let mut used = 0;
+    let mut cut = 0;
+    for (i, c) in text.char_indices() {
+        used += escaped_len(c);
+        if used > budget {
+            break;
+        }
+        cut = i + c.len_utf8();
+    }
+    *field = Some(JsonValue::String(format!(
+        "{}{}",
+        &text[..cut],
+        TRUNCATION_SUFFIX
+    )));
+    Some(original)
+}
+
+/// Key under which metadata that is not an object is kept when fields are added
+/// to it (by `with_metadata_field` or truncation).
+pub const METADATA_VALUE_KEY: &str = "value";
+
+/// `metadata` as an object to add fields to.
+fn metadata_object(metadata: Option<JsonValue>) -> serde_json::Map<String, JsonValue> {
+    match metadata {
+        Some(JsonValue::Object(map)) => map,
+        None | Some(JsonValue::Null) => serde_json::Map::new(),
+        Some(other) => serde_json::Map::from_iter([(METADATA_VALUE_KEY.to_string(), other)]),
+    }
+}
+
+fn mark_truncated(metadata: &mut Option<JsonValue>, field: &str, original_bytes: usize) {
+    let mut meta = metadata_object(metadata.take());
+    // A value already under the key is kept inside the marker.
+    let mut marker = metadata_object(meta.remove(TRUNCATION_METADATA_KEY));
+    marker.insert(
+        field.to_string(),
+        serde_json::json!({ "originalBytes": original_bytes }),
+    );
+    meta.insert(
+        TRUNCATION_METADATA_KEY.to_string(),
+        JsonValue::Object(marker),
+    );
+    *metadata = Some(JsonValue::Object(meta));
+}
+
+impl BatchIngestRequest {
+    /// Whether any trace/observation `input` or `output` is larger than `max_bytes`.
+    pub fn exceeds_io_bytes(&self, max_bytes: usize) -> bool {
+        let over = |f: &Option<JsonValue>| f.as_ref().is_some_and(|v| json_len(v) > max_bytes);
+        self.trace
+            .as_ref()
+            .is_some_and(|t| over(&t.input) || over(&t.output))
+            || self
+                .observations
+                .iter()
+                .any(|o| over(&o.input) || over(&o.output))
+    }
+
+    /// Truncates oversized I/O fields in place; returns the number of fields truncated.
+    pub fn truncate_io(&mut self, max_bytes: usize) -> usize {
+        let mut n = 0;
+        if let Some(t) = self.trace.as_mut() {
+            n += t.truncate_io(max_bytes);
+        }
+        for o in &mut self.observations {
+            n += o.truncate_io(max_bytes);
+        }
+        n
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TraceIngest {
     pub id: Uuid,
@@ -377,8 +1214,21 @@ pub struct TraceIngest {
     #[serde(default, rename = "totalCost")]
     pub total_cost: Option<f64>,
 
+    #[serde(default)]
+    pub service_name: Option<String>,
+    #[serde(default)]
+    pub service_version: Option<String>,
+    #[serde(default)]
+    pub sdk_language: Option<String>,
+    #[serde(default)]
+    pub host_name: Option<String>,
+
     #[serde(default, rename = "projectId")]
     pub project_id: Option<String>,
+
+    /// Finalize the trace once this batch is written.
+    #[serde(default)]
+    pub complete: Option<bool>,
 }
 
 impl TraceIngest {
@@ -401,20 +1251,28 @@ impl TraceIngest {
             bookmarked: None,
             latency: None,
             total_cost: None,
+            service_name: None,
+            service_version: None,
+            sdk_language: None,
+            host_name: None,
             project_id: None,
+            complete: None,
         }
     }
 
+    /// Marks the trace as finished; see [`Client::complete_trace`].
+    pub fn with_complete(mut self, complete: bool) -> Self {
+        self.complete = Some(complete);
+        self
+    }
+
     pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
         self.session_id = Some(session_id.into());
         self
     }
 
     pub fn with_metadata_field(mut self, key: &str, value: impl Serialize) -> Self {
-        let mut meta = match self.metadata {
-            Some(JsonValue::Object(map)) => map,
-            _ => serde_json::Map::new(),
-        };
+        let mut meta = metadata_object(self.metadata);
         if let Ok(v) = serde_json::to_value(value) {
             meta.insert(key.to_string(), v);
         }
@@ -429,9 +1287,23 @@ impl TraceIngest {
     pub fn with_run_id(self, run_id: impl Into<String>) -> Self {
         self.with_metadata_field("run_id", run_id.into())
     }
+
+    /// Truncates `input`/`output` above `max_bytes`; returns the number of fields truncated.
+    pub fn truncate_io(&mut self, max_bytes: usize) -> usize {
+        let mut n = 0;
+        if let Some(orig) = truncate_field(&mut self.input, max_bytes) {
+            mark_truncated(&mut self.metadata, "input", orig);
+            n += 1;
+        }
+        if let Some(orig) = truncate_field(&mut self.output, max_bytes) {
+            mark_truncated(&mut self.metadata, "output", orig);
+            n += 1;
+        }
+        n
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ObservationIngest {
     pub id: Uuid,
@@ -514,8 +1386,35 @@ pub struct ObservationIngest {
     #[serde(default)]
     pub environment: Option<String>,
 
+    #[serde(default)]
+    pub service_name: Option<String>,
+    #[serde(default)]
+    pub service_version: Option<String>,
+    #[serde(default)]
+    pub sdk_language: Option<String>,
+    #[serde(default)]
+    pub host_name: Option<String>,
+
+    /// Provider name (`openai`, `anthropic`, ...); inferred by the server when omitted.
+    #[serde(default)]
+    pub provider: Option<String>,
+
     #[serde(default, rename = "projectId")]
     pub project_id: Option<String>,
+
+    /// Apply as a partial update of a stored observation: fields left `None`
+    /// keep their stored values.
+    #[serde(default)]
+    pub update: Option<bool>,
+    /// Text appended to the stored output, e.g. a streamed chunk; implies
+    /// `update`.
+    #[serde(default)]
+    pub output_delta: Option<String>,
+    /// Position of `output_delta` in the stream, required with it and
+    /// increasing from one delta to the next. The server skips deltas at or
+    /// below the last one applied, so resending a chunk is safe.
+    #[serde(default)]
+    pub output_seq: Option<i64>,
 }
 
 impl ObservationIngest {
@@ -554,15 +1453,28 @@ impl ObservationIngest {
             unit: None,
             metadata: None,
             environment: None,
+            service_name: None,
+            service_version: None,
+            sdk_language: None,
+            host_name: None,
+            provider: None,
             project_id: None,
+            update: None,
+            output_delta: None,
+            output_seq: None,
+        }
+    }
+
+    /// A partial update of observation `id`; set the fields to change.
+    pub fn partial(id: Uuid, trace_id: Uuid) -> Self {
+        Self {
+            update: Some(true),
+            ..Self::new(id, trace_id)
         }
     }
 
     pub fn with_metadata_field(mut self, key: &str, value: impl Serialize) -> Self {
-        let mut meta = match self.metadata {
-            Some(JsonValue::Object(map)) => map,
-            _ => serde_json::Map::new(),
-        };
+        let mut meta = metadata_object(self.metadata);
         if let Ok(v) = serde_json::to_value(value) {
             meta.insert(key.to_string(), v);
         }
@@ -581,6 +1493,20 @@ impl ObservationIngest {
     pub fn with_step_type(self, step_type: impl Into<String>) -> Self {
         self.with_metadata_field("step_type", step_type.into())
     }
+
+    /// Truncates `input`/`output` above `max_bytes`; returns the number of fields truncated.
+    pub fn truncate_io(&mut self, max_bytes: usize) -> usize {
+        let mut n = 0;
+        if let Some(orig) = truncate_field(&mut self.input, max_bytes) {
+            mark_truncated(&mut self.metadata, "input", orig);
+            n += 1;
+        }
+        if let Some(orig) = truncate_field(&mut self.output, max_bytes) {
+            mark_truncated(&mut self.metadata, "output", orig);
+            n += 1;
+        }
+        n
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -603,6 +1529,7 @@ pub struct TraceListQuery {
     #[serde(default, rename = "toTimestamp")]
     pub to_timestamp: Option<DateTime<Utc>>,
 
+    /// Raw `column.direction` string; prefer [`TraceListQuery::with_order_by`].
     #[serde(default, rename = "orderBy")]
     pub order_by: Option<String>,
 
@@ -617,7 +1544,330 @@ pub struct TraceListQuery {
     pub environment: Vec<String>,
 
     #[serde(default)]
-    pub fields: Option<String>,
+    pub service_name: Option<String>,
+    #[serde(default)]
+    pub service_version: Option<String>,
+    #[serde(default)]
+    pub sdk_language: Option<String>,
+    #[serde(default)]
+    pub host_name: Option<String>,
+    /// Traces with at least one observation from this provider.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Full-text search over the content of the trace and its observations.
+    #[serde(default)]
+    pub q: Option<String>,
+    /// Traces with an observation at or above this level (`WARNING`, `ERROR`, ...).
+    #[serde(default)]
+    pub min_level: Option<String>,
+    /// Langfuse-style filter conditions: a JSON array of
+    /// `{type, column, operator, value, key?}` objects.
+    #[serde(default)]
+    pub filter: Option<JsonValue>,
+
+    /// Response field mask; `None` lets the server apply [`TraceFields::default`].
+    #[serde(default)]
+    pub fields: Option<TraceFields>,
+}
+
+impl TraceListQuery {
+    /// Sets `order_by` from a typed [`OrderBy`].
+    pub fn with_order_by(mut self, order: OrderBy) -> Self {
+        self.order_by = Some(order.to_string());
+        self
+    }
+}
+
+/// Sortable trace list columns accepted by the server's `orderBy` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderColumn {
+    Id,
+    Timestamp,
+    Name,
+    UserId,
+    Release,
+    Version,
+    Public,
+    Bookmarked,
+    SessionId,
+    Latency,
+    TotalCost,
+    /// Sum of the observations' `totalTokens`.
+    TotalTokens,
+    ObservationCount,
+    /// Observations at level `ERROR`.
+    ErrorCount,
+}
+
+impl OrderColumn {
+    pub const ALL: [OrderColumn; 14] = [
+        OrderColumn::Id,
+        OrderColumn::Timestamp,
+        OrderColumn::Name,
+        OrderColumn::UserId,
+        OrderColumn::Release,
+        OrderColumn::Version,
+        OrderColumn::Public,
+        OrderColumn::Bookmarked,
+        OrderColumn::SessionId,
+        OrderColumn::Latency,
+        OrderColumn::TotalCost,
+        OrderColumn::TotalTokens,
+        OrderColumn::ObservationCount,
+        OrderColumn::ErrorCount,
+    ];
+
+    /// Name used on the wire.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderColumn::Id => "id",
+            OrderColumn::Timestamp => "timestamp",
+            OrderColumn::Name => "name",
+            OrderColumn::UserId => "userId",
+            OrderColumn::Release => "release",
+            OrderColumn::Version => "version",
+            OrderColumn::Public => "public",
+            OrderColumn::Bookmarked => "bookmarked",
+            OrderColumn::SessionId => "sessionId",
+            OrderColumn::Latency => "latency",
+            OrderColumn::TotalCost => "totalCost",
+            OrderColumn::TotalTokens => "totalTokens",
+            OrderColumn::ObservationCount => "observationCount",
+            OrderColumn::ErrorCount => "errorCount",
+        }
+    }
+
+    /// Direction the server applies when none is given.
+    pub fn default_direction(self) -> SortDirection {
+        match self {
+            OrderColumn::Name
+            | OrderColumn::UserId
+            | OrderColumn::Release
+            | OrderColumn::Version
+            | OrderColumn::SessionId => SortDirection::Asc,
+            _ => SortDirection::Desc,
+        }
+    }
+}
+
+impl std::str::FromStr for OrderColumn {
+    type Err = ParseOrderByError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user_id" => return Ok(OrderColumn::UserId),
+            "session_id" => return Ok(OrderColumn::SessionId),
+            "total_cost" => return Ok(OrderColumn::TotalCost),
+            "total_tokens" => return Ok(OrderColumn::TotalTokens),
+            "observation_count" => return Ok(OrderColumn::ObservationCount),
+            "error_count" => return Ok(OrderColumn::ErrorCount),
+            _ => {}
+        }
+        OrderColumn::ALL
+            .into_iter()
+            .find(|c| c.as_str() == s)
+            .ok_or_else(|| ParseOrderByError(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// Typed `orderBy` value, serialized as `column.direction` (e.g. `latency.desc`).
+///
+/// ```
+/// use xtrace_client::{OrderBy, OrderColumn};
+///
+/// let order: OrderBy = "totalCost.asc".parse().unwrap();
+/// assert_eq!(order, OrderBy::asc(OrderColumn::TotalCost));
+/// assert_eq!(OrderBy::desc(OrderColumn::Latency).to_string(), "latency.desc");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrderBy {
+    pub column: OrderColumn,
+    pub direction: SortDirection,
+}
+
+impl OrderBy {
+    pub fn asc(column: OrderColumn) -> Self {
+        Self {
+            column,
+            direction: SortDirection::Asc,
+        }
+    }
+
+    pub fn desc(column: OrderColumn) -> Self {
+        Self {
+            column,
+            direction: SortDirection::Desc,
+        }
+    }
+}
+
+impl std::fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dir = match self.direction {
+            SortDirection::Asc => "asc",
+            SortDirection::Desc => "desc",
+        };
+        write!(f, "{}.{}", self.column.as_str(), dir)
+    }
+}
+
+impl std::str::FromStr for OrderBy {
+    type Err = ParseOrderByError;
+
+    /// Accepts `column` or `column.asc|desc`; a bare column uses the server default direction.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (col, dir) = match s.split_once('.') {
+            Some((col, dir)) => (col, Some(dir)),
+            None => (s, None),
+        };
+        let column: OrderColumn = col.parse()?;
+        let direction = match dir {
+            None => column.default_direction(),
+            Some("asc") => SortDirection::Asc,
+            Some("desc") => SortDirection::Desc,
+            Some(_) => return Err(ParseOrderByError(s.to_string())),
+        };
+        Ok(Self { column, direction })
+    }
+}
+
+impl From<OrderBy> for String {
+    fn from(order: OrderBy) -> Self {
+        order.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid order_by: {0}")]
+pub struct ParseOrderByError(String);
+
+/// Field mask for `list_traces`, sent as the comma-separated `fields` parameter.
+///
+/// The server deserializes the same type, so both sides agree on the names.
+/// `Default` matches the server's behaviour when `fields` is omitted: everything
+/// except `observationSummary`. Unknown names are ignored when parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceFields {
+    pub io: bool,
+    pub scores: bool,
+    pub observations: bool,
+    pub metrics: bool,
+    pub observation_summary: bool,
+}
+
+impl TraceFields {
+    const NAMES: [&'static str; 5] = [
+        "io",
+        "scores",
+        "observations",
+        "metrics",
+        "observationSummary",
+    ];
+
+    /// Only the core trace columns.
+    pub const fn none() -> Self {
+        Self {
+            io: false,
+            scores: false,
+            observations: false,
+            metrics: false,
+            observation_summary: false,
+        }
+    }
+
+    pub const fn all() -> Self {
+        Self {
+            io: true,
+            scores: true,
+            observations: true,
+            metrics: true,
+            observation_summary: true,
+        }
+    }
+
+    fn flags(&self) -> [bool; 5] {
+        [
+            self.io,
+            self.scores,
+            self.observations,
+            self.metrics,
+            self.observation_summary,
+        ]
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "io" => Some(&mut self.io),
+            "scores" => Some(&mut self.scores),
+            "observations" => Some(&mut self.observations),
+            "metrics" => Some(&mut self.metrics),
+            "observationSummary" => Some(&mut self.observation_summary),
+            _ => None,
+        }
+    }
+}
+
+impl Default for TraceFields {
+    fn default() -> Self {
+        Self {
+            io: true,
+            scores: true,
+            observations: true,
+            metrics: true,
+            observation_summary: false,
+        }
+    }
+}
+
+impl std::fmt::Display for TraceFields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut first = true;
+        for (name, on) in Self::NAMES.iter().zip(self.flags()) {
+            if on {
+                if !first {
+                    f.write_str(",")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for TraceFields {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = Self::none();
+        for name in s.split(',').map(str::trim) {
+            if let Some(flag) = fields.flag_mut(name) {
+                *flag = true;
+            }
+        }
+        Ok(fields)
+    }
+}
+
+impl Serialize for TraceFields {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TraceFields {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(s.parse()
+            .unwrap_or_else(|e: std::convert::Infallible| match e {}))
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -649,11 +1899,43 @@ pub struct TraceListItem {
     pub latency: Option<f64>,
     pub total_cost: Option<f64>,
     #[serde(default)]
+    pub service_name: Option<String>,
+    #[serde(default)]
+    pub service_version: Option<String>,
+    #[serde(default)]
+    pub sdk_language: Option<String>,
+    #[serde(default)]
+    pub host_name: Option<String>,
+    #[serde(default)]
     pub created_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub updated_at: Option<DateTime<Utc>>,
     pub observations: Vec<String>,
     pub scores: Vec<String>,
+    #[serde(default)]
+    pub observation_count: i64,
+    /// Observations at level `ERROR`.
+    #[serde(default)]
+    pub error_count: i64,
+    #[serde(default)]
+    pub total_tokens: i64,
+    /// Seconds from the trace start to the first streamed token.
+    #[serde(default)]
+    pub time_to_first_token: Option<f64>,
+    /// Present only when `fields` includes `observationSummary`.
+    #[serde(default)]
+    pub observation_summary: Option<ObservationSummary>,
+}
+
+/// Per-trace rollup of child observations.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservationSummary {
+    /// Observation counts keyed by type (`GENERATION`, `SPAN`, ...).
+    pub counts_by_type: HashMap<String, i64>,
+    pub error_count: i64,
+    pub models: Vec<String>,
+    pub total_tokens: i64,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -680,18 +1962,185 @@ pub struct MetricsDailyQuery {
     pub version: Option<String>,
     #[serde(default)]
     pub release: Option<String>,
+
+    /// IANA time zone used for day bucketing, e.g. `Asia/Shanghai` (server default `UTC`).
+    #[serde(default)]
+    pub timezone: Option<String>,
+
+    /// `environment`, `release` or `version`: one row per day and value.
+    #[serde(default, rename = "groupBy")]
+    pub group_by: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MetricsDailyItem {
     pub date: String,
+    /// Value of the `groupBy` column (`None` without `groupBy`, or for traces
+    /// without a value).
+    #[serde(default)]
+    pub group: Option<String>,
     pub count_traces: i64,
     pub count_observations: i64,
     pub total_cost: f64,
     pub usage: JsonValue,
 }
 
+/// Parameters of [`Client::user_usage`] and [`Client::model_usage`]; the
+/// server defaults to the last 30 days.
+#[derive(Debug, Default, Clone)]
+pub struct UsageQuery {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub from_timestamp: Option<DateTime<Utc>>,
+    pub to_timestamp: Option<DateTime<Utc>>,
+    pub trace_name: Option<String>,
+    pub tags: Vec<String>,
+    pub environment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserUsage {
+    pub user_id: String,
+    pub count_traces: i64,
+    pub count_observations: i64,
+    pub input_usage: i64,
+    pub output_usage: i64,
+    pub total_usage: i64,
+    pub total_cost: f64,
+    pub first_trace: DateTime<Utc>,
+    pub last_trace: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelUsage {
+    pub model: String,
+    pub provider: Option<String>,
+    pub count_requests: i64,
+    pub count_traces: i64,
+    pub input_usage: i64,
+    pub output_usage: i64,
+    pub total_usage: i64,
+    /// Seconds.
+    pub avg_latency: Option<f64>,
+    pub count_errors: i64,
+    pub error_rate: f64,
+    pub total_cost: f64,
+}
+
+/// Options for [`Client::count_traces`] and [`Client::count_observations`].
+#[derive(Debug, Default, Clone)]
+pub struct CountOptions {
+    /// Dimension to group by, e.g. `name`, `environment` or `model`.
+    pub group_by: Option<String>,
+    /// `Some(true)` always samples, `Some(false)` always counts exactly; by
+    /// default the server samples only very large ranges.
+    pub approximate: Option<bool>,
+    /// Maximum number of groups (default 100).
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountResult {
+    pub total: i64,
+    /// Counts were estimated from a sample.
+    pub approximate: bool,
+    #[serde(default)]
+    pub group_by: Option<String>,
+    /// Largest groups first; empty without `group_by`.
+    #[serde(default)]
+    pub groups: Vec<CountGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountGroup {
+    /// Group value; `None` for rows without one.
+    pub value: Option<String>,
+    pub count: i64,
+}
+
+/// Response from `GET /api/public/traces/:traceId/tree`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceTree {
+    pub trace_id: Uuid,
+    pub observation_count: usize,
+    pub total_cost: f64,
+    /// Observations without a parent in the trace, by start time.
+    pub roots: Vec<TraceTreeNode>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceTreeNode {
+    /// The observation, as returned by the observation endpoints.
+    #[serde(flatten)]
+    pub observation: serde_json::Map<String, JsonValue>,
+    /// Seconds from start to end (or the reported latency).
+    pub duration: Option<f64>,
+    pub cost: f64,
+    /// Cost of the observation and all its descendants.
+    pub subtree_cost: f64,
+    pub children: Vec<TraceTreeNode>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceWaitResult {
+    pub trace_id: Uuid,
+    /// `false` when the wait timed out while the trace was still receiving writes.
+    pub completed: bool,
+    /// `completed`, `quiet` or `timeout`.
+    pub reason: String,
+    pub observation_count: i64,
+    pub last_activity_at: DateTime<Utc>,
+    pub waited_ms: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionListQuery {
+    #[serde(default)]
+    pub page: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    /// Lower bound on the session's last activity.
+    #[serde(default)]
+    pub from_timestamp: Option<DateTime<Utc>>,
+    /// Upper bound on the session's last activity.
+    #[serde(default)]
+    pub to_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionItem {
+    pub id: String,
+    pub trace_count: i64,
+    pub total_cost: f64,
+    pub first_trace_at: Option<DateTime<Utc>>,
+    pub last_activity_at: Option<DateTime<Utc>>,
+    pub duration_seconds: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionDetail {
+    #[serde(flatten)]
+    pub session: SessionItem,
+    pub traces: Vec<Uuid>,
+}
+
+/// Response of [`Client::get_traces`].
+#[derive(Debug, Deserialize)]
+pub struct TracesById {
+    /// Found traces, in request order.
+    pub data: Vec<TraceDetailDto>,
+    pub missing: Vec<Uuid>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TraceDetailDto {
@@ -718,9 +2167,503 @@ pub struct TraceDetailDto {
     pub latency: Option<f64>,
     pub total_cost: Option<f64>,
     #[serde(default)]
+    pub service_name: Option<String>,
+    #[serde(default)]
+    pub service_version: Option<String>,
+    #[serde(default)]
+    pub sdk_language: Option<String>,
+    #[serde(default)]
+    pub host_name: Option<String>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub late_writes: i32,
+    #[serde(default)]
     pub created_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub updated_at: Option<DateTime<Utc>>,
     pub observations: Vec<JsonValue>,
-    pub scores: Vec<JsonValue>,
+    /// Present when observations were paginated via [`Client::get_trace_page`].
+    #[serde(default)]
+    pub observations_meta: Option<PageMeta>,
+    #[serde(default)]
+    pub observation_summary: Option<ObservationSummary>,
+    /// Wall-clock seconds per observation type, overlapping time counted once.
+    #[serde(default)]
+    pub latency_breakdown: HashMap<String, f64>,
+    pub scores: Vec<Score>,
+}
+
+/// Body of [`Client::create_score`]. `dataType` is inferred from `value` when unset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreCreate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub trace_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observation_id: Option<Uuid>,
+    pub name: String,
+    pub value: JsonValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<String>,
+    /// `API` (default), `EVAL` or `ANNOTATION`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<JsonValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_user_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl ScoreCreate {
+    fn new(trace_id: Uuid, name: impl Into<String>, value: JsonValue, data_type: &str) -> Self {
+        Self {
+            id: None,
+            trace_id,
+            observation_id: None,
+            name: name.into(),
+            value,
+            data_type: Some(data_type.to_string()),
+            source: None,
+            comment: None,
+            metadata: None,
+            config_id: None,
+            queue_id: None,
+            author_user_id: None,
+            environment: None,
+            timestamp: None,
+        }
+    }
+
+    pub fn numeric(trace_id: Uuid, name: impl Into<String>, value: f64) -> Self {
+        Self::new(trace_id, name, serde_json::json!(value), "NUMERIC")
+    }
+
+    pub fn categorical(trace_id: Uuid, name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self::new(
+            trace_id,
+            name,
+            JsonValue::String(value.into()),
+            "CATEGORICAL",
+        )
+    }
+
+    pub fn boolean(trace_id: Uuid, name: impl Into<String>, value: bool) -> Self {
+        Self::new(trace_id, name, JsonValue::Bool(value), "BOOLEAN")
+    }
+
+    pub fn with_id(mut self, id: Uuid) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn with_observation_id(mut self, observation_id: Uuid) -> Self {
+        self.observation_id = Some(observation_id);
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: JsonValue) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScoreCreated {
+    pub id: Uuid,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservationListQuery {
+    #[serde(default)]
+    pub page: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub trace_id: Option<Uuid>,
+    #[serde(default)]
+    pub parent_observation_id: Option<Uuid>,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// `GENERATION`, `SPAN`, `EVENT`, ...
+    #[serde(default, rename = "type")]
+    pub r#type: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>,
+    #[serde(default)]
+    pub from_start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub to_start_time: Option<DateTime<Utc>>,
+    /// Full-text search over name, input, output and metadata.
+    #[serde(default)]
+    pub q: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreListQuery {
+    #[serde(default)]
+    pub page: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub trace_id: Option<Uuid>,
+    #[serde(default)]
+    pub observation_id: Option<Uuid>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub data_type: Option<String>,
+    /// User of the scored trace.
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub config_id: Option<String>,
+    #[serde(default)]
+    pub queue_id: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>,
+    #[serde(default)]
+    pub from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub to_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Score {
+    pub id: Uuid,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    pub trace_id: Uuid,
+    #[serde(default)]
+    pub observation_id: Option<Uuid>,
+    pub name: String,
+    pub source: String,
+    pub data_type: String,
+    #[serde(default)]
+    pub value: Option<f64>,
+    /// Category label for `CATEGORICAL` / `BOOLEAN` scores.
+    #[serde(default)]
+    pub string_value: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub author_user_id: Option<String>,
+    #[serde(default)]
+    pub metadata: JsonValue,
+    #[serde(default)]
+    pub config_id: Option<String>,
+    #[serde(default)]
+    pub queue_id: Option<String>,
+    pub environment: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU16;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// A local HTTP server answering every request with the current `status`,
+    /// or not at all while it is 0.
+    struct Server {
+        url: String,
+        status: Arc<AtomicU16>,
+        requests: Arc<AtomicUsize>,
+    }
+
+    async fn server(status: u16) -> Server {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let status = Arc::new(AtomicU16::new(status));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let (current, count) = (status.clone(), requests.clone());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (current, count) = (current.clone(), count.clone());
+                tokio::spawn(async move {
+                    read_request(&mut socket).await;
+                    count.fetch_add(1, Ordering::SeqCst);
+                    match current.load(Ordering::SeqCst) {
+                        0 => tokio::time::sleep(Duration::from_secs(60)).await,
+                        status => {
+                            let body = r#"{"message":"ok"}"#;
+                            let response = format!(
+                                "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\n\
+                                 content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                                body.len()
+                            );
+                            socket.write_all(response.as_bytes()).await.ok();
+                        }
+                    }
+                });
+            }
+        });
+        Server {
+            url,
+            status,
+            requests,
+        }
+    }
+
+    /// Reads the request head and its `content-length` body.
+    async fn read_request(socket: &mut tokio::net::TcpStream) {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            let Ok(n @ 1..) = socket.read(&mut chunk).await else {
+                return;
+            };
+            buf.extend_from_slice(&chunk[..n]);
+            let text = String::from_utf8_lossy(&buf);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|l| {
+                        l.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse().unwrap_or(0))
+                    })
+                    .unwrap_or(0);
+                if buf.len() >= end + 4 + length {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// A base URL nothing listens on.
+    fn closed_url() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/", listener.local_addr().unwrap())
+    }
+
+    fn requests(server: &Server) -> usize {
+        server.requests.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn endpoints_switch_after_the_threshold_in_order() {
+        let client = Client::builder("http://a/", "t")
+            .fallback_url("http://b/")
+            .fallback_url("http://c/")
+            .failover_threshold(2)
+            .build()
+            .unwrap();
+        let ep = &client.endpoints;
+        assert_eq!(client.active_base_url().as_str(), "http://a/");
+
+        assert!(!ep.record_failure(0));
+        ep.record_success();
+        assert!(!ep.record_failure(0));
+        assert!(ep.record_failure(0));
+        assert_eq!(client.active_base_url().as_str(), "http://b/");
+        assert!(!ep.record_failure(1));
+        assert!(ep.record_failure(1));
+        assert_eq!(client.active_base_url().as_str(), "http://c/");
+        assert!(!ep.record_failure(2));
+        assert!(ep.record_failure(2));
+        assert_eq!(client.active_base_url().as_str(), "http://a/");
+
+        // A failure seen on an endpoint the client already left does not move it.
+        assert!(!ep.record_failure(1));
+        assert!(!ep.record_failure(1));
+        assert_eq!(client.active_base_url().as_str(), "http://a/");
+
+        let single = Client::builder("http://a/", "t")
+            .failover_threshold(1)
+            .build()
+            .unwrap();
+        assert!(!single.endpoints.record_failure(0));
+        assert_eq!(single.active_base_url().as_str(), "http://a/");
+    }
+
+    #[test]
+    fn invalid_fallback_urls_fail_the_build() {
+        let result = Client::builder("http://a/", "t")
+            .fallback_url("not a url")
+            .build();
+        assert!(matches!(result, Err(Error::InvalidBaseUrl(_))));
+    }
+
+    #[tokio::test]
+    async fn requests_fail_over_and_recover_the_primary() {
+        let (primary, fallback) = (server(503).await, server(200).await);
+        let client = Client::builder(&primary.url, "t")
+            .fallback_url(&fallback.url)
+            .failover_threshold(1)
+            .recovery_interval(Duration::ZERO)
+            .build()
+            .unwrap();
+
+        client.healthz().await.unwrap();
+        assert_eq!(client.active_base_url().as_str(), fallback.url);
+        assert_eq!((requests(&primary), requests(&fallback)), (1, 1));
+
+        // Still down: the probe fails and requests stay on the fallback.
+        client.healthz().await.unwrap();
+        assert_eq!(client.active_base_url().as_str(), fallback.url);
+        assert_eq!((requests(&primary), requests(&fallback)), (2, 2));
+
+        primary.status.store(200, Ordering::SeqCst);
+        client.healthz().await.unwrap();
+        assert_eq!(client.active_base_url().as_str(), primary.url);
+        // The probe, then the request itself.
+        assert_eq!((requests(&primary), requests(&fallback)), (4, 2));
+    }
+
+    #[tokio::test]
+    async fn posts_are_retried_elsewhere_only_when_not_processed() {
+        let fallback = server(200).await;
+        let batch = BatchIngestRequest::default();
+
+        // Connection refused: the batch never arrived.
+        let client = Client::builder(&closed_url(), "t")
+            .fallback_url(&fallback.url)
+            .failover_threshold(1)
+            .build()
+            .unwrap();
+        client.ingest_batch(&batch).await.unwrap();
+        assert_eq!(requests(&fallback), 1);
+
+        // Timed out: the primary may have written it, so it is not sent again,
+        // but later requests go to the fallback.
+        let primary = server(0).await;
+        let client = Client::builder(&primary.url, "t")
+            .fallback_url(&fallback.url)
+            .failover_threshold(1)
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        assert!(client.ingest_batch(&batch).await.is_err());
+        assert_eq!(requests(&fallback), 1);
+        assert_eq!(client.active_base_url().as_str(), fallback.url);
+
+        // Reads are retried after a timeout.
+        let primary = server(0).await;
+        let client = Client::builder(&primary.url, "t")
+            .fallback_url(&fallback.url)
+            .failover_threshold(1)
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        client.healthz().await.unwrap();
+        assert_eq!(requests(&fallback), 2);
+    }
+
+    #[test]
+    fn truncation_keeps_metadata_that_is_not_an_object() {
+        let mut trace = TraceIngest::new(Uuid::new_v4());
+        trace.input = Some(JsonValue::String("x".repeat(100)));
+        trace.metadata = Some(serde_json::json!(["a", "b"]));
+        assert_eq!(trace.truncate_io(50), 1);
+        assert_eq!(
+            trace.metadata,
+            Some(serde_json::json!({
+                "value": ["a", "b"],
+                "xtrace_truncated": {"input": {"originalBytes": 102}},
+            }))
+        );
+
+        let mut observation = ObservationIngest::new(Uuid::new_v4(), Uuid::new_v4());
+        observation.metadata = Some(serde_json::json!("note"));
+        let observation = observation.with_step_id("s1");
+        assert_eq!(
+            observation.metadata,
+            Some(serde_json::json!({"value": "note", "step_id": "s1"}))
+        );
+    }
+
+    #[test]
+    fn sizes_count_json_escapes() {
+        let text = "say \"hi\"\\\n\t\u{1}\u{1f}é😀 ".repeat(20);
+        let value = JsonValue::String(text);
+        assert_eq!(json_len(&value), serde_json::to_vec(&value).unwrap().len());
+
+        for max in [30, 64, 100, 257] {
+            let mut field = Some(value.clone());
+            assert!(truncate_field(&mut field, max).is_some());
+            let encoded = serde_json::to_vec(&field.unwrap()).unwrap().len();
+            assert!(encoded <= max, "{encoded} > {max}");
+        }
+        let mut field = Some(serde_json::json!({"quote": "\"".repeat(50)}));
+        assert!(truncate_field(&mut field, 40).is_some());
+        assert!(serde_json::to_vec(&field.unwrap()).unwrap().len() <= 40);
+    }
+
+    #[test]
+    fn truncation_keeps_an_existing_marker_value() {
+        let mut observation = ObservationIngest::new(Uuid::new_v4(), Uuid::new_v4());
+        observation.output = Some(JsonValue::String("x".repeat(100)));
+        observation.metadata = Some(serde_json::json!({"xtrace_truncated": "by proxy"}));
+        assert_eq!(observation.truncate_io(50), 1);
+        assert_eq!(
+            observation.metadata,
+            Some(serde_json::json!({
+                "xtrace_truncated": {"value": "by proxy", "output": {"originalBytes": 102}},
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_batches_are_not_sent() {
+        let server = server(200).await;
+        let trace_id = Uuid::new_v4();
+        let mut observation = ObservationIngest::new(Uuid::new_v4(), trace_id);
+        observation.input = Some(JsonValue::String("x".repeat(4096)));
+        let batch = BatchIngestRequest {
+            trace: Some(TraceIngest::new(trace_id)),
+            observations: vec![observation],
+            ..Default::default()
+        };
+
+        let client = Client::new(&server.url, "t")
+            .unwrap()
+            .with_max_request_bytes(2048);
+        let err = client.ingest_batch(&batch).await.unwrap_err();
+        assert!(
+            matches!(err, Error::RequestTooLarge { bytes, max: 2048 } if bytes > 4096),
+            "{err}"
+        );
+        assert_eq!(requests(&server), 0);
+
+        // Truncated I/O fits.
+        let client = client.with_max_io_bytes(256);
+        client.ingest_batch(&batch).await.unwrap();
+        assert_eq!(requests(&server), 1);
+    }
 }
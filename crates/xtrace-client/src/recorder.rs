@@ -0,0 +1,224 @@
+//! Bookkeeping for streamed LLM responses.
+//!
+//! A [`GenerationRecorder`] is started when the request is sent and fed every
+//! chunk as it arrives. On [`finish`](GenerationRecorder::finish) it returns a
+//! `GENERATION` observation with `startTime`, `completionStartTime` (first
+//! non-empty chunk), `endTime`, `latency` and `timeToFirstToken` taken from one
+//! monotonic clock, the accumulated output, and chunk timings under metadata:
+//!
+//! | Key | Meaning |
+//! |-----|---------|
+//! | `stream_chunks` | Chunks with content |
+//! | `stream_inter_chunk_mean_ms` | Mean gap between consecutive chunks |
+//! | `stream_inter_chunk_max_ms` | Longest gap between consecutive chunks |
+//!
+//! To show the generation while it streams, send [`flush`](GenerationRecorder::flush)
+//! now and then: a partial update appending the output recorded since the
+//! previous flush. The observation from `finish` then completes it.
+//!
+//! ```ignore
+//! let mut rec = GenerationRecorder::start(trace_id, "chat", "gpt-4o").with_input(&messages);
+//! while let Some(chunk) = stream.next().await {
+//!     rec.record_chunk(&chunk?.delta);
+//! }
+//! rec.record_usage(Some(usage.prompt_tokens), Some(usage.completion_tokens));
+//! client.ingest_batch(&BatchIngestRequest { observations: vec![rec.finish()], ..Default::default() }).await?;
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::ObservationIngest;
+
+/// Records one streamed generation; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct GenerationRecorder {
+    observation: ObservationIngest,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    first_chunk: Option<Instant>,
+    last_chunk: Option<Instant>,
+    chunks: usize,
+    max_gap: Duration,
+    output: String,
+    /// Bytes of `output` already sent by [`flush`](Self::flush).
+    flushed: usize,
+    /// Whether a flush sent the observation's prepared fields.
+    announced: bool,
+    /// Deltas sent so far; the sequence number of the last one.
+    deltas: i64,
+}
+
+impl GenerationRecorder {
+    /// Starts recording a generation of `model` in `trace_id` now.
+    pub fn start(trace_id: Uuid, name: impl Into<String>, model: impl Into<String>) -> Self {
+        let mut observation = ObservationIngest::new(Uuid::new_v4(), trace_id);
+        observation.r#type = Some("GENERATION".to_string());
+        observation.name = Some(name.into());
+        observation.model = Some(model.into());
+        Self::from_observation(observation)
+    }
+
+    /// Starts recording now with a prepared observation (id, parent, prompt,
+    /// prices, ...). Its `type` defaults to `GENERATION`.
+    pub fn from_observation(mut observation: ObservationIngest) -> Self {
+        let started_at = Utc::now();
+        observation
+            .r#type
+            .get_or_insert_with(|| "GENERATION".to_string());
+        observation.start_time = Some(started_at);
+        Self {
+            observation,
+            started_at,
+            started: Instant::now(),
+            first_chunk: None,
+            last_chunk: None,
+            chunks: 0,
+            max_gap: Duration::ZERO,
+            output: String::new(),
+            flushed: 0,
+            announced: false,
+            deltas: 0,
+        }
+    }
+
+    pub fn with_input(mut self, input: impl Serialize) -> Self {
+        self.observation.input = serde_json::to_value(input).ok();
+        self
+    }
+
+    pub fn with_model_parameters(mut self, parameters: impl Serialize) -> Self {
+        self.observation.model_parameters = serde_json::to_value(parameters).ok();
+        self
+    }
+
+    pub fn with_parent_observation_id(mut self, parent: Uuid) -> Self {
+        self.observation.parent_observation_id = Some(parent);
+        self
+    }
+
+    /// Id of the observation that will be produced.
+    pub fn id(&self) -> Uuid {
+        self.observation.id
+    }
+
+    /// The observation being recorded, for fields without a builder method.
+    pub fn observation_mut(&mut self) -> &mut ObservationIngest {
+        &mut self.observation
+    }
+
+    /// Appends a streamed chunk. Empty chunks (role or keep-alive deltas) do
+    /// not count as tokens.
+    pub fn record_chunk(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last) = self.last_chunk {
+            self.max_gap = self.max_gap.max(now - last);
+        }
+        self.first_chunk.get_or_insert(now);
+        self.last_chunk = Some(now);
+        self.chunks += 1;
+        self.output.push_str(text);
+    }
+
+    /// Token counts reported by the provider, usually with the last chunk.
+    /// `totalTokens` is their sum when both are known.
+    pub fn record_usage(&mut self, prompt_tokens: Option<i64>, completion_tokens: Option<i64>) {
+        let obs = &mut self.observation;
+        obs.prompt_tokens = prompt_tokens.or(obs.prompt_tokens);
+        obs.completion_tokens = completion_tokens.or(obs.completion_tokens);
+        if let (Some(p), Some(c)) = (obs.prompt_tokens, obs.completion_tokens) {
+            obs.total_tokens = Some(p + c);
+        }
+    }
+
+    /// Time from the start to the first non-empty chunk.
+    pub fn time_to_first_token(&self) -> Option<Duration> {
+        self.first_chunk.map(|t| t - self.started)
+    }
+
+    /// Output accumulated so far.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// A partial update appending the output recorded since the last flush;
+    /// the first one also carries the prepared fields (name, model, input,
+    /// `startTime`, ...). `None` when there is nothing new to send.
+    pub fn flush(&mut self) -> Option<ObservationIngest> {
+        let delta = &self.output[self.flushed..];
+        if self.announced && delta.is_empty() {
+            return None;
+        }
+        let mut update = if self.announced {
+            let obs = &self.observation;
+            let mut update = ObservationIngest::partial(obs.id, obs.trace_id);
+            update.project_id = obs.project_id.clone();
+            update.environment = obs.environment.clone();
+            update
+        } else {
+            let mut update = self.observation.clone();
+            update.update = Some(true);
+            update
+        };
+        if !delta.is_empty() {
+            self.deltas += 1;
+            update.output_delta = Some(delta.to_string());
+            update.output_seq = Some(self.deltas);
+        }
+        self.flushed = self.output.len();
+        self.announced = true;
+        Some(update)
+    }
+
+    /// Ends the generation now and returns the populated observation. The
+    /// accumulated output replaces `output` unless no chunk was recorded.
+    /// After a [`flush`](Self::flush) it is sent as an update.
+    pub fn finish(mut self) -> ObservationIngest {
+        let elapsed = self.started.elapsed();
+        let at = |d: Duration| {
+            self.started_at + chrono::Duration::from_std(d).unwrap_or(chrono::TimeDelta::MAX)
+        };
+        let ttft = self.time_to_first_token();
+        let obs = &mut self.observation;
+        obs.end_time = Some(at(elapsed));
+        obs.latency = Some(elapsed.as_secs_f64());
+        obs.completion_start_time = ttft.map(at);
+        obs.time_to_first_token = ttft.map(|d| d.as_secs_f64());
+        if self.chunks > 0 {
+            obs.output = Some(serde_json::Value::String(std::mem::take(&mut self.output)));
+        }
+        if self.announced {
+            obs.update = Some(true);
+        }
+
+        let mut observation = self.observation;
+        observation = observation.with_metadata_field("stream_chunks", self.chunks);
+        if let (Some(first), Some(last)) = (self.first_chunk, self.last_chunk) {
+            if self.chunks > 1 {
+                let mean = (last - first).as_secs_f64() * 1000.0 / (self.chunks - 1) as f64;
+                observation = observation
+                    .with_metadata_field("stream_inter_chunk_mean_ms", mean)
+                    .with_metadata_field(
+                        "stream_inter_chunk_max_ms",
+                        self.max_gap.as_secs_f64() * 1000.0,
+                    );
+            }
+        }
+        observation
+    }
+
+    /// Ends a generation whose stream failed: like
+    /// [`finish`](Self::finish), keeping the partial output, with level
+    /// `ERROR` and `message` as the status message.
+    pub fn fail(self, message: impl Into<String>) -> ObservationIngest {
+        let mut observation = self.finish();
+        observation.level = Some("ERROR".to_string());
+        observation.status_message = Some(message.into());
+        observation
+    }
+}
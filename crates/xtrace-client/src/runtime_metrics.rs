@@ -0,0 +1,138 @@
+//! Opt-in Tokio runtime health metrics for [`XtraceLayer`].
+//!
+//! [`XtraceLayer::collect_runtime_metrics`] samples a runtime's
+//! [`RuntimeMetrics`](tokio::runtime::RuntimeMetrics) on an interval and pushes
+//! the values through the layer's metric batcher:
+//!
+//! | Metric | Labels | Meaning |
+//! |--------|--------|---------|
+//! | `tokio_workers` | | Worker threads |
+//! | `tokio_alive_tasks` | | Tasks spawned and not yet completed |
+//! | `tokio_global_queue_depth` | | Tasks waiting in the global queue |
+//! | `tokio_worker_busy_ratio` | `worker` | Share of the interval the worker was busy (0–1) |
+//! | `tokio_worker_park_count` | `worker` | Times the worker parked during the interval |
+//!
+//! Built with `RUSTFLAGS="--cfg tokio_unstable"`, these are added as well:
+//! `tokio_worker_mean_poll_seconds` and `tokio_worker_local_queue_depth`
+//! (per `worker`), `tokio_blocking_threads` and `tokio_blocking_queue_depth`.
+//!
+//! ```ignore
+//! let layer = XtraceLayer::new(client);
+//! layer.collect_runtime_metrics(&tokio::runtime::Handle::current(), Duration::from_secs(10));
+//! ```
+
+use chrono::Utc;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::runtime::{Handle, RuntimeMetrics};
+use tokio::task::JoinHandle;
+
+use crate::layer::XtraceLayer;
+use crate::MetricPoint;
+
+/// Per-worker counters from the previous sample, to report interval deltas.
+#[derive(Default)]
+struct Previous {
+    busy: Vec<Duration>,
+    parks: Vec<u64>,
+}
+
+impl XtraceLayer {
+    /// Samples the runtime behind `handle` every `interval` and pushes its
+    /// metrics (see [`crate::runtime_metrics`]). The sampler runs as a task on
+    /// that runtime until the returned handle is aborted.
+    pub fn collect_runtime_metrics(&self, handle: &Handle, interval: Duration) -> JoinHandle<()> {
+        let layer = self.clone();
+        let metrics = handle.metrics();
+        handle.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut prev = Previous::default();
+            // The first tick completes immediately; it only seeds the counters.
+            ticker.tick().await;
+            sample(&metrics, interval, &mut prev);
+            loop {
+                ticker.tick().await;
+                for point in sample(&metrics, interval, &mut prev) {
+                    layer.try_send(point);
+                }
+            }
+        })
+    }
+}
+
+fn sample(metrics: &RuntimeMetrics, interval: Duration, prev: &mut Previous) -> Vec<MetricPoint> {
+    let timestamp = Utc::now();
+    let point = |name: &str, value: f64, worker: Option<usize>| {
+        let mut labels = HashMap::new();
+        if let Some(worker) = worker {
+            labels.insert("worker".to_string(), worker.to_string());
+        }
+        MetricPoint {
+            name: name.to_string(),
+            labels,
+            value,
+            timestamp,
+        }
+    };
+
+    let workers = metrics.num_workers();
+    let mut points = vec![
+        point("tokio_workers", workers as f64, None),
+        point("tokio_alive_tasks", metrics.num_alive_tasks() as f64, None),
+        point(
+            "tokio_global_queue_depth",
+            metrics.global_queue_depth() as f64,
+            None,
+        ),
+    ];
+
+    #[cfg(target_has_atomic = "64")]
+    {
+        prev.busy.resize(workers, Duration::ZERO);
+        prev.parks.resize(workers, 0);
+        for worker in 0..workers {
+            let busy = metrics.worker_total_busy_duration(worker);
+            let parks = metrics.worker_park_count(worker);
+            let busy_delta = busy.saturating_sub(prev.busy[worker]);
+            let ratio = (busy_delta.as_secs_f64() / interval.as_secs_f64()).clamp(0.0, 1.0);
+            points.push(point("tokio_worker_busy_ratio", ratio, Some(worker)));
+            points.push(point(
+                "tokio_worker_park_count",
+                parks.saturating_sub(prev.parks[worker]) as f64,
+                Some(worker),
+            ));
+            prev.busy[worker] = busy;
+            prev.parks[worker] = parks;
+        }
+    }
+    #[cfg(not(target_has_atomic = "64"))]
+    let _ = (interval, prev);
+
+    #[cfg(tokio_unstable)]
+    {
+        for worker in 0..workers {
+            points.push(point(
+                "tokio_worker_mean_poll_seconds",
+                metrics.worker_mean_poll_time(worker).as_secs_f64(),
+                Some(worker),
+            ));
+            points.push(point(
+                "tokio_worker_local_queue_depth",
+                metrics.worker_local_queue_depth(worker) as f64,
+                Some(worker),
+            ));
+        }
+        points.push(point(
+            "tokio_blocking_threads",
+            metrics.num_blocking_threads() as f64,
+            None,
+        ));
+        points.push(point(
+            "tokio_blocking_queue_depth",
+            metrics.blocking_queue_depth() as f64,
+            None,
+        ));
+    }
+
+    points
+}
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS secret_findings (
+  id BIGSERIAL PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  trace_id UUID NOT NULL,
+  observation_id UUID NULL,
+  field TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  match_count INTEGER NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_secret_findings_project_created ON secret_findings (project_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_secret_findings_trace_id ON secret_findings (trace_id);
@@ -0,0 +1,14 @@
+ALTER TABLE traces
+  ADD COLUMN IF NOT EXISTS service_name TEXT NULL,
+  ADD COLUMN IF NOT EXISTS service_version TEXT NULL,
+  ADD COLUMN IF NOT EXISTS sdk_language TEXT NULL,
+  ADD COLUMN IF NOT EXISTS host_name TEXT NULL;
+
+ALTER TABLE observations
+  ADD COLUMN IF NOT EXISTS service_name TEXT NULL,
+  ADD COLUMN IF NOT EXISTS service_version TEXT NULL,
+  ADD COLUMN IF NOT EXISTS sdk_language TEXT NULL,
+  ADD COLUMN IF NOT EXISTS host_name TEXT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_traces_service_name ON traces (project_id, service_name);
+CREATE INDEX IF NOT EXISTS idx_observations_service_name ON observations (project_id, service_name);
@@ -0,0 +1,48 @@
+CREATE TABLE IF NOT EXISTS session_aggregates (
+  project_id TEXT NOT NULL,
+  session_id TEXT NOT NULL,
+  trace_count BIGINT NOT NULL,
+  total_cost DOUBLE PRECISION NOT NULL DEFAULT 0,
+  first_trace_at TIMESTAMPTZ NULL,
+  last_activity_at TIMESTAMPTZ NULL,
+  duration_seconds DOUBLE PRECISION NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (project_id, session_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_aggregates_last_activity
+  ON session_aggregates (project_id, last_activity_at DESC);
+
+INSERT INTO session_aggregates (
+  project_id, session_id, trace_count, total_cost,
+  first_trace_at, last_activity_at, duration_seconds, updated_at
+)
+SELECT
+  pt.project_id,
+  pt.session_id,
+  COUNT(*),
+  COALESCE(SUM(pt.cost), 0),
+  MIN(pt.started_at),
+  MAX(pt.ended_at),
+  EXTRACT(EPOCH FROM MAX(pt.ended_at) - MIN(pt.started_at))::DOUBLE PRECISION,
+  NOW()
+FROM (
+  SELECT
+    t.project_id,
+    t.session_id,
+    COALESCE(t.total_cost, o.cost) AS cost,
+    LEAST(t."timestamp", o.first_start) AS started_at,
+    GREATEST(t."timestamp", o.last_end) AS ended_at
+  FROM traces t
+  LEFT JOIN LATERAL (
+    SELECT
+      SUM(calculated_total_cost) AS cost,
+      MIN(start_time) AS first_start,
+      MAX(COALESCE(end_time, start_time)) AS last_end
+    FROM observations
+    WHERE trace_id = t.id
+  ) o ON TRUE
+  WHERE t.session_id IS NOT NULL
+) pt
+GROUP BY pt.project_id, pt.session_id
+ON CONFLICT (project_id, session_id) DO NOTHING;
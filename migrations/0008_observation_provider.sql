@@ -0,0 +1,3 @@
+ALTER TABLE observations ADD COLUMN IF NOT EXISTS provider TEXT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_observations_project_provider ON observations (project_id, provider);
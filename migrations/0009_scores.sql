@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS scores (
+  id UUID PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  trace_id UUID NOT NULL,
+  observation_id UUID NULL,
+  name TEXT NOT NULL,
+  source TEXT NOT NULL DEFAULT 'API',
+  data_type TEXT NOT NULL DEFAULT 'NUMERIC',
+  value DOUBLE PRECISION NULL,
+  string_value TEXT NULL,
+  comment TEXT NULL,
+  author_user_id TEXT NULL,
+  metadata JSONB NULL,
+  config_id TEXT NULL,
+  queue_id TEXT NULL,
+  environment TEXT NOT NULL DEFAULT 'default',
+  "timestamp" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_scores_project_timestamp ON scores (project_id, "timestamp" DESC);
+CREATE INDEX IF NOT EXISTS idx_scores_project_name ON scores (project_id, name);
+CREATE INDEX IF NOT EXISTS idx_scores_trace_id ON scores (trace_id);
@@ -0,0 +1,5 @@
+ALTER TABLE traces ADD COLUMN IF NOT EXISTS completed_at TIMESTAMPTZ NULL;
+ALTER TABLE traces ADD COLUMN IF NOT EXISTS late_writes INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_traces_project_completed_at ON traces (project_id, completed_at)
+  WHERE completed_at IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Additional credentials, reloaded by the server without a restart.
+-- kind = 'bearer': key is the bearer token.
+-- kind = 'basic': key / secret are a Langfuse public / secret key pair.
+CREATE TABLE IF NOT EXISTS api_keys (
+  id BIGSERIAL PRIMARY KEY,
+  kind TEXT NOT NULL CHECK (kind IN ('bearer', 'basic')),
+  key TEXT NOT NULL,
+  secret TEXT NULL,
+  note TEXT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  revoked_at TIMESTAMPTZ NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_api_keys_kind_key ON api_keys (kind, key);
@@ -0,0 +1,16 @@
+-- Projects known to the server. Keys bound to a project only see its data.
+CREATE TABLE IF NOT EXISTS projects (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  metadata JSONB NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO projects (id, name)
+SELECT DISTINCT project_id, project_id FROM traces
+ON CONFLICT (id) DO NOTHING;
+
+-- NULL: instance-wide key (default project, may write to any project).
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS project_id TEXT NULL
+  REFERENCES projects (id) ON DELETE CASCADE;
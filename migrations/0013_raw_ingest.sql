@@ -0,0 +1,20 @@
+-- Raw ingest payloads kept for XTRACE_RAW_INGEST_TTL_SECS, to debug SDK and
+-- mapping issues via GET /api/admin/traces/{id}/raw.
+CREATE TABLE IF NOT EXISTS raw_ingest (
+  id BIGSERIAL PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  -- Traces the payload contributed to.
+  trace_ids UUID[] NOT NULL,
+  endpoint TEXT NOT NULL,
+  content_type TEXT NULL,
+  -- Request headers, credentials redacted.
+  headers JSONB NOT NULL,
+  -- Decompressed body, cut at XTRACE_RAW_INGEST_MAX_BODY_BYTES.
+  body BYTEA NOT NULL,
+  body_truncated BOOLEAN NOT NULL DEFAULT FALSE,
+  received_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_raw_ingest_trace_ids ON raw_ingest USING GIN (trace_ids);
+CREATE INDEX IF NOT EXISTS idx_raw_ingest_expires_at ON raw_ingest (expires_at);
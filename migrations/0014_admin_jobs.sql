@@ -0,0 +1,19 @@
+-- Background maintenance jobs started through POST /api/admin/jobs.
+CREATE TABLE IF NOT EXISTS admin_jobs (
+  id UUID PRIMARY KEY,
+  kind TEXT NOT NULL,
+  params JSONB NOT NULL,
+  -- running | succeeded | failed
+  status TEXT NOT NULL,
+  -- Kind-specific counters, updated as the job advances.
+  progress JSONB NOT NULL DEFAULT '{}',
+  error TEXT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  finished_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_jobs_created_at ON admin_jobs (created_at DESC);
+
+-- Scoped scans of one model's observations (cost recomputation).
+CREATE INDEX IF NOT EXISTS idx_observations_model_start_time ON observations (model, start_time, id);
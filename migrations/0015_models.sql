@@ -0,0 +1,19 @@
+-- Model prices used to compute observation costs at ingest.
+CREATE TABLE IF NOT EXISTS models (
+  id UUID PRIMARY KEY,
+  -- NULL: shared by every project.
+  project_id TEXT NULL REFERENCES projects (id) ON DELETE CASCADE,
+  model_name TEXT NOT NULL,
+  -- Regular expression matched against observations.model.
+  match_pattern TEXT NOT NULL,
+  -- Prices apply to observations starting at or after this time.
+  start_date TIMESTAMPTZ NULL,
+  unit TEXT NOT NULL DEFAULT 'TOKENS',
+  input_price DOUBLE PRECISION NULL,
+  output_price DOUBLE PRECISION NULL,
+  total_price DOUBLE PRECISION NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_models_project_id ON models (project_id);
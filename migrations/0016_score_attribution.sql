@@ -0,0 +1,5 @@
+-- Key that wrote the score (`api_key:<id>`, a Langfuse public key or a masked
+-- bearer token); NULL for scores written without a key.
+ALTER TABLE scores ADD COLUMN IF NOT EXISTS api_key_id TEXT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_scores_project_source ON scores (project_id, source, "timestamp" DESC);
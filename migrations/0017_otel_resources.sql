@@ -0,0 +1,10 @@
+-- Resource attributes of OTLP spans, stored once per distinct set instead of
+-- in every observation's metadata (`otel.resource`).
+CREATE TABLE IF NOT EXISTS otel_resources (
+  -- UUIDv5 of the canonical attributes JSON.
+  id UUID PRIMARY KEY,
+  attributes JSONB NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE observations ADD COLUMN IF NOT EXISTS resource_id UUID NULL;
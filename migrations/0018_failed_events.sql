@@ -0,0 +1,18 @@
+-- Ingest payloads that could not be written after retries, kept for
+-- inspection and replay via /api/admin/failed-events.
+CREATE TABLE IF NOT EXISTS failed_events (
+  id BIGSERIAL PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  trace_ids UUID[] NOT NULL,
+  -- Serialized payload. Not JSONB: the value that failed the write may be one
+  -- JSONB rejects (e.g. a \u0000 escape).
+  payload BYTEA NOT NULL,
+  -- Traces to finalize once the payload is written.
+  complete UUID[] NOT NULL DEFAULT '{}',
+  error TEXT NOT NULL,
+  attempts INTEGER NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_failed_events_trace_ids ON failed_events USING GIN (trace_ids);
+CREATE INDEX IF NOT EXISTS idx_failed_events_project_created ON failed_events (project_id, created_at DESC);
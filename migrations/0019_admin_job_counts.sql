@@ -0,0 +1,4 @@
+-- Items a job has processed out of its total, for percentage progress.
+ALTER TABLE admin_jobs
+  ADD COLUMN IF NOT EXISTS processed BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS total BIGINT NULL;
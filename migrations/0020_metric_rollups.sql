@@ -0,0 +1,41 @@
+-- Metric points older than XTRACE_METRICS_ROLLUP_AFTER_SECS, rolled up per
+-- series into 1-minute buckets, and those older than
+-- XTRACE_METRICS_HOURLY_ROLLUP_AFTER_SECS into 1-hour buckets.
+CREATE TABLE IF NOT EXISTS metrics_1m (
+  id BIGSERIAL PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  environment TEXT NOT NULL,
+  name TEXT NOT NULL,
+  labels JSONB NOT NULL,
+  bucket TIMESTAMPTZ NOT NULL,
+  value_count BIGINT NOT NULL,
+  value_sum DOUBLE PRECISION NOT NULL,
+  value_min DOUBLE PRECISION NOT NULL,
+  value_max DOUBLE PRECISION NOT NULL,
+  -- Latest point of the bucket.
+  value_last DOUBLE PRECISION NOT NULL,
+  last_ts TIMESTAMPTZ NOT NULL,
+  UNIQUE (project_id, environment, name, labels, bucket)
+);
+
+CREATE INDEX IF NOT EXISTS idx_metrics_1m_name_bucket ON metrics_1m (project_id, name, bucket DESC);
+CREATE INDEX IF NOT EXISTS idx_metrics_1m_bucket ON metrics_1m (bucket);
+
+CREATE TABLE IF NOT EXISTS metrics_1h (
+  id BIGSERIAL PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  environment TEXT NOT NULL,
+  name TEXT NOT NULL,
+  labels JSONB NOT NULL,
+  bucket TIMESTAMPTZ NOT NULL,
+  value_count BIGINT NOT NULL,
+  value_sum DOUBLE PRECISION NOT NULL,
+  value_min DOUBLE PRECISION NOT NULL,
+  value_max DOUBLE PRECISION NOT NULL,
+  value_last DOUBLE PRECISION NOT NULL,
+  last_ts TIMESTAMPTZ NOT NULL,
+  UNIQUE (project_id, environment, name, labels, bucket)
+);
+
+CREATE INDEX IF NOT EXISTS idx_metrics_1h_name_bucket ON metrics_1h (project_id, name, bucket DESC);
+CREATE INDEX IF NOT EXISTS idx_metrics_1h_bucket ON metrics_1h (bucket);
@@ -0,0 +1,22 @@
+-- Full-text search over trace and observation content (`q` on the list
+-- endpoints): the name and every string of input, output and metadata,
+-- without stemming so prompts in any language match word for word. Only the
+-- first 100000 characters are indexed, keeping large payloads under the
+-- tsvector size limit.
+CREATE OR REPLACE FUNCTION search_document(name TEXT, input JSONB, output JSONB, metadata JSONB)
+RETURNS tsvector
+LANGUAGE SQL IMMUTABLE PARALLEL SAFE
+AS $$
+  SELECT to_tsvector('simple', left(concat_ws(' ', name, (
+    SELECT string_agg(v #>> '{}', ' ')
+    FROM jsonb_path_query(
+      jsonb_build_array(input, output, metadata),
+      'strict $.** ? (@.type() == "string")'
+    ) v
+  )), 100000))
+$$;
+
+CREATE INDEX IF NOT EXISTS idx_traces_search
+  ON traces USING GIN (search_document(name, input, output, metadata));
+CREATE INDEX IF NOT EXISTS idx_observations_search
+  ON observations USING GIN (search_document(name, input, output, metadata));
@@ -0,0 +1,7 @@
+-- Files written by `export_traces` jobs, in order of `seq`; deleted with the job.
+CREATE TABLE IF NOT EXISTS export_chunks (
+  job_id UUID NOT NULL REFERENCES admin_jobs(id) ON DELETE CASCADE,
+  seq INTEGER NOT NULL,
+  data BYTEA NOT NULL,
+  PRIMARY KEY (job_id, seq)
+);
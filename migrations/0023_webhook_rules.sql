@@ -0,0 +1,29 @@
+-- Rules that send a `rule.matched` webhook event (through event_outbox) when
+-- an ingested trace matches every condition set.
+CREATE TABLE IF NOT EXISTS webhook_rules (
+  id UUID PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  url TEXT NOT NULL,
+  -- An observation at or above this level (DEBUG, DEFAULT, WARNING, ERROR).
+  min_level TEXT NULL,
+  -- Trace total cost and latency (seconds) at least this.
+  min_cost DOUBLE PRECISION NULL,
+  min_latency DOUBLE PRECISION NULL,
+  environment TEXT NULL,
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_rules_project ON webhook_rules (project_id) WHERE enabled;
+
+-- Traces a rule fired for, so each fires at most once per trace.
+CREATE TABLE IF NOT EXISTS webhook_rule_matches (
+  rule_id UUID NOT NULL REFERENCES webhook_rules(id) ON DELETE CASCADE,
+  trace_id UUID NOT NULL REFERENCES traces(id) ON DELETE CASCADE,
+  matched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (rule_id, trace_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_rule_matches_trace ON webhook_rule_matches (trace_id);
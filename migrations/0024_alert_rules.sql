@@ -0,0 +1,33 @@
+-- Rules that compare an aggregate of a metric over a trailing window with a
+-- threshold and notify Slack and/or email when the result changes.
+CREATE TABLE IF NOT EXISTS alert_rules (
+  id UUID PRIMARY KEY,
+  project_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  -- A metric name in metrics/metrics_1m/metrics_1h, or xtrace.cost,
+  -- xtrace.traces or xtrace.errors.
+  metric TEXT NOT NULL,
+  -- Points whose labels contain these (pushed metrics only).
+  labels JSONB NULL,
+  agg TEXT NOT NULL,
+  comparator TEXT NOT NULL,
+  threshold DOUBLE PRECISION NOT NULL,
+  window_secs INTEGER NOT NULL,
+  slack_webhook_url TEXT NULL,
+  email_to TEXT[] NOT NULL DEFAULT '{}',
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  -- ok or firing, as of the last evaluation.
+  state TEXT NOT NULL DEFAULT 'ok',
+  -- The state the sinks were last told about; differs from state until a
+  -- notification is delivered.
+  notified_state TEXT NOT NULL DEFAULT 'ok',
+  last_value DOUBLE PRECISION NULL,
+  last_evaluated_at TIMESTAMPTZ NULL,
+  last_notified_at TIMESTAMPTZ NULL,
+  -- Last failed evaluation or delivery.
+  last_error TEXT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_rules_project ON alert_rules (project_id);
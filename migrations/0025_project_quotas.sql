@@ -0,0 +1,23 @@
+-- Monthly ingest quotas per project; NULL limits are unlimited.
+CREATE TABLE IF NOT EXISTS project_quotas (
+  project_id TEXT PRIMARY KEY REFERENCES projects (id) ON DELETE CASCADE,
+  monthly_traces BIGINT NULL,
+  monthly_tokens BIGINT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Usage of projects with a quota per calendar month (UTC), recounted by the
+-- quota accounting task: traces created and observation tokens recorded.
+CREATE TABLE IF NOT EXISTS project_usage (
+  project_id TEXT NOT NULL,
+  month DATE NOT NULL,
+  traces BIGINT NOT NULL,
+  tokens BIGINT NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (project_id, month)
+);
+
+CREATE INDEX IF NOT EXISTS idx_traces_project_created ON traces (project_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_observations_project_created
+  ON observations (project_id, created_at) INCLUDE (total_tokens);
@@ -0,0 +1,3 @@
+-- full: ingest, queries and admin; ingest: writes only; read: queries only.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS access TEXT NOT NULL DEFAULT 'full'
+  CHECK (access IN ('full', 'ingest', 'read'));
@@ -0,0 +1,76 @@
+-- Traces and observations become range partitions by trace time: traces by
+-- `timestamp`, observations by `trace_timestamp`, their trace's timestamp,
+-- so time-bounded trace queries prune partitions and a trace's observations
+-- share its partition range. When a write changes a trace's timestamp, its
+-- row and its observations move to the matching partitions.
+--
+-- The existing tables are kept as the DEFAULT partitions, for rows before the
+-- migration time (and backfills older than the oldest partition); a check
+-- constraint keeps other rows out, so partitions can be added without
+-- scanning them. Existing rows from the migration time on move to the new
+-- partitions. The `_future` partitions hold rows past the newest range
+-- partition; the server splits new partitions off them ahead of time and
+-- retention drops expired ones whole.
+--
+-- Primary keys must include the partition key, so they are `(id, timestamp)`
+-- and `(id, trace_timestamp)`. Ids stay unique because writes take the
+-- trace's advisory lock and look rows up by id before inserting (see
+-- `ingest::batch::write_one`). Foreign keys to `traces (id)` are gone; trace
+-- deletion removes the rows attached to a trace itself.
+ALTER TABLE observations DROP CONSTRAINT IF EXISTS observations_trace_id_fkey;
+ALTER TABLE webhook_rule_matches DROP CONSTRAINT IF EXISTS webhook_rule_matches_trace_id_fkey;
+
+ALTER TABLE observations ADD COLUMN trace_timestamp TIMESTAMPTZ;
+UPDATE observations o SET trace_timestamp = COALESCE(
+  (SELECT t."timestamp" FROM traces t WHERE t.id = o.trace_id),
+  o.created_at
+);
+ALTER TABLE observations ALTER COLUMN trace_timestamp SET NOT NULL;
+
+DO $$
+DECLARE
+  t TEXT;
+  k TEXT;
+  names TEXT[];
+  defs TEXT[];
+  since TIMESTAMPTZ := NOW();
+  until TIMESTAMPTZ := (date_trunc('day', NOW() AT TIME ZONE 'UTC') + INTERVAL '1 day') AT TIME ZONE 'UTC';
+BEGIN
+  FOREACH t IN ARRAY ARRAY['traces', 'observations'] LOOP
+    k := CASE t WHEN 'traces' THEN 'timestamp' ELSE 'trace_timestamp' END;
+    SELECT COALESCE(array_agg(indexname::TEXT), '{}'), COALESCE(array_agg(indexdef), '{}')
+    INTO names, defs
+    FROM pg_indexes
+    WHERE schemaname = current_schema() AND tablename = t AND indexname <> t || '_pkey';
+
+    FOR i IN 1 .. COALESCE(array_length(names, 1), 0) LOOP
+      EXECUTE format('ALTER INDEX %I RENAME TO %I', names[i], names[i] || '_default');
+    END LOOP;
+    EXECUTE format('ALTER TABLE %I RENAME TO %I', t, t || '_default');
+    EXECUTE format('ALTER TABLE %I DROP CONSTRAINT %I', t || '_default', t || '_pkey');
+    EXECUTE format('ALTER TABLE %I ADD PRIMARY KEY (id, %I)', t || '_default', k);
+
+    EXECUTE format(
+      'CREATE TABLE %I (LIKE %I INCLUDING DEFAULTS INCLUDING STORAGE) PARTITION BY RANGE (%I)',
+      t, t || '_default', k);
+    EXECUTE format('ALTER TABLE %I ADD PRIMARY KEY (id, %I)', t, k);
+    EXECUTE format(
+      'CREATE TABLE %I PARTITION OF %I FOR VALUES FROM (%L) TO (%L)',
+      t || '_p' || to_char(since AT TIME ZONE 'UTC', 'YYYYMMDD'), t, since, until);
+    EXECUTE format(
+      'CREATE TABLE %I PARTITION OF %I FOR VALUES FROM (%L) TO (MAXVALUE)',
+      t || '_future', t, until);
+    EXECUTE format(
+      'WITH moved AS (DELETE FROM %I WHERE %I >= %L RETURNING *) INSERT INTO %I SELECT * FROM moved',
+      t || '_default', k, since, t);
+    EXECUTE format(
+      'ALTER TABLE %I ADD CONSTRAINT %I CHECK (%I < %L)',
+      t || '_default', t || '_default_' || k, k, since);
+    EXECUTE format('ALTER TABLE %I ATTACH PARTITION %I DEFAULT', t, t || '_default');
+
+    -- Recreated on the parent; the default partition's indexes are attached.
+    FOR i IN 1 .. COALESCE(array_length(defs, 1), 0) LOOP
+      EXECUTE defs[i];
+    END LOOP;
+  END LOOP;
+END $$;
@@ -0,0 +1,6 @@
+-- Object keys of observation `input` / `output` bodies offloaded to object
+-- storage (`XTRACE_OFFLOAD_S3_BUCKET`). The column itself then holds a
+-- preview.
+ALTER TABLE observations
+  ADD COLUMN IF NOT EXISTS input_offload TEXT NULL,
+  ADD COLUMN IF NOT EXISTS output_offload TEXT NULL;
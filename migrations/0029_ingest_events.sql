@@ -0,0 +1,10 @@
+-- Event ids of written ingest payloads, kept for `XTRACE_INGEST_DEDUP_TTL_SECS`
+-- so that retried payloads are skipped instead of applied again.
+CREATE TABLE IF NOT EXISTS ingest_events (
+  project_id TEXT NOT NULL,
+  event_id TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (project_id, event_id)
+);
+
+CREATE INDEX IF NOT EXISTS ingest_events_created_at_idx ON ingest_events (created_at);
@@ -0,0 +1,7 @@
+-- When the SDK recorded the state last written to a trace or observation
+-- (`eventTime`, else the time of the write). The `latest` conflict policy
+-- does not let older payloads replace values of newer ones.
+ALTER TABLE traces
+  ADD COLUMN IF NOT EXISTS event_time TIMESTAMPTZ NULL;
+ALTER TABLE observations
+  ADD COLUMN IF NOT EXISTS event_time TIMESTAMPTZ NULL;
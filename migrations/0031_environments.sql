@@ -0,0 +1,16 @@
+-- Environments seen per project, kept up to date by the ingest workers so
+-- environment filters do not scan `traces`. First and last seen are trace
+-- timestamps; rows stay when their traces are deleted.
+CREATE TABLE IF NOT EXISTS environments (
+  project_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  first_seen TIMESTAMPTZ NOT NULL,
+  last_seen TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (project_id, name)
+);
+
+INSERT INTO environments (project_id, name, first_seen, last_seen)
+SELECT project_id, environment, MIN(timestamp), MAX(timestamp)
+FROM traces
+GROUP BY project_id, environment
+ON CONFLICT (project_id, name) DO NOTHING;
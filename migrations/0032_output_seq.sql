@@ -0,0 +1,5 @@
+-- Sequence number of the last `outputDelta` appended to an observation's
+-- output. A delta at or below it was already applied and is skipped, so
+-- retried and replayed chunks are not appended twice.
+ALTER TABLE observations
+  ADD COLUMN IF NOT EXISTS output_seq BIGINT NULL;
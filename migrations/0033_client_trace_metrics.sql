@@ -0,0 +1,6 @@
+-- Whether a trace's latency and total cost were sent by the client. The
+-- ingest rollup from observations leaves such values alone, also when later
+-- writes of the trace omit them.
+ALTER TABLE traces
+  ADD COLUMN IF NOT EXISTS client_latency BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN IF NOT EXISTS client_total_cost BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,24 @@
+-- Trace counts per project, 15-minute bucket (by trace timestamp),
+-- environment and name, kept up to date by every writer of `traces` so the
+-- activity heatmap does not scan them. Every UTC offset is a multiple of 15
+-- minutes, so the buckets add up to exact local hours in any time zone.
+-- Unnamed traces are counted under the name ''.
+CREATE TABLE IF NOT EXISTS trace_activity (
+  project_id TEXT NOT NULL,
+  bucket TIMESTAMPTZ NOT NULL,
+  environment TEXT NOT NULL,
+  name TEXT NOT NULL,
+  trace_count BIGINT NOT NULL,
+  PRIMARY KEY (project_id, bucket, environment, name)
+);
+
+INSERT INTO trace_activity (project_id, bucket, environment, name, trace_count)
+SELECT
+  project_id,
+  date_bin('15 minutes', "timestamp", TIMESTAMPTZ 'epoch'),
+  environment,
+  COALESCE(name, ''),
+  COUNT(*)
+FROM traces
+GROUP BY 1, 2, 3, 4
+ON CONFLICT (project_id, bucket, environment, name) DO NOTHING;
@@ -0,0 +1,240 @@
+//! Alert rule evaluation: every `XTRACE_ALERT_INTERVAL_SECS`, each enabled
+//! rule's aggregate over its window ending now is compared with its
+//! threshold. A window without data is not breached, except for counts,
+//! which are 0.
+//!
+//! A rule notifies its sinks when its state differs from the one they were
+//! last told about: `firing` when the condition starts to hold, `resolved`
+//! when it stops. Failed deliveries are retried at the next evaluation, so
+//! notifications are at least once. A rule is locked while it is evaluated,
+//! so instances sharing the database do not notify twice.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use sqlx::{PgPool, QueryBuilder};
+use uuid::Uuid;
+
+use super::sinks::{self, Mailer};
+use crate::{
+    http::metrics::{agg_expr, push_metric_points},
+    state::WriteGate,
+};
+
+const RULE_COLUMNS: &str = "id, project_id, name, metric, labels, agg, comparator, threshold, \
+  window_secs, slack_webhook_url, email_to, notified_state";
+
+#[derive(Debug, sqlx::FromRow)]
+struct RuleRow {
+    id: Uuid,
+    project_id: String,
+    name: String,
+    metric: String,
+    labels: Option<serde_json::Value>,
+    agg: String,
+    comparator: String,
+    threshold: f64,
+    window_secs: i32,
+    slack_webhook_url: Option<String>,
+    email_to: Vec<String>,
+    notified_state: String,
+}
+
+impl RuleRow {
+    fn breached(&self, value: f64) -> bool {
+        match self.comparator.as_str() {
+            ">" => value > self.threshold,
+            ">=" => value >= self.threshold,
+            "<" => value < self.threshold,
+            "<=" => value <= self.threshold,
+            _ => false,
+        }
+    }
+
+    /// Subject line and text of the notification for `state`.
+    fn message(&self, state: &str, value: Option<f64>) -> (String, String) {
+        let label = if state == "firing" {
+            "FIRING"
+        } else {
+            "RESOLVED"
+        };
+        let subject = format!("[xtrace] {label}: {}", self.name);
+        let value = value.map_or_else(|| "no data".to_string(), |v| v.to_string());
+        let text = format!(
+            "{subject}\n{}({}) over the last {}s is {value} (threshold {} {}), project {}",
+            self.agg,
+            self.metric,
+            self.window_secs,
+            self.comparator,
+            self.threshold,
+            self.project_id,
+        );
+        (subject, text)
+    }
+
+    /// The aggregate over the window ending now.
+    async fn value(&self, pool: &PgPool) -> Result<Option<f64>, sqlx::Error> {
+        let to_ts = Utc::now();
+        let from_ts = to_ts - chrono::Duration::seconds(self.window_secs.into());
+        let trace_query = match self.metric.as_str() {
+            "xtrace.cost" => Some(
+                "SELECT COALESCE(SUM(t.total_cost), 0)::DOUBLE PRECISION FROM traces t \
+                 WHERE t.project_id = $1 AND t.\"timestamp\" >= $2 AND t.\"timestamp\" <= $3",
+            ),
+            "xtrace.traces" => Some(
+                "SELECT COUNT(*)::DOUBLE PRECISION FROM traces t \
+                 WHERE t.project_id = $1 AND t.\"timestamp\" >= $2 AND t.\"timestamp\" <= $3",
+            ),
+            "xtrace.errors" => Some(
+                "SELECT COUNT(*)::DOUBLE PRECISION FROM traces t JOIN observations o ON o.trace_id = t.id \
+                 WHERE t.project_id = $1 AND t.\"timestamp\" >= $2 AND t.\"timestamp\" <= $3 AND o.level = 'ERROR'",
+            ),
+            _ => None,
+        };
+        if let Some(query) = trace_query {
+            return sqlx::query_scalar(query)
+                .bind(&self.project_id)
+                .bind(from_ts)
+                .bind(to_ts)
+                .fetch_one(pool)
+                .await;
+        }
+
+        let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new("WITH points AS (\n");
+        push_metric_points(
+            &mut builder,
+            &self.project_id,
+            &self.metric,
+            from_ts,
+            to_ts,
+            self.labels.as_ref(),
+        );
+        builder.push(")\nSELECT ");
+        builder.push(agg_expr(&self.agg));
+        builder.push(" FROM points");
+        builder.build_query_scalar().fetch_one(pool).await
+    }
+}
+
+/// Evaluates the enabled rules every `interval` while writes are enabled.
+pub(crate) async fn alert_worker(
+    pool: PgPool,
+    gate: Arc<WriteGate>,
+    mailer: Option<Arc<Mailer>>,
+    interval: Duration,
+) {
+    let http = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(c) => c,
+        Err(err) => {
+            tracing::error!(error = %err, "alerts: failed to build http client");
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let ids: Vec<Uuid> =
+            match sqlx::query_scalar("SELECT id FROM alert_rules WHERE enabled ORDER BY id")
+                .fetch_all(&pool)
+                .await
+            {
+                Ok(ids) => ids,
+                Err(err) => {
+                    tracing::error!(error = %err, "alerts: failed to list rules");
+                    continue;
+                }
+            };
+        for id in ids {
+            if gate.try_reserve(1).is_err() {
+                break;
+            }
+            let result = evaluate_rule(&pool, &http, mailer.as_deref(), id).await;
+            gate.release(1);
+            if let Err(err) = result {
+                tracing::error!(error = %err, rule_id = %id, "alerts: evaluation failed");
+            }
+        }
+    }
+}
+
+async fn evaluate_rule(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    mailer: Option<&Mailer>,
+    id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let rule: Option<RuleRow> = sqlx::query_as(&format!(
+        "SELECT {RULE_COLUMNS} FROM alert_rules WHERE id = $1 AND enabled FOR UPDATE SKIP LOCKED"
+    ))
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    // Deleted, disabled, or being evaluated by another instance.
+    let Some(rule) = rule else {
+        return Ok(());
+    };
+
+    let value = rule.value(pool).await?;
+    let state = if value.is_some_and(|v| rule.breached(v)) {
+        "firing"
+    } else {
+        "ok"
+    };
+
+    let mut notified_state = rule.notified_state.as_str();
+    let mut notified = false;
+    let mut error = None;
+    if state != notified_state {
+        let (subject, text) = rule.message(state, value);
+        let mut errors = Vec::new();
+        if let Some(url) = &rule.slack_webhook_url {
+            if let Err(err) = sinks::post_slack(http, url, &text).await {
+                errors.push(err);
+            }
+        }
+        if !rule.email_to.is_empty() {
+            let sent = match mailer {
+                Some(mailer) => mailer.send(&rule.email_to, &subject, &text).await,
+                None => Err("email: XTRACE_SMTP_URL is not set".to_string()),
+            };
+            if let Err(err) = sent {
+                errors.push(err);
+            }
+        }
+        if errors.is_empty() {
+            notified_state = state;
+            notified = true;
+        } else {
+            let err = errors.join("; ");
+            tracing::warn!(rule_id = %rule.id, error = %err, "alerts: notification failed");
+            error = Some(err);
+        }
+    }
+
+    sqlx::query(
+        r#"
+UPDATE alert_rules SET
+  state = $2,
+  notified_state = $3,
+  last_value = $4,
+  last_evaluated_at = NOW(),
+  last_notified_at = CASE WHEN $5 THEN NOW() ELSE last_notified_at END,
+  last_error = $6
+WHERE id = $1
+        "#,
+    )
+    .bind(rule.id)
+    .bind(state)
+    .bind(notified_state)
+    .bind(value)
+    .bind(notified)
+    .bind(error)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
@@ -0,0 +1,328 @@
+//! Alert rules, managed through `/api/admin/alerts`: an aggregate of a metric
+//! over a trailing window compared with a threshold, e.g. the `sum` of
+//! `xtrace.cost` over the last hour `>` 50. Metrics are those pushed to
+//! `POST /v1/metrics/batch` (read from the raw and rolled-up tables
+//! alike), or one derived from traces:
+//!
+//! - `xtrace.cost`: total cost of the traces in the window
+//! - `xtrace.traces`: number of traces in the window
+//! - `xtrace.errors`: `ERROR` observations of the traces in the window
+//!
+//! The [evaluator](evaluate) notifies a Slack webhook and/or email addresses
+//! when a rule starts firing and when it resolves.
+
+pub(crate) mod evaluate;
+pub(crate) mod sinks;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use crate::{
+    http::{error::ApiError, metrics::parse_agg, projects},
+    state::AppState,
+};
+
+/// Metrics computed from traces rather than the metrics tables; their value
+/// is the total over the window.
+pub(crate) const TRACE_METRICS: [&str; 3] = ["xtrace.cost", "xtrace.traces", "xtrace.errors"];
+
+const COMPARATORS: [&str; 4] = [">", ">=", "<", "<="];
+
+/// 30 days.
+const MAX_WINDOW_SECS: i32 = 30 * 24 * 3600;
+
+const RULE_COLUMNS: &str = "id, project_id, name, metric, labels, agg, comparator, threshold, \
+  window_secs, slack_webhook_url, email_to, enabled, state, last_value, last_evaluated_at, \
+  last_notified_at, last_error, created_at, updated_at";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AlertRuleRequest {
+    name: String,
+    /// The default project when unset.
+    #[serde(default)]
+    project_id: Option<String>,
+    metric: String,
+    /// Only points whose labels contain these.
+    #[serde(default)]
+    labels: Option<JsonValue>,
+    /// Defaults to `avg`, and to `sum` for trace metrics.
+    #[serde(default)]
+    agg: Option<String>,
+    comparator: String,
+    threshold: f64,
+    window_secs: i32,
+    #[serde(default)]
+    slack_webhook_url: Option<String>,
+    #[serde(default)]
+    email_to: Vec<String>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl AlertRuleRequest {
+    fn validate(&mut self, state: &AppState) -> Result<(), ApiError> {
+        if self.name.trim().is_empty() {
+            return Err(ApiError::BadRequest("name must not be empty".to_string()));
+        }
+        if self.metric.trim().is_empty() {
+            return Err(ApiError::BadRequest("metric must not be empty".to_string()));
+        }
+        if self
+            .project_id
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest(
+                "projectId must not be empty".to_string(),
+            ));
+        }
+        let agg = if TRACE_METRICS.contains(&self.metric.as_str()) {
+            if self.labels.is_some() {
+                return Err(ApiError::BadRequest(format!(
+                    "labels do not apply to {}",
+                    self.metric
+                )));
+            }
+            match self.agg.as_deref() {
+                None | Some("sum") => "sum",
+                Some(_) => {
+                    return Err(ApiError::BadRequest(format!(
+                        "{} only supports agg sum",
+                        self.metric
+                    )))
+                }
+            }
+        } else {
+            if let Some(labels) = &self.labels {
+                if !labels
+                    .as_object()
+                    .is_some_and(|l| l.values().all(JsonValue::is_string))
+                {
+                    return Err(ApiError::BadRequest(
+                        "labels must be an object of strings".to_string(),
+                    ));
+                }
+            }
+            match self.agg.as_deref() {
+                Some("count") => "count",
+                agg => parse_agg(agg)?,
+            }
+        };
+        self.agg = Some(agg.to_string());
+        if !COMPARATORS.contains(&self.comparator.as_str()) {
+            return Err(ApiError::BadRequest(format!(
+                "invalid comparator: {} (expected >, >=, < or <=)",
+                self.comparator
+            )));
+        }
+        if !self.threshold.is_finite() {
+            return Err(ApiError::BadRequest(
+                "threshold must be a finite number".to_string(),
+            ));
+        }
+        if !(1..=MAX_WINDOW_SECS).contains(&self.window_secs) {
+            return Err(ApiError::BadRequest(format!(
+                "windowSecs must be between 1 and {MAX_WINDOW_SECS}"
+            )));
+        }
+        if self.slack_webhook_url.is_none() && self.email_to.is_empty() {
+            return Err(ApiError::BadRequest(
+                "one of slackWebhookUrl or emailTo is required".to_string(),
+            ));
+        }
+        if let Some(url) = &self.slack_webhook_url {
+            match reqwest::Url::parse(url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => {
+                    return Err(ApiError::BadRequest(
+                        "slackWebhookUrl must be an http(s) URL".to_string(),
+                    ))
+                }
+            }
+        }
+        if !self.email_to.is_empty() {
+            if state.mailer.is_none() {
+                return Err(ApiError::BadRequest(
+                    "emailTo requires XTRACE_SMTP_URL".to_string(),
+                ));
+            }
+            for address in &self.email_to {
+                sinks::parse_mailbox(address).map_err(ApiError::BadRequest)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn project_id(&self, state: &AppState) -> String {
+        self.project_id
+            .clone()
+            .unwrap_or_else(|| state.default_project_id.to_string())
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct AlertRuleDto {
+    id: Uuid,
+    project_id: String,
+    name: String,
+    metric: String,
+    labels: Option<JsonValue>,
+    agg: String,
+    comparator: String,
+    threshold: f64,
+    window_secs: i32,
+    slack_webhook_url: Option<String>,
+    email_to: Vec<String>,
+    enabled: bool,
+    /// `ok` or `firing`, as of `lastEvaluatedAt`.
+    state: String,
+    /// The aggregate at `lastEvaluatedAt`; `null` without data.
+    last_value: Option<f64>,
+    last_evaluated_at: Option<DateTime<Utc>>,
+    last_notified_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AlertRuleListQuery {
+    #[serde(default)]
+    project_id: Option<String>,
+}
+
+pub(crate) async fn get_alert_rules(
+    State(state): State<AppState>,
+    Query(q): Query<AlertRuleListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let data: Vec<AlertRuleDto> = sqlx::query_as(&format!(
+        "SELECT {RULE_COLUMNS} FROM alert_rules WHERE ($1::TEXT IS NULL OR project_id = $1) ORDER BY project_id, name, id"
+    ))
+    .bind(q.project_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(serde_json::json!({ "data": data }))))
+}
+
+pub(crate) async fn get_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row: Option<AlertRuleDto> = sqlx::query_as(&format!(
+        "SELECT {RULE_COLUMNS} FROM alert_rules WHERE id = $1"
+    ))
+    .bind(rule_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(row.ok_or(ApiError::NotFound)?)))
+}
+
+pub(crate) async fn post_alert_rule(
+    State(state): State<AppState>,
+    Json(mut req): Json<AlertRuleRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    req.validate(&state)?;
+    let project_id = req.project_id(&state);
+    projects::register(&state.pool, std::slice::from_ref(&project_id)).await?;
+    let row: AlertRuleDto = sqlx::query_as(&format!(
+        r#"
+INSERT INTO alert_rules (
+  id, project_id, name, metric, labels, agg, comparator, threshold, window_secs,
+  slack_webhook_url, email_to, enabled
+) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+RETURNING {RULE_COLUMNS}
+        "#
+    ))
+    .bind(Uuid::new_v4())
+    .bind(project_id)
+    .bind(req.name.trim())
+    .bind(&req.metric)
+    .bind(&req.labels)
+    .bind(&req.agg)
+    .bind(&req.comparator)
+    .bind(req.threshold)
+    .bind(req.window_secs)
+    .bind(&req.slack_webhook_url)
+    .bind(&req.email_to)
+    .bind(req.enabled)
+    .fetch_one(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(row)))
+}
+
+/// Replaces every field of a rule. Its state is kept: a firing rule whose
+/// new condition no longer holds resolves at the next evaluation.
+pub(crate) async fn put_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+    Json(mut req): Json<AlertRuleRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    req.validate(&state)?;
+    let project_id = req.project_id(&state);
+    projects::register(&state.pool, std::slice::from_ref(&project_id)).await?;
+    let row: AlertRuleDto = sqlx::query_as(&format!(
+        r#"
+UPDATE alert_rules SET
+  project_id = $2,
+  name = $3,
+  metric = $4,
+  labels = $5,
+  agg = $6,
+  comparator = $7,
+  threshold = $8,
+  window_secs = $9,
+  slack_webhook_url = $10,
+  email_to = $11,
+  enabled = $12,
+  updated_at = NOW()
+WHERE id = $1
+RETURNING {RULE_COLUMNS}
+        "#
+    ))
+    .bind(rule_id)
+    .bind(project_id)
+    .bind(req.name.trim())
+    .bind(&req.metric)
+    .bind(&req.labels)
+    .bind(&req.agg)
+    .bind(&req.comparator)
+    .bind(req.threshold)
+    .bind(req.window_secs)
+    .bind(&req.slack_webhook_url)
+    .bind(&req.email_to)
+    .bind(req.enabled)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    Ok((StatusCode::OK, Json(row)))
+}
+
+pub(crate) async fn delete_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let deleted = sqlx::query("DELETE FROM alert_rules WHERE id = $1")
+        .bind(rule_id)
+        .execute(&state.pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok((StatusCode::OK, Json(serde_json::json!({ "id": rule_id }))))
+}
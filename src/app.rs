@@ -1,117 +1,795 @@
 use axum::{
     extract::DefaultBodyLimit,
     middleware::{self},
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use sqlx::postgres::PgPoolOptions;
 use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::mpsc;
+use tonic::transport::Server as GrpcServer;
 use tower_http::trace::TraceLayer;
 
+use crate::alerts::{self, evaluate::alert_worker, sinks::Mailer};
 use crate::http::common::{healthz, readyz};
 use crate::http::{
-    auth::{auth, rate_limit},
+    analytics,
+    auth::{admin_only, auth, ingest_rate_limit, rate_limit, require_read, require_write},
+    config::{effective_config, get_config},
+    environments,
+    ingest_limit::IngestCostLimiter,
+    keys::{key_reload_worker, KeyAccess, KeySet, KeyStore},
+    meta,
+    metric_rollups::{metrics_rollup_worker, RollupSettings},
     metrics::{self, metrics_worker, post_metrics_batch, MetricsBatchRequest},
-    ops::get_rate_limit_stats,
-    projects::get_projects,
-    traces,
+    models,
+    network::{network_policy, ClientCertMode, GroupPolicy, IpAllowlist, RouteGroup},
+    observations,
+    ops::{get_rate_limit_stats, get_stats, get_write_mode, put_write_mode},
+    projects::{self, get_projects},
+    quotas, scores,
+    services::get_service_map,
+    sessions, sharing, tags,
+    telemetry::{get_metrics, track_requests},
+    tls::{serve_tls, TlsFiles},
+    trace_delete::{delete_trace, post_delete_traces},
+    trace_tree, trace_update, traces, usage, users,
+    version::{api_version, ApiVersionPolicy, VersionLifecycle},
+    wait::wait_for_trace,
+    window::QueryWindows,
 };
-use crate::ingest::batch::{ingest_worker, post_batch, BatchIngestRequest};
+use crate::ingest::batch::{
+    ingest_worker, post_batch, IngestBatching, IngestQueue, IngestSettings,
+};
+use crate::ingest::completion::{post_trace_complete, LateWriteMode};
+use crate::ingest::conflicts::ConflictPolicies;
+use crate::ingest::dead_letter;
+use crate::ingest::dedup::dedup_purge_worker;
+#[cfg(feature = "kafka")]
+use crate::ingest::kafka::{self, KafkaSettings};
+use crate::ingest::langfuse;
+use crate::ingest::models::ModelAliases;
 use crate::ingest::otlp;
-use crate::state::{AppState, RateLimitStats, ServerConfig};
+use crate::ingest::otlp_grpc::OtlpTraceService;
+use crate::ingest::pricing::PriceRegistry;
+use crate::ingest::raw::{self, raw_ingest_worker, RawIngestRecorder, RAW_INGEST_QUEUE};
+use crate::ingest::sampling::SamplingPolicy;
+use crate::ingest::secrets::SecretScanner;
+use crate::ingest::sessions::session_reconcile_worker;
+use crate::ingest::wal::Wal;
+use crate::jobs::{
+    self, export, get_job, get_job_events, get_jobs,
+    partitions::{self, PartitionInterval},
+    post_job,
+    retention::{retention_worker, RetentionPolicy},
+};
+use crate::offload::{
+    s3::{S3Client, S3Config},
+    Offloader,
+};
+use crate::state::{AppState, Backpressure, RateLimitStats, ServerConfig, WriteGate, WriteMode};
+use crate::webhooks::{
+    outbox::{outbox_worker, OutboxConfig},
+    rules,
+};
 
 /// Start xtrace server (blocks until shutdown signal)
 pub async fn run_server(config: ServerConfig) -> anyhow::Result<()> {
+    let addr: SocketAddr = config.bind_addr.parse()?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    serve(config, listener).await
+}
+
+/// Like [`run_server`], on an already bound listener (`bind_addr` is not
+/// used), e.g. one on port 0 in tests.
+pub async fn serve(config: ServerConfig, listener: tokio::net::TcpListener) -> anyhow::Result<()> {
+    let ingest_workers = config.ingest_workers.max(1);
+    // Each ingest worker holds a connection for its transactions.
     let pool = PgPoolOptions::new()
-        .max_connections(20)
+        .max_connections(
+            config
+                .db_max_connections
+                .unwrap_or(20 + ingest_workers as u32),
+        )
+        .min_connections(config.db_min_connections)
+        .acquire_timeout(std::time::Duration::from_secs(
+            config.db_acquire_timeout_secs,
+        ))
         .connect(&config.database_url)
         .await?;
 
     sqlx::migrate!("./migrations").run(&pool).await?;
 
-    let (ingest_tx, ingest_rx) = mpsc::channel::<BatchIngestRequest>(1000);
+    let partition_interval =
+        PartitionInterval::parse(&config.partition_interval).map_err(|e| anyhow::anyhow!(e))?;
+    let created = partitions::ensure(&pool, partition_interval).await?;
+    if created > 0 {
+        tracing::info!(created, "created trace partitions");
+    }
+
+    let interrupted = jobs::fail_interrupted(&pool).await?;
+    if interrupted > 0 {
+        tracing::warn!(
+            count = interrupted,
+            "marked interrupted admin jobs as failed"
+        );
+    }
+
+    let (ingest_queue, ingest_rxs) = IngestQueue::new(ingest_workers, config.ingest_queue_capacity);
     let (metrics_tx, metrics_rx) = mpsc::channel::<MetricsBatchRequest>(5000);
 
     let qps = config.rate_limit_qps;
     let burst = config.rate_limit_burst;
     let query_limiter = AppState::build_limiter(qps, burst);
     let rate_limit_stats = Arc::new(RateLimitStats::new());
+    let ingest_rate_limiter = (config.ingest_rate_limit_qps > 0).then(|| {
+        let burst = match config.ingest_rate_limit_burst {
+            0 => config.ingest_rate_limit_qps.saturating_mul(2),
+            b => b,
+        };
+        tracing::info!(
+            "ingest rate limit: {} qps, burst {}",
+            config.ingest_rate_limit_qps,
+            burst
+        );
+        AppState::build_limiter(config.ingest_rate_limit_qps, burst)
+    });
+    // Burst defaults to ten seconds' worth of budget.
+    let ingest_burst = match config.ingest_cost_burst {
+        0 => config.ingest_cost_per_sec.saturating_mul(10),
+        b => b,
+    };
+    let ingest_limiter = IngestCostLimiter::new(
+        config.ingest_cost_per_sec,
+        ingest_burst,
+        config.ingest_cost_bytes_per_unit,
+    )
+    .map(Arc::new);
+    if let Some(limiter) = &ingest_limiter {
+        tracing::info!(
+            "ingest cost limit: {} units/s, burst {}, {} bytes/unit",
+            limiter.units_per_sec(),
+            limiter.burst(),
+            limiter.bytes_per_unit()
+        );
+    }
+
+    let effective_config = Arc::new(effective_config(&config, ingest_limiter.as_deref()));
+
+    let conflict_policies = Arc::new(
+        ConflictPolicies::parse(
+            config.conflict_policy.as_deref(),
+            config.project_conflict_policies.as_deref(),
+        )
+        .map_err(|e| anyhow::anyhow!(e))?,
+    );
+    let sampling = Arc::new(
+        SamplingPolicy::parse(
+            config.sample_rate,
+            config.project_sample_rates.as_deref(),
+            config.sample_keep_errors,
+        )
+        .map_err(|e| anyhow::anyhow!(e))?,
+    );
+    let retention = Arc::new(
+        RetentionPolicy::parse(
+            config.retention_days,
+            config.project_retention_days.as_deref(),
+        )
+        .map_err(|e| anyhow::anyhow!(e))?,
+    );
+
+    projects::register(&pool, std::slice::from_ref(&config.default_project_id)).await?;
+
+    let mut static_keys = KeySet::default();
+    static_keys.add_bearer(config.api_bearer_token, None, KeyAccess::Full);
+    if let (Some(public_key), Some(secret_key)) =
+        (config.langfuse_public_key, config.langfuse_secret_key)
+    {
+        static_keys.add_basic(public_key, secret_key, None, KeyAccess::Full);
+    }
+    if let Some(token) = config.ingest_token {
+        static_keys.add_bearer(token, None, KeyAccess::Ingest);
+    }
+    if let Some(token) = config.read_token {
+        static_keys.add_bearer(token, None, KeyAccess::Read);
+    }
+    let keys = Arc::new(KeyStore::new(static_keys, config.keys_file.map(Into::into)));
+
+    // The worker also purges expired payloads while recording is off.
+    let (raw_ingest_tx, raw_ingest_rx) = mpsc::channel(RAW_INGEST_QUEUE);
+    tokio::spawn(raw_ingest_worker(pool.clone(), raw_ingest_rx));
+    let raw_ingest = (config.raw_ingest_ttl_secs > 0).then(|| {
+        Arc::new(RawIngestRecorder::new(
+            raw_ingest_tx,
+            std::time::Duration::from_secs(config.raw_ingest_ttl_secs),
+            config.raw_ingest_max_body_bytes,
+        ))
+    });
+    // Fail fast on a bad keys file; later reload errors keep the previous keys.
+    keys.reload(&pool).await?;
+
+    let (wal, wal_replay) = match config.wal_dir.as_deref() {
+        Some(dir) => {
+            let (wal, replay) = Wal::open(dir)
+                .map_err(|e| anyhow::anyhow!("failed to open write-ahead log in {dir}: {e}"))?;
+            (Some(Arc::new(wal)), replay)
+        }
+        None => (None, Vec::new()),
+    };
+
+    let mailer = match config.smtp_url.as_deref() {
+        Some(url) => Some(Arc::new(
+            Mailer::new(url, &config.smtp_from).map_err(|e| anyhow::anyhow!(e))?,
+        )),
+        None => None,
+    };
+
+    let offloader = match config.offload_s3_bucket.clone() {
+        Some(bucket) => {
+            let http = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(30))
+                .build()?;
+            let s3 = S3Config {
+                endpoint: config.offload_s3_endpoint.clone().unwrap_or_else(|| {
+                    format!("https://s3.{}.amazonaws.com", config.offload_s3_region)
+                }),
+                bucket,
+                region: config.offload_s3_region.clone(),
+                access_key: config.offload_s3_access_key.clone().unwrap_or_default(),
+                secret_key: config.offload_s3_secret_key.clone().unwrap_or_default(),
+            };
+            let client = S3Client::new(http, s3).map_err(|e| anyhow::anyhow!(e))?;
+            Some(Arc::new(Offloader::new(
+                client,
+                config.offload_s3_prefix.clone(),
+                config.offload_threshold_bytes,
+                config.offload_preview_bytes,
+            )))
+        }
+        None => None,
+    };
+
+    let dedup_ttl = (config.ingest_dedup_ttl_secs > 0)
+        .then(|| std::time::Duration::from_secs(config.ingest_dedup_ttl_secs));
+    if let Some(ttl) = dedup_ttl {
+        tokio::spawn(dedup_purge_worker(pool.clone(), ttl));
+    }
 
     let state = AppState {
         pool,
-        api_bearer_token: Arc::from(config.api_bearer_token),
-        langfuse_public_key: config.langfuse_public_key.map(Arc::from),
-        langfuse_secret_key: config.langfuse_secret_key.map(Arc::from),
+        keys,
         default_project_id: Arc::from(config.default_project_id),
-        ingest_tx,
+        ingest_queue,
         metrics_tx,
         query_limiter,
         rate_limit_stats,
         rate_limit_qps: qps,
         rate_limit_burst: burst,
+        ingest_rate_limiter,
+        ingest_limiter,
+        conflict_policies: conflict_policies.clone(),
         allow_unauthenticated_compat: config.allow_unauthenticated_compat,
+        api_versions: Arc::new(ApiVersionPolicy {
+            v1: VersionLifecycle {
+                deprecated_at: config.api_v1_deprecated_at,
+                sunset_at: config.api_v1_sunset_at,
+            },
+        }),
+        write_gate: Arc::new(WriteGate::new(
+            if config.read_only {
+                WriteMode::ReadOnly
+            } else {
+                WriteMode::Normal
+            },
+            config.maintenance_retry_after_secs,
+        )),
+        backpressure: Backpressure {
+            enqueue_timeout: std::time::Duration::from_millis(config.ingest_enqueue_timeout_ms),
+            retry_after_secs: config.ingest_retry_after_secs,
+        },
+        raw_ingest,
+        wal: wal.clone(),
+        dedup_ttl,
+        prices: Arc::new(PriceRegistry::default()),
+        telemetry: Arc::default(),
+        effective_config,
+        retention,
+        mailer,
+        quotas: Arc::default(),
+        offloader: offloader.clone(),
+        query_windows: QueryWindows::from_secs(
+            config.default_query_window_secs,
+            config.max_query_range_secs,
+        ),
+        batch_get_max_traces: config.batch_get_max_traces,
+    };
+
+    let outbox = OutboxConfig {
+        webhook_url: config.webhook_url.map(Arc::from),
+        max_age: chrono::Duration::seconds(config.webhook_max_age_secs),
+    };
+
+    let model_aliases = match config.model_aliases.as_deref() {
+        Some(spec) => Some(ModelAliases::parse(spec).map_err(|e| anyhow::anyhow!(e))?),
+        None => None,
+    }
+    .filter(|a| !a.is_empty())
+    .map(Arc::new);
+
+    let ingest_settings = IngestSettings {
+        default_project_id: state.default_project_id.clone(),
+        outbox: outbox.clone(),
+        secret_scanner: config
+            .secret_scanning
+            .then(|| Arc::new(SecretScanner::new())),
+        offloader,
+        model_aliases,
+        prices: state.prices.clone(),
+        late_write_mode: if config.strict_trace_completion {
+            LateWriteMode::Strict
+        } else {
+            LateWriteMode::Lenient
+        },
+        conflict_policies,
+        sampling,
+        wal,
+        dedup_ttl,
+        telemetry: state.telemetry.clone(),
     };
 
-    tokio::spawn(ingest_worker(
+    let batching = IngestBatching {
+        max_batches: config.ingest_max_batches.max(1),
+        window: std::time::Duration::from_millis(config.ingest_batch_window_ms),
+    };
+    for ingest_rx in ingest_rxs {
+        tokio::spawn(ingest_worker(
+            state.pool.clone(),
+            ingest_settings.clone(),
+            batching,
+            state.write_gate.clone(),
+            ingest_rx,
+        ));
+    }
+    // Payloads accepted by the previous run go first; the workers are running,
+    // so this waits for queue space rather than failing.
+    state.write_gate.reserve_replayed(wal_replay.len() as u64);
+    let replayed_at = std::time::Instant::now();
+    state
+        .telemetry
+        .record_accepted(replayed_at, wal_replay.len() as u64);
+    for mut payload in wal_replay {
+        payload.accepted_at = Some(replayed_at);
+        state.ingest_queue.send(payload).await?;
+    }
+
+    if let Some(brokers) = config.kafka_brokers {
+        #[cfg(feature = "kafka")]
+        {
+            let consumer = kafka::consumer(&KafkaSettings {
+                brokers,
+                topic: config.kafka_topic,
+                group_id: config.kafka_group_id,
+            })
+            .map_err(|e| anyhow::anyhow!("failed to start the Kafka consumer: {e}"))?;
+            tokio::spawn(kafka::kafka_ingest_worker(state.clone(), consumer));
+        }
+        #[cfg(not(feature = "kafka"))]
+        anyhow::bail!(
+            "XTRACE_KAFKA_BROKERS is set ({brokers}), but xtrace was built without the `kafka` feature"
+        );
+    }
+
+    tokio::spawn(session_reconcile_worker(state.pool.clone()));
+
+    tokio::spawn(key_reload_worker(
+        state.keys.clone(),
         state.pool.clone(),
-        state.default_project_id.clone(),
-        ingest_rx,
+        std::time::Duration::from_secs(config.keys_reload_secs),
     ));
 
+    // Webhook rules can add delivery targets at any time, so the worker runs
+    // even without `XTRACE_WEBHOOK_URL`.
+    tokio::spawn(outbox_worker(state.pool.clone(), outbox));
+
     tokio::spawn(metrics_worker(
         state.pool.clone(),
         state.default_project_id.clone(),
+        state.write_gate.clone(),
+        state.telemetry.clone(),
         metrics_rx,
     ));
+    if config.metrics_rollup_after_secs > 0 {
+        let secs = |secs: u64| chrono::Duration::seconds(secs.min(i64::MAX as u64 / 1000) as i64);
+        tokio::spawn(metrics_rollup_worker(
+            state.pool.clone(),
+            state.write_gate.clone(),
+            RollupSettings {
+                minute_after: secs(config.metrics_rollup_after_secs),
+                hour_after: (config.metrics_hourly_rollup_after_secs > 0)
+                    .then(|| secs(config.metrics_hourly_rollup_after_secs)),
+                interval: std::time::Duration::from_secs(
+                    config.metrics_rollup_interval_secs.max(1),
+                ),
+            },
+        ));
+    }
+
+    if config.alert_interval_secs > 0 {
+        tokio::spawn(alert_worker(
+            state.pool.clone(),
+            state.write_gate.clone(),
+            state.mailer.clone(),
+            std::time::Duration::from_secs(config.alert_interval_secs),
+        ));
+    }
+
+    tokio::spawn(quotas::quota_worker(
+        state.pool.clone(),
+        state.quotas.clone(),
+        std::time::Duration::from_secs(config.quota_interval_secs.max(1)),
+    ));
+
+    tokio::spawn(partitions::partition_worker(
+        state.pool.clone(),
+        partition_interval,
+    ));
+
+    if state.retention.is_enabled() {
+        tokio::spawn(retention_worker(
+            state.clone(),
+            std::time::Duration::from_secs(config.retention_interval_secs.max(1)),
+        ));
+    }
+
+    let tls = match (config.tls_cert, config.tls_key) {
+        (Some(cert), Some(key)) => Some(TlsFiles {
+            cert,
+            key,
+            client_ca: config.tls_client_ca,
+        }),
+        (None, None) if config.tls_client_ca.is_none() => None,
+        _ => anyhow::bail!(
+            "XTRACE_TLS_CERT and XTRACE_TLS_KEY must be set together (XTRACE_TLS_CLIENT_CA needs both)"
+        ),
+    };
+    let has_client_ca = tls.as_ref().is_some_and(|t| t.client_ca.is_some());
+    let client_cert_mode = |var: &str, spec: Option<&str>| {
+        let mode = match spec {
+            Some(spec) => ClientCertMode::parse(spec).map_err(|e| anyhow::anyhow!("{var}: {e}"))?,
+            None => ClientCertMode::Off,
+        };
+        if mode != ClientCertMode::Off && !has_client_ca {
+            anyhow::bail!("{var} requires XTRACE_TLS_CLIENT_CA");
+        }
+        anyhow::Ok(mode)
+    };
+    let ingest_client_cert = client_cert_mode(
+        "XTRACE_INGEST_CLIENT_CERT",
+        config.ingest_client_cert.as_deref(),
+    )?;
+    let query_client_cert = client_cert_mode(
+        "XTRACE_QUERY_CLIENT_CERT",
+        config.query_client_cert.as_deref(),
+    )?;
+    let admin_client_cert = client_cert_mode(
+        "XTRACE_ADMIN_CLIENT_CERT",
+        config.admin_client_cert.as_deref(),
+    )?;
+    let allowlist = |spec: Option<&str>| {
+        spec.map(IpAllowlist::parse)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!(e))
+    };
+    let trusted_proxies =
+        Arc::new(allowlist(config.trusted_proxies.as_deref())?.unwrap_or_default());
+    let query_policy = Arc::new(GroupPolicy::new(
+        RouteGroup::Query,
+        allowlist(config.query_allowed_ips.as_deref())?,
+        trusted_proxies.clone(),
+        query_client_cert,
+    ));
+    let ingest_policy = Arc::new(GroupPolicy::new(
+        RouteGroup::Ingest,
+        allowlist(config.ingest_allowed_ips.as_deref())?,
+        trusted_proxies.clone(),
+        ingest_client_cert,
+    ));
+    let admin_policy = Arc::new(GroupPolicy::new(
+        RouteGroup::Admin,
+        allowlist(config.admin_allowed_ips.as_deref())?,
+        trusted_proxies,
+        admin_client_cert,
+    ));
 
-    // Query routes — apply both auth and per-token rate limiting.
-    let query_routes = Router::new()
-        .route("/api/public/metrics/daily", get(metrics::get_metrics_daily))
-        .route("/api/public/metrics/query", get(metrics::get_metrics_query))
-        .route("/api/public/metrics/names", get(metrics::get_metrics_names))
-        .route("/api/public/traces", get(traces::get_traces))
-        .route("/api/public/traces/:traceId", get(traces::get_trace))
-        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit));
+    // Public query routes — apply both auth and per-token rate limiting; not
+    // for ingest-only keys.
+    let public_query_routes = Router::new()
+        .route("/metrics", get(analytics::get_analytics))
+        .route("/metrics/daily", get(metrics::get_metrics_daily))
+        .route("/metrics/activity", get(metrics::get_metrics_activity))
+        .route("/metrics/users", get(usage::get_user_usage))
+        .route("/metrics/models", get(usage::get_model_usage))
+        .route("/metrics/query", get(metrics::get_metrics_query))
+        .route("/metrics/names", get(metrics::get_metrics_names))
+        .route("/traces", get(traces::get_traces))
+        .route("/traces/count", get(traces::count_traces))
+        .route("/traces/batch-get", post(traces::get_many_traces))
+        .route("/traces/get-many", post(traces::get_many_traces))
+        .route(
+            "/traces/:traceId",
+            get(traces::get_trace).head(traces::head_trace),
+        )
+        .route(
+            "/traces/:traceId/observations",
+            get(traces::get_trace_observations),
+        )
+        .route("/traces/:traceId/tree", get(trace_tree::get_trace_tree))
+        .route("/traces/:traceId/wait", get(wait_for_trace))
+        .route("/observations", get(observations::get_observations))
+        .route("/observations/count", get(observations::count_observations))
+        .route(
+            "/observations/:observationId",
+            get(observations::get_observation),
+        )
+        .route("/scores", get(scores::get_scores))
+        .route("/scores/metrics", get(scores::get_score_metrics))
+        .route("/scores/:scoreId", get(scores::get_score))
+        .route("/models", get(models::get_models))
+        .route("/environments", get(environments::get_environments))
+        .route("/tags", get(tags::get_tags))
+        .route("/users", get(users::get_users))
+        .route("/users/:userId", get(users::get_user))
+        .route("/models/:modelId", get(models::get_model))
+        .route("/service-map", get(get_service_map))
+        .route("/meta/columns", get(meta::get_column_stats))
+        .route("/sessions", get(sessions::get_sessions))
+        .route("/sessions/:sessionId", get(sessions::get_session))
+        .route("/exports", get(export::get_exports))
+        .route("/exports/:exportId", get(export::get_export))
+        .route(
+            "/exports/:exportId/download",
+            get(export::get_export_download),
+        )
+        .route_layer(middleware::from_fn(require_read))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(state.clone(), api_version));
 
-    // Write / compat routes — auth only, no rate limit (channel backpressure applies).
+    // Public compat and write routes — auth and the optional per-key write
+    // rate limit. Writes are not for read-only keys; exports read data, so
+    // they are not for ingest-only keys.
+    let public_write_routes = Router::new()
+        .route("/otel/v1/traces", post(otlp::post_otel_traces))
+        .route("/ingestion", post(langfuse::post_ingestion))
+        .route(
+            "/traces/:traceId",
+            delete(delete_trace).patch(trace_update::patch_trace),
+        )
+        .route("/traces/delete", post(post_delete_traces))
+        .route("/traces/:traceId/complete", post(post_trace_complete))
+        .route("/scores", post(scores::post_score))
+        .route("/models", post(models::post_model))
+        .route(
+            "/models/:modelId",
+            put(models::put_model).delete(models::delete_model),
+        )
+        .route_layer(middleware::from_fn(require_write));
+    let public_compat_routes = Router::new()
+        .route("/projects", get(get_projects))
+        .route(
+            "/exports",
+            post(export::post_export).route_layer(middleware::from_fn(require_read)),
+        )
+        .route(
+            "/exports/:exportId",
+            delete(export::delete_export).route_layer(middleware::from_fn(require_read)),
+        )
+        .merge(public_write_routes)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            ingest_rate_limit,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), api_version));
+
+    // Served at `/api/public` and the explicit version alias `/api/v1`.
+    let public_routes = Router::new()
+        .merge(guarded(public_query_routes, &state, &query_policy))
+        .merge(guarded(public_compat_routes, &state, &ingest_policy));
+
+    // Write routes — auth and the optional per-key write rate limit; trace
+    // ingest is also charged against the per-key cost budget in the handlers
+    // (channel backpressure applies as well). Not for read-only keys.
     let write_routes = Router::new()
         .route("/v1/l/batch", post(post_batch))
         .route("/v1/metrics/batch", post(post_metrics_batch))
-        .route("/api/public/projects", get(get_projects))
-        .route("/api/public/otel/v1/traces", post(otlp::post_otel_traces));
+        .route_layer(middleware::from_fn(require_write))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            ingest_rate_limit,
+        ));
+
+    // Admin routes — instance-wide keys only.
+    let admin_routes = Router::new()
+        .route("/api/admin/mode", get(get_write_mode).put(put_write_mode))
+        .route("/api/admin/config", get(get_config))
+        .route("/api/admin/stats", get(get_stats))
+        .route("/api/admin/traces/:traceId/raw", get(raw::get_trace_raw))
+        .route(
+            "/api/admin/failed-events",
+            get(dead_letter::get_failed_events),
+        )
+        .route(
+            "/api/admin/failed-events/:id",
+            delete(dead_letter::delete_failed_event),
+        )
+        .route(
+            "/api/admin/failed-events/:id/replay",
+            post(dead_letter::replay_failed_event),
+        )
+        .route("/api/admin/jobs", get(get_jobs).post(post_job))
+        .route("/api/admin/jobs/:jobId", get(get_job))
+        .route("/api/admin/jobs/:jobId/events", get(get_job_events))
+        .route(
+            "/api/admin/alerts",
+            get(alerts::get_alert_rules).post(alerts::post_alert_rule),
+        )
+        .route(
+            "/api/admin/alerts/:ruleId",
+            get(alerts::get_alert_rule)
+                .put(alerts::put_alert_rule)
+                .delete(alerts::delete_alert_rule),
+        )
+        .route("/api/admin/quotas", get(quotas::get_quotas))
+        .route(
+            "/api/admin/quotas/:projectId",
+            get(quotas::get_quota)
+                .put(quotas::put_quota)
+                .delete(quotas::delete_quota),
+        )
+        .route(
+            "/api/admin/webhooks",
+            get(rules::get_webhook_rules).post(rules::post_webhook_rule),
+        )
+        .route(
+            "/api/admin/webhooks/:ruleId",
+            get(rules::get_webhook_rule)
+                .put(rules::put_webhook_rule)
+                .delete(rules::delete_webhook_rule),
+        )
+        .route_layer(middleware::from_fn(admin_only));
 
     let protected_routes = Router::new()
-        .merge(query_routes)
-        .merge(write_routes)
-        .route_layer(middleware::from_fn_with_state(state.clone(), auth));
+        .nest("/api/public", public_routes.clone())
+        .nest("/api/v1", public_routes)
+        .merge(guarded(write_routes, &state, &ingest_policy))
+        .merge(guarded(admin_routes, &state, &admin_policy));
 
-    let addr: SocketAddr = config.bind_addr.parse()?;
     tracing::info!(
         "listening on {} (rate_limit: {} qps, burst {})",
-        addr,
+        listener.local_addr()?,
         qps,
         burst
     );
 
     let max_body = config.max_request_body_bytes;
+    let otlp_grpc_addr: Option<SocketAddr> = config
+        .otlp_grpc_addr
+        .as_deref()
+        .map(str::parse)
+        .transpose()?;
+    let otlp_grpc = match otlp_grpc_addr {
+        Some(addr) => {
+            tracing::info!("OTLP/gRPC listening on {}", addr);
+            let mut server = GrpcServer::builder();
+            if let Some(tls) = &tls {
+                server = server.tls_config(tls.grpc_config().map_err(|e| anyhow::anyhow!(e))?)?;
+            }
+            Some(
+                server
+                    .add_service(OtlpTraceService::server(
+                        state.clone(),
+                        ingest_policy.clone(),
+                        max_body,
+                    ))
+                    .serve_with_shutdown(addr, shutdown_signal()),
+            )
+        }
+        None => None,
+    };
+
+    // Shared traces — no credentials, but the query allowlist and rate limit.
+    let shared_routes = Router::new()
+        .route(
+            "/api/public/shared/traces/:traceId",
+            get(sharing::get_shared_trace),
+        )
+        .route(
+            "/api/v1/shared/traces/:traceId",
+            get(sharing::get_shared_trace),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(
+            query_policy.clone(),
+            network_policy,
+        ));
+
+    // The web UI loads without credentials; its API calls authenticate.
+    #[cfg(feature = "ui")]
+    let shared_routes = shared_routes.merge(crate::ui::routes().route_layer(
+        middleware::from_fn_with_state(query_policy.clone(), network_policy),
+    ));
+
     let app = Router::new()
         .route("/healthz", get(healthz))
         .route("/readyz", get(readyz))
         .route("/api/internal/rate_limit_stats", get(get_rate_limit_stats))
+        .merge(
+            Router::new()
+                .route("/metrics", get(get_metrics))
+                .route_layer(middleware::from_fn_with_state(
+                    admin_policy.clone(),
+                    network_policy,
+                )),
+        )
+        .merge(shared_routes)
         .merge(protected_routes)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_requests,
+        ))
         .layer(DefaultBodyLimit::max(max_body))
         .with_state(state)
         .layer(TraceLayer::new_for_http());
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let http = async move {
+        match tls {
+            Some(tls) => {
+                let config = tls.server_config().map_err(|e| anyhow::anyhow!(e))?;
+                tracing::info!(
+                    "serving TLS (client certificates: {})",
+                    if tls.client_ca.is_some() {
+                        "verified"
+                    } else {
+                        "not requested"
+                    }
+                );
+                serve_tls(listener, app, config, shutdown_signal()).await?
+            }
+            None => {
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown_signal())
+                .await?
+            }
+        }
+        anyhow::Ok(())
+    };
+    match otlp_grpc {
+        Some(grpc) => {
+            tokio::try_join!(http, async { grpc.await.map_err(anyhow::Error::from) })?;
+        }
+        None => http.await?,
+    }
 
     Ok(())
 }
 
+/// Applies a route group's network policy, then authenticates.
+fn guarded(
+    routes: Router<AppState>,
+    state: &AppState,
+    policy: &Arc<GroupPolicy>,
+) -> Router<AppState> {
+    routes
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth))
+        .route_layer(middleware::from_fn_with_state(
+            policy.clone(),
+            network_policy,
+        ))
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
@@ -0,0 +1,417 @@
+//! `GET /api/public/metrics`: Langfuse v2 style analytics. The `query`
+//! parameter is a JSON object naming a view, dimensions to group by, metrics
+//! (a measure and an aggregation), filters and an optional time dimension;
+//! it is compiled to a single aggregate query.
+//!
+//! ```json
+//! {
+//!   "view": "observations",
+//!   "dimensions": [{"field": "model"}],
+//!   "metrics": [{"measure": "totalCost", "aggregation": "sum"}, {"measure": "latency", "aggregation": "p95"}],
+//!   "filters": [{"type": "string", "column": "type", "operator": "=", "value": "GENERATION"}],
+//!   "timeDimension": {"granularity": "day"},
+//!   "fromTimestamp": "2026-01-01T00:00:00Z",
+//!   "toTimestamp": "2026-02-01T00:00:00Z"
+//! }
+//! ```
+//!
+//! Rows hold the dimension fields, `time_dimension` (the bucket start, UTC)
+//! and one `<aggregation>_<measure>` value per metric. Every name is checked
+//! against the tables below, so only bound values reach the SQL as data.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use sqlx::QueryBuilder;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        error::ApiError,
+        trace_filter::{Column, ColumnKind, Filter, RawCondition, TRACE_COLUMNS},
+    },
+    state::AppState,
+};
+
+const DEFAULT_ROW_LIMIT: i64 = 100;
+const MAX_ROW_LIMIT: i64 = 1000;
+
+/// A queryable table: its rows, their time, and what can be asked of them.
+struct View {
+    from: &'static str,
+    project_column: &'static str,
+    time_column: &'static str,
+    /// API name and SQL expression.
+    dimensions: &'static [(&'static str, &'static str)],
+    measures: &'static [(&'static str, &'static str)],
+    filters: &'static [Column],
+}
+
+const TRACES: View = View {
+    from: "traces t",
+    project_column: "t.project_id",
+    time_column: "t.\"timestamp\"",
+    dimensions: &[
+        ("name", "t.name"),
+        ("userId", "t.user_id"),
+        ("sessionId", "t.session_id"),
+        ("release", "t.release"),
+        ("version", "t.version"),
+        ("environment", "t.environment"),
+        ("serviceName", "t.service_name"),
+    ],
+    measures: &[
+        ("count", "1"),
+        ("latency", "t.latency"),
+        ("totalCost", "t.total_cost"),
+        (
+            "totalTokens",
+            "(SELECT SUM(o.total_tokens) FROM observations o WHERE o.trace_id = t.id)",
+        ),
+        (
+            "observationsCount",
+            "(SELECT COUNT(*) FROM observations o WHERE o.trace_id = t.id)",
+        ),
+    ],
+    filters: TRACE_COLUMNS,
+};
+
+const OBSERVATIONS: View = View {
+    from: "observations o JOIN traces t ON t.id = o.trace_id",
+    project_column: "o.project_id",
+    time_column: "o.start_time",
+    dimensions: &[
+        ("name", "o.name"),
+        ("type", "o.type"),
+        ("model", "o.model"),
+        ("provider", "o.provider"),
+        ("level", "o.level"),
+        ("environment", "o.environment"),
+        ("promptName", "o.prompt_name"),
+        ("traceName", "t.name"),
+        ("userId", "t.user_id"),
+    ],
+    measures: &[
+        ("count", "1"),
+        ("latency", "EXTRACT(EPOCH FROM o.end_time - o.start_time)"),
+        (
+            "timeToFirstToken",
+            "EXTRACT(EPOCH FROM o.completion_start_time - o.start_time)",
+        ),
+        ("inputTokens", "o.prompt_tokens"),
+        ("outputTokens", "o.completion_tokens"),
+        ("totalTokens", "o.total_tokens"),
+        ("inputCost", "o.calculated_input_cost"),
+        ("outputCost", "o.calculated_output_cost"),
+        ("totalCost", "o.calculated_total_cost"),
+    ],
+    filters: &[
+        ("id", "o.id::TEXT", ColumnKind::String),
+        ("traceId", "o.trace_id::TEXT", ColumnKind::String),
+        ("name", "o.name", ColumnKind::String),
+        ("type", "o.type", ColumnKind::String),
+        ("model", "o.model", ColumnKind::String),
+        ("provider", "o.provider", ColumnKind::String),
+        ("level", "o.level", ColumnKind::String),
+        ("environment", "o.environment", ColumnKind::String),
+        ("statusMessage", "o.status_message", ColumnKind::String),
+        ("promptName", "o.prompt_name", ColumnKind::String),
+        ("traceName", "t.name", ColumnKind::String),
+        ("userId", "t.user_id", ColumnKind::String),
+        ("sessionId", "t.session_id", ColumnKind::String),
+        ("startTime", "o.start_time", ColumnKind::Datetime),
+        ("endTime", "o.end_time", ColumnKind::Datetime),
+        ("totalTokens", "o.total_tokens", ColumnKind::Number),
+        ("totalCost", "o.calculated_total_cost", ColumnKind::Number),
+        ("tags", "t.tags", ColumnKind::Array),
+        ("metadata", "o.metadata", ColumnKind::Object),
+    ],
+};
+
+const SCORES: View = View {
+    from: "scores s",
+    project_column: "s.project_id",
+    time_column: "s.\"timestamp\"",
+    dimensions: &[
+        ("name", "s.name"),
+        ("source", "s.source"),
+        ("dataType", "s.data_type"),
+        ("stringValue", "s.string_value"),
+        ("environment", "s.environment"),
+    ],
+    measures: &[("count", "1"), ("value", "s.value")],
+    filters: &[
+        ("traceId", "s.trace_id::TEXT", ColumnKind::String),
+        ("name", "s.name", ColumnKind::String),
+        ("source", "s.source", ColumnKind::String),
+        ("dataType", "s.data_type", ColumnKind::String),
+        ("stringValue", "s.string_value", ColumnKind::String),
+        ("environment", "s.environment", ColumnKind::String),
+        ("authorUserId", "s.author_user_id", ColumnKind::String),
+        ("value", "s.value", ColumnKind::Number),
+        ("timestamp", "s.\"timestamp\"", ColumnKind::Datetime),
+        ("metadata", "s.metadata", ColumnKind::Object),
+    ],
+};
+
+/// Aggregation name and SQL template (`{}` is the measure).
+const AGGREGATIONS: &[(&str, &str)] = &[
+    ("count", "COUNT({})"),
+    ("sum", "SUM({})"),
+    ("avg", "AVG({})"),
+    ("min", "MIN({})"),
+    ("max", "MAX({})"),
+    ("p50", "percentile_cont(0.5) WITHIN GROUP (ORDER BY {})"),
+    ("p75", "percentile_cont(0.75) WITHIN GROUP (ORDER BY {})"),
+    ("p90", "percentile_cont(0.9) WITHIN GROUP (ORDER BY {})"),
+    ("p95", "percentile_cont(0.95) WITHIN GROUP (ORDER BY {})"),
+    ("p99", "percentile_cont(0.99) WITHIN GROUP (ORDER BY {})"),
+];
+
+const GRANULARITIES: &[&str] = &["minute", "hour", "day", "week", "month"];
+
+const TIME_DIMENSION: &str = "time_dimension";
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AnalyticsParams {
+    query: String,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct AnalyticsQuery {
+    view: String,
+    #[serde(default)]
+    dimensions: Vec<Field>,
+    metrics: Vec<Metric>,
+    #[serde(default)]
+    filters: Vec<RawCondition>,
+    time_dimension: Option<TimeDimension>,
+    from_timestamp: DateTime<Utc>,
+    to_timestamp: DateTime<Utc>,
+    #[serde(default)]
+    order_by: Vec<OrderBy>,
+    #[serde(default)]
+    config: Config,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Field {
+    field: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Metric {
+    measure: String,
+    aggregation: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimeDimension {
+    granularity: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrderBy {
+    field: String,
+    #[serde(default = "default_direction")]
+    direction: String,
+}
+
+fn default_direction() -> String {
+    "asc".to_string()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    row_limit: Option<i64>,
+}
+
+fn lookup(
+    table: &[(&'static str, &'static str)],
+    name: &str,
+    what: &str,
+) -> Result<&'static str, ApiError> {
+    table
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, expr)| *expr)
+        .ok_or_else(|| {
+            let names: Vec<&str> = table.iter().map(|(n, _)| *n).collect();
+            ApiError::BadRequest(format!(
+                "unknown {what}: {name:?} (expected one of {})",
+                names.join(", ")
+            ))
+        })
+}
+
+/// `auto` picks the finest granularity that keeps a range to a few hundred
+/// buckets.
+fn granularity(name: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<&str, ApiError> {
+    if name == "auto" {
+        let hours = (to - from).num_hours();
+        return Ok(match hours {
+            ..=6 => "minute",
+            7..=168 => "hour",
+            169..=2160 => "day",
+            2161..=17520 => "week",
+            _ => "month",
+        });
+    }
+    GRANULARITIES
+        .iter()
+        .find(|g| **g == name)
+        .copied()
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "unknown granularity: {name:?} (expected auto, {})",
+                GRANULARITIES.join(", ")
+            ))
+        })
+}
+
+pub(crate) async fn get_analytics(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(params): Query<AnalyticsParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let q: AnalyticsQuery = serde_json::from_str(&params.query)
+        .map_err(|e| ApiError::BadRequest(format!("invalid query: {e}")))?;
+    let view = match q.view.as_str() {
+        "traces" => &TRACES,
+        "observations" => &OBSERVATIONS,
+        "scores" => &SCORES,
+        other => {
+            return Err(ApiError::BadRequest(format!(
+                "unknown view: {other:?} (expected traces, observations or scores)"
+            )))
+        }
+    };
+    if q.from_timestamp > q.to_timestamp {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp must be <= toTimestamp".to_string(),
+        ));
+    }
+    state.query_windows.check_range(
+        &scope,
+        q.from_timestamp,
+        q.to_timestamp,
+        params.ignore_range_limit,
+    )?;
+    if q.metrics.is_empty() {
+        return Err(ApiError::BadRequest(
+            "at least one metric is required".to_string(),
+        ));
+    }
+
+    // Output columns: alias and SQL expression.
+    let mut dimensions = Vec::new();
+    for d in &q.dimensions {
+        let expr = lookup(view.dimensions, &d.field, "dimension")?;
+        if !dimensions.iter().any(|(alias, _)| *alias == d.field) {
+            dimensions.push((d.field.clone(), expr.to_string()));
+        }
+    }
+    if let Some(time) = &q.time_dimension {
+        let granularity = granularity(&time.granularity, q.from_timestamp, q.to_timestamp)?;
+        dimensions.push((
+            TIME_DIMENSION.to_string(),
+            format!("date_trunc('{granularity}', {}, 'UTC')", view.time_column),
+        ));
+    }
+    let mut metrics = Vec::new();
+    for m in &q.metrics {
+        let measure = lookup(view.measures, &m.measure, "measure")?;
+        let template = lookup(AGGREGATIONS, &m.aggregation, "aggregation")?;
+        if m.measure == "count" && m.aggregation != "count" {
+            return Err(ApiError::BadRequest(
+                "the count measure only supports the count aggregation".to_string(),
+            ));
+        }
+        let expr = template.replace("{}", measure);
+        let expr = if m.aggregation == "count" {
+            expr
+        } else {
+            format!("({expr})::DOUBLE PRECISION")
+        };
+        metrics.push((format!("{}_{}", m.aggregation, m.measure), expr));
+    }
+
+    let mut order = Vec::new();
+    for o in &q.order_by {
+        if !dimensions
+            .iter()
+            .chain(&metrics)
+            .any(|(alias, _)| *alias == o.field)
+        {
+            return Err(ApiError::BadRequest(format!(
+                "orderBy field {:?} is not a dimension or metric of the query",
+                o.field
+            )));
+        }
+        let direction = match o.direction.to_ascii_lowercase().as_str() {
+            "asc" => "ASC",
+            "desc" => "DESC",
+            other => {
+                return Err(ApiError::BadRequest(format!(
+                    "invalid orderBy direction: {other:?} (expected asc or desc)"
+                )))
+            }
+        };
+        order.push(format!("\"{}\" {direction}", o.field));
+    }
+    if order.is_empty() {
+        order.push(match q.time_dimension {
+            Some(_) => format!("\"{TIME_DIMENSION}\" ASC"),
+            None => format!("\"{}\" DESC", metrics[0].0),
+        });
+    }
+
+    let filter = Filter::resolve(q.filters, view.filters).map_err(ApiError::BadRequest)?;
+    let row_limit = q
+        .config
+        .row_limit
+        .unwrap_or(DEFAULT_ROW_LIMIT)
+        .clamp(1, MAX_ROW_LIMIT);
+
+    let select = dimensions
+        .iter()
+        .chain(&metrics)
+        .map(|(alias, expr)| format!("{expr} AS \"{alias}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(format!(
+        "SELECT to_jsonb(r) FROM (SELECT {select} FROM {} WHERE {} = ",
+        view.from, view.project_column
+    ));
+    builder.push_bind(scope.project_id().to_string());
+    builder.push(format!(" AND {} >= ", view.time_column));
+    builder.push_bind(q.from_timestamp);
+    builder.push(format!(" AND {} <= ", view.time_column));
+    builder.push_bind(q.to_timestamp);
+    filter.push_sql(&mut builder);
+    if !dimensions.is_empty() {
+        let positions: Vec<String> = (1..=dimensions.len()).map(|i| i.to_string()).collect();
+        builder.push(format!(" GROUP BY {}", positions.join(", ")));
+    }
+    builder.push(format!(" ORDER BY {} LIMIT ", order.join(", ")));
+    builder.push_bind(row_limit);
+    builder.push(") r");
+
+    let data: Vec<JsonValue> = builder.build_query_scalar().fetch_all(&state.pool).await?;
+    Ok((StatusCode::OK, Json(json!({ "data": data }))))
+}
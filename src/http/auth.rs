@@ -8,12 +8,121 @@ use axum::{
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use base64::Engine;
 use chrono::Utc;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::{
-    http::common::ApiResponse,
-    state::{mask_client_key, AppState},
+    http::{
+        common::ApiResponse,
+        error::ApiError,
+        keys::{KeyAccess, KeyGrant},
+        network::CertAuthenticated,
+    },
+    state::{mask_client_key, AppState, KeyedRateLimiter},
 };
 
+/// Project a request acts on, resolved from its credentials by [`auth`] and
+/// stored in the request extensions.
+#[derive(Debug, Clone)]
+pub(crate) struct ProjectScope {
+    project_id: Arc<str>,
+    /// The key is bound to `project_id`; otherwise it is instance-wide and
+    /// `project_id` is `DEFAULT_PROJECT_ID`.
+    bound: bool,
+    /// See [`KeyGrant::key_id`]; `None` for requests admitted without a key.
+    key_id: Option<Arc<str>>,
+    /// `Full` for requests admitted without a key.
+    access: KeyAccess,
+}
+
+impl ProjectScope {
+    fn resolve(grant: Option<&KeyGrant>, default_project_id: &Arc<str>) -> Self {
+        let key_id = grant.map(|g| g.key_id.clone());
+        let access = grant.map(|g| g.access).unwrap_or_default();
+        match grant.and_then(|g| g.project_id.clone()) {
+            Some(project_id) => Self {
+                project_id,
+                bound: true,
+                key_id,
+                access,
+            },
+            None => Self {
+                project_id: default_project_id.clone(),
+                bound: false,
+                key_id,
+                access,
+            },
+        }
+    }
+
+    /// Rejects ingest-only keys.
+    pub(crate) fn check_read(&self) -> Result<(), ApiError> {
+        if self.access.can_read() {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(
+            "ingest-only keys cannot read data".to_string(),
+        ))
+    }
+
+    /// Rejects read-only keys.
+    pub(crate) fn check_write(&self) -> Result<(), ApiError> {
+        if self.access.can_write() {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(
+            "read-only keys cannot write data".to_string(),
+        ))
+    }
+
+    pub(crate) fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    pub(crate) fn project_id(&self) -> &str {
+        &self.project_id
+    }
+
+    pub(crate) fn is_bound(&self) -> bool {
+        self.bound
+    }
+
+    /// Fills in a missing ingest `projectId`. Bound keys may not write to
+    /// another project; instance-wide keys may.
+    pub(crate) fn claim(&self, project_id: &mut Option<String>) -> Result<(), ApiError> {
+        match project_id {
+            None => *project_id = Some(self.project_id.to_string()),
+            Some(p) if self.bound && *p != *self.project_id => {
+                return Err(ApiError::Forbidden(format!(
+                    "key is not allowed to write to project {p:?}"
+                )))
+            }
+            Some(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Rejects writes by a bound key that refer to another project's trace.
+    /// Traces not ingested yet pass.
+    pub(crate) async fn check_trace(&self, pool: &PgPool, trace_id: Uuid) -> Result<(), ApiError> {
+        if !self.bound {
+            return Ok(());
+        }
+        let project_id: Option<String> =
+            sqlx::query_scalar("SELECT project_id FROM traces WHERE id = $1")
+                .bind(trace_id)
+                .fetch_optional(pool)
+                .await?;
+        match project_id {
+            Some(p) if p != *self.project_id => Err(ApiError::Forbidden(
+                "trace belongs to another project".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
 enum AuthHeader {
     Bearer(String),
     Basic { username: String, password: String },
@@ -45,7 +154,7 @@ fn extract_auth(headers: &HeaderMap) -> Result<AuthHeader, ()> {
     Err(())
 }
 
-fn extract_client_key(headers: &HeaderMap) -> String {
+pub(crate) fn extract_client_key(headers: &HeaderMap) -> String {
     if let Ok(auth) = extract_auth(headers) {
         match auth {
             AuthHeader::Bearer(token) => return format!("bearer:{token}"),
@@ -58,36 +167,25 @@ fn extract_client_key(headers: &HeaderMap) -> String {
 pub(crate) async fn auth(
     State(state): State<AppState>,
     headers: HeaderMap,
-    request: axum::extract::Request,
+    mut request: axum::extract::Request,
     next: Next,
 ) -> impl IntoResponse {
-    let path = request.uri().path();
-    let is_langfuse_compat = matches!(path, "/api/public/projects" | "/api/public/otel/v1/traces");
-    let langfuse_auth_not_configured =
-        state.langfuse_public_key.is_none() && state.langfuse_secret_key.is_none();
-    let open_compat = state.allow_unauthenticated_compat && langfuse_auth_not_configured;
-
-    match extract_auth(&headers) {
-        Ok(AuthHeader::Bearer(token)) if token == state.api_bearer_token.as_ref() => {
-            next.run(request).await
-        }
-        Ok(AuthHeader::Basic { username, password })
-            if state
-                .langfuse_public_key
-                .as_deref()
-                .is_some_and(|k| k == username)
-                && state
-                    .langfuse_secret_key
-                    .as_deref()
-                    .is_some_and(|k| k == password) =>
-        {
-            next.run(request).await
-        }
-        Err(()) if is_langfuse_compat && open_compat => next.run(request).await,
-        Ok(AuthHeader::Basic { .. }) if is_langfuse_compat && open_compat => {
-            next.run(request).await
-        }
-        _ => (
+    let path = request
+        .extensions()
+        .get::<axum::extract::OriginalUri>()
+        .map_or(request.uri().path(), |u| u.0.path());
+    let is_langfuse_compat = matches!(
+        path,
+        "/api/public/projects"
+            | "/api/public/otel/v1/traces"
+            | "/api/public/ingestion"
+            | "/api/v1/projects"
+            | "/api/v1/otel/v1/traces"
+            | "/api/v1/ingestion"
+    );
+    let cert_authenticated = request.extensions().get::<CertAuthenticated>().is_some();
+    let Some(scope) = authenticate(&state, &headers, is_langfuse_compat, cert_authenticated) else {
+        return (
             StatusCode::UNAUTHORIZED,
             Json(ApiResponse::<serde_json::Value> {
                 message: "Unauthorized".to_string(),
@@ -95,19 +193,119 @@ pub(crate) async fn auth(
                 data: None,
             }),
         )
+            .into_response();
+    };
+
+    request.extensions_mut().insert(scope);
+    next.run(request).await
+}
+
+/// Resolves the credentials in `headers`; `None` when they are missing or
+/// unknown. `compat` endpoints (Langfuse / OTLP) also admit unknown Basic
+/// credentials or none at all when unauthenticated compat access is allowed.
+/// With `cert_authenticated` (see [`CertAuthenticated`]) a request without
+/// credentials is admitted as instance-wide.
+pub(crate) fn authenticate(
+    state: &AppState,
+    headers: &HeaderMap,
+    compat: bool,
+    cert_authenticated: bool,
+) -> Option<ProjectScope> {
+    let keys = state.keys.current();
+    let open_compat = state.allow_unauthenticated_compat && !keys.has_basic();
+
+    let auth_header = extract_auth(headers);
+    let granted = match &auth_header {
+        Ok(AuthHeader::Bearer(token)) => keys.bearer(token).map(Some),
+        Ok(AuthHeader::Basic { username, password }) => keys.basic(username, password).map(Some),
+        Err(()) => None,
+    };
+    let open = (compat && open_compat && !matches!(auth_header, Ok(AuthHeader::Bearer(_))))
+        || (cert_authenticated && !headers.contains_key(header::AUTHORIZATION));
+    let grant = match granted {
+        Some(grant) => grant,
+        None if open => None,
+        None => return None,
+    };
+    Some(ProjectScope::resolve(grant, &state.default_project_id))
+}
+
+/// Restricts a route to instance-wide keys with full access.
+pub(crate) async fn admin_only(request: axum::extract::Request, next: Next) -> impl IntoResponse {
+    match request.extensions().get::<ProjectScope>() {
+        Some(scope) if !scope.bound && scope.access == KeyAccess::Full => next.run(request).await,
+        Some(scope) if !scope.bound => ApiError::Forbidden(format!(
+            "{}-only keys cannot use admin endpoints",
+            scope.access.as_str()
+        ))
+        .into_response(),
+        _ => ApiError::Forbidden("project keys cannot use admin endpoints".to_string())
             .into_response(),
     }
 }
 
+/// Restricts a route to keys that may read (not ingest-only).
+pub(crate) async fn require_read(request: axum::extract::Request, next: Next) -> impl IntoResponse {
+    match request
+        .extensions()
+        .get::<ProjectScope>()
+        .map(ProjectScope::check_read)
+    {
+        Some(Err(err)) => err.into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+/// Restricts a route to keys that may write (not read-only).
+pub(crate) async fn require_write(
+    request: axum::extract::Request,
+    next: Next,
+) -> impl IntoResponse {
+    match request
+        .extensions()
+        .get::<ProjectScope>()
+        .map(ProjectScope::check_write)
+    {
+        Some(Err(err)) => err.into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+/// Per-key token bucket of the query routes (`RATE_LIMIT_QPS`).
 pub(crate) async fn rate_limit(
     State(state): State<AppState>,
     headers: HeaderMap,
     request: axum::extract::Request,
     next: Next,
 ) -> axum::response::Response {
-    let key = extract_client_key(&headers);
+    let limiter = state.query_limiter.clone();
+    check_rate_limit(&state, &limiter, &headers, request, next).await
+}
+
+/// Per-key token bucket of the write routes (`XTRACE_INGEST_RATE_LIMIT_QPS`);
+/// a no-op unless configured.
+pub(crate) async fn ingest_rate_limit(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: axum::extract::Request,
+    next: Next,
+) -> axum::response::Response {
+    match state.ingest_rate_limiter.clone() {
+        Some(limiter) => check_rate_limit(&state, &limiter, &headers, request, next).await,
+        None => next.run(request).await,
+    }
+}
+
+async fn check_rate_limit(
+    state: &AppState,
+    limiter: &KeyedRateLimiter,
+    headers: &HeaderMap,
+    request: axum::extract::Request,
+    next: Next,
+) -> axum::response::Response {
+    let key = extract_client_key(headers);
 
-    match state.query_limiter.check_key(&key) {
+    match limiter.check_key(&key) {
         Ok(_) => {
             state.rate_limit_stats.record_allowed();
             next.run(request).await
@@ -115,8 +313,7 @@ pub(crate) async fn rate_limit(
         Err(not_until) => {
             let masked = mask_client_key(&key);
             state.rate_limit_stats.record_rejected(&masked);
-            let wait =
-                not_until.wait_time_from(governor::clock::Clock::now(state.query_limiter.clock()));
+            let wait = not_until.wait_time_from(governor::clock::Clock::now(limiter.clock()));
             let retry_after_secs = wait.as_secs().max(1);
             let reset_at = Utc::now() + chrono::Duration::seconds(retry_after_secs as i64);
 
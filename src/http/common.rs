@@ -1,7 +1,7 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::Serialize;
 
-use crate::state::AppState;
+use crate::state::{AppState, WriteMode};
 
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
@@ -31,8 +31,16 @@ pub async fn healthz() -> impl IntoResponse {
     StatusCode::OK
 }
 
-/// Readiness probe: verifies PostgreSQL connectivity. Unauthenticated (for orchestrators).
+/// Readiness probe: verifies PostgreSQL connectivity and reports not ready while
+/// draining. Unauthenticated (for orchestrators).
 pub async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    if state.write_gate.mode() == WriteMode::Draining {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "draining" })),
+        )
+            .into_response();
+    }
     match sqlx::query_scalar::<_, i32>("SELECT 1")
         .fetch_one(&state.pool)
         .await
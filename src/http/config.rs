@@ -0,0 +1,189 @@
+//! `GET /api/admin/config`: the configuration a running instance uses.
+//!
+//! The settings are captured once at startup, after defaults and derived
+//! values are applied, with credentials replaced by `***`; state that changes
+//! at runtime (write mode, reloaded keys) is read per request.
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    http::ingest_limit::IngestCostLimiter,
+    state::{AppState, ServerConfig},
+};
+
+const REDACTED: &str = "***";
+
+fn redact(secret: Option<&str>) -> Option<&'static str> {
+    secret.map(|_| REDACTED)
+}
+
+/// `url` without its password and query parameter values.
+fn redact_url(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return REDACTED.to_string();
+    };
+    if parsed.password().is_some() {
+        let _ = parsed.set_password(Some(REDACTED));
+    }
+    let keys: Vec<String> = parsed.query_pairs().map(|(k, _)| k.into_owned()).collect();
+    if !keys.is_empty() {
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(keys.iter().map(|k| (k, REDACTED)));
+    }
+    parsed.to_string()
+}
+
+/// Redacted settings of `config`, grouped by concern. `ingest_limiter` is the
+/// limiter built from it, with the defaulted burst.
+pub(crate) fn effective_config(
+    config: &ServerConfig,
+    ingest_limiter: Option<&IngestCostLimiter>,
+) -> JsonValue {
+    json!({
+        "server": {
+            "bindAddr": config.bind_addr,
+            "otlpGrpcAddr": config.otlp_grpc_addr,
+            "databaseUrl": redact_url(&config.database_url),
+            "dbMaxConnections": config.db_max_connections,
+            "dbMinConnections": config.db_min_connections,
+            "dbAcquireTimeoutSecs": config.db_acquire_timeout_secs,
+            "defaultProjectId": config.default_project_id,
+            "maxRequestBodyBytes": config.max_request_body_bytes,
+            "readOnlyAtStartup": config.read_only,
+            "maintenanceRetryAfterSecs": config.maintenance_retry_after_secs,
+        },
+        "tls": {
+            "cert": config.tls_cert,
+            "key": config.tls_key,
+            "clientCa": config.tls_client_ca,
+            "ingestClientCert": config.ingest_client_cert,
+            "queryClientCert": config.query_client_cert,
+            "adminClientCert": config.admin_client_cert,
+        },
+        "network": {
+            "queryAllowedIps": config.query_allowed_ips,
+            "ingestAllowedIps": config.ingest_allowed_ips,
+            "adminAllowedIps": config.admin_allowed_ips,
+            "trustedProxies": config.trusted_proxies,
+        },
+        "auth": {
+            "apiBearerToken": REDACTED,
+            "langfusePublicKey": config.langfuse_public_key,
+            "langfuseSecretKey": redact(config.langfuse_secret_key.as_deref()),
+            "ingestToken": redact(config.ingest_token.as_deref()),
+            "readToken": redact(config.read_token.as_deref()),
+            "keysFile": config.keys_file,
+            "keysReloadSecs": config.keys_reload_secs,
+            "allowUnauthenticatedCompat": config.allow_unauthenticated_compat,
+        },
+        "limits": {
+            "rateLimitQps": config.rate_limit_qps,
+            "rateLimitBurst": config.rate_limit_burst,
+            "ingestRateLimitQps": config.ingest_rate_limit_qps,
+            "ingestRateLimitBurst": config.ingest_rate_limit_burst,
+            "ingestCost": ingest_limiter.map(|l| json!({
+                "unitsPerSec": l.units_per_sec(),
+                "burst": l.burst(),
+                "bytesPerUnit": l.bytes_per_unit(),
+            })),
+            "defaultQueryWindowSecs": config.default_query_window_secs,
+            "maxQueryRangeSecs": config.max_query_range_secs,
+            "batchGetMaxTraces": config.batch_get_max_traces,
+            "quotaIntervalSecs": config.quota_interval_secs,
+        },
+        "ingest": {
+            "queueCapacity": config.ingest_queue_capacity,
+            "enqueueTimeoutMs": config.ingest_enqueue_timeout_ms,
+            "retryAfterSecs": config.ingest_retry_after_secs,
+            "workers": config.ingest_workers.max(1),
+            "maxBatches": config.ingest_max_batches,
+            "batchWindowMs": config.ingest_batch_window_ms,
+            "walDir": config.wal_dir,
+            "dedupTtlSecs": config.ingest_dedup_ttl_secs,
+            "strictTraceCompletion": config.strict_trace_completion,
+            "conflictPolicy": config.conflict_policy,
+            "projectConflictPolicies": config.project_conflict_policies,
+            "sampling": {
+                "rate": config.sample_rate,
+                "projectRates": config.project_sample_rates,
+                "keepErrors": config.sample_keep_errors,
+            },
+            "modelAliases": config.model_aliases,
+            "secretScanning": config.secret_scanning,
+            "kafka": config.kafka_brokers.as_ref().map(|brokers| json!({
+                "brokers": brokers,
+                "topic": config.kafka_topic,
+                "groupId": config.kafka_group_id,
+            })),
+        },
+        "retention": {
+            "rawIngestTtlSecs": config.raw_ingest_ttl_secs,
+            "rawIngestMaxBodyBytes": config.raw_ingest_max_body_bytes,
+            "webhookMaxAgeSecs": config.webhook_max_age_secs,
+            "metricsRollupAfterSecs": config.metrics_rollup_after_secs,
+            "metricsHourlyRollupAfterSecs": config.metrics_hourly_rollup_after_secs,
+            "metricsRollupIntervalSecs": config.metrics_rollup_interval_secs,
+            "retentionDays": config.retention_days,
+            "projectRetentionDays": config.project_retention_days,
+            "retentionIntervalSecs": config.retention_interval_secs,
+            "partitionInterval": config.partition_interval,
+        },
+        "offload": config.offload_s3_bucket.as_ref().map(|bucket| json!({
+            "s3Bucket": bucket,
+            "s3Endpoint": config.offload_s3_endpoint,
+            "s3Region": config.offload_s3_region,
+            "s3AccessKey": redact(config.offload_s3_access_key.as_deref()),
+            "s3SecretKey": redact(config.offload_s3_secret_key.as_deref()),
+            "s3Prefix": config.offload_s3_prefix,
+            "thresholdBytes": config.offload_threshold_bytes,
+            "previewBytes": config.offload_preview_bytes,
+        })),
+        "webhooks": {
+            "url": config.webhook_url.as_deref().map(redact_url),
+        },
+        "alerts": {
+            "intervalSecs": config.alert_interval_secs,
+            "smtpUrl": config.smtp_url.as_deref().map(redact_url),
+            "smtpFrom": config.smtp_from,
+        },
+        "features": {
+            "kafka": cfg!(feature = "kafka"),
+        },
+        "apiVersions": {
+            "v1DeprecatedAt": config.api_v1_deprecated_at,
+            "v1SunsetAt": config.api_v1_sunset_at,
+        },
+    })
+}
+
+/// The startup settings plus the current runtime state.
+pub(crate) async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
+    let mut body = (*state.effective_config).clone();
+    body["runtime"] = json!({
+        "writeMode": state.write_gate.mode().as_str(),
+        "pendingWrites": state.write_gate.pending(),
+        "keys": state.keys.current().len(),
+    });
+    (StatusCode::OK, Json(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_lose_credentials() {
+        assert_eq!(
+            redact_url("postgres://xtrace:hunter2@db:5432/xtrace?sslmode=require"),
+            "postgres://xtrace:***@db:5432/xtrace?sslmode=***"
+        );
+        assert_eq!(
+            redact_url("https://hooks.example.com/in"),
+            "https://hooks.example.com/in"
+        );
+        assert_eq!(redact_url("not a url"), REDACTED);
+    }
+}
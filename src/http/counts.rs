@@ -0,0 +1,164 @@
+//! Count-only variants of the list endpoints (`/traces/count`,
+//! `/observations/count`), optionally grouped by one dimension.
+//!
+//! Counts are exact unless the filtered range is large: the planner's row
+//! estimate decides, and above [`EXACT_COUNT_LIMIT`] rows (or always, with
+//! `approximate=true`) the count runs over a `TABLESAMPLE SYSTEM` block sample
+//! sized to about [`SAMPLE_ROWS`] rows and is scaled up. Responses say whether
+//! the numbers are approximate.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+use crate::http::error::ApiError;
+
+/// Estimated rows above which `approximate=auto` switches to sampling.
+const EXACT_COUNT_LIMIT: f64 = 1_000_000.0;
+/// Rows a sampled count aims to read.
+const SAMPLE_ROWS: f64 = 100_000.0;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CountQuery {
+    #[serde(default, rename = "groupBy")]
+    pub group_by: Option<String>,
+    /// `auto` (default), `true` or `false`.
+    #[serde(default)]
+    approximate: Option<String>,
+    /// Maximum number of groups, largest first.
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// How to group a count.
+pub(crate) struct CountGroupBy {
+    /// Grouping expression.
+    pub expr: &'static str,
+    /// Join needed by `expr`, e.g. observations for a per-model trace count.
+    pub join: Option<&'static str>,
+    /// Counted as `COUNT(DISTINCT ..)` when the join fans out rows.
+    pub distinct: Option<&'static str>,
+}
+
+impl CountGroupBy {
+    pub(crate) const fn column(expr: &'static str) -> Self {
+        Self {
+            expr,
+            join: None,
+            distinct: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CountGroup {
+    value: Option<String>,
+    count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CountResponse {
+    total: i64,
+    approximate: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<CountGroup>>,
+}
+
+/// Pushes ` WHERE ...` for the request's filters.
+pub(crate) type PushFilters<'f> = dyn Fn(&mut QueryBuilder<'_, Postgres>) + Send + Sync + 'f;
+
+/// Counts rows of `table` (`"<name> <alias>"`) matching `filters`.
+pub(crate) async fn count(
+    pool: &PgPool,
+    table: &str,
+    q: &CountQuery,
+    group: Option<CountGroupBy>,
+    filters: &PushFilters<'_>,
+) -> Result<CountResponse, ApiError> {
+    let mode = q.approximate.as_deref().unwrap_or("auto");
+    let sample_pct = match mode {
+        "false" => None,
+        "true" | "auto" => {
+            let estimate = estimate_rows(pool, table, filters).await?;
+            let threshold = if mode == "true" {
+                SAMPLE_ROWS
+            } else {
+                EXACT_COUNT_LIMIT
+            };
+            (estimate > threshold).then(|| (SAMPLE_ROWS / estimate * 100.0).clamp(0.01, 100.0))
+        }
+        other => {
+            return Err(ApiError::BadRequest(format!(
+                "invalid approximate: {other} (expected auto, true or false)"
+            )))
+        }
+    };
+    let scale = sample_pct.map_or(1.0, |pct| 100.0 / pct);
+    let from = match sample_pct {
+        // A fixed seed keeps the total and the groups on the same sample.
+        Some(pct) => format!("{table} TABLESAMPLE SYSTEM ({pct}) REPEATABLE (0)"),
+        None => table.to_string(),
+    };
+
+    let mut builder: QueryBuilder<'_, Postgres> =
+        QueryBuilder::new(format!("SELECT COUNT(*)::BIGINT FROM {from}"));
+    filters(&mut builder);
+    let total: i64 = builder.build_query_scalar().fetch_one(pool).await?;
+
+    let groups = match &group {
+        None => None,
+        Some(g) => {
+            let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+            let counted = g
+                .distinct
+                .map_or("*".to_string(), |d| format!("DISTINCT {d}"));
+            let mut builder: QueryBuilder<'_, Postgres> = QueryBuilder::new(format!(
+                "SELECT {}::TEXT AS value, COUNT({counted})::BIGINT AS count FROM {from} {}",
+                g.expr,
+                g.join.unwrap_or_default()
+            ));
+            filters(&mut builder);
+            builder.push(" GROUP BY 1 ORDER BY 2 DESC, 1 LIMIT ");
+            builder.push_bind(limit);
+            let rows: Vec<(Option<String>, i64)> = builder.build_query_as().fetch_all(pool).await?;
+            Some(
+                rows.into_iter()
+                    .map(|(value, count)| CountGroup {
+                        value,
+                        count: scaled(count, scale),
+                    })
+                    .collect(),
+            )
+        }
+    };
+
+    Ok(CountResponse {
+        total: scaled(total, scale),
+        approximate: sample_pct.is_some(),
+        group_by: group.and(q.group_by.clone()),
+        groups,
+    })
+}
+
+fn scaled(count: i64, scale: f64) -> i64 {
+    (count as f64 * scale).round() as i64
+}
+
+/// The planner's estimate of the rows matching `filters`.
+async fn estimate_rows(
+    pool: &PgPool,
+    table: &str,
+    filters: &PushFilters<'_>,
+) -> Result<f64, ApiError> {
+    let mut builder: QueryBuilder<'_, Postgres> =
+        QueryBuilder::new(format!("EXPLAIN (FORMAT JSON) SELECT 1 FROM {table}"));
+    filters(&mut builder);
+    let plan: JsonValue = builder.build_query_scalar().fetch_one(pool).await?;
+    Ok(plan
+        .pointer("/0/Plan/Plan Rows")
+        .and_then(JsonValue::as_f64)
+        .unwrap_or(0.0))
+}
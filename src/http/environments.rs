@@ -0,0 +1,43 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    http::{auth::ProjectScope, error::ApiError},
+    state::AppState,
+};
+
+#[derive(Debug, Serialize)]
+struct EnvironmentsResponse {
+    data: Vec<Environment>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct Environment {
+    name: String,
+    /// Earliest trace timestamp in the environment.
+    first_seen: DateTime<Utc>,
+    /// Latest trace timestamp in the environment.
+    last_seen: DateTime<Utc>,
+}
+
+/// Environments the project has traces in, most recently seen first, from
+/// the table the ingest workers maintain rather than from `traces`.
+pub(crate) async fn get_environments(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+) -> Result<impl IntoResponse, ApiError> {
+    let data: Vec<Environment> = sqlx::query_as(
+        r#"
+SELECT name, first_seen, last_seen
+FROM environments
+WHERE project_id = $1
+ORDER BY last_seen DESC, name
+        "#,
+    )
+    .bind(scope.project_id())
+    .fetch_all(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(EnvironmentsResponse { data })))
+}
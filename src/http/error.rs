@@ -1,7 +1,22 @@
-use axum::{http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
 use thiserror::Error;
 
-use crate::http::common::ApiResponse;
+use crate::http::{common::ApiResponse, quotas::QuotaExceeded};
+
+/// A write queue that had no room for a payload; the body of its `429`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueFull {
+    /// Payloads queued when the write was rejected.
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    pub retry_after_secs: u64,
+}
 
 #[derive(Debug, Error)]
 pub enum ApiError {
@@ -9,10 +24,27 @@ pub enum ApiError {
     BadRequest(String),
     #[error("not found")]
     NotFound,
-    #[error("too many requests")]
-    TooManyRequests,
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    /// A write queue is full.
+    #[error("queue full")]
+    QueueFull(QueueFull),
+    /// Per-key ingest cost limit exceeded.
+    #[error("rate limited")]
+    RateLimited { retry_after_secs: u64 },
     #[error("service unavailable")]
     ServiceUnavailable,
+    /// Writes are paused (read-only / drain mode).
+    #[error("writes are paused ({mode})")]
+    WritesPaused {
+        mode: &'static str,
+        retry_after_secs: u64,
+    },
+    /// The project's monthly quota is used up.
+    #[error("quota exceeded")]
+    QuotaExceeded(QuotaExceeded),
     #[error(transparent)]
     Sqlx(#[from] sqlx::Error),
 }
@@ -22,20 +54,81 @@ impl IntoResponse for ApiError {
         if let ApiError::Sqlx(err) = &self {
             tracing::error!(error = %err, "sqlx error");
         }
+        if let ApiError::WritesPaused {
+            mode,
+            retry_after_secs,
+        } = &self
+        {
+            let body = Json(ApiResponse::<serde_json::Value> {
+                message: format!("Writes are paused ({mode})"),
+                code: Some("SERVICE_UNAVAILABLE"),
+                data: None,
+            });
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                body,
+            )
+                .into_response();
+        }
+
+        if let ApiError::RateLimited { retry_after_secs } = &self {
+            let body = Json(ApiResponse::<serde_json::Value> {
+                message: "Too Many Requests".to_string(),
+                code: Some("TOO_MANY_REQUESTS"),
+                data: None,
+            });
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                body,
+            )
+                .into_response();
+        }
+
+        if let ApiError::QuotaExceeded(quota) = &self {
+            let body = Json(ApiResponse::<serde_json::Value> {
+                message: quota.message(),
+                code: Some("QUOTA_EXCEEDED"),
+                data: None,
+            });
+            return (StatusCode::PAYMENT_REQUIRED, quota.headers(), body).into_response();
+        }
 
         let (status, code, msg) = match self {
             ApiError::BadRequest(m) => (StatusCode::BAD_REQUEST, "BAD_REQUEST", m),
             ApiError::NotFound => (StatusCode::NOT_FOUND, "NOT_FOUND", "Not Found".to_string()),
-            ApiError::TooManyRequests => (
+            ApiError::Forbidden(m) => (StatusCode::FORBIDDEN, "FORBIDDEN", m),
+            ApiError::Conflict(m) => (StatusCode::CONFLICT, "CONFLICT", m),
+            ApiError::QueueFull(full) => {
+                let retry_after = full.retry_after_secs.to_string();
+                let body = Json(ApiResponse {
+                    message: "Ingest queue is full".to_string(),
+                    code: Some("TOO_MANY_REQUESTS"),
+                    data: Some(full),
+                });
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after)],
+                    body,
+                )
+                    .into_response();
+            }
+            ApiError::RateLimited { .. } => (
                 StatusCode::TOO_MANY_REQUESTS,
                 "TOO_MANY_REQUESTS",
                 "Too Many Requests".to_string(),
             ),
-            ApiError::ServiceUnavailable => (
+            ApiError::ServiceUnavailable | ApiError::WritesPaused { .. } => (
                 StatusCode::SERVICE_UNAVAILABLE,
                 "SERVICE_UNAVAILABLE",
                 "Service Unavailable".to_string(),
             ),
+            ApiError::QuotaExceeded(quota) => (
+                StatusCode::PAYMENT_REQUIRED,
+                "QUOTA_EXCEEDED",
+                quota.message(),
+            ),
             ApiError::Sqlx(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "INTERNAL_ERROR",
@@ -0,0 +1,111 @@
+//! Cost-based rate limiting for ingest routes.
+//!
+//! Instead of counting requests, each ingest request is charged a normalized
+//! cost: one unit for the request, one per observation and one per
+//! `bytes_per_unit` bytes of (decompressed) payload. Each client key has its
+//! own token bucket refilled at `units_per_sec` up to `burst` units, so many
+//! tiny batches and a few giant ones drain the same budget.
+//!
+//! The limit is soft for oversized requests: a request costing more than the
+//! whole burst is charged the burst instead, i.e. it is admitted when the key
+//! has its full credit and leaves the bucket empty.
+
+use std::{
+    num::NonZeroU32,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use axum::http::HeaderMap;
+use governor::{clock::Clock, Quota};
+
+use crate::{
+    http::{auth::extract_client_key, error::ApiError},
+    state::KeyedRateLimiter,
+};
+
+/// What an ingest request carries, before normalization.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IngestCost {
+    pub bytes: usize,
+    pub observations: usize,
+}
+
+pub struct IngestCostLimiter {
+    limiter: KeyedRateLimiter,
+    units_per_sec: u32,
+    burst: u32,
+    bytes_per_unit: u32,
+    pub charged_units: AtomicU64,
+    pub rejected: AtomicU64,
+}
+
+impl IngestCostLimiter {
+    /// Returns `None` when `units_per_sec` is 0 (limiting disabled).
+    pub fn new(units_per_sec: u32, burst: u32, bytes_per_unit: u32) -> Option<Self> {
+        let rate = NonZeroU32::new(units_per_sec)?;
+        let burst = burst.max(units_per_sec);
+        let quota = Quota::per_second(rate).allow_burst(NonZeroU32::new(burst)?);
+        Some(Self {
+            limiter: KeyedRateLimiter::keyed(quota),
+            units_per_sec,
+            burst,
+            bytes_per_unit: bytes_per_unit.max(1),
+            charged_units: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        })
+    }
+
+    pub fn units_per_sec(&self) -> u32 {
+        self.units_per_sec
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    pub fn bytes_per_unit(&self) -> u32 {
+        self.bytes_per_unit
+    }
+
+    /// Normalized cost of a request, in units.
+    pub(crate) fn units(&self, cost: IngestCost) -> u32 {
+        let byte_units = cost.bytes.div_ceil(self.bytes_per_unit as usize);
+        (1 + cost.observations + byte_units).min(u32::MAX as usize) as u32
+    }
+
+    /// Charges `cost` to `key`; on rejection returns how long to wait until the
+    /// bucket holds enough credit.
+    pub(crate) fn check(&self, key: &str, cost: IngestCost) -> Result<u32, Duration> {
+        let units = self.units(cost).clamp(1, self.burst);
+        let n = NonZeroU32::new(units).unwrap_or(NonZeroU32::MIN);
+        match self.limiter.check_key_n(&key.to_string(), n) {
+            Ok(Ok(())) => {
+                self.charged_units
+                    .fetch_add(u64::from(units), Ordering::Relaxed);
+                Ok(units)
+            }
+            Ok(Err(not_until)) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(not_until.wait_time_from(self.limiter.clock().now()))
+            }
+            // Unreachable: `units` is clamped to the burst.
+            Err(_) => Ok(units),
+        }
+    }
+
+    /// Charges the request identified by `headers`, mapping a rejection to `429`.
+    pub(crate) fn charge(&self, headers: &HeaderMap, cost: IngestCost) -> Result<(), ApiError> {
+        let key = extract_client_key(headers);
+        self.check(&key, cost).map(|_| ()).map_err(|wait| {
+            tracing::debug!(
+                key = %crate::state::mask_client_key(&key),
+                units = self.units(cost),
+                "ingest cost limit exceeded"
+            );
+            ApiError::RateLimited {
+                retry_after_secs: wait.as_secs().max(1),
+            }
+        })
+    }
+}
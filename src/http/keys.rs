@@ -0,0 +1,317 @@
+//! Valid API credentials, reloadable at runtime.
+//!
+//! `API_BEARER_TOKEN` and the `XTRACE_PUBLIC_KEY` / `XTRACE_SECRET_KEY` pair
+//! from the environment are always accepted. On top of those, keys are read
+//! from an optional keys file (`XTRACE_KEYS_FILE`) and from the non-revoked
+//! rows of `api_keys`, and re-read on `SIGHUP` and every reload interval, so
+//! keys can be rotated or revoked without restarting the server.
+//!
+//! A key may be bound to a project (`api_keys.project_id`, or a trailing
+//! project id in the keys file); requests made with it are scoped to that
+//! project. Unbound keys, including the environment keys, act on
+//! `DEFAULT_PROJECT_ID` and may write to any project.
+//!
+//! A key may also be limited to ingest or to reads ([`KeyAccess`]):
+//! `XTRACE_INGEST_TOKEN` and `XTRACE_READ_TOKEN`, `api_keys.access`, or a
+//! leading `ingest` / `read` in the keys file.
+//!
+//! Keys file format, one key per line (`#` starts a comment):
+//!
+//! ```text
+//! [ingest|read] bearer <token> [<project-id>]
+//! [ingest|read] basic <langfuse-public-key> <langfuse-secret-key> [<project-id>]
+//! <token>
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use sqlx::PgPool;
+use thiserror::Error;
+use tokio::time::Duration;
+
+use crate::state::mask_client_key;
+
+#[derive(Debug, Error)]
+pub enum KeyLoadError {
+    #[error("failed to read keys file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("keys file line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+}
+
+/// Which routes a key may use.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAccess {
+    /// Ingest, queries and (for instance-wide keys) admin endpoints.
+    #[default]
+    Full,
+    /// Ingest and other writes only, e.g. for producers.
+    Ingest,
+    /// Queries only, e.g. for dashboards.
+    Read,
+}
+
+impl KeyAccess {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "full" => Some(Self::Full),
+            "ingest" => Some(Self::Ingest),
+            "read" => Some(Self::Read),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Ingest => "ingest",
+            Self::Read => "read",
+        }
+    }
+
+    pub fn can_read(self) -> bool {
+        self != Self::Ingest
+    }
+
+    pub fn can_write(self) -> bool {
+        self != Self::Read
+    }
+}
+
+/// What an accepted key grants.
+#[derive(Debug, Default, Clone)]
+pub struct KeyGrant {
+    /// Project the key is bound to; `None` for instance-wide keys.
+    pub project_id: Option<Arc<str>>,
+    pub access: KeyAccess,
+    /// Non-secret identifier of the key, recorded on the scores it writes:
+    /// `api_key:<id>` for `api_keys` rows, the public key of Langfuse key
+    /// pairs, and a masked token for other bearer tokens.
+    pub key_id: Arc<str>,
+}
+
+/// One snapshot of accepted credentials.
+#[derive(Debug, Default, Clone)]
+pub struct KeySet {
+    bearer: HashMap<String, KeyGrant>,
+    /// Langfuse public key -> (secret key, grant).
+    basic: HashMap<String, (String, KeyGrant)>,
+}
+
+fn grant(project_id: Option<&str>, access: KeyAccess, key_id: &str) -> KeyGrant {
+    KeyGrant {
+        project_id: project_id.map(Arc::from),
+        access,
+        key_id: Arc::from(key_id),
+    }
+}
+
+impl KeySet {
+    pub fn add_bearer(
+        &mut self,
+        token: impl Into<String>,
+        project_id: Option<&str>,
+        access: KeyAccess,
+    ) {
+        let token = token.into();
+        let key_id = mask_client_key(&format!("bearer:{token}"));
+        self.bearer
+            .insert(token, grant(project_id, access, &key_id));
+    }
+
+    pub fn add_basic(
+        &mut self,
+        public_key: impl Into<String>,
+        secret_key: impl Into<String>,
+        project_id: Option<&str>,
+        access: KeyAccess,
+    ) {
+        let public_key = public_key.into();
+        let grant = grant(project_id, access, &public_key);
+        self.basic.insert(public_key, (secret_key.into(), grant));
+    }
+
+    pub fn bearer(&self, token: &str) -> Option<&KeyGrant> {
+        self.bearer.get(token)
+    }
+
+    pub fn basic(&self, public_key: &str, secret_key: &str) -> Option<&KeyGrant> {
+        self.basic
+            .get(public_key)
+            .filter(|(s, _)| s == secret_key)
+            .map(|(_, g)| g)
+    }
+
+    /// Projects that keys are bound to.
+    pub fn projects(&self) -> HashSet<&str> {
+        self.bearer
+            .values()
+            .chain(self.basic.values().map(|(_, g)| g))
+            .filter_map(|g| g.project_id.as_deref())
+            .collect()
+    }
+
+    /// Whether any Langfuse key pair is configured.
+    pub fn has_basic(&self) -> bool {
+        !self.basic.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bearer.len() + self.basic.len()
+    }
+
+    fn extend(&mut self, other: KeySet) {
+        self.bearer.extend(other.bearer);
+        self.basic.extend(other.basic);
+    }
+
+    fn parse_file(contents: &str) -> Result<Self, KeyLoadError> {
+        let mut set = KeySet::default();
+        for (i, raw) in contents.lines().enumerate() {
+            let line = raw.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts: Vec<&str> = line.split_whitespace().collect();
+            let mut access = KeyAccess::Full;
+            if let [qualifier @ ("ingest" | "read"), _, ..] = parts.as_slice() {
+                access = KeyAccess::parse(qualifier).unwrap_or_default();
+                parts.remove(0);
+            }
+            match parts.as_slice() {
+                [token] | ["bearer", token] => set.add_bearer(*token, None, access),
+                ["bearer", token, project_id] => set.add_bearer(*token, Some(project_id), access),
+                ["basic", public_key, secret_key] => {
+                    set.add_basic(*public_key, *secret_key, None, access)
+                }
+                ["basic", public_key, secret_key, project_id] => {
+                    set.add_basic(*public_key, *secret_key, Some(project_id), access)
+                }
+                _ => {
+                    return Err(KeyLoadError::Parse {
+                        line: i + 1,
+                        message: "expected `[ingest|read] bearer <token> [<project>]` or `[ingest|read] basic <public> <secret> [<project>]`"
+                            .to_string(),
+                    })
+                }
+            }
+        }
+        Ok(set)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ApiKeyRow {
+    id: i64,
+    kind: String,
+    key: String,
+    secret: Option<String>,
+    project_id: Option<String>,
+    access: String,
+}
+
+pub struct KeyStore {
+    static_keys: KeySet,
+    file: Option<PathBuf>,
+    current: RwLock<Arc<KeySet>>,
+}
+
+impl KeyStore {
+    pub fn new(static_keys: KeySet, file: Option<PathBuf>) -> Self {
+        let current = RwLock::new(Arc::new(static_keys.clone()));
+        Self {
+            static_keys,
+            file,
+            current,
+        }
+    }
+
+    /// The credentials accepted right now.
+    pub fn current(&self) -> Arc<KeySet> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Rebuilds the key set from the environment keys, the keys file and
+    /// `api_keys`. On error the previous set stays in effect.
+    pub async fn reload(&self, pool: &PgPool) -> Result<usize, KeyLoadError> {
+        let mut next = self.static_keys.clone();
+        if let Some(path) = &self.file {
+            let contents = tokio::fs::read_to_string(path).await?;
+            next.extend(KeySet::parse_file(&contents)?);
+        }
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(
+            "SELECT id, kind, key, secret, project_id, access FROM api_keys WHERE revoked_at IS NULL",
+        )
+        .fetch_all(pool)
+        .await?;
+        for row in rows {
+            let grant = grant(
+                row.project_id.as_deref(),
+                KeyAccess::parse(&row.access).unwrap_or_default(),
+                &format!("api_key:{}", row.id),
+            );
+            match (row.kind.as_str(), row.secret) {
+                ("bearer", _) => {
+                    next.bearer.insert(row.key, grant);
+                }
+                ("basic", Some(secret)) => {
+                    next.basic.insert(row.key, (secret, grant));
+                }
+                _ => {}
+            }
+        }
+
+        let projects: Vec<String> = next.projects().into_iter().map(String::from).collect();
+        crate::http::projects::register(pool, &projects).await?;
+
+        let n = next.len();
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(next);
+        Ok(n)
+    }
+}
+
+/// Reloads keys every `interval` and on `SIGHUP`.
+pub(crate) async fn key_reload_worker(store: Arc<KeyStore>, pool: PgPool, interval: Duration) {
+    #[cfg(unix)]
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(s) => Some(s),
+        Err(err) => {
+            tracing::warn!(error = %err, "keys: failed to install SIGHUP handler");
+            None
+        }
+    };
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        #[cfg(unix)]
+        {
+            let hup = async {
+                match hangup.as_mut() {
+                    Some(s) => s.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = ticker.tick() => {},
+                _ = hup => tracing::info!("keys: SIGHUP received, reloading"),
+            }
+        }
+        #[cfg(not(unix))]
+        ticker.tick().await;
+
+        match store.reload(&pool).await {
+            Ok(n) => tracing::debug!(keys = n, "keys: reloaded"),
+            Err(err) => tracing::error!(error = %err, "keys: reload failed, keeping previous keys"),
+        }
+    }
+}
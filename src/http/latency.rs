@@ -0,0 +1,88 @@
+//! Latency breakdown of a trace by observation type.
+//!
+//! Every instant during which an observation runs is attributed to the
+//! innermost running one: the observation started last (of those started at
+//! the same time, the one ending first). Summed per type, this tells how much
+//! of a trace's wall-clock time went to generations, tools, or the spans
+//! orchestrating them, with overlapping time counted once.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use chrono::{DateTime, Utc};
+
+/// Seconds per observation type. Observations without an end (or ending
+/// before they start) are skipped.
+pub(crate) fn latency_breakdown<'a>(
+    observations: impl IntoIterator<Item = (&'a str, DateTime<Utc>, Option<DateTime<Utc>>)>,
+) -> BTreeMap<String, f64> {
+    let intervals: Vec<(&str, i64, i64)> = observations
+        .into_iter()
+        .filter_map(|(kind, start, end)| {
+            let (start, end) = (start.timestamp_micros(), end?.timestamp_micros());
+            (end > start).then_some((kind, start, end))
+        })
+        .collect();
+
+    // (time, is_start, index); ends sort before starts at the same time.
+    let mut events: Vec<(i64, bool, usize)> = Vec::with_capacity(intervals.len() * 2);
+    for (i, (_, start, end)) in intervals.iter().enumerate() {
+        events.push((*start, true, i));
+        events.push((*end, false, i));
+    }
+    events.sort_unstable();
+
+    let mut micros: BTreeMap<&str, i64> = BTreeMap::new();
+    let mut running: BTreeSet<(i64, Reverse<i64>, usize)> = BTreeSet::new();
+    let mut last = None;
+    for (time, is_start, i) in events {
+        if let (Some(from), Some(&(_, _, innermost))) = (last, running.last()) {
+            *micros.entry(intervals[innermost].0).or_default() += time - from;
+        }
+        let (_, start, end) = intervals[i];
+        if is_start {
+            running.insert((start, Reverse(end), i));
+        } else {
+            running.remove(&(start, Reverse(end), i));
+        }
+        last = Some(time);
+    }
+
+    micros
+        .into_iter()
+        .filter(|(_, us)| *us > 0)
+        .map(|(kind, us)| (kind.to_string(), us as f64 / 1e6))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn attributes_time_to_innermost_observation() {
+        let breakdown = latency_breakdown([
+            // Root span 0..10 running a generation 1..5 and a tool 4..7 that
+            // overlaps it; a second generation 6..8 runs inside the tool.
+            ("SPAN", at(0), Some(at(10))),
+            ("GENERATION", at(1), Some(at(5))),
+            ("TOOL", at(4), Some(at(7))),
+            ("GENERATION", at(6), Some(at(8))),
+            ("EVENT", at(3), Some(at(3))),
+            ("GENERATION", at(2), None),
+        ]);
+        // 1..4 and 6..8 (4..5 belongs to the tool, which started later).
+        assert_eq!(breakdown["GENERATION"], 5.0);
+        assert_eq!(breakdown["TOOL"], 2.0);
+        assert_eq!(breakdown["SPAN"], 3.0);
+        assert!(!breakdown.contains_key("EVENT"));
+        assert_eq!(breakdown.values().sum::<f64>(), 10.0);
+    }
+}
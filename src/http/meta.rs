@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::{
+    http::{auth::ProjectScope, error::ApiError},
+    state::AppState,
+};
+
+/// Distinct counts above this are flagged as high cardinality.
+const HIGH_CARDINALITY: i64 = 1000;
+
+/// Filterable column: API name, row source (aliased `t`), value expression
+/// and the timestamp column the range applies to.
+struct Column {
+    name: &'static str,
+    from: &'static str,
+    expr: &'static str,
+    time: &'static str,
+}
+
+const COLUMNS: &[Column] = &[
+    Column {
+        name: "name",
+        from: "traces t",
+        expr: "t.name",
+        time: "t.timestamp",
+    },
+    Column {
+        name: "userId",
+        from: "traces t",
+        expr: "t.user_id",
+        time: "t.timestamp",
+    },
+    Column {
+        name: "tags",
+        from: "traces t LEFT JOIN LATERAL unnest(t.tags) AS tag ON TRUE",
+        expr: "tag",
+        time: "t.timestamp",
+    },
+    Column {
+        name: "model",
+        from: "observations t",
+        expr: "t.model",
+        time: "t.start_time",
+    },
+    Column {
+        name: "environment",
+        from: "traces t",
+        expr: "t.environment",
+        time: "t.timestamp",
+    },
+];
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ColumnStatsQuery {
+    #[serde(default, rename = "fromTimestamp")]
+    from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toTimestamp")]
+    to_timestamp: Option<DateTime<Utc>>,
+    /// Comma separated subset of the columns; all by default.
+    #[serde(default)]
+    columns: Option<String>,
+    #[serde(default, rename = "topK")]
+    top_k: Option<i64>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ColumnStatsRow {
+    distinct_count: i64,
+    null_count: i64,
+    top_values: JsonValue,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ColumnStats {
+    column: &'static str,
+    distinct_count: i64,
+    /// Rows without a value (traces without tags for `tags`).
+    null_count: i64,
+    high_cardinality: bool,
+    /// `[{ "value": ..., "count": ... }]`, most frequent first.
+    top_values: JsonValue,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ColumnStatsResponse {
+    data: Vec<ColumnStats>,
+    from_timestamp: DateTime<Utc>,
+    to_timestamp: DateTime<Utc>,
+}
+
+/// Distinct counts and most frequent values of the filterable columns within
+/// a time range (default: the last 24 hours).
+pub(crate) async fn get_column_stats(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<ColumnStatsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let to_ts = q.to_timestamp.unwrap_or_else(Utc::now);
+    let from_ts = q
+        .from_timestamp
+        .unwrap_or_else(|| to_ts - chrono::Duration::hours(24));
+    if from_ts > to_ts {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp must be <= toTimestamp".to_string(),
+        ));
+    }
+    state
+        .query_windows
+        .check_range(&scope, from_ts, to_ts, q.ignore_range_limit)?;
+    let top_k = q.top_k.unwrap_or(10).clamp(1, 100);
+
+    let columns: Vec<&Column> = match q.columns.as_deref() {
+        None => COLUMNS.iter().collect(),
+        Some(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(|c| {
+                COLUMNS.iter().find(|col| col.name == c).ok_or_else(|| {
+                    ApiError::BadRequest(format!(
+                        "invalid column: {c} (expected name, userId, tags, model or environment)"
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    let mut data = Vec::with_capacity(columns.len());
+    for col in columns {
+        let row: ColumnStatsRow = sqlx::query_as(&format!(
+            r#"
+WITH v AS (
+  SELECT {expr}::TEXT AS value
+  FROM {from}
+  WHERE t.project_id = $1 AND {time} >= $2 AND {time} <= $3
+),
+g AS (
+  SELECT value, COUNT(*)::BIGINT AS cnt FROM v WHERE value IS NOT NULL GROUP BY value
+)
+SELECT
+  (SELECT COUNT(*) FROM g)::BIGINT AS distinct_count,
+  (SELECT COUNT(*) FROM v WHERE value IS NULL)::BIGINT AS null_count,
+  COALESCE(
+    (SELECT jsonb_agg(jsonb_build_object('value', value, 'count', cnt) ORDER BY cnt DESC, value)
+     FROM (SELECT value, cnt FROM g ORDER BY cnt DESC, value LIMIT $4) top),
+    '[]'::jsonb
+  ) AS top_values
+            "#,
+            expr = col.expr,
+            from = col.from,
+            time = col.time,
+        ))
+        .bind(scope.project_id())
+        .bind(from_ts)
+        .bind(to_ts)
+        .bind(top_k)
+        .fetch_one(&state.pool)
+        .await?;
+
+        data.push(ColumnStats {
+            column: col.name,
+            distinct_count: row.distinct_count,
+            null_count: row.null_count,
+            high_cardinality: row.distinct_count > HIGH_CARDINALITY,
+            top_values: row.top_values,
+        });
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(ColumnStatsResponse {
+            data,
+            from_timestamp: from_ts,
+            to_timestamp: to_ts,
+        }),
+    ))
+}
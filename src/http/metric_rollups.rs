@@ -0,0 +1,151 @@
+//! Downsampling of old metric points.
+//!
+//! With `XTRACE_METRICS_ROLLUP_AFTER_SECS`, points older than that are moved
+//! from `metrics` into per-series 1-minute buckets in `metrics_1m` (count,
+//! sum, min, max and the latest value), and with
+//! `XTRACE_METRICS_HOURLY_ROLLUP_AFTER_SECS` minute buckets older than that
+//! are merged into 1-hour buckets in `metrics_1h`. Each step deletes the rows
+//! it rolls up in the same transaction, a batch at a time; points that arrive
+//! late are merged into existing buckets. `GET /api/public/metrics/query`
+//! reads all three tables.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::state::WriteGate;
+
+/// Rows rolled up per transaction.
+const BATCH_SIZE: i64 = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RollupSettings {
+    /// Age after which raw points go to minute buckets.
+    pub(crate) minute_after: chrono::Duration,
+    /// Age after which minute buckets go to hour buckets; never when unset.
+    pub(crate) hour_after: Option<chrono::Duration>,
+    pub(crate) interval: Duration,
+}
+
+/// Raw points to minute buckets. `$1` is the cutoff, `$2` the batch size.
+const ROLL_UP_MINUTES: &str = r#"
+WITH batch AS (
+  DELETE FROM metrics
+  WHERE id IN (
+    SELECT id FROM metrics
+    WHERE timestamp < $1
+    ORDER BY timestamp
+    LIMIT $2
+    FOR UPDATE SKIP LOCKED
+  )
+  RETURNING project_id, environment, name, labels, value, timestamp
+),
+inserted AS (
+  INSERT INTO metrics_1m (project_id, environment, name, labels, bucket, value_count, value_sum, value_min, value_max, value_last, last_ts)
+  SELECT
+    project_id, environment, name, labels,
+    to_timestamp(floor(extract(epoch from timestamp) / 60) * 60),
+    COUNT(*), SUM(value), MIN(value), MAX(value),
+    (ARRAY_AGG(value ORDER BY timestamp DESC))[1],
+    MAX(timestamp)
+  FROM batch
+  GROUP BY 1, 2, 3, 4, 5
+  ON CONFLICT (project_id, environment, name, labels, bucket) DO UPDATE SET
+    value_count = metrics_1m.value_count + EXCLUDED.value_count,
+    value_sum = metrics_1m.value_sum + EXCLUDED.value_sum,
+    value_min = LEAST(metrics_1m.value_min, EXCLUDED.value_min),
+    value_max = GREATEST(metrics_1m.value_max, EXCLUDED.value_max),
+    value_last = CASE WHEN EXCLUDED.last_ts >= metrics_1m.last_ts
+      THEN EXCLUDED.value_last ELSE metrics_1m.value_last END,
+    last_ts = GREATEST(metrics_1m.last_ts, EXCLUDED.last_ts)
+)
+SELECT COUNT(*) FROM batch
+"#;
+
+/// Minute buckets to hour buckets, like [`ROLL_UP_MINUTES`].
+const ROLL_UP_HOURS: &str = r#"
+WITH batch AS (
+  DELETE FROM metrics_1m
+  WHERE id IN (
+    SELECT id FROM metrics_1m
+    WHERE bucket < $1
+    ORDER BY bucket
+    LIMIT $2
+    FOR UPDATE SKIP LOCKED
+  )
+  RETURNING project_id, environment, name, labels, bucket, value_count, value_sum, value_min, value_max, value_last, last_ts
+),
+inserted AS (
+  INSERT INTO metrics_1h (project_id, environment, name, labels, bucket, value_count, value_sum, value_min, value_max, value_last, last_ts)
+  SELECT
+    project_id, environment, name, labels,
+    to_timestamp(floor(extract(epoch from bucket) / 3600) * 3600),
+    SUM(value_count), SUM(value_sum), MIN(value_min), MAX(value_max),
+    (ARRAY_AGG(value_last ORDER BY last_ts DESC))[1],
+    MAX(last_ts)
+  FROM batch
+  GROUP BY 1, 2, 3, 4, 5
+  ON CONFLICT (project_id, environment, name, labels, bucket) DO UPDATE SET
+    value_count = metrics_1h.value_count + EXCLUDED.value_count,
+    value_sum = metrics_1h.value_sum + EXCLUDED.value_sum,
+    value_min = LEAST(metrics_1h.value_min, EXCLUDED.value_min),
+    value_max = GREATEST(metrics_1h.value_max, EXCLUDED.value_max),
+    value_last = CASE WHEN EXCLUDED.last_ts >= metrics_1h.last_ts
+      THEN EXCLUDED.value_last ELSE metrics_1h.value_last END,
+    last_ts = GREATEST(metrics_1h.last_ts, EXCLUDED.last_ts)
+)
+SELECT COUNT(*) FROM batch
+"#;
+
+/// Runs `query` in batches until nothing older than `cutoff` is left or
+/// writes are paused. Returns the rows rolled up.
+async fn roll_up(
+    pool: &PgPool,
+    gate: &WriteGate,
+    query: &str,
+    cutoff: chrono::DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    let mut total = 0;
+    loop {
+        if gate.try_reserve(1).is_err() {
+            return Ok(total);
+        }
+        let result = sqlx::query_scalar::<_, i64>(query)
+            .bind(cutoff)
+            .bind(BATCH_SIZE)
+            .fetch_one(pool)
+            .await;
+        gate.release(1);
+        let rolled_up = result?;
+        total += rolled_up;
+        if rolled_up < BATCH_SIZE {
+            return Ok(total);
+        }
+    }
+}
+
+pub(crate) async fn metrics_rollup_worker(
+    pool: PgPool,
+    gate: Arc<WriteGate>,
+    settings: RollupSettings,
+) {
+    let mut interval = tokio::time::interval(settings.interval);
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        match roll_up(&pool, &gate, ROLL_UP_MINUTES, now - settings.minute_after).await {
+            Ok(0) => {}
+            Ok(n) => tracing::debug!(points = n, "metrics: rolled up into minute buckets"),
+            Err(err) => tracing::error!(error = %err, "metrics: minute rollup failed"),
+        }
+        let Some(hour_after) = settings.hour_after else {
+            continue;
+        };
+        match roll_up(&pool, &gate, ROLL_UP_HOURS, now - hour_after).await {
+            Ok(0) => {}
+            Ok(n) => tracing::debug!(buckets = n, "metrics: rolled up into hour buckets"),
+            Err(err) => tracing::error!(error = %err, "metrics: hour rollup failed"),
+        }
+    }
+}
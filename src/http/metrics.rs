@@ -2,9 +2,9 @@ use axum::{
     extract::{Query, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use sqlx::{PgPool, QueryBuilder};
@@ -16,15 +16,21 @@ use tokio::{sync::mpsc, time::Duration};
 
 use crate::{
     http::{
+        auth::ProjectScope,
         common::{ApiResponse, PageMeta, PagedData},
         error::ApiError,
+        telemetry::Telemetry,
     },
-    state::AppState,
+    ingest::activity,
+    state::{AppState, WriteGate},
 };
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct MetricsBatchRequest {
     pub metrics: Vec<MetricPointIngest>,
+    /// Set from the request's key; not part of the payload.
+    #[serde(skip)]
+    pub project_id: Option<Arc<str>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,6 +52,9 @@ pub(crate) struct MetricsQuery {
     step: Option<String>,
     agg: Option<String>,
     group_by: Option<String>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -83,20 +92,19 @@ struct MetricsQueryRow {
 
 pub(crate) async fn post_metrics_batch(
     State(state): State<AppState>,
-    Json(payload): Json<MetricsBatchRequest>,
+    Extension(scope): Extension<ProjectScope>,
+    Json(mut payload): Json<MetricsBatchRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    match state.metrics_tx.try_send(payload) {
-        Ok(()) => Ok((
-            StatusCode::OK,
-            Json(ApiResponse::<serde_json::Value> {
-                message: "Request Successful.".to_string(),
-                code: None,
-                data: None,
-            }),
-        )),
-        Err(mpsc::error::TrySendError::Full(_)) => Err(ApiError::TooManyRequests),
-        Err(mpsc::error::TrySendError::Closed(_)) => Err(ApiError::ServiceUnavailable),
-    }
+    payload.project_id = Some(Arc::from(scope.project_id()));
+    state.enqueue_metrics(payload).await?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse::<serde_json::Value> {
+            message: "Request Successful.".to_string(),
+            code: None,
+            data: None,
+        }),
+    ))
 }
 
 fn parse_step_seconds(step: Option<&str>) -> Result<i64, ApiError> {
@@ -115,7 +123,7 @@ fn parse_step_seconds(step: Option<&str>) -> Result<i64, ApiError> {
     Ok(secs)
 }
 
-fn parse_agg(agg: Option<&str>) -> Result<&'static str, ApiError> {
+pub(crate) fn parse_agg(agg: Option<&str>) -> Result<&'static str, ApiError> {
     let agg = agg.unwrap_or("avg").trim().to_ascii_lowercase();
     match agg.as_str() {
         "avg" => Ok("avg"),
@@ -132,16 +140,92 @@ fn parse_agg(agg: Option<&str>) -> Result<&'static str, ApiError> {
     }
 }
 
+/// Aggregate of [`push_metric_points`] rows for `agg` (see [`parse_agg`];
+/// `count` counts points). Raw points and rollup buckets share the shape of a
+/// bucket; a raw point is a bucket of one. Percentiles of rolled-up data are
+/// taken over bucket averages.
+pub(crate) fn agg_expr(agg: &str) -> &'static str {
+    match agg {
+        "avg" => "(SUM(value_sum) / SUM(value_count))::DOUBLE PRECISION",
+        "max" => "MAX(value_max)::DOUBLE PRECISION",
+        "min" => "MIN(value_min)::DOUBLE PRECISION",
+        "sum" => "SUM(value_sum)::DOUBLE PRECISION",
+        "last" => "(ARRAY_AGG(value_last ORDER BY last_ts DESC))[1]::DOUBLE PRECISION",
+        "count" => "COALESCE(SUM(value_count), 0)::DOUBLE PRECISION",
+        "p50" => "(percentile_cont(0.5) WITHIN GROUP (ORDER BY value_sum / value_count))::DOUBLE PRECISION",
+        "p90" => "(percentile_cont(0.9) WITHIN GROUP (ORDER BY value_sum / value_count))::DOUBLE PRECISION",
+        "p99" => "(percentile_cont(0.99) WITHIN GROUP (ORDER BY value_sum / value_count))::DOUBLE PRECISION",
+        _ => unreachable!(),
+    }
+}
+
+/// Pushes a `SELECT` of the points of one metric in `[from, to]` across
+/// `metrics`, `metrics_1m` and `metrics_1h`, as rows of `ts`, `labels`,
+/// `value_count`, `value_sum`, `value_min`, `value_max`, `value_last` and
+/// `last_ts`.
+pub(crate) fn push_metric_points(
+    builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    project_id: &str,
+    name: &str,
+    from_ts: DateTime<Utc>,
+    to_ts: DateTime<Utc>,
+    labels: Option<&JsonValue>,
+) {
+    for (i, (source, columns, ts)) in [
+        (
+            "metrics",
+            "1::BIGINT AS value_count, value AS value_sum, value AS value_min, value AS value_max, value AS value_last, timestamp AS last_ts",
+            "timestamp",
+        ),
+        (
+            "metrics_1m",
+            "value_count, value_sum, value_min, value_max, value_last, last_ts",
+            "bucket",
+        ),
+        (
+            "metrics_1h",
+            "value_count, value_sum, value_min, value_max, value_last, last_ts",
+            "bucket",
+        ),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if i > 0 {
+            builder.push("  UNION ALL\n");
+        }
+        builder.push(format_args!(
+            "  SELECT {ts} AS ts, labels, {columns}\n  FROM {source}\n  WHERE project_id = "
+        ));
+        builder.push_bind(project_id.to_string());
+        builder.push(" AND environment = 'default'");
+        builder.push(" AND name = ");
+        builder.push_bind(name.to_string());
+        builder.push(format_args!(" AND {ts} >= "));
+        builder.push_bind(from_ts);
+        builder.push(format_args!(" AND {ts} <= "));
+        builder.push_bind(to_ts);
+        if let Some(f) = labels {
+            builder.push(" AND labels @> ");
+            builder.push_bind(f.clone());
+        }
+        builder.push("\n");
+    }
+}
+
 pub(crate) async fn get_metrics_names(
     State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let project_id = state.default_project_id.as_ref();
+    let project_id = scope.project_id();
 
     let names: Vec<String> = sqlx::query_scalar(
         r#"
-SELECT DISTINCT name
-FROM metrics
-WHERE project_id = $1 AND environment = 'default'
+SELECT name FROM metrics WHERE project_id = $1 AND environment = 'default'
+UNION
+SELECT name FROM metrics_1m WHERE project_id = $1 AND environment = 'default'
+UNION
+SELECT name FROM metrics_1h WHERE project_id = $1 AND environment = 'default'
 ORDER BY name
         "#,
     )
@@ -154,6 +238,7 @@ ORDER BY name
 
 pub(crate) async fn get_metrics_query(
     State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
     Query(q): Query<MetricsQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     let now = Utc::now();
@@ -163,6 +248,9 @@ pub(crate) async fn get_metrics_query(
     if from_ts > to_ts {
         return Err(ApiError::BadRequest("from must be <= to".to_string()));
     }
+    state
+        .query_windows
+        .check_range(&scope, from_ts, to_ts, q.ignore_range_limit)?;
 
     let step_seconds = parse_step_seconds(q.step.as_deref())?;
     let agg = parse_agg(q.agg.as_deref())?;
@@ -175,44 +263,29 @@ pub(crate) async fn get_metrics_query(
         _ => None,
     };
 
-    let project_id = state.default_project_id.as_ref();
-
-    let agg_expr = match agg {
-        "avg" => "AVG(value)::DOUBLE PRECISION",
-        "max" => "MAX(value)::DOUBLE PRECISION",
-        "min" => "MIN(value)::DOUBLE PRECISION",
-        "sum" => "SUM(value)::DOUBLE PRECISION",
-        "last" => "(ARRAY_AGG(value ORDER BY timestamp DESC))[1]::DOUBLE PRECISION",
-        "p50" => "(percentile_cont(0.5) WITHIN GROUP (ORDER BY value))::DOUBLE PRECISION",
-        "p90" => "(percentile_cont(0.9) WITHIN GROUP (ORDER BY value))::DOUBLE PRECISION",
-        "p99" => "(percentile_cont(0.99) WITHIN GROUP (ORDER BY value))::DOUBLE PRECISION",
-        _ => unreachable!(),
-    };
+    let project_id = scope.project_id();
+
+    let agg_expr = agg_expr(agg);
 
     const MAX_POINTS_PER_SERIES: usize = 1000;
     const MAX_SERIES: usize = 50;
 
-    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
-        "WITH filtered AS (\n  SELECT\n    to_timestamp(floor(extract(epoch from timestamp) / ",
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new("WITH points AS (\n");
+    push_metric_points(
+        &mut builder,
+        project_id,
+        &q.name,
+        from_ts,
+        to_ts,
+        labels_filter.as_ref(),
     );
+    builder.push("),\nfiltered AS (\n  SELECT\n    to_timestamp(floor(extract(epoch from ts) / ");
     builder.push_bind(step_seconds);
     builder.push(") * ");
     builder.push_bind(step_seconds);
     builder.push(
-        ") AS bucket_ts,\n    labels,\n    value,\n    timestamp\n  FROM metrics\n  WHERE project_id = ",
+        ") AS bucket_ts,\n    labels, value_count, value_sum, value_min, value_max, value_last, last_ts\n  FROM points\n",
     );
-    builder.push_bind(project_id);
-    builder.push(" AND environment = 'default'");
-    builder.push(" AND name = ");
-    builder.push_bind(q.name);
-    builder.push(" AND timestamp >= ");
-    builder.push_bind(from_ts);
-    builder.push(" AND timestamp <= ");
-    builder.push_bind(to_ts);
-    if let Some(f) = &labels_filter {
-        builder.push(" AND labels @> ");
-        builder.push_bind(f.clone());
-    }
 
     builder.push(")\nSELECT\n  bucket_ts,\n  ");
     if let Some(ref group_key) = q.group_by {
@@ -304,11 +377,22 @@ pub(crate) struct MetricsDailyQuery {
     version: Option<String>,
     #[serde(default)]
     release: Option<String>,
+
+    /// IANA time zone used for day bucketing (default `UTC`).
+    #[serde(default)]
+    timezone: Option<String>,
+    /// `environment`, `release` or `version`; omitted means one row per day.
+    #[serde(default, rename = "groupBy")]
+    group_by: Option<String>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
 struct MetricsDailyRow {
     day: NaiveDate,
+    group_key: Option<String>,
     count_traces: i64,
     count_observations: i64,
     total_cost: f64,
@@ -319,6 +403,9 @@ struct MetricsDailyRow {
 #[serde(rename_all = "camelCase")]
 struct MetricsDailyItem {
     date: String,
+    /// Value of the `groupBy` column; `null` for traces without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<Option<String>>,
     count_traces: i64,
     count_observations: i64,
     total_cost: f64,
@@ -327,6 +414,7 @@ struct MetricsDailyItem {
 
 pub(crate) async fn get_metrics_daily(
     State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
     Query(q): Query<MetricsDailyQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     let page = q.page.unwrap_or(1).max(1);
@@ -338,12 +426,32 @@ pub(crate) async fn get_metrics_daily(
     let from_ts = q
         .from_timestamp
         .unwrap_or_else(|| to_ts - chrono::Duration::days(30));
+    state
+        .query_windows
+        .check_range(&scope, from_ts, to_ts, q.ignore_range_limit)?;
+
+    let timezone = parse_timezone(q.timezone.as_deref())?;
+    let group_expr = match q.group_by.as_deref() {
+        None | Some("") => None,
+        Some("environment") => Some("t.environment"),
+        Some("release") => Some("t.release"),
+        Some("version") => Some("t.version"),
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "invalid groupBy: {other} (expected environment, release or version)"
+            )))
+        }
+    };
 
-    let project_id = state.default_project_id.as_ref();
+    let project_id = scope.project_id();
 
     let mut count_builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
-        "SELECT COUNT(*)::BIGINT FROM (SELECT date_trunc('day', t.\"timestamp\")::date AS day FROM traces t WHERE 1=1",
+        "SELECT COUNT(*)::BIGINT FROM (SELECT date_trunc('day', t.\"timestamp\" AT TIME ZONE ",
     );
+    count_builder.push_bind(timezone.clone());
+    count_builder.push(")::date AS day, ");
+    count_builder.push(group_expr.unwrap_or("NULL::TEXT"));
+    count_builder.push(" AS group_key FROM traces t WHERE 1=1");
     count_builder.push(" AND t.project_id = ");
     count_builder.push_bind(project_id.to_string());
     count_builder.push(" AND t.\"timestamp\" >= ");
@@ -371,7 +479,7 @@ pub(crate) async fn get_metrics_daily(
         count_builder.push(" AND t.release = ");
         count_builder.push_bind(release.clone());
     }
-    count_builder.push(" GROUP BY 1) x");
+    count_builder.push(" GROUP BY 1, 2) x");
 
     let total_items: i64 = count_builder
         .build_query_scalar()
@@ -384,8 +492,13 @@ pub(crate) async fn get_metrics_daily(
         (total_items + limit - 1) / limit
     };
 
-    let mut builder: QueryBuilder<'_, sqlx::Postgres> =
-        QueryBuilder::new("WITH filtered_traces AS (SELECT t.* FROM traces t WHERE 1=1");
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
+        "WITH filtered_traces AS (SELECT t.*, date_trunc('day', t.\"timestamp\" AT TIME ZONE ",
+    );
+    builder.push_bind(timezone);
+    builder.push(")::date AS local_day, ");
+    builder.push(group_expr.unwrap_or("NULL::TEXT"));
+    builder.push(" AS group_key FROM traces t WHERE 1=1");
     builder.push(" AND t.project_id = ");
     builder.push_bind(project_id.to_string());
     builder.push(" AND t.\"timestamp\" >= ");
@@ -415,7 +528,73 @@ pub(crate) async fn get_metrics_daily(
     }
 
     builder.push(
-        ")\n, daily AS (\n  SELECT\n    date_trunc('day', ft.\"timestamp\")::date AS day,\n    COUNT(*)::BIGINT AS count_traces,\n    COALESCE(SUM(ft.total_cost), 0)::DOUBLE PRECISION AS total_cost\n  FROM filtered_traces ft\n  GROUP BY 1\n)\n, daily_obs AS (\n  SELECT\n    date_trunc('day', ft.\"timestamp\")::date AS day,\n    COUNT(o.id)::BIGINT AS count_observations\n  FROM filtered_traces ft\n  JOIN observations o ON o.trace_id = ft.id\n  GROUP BY 1\n)\n, model_usage AS (\n  SELECT\n    date_trunc('day', ft.\"timestamp\")::date AS day,\n    COALESCE(o.model, 'unknown') AS model,\n    COALESCE(SUM(o.prompt_tokens), 0)::BIGINT AS input_usage,\n    COALESCE(SUM(o.completion_tokens), 0)::BIGINT AS output_usage,\n    COALESCE(SUM(o.total_tokens), 0)::BIGINT AS total_usage,\n    COUNT(DISTINCT ft.id)::BIGINT AS count_traces,\n    COUNT(o.id)::BIGINT AS count_observations,\n    COALESCE(SUM(o.calculated_total_cost), 0)::DOUBLE PRECISION AS total_cost\n  FROM filtered_traces ft\n  JOIN observations o ON o.trace_id = ft.id\n  WHERE o.type = 'GENERATION'\n  GROUP BY 1, 2\n)\n, daily_usage AS (\n  SELECT\n    mu.day,\n    COALESCE(jsonb_agg(\n      jsonb_build_object(\n        'model', mu.model,\n        'inputUsage', mu.input_usage,\n        'outputUsage', mu.output_usage,\n        'totalUsage', mu.total_usage,\n        'countTraces', mu.count_traces,\n        'countObservations', mu.count_observations,\n        'totalCost', mu.total_cost\n      ) ORDER BY mu.total_cost DESC\n    ), '[]'::jsonb) AS usage\n  FROM model_usage mu\n  GROUP BY 1\n)\nSELECT\n  d.day AS day,\n  d.count_traces AS count_traces,\n  COALESCE(dob.count_observations, 0) AS count_observations,\n  d.total_cost AS total_cost,\n  COALESCE(du.usage, '[]'::jsonb) AS usage\nFROM daily d\nLEFT JOIN daily_obs dob ON dob.day = d.day\nLEFT JOIN daily_usage du ON du.day = d.day\nORDER BY d.day DESC\nLIMIT ",
+        r#")
+, daily AS (
+  SELECT
+    ft.local_day AS day,
+    ft.group_key,
+    COUNT(*)::BIGINT AS count_traces,
+    COALESCE(SUM(ft.total_cost), 0)::DOUBLE PRECISION AS total_cost
+  FROM filtered_traces ft
+  GROUP BY 1, 2
+)
+, daily_obs AS (
+  SELECT
+    ft.local_day AS day,
+    ft.group_key,
+    COUNT(o.id)::BIGINT AS count_observations
+  FROM filtered_traces ft
+  JOIN observations o ON o.trace_id = ft.id
+  GROUP BY 1, 2
+)
+, model_usage AS (
+  SELECT
+    ft.local_day AS day,
+    ft.group_key,
+    COALESCE(o.model, 'unknown') AS model,
+    o.provider AS provider,
+    COALESCE(SUM(o.prompt_tokens), 0)::BIGINT AS input_usage,
+    COALESCE(SUM(o.completion_tokens), 0)::BIGINT AS output_usage,
+    COALESCE(SUM(o.total_tokens), 0)::BIGINT AS total_usage,
+    COUNT(DISTINCT ft.id)::BIGINT AS count_traces,
+    COUNT(o.id)::BIGINT AS count_observations,
+    COALESCE(SUM(o.calculated_total_cost), 0)::DOUBLE PRECISION AS total_cost
+  FROM filtered_traces ft
+  JOIN observations o ON o.trace_id = ft.id
+  WHERE o.type = 'GENERATION'
+  GROUP BY 1, 2, 3, 4
+)
+, daily_usage AS (
+  SELECT
+    mu.day,
+    mu.group_key,
+    COALESCE(jsonb_agg(
+      jsonb_build_object(
+        'model', mu.model,
+        'provider', mu.provider,
+        'inputUsage', mu.input_usage,
+        'outputUsage', mu.output_usage,
+        'totalUsage', mu.total_usage,
+        'countTraces', mu.count_traces,
+        'countObservations', mu.count_observations,
+        'totalCost', mu.total_cost
+      ) ORDER BY mu.total_cost DESC
+    ), '[]'::jsonb) AS usage
+  FROM model_usage mu
+  GROUP BY 1, 2
+)
+SELECT
+  d.day AS day,
+  d.group_key AS group_key,
+  d.count_traces AS count_traces,
+  COALESCE(dob.count_observations, 0) AS count_observations,
+  d.total_cost AS total_cost,
+  COALESCE(du.usage, '[]'::jsonb) AS usage
+FROM daily d
+LEFT JOIN daily_obs dob ON dob.day = d.day AND dob.group_key IS NOT DISTINCT FROM d.group_key
+LEFT JOIN daily_usage du ON du.day = d.day AND du.group_key IS NOT DISTINCT FROM d.group_key
+ORDER BY d.day DESC, d.total_cost DESC, d.group_key
+LIMIT "#,
     );
     builder.push_bind(limit);
     builder.push(" OFFSET ");
@@ -427,6 +606,7 @@ pub(crate) async fn get_metrics_daily(
         .into_iter()
         .map(|r| MetricsDailyItem {
             date: r.day.to_string(),
+            group: group_expr.map(|_| r.group_key),
             count_traces: r.count_traces,
             count_observations: r.count_observations,
             total_cost: r.total_cost,
@@ -448,6 +628,172 @@ pub(crate) async fn get_metrics_daily(
     ))
 }
 
+/// Validates an IANA time zone name, defaulting to `UTC`.
+pub(crate) fn parse_timezone(tz: Option<&str>) -> Result<String, ApiError> {
+    match tz.map(str::trim) {
+        None | Some("") => Ok("UTC".to_string()),
+        Some(tz) => tz
+            .parse::<chrono_tz::Tz>()
+            .map(|tz| tz.name().to_string())
+            .map_err(|_| ApiError::BadRequest(format!("unknown IANA timezone: {tz}"))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MetricsActivityQuery {
+    #[serde(default, rename = "fromTimestamp")]
+    from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toTimestamp")]
+    to_timestamp: Option<DateTime<Utc>>,
+    /// `name` or `environment`; omitted means a single series.
+    #[serde(default, rename = "groupBy")]
+    group_by: Option<String>,
+    #[serde(default)]
+    timezone: Option<String>,
+    #[serde(default)]
+    environment: Vec<String>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct MetricsActivityRow {
+    hour: NaiveDateTime,
+    group_key: Option<String>,
+    count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetricsActivityItem {
+    hour: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetricsActivityResponse {
+    data: Vec<MetricsActivityItem>,
+    timezone: String,
+    from_timestamp: DateTime<Utc>,
+    to_timestamp: DateTime<Utc>,
+}
+
+/// Per-hour trace counts for activity heatmaps. Hours are local to `timezone`
+/// (default `UTC`) and only non-empty buckets are returned.
+///
+/// Counts come from `trace_activity` for the 15-minute buckets the range
+/// covers whole; only the partial buckets at its ends are counted in `traces`.
+pub(crate) async fn get_metrics_activity(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<MetricsActivityQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    const MAX_RANGE_DAYS: i64 = 366;
+
+    let to_ts = q.to_timestamp.unwrap_or_else(Utc::now);
+    let from_ts = q
+        .from_timestamp
+        .unwrap_or_else(|| to_ts - chrono::Duration::days(30));
+    if from_ts > to_ts {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp must be <= toTimestamp".to_string(),
+        ));
+    }
+    state
+        .query_windows
+        .check_range(&scope, from_ts, to_ts, q.ignore_range_limit)?;
+    if to_ts - from_ts > chrono::Duration::days(MAX_RANGE_DAYS) {
+        return Err(ApiError::BadRequest(format!(
+            "time range must not exceed {MAX_RANGE_DAYS} days"
+        )));
+    }
+
+    let (rollup_group, trace_group) = match q.group_by.as_deref() {
+        None | Some("") => ("NULL::TEXT", "NULL::TEXT"),
+        Some("name") => ("NULLIF(a.name, '')", "t.name"),
+        Some("environment") => ("a.environment", "t.environment"),
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "invalid groupBy: {other} (expected name or environment)"
+            )))
+        }
+    };
+    let timezone = parse_timezone(q.timezone.as_deref())?;
+
+    // Whole buckets lie in [first_bucket, last_bucket).
+    let bucket = chrono::Duration::seconds(activity::BUCKET_SECS);
+    let floor = |ts: DateTime<Utc>| {
+        let secs = ts.timestamp();
+        DateTime::from_timestamp(secs - secs.rem_euclid(activity::BUCKET_SECS), 0).unwrap_or(ts)
+    };
+    let first_bucket = match floor(from_ts) {
+        start if start == from_ts => start,
+        start => start + bucket,
+    };
+    let last_bucket = floor(to_ts);
+
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new("SELECT date_trunc('hour', x.ts AT TIME ZONE ");
+    builder.push_bind(timezone.clone());
+    builder.push(") AS hour, x.group_key, SUM(x.n)::BIGINT AS count FROM (SELECT a.bucket AS ts, ");
+    builder.push(rollup_group);
+    builder.push(" AS group_key, a.trace_count AS n FROM trace_activity a WHERE a.project_id = ");
+    builder.push_bind(scope.project_id().to_string());
+    builder.push(" AND a.bucket >= ");
+    builder.push_bind(first_bucket);
+    builder.push(" AND a.bucket < ");
+    builder.push_bind(last_bucket);
+    if !q.environment.is_empty() {
+        builder.push(" AND a.environment = ANY(");
+        builder.push_bind(q.environment.clone());
+        builder.push(")");
+    }
+    builder.push(" UNION ALL SELECT t.\"timestamp\", ");
+    builder.push(trace_group);
+    builder.push(", 1 FROM traces t WHERE t.project_id = ");
+    builder.push_bind(scope.project_id().to_string());
+    builder.push(" AND t.\"timestamp\" >= ");
+    builder.push_bind(from_ts);
+    builder.push(" AND t.\"timestamp\" <= ");
+    builder.push_bind(to_ts);
+    builder.push(" AND (t.\"timestamp\" < ");
+    builder.push_bind(first_bucket);
+    builder.push(" OR t.\"timestamp\" >= ");
+    builder.push_bind(last_bucket);
+    builder.push(")");
+    if !q.environment.is_empty() {
+        builder.push(" AND t.environment = ANY(");
+        builder.push_bind(q.environment.clone());
+        builder.push(")");
+    }
+    builder.push(") x GROUP BY 1, 2 ORDER BY 1 ASC, 2 ASC");
+
+    let rows: Vec<MetricsActivityRow> = builder.build_query_as().fetch_all(&state.pool).await?;
+
+    let data = rows
+        .into_iter()
+        .map(|r| MetricsActivityItem {
+            hour: r.hour.format("%Y-%m-%dT%H:00:00").to_string(),
+            group: r.group_key,
+            count: r.count,
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(MetricsActivityResponse {
+            data,
+            timezone,
+            from_timestamp: from_ts,
+            to_timestamp: to_ts,
+        }),
+    ))
+}
+
 fn labels_to_json(labels: HashMap<String, String>) -> JsonValue {
     let mut m = serde_json::Map::with_capacity(labels.len());
     for (k, v) in labels {
@@ -459,6 +805,8 @@ fn labels_to_json(labels: HashMap<String, String>) -> JsonValue {
 pub(crate) async fn metrics_worker(
     pool: PgPool,
     default_project_id: Arc<str>,
+    gate: Arc<WriteGate>,
+    telemetry: Arc<Telemetry>,
     mut rx: mpsc::Receiver<MetricsBatchRequest>,
 ) {
     const MAX_BATCHES: usize = 200;
@@ -483,9 +831,13 @@ pub(crate) async fn metrics_worker(
             }
         }
 
-        if let Err(err) = write_metrics_batches(&pool, default_project_id.as_ref(), batches).await {
-            tracing::error!(error = ?err, "failed to write metrics batch");
+        let n = batches.len() as u64;
+        let points = batches.iter().map(|b| b.metrics.len()).sum();
+        match write_metrics_batches(&pool, default_project_id.as_ref(), batches).await {
+            Ok(()) => telemetry.record_metric_points(points),
+            Err(err) => tracing::error!(error = ?err, "failed to write metrics batch"),
         }
+        gate.release(n);
     }
 }
 
@@ -494,9 +846,12 @@ async fn write_metrics_batches(
     default_project_id: &str,
     payloads: Vec<MetricsBatchRequest>,
 ) -> Result<(), sqlx::Error> {
-    let mut points: Vec<MetricPointIngest> = Vec::new();
+    let mut points: Vec<(Arc<str>, MetricPointIngest)> = Vec::new();
     for p in payloads {
-        points.extend(p.metrics);
+        let project_id = p
+            .project_id
+            .unwrap_or_else(|| Arc::from(default_project_id));
+        points.extend(p.metrics.into_iter().map(|m| (project_id.clone(), m)));
     }
     if points.is_empty() {
         return Ok(());
@@ -507,8 +862,8 @@ async fn write_metrics_batches(
     let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
         "INSERT INTO metrics (project_id, environment, name, labels, value, timestamp) ",
     );
-    builder.push_values(points.into_iter(), |mut b, m| {
-        b.push_bind(default_project_id.to_string())
+    builder.push_values(points, |mut b, (project_id, m)| {
+        b.push_bind(project_id.to_string())
             .push_bind("default".to_string())
             .push_bind(m.name)
             .push_bind(labels_to_json(m.labels))
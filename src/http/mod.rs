@@ -1,7 +1,36 @@
+pub(crate) mod analytics;
 pub(crate) mod auth;
 pub(crate) mod common;
+pub(crate) mod config;
+pub(crate) mod counts;
+pub(crate) mod environments;
 pub(crate) mod error;
+pub(crate) mod ingest_limit;
+pub(crate) mod keys;
+pub(crate) mod latency;
+pub(crate) mod meta;
+pub(crate) mod metric_rollups;
 pub(crate) mod metrics;
+pub(crate) mod models;
+pub(crate) mod network;
+pub(crate) mod observations;
 pub(crate) mod ops;
 pub(crate) mod projects;
+pub(crate) mod quotas;
+pub(crate) mod scores;
+pub(crate) mod services;
+pub(crate) mod sessions;
+pub(crate) mod sharing;
+pub(crate) mod tags;
+pub(crate) mod telemetry;
+pub(crate) mod tls;
+pub(crate) mod trace_delete;
+pub(crate) mod trace_filter;
+pub(crate) mod trace_tree;
+pub(crate) mod trace_update;
 pub(crate) mod traces;
+pub(crate) mod usage;
+pub(crate) mod users;
+pub(crate) mod version;
+pub(crate) mod wait;
+pub(crate) mod window;
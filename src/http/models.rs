@@ -0,0 +1,316 @@
+//! `/api/public/models`: the model pricing registry (see
+//! [`crate::ingest::pricing`]).
+//!
+//! Models created with a project key belong to its project. Models created
+//! with an instance-wide key are shared by every project unless the request
+//! names a `projectId`. Project keys see their own and the shared models but
+//! can only change their own.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        common::{PageMeta, PagedData},
+        error::ApiError,
+        projects,
+    },
+    state::AppState,
+};
+
+const MODEL_COLUMNS: &str = "id, project_id, model_name, match_pattern, start_date, unit, \
+  input_price, output_price, total_price, created_at, updated_at";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ModelRequest {
+    model_name: String,
+    /// Regular expression matched against observation model names.
+    match_pattern: String,
+    #[serde(default)]
+    start_date: Option<DateTime<Utc>>,
+    /// `TOKENS` when omitted.
+    #[serde(default)]
+    unit: Option<String>,
+    /// Prices per unit.
+    #[serde(default)]
+    input_price: Option<f64>,
+    #[serde(default)]
+    output_price: Option<f64>,
+    #[serde(default)]
+    total_price: Option<f64>,
+    /// Instance-wide keys only; shared by every project when unset.
+    #[serde(default)]
+    project_id: Option<String>,
+}
+
+impl ModelRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.model_name.trim().is_empty() {
+            return Err(ApiError::BadRequest(
+                "modelName must not be empty".to_string(),
+            ));
+        }
+        regex::Regex::new(&self.match_pattern)
+            .map_err(|e| ApiError::BadRequest(format!("invalid matchPattern: {e}")))?;
+        if self.unit.as_deref().is_some_and(|u| u.trim().is_empty()) {
+            return Err(ApiError::BadRequest("unit must not be empty".to_string()));
+        }
+        let prices = [
+            ("inputPrice", self.input_price),
+            ("outputPrice", self.output_price),
+            ("totalPrice", self.total_price),
+        ];
+        if prices.iter().all(|(_, p)| p.is_none()) {
+            return Err(ApiError::BadRequest(
+                "one of inputPrice, outputPrice or totalPrice is required".to_string(),
+            ));
+        }
+        for (name, price) in prices {
+            if price.is_some_and(|p| !p.is_finite() || p < 0.0) {
+                return Err(ApiError::BadRequest(format!(
+                    "{name} must be a non-negative number"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn unit(&self) -> String {
+        self.unit
+            .as_deref()
+            .map_or("TOKENS".to_string(), |u| u.trim().to_ascii_uppercase())
+    }
+
+    /// Project the model is stored under; `None` for shared models.
+    fn project_id(&self, scope: &ProjectScope) -> Result<Option<String>, ApiError> {
+        if !scope.is_bound() {
+            return Ok(self.project_id.clone());
+        }
+        match &self.project_id {
+            Some(p) if p != scope.project_id() => Err(ApiError::Forbidden(format!(
+                "key is not allowed to write to project {p:?}"
+            ))),
+            _ => Ok(Some(scope.project_id().to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ModelListQuery {
+    #[serde(default)]
+    page: Option<i64>,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct ModelDto {
+    id: Uuid,
+    /// `null` for models shared by every project.
+    project_id: Option<String>,
+    model_name: String,
+    match_pattern: String,
+    start_date: Option<DateTime<Utc>>,
+    unit: String,
+    input_price: Option<f64>,
+    output_price: Option<f64>,
+    total_price: Option<f64>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+/// Paginated models visible to the key, project models first.
+pub(crate) async fn get_models(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<ModelListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = q.page.unwrap_or(1).max(1);
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * limit;
+
+    let total_items: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*)::BIGINT FROM models WHERE project_id IS NULL OR project_id = $1 OR NOT $2",
+    )
+    .bind(scope.project_id())
+    .bind(scope.is_bound())
+    .fetch_one(&state.pool)
+    .await?;
+    let total_pages = if total_items == 0 {
+        0
+    } else {
+        (total_items + limit - 1) / limit
+    };
+
+    let rows: Vec<ModelDto> = sqlx::query_as(&format!(
+        r#"
+SELECT {MODEL_COLUMNS}
+FROM models
+WHERE project_id IS NULL OR project_id = $1 OR NOT $2
+ORDER BY project_id IS NULL, model_name, start_date DESC NULLS LAST, id
+LIMIT $3 OFFSET $4
+        "#
+    ))
+    .bind(scope.project_id())
+    .bind(scope.is_bound())
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data: rows,
+            meta: PageMeta {
+                page,
+                limit,
+                totalItems: total_items,
+                totalPages: total_pages,
+            },
+        }),
+    ))
+}
+
+pub(crate) async fn get_model(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(model_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row: Option<ModelDto> = sqlx::query_as(&format!(
+        "SELECT {MODEL_COLUMNS} FROM models WHERE id = $1 AND (project_id IS NULL OR project_id = $2 OR NOT $3)"
+    ))
+    .bind(model_id)
+    .bind(scope.project_id())
+    .bind(scope.is_bound())
+    .fetch_optional(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(row.ok_or(ApiError::NotFound)?)))
+}
+
+pub(crate) async fn post_model(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Json(req): Json<ModelRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    req.validate()?;
+    let project_id = req.project_id(&scope)?;
+    if let Some(p) = &project_id {
+        projects::register(&state.pool, std::slice::from_ref(p)).await?;
+    }
+    let row: ModelDto = sqlx::query_as(&format!(
+        r#"
+INSERT INTO models (
+  id, project_id, model_name, match_pattern, start_date, unit, input_price, output_price, total_price
+) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+RETURNING {MODEL_COLUMNS}
+        "#
+    ))
+    .bind(Uuid::new_v4())
+    .bind(project_id)
+    .bind(req.model_name.trim())
+    .bind(&req.match_pattern)
+    .bind(req.start_date)
+    .bind(req.unit())
+    .bind(req.input_price)
+    .bind(req.output_price)
+    .bind(req.total_price)
+    .fetch_one(&state.pool)
+    .await?;
+    state.prices.invalidate();
+    Ok((StatusCode::OK, Json(row)))
+}
+
+/// Rejects changes by a project key to a model it does not own; `404` when
+/// the model is not visible at all.
+async fn check_writable(
+    state: &AppState,
+    scope: &ProjectScope,
+    model_id: Uuid,
+) -> Result<(), ApiError> {
+    let owner: Option<Option<String>> =
+        sqlx::query_scalar("SELECT project_id FROM models WHERE id = $1")
+            .bind(model_id)
+            .fetch_optional(&state.pool)
+            .await?;
+    match owner {
+        None => Err(ApiError::NotFound),
+        Some(_) if !scope.is_bound() => Ok(()),
+        Some(Some(p)) if p == scope.project_id() => Ok(()),
+        Some(None) => Err(ApiError::Forbidden(
+            "shared models can only be changed with an instance-wide key".to_string(),
+        )),
+        Some(Some(_)) => Err(ApiError::NotFound),
+    }
+}
+
+/// Replaces every field of a model.
+pub(crate) async fn put_model(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(model_id): Path<Uuid>,
+    Json(req): Json<ModelRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    req.validate()?;
+    let project_id = req.project_id(&scope)?;
+    check_writable(&state, &scope, model_id).await?;
+    if let Some(p) = &project_id {
+        projects::register(&state.pool, std::slice::from_ref(p)).await?;
+    }
+    let row: ModelDto = sqlx::query_as(&format!(
+        r#"
+UPDATE models SET
+  project_id = $2,
+  model_name = $3,
+  match_pattern = $4,
+  start_date = $5,
+  unit = $6,
+  input_price = $7,
+  output_price = $8,
+  total_price = $9,
+  updated_at = NOW()
+WHERE id = $1
+RETURNING {MODEL_COLUMNS}
+        "#
+    ))
+    .bind(model_id)
+    .bind(project_id)
+    .bind(req.model_name.trim())
+    .bind(&req.match_pattern)
+    .bind(req.start_date)
+    .bind(req.unit())
+    .bind(req.input_price)
+    .bind(req.output_price)
+    .bind(req.total_price)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    state.prices.invalidate();
+    Ok((StatusCode::OK, Json(row)))
+}
+
+/// Deletes a model. Costs already calculated with it are kept; see the
+/// `recompute_costs` admin job.
+pub(crate) async fn delete_model(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(model_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    check_writable(&state, &scope, model_id).await?;
+    sqlx::query("DELETE FROM models WHERE id = $1")
+        .bind(model_id)
+        .execute(&state.pool)
+        .await?;
+    state.prices.invalidate();
+    Ok((StatusCode::OK, Json(serde_json::json!({ "id": model_id }))))
+}
@@ -0,0 +1,206 @@
+//! Network-level restrictions per route group: source IP allowlists and, for
+//! ingest, client certificates presented over mutual TLS (see [`crate::http::tls`]).
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use crate::http::error::ApiError;
+
+/// Route groups that can be restricted separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RouteGroup {
+    /// Public query endpoints.
+    Query,
+    /// Trace / metrics / score writes and the Langfuse/OTLP compat endpoints.
+    Ingest,
+    /// `/api/admin/*`.
+    Admin,
+}
+
+impl RouteGroup {
+    fn as_str(self) -> &'static str {
+        match self {
+            RouteGroup::Query => "query",
+            RouteGroup::Ingest => "ingest",
+            RouteGroup::Admin => "admin",
+        }
+    }
+}
+
+/// CIDR ranges (or single addresses) a client address must fall in.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IpAllowlist {
+    nets: Vec<IpNet>,
+}
+
+impl IpAllowlist {
+    /// Parses comma or whitespace separated entries (at least one), e.g.
+    /// `10.0.0.0/8, 192.168.1.7, ::1`.
+    pub(crate) fn parse(spec: &str) -> Result<Self, String> {
+        let nets = spec
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<IpNet>()
+                    .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("invalid CIDR or IP address: {s:?}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if nets.is_empty() {
+            return Err("empty IP allowlist".to_string());
+        }
+        Ok(Self { nets })
+    }
+
+    pub(crate) fn allows(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// How ingest endpoints treat a verified client certificate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ClientCertMode {
+    /// Certificates are not considered.
+    #[default]
+    Off,
+    /// A certificate is required in addition to credentials.
+    Required,
+    /// A certificate authenticates requests that carry no credentials.
+    Sufficient,
+}
+
+impl ClientCertMode {
+    pub(crate) fn parse(spec: &str) -> Result<Self, String> {
+        match spec.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "required" => Ok(Self::Required),
+            "sufficient" => Ok(Self::Sufficient),
+            other => Err(format!(
+                "invalid client certificate mode: {other:?} (expected off, required or sufficient)"
+            )),
+        }
+    }
+}
+
+/// Set on requests whose connection presented a verified client certificate.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClientCertificate;
+
+/// Set by [`network_policy`] when the client certificate stands in for
+/// credentials ([`ClientCertMode::Sufficient`]).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CertAuthenticated;
+
+/// Restrictions of one route group.
+#[derive(Debug)]
+pub(crate) struct GroupPolicy {
+    group: RouteGroup,
+    allowlist: Option<IpAllowlist>,
+    /// Proxies whose `X-Forwarded-For` entries are trusted.
+    trusted_proxies: Arc<IpAllowlist>,
+    client_cert: ClientCertMode,
+}
+
+impl GroupPolicy {
+    pub(crate) fn new(
+        group: RouteGroup,
+        allowlist: Option<IpAllowlist>,
+        trusted_proxies: Arc<IpAllowlist>,
+        client_cert: ClientCertMode,
+    ) -> Self {
+        Self {
+            group,
+            allowlist,
+            trusted_proxies,
+            client_cert,
+        }
+    }
+
+    /// Checks a request; `Ok(true)` when its client certificate authenticates it.
+    pub(crate) fn check(
+        &self,
+        peer: Option<IpAddr>,
+        headers: &HeaderMap,
+        has_client_cert: bool,
+    ) -> Result<bool, ApiError> {
+        if let Some(allowlist) = &self.allowlist {
+            let client = peer.map(|p| client_ip(p, headers, &self.trusted_proxies));
+            if !client.is_some_and(|ip| allowlist.allows(ip)) {
+                tracing::debug!(
+                    group = self.group.as_str(),
+                    client = ?client,
+                    "source address not in allowlist"
+                );
+                return Err(ApiError::Forbidden(
+                    "source address is not allowed".to_string(),
+                ));
+            }
+        }
+        match self.client_cert {
+            ClientCertMode::Required if !has_client_cert => Err(ApiError::Forbidden(
+                "client certificate required".to_string(),
+            )),
+            ClientCertMode::Sufficient => Ok(has_client_cert),
+            _ => Ok(false),
+        }
+    }
+}
+
+/// The client address: the peer, or the rightmost `X-Forwarded-For` entry
+/// not added by a trusted proxy when the peer is one.
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &IpAllowlist) -> IpAddr {
+    if !trusted_proxies.allows(peer) {
+        return peer;
+    }
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|s| s.trim().parse::<IpAddr>().ok())
+        .collect::<Vec<_>>();
+    let mut client = peer;
+    for hop in forwarded.into_iter().rev() {
+        match hop {
+            Some(ip) => {
+                client = ip;
+                if !trusted_proxies.allows(ip) {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+    client
+}
+
+/// Applies a [`GroupPolicy`]; runs before [`crate::http::auth::auth`].
+pub(crate) async fn network_policy(
+    State(policy): State<Arc<GroupPolicy>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0.ip());
+    let has_cert = request.extensions().get::<ClientCertificate>().is_some();
+    match policy.check(peer, request.headers(), has_cert) {
+        Ok(true) => {
+            request.extensions_mut().insert(CertAuthenticated);
+        }
+        Ok(false) => {}
+        Err(err) => return err.into_response(),
+    }
+    next.run(request).await
+}
@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::QueryBuilder;
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        common::{PageMeta, PagedData},
+        counts::{self, CountGroupBy, CountQuery},
+        error::ApiError,
+        traces::{
+            observation_to_dto, push_search_match, rehydrate_observations, search_query,
+            ObservationRow, OBSERVATION_COLUMNS,
+        },
+    },
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ObservationListQuery {
+    #[serde(default)]
+    page: Option<i64>,
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default, rename = "traceId")]
+    trace_id: Option<Uuid>,
+    #[serde(default, rename = "parentObservationId")]
+    parent_observation_id: Option<Uuid>,
+    #[serde(default)]
+    name: Option<String>,
+    /// `GENERATION`, `SPAN`, `EVENT`, ...
+    #[serde(default, rename = "type")]
+    r#type: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    /// `DEBUG`, `DEFAULT`, `WARNING` or `ERROR`.
+    #[serde(default)]
+    level: Option<String>,
+    #[serde(default)]
+    environment: Option<String>,
+    #[serde(default, rename = "fromStartTime")]
+    from_start_time: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toStartTime")]
+    to_start_time: Option<DateTime<Utc>>,
+    /// Full-text search over name, input, output and metadata.
+    #[serde(default, rename = "q", alias = "searchQuery")]
+    search: Option<String>,
+}
+
+fn push_observation_filters(
+    builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    project_id: &str,
+    q: &ObservationListQuery,
+) {
+    builder.push(" WHERE project_id = ");
+    builder.push_bind(project_id.to_string());
+    if let Some(trace_id) = q.trace_id {
+        builder.push(" AND trace_id = ");
+        builder.push_bind(trace_id);
+    }
+    if let Some(parent) = q.parent_observation_id {
+        builder.push(" AND parent_observation_id = ");
+        builder.push_bind(parent);
+    }
+    if let Some(name) = &q.name {
+        builder.push(" AND name = ");
+        builder.push_bind(name.clone());
+    }
+    if let Some(r#type) = &q.r#type {
+        builder.push(" AND type = ");
+        builder.push_bind(r#type.to_ascii_uppercase());
+    }
+    if let Some(model) = &q.model {
+        builder.push(" AND model = ");
+        builder.push_bind(model.clone());
+    }
+    if let Some(level) = &q.level {
+        builder.push(" AND level = ");
+        builder.push_bind(level.to_ascii_uppercase());
+    }
+    if let Some(environment) = &q.environment {
+        builder.push(" AND environment = ");
+        builder.push_bind(environment.clone());
+    }
+    if let Some(from) = q.from_start_time {
+        builder.push(" AND start_time >= ");
+        builder.push_bind(from);
+    }
+    if let Some(to) = q.to_start_time {
+        builder.push(" AND start_time <= ");
+        builder.push_bind(to);
+    }
+    if let Some(search) = search_query(q.search.as_deref()) {
+        builder.push(" AND ");
+        push_search_match(builder, "", search);
+    }
+}
+
+fn observation_count_group(group_by: &str) -> Option<CountGroupBy> {
+    Some(CountGroupBy::column(match group_by {
+        "name" => "name",
+        "type" => "type",
+        "model" => "model",
+        "environment" => "environment",
+        "level" => "level",
+        "provider" => "provider",
+        "serviceName" => "service_name",
+        _ => return None,
+    }))
+}
+
+/// Number of observations matching the list filters, optionally per `groupBy`.
+pub(crate) async fn count_observations(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(mut q): Query<ObservationListQuery>,
+    Query(c): Query<CountQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.from_start_time = state
+        .query_windows
+        .list_from(q.from_start_time, q.to_start_time);
+    let group = match c.group_by.as_deref() {
+        None => None,
+        Some(g) => Some(observation_count_group(g).ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "invalid groupBy: {g} (expected name, type, model, environment, level, provider or serviceName)"
+            ))
+        })?),
+    };
+    let filters = |b: &mut QueryBuilder<'_, sqlx::Postgres>| {
+        push_observation_filters(b, scope.project_id(), &q);
+    };
+    let resp = counts::count(&state.pool, "observations", &c, group, &filters).await?;
+    Ok((StatusCode::OK, Json(resp)))
+}
+
+/// Paginated observations across traces, newest first.
+pub(crate) async fn get_observations(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(mut q): Query<ObservationListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.from_start_time = state
+        .query_windows
+        .list_from(q.from_start_time, q.to_start_time);
+    let page = q.page.unwrap_or(1).max(1);
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * limit;
+    let project_id = scope.project_id();
+
+    let mut count_builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new("SELECT COUNT(*)::BIGINT FROM observations");
+    push_observation_filters(&mut count_builder, project_id, &q);
+    let total_items: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&state.pool)
+        .await?;
+
+    let total_pages = if total_items == 0 {
+        0
+    } else {
+        (total_items + limit - 1) / limit
+    };
+
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new(format!("SELECT {OBSERVATION_COLUMNS} FROM observations"));
+    push_observation_filters(&mut builder, project_id, &q);
+    builder.push(" ORDER BY start_time DESC NULLS LAST, created_at DESC, id LIMIT ");
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+
+    let rows: Vec<ObservationRow> = builder.build_query_as().fetch_all(&state.pool).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data: rows.into_iter().map(observation_to_dto).collect::<Vec<_>>(),
+            meta: PageMeta {
+                page,
+                limit,
+                totalItems: total_items,
+                totalPages: total_pages,
+            },
+        }),
+    ))
+}
+
+pub(crate) async fn get_observation(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(observation_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row: Option<ObservationRow> = sqlx::query_as(&format!(
+        "SELECT {OBSERVATION_COLUMNS} FROM observations WHERE project_id = $1 AND id = $2"
+    ))
+    .bind(scope.project_id())
+    .bind(observation_id)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    let mut row = row.ok_or(ApiError::NotFound)?;
+    rehydrate_observations(&state, std::slice::from_mut(&mut row)).await;
+    Ok((StatusCode::OK, Json(observation_to_dto(row))))
+}
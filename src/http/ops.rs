@@ -1,7 +1,12 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::Ordering;
 
-use crate::state::AppState;
+use crate::{
+    http::{error::ApiError, telemetry::THROUGHPUT_MINUTES},
+    state::{AppState, WriteMode},
+};
 
 pub(crate) async fn get_rate_limit_stats(State(state): State<AppState>) -> impl IntoResponse {
     let total_allowed = state.rate_limit_stats.total_allowed.load(Ordering::Relaxed);
@@ -16,7 +21,7 @@ pub(crate) async fn get_rate_limit_stats(State(state): State<AppState>) -> impl
         .iter()
         .map(|entry| (entry.key().clone(), *entry.value()))
         .collect();
-    per_token.sort_by(|a, b| b.1.cmp(&a.1));
+    per_token.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     let top = per_token
         .into_iter()
@@ -35,7 +40,152 @@ pub(crate) async fn get_rate_limit_stats(State(state): State<AppState>) -> impl
             0.0
         },
         "top_rejected_tokens": top,
+        "ingest_cost": state.ingest_limiter.as_ref().map(|l| serde_json::json!({
+            "units_per_sec": l.units_per_sec(),
+            "burst": l.burst(),
+            "bytes_per_unit": l.bytes_per_unit(),
+            "charged_units": l.charged_units.load(Ordering::Relaxed),
+            "rejected": l.rejected.load(Ordering::Relaxed),
+        })),
     });
 
     (StatusCode::OK, Json(body))
 }
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WriteModeRequest {
+    /// `normal`, `read_only` or `drain`.
+    mode: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WriteModeResponse {
+    mode: &'static str,
+    /// Payloads accepted but not yet written to the database.
+    pending_writes: u64,
+    /// True once writes are paused and every accepted payload has been written.
+    drained: bool,
+}
+
+fn write_mode_response(state: &AppState) -> Json<WriteModeResponse> {
+    let mode = state.write_gate.mode();
+    let pending_writes = state.write_gate.pending();
+    Json(WriteModeResponse {
+        mode: mode.as_str(),
+        pending_writes,
+        drained: mode != WriteMode::Normal && pending_writes == 0,
+    })
+}
+
+pub(crate) async fn get_write_mode(State(state): State<AppState>) -> impl IntoResponse {
+    (StatusCode::OK, write_mode_response(&state))
+}
+
+/// Switches between normal, read-only and drain mode. Poll `GET` until
+/// `drained` before shutting down or running maintenance.
+pub(crate) async fn put_write_mode(
+    State(state): State<AppState>,
+    Json(req): Json<WriteModeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mode = WriteMode::parse(&req.mode).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "invalid mode: {} (expected normal, read_only or drain)",
+            req.mode
+        ))
+    })?;
+    let previous = state.write_gate.mode();
+    state.write_gate.set_mode(mode);
+    if previous != mode {
+        tracing::warn!(
+            from = previous.as_str(),
+            to = mode.as_str(),
+            "write mode changed"
+        );
+    }
+    Ok((StatusCode::OK, write_mode_response(&state)))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct TableStats {
+    name: String,
+    /// Live rows as estimated by the statistics collector.
+    rows: i64,
+    /// Size on disk including indexes and TOAST.
+    bytes: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MinuteStats {
+    minute: DateTime<Utc>,
+    batches: u64,
+    traces: u64,
+    observations: u64,
+}
+
+/// Row counts and sizes per table, ingest throughput and queue state, for a
+/// quick look at an instance without psql. Queue and throughput figures
+/// cover this instance only.
+pub(crate) async fn get_stats(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let database_bytes: i64 = sqlx::query_scalar("SELECT pg_database_size(current_database())")
+        .fetch_one(&state.pool)
+        .await?;
+    // Partitions count towards the table they belong to.
+    let tables: Vec<TableStats> = sqlx::query_as(
+        r#"
+        SELECT COALESCE(parent.relname, s.relname)::TEXT AS name,
+               SUM(s.n_live_tup)::BIGINT AS rows,
+               SUM(pg_total_relation_size(s.relid))::BIGINT AS bytes
+        FROM pg_stat_user_tables s
+        LEFT JOIN pg_inherits i ON i.inhrelid = s.relid
+        LEFT JOIN pg_class parent ON parent.oid = i.inhparent
+        WHERE s.schemaname = current_schema()
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let per_minute: Vec<_> = state
+        .telemetry
+        .recent_writes()
+        .into_iter()
+        .map(|m| MinuteStats {
+            minute: DateTime::from_timestamp(m.minute as i64 * 60, 0).unwrap_or_default(),
+            batches: m.batches,
+            traces: m.traces,
+            observations: m.observations,
+        })
+        .collect();
+    let metrics_capacity = state.metrics_tx.max_capacity();
+    let body = serde_json::json!({
+        "database": {
+            "sizeBytes": database_bytes,
+            "tables": tables,
+        },
+        "ingest": {
+            "queueDepth": state.ingest_queue.depths().into_iter().sum::<usize>(),
+            "queueCapacity": state.ingest_queue.capacity(),
+            "metricsQueueDepth": metrics_capacity - state.metrics_tx.capacity(),
+            "metricsQueueCapacity": metrics_capacity,
+            "pendingWrites": state.write_gate.pending(),
+            "oldestPendingSecs": state
+                .telemetry
+                .oldest_pending_ingest()
+                .map(|age| age.as_secs_f64()),
+            "throughput": {
+                "windowMinutes": THROUGHPUT_MINUTES,
+                "traces": per_minute.iter().map(|m| m.traces).sum::<u64>(),
+                "observations": per_minute.iter().map(|m| m.observations).sum::<u64>(),
+                "perMinute": per_minute,
+            },
+        },
+        "writeMode": state.write_gate.mode().as_str(),
+    });
+    Ok((StatusCode::OK, Json(body)))
+}
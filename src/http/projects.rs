@@ -1,39 +1,81 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use chrono::Utc;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use serde_json::Value as JsonValue;
+use sqlx::PgExecutor;
 
-use crate::state::AppState;
+use crate::{
+    http::{auth::ProjectScope, error::ApiError},
+    state::AppState,
+};
 
 #[derive(Debug, Serialize)]
 struct ProjectsResponse {
     data: Vec<Project>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
 struct Project {
     id: String,
     name: String,
-    created_at: String,
-    updated_at: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    metadata: Option<JsonValue>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    metadata: JsonValue,
 }
 
-pub(crate) async fn get_projects(State(state): State<AppState>) -> impl IntoResponse {
-    let now = Utc::now().to_rfc3339();
-    let project_id = state.default_project_id.as_ref().to_string();
-    (
-        StatusCode::OK,
-        Json(ProjectsResponse {
-            data: vec![Project {
-                id: project_id.clone(),
-                name: project_id,
-                created_at: now.clone(),
-                updated_at: now,
-                metadata: Some(JsonValue::Object(serde_json::Map::new())),
-            }],
-        }),
+/// Makes sure `project_ids` exist in `projects` (named after their id).
+pub(crate) async fn register<'e>(
+    executor: impl PgExecutor<'e>,
+    project_ids: &[String],
+) -> Result<(), sqlx::Error> {
+    if project_ids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+INSERT INTO projects (id, name)
+SELECT p, p FROM UNNEST($1::TEXT[]) AS p
+ON CONFLICT (id) DO NOTHING
+        "#,
     )
+    .bind(project_ids)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// The key's project for project keys; every project (the request's default
+/// first) for instance-wide keys. Langfuse SDKs read the first entry.
+pub(crate) async fn get_projects(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows: Vec<Project> = sqlx::query_as(
+        r#"
+SELECT id, name, created_at, updated_at, COALESCE(metadata, '{}'::JSONB) AS metadata
+FROM projects
+WHERE id = $1 OR NOT $2
+ORDER BY id = $1 DESC, id
+        "#,
+    )
+    .bind(scope.project_id())
+    .bind(scope.is_bound())
+    .fetch_all(&state.pool)
+    .await?;
+
+    // Keys file projects are registered on reload; this only covers a race.
+    let data = if rows.is_empty() {
+        let now = Utc::now();
+        vec![Project {
+            id: scope.project_id().to_string(),
+            name: scope.project_id().to_string(),
+            created_at: now,
+            updated_at: now,
+            metadata: JsonValue::Object(serde_json::Map::new()),
+        }]
+    } else {
+        rows
+    };
+    Ok((StatusCode::OK, Json(ProjectsResponse { data })))
 }
@@ -0,0 +1,398 @@
+//! Monthly usage quotas per project, managed through `/api/admin/quotas`:
+//! traces ingested and tokens recorded per calendar month (UTC).
+//!
+//! Usage is counted at ingest: every ingest transaction adds the traces it
+//! creates and the tokens it records to the month's `project_usage` row, so
+//! deleting data does not free quota. Every `XTRACE_QUOTA_INTERVAL_SECS` (and
+//! when a quota is set) the projects at or over a limit are loaded into
+//! memory, and the HTTP ingest endpoints reject their payloads with `402` and
+//! `X-Xtrace-Quota-*` headers until the month ends or the quota is raised.
+//! Enforcement is soft: a project can go over its limit by what it ingests
+//! until the next check.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderName, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    http::{error::ApiError, projects},
+    state::AppState,
+};
+
+/// A limit a project has reached; the `402` response of its ingest requests.
+#[derive(Debug, Clone)]
+pub struct QuotaExceeded {
+    pub project_id: String,
+    /// `traces` or `tokens`.
+    pub metric: &'static str,
+    pub limit: i64,
+    pub used: i64,
+    pub resets_at: DateTime<Utc>,
+}
+
+impl QuotaExceeded {
+    pub(crate) fn message(&self) -> String {
+        format!(
+            "Monthly {} quota of project {} exceeded ({} of {})",
+            self.metric, self.project_id, self.used, self.limit
+        )
+    }
+
+    pub(crate) fn headers(&self) -> [(HeaderName, String); 5] {
+        let retry_after = (self.resets_at - Utc::now()).num_seconds().max(1);
+        [
+            (
+                HeaderName::from_static("x-xtrace-quota-metric"),
+                self.metric.to_string(),
+            ),
+            (
+                HeaderName::from_static("x-xtrace-quota-limit"),
+                self.limit.to_string(),
+            ),
+            (
+                HeaderName::from_static("x-xtrace-quota-used"),
+                self.used.to_string(),
+            ),
+            (
+                HeaderName::from_static("x-xtrace-quota-reset"),
+                self.resets_at.to_rfc3339(),
+            ),
+            (header::RETRY_AFTER, retry_after.to_string()),
+        ]
+    }
+}
+
+/// Projects over quota as of the last count.
+#[derive(Default)]
+pub(crate) struct QuotaStore {
+    exceeded: RwLock<HashMap<String, QuotaExceeded>>,
+}
+
+impl QuotaStore {
+    /// Fails for the first of `project_ids` that is over quota.
+    pub(crate) fn check<'a>(
+        &self,
+        project_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), ApiError> {
+        let exceeded = self.exceeded.read().unwrap();
+        if exceeded.is_empty() {
+            return Ok(());
+        }
+        for project_id in project_ids {
+            if let Some(q) = exceeded.get(project_id) {
+                if q.resets_at > Utc::now() {
+                    return Err(ApiError::QuotaExceeded(q.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Start of the month of `now` and of the next one.
+fn month_bounds(now: DateTime<Utc>) -> (NaiveDate, DateTime<Utc>, DateTime<Utc>) {
+    let month = NaiveDate::from_ymd_opt(now.year(), now.month(), 1).expect("valid date");
+    let next = month
+        .checked_add_months(chrono::Months::new(1))
+        .expect("valid date");
+    let at = |d: NaiveDate| Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0).expect("midnight"));
+    (month, at(month), at(next))
+}
+
+/// Usage added by an ingest transaction: traces created and tokens recorded
+/// per project.
+#[derive(Debug, Default)]
+pub(crate) struct UsageDelta(BTreeMap<String, (i64, i64)>);
+
+impl UsageDelta {
+    /// Adds the difference between two [`usage_of`] results.
+    pub(crate) fn record(
+        &mut self,
+        before: Vec<(String, i64, i64)>,
+        after: Vec<(String, i64, i64)>,
+    ) {
+        for (project_id, traces, tokens) in before {
+            let usage = self.0.entry(project_id).or_default();
+            usage.0 -= traces;
+            usage.1 -= tokens;
+        }
+        for (project_id, traces, tokens) in after {
+            let usage = self.0.entry(project_id).or_default();
+            usage.0 += traces;
+            usage.1 += tokens;
+        }
+    }
+}
+
+/// Per project, how many of `trace_ids` exist and the tokens of
+/// `observation_ids`.
+pub(crate) async fn usage_of(
+    conn: &mut PgConnection,
+    trace_ids: &[Uuid],
+    observation_ids: &[Uuid],
+) -> Result<Vec<(String, i64, i64)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT project_id, SUM(traces)::BIGINT, SUM(tokens)::BIGINT
+FROM (
+  SELECT project_id, 1 AS traces, 0 AS tokens FROM traces WHERE id = ANY($1)
+  UNION ALL
+  SELECT project_id, 0, COALESCE(total_tokens, 0) FROM observations WHERE id = ANY($2)
+) u
+GROUP BY project_id
+        "#,
+    )
+    .bind(trace_ids)
+    .bind(observation_ids)
+    .fetch_all(conn)
+    .await
+}
+
+/// Adds `delta` to this month's usage. Rows are locked in project order, so
+/// concurrent workers do not deadlock.
+pub(crate) async fn record_usage(
+    conn: &mut PgConnection,
+    delta: UsageDelta,
+) -> Result<(), sqlx::Error> {
+    let (projects, (traces, tokens)): (Vec<String>, (Vec<i64>, Vec<i64>)) = delta
+        .0
+        .into_iter()
+        .filter(|(_, usage)| *usage != (0, 0))
+        .unzip();
+    if projects.is_empty() {
+        return Ok(());
+    }
+    let (month, _, _) = month_bounds(Utc::now());
+    sqlx::query(
+        r#"
+INSERT INTO project_usage (project_id, month, traces, tokens)
+SELECT d.project_id, $1, d.traces, d.tokens
+FROM UNNEST($2::TEXT[], $3::BIGINT[], $4::BIGINT[]) WITH ORDINALITY AS d(project_id, traces, tokens, n)
+ORDER BY d.n
+ON CONFLICT (project_id, month) DO UPDATE SET
+  traces = project_usage.traces + EXCLUDED.traces,
+  tokens = project_usage.tokens + EXCLUDED.tokens,
+  updated_at = NOW()
+        "#,
+    )
+    .bind(month)
+    .bind(projects)
+    .bind(traces)
+    .bind(tokens)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct UsageRow {
+    project_id: String,
+    traces: i64,
+    tokens: i64,
+    monthly_traces: Option<i64>,
+    monthly_tokens: Option<i64>,
+}
+
+/// Reloads the projects over quota this month into `store`.
+pub(crate) async fn account(pool: &PgPool, store: &QuotaStore) -> Result<(), sqlx::Error> {
+    let (month, _, resets_at) = month_bounds(Utc::now());
+    let rows: Vec<UsageRow> = sqlx::query_as(
+        r#"
+SELECT
+  q.project_id, COALESCE(u.traces, 0) AS traces, COALESCE(u.tokens, 0) AS tokens,
+  q.monthly_traces, q.monthly_tokens
+FROM project_quotas q
+LEFT JOIN project_usage u ON u.project_id = q.project_id AND u.month = $1
+        "#,
+    )
+    .bind(month)
+    .fetch_all(pool)
+    .await?;
+
+    let mut exceeded = HashMap::new();
+    for row in rows {
+        let over = [
+            ("traces", row.monthly_traces, row.traces),
+            ("tokens", row.monthly_tokens, row.tokens),
+        ]
+        .into_iter()
+        .find_map(|(metric, limit, used)| {
+            limit
+                .filter(|limit| used >= *limit)
+                .map(|limit| (metric, limit, used))
+        });
+        if let Some((metric, limit, used)) = over {
+            exceeded.insert(
+                row.project_id.clone(),
+                QuotaExceeded {
+                    project_id: row.project_id,
+                    metric,
+                    limit,
+                    used,
+                    resets_at,
+                },
+            );
+        }
+    }
+    *store.exceeded.write().unwrap() = exceeded;
+    Ok(())
+}
+
+pub(crate) async fn quota_worker(pool: PgPool, store: Arc<QuotaStore>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if let Err(err) = account(&pool, &store).await {
+            tracing::error!(error = %err, "quotas: accounting failed");
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuotaRequest {
+    #[serde(default)]
+    monthly_traces: Option<i64>,
+    #[serde(default)]
+    monthly_tokens: Option<i64>,
+}
+
+impl QuotaRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.monthly_traces.is_none() && self.monthly_tokens.is_none() {
+            return Err(ApiError::BadRequest(
+                "one of monthlyTraces or monthlyTokens is required".to_string(),
+            ));
+        }
+        if self.monthly_traces.is_some_and(|l| l < 0) || self.monthly_tokens.is_some_and(|l| l < 0)
+        {
+            return Err(ApiError::BadRequest(
+                "quota limits must not be negative".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct QuotaDto {
+    project_id: String,
+    monthly_traces: Option<i64>,
+    monthly_tokens: Option<i64>,
+    /// First day of the current month.
+    month: NaiveDate,
+    /// Usage this month as of `accountedAt`; 0 before anything was ingested.
+    traces: i64,
+    tokens: i64,
+    accounted_at: Option<DateTime<Utc>>,
+    /// `traces` or `tokens` when a limit is reached.
+    exceeded: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+const QUOTA_SELECT: &str = r#"
+SELECT
+  q.project_id, q.monthly_traces, q.monthly_tokens, $1::DATE AS month,
+  COALESCE(u.traces, 0) AS traces, COALESCE(u.tokens, 0) AS tokens, u.updated_at AS accounted_at,
+  CASE
+    WHEN q.monthly_traces IS NOT NULL AND COALESCE(u.traces, 0) >= q.monthly_traces THEN 'traces'
+    WHEN q.monthly_tokens IS NOT NULL AND COALESCE(u.tokens, 0) >= q.monthly_tokens THEN 'tokens'
+  END AS exceeded,
+  q.created_at, q.updated_at
+FROM project_quotas q
+LEFT JOIN project_usage u ON u.project_id = q.project_id AND u.month = $1
+"#;
+
+async fn fetch_quota(pool: &PgPool, project_id: &str) -> Result<Option<QuotaDto>, sqlx::Error> {
+    let (month, _, _) = month_bounds(Utc::now());
+    sqlx::query_as(&format!("{QUOTA_SELECT} WHERE q.project_id = $2"))
+        .bind(month)
+        .bind(project_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub(crate) async fn get_quotas(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (month, _, _) = month_bounds(Utc::now());
+    let data: Vec<QuotaDto> = sqlx::query_as(&format!("{QUOTA_SELECT} ORDER BY q.project_id"))
+        .bind(month)
+        .fetch_all(&state.pool)
+        .await?;
+    Ok((StatusCode::OK, Json(serde_json::json!({ "data": data }))))
+}
+
+pub(crate) async fn get_quota(
+    State(state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row = fetch_quota(&state.pool, &project_id).await?;
+    Ok((StatusCode::OK, Json(row.ok_or(ApiError::NotFound)?)))
+}
+
+/// Sets a project's limits (an omitted limit is unlimited) and checks usage
+/// against them, so the limits apply on this instance right away.
+pub(crate) async fn put_quota(
+    State(state): State<AppState>,
+    Path(project_id): Path<String>,
+    Json(req): Json<QuotaRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    req.validate()?;
+    projects::register(&state.pool, std::slice::from_ref(&project_id)).await?;
+    sqlx::query(
+        r#"
+INSERT INTO project_quotas (project_id, monthly_traces, monthly_tokens)
+VALUES ($1, $2, $3)
+ON CONFLICT (project_id) DO UPDATE SET
+  monthly_traces = EXCLUDED.monthly_traces,
+  monthly_tokens = EXCLUDED.monthly_tokens,
+  updated_at = NOW()
+        "#,
+    )
+    .bind(&project_id)
+    .bind(req.monthly_traces)
+    .bind(req.monthly_tokens)
+    .execute(&state.pool)
+    .await?;
+    account(&state.pool, &state.quotas).await?;
+    let row = fetch_quota(&state.pool, &project_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok((StatusCode::OK, Json(row)))
+}
+
+/// Removes a project's limits. Its usage history is kept.
+pub(crate) async fn delete_quota(
+    State(state): State<AppState>,
+    Path(project_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let deleted = sqlx::query("DELETE FROM project_quotas WHERE project_id = $1")
+        .bind(&project_id)
+        .execute(&state.pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError::NotFound);
+    }
+    account(&state.pool, &state.quotas).await?;
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({ "projectId": project_id })),
+    ))
+}
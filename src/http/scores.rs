@@ -0,0 +1,721 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::{PgPool, QueryBuilder};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        common::{PageMeta, PagedData},
+        error::ApiError,
+        metrics::parse_timezone,
+    },
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ScoreMetricsQuery {
+    #[serde(default)]
+    name: Option<String>,
+    /// `API`, `EVAL` or `ANNOTATION`.
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default, rename = "traceName")]
+    trace_name: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default, rename = "promptVersion")]
+    prompt_version: Option<String>,
+    #[serde(default, rename = "fromTimestamp")]
+    from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toTimestamp")]
+    to_timestamp: Option<DateTime<Utc>>,
+    /// `hour`, `day` (default), `week` or `month`.
+    #[serde(default)]
+    granularity: Option<String>,
+    #[serde(default)]
+    timezone: Option<String>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ScoreMetricsRow {
+    bucket: NaiveDateTime,
+    name: String,
+    data_type: String,
+    count: i64,
+    avg: Option<f64>,
+    p50: Option<f64>,
+    min: Option<f64>,
+    max: Option<f64>,
+    distribution: JsonValue,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScoreMetricsItem {
+    bucket: String,
+    name: String,
+    data_type: String,
+    count: i64,
+    avg: Option<f64>,
+    p50: Option<f64>,
+    min: Option<f64>,
+    max: Option<f64>,
+    /// Counts per category (`stringValue`) for categorical and boolean scores.
+    distribution: JsonValue,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScoreMetricsResponse {
+    data: Vec<ScoreMetricsItem>,
+    granularity: &'static str,
+    timezone: String,
+    from_timestamp: DateTime<Utc>,
+    to_timestamp: DateTime<Utc>,
+}
+
+/// Score value aggregates per time bucket and score name.
+pub(crate) async fn get_score_metrics(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<ScoreMetricsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let granularity = match q.granularity.as_deref() {
+        None | Some("") | Some("day") => "day",
+        Some("hour") => "hour",
+        Some("week") => "week",
+        Some("month") => "month",
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "invalid granularity: {other} (expected hour, day, week or month)"
+            )))
+        }
+    };
+    let timezone = parse_timezone(q.timezone.as_deref())?;
+    let source = q.source.as_deref().map(parse_source).transpose()?;
+
+    let to_ts = q.to_timestamp.unwrap_or_else(Utc::now);
+    let from_ts = q
+        .from_timestamp
+        .unwrap_or_else(|| to_ts - chrono::Duration::days(30));
+    if from_ts > to_ts {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp must be <= toTimestamp".to_string(),
+        ));
+    }
+    state
+        .query_windows
+        .check_range(&scope, from_ts, to_ts, q.ignore_range_limit)?;
+
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new("WITH base AS (SELECT date_trunc('");
+    builder.push(granularity);
+    builder.push("', s.\"timestamp\" AT TIME ZONE ");
+    builder.push_bind(timezone.clone());
+    builder.push(
+        ") AS bucket, s.name, s.data_type, s.value, s.string_value FROM scores s WHERE s.project_id = ",
+    );
+    builder.push_bind(scope.project_id().to_string());
+    builder.push(" AND s.\"timestamp\" >= ");
+    builder.push_bind(from_ts);
+    builder.push(" AND s.\"timestamp\" <= ");
+    builder.push_bind(to_ts);
+
+    if let Some(name) = &q.name {
+        builder.push(" AND s.name = ");
+        builder.push_bind(name.clone());
+    }
+    if let Some(source) = source {
+        builder.push(" AND s.source = ");
+        builder.push_bind(source);
+    }
+    if let Some(trace_name) = &q.trace_name {
+        builder.push(" AND EXISTS (SELECT 1 FROM traces t WHERE t.id = s.trace_id AND t.name = ");
+        builder.push_bind(trace_name.clone());
+        builder.push(")");
+    }
+    // Observation-level scores match their own observation; trace-level scores
+    // match any observation of the trace.
+    if q.model.is_some() || q.prompt_version.is_some() {
+        builder.push(
+            " AND EXISTS (SELECT 1 FROM observations o WHERE o.trace_id = s.trace_id AND (s.observation_id IS NULL OR o.id = s.observation_id)",
+        );
+        if let Some(model) = &q.model {
+            builder.push(" AND o.model = ");
+            builder.push_bind(model.clone());
+        }
+        if let Some(prompt_version) = &q.prompt_version {
+            builder.push(" AND o.prompt_version = ");
+            builder.push_bind(prompt_version.clone());
+        }
+        builder.push(")");
+    }
+
+    builder.push(
+        r#")
+, stats AS (
+  SELECT
+    bucket,
+    name,
+    data_type,
+    COUNT(*)::BIGINT AS count,
+    AVG(value)::DOUBLE PRECISION AS avg,
+    (percentile_cont(0.5) WITHIN GROUP (ORDER BY value))::DOUBLE PRECISION AS p50,
+    MIN(value)::DOUBLE PRECISION AS min,
+    MAX(value)::DOUBLE PRECISION AS max
+  FROM base
+  GROUP BY 1, 2, 3
+)
+, categories AS (
+  SELECT bucket, name, data_type, string_value, COUNT(*)::BIGINT AS cnt
+  FROM base
+  WHERE string_value IS NOT NULL
+  GROUP BY 1, 2, 3, 4
+)
+, dist AS (
+  SELECT bucket, name, data_type, jsonb_object_agg(string_value, cnt) AS distribution
+  FROM categories
+  GROUP BY 1, 2, 3
+)
+SELECT
+  st.bucket,
+  st.name,
+  st.data_type,
+  st.count,
+  st.avg,
+  st.p50,
+  st.min,
+  st.max,
+  COALESCE(d.distribution, '{}'::jsonb) AS distribution
+FROM stats st
+LEFT JOIN dist d
+  ON d.bucket = st.bucket AND d.name = st.name AND d.data_type = st.data_type
+ORDER BY st.bucket ASC, st.name ASC
+"#,
+    );
+
+    let rows: Vec<ScoreMetricsRow> = builder.build_query_as().fetch_all(&state.pool).await?;
+
+    let data = rows
+        .into_iter()
+        .map(|r| ScoreMetricsItem {
+            bucket: r.bucket.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            name: r.name,
+            data_type: r.data_type,
+            count: r.count,
+            avg: r.avg,
+            p50: r.p50,
+            min: r.min,
+            max: r.max,
+            distribution: r.distribution,
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(ScoreMetricsResponse {
+            data,
+            granularity,
+            timezone,
+            from_timestamp: from_ts,
+            to_timestamp: to_ts,
+        }),
+    ))
+}
+
+const SCORE_COLUMNS: &str = r#"id, project_id, trace_id, observation_id, name, source, data_type, value, string_value,
+  comment, author_user_id, api_key_id, metadata, config_id, queue_id, environment, "timestamp", created_at,
+  updated_at"#;
+
+#[derive(Debug, Deserialize)]
+#[allow(non_snake_case)]
+pub(crate) struct ScoreCreateRequest {
+    #[serde(default)]
+    id: Option<Uuid>,
+    traceId: Uuid,
+    #[serde(default)]
+    observationId: Option<Uuid>,
+    name: String,
+    /// Number for `NUMERIC`, string for `CATEGORICAL`, bool or 0/1 for `BOOLEAN`.
+    value: JsonValue,
+    /// Inferred from `value` when omitted.
+    #[serde(default)]
+    dataType: Option<String>,
+    /// `API` (default), `EVAL` or `ANNOTATION`.
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    comment: Option<String>,
+    #[serde(default)]
+    metadata: Option<JsonValue>,
+    #[serde(default)]
+    configId: Option<String>,
+    #[serde(default)]
+    queueId: Option<String>,
+    #[serde(default)]
+    authorUserId: Option<String>,
+    #[serde(default)]
+    environment: Option<String>,
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ScoreListQuery {
+    #[serde(default)]
+    page: Option<i64>,
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default, rename = "traceId")]
+    trace_id: Option<Uuid>,
+    #[serde(default, rename = "observationId")]
+    observation_id: Option<Uuid>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default, rename = "authorUserId")]
+    author_user_id: Option<String>,
+    /// Key that wrote the score (see `apiKeyId` on the score).
+    #[serde(default, rename = "apiKeyId")]
+    api_key_id: Option<String>,
+    #[serde(default, rename = "dataType")]
+    data_type: Option<String>,
+    /// User of the scored trace.
+    #[serde(default, rename = "userId")]
+    user_id: Option<String>,
+    #[serde(default, rename = "configId")]
+    config_id: Option<String>,
+    #[serde(default, rename = "queueId")]
+    queue_id: Option<String>,
+    #[serde(default)]
+    environment: Option<String>,
+    #[serde(default, rename = "fromTimestamp")]
+    from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toTimestamp")]
+    to_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ScoreRow {
+    id: Uuid,
+    project_id: String,
+    trace_id: Uuid,
+    observation_id: Option<Uuid>,
+    name: String,
+    source: String,
+    data_type: String,
+    value: Option<f64>,
+    string_value: Option<String>,
+    comment: Option<String>,
+    author_user_id: Option<String>,
+    api_key_id: Option<String>,
+    metadata: Option<JsonValue>,
+    config_id: Option<String>,
+    queue_id: Option<String>,
+    environment: String,
+    timestamp: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScoreV1Dto {
+    id: String,
+    project_id: String,
+    trace_id: String,
+    observation_id: Option<String>,
+    name: String,
+    source: String,
+    #[serde(rename = "dataType")]
+    data_type: String,
+    value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    string_value: Option<String>,
+    comment: Option<String>,
+    author_user_id: Option<String>,
+    /// Key that wrote the score; `null` when written without a key.
+    api_key_id: Option<String>,
+    metadata: JsonValue,
+    config_id: Option<String>,
+    queue_id: Option<String>,
+    environment: String,
+    timestamp: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<ScoreRow> for ScoreV1Dto {
+    fn from(r: ScoreRow) -> Self {
+        Self {
+            id: r.id.to_string(),
+            project_id: r.project_id,
+            trace_id: r.trace_id.to_string(),
+            observation_id: r.observation_id.map(|id| id.to_string()),
+            name: r.name,
+            source: r.source,
+            data_type: r.data_type,
+            value: r.value,
+            string_value: r.string_value,
+            comment: r.comment,
+            author_user_id: r.author_user_id,
+            api_key_id: r.api_key_id,
+            metadata: r.metadata.unwrap_or(JsonValue::Null),
+            config_id: r.config_id,
+            queue_id: r.queue_id,
+            environment: r.environment,
+            timestamp: r.timestamp,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+/// Validates `value` against `data_type` (inferred from the JSON type when
+/// omitted); returns `(data_type, value, string_value)`.
+fn resolve_value(
+    value: &JsonValue,
+    data_type: Option<&str>,
+) -> Result<(&'static str, Option<f64>, Option<String>), ApiError> {
+    let data_type = match data_type.map(str::to_ascii_uppercase).as_deref() {
+        Some("NUMERIC") => "NUMERIC",
+        Some("CATEGORICAL") => "CATEGORICAL",
+        Some("BOOLEAN") => "BOOLEAN",
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "invalid dataType: {other} (expected NUMERIC, CATEGORICAL or BOOLEAN)"
+            )))
+        }
+        None => match value {
+            JsonValue::Bool(_) => "BOOLEAN",
+            JsonValue::String(_) => "CATEGORICAL",
+            _ => "NUMERIC",
+        },
+    };
+
+    let invalid = || ApiError::BadRequest(format!("invalid value for {data_type} score: {value}"));
+    match (data_type, value) {
+        ("NUMERIC", JsonValue::Number(n)) => Ok((data_type, n.as_f64(), None)),
+        ("CATEGORICAL", JsonValue::String(s)) => Ok((data_type, None, Some(s.clone()))),
+        ("CATEGORICAL", JsonValue::Number(n)) => Ok((data_type, n.as_f64(), Some(n.to_string()))),
+        ("BOOLEAN", v) => {
+            let b = match v {
+                JsonValue::Bool(b) => *b,
+                JsonValue::Number(n) if n.as_f64() == Some(1.0) => true,
+                JsonValue::Number(n) if n.as_f64() == Some(0.0) => false,
+                _ => return Err(invalid()),
+            };
+            let label = if b { "True" } else { "False" };
+            Ok((
+                data_type,
+                Some(f64::from(u8::from(b))),
+                Some(label.to_string()),
+            ))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Validates a score `source` (case-insensitive).
+fn parse_source(source: &str) -> Result<&'static str, ApiError> {
+    match source.to_ascii_uppercase().as_str() {
+        "API" => Ok("API"),
+        "EVAL" => Ok("EVAL"),
+        "ANNOTATION" => Ok("ANNOTATION"),
+        other => Err(ApiError::BadRequest(format!(
+            "invalid source: {other} (expected API, EVAL or ANNOTATION)"
+        ))),
+    }
+}
+
+/// Creates or replaces (by `id`) a score on a trace or observation. The trace
+/// does not have to be ingested yet.
+pub(crate) async fn post_score(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Json(req): Json<ScoreCreateRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = insert_score(&state, &scope, req).await?;
+    Ok((StatusCode::OK, Json(serde_json::json!({ "id": id }))))
+}
+
+/// Validates and upserts one score; shared with the Langfuse ingestion endpoint.
+pub(crate) async fn insert_score(
+    state: &AppState,
+    scope: &ProjectScope,
+    req: ScoreCreateRequest,
+) -> Result<Uuid, ApiError> {
+    if req.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".to_string()));
+    }
+    let (data_type, value, string_value) = resolve_value(&req.value, req.dataType.as_deref())?;
+    let source = req.source.as_deref().map_or(Ok("API"), parse_source)?;
+    let id = req.id.unwrap_or_else(Uuid::new_v4);
+    // The score lands in its trace's project.
+    scope.check_trace(&state.pool, req.traceId).await?;
+
+    sqlx::query(
+        r#"
+INSERT INTO scores (
+  id, project_id, trace_id, observation_id, name, source, data_type, value, string_value,
+  comment, author_user_id, metadata, config_id, queue_id, environment, "timestamp", api_key_id
+) VALUES (
+  $1,
+  COALESCE((SELECT project_id FROM traces WHERE id = $3), $2),
+  $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14,
+  COALESCE($15, (SELECT environment FROM traces WHERE id = $3), 'default'),
+  COALESCE($16, NOW()),
+  $17
+)
+ON CONFLICT (id) DO UPDATE SET
+  project_id = EXCLUDED.project_id,
+  trace_id = EXCLUDED.trace_id,
+  observation_id = EXCLUDED.observation_id,
+  name = EXCLUDED.name,
+  source = EXCLUDED.source,
+  data_type = EXCLUDED.data_type,
+  value = EXCLUDED.value,
+  string_value = EXCLUDED.string_value,
+  comment = EXCLUDED.comment,
+  author_user_id = EXCLUDED.author_user_id,
+  metadata = EXCLUDED.metadata,
+  config_id = EXCLUDED.config_id,
+  queue_id = EXCLUDED.queue_id,
+  environment = EXCLUDED.environment,
+  "timestamp" = EXCLUDED."timestamp",
+  api_key_id = EXCLUDED.api_key_id,
+  updated_at = NOW()
+        "#,
+    )
+    .bind(id)
+    .bind(scope.project_id())
+    .bind(req.traceId)
+    .bind(req.observationId)
+    .bind(req.name.trim())
+    .bind(source)
+    .bind(data_type)
+    .bind(value)
+    .bind(string_value)
+    .bind(req.comment)
+    .bind(req.authorUserId)
+    .bind(req.metadata)
+    .bind(req.configId)
+    .bind(req.queueId)
+    .bind(req.environment)
+    .bind(req.timestamp)
+    .bind(scope.key_id())
+    .execute(&state.pool)
+    .await?;
+
+    Ok(id)
+}
+
+fn push_score_filters(
+    builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    project_id: &str,
+    q: &ScoreListQuery,
+    source: Option<&'static str>,
+) {
+    builder.push(" WHERE s.project_id = ");
+    builder.push_bind(project_id.to_string());
+    if let Some(trace_id) = q.trace_id {
+        builder.push(" AND s.trace_id = ");
+        builder.push_bind(trace_id);
+    }
+    if let Some(observation_id) = q.observation_id {
+        builder.push(" AND s.observation_id = ");
+        builder.push_bind(observation_id);
+    }
+    if let Some(name) = &q.name {
+        builder.push(" AND s.name = ");
+        builder.push_bind(name.clone());
+    }
+    if let Some(source) = source {
+        builder.push(" AND s.source = ");
+        builder.push_bind(source);
+    }
+    if let Some(author_user_id) = &q.author_user_id {
+        builder.push(" AND s.author_user_id = ");
+        builder.push_bind(author_user_id.clone());
+    }
+    if let Some(api_key_id) = &q.api_key_id {
+        builder.push(" AND s.api_key_id = ");
+        builder.push_bind(api_key_id.clone());
+    }
+    if let Some(data_type) = &q.data_type {
+        builder.push(" AND s.data_type = ");
+        builder.push_bind(data_type.to_ascii_uppercase());
+    }
+    if let Some(config_id) = &q.config_id {
+        builder.push(" AND s.config_id = ");
+        builder.push_bind(config_id.clone());
+    }
+    if let Some(queue_id) = &q.queue_id {
+        builder.push(" AND s.queue_id = ");
+        builder.push_bind(queue_id.clone());
+    }
+    if let Some(environment) = &q.environment {
+        builder.push(" AND s.environment = ");
+        builder.push_bind(environment.clone());
+    }
+    if let Some(from) = q.from_timestamp {
+        builder.push(" AND s.\"timestamp\" >= ");
+        builder.push_bind(from);
+    }
+    if let Some(to) = q.to_timestamp {
+        builder.push(" AND s.\"timestamp\" <= ");
+        builder.push_bind(to);
+    }
+    if let Some(user_id) = &q.user_id {
+        builder
+            .push(" AND EXISTS (SELECT 1 FROM traces t WHERE t.id = s.trace_id AND t.user_id = ");
+        builder.push_bind(user_id.clone());
+        builder.push(")");
+    }
+}
+
+/// Paginated scores, newest first.
+pub(crate) async fn get_scores(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(mut q): Query<ScoreListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.from_timestamp = state
+        .query_windows
+        .list_from(q.from_timestamp, q.to_timestamp);
+    let page = q.page.unwrap_or(1).max(1);
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * limit;
+    let project_id = scope.project_id();
+    let source = q.source.as_deref().map(parse_source).transpose()?;
+
+    let mut count_builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new("SELECT COUNT(*)::BIGINT FROM scores s");
+    push_score_filters(&mut count_builder, project_id, &q, source);
+    let total_items: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&state.pool)
+        .await?;
+
+    let total_pages = if total_items == 0 {
+        0
+    } else {
+        (total_items + limit - 1) / limit
+    };
+
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new(format!("SELECT {SCORE_COLUMNS} FROM scores s"));
+    push_score_filters(&mut builder, project_id, &q, source);
+    builder.push(" ORDER BY s.\"timestamp\" DESC, s.id LIMIT ");
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+
+    let rows: Vec<ScoreRow> = builder.build_query_as().fetch_all(&state.pool).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data: rows.into_iter().map(ScoreV1Dto::from).collect::<Vec<_>>(),
+            meta: PageMeta {
+                page,
+                limit,
+                totalItems: total_items,
+                totalPages: total_pages,
+            },
+        }),
+    ))
+}
+
+pub(crate) async fn get_score(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(score_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row: Option<ScoreRow> = sqlx::query_as(&format!(
+        "SELECT {SCORE_COLUMNS} FROM scores WHERE project_id = $1 AND id = $2"
+    ))
+    .bind(scope.project_id())
+    .bind(score_id)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    let row = row.ok_or(ApiError::NotFound)?;
+    Ok((StatusCode::OK, Json(ScoreV1Dto::from(row))))
+}
+
+/// All scores of one trace, oldest first.
+pub(crate) async fn fetch_trace_scores(
+    pool: &PgPool,
+    trace_id: Uuid,
+) -> Result<Vec<ScoreV1Dto>, sqlx::Error> {
+    let rows: Vec<ScoreRow> = sqlx::query_as(&format!(
+        "SELECT {SCORE_COLUMNS} FROM scores WHERE trace_id = $1 ORDER BY \"timestamp\", id"
+    ))
+    .bind(trace_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(ScoreV1Dto::from).collect())
+}
+
+/// Scores per trace, in the same order as [`fetch_trace_scores`].
+pub(crate) async fn fetch_scores_by_trace(
+    pool: &PgPool,
+    trace_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<ScoreV1Dto>>, sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows: Vec<ScoreRow> = sqlx::query_as(&format!(
+        "SELECT {SCORE_COLUMNS} FROM scores WHERE trace_id = ANY($1) ORDER BY \"timestamp\", id"
+    ))
+    .bind(trace_ids)
+    .fetch_all(pool)
+    .await?;
+    let mut by_trace: HashMap<Uuid, Vec<ScoreV1Dto>> = HashMap::new();
+    for row in rows {
+        by_trace
+            .entry(row.trace_id)
+            .or_default()
+            .push(ScoreV1Dto::from(row));
+    }
+    Ok(by_trace)
+}
+
+/// Score ids per trace, for the trace list.
+pub(crate) async fn fetch_score_ids(
+    pool: &PgPool,
+    trace_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<String>>, sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows: Vec<(Uuid, Vec<Uuid>)> = sqlx::query_as(
+        r#"
+SELECT trace_id, array_agg(id ORDER BY "timestamp", id)
+FROM scores
+WHERE trace_id = ANY($1)
+GROUP BY trace_id
+        "#,
+    )
+    .bind(trace_ids)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(trace_id, ids)| (trace_id, ids.iter().map(Uuid::to_string).collect()))
+        .collect())
+}
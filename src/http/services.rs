@@ -0,0 +1,134 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    http::{auth::ProjectScope, error::ApiError},
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ServiceMapQuery {
+    #[serde(default, rename = "fromTimestamp")]
+    from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toTimestamp")]
+    to_timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    environment: Option<String>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct ServiceNode {
+    service: String,
+    request_count: i64,
+    error_count: i64,
+    error_rate: f64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct ServiceEdge {
+    source: String,
+    target: String,
+    request_count: i64,
+    error_count: i64,
+    error_rate: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServiceMapResponse {
+    services: Vec<ServiceNode>,
+    edges: Vec<ServiceEdge>,
+    from_timestamp: DateTime<Utc>,
+    to_timestamp: DateTime<Utc>,
+}
+
+/// Services and cross-service call edges derived from `observations.service_name`
+/// and `parent_observation_id`. Observations without a service are reported as `unknown`.
+pub(crate) async fn get_service_map(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<ServiceMapQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let to_ts = q.to_timestamp.unwrap_or_else(Utc::now);
+    let from_ts = q
+        .from_timestamp
+        .unwrap_or_else(|| to_ts - chrono::Duration::hours(24));
+    if from_ts > to_ts {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp must be <= toTimestamp".to_string(),
+        ));
+    }
+    state
+        .query_windows
+        .check_range(&scope, from_ts, to_ts, q.ignore_range_limit)?;
+
+    let project_id = scope.project_id();
+
+    let services: Vec<ServiceNode> = sqlx::query_as(
+        r#"
+SELECT
+  COALESCE(o.service_name, 'unknown') AS service,
+  COUNT(*)::BIGINT AS request_count,
+  COUNT(*) FILTER (WHERE o.level = 'ERROR')::BIGINT AS error_count,
+  (COUNT(*) FILTER (WHERE o.level = 'ERROR'))::DOUBLE PRECISION / COUNT(*) AS error_rate
+FROM observations o
+WHERE o.project_id = $1
+  AND COALESCE(o.start_time, o.created_at) BETWEEN $2 AND $3
+  AND ($4::TEXT IS NULL OR o.environment = $4)
+GROUP BY 1
+ORDER BY request_count DESC
+        "#,
+    )
+    .bind(project_id)
+    .bind(from_ts)
+    .bind(to_ts)
+    .bind(q.environment.as_deref())
+    .fetch_all(&state.pool)
+    .await?;
+
+    let edges: Vec<ServiceEdge> = sqlx::query_as(
+        r#"
+SELECT
+  COALESCE(p.service_name, 'unknown') AS source,
+  COALESCE(c.service_name, 'unknown') AS target,
+  COUNT(*)::BIGINT AS request_count,
+  COUNT(*) FILTER (WHERE c.level = 'ERROR')::BIGINT AS error_count,
+  (COUNT(*) FILTER (WHERE c.level = 'ERROR'))::DOUBLE PRECISION / COUNT(*) AS error_rate
+FROM observations c
+JOIN observations p ON p.id = c.parent_observation_id
+WHERE c.project_id = $1
+  AND COALESCE(c.start_time, c.created_at) BETWEEN $2 AND $3
+  AND ($4::TEXT IS NULL OR c.environment = $4)
+  AND c.service_name IS DISTINCT FROM p.service_name
+GROUP BY 1, 2
+ORDER BY request_count DESC
+        "#,
+    )
+    .bind(project_id)
+    .bind(from_ts)
+    .bind(to_ts)
+    .bind(q.environment.as_deref())
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ServiceMapResponse {
+            services,
+            edges,
+            from_timestamp: from_ts,
+            to_timestamp: to_ts,
+        }),
+    ))
+}
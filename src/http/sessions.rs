@@ -0,0 +1,209 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::QueryBuilder;
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        common::{PageMeta, PagedData},
+        error::ApiError,
+    },
+    ingest::sessions,
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SessionListQuery {
+    #[serde(default)]
+    page: Option<i64>,
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default, rename = "fromTimestamp")]
+    from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toTimestamp")]
+    to_timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct SessionRow {
+    session_id: String,
+    trace_count: i64,
+    total_cost: f64,
+    first_trace_at: Option<DateTime<Utc>>,
+    last_activity_at: Option<DateTime<Utc>>,
+    duration_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionItem {
+    id: String,
+    trace_count: i64,
+    total_cost: f64,
+    first_trace_at: Option<DateTime<Utc>>,
+    last_activity_at: Option<DateTime<Utc>>,
+    duration_seconds: Option<f64>,
+}
+
+impl From<SessionRow> for SessionItem {
+    fn from(r: SessionRow) -> Self {
+        Self {
+            id: r.session_id,
+            trace_count: r.trace_count,
+            total_cost: r.total_cost,
+            first_trace_at: r.first_trace_at,
+            last_activity_at: r.last_activity_at,
+            duration_seconds: r.duration_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionDetail {
+    #[serde(flatten)]
+    session: SessionItem,
+    traces: Vec<Uuid>,
+}
+
+fn push_session_filters<'a>(
+    builder: &mut QueryBuilder<'a, sqlx::Postgres>,
+    project_id: &str,
+    q: &SessionListQuery,
+) {
+    builder.push(" WHERE s.project_id = ");
+    builder.push_bind(project_id.to_string());
+    if let Some(from_ts) = q.from_timestamp {
+        builder.push(" AND s.last_activity_at >= ");
+        builder.push_bind(from_ts);
+    }
+    if let Some(to_ts) = q.to_timestamp {
+        builder.push(" AND s.last_activity_at <= ");
+        builder.push_bind(to_ts);
+    }
+}
+
+/// Sessions ordered by last activity, served from `session_aggregates`.
+pub(crate) async fn get_sessions(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(mut q): Query<SessionListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.from_timestamp = state
+        .query_windows
+        .list_from(q.from_timestamp, q.to_timestamp);
+    let page = q.page.unwrap_or(1).max(1);
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * limit;
+    let project_id = scope.project_id();
+
+    let mut count_builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new("SELECT COUNT(*)::BIGINT FROM session_aggregates s");
+    push_session_filters(&mut count_builder, project_id, &q);
+    let total_items: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&state.pool)
+        .await?;
+
+    let total_pages = if total_items == 0 {
+        0
+    } else {
+        (total_items + limit - 1) / limit
+    };
+
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
+        "SELECT s.session_id, s.trace_count, s.total_cost, s.first_trace_at, s.last_activity_at, s.duration_seconds FROM session_aggregates s",
+    );
+    push_session_filters(&mut builder, project_id, &q);
+    builder.push(" ORDER BY s.last_activity_at DESC NULLS LAST, s.session_id LIMIT ");
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+
+    let rows: Vec<SessionRow> = builder.build_query_as().fetch_all(&state.pool).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data: rows.into_iter().map(SessionItem::from).collect::<Vec<_>>(),
+            meta: PageMeta {
+                page,
+                limit,
+                totalItems: total_items,
+                totalPages: total_pages,
+            },
+        }),
+    ))
+}
+
+async fn fetch_session(
+    state: &AppState,
+    project_id: &str,
+    session_id: &str,
+) -> Result<Option<SessionRow>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT session_id, trace_count, total_cost, first_trace_at, last_activity_at, duration_seconds
+FROM session_aggregates
+WHERE project_id = $1 AND session_id = $2
+        "#,
+    )
+    .bind(project_id)
+    .bind(session_id)
+    .fetch_optional(&state.pool)
+    .await
+}
+
+/// Single session with its trace ids. The stored aggregate is checked against
+/// the live trace list and recomputed if it has drifted.
+pub(crate) async fn get_session(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let project_id = scope.project_id();
+
+    let traces: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+SELECT id FROM traces
+WHERE project_id = $1 AND session_id = $2
+ORDER BY "timestamp" ASC
+        "#,
+    )
+    .bind(project_id)
+    .bind(&session_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut row = fetch_session(&state, project_id, &session_id).await?;
+    let stale = row.as_ref().map(|r| r.trace_count) != Some(traces.len() as i64)
+        && !(row.is_none() && traces.is_empty());
+    if stale {
+        tracing::warn!(session_id = %session_id, "session aggregate drifted; recomputing");
+        let keys = BTreeSet::from([(project_id.to_string(), session_id.clone())]);
+        let mut tx = state.pool.begin().await?;
+        sessions::refresh(&mut tx, &keys).await?;
+        tx.commit().await?;
+        row = fetch_session(&state, project_id, &session_id).await?;
+    }
+
+    let Some(row) = row else {
+        return Err(ApiError::NotFound);
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(SessionDetail {
+            session: row.into(),
+            traces,
+        }),
+    ))
+}
@@ -0,0 +1,32 @@
+//! Public trace share links: `GET /api/public/shared/traces/:traceId` serves
+//! the detail of traces marked `public` (by ingest or
+//! [`crate::http::trace_update`]) without credentials, so a trace can be
+//! shared with people who have no API key.
+//!
+//! The shared route answers `404` for traces that are not public, exactly as
+//! for unknown ones. Ingest keeps the flag unless a payload sets `public`.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        error::ApiError,
+        traces::{load_trace_detail, TraceAccess, TraceDetailQuery},
+    },
+    state::AppState,
+};
+
+pub(crate) async fn get_shared_trace(
+    State(state): State<AppState>,
+    Path(trace_id): Path<Uuid>,
+    Query(q): Query<TraceDetailQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let dto = load_trace_detail(&state, trace_id, TraceAccess::Shared, &q).await?;
+    Ok((StatusCode::OK, Json(dto)))
+}
@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    http::{auth::ProjectScope, error::ApiError},
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TagsQuery {
+    #[serde(default, rename = "fromTimestamp")]
+    from_timestamp: Option<DateTime<Utc>>,
+    #[serde(default, rename = "toTimestamp")]
+    to_timestamp: Option<DateTime<Utc>>,
+    /// Only tags starting with this, ignoring case (for autocomplete).
+    #[serde(default)]
+    prefix: Option<String>,
+    #[serde(default)]
+    environment: Option<String>,
+    #[serde(default)]
+    limit: Option<i64>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default, rename = "ignoreRangeLimit")]
+    ignore_range_limit: bool,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct TagCount {
+    tag: String,
+    /// Traces in the range carrying the tag.
+    count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TagsResponse {
+    data: Vec<TagCount>,
+    from_timestamp: DateTime<Utc>,
+    to_timestamp: DateTime<Utc>,
+}
+
+/// Distinct trace tags with the number of traces carrying them within a time
+/// range (default: the last 24 hours), most used first.
+pub(crate) async fn get_tags(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<TagsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let to_ts = q.to_timestamp.unwrap_or_else(Utc::now);
+    let from_ts = q
+        .from_timestamp
+        .unwrap_or_else(|| to_ts - chrono::Duration::hours(24));
+    if from_ts > to_ts {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp must be <= toTimestamp".to_string(),
+        ));
+    }
+    state
+        .query_windows
+        .check_range(&scope, from_ts, to_ts, q.ignore_range_limit)?;
+    let limit = q.limit.unwrap_or(100).clamp(1, 1000);
+
+    let data: Vec<TagCount> = sqlx::query_as(
+        r#"
+SELECT tag, COUNT(DISTINCT t.id)::BIGINT AS count
+FROM traces t, unnest(t.tags) AS tag
+WHERE t.project_id = $1
+  AND t.timestamp >= $2 AND t.timestamp <= $3
+  AND ($4::TEXT IS NULL OR t.environment = $4)
+  AND ($5::TEXT IS NULL OR starts_with(lower(tag), lower($5)))
+GROUP BY tag
+ORDER BY count DESC, tag
+LIMIT $6
+        "#,
+    )
+    .bind(scope.project_id())
+    .bind(from_ts)
+    .bind(to_ts)
+    .bind(q.environment.as_deref())
+    .bind(q.prefix.as_deref().filter(|p| !p.is_empty()))
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(TagsResponse {
+            data,
+            from_timestamp: from_ts,
+            to_timestamp: to_ts,
+        }),
+    ))
+}
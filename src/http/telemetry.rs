@@ -0,0 +1,393 @@
+//! `GET /metrics`: counters of the server itself in the Prometheus text
+//! exposition format, for alerting on xtrace rather than on the workloads it
+//! observes (those are in [`crate::http::metrics`]).
+//!
+//! Counters live in [`Telemetry`], updated by the ingest and metrics workers
+//! and by the request middleware; queue depths, pending writes and pool usage
+//! are read when scraped. Like `/healthz`, the endpoint needs no key; the
+//! admin IP allowlist applies.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+
+use crate::state::{AppState, WriteMode};
+
+/// Upper bounds (seconds) of the write duration histogram buckets.
+const WRITE_SECONDS_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket (not cumulative); the last one is `+Inf`.
+    buckets: [AtomicU64; WRITE_SECONDS_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let i = WRITE_SECONDS_BUCKETS
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(WRITE_SECONDS_BUCKETS.len());
+        self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let mut cumulative = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            match WRITE_SECONDS_BUCKETS.get(i) {
+                Some(le) => writeln!(out, "{name}_bucket{{le=\"{le}\"}} {cumulative}"),
+                None => writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}"),
+            }
+            .ok();
+        }
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+        writeln!(out, "{name}_sum {sum}\n{name}_count {cumulative}").ok();
+    }
+}
+
+/// Minutes of ingest throughput kept for `GET /api/admin/stats`.
+pub(crate) const THROUGHPUT_MINUTES: u64 = 15;
+
+/// Ingest rows committed in one wall-clock minute.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct MinuteWrites {
+    /// Minutes since the Unix epoch.
+    pub minute: u64,
+    pub batches: u64,
+    pub traces: u64,
+    pub observations: u64,
+}
+
+fn current_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 60
+}
+
+/// Counters of the running instance.
+#[derive(Default)]
+pub(crate) struct Telemetry {
+    /// Ingest transactions committed.
+    ingest_batches_written: AtomicU64,
+    /// Ingest transactions rolled back (their payloads are retried one by one).
+    ingest_batches_failed: AtomicU64,
+    /// Payloads kept in `failed_events`.
+    ingest_payloads_dead_lettered: AtomicU64,
+    ingest_write_seconds: Histogram,
+    traces_written: AtomicU64,
+    observations_written: AtomicU64,
+    metric_points_written: AtomicU64,
+    /// Traces dropped by server-side sampling.
+    traces_sampled_out: AtomicU64,
+    /// Ingest events skipped as retries of events already written.
+    events_deduplicated: AtomicU64,
+    /// Responses by method, matched route and status.
+    http_requests: DashMap<(String, String, u16), u64>,
+    /// Ingest writes of the last [`THROUGHPUT_MINUTES`], oldest first.
+    recent_writes: Mutex<VecDeque<MinuteWrites>>,
+    /// Accept times of the ingest payloads queued or being written, with
+    /// the number of payloads accepted at each.
+    pending_ingest: Mutex<BTreeMap<Instant, u64>>,
+}
+
+impl Telemetry {
+    /// Records an ingest transaction and, when committed, its rows.
+    pub(crate) fn record_ingest_write(&self, elapsed: Duration, written: Option<(usize, usize)>) {
+        self.ingest_write_seconds.observe(elapsed);
+        match written {
+            Some((traces, observations)) => {
+                self.ingest_batches_written.fetch_add(1, Ordering::Relaxed);
+                self.traces_written
+                    .fetch_add(traces as u64, Ordering::Relaxed);
+                self.observations_written
+                    .fetch_add(observations as u64, Ordering::Relaxed);
+                self.record_recent_write(traces as u64, observations as u64);
+            }
+            None => {
+                self.ingest_batches_failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn record_recent_write(&self, traces: u64, observations: u64) {
+        let minute = current_minute();
+        let mut recent = self.recent_writes.lock().unwrap();
+        if recent.back().is_none_or(|m| m.minute != minute) {
+            recent.push_back(MinuteWrites {
+                minute,
+                ..Default::default()
+            });
+        }
+        while recent
+            .front()
+            .is_some_and(|m| m.minute + THROUGHPUT_MINUTES <= minute)
+        {
+            recent.pop_front();
+        }
+        let current = recent.back_mut().expect("pushed above");
+        current.batches += 1;
+        current.traces += traces;
+        current.observations += observations;
+    }
+
+    /// Ingest writes per minute of the last [`THROUGHPUT_MINUTES`], oldest
+    /// first and including minutes without writes; the last one is the
+    /// current, incomplete minute.
+    pub(crate) fn recent_writes(&self) -> Vec<MinuteWrites> {
+        let now = current_minute();
+        let recent = self.recent_writes.lock().unwrap();
+        (now + 1 - THROUGHPUT_MINUTES..=now)
+            .map(|minute| {
+                recent
+                    .iter()
+                    .find(|m| m.minute == minute)
+                    .copied()
+                    .unwrap_or(MinuteWrites {
+                        minute,
+                        ..Default::default()
+                    })
+            })
+            .collect()
+    }
+
+    /// Records `count` ingest payloads accepted at `at`, until
+    /// [`Telemetry::record_settled`] is called for them.
+    pub(crate) fn record_accepted(&self, at: Instant, count: u64) {
+        if count > 0 {
+            *self.pending_ingest.lock().unwrap().entry(at).or_default() += count;
+        }
+    }
+
+    /// Records ingest payloads, by accept time, as written, dead-lettered
+    /// or not queued after all.
+    pub(crate) fn record_settled(&self, accepted: impl IntoIterator<Item = Instant>) {
+        let mut pending = self.pending_ingest.lock().unwrap();
+        for at in accepted {
+            if let std::collections::btree_map::Entry::Occupied(mut entry) = pending.entry(at) {
+                *entry.get_mut() -= 1;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+    }
+
+    /// How long the oldest ingest payload that is not written yet has been
+    /// waiting; `None` when there is none.
+    pub(crate) fn oldest_pending_ingest(&self) -> Option<Duration> {
+        let pending = self.pending_ingest.lock().unwrap();
+        pending.keys().next().map(Instant::elapsed)
+    }
+
+    pub(crate) fn record_dead_letter(&self) {
+        self.ingest_payloads_dead_lettered
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_metric_points(&self, points: usize) {
+        self.metric_points_written
+            .fetch_add(points as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_sampled_out(&self, traces: usize) {
+        self.traces_sampled_out
+            .fetch_add(traces as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_deduplicated(&self, events: usize) {
+        self.events_deduplicated
+            .fetch_add(events as u64, Ordering::Relaxed);
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}"
+    )
+    .ok();
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}"
+    )
+    .ok();
+}
+
+/// Label values escaped as the text format requires.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Counts every response by method, matched route (not the raw path, to
+/// bound the label values) and status.
+pub(crate) async fn track_requests(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |p| p.as_str().to_string());
+    let response = next.run(request).await;
+    *state
+        .telemetry
+        .http_requests
+        .entry((method, route, response.status().as_u16()))
+        .or_default() += 1;
+    response
+}
+
+pub(crate) async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let t = &state.telemetry;
+    let load = |c: &AtomicU64| c.load(Ordering::Relaxed);
+    let mut out = String::new();
+
+    out.push_str("# HELP xtrace_ingest_queue_depth Ingest payloads queued per worker.\n# TYPE xtrace_ingest_queue_depth gauge\n");
+    for (worker, depth) in state.ingest_queue.depths().into_iter().enumerate() {
+        writeln!(
+            out,
+            "xtrace_ingest_queue_depth{{worker=\"{worker}\"}} {depth}"
+        )
+        .ok();
+    }
+    gauge(
+        &mut out,
+        "xtrace_pending_writes",
+        "Payloads accepted but not yet written.",
+        state.write_gate.pending(),
+    );
+    gauge(
+        &mut out,
+        "xtrace_ingest_oldest_pending_seconds",
+        "Age of the oldest ingest payload not written yet (0 when there is none).",
+        t.oldest_pending_ingest()
+            .map_or(0.0, |age| age.as_secs_f64()),
+    );
+    out.push_str("# HELP xtrace_write_mode Current write mode (1 for the active one).\n# TYPE xtrace_write_mode gauge\n");
+    let mode = state.write_gate.mode();
+    for m in [WriteMode::Normal, WriteMode::ReadOnly, WriteMode::Draining] {
+        writeln!(
+            out,
+            "xtrace_write_mode{{mode=\"{}\"}} {}",
+            m.as_str(),
+            u8::from(m == mode)
+        )
+        .ok();
+    }
+
+    counter(
+        &mut out,
+        "xtrace_ingest_batches_written_total",
+        "Ingest transactions committed.",
+        load(&t.ingest_batches_written),
+    );
+    counter(
+        &mut out,
+        "xtrace_ingest_batches_failed_total",
+        "Ingest transactions rolled back.",
+        load(&t.ingest_batches_failed),
+    );
+    counter(
+        &mut out,
+        "xtrace_ingest_payloads_dead_lettered_total",
+        "Ingest payloads kept in failed_events.",
+        load(&t.ingest_payloads_dead_lettered),
+    );
+    out.push_str("# HELP xtrace_ingest_write_duration_seconds Duration of ingest transactions.\n# TYPE xtrace_ingest_write_duration_seconds histogram\n");
+    t.ingest_write_seconds
+        .render(&mut out, "xtrace_ingest_write_duration_seconds");
+    counter(
+        &mut out,
+        "xtrace_traces_sampled_out_total",
+        "Traces dropped by server-side sampling.",
+        load(&t.traces_sampled_out),
+    );
+    counter(
+        &mut out,
+        "xtrace_ingest_events_deduplicated_total",
+        "Ingest events skipped as retries of events already written.",
+        load(&t.events_deduplicated),
+    );
+    out.push_str("# HELP xtrace_rows_written_total Rows inserted or updated by the ingest and metrics workers.\n# TYPE xtrace_rows_written_total counter\n");
+    for (table, count) in [
+        ("traces", &t.traces_written),
+        ("observations", &t.observations_written),
+        ("metrics", &t.metric_points_written),
+    ] {
+        writeln!(
+            out,
+            "xtrace_rows_written_total{{table=\"{table}\"}} {}",
+            load(count)
+        )
+        .ok();
+    }
+
+    out.push_str("# HELP xtrace_http_requests_total HTTP responses by method, route and status.\n# TYPE xtrace_http_requests_total counter\n");
+    let mut requests: Vec<_> = t
+        .http_requests
+        .iter()
+        .map(|e| (e.key().clone(), *e.value()))
+        .collect();
+    requests.sort_unstable();
+    for ((method, route, status), count) in requests {
+        writeln!(
+            out,
+            "xtrace_http_requests_total{{method=\"{method}\",route=\"{}\",status=\"{status}\"}} {count}",
+            escape(&route)
+        )
+        .ok();
+    }
+
+    let pool = &state.pool;
+    let (size, idle) = (pool.size(), pool.num_idle() as u32);
+    out.push_str("# HELP xtrace_db_pool_connections Database connections by state.\n# TYPE xtrace_db_pool_connections gauge\n");
+    writeln!(
+        out,
+        "xtrace_db_pool_connections{{state=\"active\"}} {}\nxtrace_db_pool_connections{{state=\"idle\"}} {idle}",
+        size.saturating_sub(idle)
+    )
+    .ok();
+    gauge(
+        &mut out,
+        "xtrace_db_pool_max_connections",
+        "Size limit of the database pool.",
+        pool.options().get_max_connections(),
+    );
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        out,
+    )
+}
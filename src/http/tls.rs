@@ -0,0 +1,158 @@
+//! HTTPS listener with optional client certificate verification (rustls).
+//!
+//! Client certificates are verified against the configured CA but not required
+//! at the handshake, so token-only clients keep working; route groups decide
+//! what a certificate is worth (see [`crate::http::network::ClientCertMode`]).
+
+use axum::{extract::ConnectInfo, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore,
+};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+
+use crate::http::network::ClientCertificate;
+
+/// How long open connections may finish their requests after shutdown.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+/// PEM files of the server identity and, optionally, the client CA bundle.
+#[derive(Debug, Clone)]
+pub(crate) struct TlsFiles {
+    pub cert: String,
+    pub key: String,
+    pub client_ca: Option<String>,
+}
+
+impl TlsFiles {
+    pub(crate) fn server_config(&self) -> Result<Arc<rustls::ServerConfig>, String> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("tls: {e}"))?;
+        let builder = match &self.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(path)? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| format!("tls: client CA {path}: {e}"))?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(roots.into(), provider)
+                    .allow_unauthenticated()
+                    .build()
+                    .map_err(|e| format!("tls: client CA {path}: {e}"))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let key = PrivateKeyDer::from_pem_file(&self.key)
+            .map_err(|e| format!("tls: key {}: {e}", self.key))?;
+        let mut config = builder
+            .with_single_cert(read_certs(&self.cert)?, key)
+            .map_err(|e| format!("tls: {e}"))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    /// Identity and client CA for the OTLP/gRPC listener.
+    pub(crate) fn grpc_config(&self) -> Result<tonic::transport::ServerTlsConfig, String> {
+        let read = |path: &str| std::fs::read(path).map_err(|e| format!("tls: {path}: {e}"));
+        let mut config = tonic::transport::ServerTlsConfig::new().identity(
+            tonic::transport::Identity::from_pem(read(&self.cert)?, read(&self.key)?),
+        );
+        if let Some(path) = &self.client_ca {
+            config = config
+                .client_ca_root(tonic::transport::Certificate::from_pem(read(path)?))
+                .client_auth_optional(true);
+        }
+        Ok(config)
+    }
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("tls: {path}: {e}"))?;
+    if certs.is_empty() {
+        return Err(format!("tls: {path}: no certificates found"));
+    }
+    Ok(certs)
+}
+
+/// Serves `app` over TLS until `shutdown` resolves, then lets open
+/// connections finish for up to [`SHUTDOWN_GRACE`].
+///
+/// Requests carry the peer address as `ConnectInfo<SocketAddr>` and, when the
+/// client presented a verified certificate, [`ClientCertificate`].
+pub(crate) async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    config: Arc<rustls::ServerConfig>,
+    shutdown: impl Future<Output = ()>,
+) -> std::io::Result<()> {
+    let acceptor = TlsAcceptor::from(config);
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        let (tcp, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(err) => {
+                    tracing::warn!(error = %err, "accept failed");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let watcher = graceful.watcher();
+        // Handshake off the accept loop so a slow client cannot stall it.
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(tcp).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::debug!(%peer, error = %err, "tls handshake failed");
+                    return;
+                }
+            };
+            let client_cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .is_some_and(|certs| !certs.is_empty());
+            let service = hyper::service::service_fn(move |mut req: hyper::Request<_>| {
+                req.extensions_mut().insert(ConnectInfo(peer));
+                if client_cert {
+                    req.extensions_mut().insert(ClientCertificate);
+                }
+                app.clone().oneshot(req)
+            });
+            let conn = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .into_owned();
+            if let Err(err) = watcher.watch(conn).await {
+                tracing::debug!(%peer, error = %err, "connection error");
+            }
+        });
+    }
+
+    if tokio::time::timeout(SHUTDOWN_GRACE, graceful.shutdown())
+        .await
+        .is_err()
+    {
+        tracing::warn!("timed out waiting for connections to close");
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Trace deletion: `DELETE /api/public/traces/:traceId` and
+//! `POST /api/public/traces/delete`, e.g. to remove accidental or sensitive
+//! ingests.
+//!
+//! A trace is deleted with its observations, scores, secret findings and the
+//! raw ingest payloads that contributed to it; the aggregates of its session
+//! and its activity counts are refreshed in the same transaction; offloaded payloads are deleted from
+//! object storage once it commits. Deletes are writes, so read-only and
+//! drain mode reject them. A payload of the trace ingested afterwards creates
+//! it again.
+
+use std::collections::{BTreeSet, HashSet};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        error::ApiError,
+        traces::{apply_trace_filters, TraceListQuery},
+    },
+    ingest::{
+        activity::{self, ActivityDelta},
+        sessions,
+    },
+    state::AppState,
+};
+
+/// Most ids `POST /traces/delete` accepts per request.
+const MAX_DELETE_TRACE_IDS: usize = 1000;
+
+/// Traces deleted per transaction.
+const BATCH_SIZE: i64 = 500;
+
+/// Rows removed by [`delete_traces`].
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeletedTraces {
+    pub(crate) traces: i64,
+    pub(crate) observations: i64,
+    pub(crate) scores: i64,
+    #[serde(skip)]
+    pub(crate) sessions_refreshed: usize,
+    /// Keys of offloaded payloads of the deleted observations, to delete
+    /// once the transaction commits.
+    #[serde(skip)]
+    pub(crate) offloaded: Vec<String>,
+}
+
+impl DeletedTraces {
+    fn add(&mut self, other: DeletedTraces) {
+        self.traces += other.traces;
+        self.observations += other.observations;
+        self.scores += other.scores;
+        self.sessions_refreshed += other.sessions_refreshed;
+        self.offloaded.extend(other.offloaded);
+    }
+}
+
+/// Deletes `trace_ids` with everything attached to them and refreshes their
+/// sessions. The caller owns the transaction (and should have locked the
+/// traces).
+pub(crate) async fn delete_traces(
+    conn: &mut PgConnection,
+    trace_ids: &[Uuid],
+) -> Result<DeletedTraces, sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(DeletedTraces::default());
+    }
+    let mut touched = BTreeSet::new();
+    sessions::collect_sessions(conn, trace_ids, &mut touched).await?;
+    let mut activity = ActivityDelta::default();
+    activity.record(activity::keys(conn, trace_ids).await?, Vec::new());
+    let (scores, observations, offloaded): (i64, i64, Vec<String>) = sqlx::query_as(
+        r#"
+WITH scores_deleted AS (
+  DELETE FROM scores WHERE trace_id = ANY($1) RETURNING 1
+),
+findings_deleted AS (
+  DELETE FROM secret_findings WHERE trace_id = ANY($1)
+),
+raw_deleted AS (
+  DELETE FROM raw_ingest WHERE trace_ids && $1
+),
+matches_deleted AS (
+  DELETE FROM webhook_rule_matches WHERE trace_id = ANY($1)
+),
+observations_deleted AS (
+  DELETE FROM observations WHERE trace_id = ANY($1)
+  RETURNING input_offload, output_offload
+)
+SELECT
+  (SELECT COUNT(*) FROM scores_deleted),
+  (SELECT COUNT(*) FROM observations_deleted),
+  ARRAY(
+    SELECT k FROM observations_deleted, LATERAL (VALUES (input_offload), (output_offload)) AS o(k)
+    WHERE k IS NOT NULL
+  )
+        "#,
+    )
+    .bind(trace_ids)
+    .fetch_one(&mut *conn)
+    .await?;
+    let traces = sqlx::query("DELETE FROM traces WHERE id = ANY($1)")
+        .bind(trace_ids)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    sessions::refresh(conn, &touched).await?;
+    activity::apply(conn, activity).await?;
+    Ok(DeletedTraces {
+        traces: traces as i64,
+        observations,
+        scores,
+        sessions_refreshed: touched.len(),
+        offloaded,
+    })
+}
+
+/// Locks and deletes the traces `select` returns, in one transaction through
+/// the write gate.
+async fn delete_batch(
+    state: &AppState,
+    mut select: QueryBuilder<'_, sqlx::Postgres>,
+) -> Result<(Vec<Uuid>, DeletedTraces), ApiError> {
+    state
+        .write_gate
+        .try_reserve(1)
+        .map_err(|mode| state.writes_paused(mode))?;
+    let result = async {
+        let mut tx = state.pool.begin().await?;
+        let trace_ids: Vec<Uuid> = select.build_query_scalar().fetch_all(&mut *tx).await?;
+        let mut deleted = delete_traces(&mut tx, &trace_ids).await?;
+        tx.commit().await?;
+        state.delete_offloaded(std::mem::take(&mut deleted.offloaded));
+        Ok::<_, sqlx::Error>((trace_ids, deleted))
+    }
+    .await;
+    state.write_gate.release(1);
+    Ok(result?)
+}
+
+pub(crate) async fn delete_trace(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(trace_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut select = QueryBuilder::new("SELECT id FROM traces WHERE id = ");
+    select.push_bind(trace_id);
+    select.push(" AND project_id = ");
+    select.push_bind(scope.project_id().to_string());
+    select.push(" FOR UPDATE");
+    let (found, deleted) = delete_batch(&state, select).await?;
+    if found.is_empty() {
+        return Err(ApiError::NotFound);
+    }
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "id": trace_id,
+            "observations": deleted.observations,
+            "scores": deleted.scores,
+        })),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteTracesRequest {
+    #[serde(default)]
+    trace_ids: Option<Vec<Uuid>>,
+    /// Filters of `GET /traces`; at least one must be set.
+    #[serde(default)]
+    filter: Option<TraceListQuery>,
+}
+
+#[derive(Debug, Serialize)]
+struct DeleteTracesResponse {
+    deleted: DeletedTraces,
+    /// Requested ids that do not exist (or belong to another project).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    missing: Option<Vec<Uuid>>,
+}
+
+/// Deletes the listed traces, or every trace matching a filter, a batch at
+/// a time.
+pub(crate) async fn post_delete_traces(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Json(req): Json<DeleteTracesRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let project_id = scope.project_id().to_string();
+    let mut deleted = DeletedTraces::default();
+    let response = match (req.trace_ids, req.filter) {
+        (Some(mut trace_ids), None) => {
+            let mut seen = HashSet::new();
+            trace_ids.retain(|id| seen.insert(*id));
+            if trace_ids.len() > MAX_DELETE_TRACE_IDS {
+                return Err(ApiError::BadRequest(format!(
+                    "at most {MAX_DELETE_TRACE_IDS} traceIds per request"
+                )));
+            }
+            let mut found = HashSet::new();
+            for chunk in trace_ids.chunks(BATCH_SIZE as usize) {
+                let mut select = QueryBuilder::new("SELECT id FROM traces WHERE id = ANY(");
+                select.push_bind(chunk.to_vec());
+                select.push(") AND project_id = ");
+                select.push_bind(project_id.clone());
+                select.push(" FOR UPDATE");
+                let (ids, batch) = delete_batch(&state, select).await?;
+                found.extend(ids);
+                deleted.add(batch);
+            }
+            trace_ids.retain(|id| !found.contains(id));
+            DeleteTracesResponse {
+                deleted,
+                missing: Some(trace_ids),
+            }
+        }
+        (None, Some(filter)) => {
+            if !filter.has_filters() {
+                return Err(ApiError::BadRequest(
+                    "filter must set at least one condition".to_string(),
+                ));
+            }
+            loop {
+                let mut select =
+                    QueryBuilder::new("SELECT t.id FROM traces t WHERE t.project_id = ");
+                select.push_bind(project_id.clone());
+                apply_trace_filters(&mut select, &filter);
+                select.push(" LIMIT ");
+                select.push_bind(BATCH_SIZE);
+                select.push(" FOR UPDATE SKIP LOCKED");
+                let (ids, batch) = delete_batch(&state, select).await?;
+                deleted.add(batch);
+                if (ids.len() as i64) < BATCH_SIZE {
+                    break;
+                }
+            }
+            DeleteTracesResponse {
+                deleted,
+                missing: None,
+            }
+        }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "set either traceIds or filter".to_string(),
+            ))
+        }
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
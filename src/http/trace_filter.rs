@@ -0,0 +1,504 @@
+//! Langfuse-style structured filters for `GET /api/public/traces`: the
+//! `filter` parameter is a JSON array of conditions, all of which must hold.
+//!
+//! ```json
+//! [
+//!   {"type": "string", "column": "name", "operator": "contains", "value": "chat"},
+//!   {"type": "number", "column": "latency", "operator": ">", "value": 2.5},
+//!   {"type": "arrayOptions", "column": "tags", "operator": "any of", "value": ["a", "b"]},
+//!   {"type": "stringObject", "column": "metadata", "key": "user.plan", "operator": "=", "value": "pro"}
+//! ]
+//! ```
+//!
+//! The `type` selects the operators and the shape of `value` and must fit the
+//! column; conditions are validated when the query is parsed, so a bad filter
+//! is a `400`. Other views (see [`crate::http::analytics`]) resolve the same
+//! conditions against their own column tables.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value as JsonValue;
+use sqlx::QueryBuilder;
+
+/// Conditions of a `filter` parameter, given as a JSON string (query
+/// strings) or an array (JSON bodies).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TraceFilter(Filter);
+
+impl TraceFilter {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Appends ` AND <condition>` per condition, for traces aliased `t`.
+    pub(crate) fn push_sql(&self, builder: &mut QueryBuilder<'_, sqlx::Postgres>) {
+        self.0.push_sql(builder);
+    }
+}
+
+/// Conditions resolved against a column table.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Filter(Vec<Condition>);
+
+impl Filter {
+    pub(crate) fn resolve(raw: Vec<RawCondition>, columns: &[Column]) -> Result<Self, String> {
+        raw.into_iter()
+            .map(|c| c.resolve(columns))
+            .collect::<Result<_, _>>()
+            .map(Filter)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Appends ` AND <condition>` per condition.
+    pub(crate) fn push_sql(&self, builder: &mut QueryBuilder<'_, sqlx::Postgres>) {
+        for condition in &self.0 {
+            builder.push(" AND ");
+            condition.push_sql(builder);
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TraceFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FilterVisitor;
+
+        impl<'de> de::Visitor<'de> for FilterVisitor {
+            type Value = TraceFilter;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array of filter conditions, or one encoded as a JSON string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                if v.trim().is_empty() {
+                    return Ok(TraceFilter::default());
+                }
+                serde_json::from_str(v)
+                    .map(|conditions| TraceFilter(Filter(conditions)))
+                    .map_err(|e| E::custom(format!("invalid filter: {e}")))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut conditions = Vec::new();
+                while let Some(condition) = seq.next_element()? {
+                    conditions.push(condition);
+                }
+                Ok(TraceFilter(Filter(conditions)))
+            }
+        }
+
+        deserializer.deserialize_any(FilterVisitor)
+    }
+}
+
+/// A condition as given, before it is resolved against a column table.
+#[derive(Debug, Deserialize)]
+pub(crate) struct RawCondition {
+    #[serde(rename = "type")]
+    kind: String,
+    column: String,
+    operator: String,
+    #[serde(default)]
+    value: JsonValue,
+    /// Key path of `stringObject` / `numberObject` conditions, dot-separated.
+    #[serde(default)]
+    key: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColumnKind {
+    String,
+    /// Stored as JSON; only `contains` / `does not contain` on its text.
+    Json,
+    Datetime,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+/// A filterable column: API name, SQL expression, kind.
+pub(crate) type Column = (&'static str, &'static str, ColumnKind);
+
+/// Filterable trace columns, for traces aliased `t`.
+pub(crate) const TRACE_COLUMNS: &[Column] = &[
+    ("id", "t.id::TEXT", ColumnKind::String),
+    ("name", "t.name", ColumnKind::String),
+    ("userId", "t.user_id", ColumnKind::String),
+    ("sessionId", "t.session_id", ColumnKind::String),
+    ("release", "t.release", ColumnKind::String),
+    ("version", "t.version", ColumnKind::String),
+    ("environment", "t.environment", ColumnKind::String),
+    ("externalId", "t.external_id", ColumnKind::String),
+    ("serviceName", "t.service_name", ColumnKind::String),
+    ("serviceVersion", "t.service_version", ColumnKind::String),
+    ("sdkLanguage", "t.sdk_language", ColumnKind::String),
+    ("hostName", "t.host_name", ColumnKind::String),
+    ("input", "t.input::TEXT", ColumnKind::Json),
+    ("output", "t.output::TEXT", ColumnKind::Json),
+    ("timestamp", "t.timestamp", ColumnKind::Datetime),
+    ("createdAt", "t.created_at", ColumnKind::Datetime),
+    ("updatedAt", "t.updated_at", ColumnKind::Datetime),
+    ("latency", "t.latency", ColumnKind::Number),
+    ("totalCost", "t.total_cost", ColumnKind::Number),
+    ("bookmarked", "t.bookmarked", ColumnKind::Boolean),
+    ("public", "t.public", ColumnKind::Boolean),
+    ("tags", "t.tags", ColumnKind::Array),
+    ("metadata", "t.metadata", ColumnKind::Object),
+];
+
+fn column(columns: &[Column], name: &str) -> Result<(&'static str, ColumnKind), String> {
+    columns
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|(_, expr, kind)| (*expr, *kind))
+        .ok_or_else(|| format!("unknown filter column: {name:?}"))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TextOp {
+    Eq,
+    Contains,
+    NotContains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArrayOp {
+    Overlaps,
+    Contains,
+    Disjoint,
+}
+
+/// A column, or a key path into an object column.
+#[derive(Debug, Clone)]
+enum Target {
+    Column(&'static str),
+    Object(&'static str, Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+enum Bound {
+    Datetime(DateTime<Utc>),
+    Number(f64),
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Text {
+        target: Target,
+        op: TextOp,
+        value: String,
+    },
+    Options {
+        expr: &'static str,
+        none_of: bool,
+        values: Vec<String>,
+    },
+    Compare {
+        target: Target,
+        op: &'static str,
+        value: Bound,
+    },
+    Array {
+        expr: &'static str,
+        op: ArrayOp,
+        values: Vec<String>,
+    },
+    Null {
+        target: Target,
+        not: bool,
+    },
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        RawCondition::deserialize(deserializer)?
+            .resolve(TRACE_COLUMNS)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl RawCondition {
+    /// Validates the condition against `columns`.
+    fn resolve(self, columns: &[Column]) -> Result<Condition, String> {
+        let raw = self;
+        let (expr, kind) = column(columns, &raw.column)?;
+        let op = raw.operator.as_str();
+        let invalid_op = || {
+            format!(
+                "invalid operator {op:?} for {} filter on {:?}",
+                raw.kind, raw.column
+            )
+        };
+        let expect_kind = |expected: &[ColumnKind]| {
+            if expected.contains(&kind) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "column {:?} does not support {} filters",
+                    raw.column, raw.kind
+                ))
+            }
+        };
+        let target = match (&raw.key, kind) {
+            (Some(key), ColumnKind::Object) => Target::Object(expr, key_path(key)?),
+            (None, ColumnKind::Object) if raw.kind != "null" => {
+                return Err(format!(
+                    "{} filter on {:?} needs a key",
+                    raw.kind, raw.column
+                ))
+            }
+            _ => Target::Column(expr),
+        };
+
+        match raw.kind.as_str() {
+            "string" | "stringObject" => {
+                if raw.kind == "string" {
+                    expect_kind(&[ColumnKind::String, ColumnKind::Json])?;
+                } else {
+                    expect_kind(&[ColumnKind::Object])?;
+                }
+                let op = match op {
+                    "=" if kind != ColumnKind::Json => TextOp::Eq,
+                    "contains" => TextOp::Contains,
+                    "does not contain" => TextOp::NotContains,
+                    "starts with" if kind != ColumnKind::Json => TextOp::StartsWith,
+                    "ends with" if kind != ColumnKind::Json => TextOp::EndsWith,
+                    _ => return Err(invalid_op()),
+                };
+                Ok(Condition::Text {
+                    target,
+                    op,
+                    value: string_value(&raw.value)?,
+                })
+            }
+            "stringOptions" => {
+                expect_kind(&[ColumnKind::String])?;
+                let none_of = match op {
+                    "any of" => false,
+                    "none of" => true,
+                    _ => return Err(invalid_op()),
+                };
+                Ok(Condition::Options {
+                    expr,
+                    none_of,
+                    values: string_values(&raw.value)?,
+                })
+            }
+            "arrayOptions" => {
+                expect_kind(&[ColumnKind::Array])?;
+                let op = match op {
+                    "any of" => ArrayOp::Overlaps,
+                    "all of" => ArrayOp::Contains,
+                    "none of" => ArrayOp::Disjoint,
+                    _ => return Err(invalid_op()),
+                };
+                Ok(Condition::Array {
+                    expr,
+                    op,
+                    values: string_values(&raw.value)?,
+                })
+            }
+            "datetime" => {
+                expect_kind(&[ColumnKind::Datetime])?;
+                let op = comparison(op, &[">", "<", ">=", "<="]).ok_or_else(invalid_op)?;
+                let value = raw
+                    .value
+                    .as_str()
+                    .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                    .ok_or_else(|| "datetime filter value must be an RFC 3339 timestamp".to_string())?;
+                Ok(Condition::Compare {
+                    target,
+                    op,
+                    value: Bound::Datetime(value.with_timezone(&Utc)),
+                })
+            }
+            "number" | "numberObject" => {
+                if raw.kind == "number" {
+                    expect_kind(&[ColumnKind::Number])?;
+                } else {
+                    expect_kind(&[ColumnKind::Object])?;
+                }
+                let op = comparison(op, &["=", ">", "<", ">=", "<="]).ok_or_else(invalid_op)?;
+                let value = raw
+                    .value
+                    .as_f64()
+                    .ok_or_else(|| "number filter value must be a number".to_string())?;
+                Ok(Condition::Compare {
+                    target,
+                    op,
+                    value: Bound::Number(value),
+                })
+            }
+            "boolean" => {
+                expect_kind(&[ColumnKind::Boolean])?;
+                let op = comparison(op, &["=", "<>"]).ok_or_else(invalid_op)?;
+                let value = raw
+                    .value
+                    .as_bool()
+                    .ok_or_else(|| "boolean filter value must be true or false".to_string())?;
+                Ok(Condition::Compare {
+                    target,
+                    op,
+                    value: Bound::Boolean(value),
+                })
+            }
+            "null" => {
+                let not = match op {
+                    "is null" => false,
+                    "is not null" => true,
+                    _ => return Err(invalid_op()),
+                };
+                Ok(Condition::Null { target, not })
+            }
+            other => Err(format!(
+                "unknown filter type: {other:?} (expected string, stringOptions, arrayOptions, datetime, number, boolean, stringObject, numberObject or null)"
+            )),
+        }
+    }
+}
+
+fn comparison(op: &str, allowed: &[&'static str]) -> Option<&'static str> {
+    allowed.iter().find(|a| **a == op).copied()
+}
+
+fn key_path(key: &str) -> Result<Vec<String>, String> {
+    let path: Vec<String> = key.split('.').map(str::to_string).collect();
+    if path.iter().any(String::is_empty) {
+        return Err(format!("invalid metadata key: {key:?}"));
+    }
+    Ok(path)
+}
+
+fn string_value(value: &JsonValue) -> Result<String, String> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "filter value must be a string".to_string())
+}
+
+fn string_values(value: &JsonValue) -> Result<Vec<String>, String> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| "filter value must be an array of strings".to_string())
+}
+
+/// Escapes `LIKE` wildcards.
+fn like_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+impl Target {
+    /// The target as text, or as a number (`NULL` unless the metadata value
+    /// is a JSON number).
+    fn push_sql(&self, builder: &mut QueryBuilder<'_, sqlx::Postgres>, numeric: bool) {
+        match (self, numeric) {
+            (Target::Column(expr), _) => {
+                builder.push(*expr);
+            }
+            (Target::Object(expr, path), false) => {
+                builder.push(format!("({expr} #>> "));
+                builder.push_bind(path.clone());
+                builder.push(")");
+            }
+            (Target::Object(expr, path), true) => {
+                builder.push(format!("(CASE WHEN jsonb_typeof({expr} #> "));
+                builder.push_bind(path.clone());
+                builder.push(format!(") = 'number' THEN ({expr} #>> "));
+                builder.push_bind(path.clone());
+                builder.push(")::DOUBLE PRECISION END)");
+            }
+        }
+    }
+}
+
+impl Condition {
+    fn push_sql(&self, builder: &mut QueryBuilder<'_, sqlx::Postgres>) {
+        match self {
+            Condition::Text { target, op, value } => {
+                let pattern = match op {
+                    TextOp::Eq => None,
+                    TextOp::Contains | TextOp::NotContains => {
+                        Some(format!("%{}%", like_escape(value)))
+                    }
+                    TextOp::StartsWith => Some(format!("{}%", like_escape(value))),
+                    TextOp::EndsWith => Some(format!("%{}", like_escape(value))),
+                };
+                match (op, pattern) {
+                    (TextOp::NotContains, Some(pattern)) => {
+                        builder.push("(");
+                        target.push_sql(builder, false);
+                        builder.push(" IS NULL OR ");
+                        target.push_sql(builder, false);
+                        builder.push(" NOT ILIKE ");
+                        builder.push_bind(pattern);
+                        builder.push(")");
+                    }
+                    (_, Some(pattern)) => {
+                        target.push_sql(builder, false);
+                        builder.push(" ILIKE ");
+                        builder.push_bind(pattern);
+                    }
+                    (_, None) => {
+                        target.push_sql(builder, false);
+                        builder.push(" = ");
+                        builder.push_bind(value.clone());
+                    }
+                }
+            }
+            Condition::Options {
+                expr,
+                none_of,
+                values,
+            } => {
+                if *none_of {
+                    builder.push(format!("({expr} IS NULL OR NOT {expr} = ANY("));
+                    builder.push_bind(values.clone());
+                    builder.push("))");
+                } else {
+                    builder.push(format!("{expr} = ANY("));
+                    builder.push_bind(values.clone());
+                    builder.push(")");
+                }
+            }
+            Condition::Compare { target, op, value } => {
+                target.push_sql(builder, matches!(value, Bound::Number(_)));
+                builder.push(format!(" {op} "));
+                match value {
+                    Bound::Datetime(v) => builder.push_bind(*v),
+                    Bound::Number(v) => builder.push_bind(*v),
+                    Bound::Boolean(v) => builder.push_bind(*v),
+                };
+            }
+            Condition::Array { expr, op, values } => {
+                builder.push(match op {
+                    ArrayOp::Overlaps => format!("{expr} && "),
+                    ArrayOp::Contains => format!("{expr} @> "),
+                    ArrayOp::Disjoint => format!("NOT {expr} && "),
+                });
+                builder.push_bind(values.clone());
+            }
+            Condition::Null { target, not } => {
+                target.push_sql(builder, false);
+                builder.push(if *not { " IS NOT NULL" } else { " IS NULL" });
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! `GET /api/public/traces/:traceId/tree`: the observations of a trace nested
+//! by `parentObservationId`, with the duration and cost of each node and its
+//! subtree.
+//!
+//! Observations whose parent is not part of the trace are roots, as is one
+//! observation of every parent cycle. Siblings are ordered by start time.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        error::ApiError,
+        traces::{fetch_trace_observations, ObservationsViewDto},
+    },
+    state::AppState,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TreeNode {
+    #[serde(flatten)]
+    observation: ObservationsViewDto,
+    /// Seconds, see [`ObservationsViewDto::duration`].
+    duration: Option<f64>,
+    /// Calculated cost of this observation.
+    cost: f64,
+    /// Cost of this observation and all its descendants.
+    subtree_cost: f64,
+    children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceTree {
+    trace_id: Uuid,
+    observation_count: usize,
+    total_cost: f64,
+    roots: Vec<TreeNode>,
+}
+
+pub(crate) async fn get_trace_tree(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(trace_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM traces WHERE id = $1 AND project_id = $2)",
+    )
+    .bind(trace_id)
+    .bind(scope.project_id())
+    .fetch_one(&state.pool)
+    .await?;
+    if !exists {
+        return Err(ApiError::NotFound);
+    }
+
+    let observations = fetch_trace_observations(&state, trace_id, None).await?;
+    let observation_count = observations.len();
+    let roots = build_tree(observations);
+    Ok((
+        StatusCode::OK,
+        Json(TraceTree {
+            trace_id,
+            observation_count,
+            total_cost: roots.iter().map(|n| n.subtree_cost).sum(),
+            roots,
+        }),
+    ))
+}
+
+/// Nests `observations` (in start time order) under their parents, without
+/// recursion so deep chains cannot exhaust the stack.
+fn build_tree(observations: Vec<ObservationsViewDto>) -> Vec<TreeNode> {
+    let n = observations.len();
+    let index: HashMap<Uuid, usize> = observations
+        .iter()
+        .enumerate()
+        .map(|(i, o)| (o.id(), i))
+        .collect();
+    let mut parent: Vec<Option<usize>> = observations
+        .iter()
+        .enumerate()
+        .map(|(i, o)| {
+            o.parent_observation_id()
+                .and_then(|p| index.get(&p).copied())
+                .filter(|p| *p != i)
+        })
+        .collect();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (i, p) in parent.iter().enumerate() {
+        if let Some(p) = p {
+            children[*p].push(i);
+        }
+    }
+
+    // Pre-order, so every node comes before its descendants. Nodes on a
+    // parent cycle are unreachable from the roots; the first of each becomes
+    // a root.
+    let mut order = Vec::with_capacity(n);
+    let mut visited = vec![false; n];
+    for (i, p) in parent.iter().enumerate() {
+        if p.is_none() {
+            visit(i, &children, &mut visited, &mut order);
+        }
+    }
+    for i in 0..n {
+        if !visited[i] {
+            parent[i] = None;
+            visit(i, &children, &mut visited, &mut order);
+        }
+    }
+
+    // Assemble bottom-up: children are complete before their parent.
+    let mut nodes: Vec<Option<TreeNode>> = observations
+        .into_iter()
+        .map(|observation| {
+            let cost = observation.cost();
+            Some(TreeNode {
+                duration: observation.duration(),
+                cost,
+                subtree_cost: cost,
+                children: Vec::new(),
+                observation,
+            })
+        })
+        .collect();
+    let mut roots = Vec::new();
+    for &i in order.iter().rev() {
+        let Some(mut node) = nodes[i].take() else {
+            continue;
+        };
+        node.children.reverse();
+        node.subtree_cost += node.children.iter().map(|c| c.subtree_cost).sum::<f64>();
+        match parent[i].and_then(|p| nodes[p].as_mut()) {
+            Some(p) => p.children.push(node),
+            None => roots.push(node),
+        }
+    }
+    roots.reverse();
+    roots
+}
+
+fn visit(start: usize, children: &[Vec<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+    let mut stack = vec![start];
+    while let Some(i) = stack.pop() {
+        if std::mem::replace(&mut visited[i], true) {
+            continue;
+        }
+        order.push(i);
+        stack.extend(children[i].iter().rev());
+    }
+}
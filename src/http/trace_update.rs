@@ -0,0 +1,167 @@
+//! `PATCH /api/public/traces/:traceId`: triage of stored traces. Updates any
+//! of `tags` (added and removed), `bookmarked`, `public` and `metadata`
+//! (merged by top-level key) and leaves every other field as ingested.
+//!
+//! Re-ingesting the trace replaces tags and metadata again; `bookmarked` and
+//! `public` only change when a payload sets them. The update takes the
+//! trace's ingest write lock, so it lands before or after a concurrent ingest
+//! write, not in the middle of one.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    http::{auth::ProjectScope, error::ApiError},
+    ingest::batch,
+    state::AppState,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagChanges {
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PatchTraceRequest {
+    #[serde(default)]
+    tags: TagChanges,
+    bookmarked: Option<bool>,
+    public: Option<bool>,
+    /// Keys set to `null` are removed.
+    metadata: Option<Map<String, JsonValue>>,
+}
+
+impl PatchTraceRequest {
+    fn is_empty(&self) -> bool {
+        self.tags.add.is_empty()
+            && self.tags.remove.is_empty()
+            && self.bookmarked.is_none()
+            && self.public.is_none()
+            && self.metadata.is_none()
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PatchedRow {
+    tags: Vec<String>,
+    bookmarked: bool,
+    public: bool,
+    metadata: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PatchTraceResponse {
+    id: Uuid,
+    tags: Vec<String>,
+    bookmarked: bool,
+    public: bool,
+    metadata: JsonValue,
+    /// Path of the unauthenticated share link, for public traces.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    share_path: Option<String>,
+}
+
+pub(crate) async fn patch_trace(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(trace_id): Path<Uuid>,
+    Json(req): Json<PatchTraceRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.is_empty() {
+        return Err(ApiError::BadRequest(
+            "nothing to update: set tags, bookmarked, public or metadata".to_string(),
+        ));
+    }
+    state
+        .write_gate
+        .try_reserve(1)
+        .map_err(|mode| state.writes_paused(mode))?;
+    let result = patch(&state.pool, scope.project_id(), trace_id, req).await;
+    state.write_gate.release(1);
+    let row = result?.ok_or(ApiError::NotFound)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PatchTraceResponse {
+            id: trace_id,
+            tags: row.tags,
+            bookmarked: row.bookmarked,
+            public: row.public,
+            metadata: row.metadata.unwrap_or(JsonValue::Null),
+            share_path: row
+                .public
+                .then(|| format!("/api/public/shared/traces/{trace_id}")),
+        }),
+    ))
+}
+
+/// Applies the changes under the trace's write lock, so they do not race
+/// with an ingest write of the same trace.
+async fn patch(
+    pool: &PgPool,
+    project_id: &str,
+    trace_id: Uuid,
+    req: PatchTraceRequest,
+) -> Result<Option<PatchedRow>, sqlx::Error> {
+    let (metadata_set, metadata_removed) = match req.metadata {
+        Some(fields) => {
+            let (removed, set): (Vec<_>, Vec<_>) =
+                fields.into_iter().partition(|(_, v)| v.is_null());
+            (
+                Some(JsonValue::Object(set.into_iter().collect())),
+                removed.into_iter().map(|(k, _)| k).collect(),
+            )
+        }
+        None => (None, Vec::new()),
+    };
+
+    let mut tx = pool.begin().await?;
+    batch::lock_traces(&mut tx, &[trace_id]).await?;
+    // Tags keep their order; added ones are appended once. Metadata that is
+    // not an object is replaced when fields are merged.
+    let row = sqlx::query_as::<_, PatchedRow>(
+        r#"
+UPDATE traces SET
+  tags = ARRAY(
+    SELECT tag FROM unnest(tags || $3::TEXT[]) WITH ORDINALITY AS u(tag, i)
+    WHERE tag <> ALL($4::TEXT[])
+    GROUP BY tag ORDER BY MIN(i)
+  ),
+  bookmarked = COALESCE($5, bookmarked),
+  public = COALESCE($6, public),
+  metadata = CASE WHEN $7::JSONB IS NULL THEN metadata ELSE
+    (CASE WHEN jsonb_typeof(metadata) = 'object' THEN metadata ELSE '{}'::JSONB END
+      - $8::TEXT[]) || $7::JSONB
+  END,
+  updated_at = NOW()
+WHERE id = $1 AND project_id = $2
+RETURNING tags, bookmarked, public, metadata
+        "#,
+    )
+    .bind(trace_id)
+    .bind(project_id)
+    .bind(&req.tags.add)
+    .bind(&req.tags.remove)
+    .bind(req.bookmarked)
+    .bind(req.public)
+    .bind(metadata_set)
+    .bind(metadata_removed)
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(row)
+}
@@ -1,25 +1,31 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, StatusCode},
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use sqlx::QueryBuilder;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
+use xtrace_client::TraceFields;
 
 use crate::{
     http::{
+        auth::ProjectScope,
         common::{PageMeta, PagedData},
+        counts::{self, CountGroupBy, CountQuery},
         error::ApiError,
+        latency::latency_breakdown,
+        scores::{fetch_score_ids, fetch_scores_by_trace, fetch_trace_scores, ScoreV1Dto},
+        trace_filter::TraceFilter,
     },
     state::AppState,
 };
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Deserialize)]
 #[allow(non_snake_case)]
 pub(crate) struct TraceListQuery {
     #[serde(default)]
@@ -52,8 +58,31 @@ pub(crate) struct TraceListQuery {
     #[serde(default)]
     environment: Vec<String>,
 
+    #[serde(default, rename = "serviceName")]
+    service_name: Option<String>,
+    #[serde(default, rename = "serviceVersion")]
+    service_version: Option<String>,
+    #[serde(default, rename = "sdkLanguage")]
+    sdk_language: Option<String>,
+    #[serde(default, rename = "hostName")]
+    host_name: Option<String>,
+    /// Traces with at least one observation from this provider.
     #[serde(default)]
-    fields: Option<String>,
+    provider: Option<String>,
+    /// Full-text search over the name, input, output and metadata of the
+    /// trace and its observations (web search syntax: `"phrase"`, `or`,
+    /// `-word`).
+    #[serde(default, rename = "q", alias = "searchQuery")]
+    search: Option<String>,
+    /// Traces with at least one observation at or above this level.
+    #[serde(default, rename = "minLevel")]
+    min_level: Option<MinLevel>,
+    /// Structured conditions, see [`TraceFilter`].
+    #[serde(default)]
+    filter: TraceFilter,
+
+    #[serde(default)]
+    fields: Option<TraceFields>,
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -76,9 +105,17 @@ struct TraceListRow {
     environment: String,
     latency: Option<f64>,
     total_cost: Option<f64>,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
     observations: Vec<Uuid>,
+    observation_count: i64,
+    error_count: i64,
+    total_tokens: i64,
+    time_to_first_token: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -106,45 +143,167 @@ struct TraceListItem {
     html_path: String,
     latency: Option<f64>,
     total_cost: Option<f64>,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
     observations: Vec<String>,
     scores: Vec<String>,
+    observation_count: i64,
+    /// Observations at level `ERROR`.
+    error_count: i64,
+    total_tokens: i64,
+    /// Seconds from the trace start to the first streamed token of any
+    /// observation.
+    time_to_first_token: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    observation_summary: Option<ObservationSummary>,
 }
 
-#[derive(Clone, Copy)]
-struct TraceFieldsMask {
-    io: bool,
-    scores: bool,
-    observations: bool,
-    metrics: bool,
+/// Per-trace rollup of child observations, returned with `fields=observationSummary`.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ObservationSummary {
+    counts_by_type: JsonValue,
+    error_count: i64,
+    models: Vec<String>,
+    total_tokens: i64,
 }
 
-fn parse_trace_fields(fields: Option<&str>) -> TraceFieldsMask {
-    let Some(fields) = fields else {
-        return TraceFieldsMask {
-            io: true,
-            scores: true,
-            observations: true,
-            metrics: true,
-        };
-    };
+#[derive(Debug, sqlx::FromRow)]
+struct ObservationSummaryRow {
+    trace_id: Uuid,
+    counts_by_type: JsonValue,
+    error_count: i64,
+    models: Vec<String>,
+    total_tokens: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ObservationIntervalRow {
+    trace_id: Uuid,
+    r#type: String,
+    start_time: DateTime<Utc>,
+    end_time: Option<DateTime<Utc>>,
+}
 
-    let set: HashSet<&str> = fields
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect();
+/// Latency breakdown (see [`latency_breakdown`]) of each trace, over all of
+/// its observations. Observations without `endTime` end after their `latency`.
+async fn fetch_latency_breakdowns(
+    pool: &sqlx::PgPool,
+    trace_ids: &[Uuid],
+) -> Result<HashMap<Uuid, BTreeMap<String, f64>>, ApiError> {
+    if trace_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let rows: Vec<ObservationIntervalRow> = sqlx::query_as(
+        r#"
+SELECT trace_id, type, start_time,
+  COALESCE(end_time, start_time + latency * INTERVAL '1 second') AS end_time
+FROM observations
+WHERE trace_id = ANY($1) AND start_time IS NOT NULL
+        "#,
+    )
+    .bind(trace_ids)
+    .fetch_all(pool)
+    .await?;
 
-    TraceFieldsMask {
-        io: set.contains("io"),
-        scores: set.contains("scores"),
-        observations: set.contains("observations"),
-        metrics: set.contains("metrics"),
+    let mut by_trace: HashMap<Uuid, Vec<ObservationIntervalRow>> = HashMap::new();
+    for row in rows {
+        by_trace.entry(row.trace_id).or_default().push(row);
     }
+    Ok(by_trace
+        .into_iter()
+        .map(|(trace_id, rows)| {
+            let breakdown = latency_breakdown(
+                rows.iter()
+                    .map(|r| (r.r#type.as_str(), r.start_time, r.end_time)),
+            );
+            (trace_id, breakdown)
+        })
+        .collect())
 }
 
-fn apply_trace_filters(builder: &mut QueryBuilder<'_, sqlx::Postgres>, q: &TraceListQuery) {
+async fn fetch_observation_summaries(
+    pool: &sqlx::PgPool,
+    trace_ids: &[Uuid],
+) -> Result<HashMap<Uuid, ObservationSummary>, ApiError> {
+    if trace_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let rows: Vec<ObservationSummaryRow> = sqlx::query_as(
+        r#"
+WITH per_type AS (
+  SELECT trace_id, type, COUNT(*)::BIGINT AS cnt
+  FROM observations
+  WHERE trace_id = ANY($1)
+  GROUP BY trace_id, type
+)
+SELECT
+  o.trace_id,
+  COALESCE(
+    (SELECT jsonb_object_agg(pt.type, pt.cnt) FROM per_type pt WHERE pt.trace_id = o.trace_id),
+    '{}'::jsonb
+  ) AS counts_by_type,
+  COUNT(*) FILTER (WHERE o.level = 'ERROR')::BIGINT AS error_count,
+  COALESCE(array_agg(DISTINCT o.model) FILTER (WHERE o.model IS NOT NULL), '{}') AS models,
+  COALESCE(SUM(o.total_tokens), 0)::BIGINT AS total_tokens
+FROM observations o
+WHERE o.trace_id = ANY($1)
+GROUP BY o.trace_id
+        "#,
+    )
+    .bind(trace_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            (
+                r.trace_id,
+                ObservationSummary {
+                    counts_by_type: r.counts_by_type,
+                    error_count: r.error_count,
+                    models: r.models,
+                    total_tokens: r.total_tokens,
+                },
+            )
+        })
+        .collect())
+}
+
+impl TraceListQuery {
+    /// Whether any filter (not paging or ordering) is set.
+    pub(crate) fn has_filters(&self) -> bool {
+        self.user_id.is_some()
+            || self.name.is_some()
+            || self.session_id.is_some()
+            || self.from_timestamp.is_some()
+            || self.to_timestamp.is_some()
+            || !self.tags.is_empty()
+            || self.version.is_some()
+            || self.release.is_some()
+            || !self.environment.is_empty()
+            || self.service_name.is_some()
+            || self.service_version.is_some()
+            || self.sdk_language.is_some()
+            || self.host_name.is_some()
+            || self.provider.is_some()
+            || search_query(self.search.as_deref()).is_some()
+            || self.min_level.is_some()
+            || !self.filter.is_empty()
+    }
+}
+
+pub(crate) fn apply_trace_filters(
+    builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    q: &TraceListQuery,
+) {
     if let Some(user_id) = &q.user_id {
         builder.push(" AND t.user_id = ");
         builder.push_bind(user_id.clone());
@@ -183,6 +342,100 @@ fn apply_trace_filters(builder: &mut QueryBuilder<'_, sqlx::Postgres>, q: &Trace
         builder.push_bind(q.environment.clone());
         builder.push(")");
     }
+    if let Some(service_name) = &q.service_name {
+        builder.push(" AND t.service_name = ");
+        builder.push_bind(service_name.clone());
+    }
+    if let Some(service_version) = &q.service_version {
+        builder.push(" AND t.service_version = ");
+        builder.push_bind(service_version.clone());
+    }
+    if let Some(sdk_language) = &q.sdk_language {
+        builder.push(" AND t.sdk_language = ");
+        builder.push_bind(sdk_language.clone());
+    }
+    if let Some(host_name) = &q.host_name {
+        builder.push(" AND t.host_name = ");
+        builder.push_bind(host_name.clone());
+    }
+    if let Some(provider) = &q.provider {
+        builder.push(
+            " AND EXISTS (SELECT 1 FROM observations o WHERE o.trace_id = t.id AND o.provider = ",
+        );
+        builder.push_bind(provider.clone());
+        builder.push(")");
+    }
+    if let Some(search) = search_query(q.search.as_deref()) {
+        builder.push(" AND (");
+        push_search_match(builder, "t.", search);
+        builder.push(" OR EXISTS (SELECT 1 FROM observations o WHERE o.trace_id = t.id AND ");
+        push_search_match(builder, "o.", search);
+        builder.push("))");
+    }
+    if let Some(min_level) = q.min_level {
+        builder.push(
+            " AND EXISTS (SELECT 1 FROM observations o WHERE o.trace_id = t.id AND (o.level = ANY(",
+        );
+        builder.push_bind(min_level.levels());
+        builder.push(")");
+        if min_level.includes_default() {
+            // Observations without a level are `DEFAULT`.
+            builder.push(" OR o.level IS NULL");
+        }
+        builder.push("))");
+    }
+    q.filter.push_sql(builder);
+}
+
+/// Observation levels, least severe first.
+pub(crate) const LEVELS: [&str; 4] = ["DEBUG", "DEFAULT", "WARNING", "ERROR"];
+
+/// `minLevel` of the trace list: an index into [`LEVELS`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MinLevel(usize);
+
+impl MinLevel {
+    fn levels(self) -> Vec<String> {
+        LEVELS[self.0..].iter().map(|l| l.to_string()).collect()
+    }
+
+    fn includes_default(self) -> bool {
+        self.0 <= 1
+    }
+}
+
+impl<'de> Deserialize<'de> for MinLevel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        LEVELS
+            .iter()
+            .position(|l| l.eq_ignore_ascii_case(&s))
+            .map(MinLevel)
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "invalid minLevel: {s} (expected DEBUG, DEFAULT, WARNING or ERROR)"
+                ))
+            })
+    }
+}
+
+/// The search text of a `q` parameter; `None` when blank.
+pub(crate) fn search_query(q: Option<&str>) -> Option<&str> {
+    q.map(str::trim).filter(|q| !q.is_empty())
+}
+
+/// Matches `search` against the `search_document` index of the row at
+/// `alias` (`"t."`, `"o."` or `""`).
+pub(crate) fn push_search_match(
+    builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    alias: &str,
+    search: &str,
+) {
+    builder.push(format!(
+        "search_document({alias}name, {alias}input, {alias}output, {alias}metadata) @@ websearch_to_tsquery('simple', "
+    ));
+    builder.push_bind(search.to_string());
+    builder.push(")");
 }
 
 fn parse_order_by(order_by: Option<&str>) -> Result<(&'static str, bool), ApiError> {
@@ -200,6 +453,10 @@ fn parse_order_by(order_by: Option<&str>) -> Result<(&'static str, bool), ApiErr
         "sessionId" | "session_id" => ("t.session_id", false),
         "latency" => ("t.latency", true),
         "totalCost" | "total_cost" => ("t.total_cost", true),
+        // Aggregates over the observations joined by the list query.
+        "totalTokens" | "total_tokens" => ("COALESCE(SUM(o.total_tokens), 0)", true),
+        "observationCount" | "observation_count" => ("COUNT(o.id)", true),
+        "errorCount" | "error_count" => ("COUNT(o.id) FILTER (WHERE o.level = 'ERROR')", true),
         _ => return Err(ApiError::BadRequest("invalid order_by".into())),
     };
     let desc = match dir {
@@ -210,21 +467,72 @@ fn parse_order_by(order_by: Option<&str>) -> Result<(&'static str, bool), ApiErr
     Ok((col, desc))
 }
 
+fn trace_count_group(group_by: &str) -> Option<CountGroupBy> {
+    Some(match group_by {
+        "name" => CountGroupBy::column("t.name"),
+        "environment" => CountGroupBy::column("t.environment"),
+        "userId" => CountGroupBy::column("t.user_id"),
+        "sessionId" => CountGroupBy::column("t.session_id"),
+        "release" => CountGroupBy::column("t.release"),
+        "version" => CountGroupBy::column("t.version"),
+        "serviceName" => CountGroupBy::column("t.service_name"),
+        // Traces per observation model; a trace using two models counts for both.
+        "model" => CountGroupBy {
+            expr: "o.model",
+            join: Some("JOIN observations o ON o.trace_id = t.id"),
+            distinct: Some("t.id"),
+        },
+        _ => return None,
+    })
+}
+
+/// Number of traces matching the list filters, optionally per `groupBy`.
+pub(crate) async fn count_traces(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(mut q): Query<TraceListQuery>,
+    Query(c): Query<CountQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.from_timestamp = state
+        .query_windows
+        .list_from(q.from_timestamp, q.to_timestamp);
+    let group = match c.group_by.as_deref() {
+        None => None,
+        Some(g) => Some(trace_count_group(g).ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "invalid groupBy: {g} (expected name, environment, userId, sessionId, release, version, serviceName or model)"
+            ))
+        })?),
+    };
+    let project_id = scope.project_id().to_string();
+    let filters = |b: &mut QueryBuilder<'_, sqlx::Postgres>| {
+        b.push(" WHERE t.project_id = ");
+        b.push_bind(project_id.clone());
+        apply_trace_filters(b, &q);
+    };
+    let resp = counts::count(&state.pool, "traces t", &c, group, &filters).await?;
+    Ok((StatusCode::OK, Json(resp)))
+}
+
 pub(crate) async fn get_traces(
     State(state): State<AppState>,
-    Query(q): Query<TraceListQuery>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(mut q): Query<TraceListQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
+    q.from_timestamp = state
+        .query_windows
+        .list_from(q.from_timestamp, q.to_timestamp);
     let page = q.page.unwrap_or(1).max(1);
     let limit = q.limit.unwrap_or(50).clamp(1, 200);
     let offset = (page - 1) * limit;
 
-    let fields = parse_trace_fields(q.fields.as_deref());
+    let fields = q.fields.unwrap_or_default();
     let (order_column, order_desc) = parse_order_by(q.order_by.as_deref())?;
 
     let mut count_builder: QueryBuilder<'_, sqlx::Postgres> =
         QueryBuilder::new("SELECT COUNT(*)::BIGINT AS cnt FROM traces t WHERE 1=1");
     count_builder.push(" AND t.project_id = ");
-    count_builder.push_bind(state.default_project_id.to_string());
+    count_builder.push_bind(scope.project_id().to_string());
     apply_trace_filters(&mut count_builder, &q);
 
     let total_items: i64 = count_builder
@@ -259,9 +567,20 @@ SELECT
   t.environment,
   t.latency,
   t.total_cost,
+  t.service_name,
+  t.service_version,
+  t.sdk_language,
+  t.host_name,
   t.created_at,
   t.updated_at,
-  COALESCE(array_agg(o.id) FILTER (WHERE o.id IS NOT NULL), '{}') AS observations
+  COALESCE(array_agg(o.id) FILTER (WHERE o.id IS NOT NULL), '{}') AS observations,
+  COUNT(o.id)::BIGINT AS observation_count,
+  COUNT(o.id) FILTER (WHERE o.level = 'ERROR')::BIGINT AS error_count,
+  COALESCE(SUM(o.total_tokens), 0)::BIGINT AS total_tokens,
+  EXTRACT(EPOCH FROM MIN(COALESCE(
+    o.completion_start_time,
+    o.start_time + o.time_to_first_token * INTERVAL '1 second'
+  )) - t.timestamp)::DOUBLE PRECISION AS time_to_first_token
 FROM traces t
 LEFT JOIN observations o ON o.trace_id = t.id
 WHERE 1=1
@@ -269,14 +588,18 @@ WHERE 1=1
     );
 
     builder.push(" AND t.project_id = ");
-    builder.push_bind(state.default_project_id.to_string());
+    builder.push_bind(scope.project_id().to_string());
 
     apply_trace_filters(&mut builder, &q);
-    builder.push(" GROUP BY t.id");
+    builder.push(" GROUP BY t.id, t.timestamp");
 
     builder.push(" ORDER BY ");
     builder.push(order_column);
     builder.push(if order_desc { " DESC" } else { " ASC" });
+    // Stable pages when many traces tie, e.g. on an aggregate.
+    if order_column != "t.id" {
+        builder.push(", t.id");
+    }
     builder.push(" LIMIT ");
     builder.push_bind(limit);
     builder.push(" OFFSET ");
@@ -284,9 +607,35 @@ WHERE 1=1
 
     let rows: Vec<TraceListRow> = builder.build_query_as().fetch_all(&state.pool).await?;
 
+    let mut summaries = if fields.observation_summary {
+        let ids = rows.iter().map(|r| r.id).collect::<Vec<_>>();
+        fetch_observation_summaries(&state.pool, &ids).await?
+    } else {
+        HashMap::new()
+    };
+    let mut score_ids = if fields.scores {
+        let ids = rows.iter().map(|r| r.id).collect::<Vec<_>>();
+        fetch_score_ids(&state.pool, &ids).await?
+    } else {
+        HashMap::new()
+    };
+
     let items = rows
         .into_iter()
         .map(|r| {
+            let observation_summary = if fields.observation_summary {
+                Some(
+                    summaries
+                        .remove(&r.id)
+                        .unwrap_or_else(|| ObservationSummary {
+                            counts_by_type: serde_json::json!({}),
+                            ..Default::default()
+                        }),
+                )
+            } else {
+                None
+            };
+
             let observations = if fields.observations {
                 r.observations
                     .into_iter()
@@ -295,11 +644,7 @@ WHERE 1=1
             } else {
                 vec![]
             };
-            let scores = if fields.scores {
-                Vec::new()
-            } else {
-                Vec::with_capacity(0)
-            };
+            let scores = score_ids.remove(&r.id).unwrap_or_default();
 
             let latency = if fields.metrics {
                 r.latency
@@ -344,10 +689,19 @@ WHERE 1=1
                 environment: r.environment,
                 latency,
                 total_cost,
+                service_name: r.service_name,
+                service_version: r.service_version,
+                sdk_language: r.sdk_language,
+                host_name: r.host_name,
                 created_at: r.created_at,
                 updated_at: r.updated_at,
                 observations,
                 scores,
+                observation_count: r.observation_count,
+                error_count: r.error_count,
+                total_tokens: r.total_tokens,
+                time_to_first_token: r.time_to_first_token,
+                observation_summary,
             }
         })
         .collect::<Vec<_>>();
@@ -366,9 +720,60 @@ WHERE 1=1
     ))
 }
 
+/// Columns selected into [`ObservationRow`]. Resource attributes moved to
+/// `otel_resources` at ingest are put back into `metadata`.
+pub(crate) const OBSERVATION_COLUMNS: &str = r#"
+  id,
+  trace_id,
+  type as "type",
+  name,
+  start_time,
+  end_time,
+  completion_start_time,
+  model,
+  model_parameters,
+  input,
+  output,
+  input_offload,
+  output_offload,
+  usage,
+  level,
+  status_message,
+  parent_observation_id,
+  prompt_id,
+  prompt_name,
+  prompt_version,
+  model_id,
+  input_price,
+  output_price,
+  total_price,
+  calculated_input_cost,
+  calculated_output_cost,
+  calculated_total_cost,
+  latency,
+  time_to_first_token,
+  completion_tokens,
+  prompt_tokens,
+  total_tokens,
+  unit,
+  CASE WHEN resource_id IS NULL THEN metadata
+    ELSE COALESCE(metadata, '{}'::jsonb) || jsonb_build_object('otel.resource',
+      (SELECT r.attributes FROM otel_resources r WHERE r.id = resource_id))
+  END AS metadata,
+  environment,
+  service_name,
+  service_version,
+  sdk_language,
+  host_name,
+  provider,
+  project_id,
+  created_at,
+  updated_at
+"#;
+
 #[derive(Debug, Serialize, sqlx::FromRow)]
 #[allow(dead_code)]
-struct ObservationRow {
+pub(crate) struct ObservationRow {
     id: Uuid,
     trace_id: Uuid,
     r#type: String,
@@ -380,6 +785,11 @@ struct ObservationRow {
     model_parameters: Option<JsonValue>,
     input: Option<JsonValue>,
     output: Option<JsonValue>,
+    /// Object keys of offloaded bodies; `input` / `output` hold previews.
+    #[serde(skip)]
+    input_offload: Option<String>,
+    #[serde(skip)]
+    output_offload: Option<String>,
     usage: Option<JsonValue>,
     level: Option<String>,
     status_message: Option<String>,
@@ -402,11 +812,41 @@ struct ObservationRow {
     unit: Option<String>,
     metadata: Option<JsonValue>,
     environment: String,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
+    provider: Option<String>,
     project_id: String,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
 
+impl ObservationRow {
+    pub(crate) fn trace_id(&self) -> Uuid {
+        self.trace_id
+    }
+}
+
+/// Puts offloaded inputs and outputs back in place of their previews.
+pub(crate) async fn rehydrate_observations(state: &AppState, rows: &mut [ObservationRow]) {
+    let Some(offloader) = &state.offloader else {
+        return;
+    };
+    let mut targets = Vec::new();
+    for row in rows.iter_mut() {
+        if let Some(key) = row.input_offload.clone() {
+            targets.push((&mut row.input, key));
+        }
+        if let Some(key) = row.output_offload.clone() {
+            targets.push((&mut row.output, key));
+        }
+    }
+    if !targets.is_empty() {
+        offloader.rehydrate(targets).await;
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct PublicUsage {
@@ -421,7 +861,7 @@ struct PublicUsage {
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct ObservationsViewDto {
+pub(crate) struct ObservationsViewDto {
     id: Uuid,
     trace_id: Option<Uuid>,
     r#type: String,
@@ -457,6 +897,33 @@ struct ObservationsViewDto {
     usage_details: JsonValue,
     cost_details: JsonValue,
     environment: String,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
+    provider: Option<String>,
+}
+
+impl ObservationsViewDto {
+    pub(crate) fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub(crate) fn parent_observation_id(&self) -> Option<Uuid> {
+        self.parent_observation_id
+    }
+
+    /// Seconds from start to end, or the reported `latency` without an end.
+    pub(crate) fn duration(&self) -> Option<f64> {
+        match self.end_time {
+            Some(end) => Some((end - self.start_time).num_milliseconds() as f64 / 1000.0),
+            None => self.latency,
+        }
+    }
+
+    pub(crate) fn cost(&self) -> f64 {
+        self.calculated_total_cost.unwrap_or(0.0)
+    }
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -479,13 +946,19 @@ struct TraceRow {
     external_id: Option<String>,
     bookmarked: bool,
     project_id: String,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
+    completed_at: Option<DateTime<Utc>>,
+    late_writes: i32,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct TraceDetailDto {
+pub(crate) struct TraceDetailDto {
     id: Uuid,
     timestamp: DateTime<Utc>,
     name: Option<String>,
@@ -505,195 +978,242 @@ struct TraceDetailDto {
     html_path: String,
     latency: Option<f64>,
     total_cost: Option<f64>,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
+    /// Set once the trace was explicitly completed.
+    completed_at: Option<DateTime<Utc>>,
+    /// Writes received after completion (stored or dropped, per completion mode).
+    late_writes: i32,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
     observations: Vec<ObservationsViewDto>,
+    /// Present when observations were paginated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    observations_meta: Option<PageMeta>,
+    observation_summary: ObservationSummary,
+    /// Wall-clock seconds per observation type, overlapping time counted once.
+    latency_breakdown: BTreeMap<String, f64>,
     scores: Vec<ScoreV1Dto>,
 }
 
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ScoreV1Dto {
-    #[serde(rename = "dataType")]
-    data_type: String,
-    id: String,
-    trace_id: String,
-    name: String,
-    source: String,
-    observation_id: Option<String>,
-    timestamp: DateTime<Utc>,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
-    author_user_id: Option<String>,
-    comment: Option<String>,
-    metadata: JsonValue,
-    config_id: Option<String>,
-    queue_id: Option<String>,
-    environment: String,
-    value: f64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    string_value: Option<String>,
+#[derive(Debug, Deserialize)]
+pub(crate) struct TraceDetailQuery {
+    /// Paginate observations; when neither is set all observations are returned.
+    #[serde(default, rename = "observationsPage")]
+    observations_page: Option<i64>,
+    #[serde(default, rename = "observationsLimit")]
+    observations_limit: Option<i64>,
 }
 
 pub(crate) async fn get_trace(
     State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
     Path(trace_id): Path<Uuid>,
+    Query(q): Query<TraceDetailQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let trace: Option<TraceRow> = sqlx::query_as(
-        r#"
-SELECT
-  id,
-  timestamp,
-  name,
-  input,
-  output,
-  session_id,
-  release,
-  version,
-  user_id,
-  metadata,
-  tags,
-  public,
-  environment,
-  latency,
-  total_cost,
-  external_id,
-  bookmarked,
-  project_id,
-  created_at,
-  updated_at
-FROM traces
-WHERE id = $1
-         "#,
+    let dto = load_trace_detail(
+        &state,
+        trace_id,
+        TraceAccess::Project(scope.project_id()),
+        &q,
     )
-    .bind(trace_id)
-    .fetch_optional(&state.pool)
     .await?;
+    Ok((StatusCode::OK, Json(dto)))
+}
+
+/// Whether the trace exists, without loading it: `200` with the time of its
+/// last write as `Last-Modified`, else `404`. For checking delivery.
+pub(crate) async fn head_trace(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(trace_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let updated_at: DateTime<Utc> =
+        sqlx::query_scalar("SELECT updated_at FROM traces WHERE id = $1 AND project_id = $2")
+            .bind(trace_id)
+            .bind(scope.project_id())
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    let last_modified = updated_at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    Ok((StatusCode::OK, [(header::LAST_MODIFIED, last_modified)]))
+}
+
+/// Traces a detail request may see.
+pub(crate) enum TraceAccess<'a> {
+    Project(&'a str),
+    /// Traces marked `public`, of any project.
+    Shared,
+}
+
+/// The detail of one trace; `404` when it does not exist or `access` does
+/// not cover it.
+pub(crate) async fn load_trace_detail(
+    state: &AppState,
+    trace_id: Uuid,
+    access: TraceAccess<'_>,
+    q: &TraceDetailQuery,
+) -> Result<TraceDetailDto, ApiError> {
+    let trace: Option<TraceRow> = match access {
+        TraceAccess::Project(project_id) => {
+            sqlx::query_as(&format!(
+                "SELECT {TRACE_COLUMNS} FROM traces WHERE id = $1 AND project_id = $2"
+            ))
+            .bind(trace_id)
+            .bind(project_id)
+            .fetch_optional(&state.pool)
+            .await?
+        }
+        TraceAccess::Shared => {
+            sqlx::query_as(&format!(
+                "SELECT {TRACE_COLUMNS} FROM traces WHERE id = $1 AND public"
+            ))
+            .bind(trace_id)
+            .fetch_optional(&state.pool)
+            .await?
+        }
+    };
 
     let Some(trace) = trace else {
         return Err(ApiError::NotFound);
     };
 
-    let observations: Vec<ObservationRow> = sqlx::query_as(
-        r#"
-SELECT
-  id,
-  trace_id,
-  type as "type",
-  name,
-  start_time,
-  end_time,
-  completion_start_time,
-  model,
-  model_parameters,
-  input,
-  output,
-  usage,
-  level,
-  status_message,
-  parent_observation_id,
-  prompt_id,
-  prompt_name,
-  prompt_version,
-  model_id,
-  input_price,
-  output_price,
-  total_price,
-  calculated_input_cost,
-  calculated_output_cost,
-  calculated_total_cost,
-  latency,
-  time_to_first_token,
-  completion_tokens,
-  prompt_tokens,
-  total_tokens,
-  unit,
-  metadata,
-  environment,
-  project_id,
-  created_at,
-  updated_at
-FROM observations
-WHERE trace_id = $1
-ORDER BY start_time NULLS LAST, created_at
-         "#,
-    )
-    .bind(trace_id)
+    let summary = fetch_observation_summaries(&state.pool, &[trace_id])
+        .await?
+        .remove(&trace_id)
+        .unwrap_or_else(|| ObservationSummary {
+            counts_by_type: serde_json::json!({}),
+            ..Default::default()
+        });
+
+    let (obs_dtos, observations_meta) =
+        if q.observations_page.is_some() || q.observations_limit.is_some() {
+            let (page, limit) = observation_page_params(q.observations_page, q.observations_limit);
+            let total_items = count_trace_observations(&state.pool, trace_id).await?;
+            let obs = fetch_trace_observations(state, trace_id, Some((page, limit))).await?;
+            (obs, Some(page_meta(page, limit, total_items)))
+        } else {
+            (fetch_trace_observations(state, trace_id, None).await?, None)
+        };
+
+    let breakdown = fetch_latency_breakdowns(&state.pool, &[trace_id])
+        .await?
+        .remove(&trace_id)
+        .unwrap_or_default();
+    let scores = fetch_trace_scores(&state.pool, trace_id).await?;
+    Ok(trace_detail(
+        trace,
+        obs_dtos,
+        observations_meta,
+        summary,
+        breakdown,
+        scores,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct GetManyTracesRequest {
+    #[serde(rename = "traceIds")]
+    trace_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+struct GetManyTracesResponse {
+    /// Found traces, in request order.
+    data: Vec<TraceDetailDto>,
+    /// Requested ids that do not exist (or belong to another project).
+    missing: Vec<Uuid>,
+}
+
+/// Trace details for several ids in one round trip, with all observations
+/// (`POST /traces/batch-get`, or its older name `/traces/get-many`).
+pub(crate) async fn get_many_traces(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Json(req): Json<GetManyTracesRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut trace_ids = req.trace_ids;
+    let mut seen = std::collections::HashSet::new();
+    trace_ids.retain(|id| seen.insert(*id));
+    if trace_ids.len() > state.batch_get_max_traces {
+        return Err(ApiError::BadRequest(format!(
+            "at most {} traceIds per request",
+            state.batch_get_max_traces
+        )));
+    }
+
+    let rows: Vec<TraceRow> = sqlx::query_as(&format!(
+        "SELECT {TRACE_COLUMNS} FROM traces WHERE id = ANY($1) AND project_id = $2"
+    ))
+    .bind(&trace_ids)
+    .bind(scope.project_id())
     .fetch_all(&state.pool)
     .await?;
+    let found: Vec<Uuid> = rows.iter().map(|r| r.id).collect();
 
-    let obs_dtos = observations
-        .into_iter()
-        .map(|o| {
-            let prompt_tokens = o.prompt_tokens.unwrap_or(0);
-            let completion_tokens = o.completion_tokens.unwrap_or(0);
-            let total_tokens = o.total_tokens.unwrap_or(0);
-            let calculated_input_cost = o.calculated_input_cost.unwrap_or(0.0);
-            let calculated_output_cost = o.calculated_output_cost.unwrap_or(0.0);
-            let calculated_total_cost = o.calculated_total_cost.unwrap_or(0.0);
-
-            ObservationsViewDto {
-                version: None,
-                id: o.id,
-                trace_id: Some(o.trace_id),
-                r#type: o.r#type,
-                name: o.name,
-                start_time: o.start_time.unwrap_or(o.created_at),
-                end_time: o.end_time,
-                completion_start_time: o.completion_start_time,
-                model: o.model,
-                model_parameters: o.model_parameters.unwrap_or_else(|| serde_json::json!({})),
-                input: o.input.unwrap_or(JsonValue::Null),
-                metadata: o.metadata.unwrap_or(JsonValue::Null),
-                output: o.output.unwrap_or(JsonValue::Null),
-                usage: PublicUsage {
-                    input: prompt_tokens,
-                    output: completion_tokens,
-                    total: total_tokens,
-                    unit: o.unit.clone(),
-                    input_cost: o.calculated_input_cost,
-                    output_cost: o.calculated_output_cost,
-                    total_cost: o.calculated_total_cost,
-                },
-                usage_details: serde_json::json!({
-                    "input": prompt_tokens,
-                    "output": completion_tokens,
-                    "total": total_tokens
-                }),
-                cost_details: serde_json::json!({
-                    "input": calculated_input_cost,
-                    "output": calculated_output_cost,
-                    "total": calculated_total_cost
-                }),
-                level: o.level.unwrap_or_else(|| "DEFAULT".to_string()),
-                status_message: o.status_message,
-                parent_observation_id: o.parent_observation_id,
-                prompt_id: o.prompt_id,
-                prompt_name: o.prompt_name,
-                prompt_version: o
-                    .prompt_version
-                    .as_deref()
-                    .and_then(|s| s.parse::<i64>().ok()),
-                model_id: o.model_id,
-                input_price: o.input_price,
-                output_price: o.output_price,
-                total_price: o.total_price,
-                calculated_input_cost: o.calculated_input_cost,
-                calculated_output_cost: o.calculated_output_cost,
-                calculated_total_cost: o.calculated_total_cost,
-                latency: o.latency,
-                time_to_first_token: o.time_to_first_token,
-                prompt_tokens,
-                completion_tokens,
-                total_tokens,
-                environment: o.environment,
-            }
-        })
-        .collect::<Vec<_>>();
+    let mut summaries = fetch_observation_summaries(&state.pool, &found).await?;
+    let mut breakdowns = fetch_latency_breakdowns(&state.pool, &found).await?;
+    let mut scores = fetch_scores_by_trace(&state.pool, &found).await?;
+    let mut obs_rows: Vec<ObservationRow> = sqlx::query_as(&format!(
+        "SELECT {OBSERVATION_COLUMNS} FROM observations WHERE trace_id = ANY($1) ORDER BY start_time NULLS LAST, created_at, id"
+    ))
+    .bind(&found)
+    .fetch_all(&state.pool)
+    .await?;
+    rehydrate_observations(&state, &mut obs_rows).await;
+    let mut observations: HashMap<Uuid, Vec<ObservationsViewDto>> = HashMap::new();
+    for row in obs_rows {
+        observations
+            .entry(row.trace_id)
+            .or_default()
+            .push(observation_to_dto(row));
+    }
 
-    let dto = TraceDetailDto {
+    let mut by_id: HashMap<Uuid, TraceRow> = rows.into_iter().map(|r| (r.id, r)).collect();
+    let mut data = Vec::with_capacity(by_id.len());
+    let mut missing = Vec::new();
+    for id in trace_ids {
+        let Some(trace) = by_id.remove(&id) else {
+            missing.push(id);
+            continue;
+        };
+        let summary = summaries.remove(&id).unwrap_or_else(|| ObservationSummary {
+            counts_by_type: serde_json::json!({}),
+            ..Default::default()
+        });
+        data.push(trace_detail(
+            trace,
+            observations.remove(&id).unwrap_or_default(),
+            None,
+            summary,
+            breakdowns.remove(&id).unwrap_or_default(),
+            scores.remove(&id).unwrap_or_default(),
+        ));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(GetManyTracesResponse { data, missing }),
+    ))
+}
+
+const TRACE_COLUMNS: &str = r#"id, timestamp, name, input, output, session_id, release, version, user_id,
+  metadata, tags, public, environment, latency, total_cost, external_id, bookmarked, project_id,
+  service_name, service_version, sdk_language, host_name, completed_at, late_writes, created_at, updated_at"#;
+
+fn trace_detail(
+    trace: TraceRow,
+    observations: Vec<ObservationsViewDto>,
+    observations_meta: Option<PageMeta>,
+    observation_summary: ObservationSummary,
+    latency_breakdown: BTreeMap<String, f64>,
+    scores: Vec<ScoreV1Dto>,
+) -> TraceDetailDto {
+    TraceDetailDto {
         html_path: format!("/project/{}/traces/{}", trace.project_id, trace.id),
-        scores: vec![],
+        scores,
         id: trace.id,
         timestamp: trace.timestamp,
         name: trace.name,
@@ -712,10 +1232,181 @@ ORDER BY start_time NULLS LAST, created_at
         environment: trace.environment,
         latency: trace.latency,
         total_cost: trace.total_cost,
+        service_name: trace.service_name,
+        service_version: trace.service_version,
+        sdk_language: trace.sdk_language,
+        host_name: trace.host_name,
+        completed_at: trace.completed_at,
+        late_writes: trace.late_writes,
         created_at: trace.created_at,
         updated_at: trace.updated_at,
-        observations: obs_dtos,
+        observations,
+        observations_meta,
+        observation_summary,
+        latency_breakdown,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TraceObservationsQuery {
+    #[serde(default)]
+    page: Option<i64>,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// Paginated observations of one trace, in the same order as the trace detail.
+pub(crate) async fn get_trace_observations(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(trace_id): Path<Uuid>,
+    Query(q): Query<TraceObservationsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM traces WHERE id = $1 AND project_id = $2)",
+    )
+    .bind(trace_id)
+    .bind(scope.project_id())
+    .fetch_one(&state.pool)
+    .await?;
+    if !exists {
+        return Err(ApiError::NotFound);
+    }
+
+    let (page, limit) = observation_page_params(q.page, q.limit);
+    let total_items = count_trace_observations(&state.pool, trace_id).await?;
+    let data = fetch_trace_observations(&state, trace_id, Some((page, limit))).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data,
+            meta: page_meta(page, limit, total_items),
+        }),
+    ))
+}
+
+fn observation_page_params(page: Option<i64>, limit: Option<i64>) -> (i64, i64) {
+    (
+        page.unwrap_or(1).max(1),
+        limit.unwrap_or(100).clamp(1, 1000),
+    )
+}
+
+fn page_meta(page: i64, limit: i64, total_items: i64) -> PageMeta {
+    let total_pages = if total_items == 0 {
+        0
+    } else {
+        (total_items + limit - 1) / limit
     };
+    PageMeta {
+        page,
+        limit,
+        totalItems: total_items,
+        totalPages: total_pages,
+    }
+}
 
-    Ok((StatusCode::OK, Json(dto)))
+async fn count_trace_observations(pool: &sqlx::PgPool, trace_id: Uuid) -> Result<i64, ApiError> {
+    Ok(
+        sqlx::query_scalar("SELECT COUNT(*)::BIGINT FROM observations WHERE trace_id = $1")
+            .bind(trace_id)
+            .fetch_one(pool)
+            .await?,
+    )
+}
+
+/// Observations of a trace ordered by start time, with offloaded payloads;
+/// `page` is `(page, limit)`.
+pub(crate) async fn fetch_trace_observations(
+    state: &AppState,
+    trace_id: Uuid,
+    page: Option<(i64, i64)>,
+) -> Result<Vec<ObservationsViewDto>, ApiError> {
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(format!(
+        "SELECT {OBSERVATION_COLUMNS} FROM observations WHERE trace_id = "
+    ));
+    builder.push_bind(trace_id);
+    builder.push(" ORDER BY start_time NULLS LAST, created_at, id");
+    if let Some((page, limit)) = page {
+        builder.push(" LIMIT ");
+        builder.push_bind(limit);
+        builder.push(" OFFSET ");
+        builder.push_bind((page - 1) * limit);
+    }
+
+    let mut rows: Vec<ObservationRow> = builder.build_query_as().fetch_all(&state.pool).await?;
+    rehydrate_observations(state, &mut rows).await;
+    Ok(rows.into_iter().map(observation_to_dto).collect())
+}
+
+pub(crate) fn observation_to_dto(o: ObservationRow) -> ObservationsViewDto {
+    let prompt_tokens = o.prompt_tokens.unwrap_or(0);
+    let completion_tokens = o.completion_tokens.unwrap_or(0);
+    let total_tokens = o.total_tokens.unwrap_or(0);
+    let calculated_input_cost = o.calculated_input_cost.unwrap_or(0.0);
+    let calculated_output_cost = o.calculated_output_cost.unwrap_or(0.0);
+    let calculated_total_cost = o.calculated_total_cost.unwrap_or(0.0);
+
+    ObservationsViewDto {
+        version: None,
+        id: o.id,
+        trace_id: Some(o.trace_id),
+        r#type: o.r#type,
+        name: o.name,
+        start_time: o.start_time.unwrap_or(o.created_at),
+        end_time: o.end_time,
+        completion_start_time: o.completion_start_time,
+        model: o.model,
+        model_parameters: o.model_parameters.unwrap_or_else(|| serde_json::json!({})),
+        input: o.input.unwrap_or(JsonValue::Null),
+        metadata: o.metadata.unwrap_or(JsonValue::Null),
+        output: o.output.unwrap_or(JsonValue::Null),
+        usage: PublicUsage {
+            input: prompt_tokens,
+            output: completion_tokens,
+            total: total_tokens,
+            unit: o.unit.clone(),
+            input_cost: o.calculated_input_cost,
+            output_cost: o.calculated_output_cost,
+            total_cost: o.calculated_total_cost,
+        },
+        usage_details: serde_json::json!({
+            "input": prompt_tokens,
+            "output": completion_tokens,
+            "total": total_tokens
+        }),
+        cost_details: serde_json::json!({
+            "input": calculated_input_cost,
+            "output": calculated_output_cost,
+            "total": calculated_total_cost
+        }),
+        level: o.level.unwrap_or_else(|| "DEFAULT".to_string()),
+        status_message: o.status_message,
+        parent_observation_id: o.parent_observation_id,
+        prompt_id: o.prompt_id,
+        prompt_name: o.prompt_name,
+        prompt_version: o
+            .prompt_version
+            .as_deref()
+            .and_then(|s| s.parse::<i64>().ok()),
+        model_id: o.model_id,
+        input_price: o.input_price,
+        output_price: o.output_price,
+        total_price: o.total_price,
+        calculated_input_cost: o.calculated_input_cost,
+        calculated_output_cost: o.calculated_output_cost,
+        calculated_total_cost: o.calculated_total_cost,
+        latency: o.latency,
+        time_to_first_token: o.time_to_first_token,
+        prompt_tokens,
+        completion_tokens,
+        total_tokens,
+        environment: o.environment,
+        service_name: o.service_name,
+        service_version: o.service_version,
+        sdk_language: o.sdk_language,
+        host_name: o.host_name,
+        provider: o.provider,
+    }
 }
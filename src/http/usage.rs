@@ -0,0 +1,267 @@
+//! Usage summaries over a time range: `GET /api/public/metrics/users` totals
+//! traces, tokens and cost per `userId`, e.g. for per-customer chargeback,
+//! and `GET /api/public/metrics/models` requests, tokens, latency, errors and
+//! cost per model.
+//!
+//! The range applies to the trace timestamp and defaults to the last 30 days,
+//! like `GET /api/public/metrics/daily`.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::QueryBuilder;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        common::{PageMeta, PagedData},
+        error::ApiError,
+    },
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UsageQuery {
+    page: Option<i64>,
+    limit: Option<i64>,
+    from_timestamp: Option<DateTime<Utc>>,
+    to_timestamp: Option<DateTime<Utc>>,
+    trace_name: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    environment: Option<String>,
+    /// Lifts the maximum range (instance-wide keys only).
+    #[serde(default)]
+    ignore_range_limit: bool,
+}
+
+impl UsageQuery {
+    fn range(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let to_ts = self.to_timestamp.unwrap_or_else(Utc::now);
+        let from_ts = self
+            .from_timestamp
+            .unwrap_or_else(|| to_ts - chrono::Duration::days(30));
+        (from_ts, to_ts)
+    }
+
+    /// Appends the project, range and trace filters, for traces aliased `t`.
+    fn push_trace_filters(&self, builder: &mut QueryBuilder<'_, sqlx::Postgres>, project_id: &str) {
+        let (from_ts, to_ts) = self.range();
+        builder.push(" t.project_id = ");
+        builder.push_bind(project_id.to_string());
+        builder.push(" AND t.\"timestamp\" >= ");
+        builder.push_bind(from_ts);
+        builder.push(" AND t.\"timestamp\" <= ");
+        builder.push_bind(to_ts);
+        if let Some(trace_name) = &self.trace_name {
+            builder.push(" AND t.name = ");
+            builder.push_bind(trace_name.clone());
+        }
+        if !self.tags.is_empty() {
+            builder.push(" AND t.tags @> ");
+            builder.push_bind(self.tags.clone());
+        }
+        if let Some(environment) = &self.environment {
+            builder.push(" AND t.environment = ");
+            builder.push_bind(environment.clone());
+        }
+    }
+
+    /// Page, limit and offset.
+    fn page(&self) -> (i64, i64, i64) {
+        let page = self.page.unwrap_or(1).max(1);
+        let limit = self.limit.unwrap_or(50).clamp(1, 200);
+        (page, limit, (page - 1) * limit)
+    }
+
+    fn check(&self, state: &AppState, scope: &ProjectScope) -> Result<(), ApiError> {
+        let (from_ts, to_ts) = self.range();
+        if from_ts > to_ts {
+            return Err(ApiError::BadRequest(
+                "fromTimestamp must be <= toTimestamp".to_string(),
+            ));
+        }
+        state
+            .query_windows
+            .check_range(scope, from_ts, to_ts, self.ignore_range_limit)
+    }
+}
+
+fn page_meta(page: i64, limit: i64, total_items: i64) -> PageMeta {
+    PageMeta {
+        page,
+        limit,
+        totalItems: total_items,
+        totalPages: (total_items + limit - 1) / limit,
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct UserUsage {
+    user_id: String,
+    count_traces: i64,
+    count_observations: i64,
+    input_usage: i64,
+    output_usage: i64,
+    total_usage: i64,
+    total_cost: f64,
+    first_trace: DateTime<Utc>,
+    last_trace: DateTime<Utc>,
+}
+
+/// Traces without a `userId` are not counted. Users are ordered by cost,
+/// highest first.
+pub(crate) async fn get_user_usage(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<UsageQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.check(&state, &scope)?;
+    let (page, limit, offset) = q.page();
+
+    let mut count_builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new("SELECT COUNT(DISTINCT t.user_id) FROM traces t WHERE");
+    q.push_trace_filters(&mut count_builder, scope.project_id());
+    count_builder.push(" AND t.user_id IS NOT NULL");
+    let total_items: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&state.pool)
+        .await?;
+
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
+        "WITH filtered_traces AS (SELECT t.id, t.user_id, t.total_cost, t.\"timestamp\" FROM traces t WHERE",
+    );
+    q.push_trace_filters(&mut builder, scope.project_id());
+    builder.push(
+        r#" AND t.user_id IS NOT NULL)
+, users AS (
+  SELECT
+    ft.user_id,
+    COUNT(*)::BIGINT AS count_traces,
+    COALESCE(SUM(ft.total_cost), 0)::DOUBLE PRECISION AS total_cost,
+    MIN(ft."timestamp") AS first_trace,
+    MAX(ft."timestamp") AS last_trace
+  FROM filtered_traces ft
+  GROUP BY 1
+)
+, usage AS (
+  SELECT
+    ft.user_id,
+    COUNT(o.id)::BIGINT AS count_observations,
+    COALESCE(SUM(o.prompt_tokens), 0)::BIGINT AS input_usage,
+    COALESCE(SUM(o.completion_tokens), 0)::BIGINT AS output_usage,
+    COALESCE(SUM(o.total_tokens), 0)::BIGINT AS total_usage
+  FROM filtered_traces ft
+  JOIN observations o ON o.trace_id = ft.id
+  GROUP BY 1
+)
+SELECT
+  u.user_id,
+  u.count_traces,
+  COALESCE(us.count_observations, 0) AS count_observations,
+  COALESCE(us.input_usage, 0) AS input_usage,
+  COALESCE(us.output_usage, 0) AS output_usage,
+  COALESCE(us.total_usage, 0) AS total_usage,
+  u.total_cost,
+  u.first_trace,
+  u.last_trace
+FROM users u
+LEFT JOIN usage us ON us.user_id = u.user_id
+ORDER BY u.total_cost DESC, u.user_id
+LIMIT "#,
+    );
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+    let data: Vec<UserUsage> = builder.build_query_as().fetch_all(&state.pool).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data,
+            meta: page_meta(page, limit, total_items),
+        }),
+    ))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct ModelUsage {
+    model: String,
+    provider: Option<String>,
+    /// Observations with the model.
+    count_requests: i64,
+    count_traces: i64,
+    input_usage: i64,
+    output_usage: i64,
+    total_usage: i64,
+    /// Seconds from start to end, over observations with both.
+    avg_latency: Option<f64>,
+    count_errors: i64,
+    /// Share of requests at level `ERROR`.
+    error_rate: f64,
+    total_cost: f64,
+}
+
+/// Observations without a model are not counted. Models are ordered by cost,
+/// highest first.
+pub(crate) async fn get_model_usage(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<UsageQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.check(&state, &scope)?;
+    let (page, limit, offset) = q.page();
+
+    let mut count_builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
+        "SELECT COUNT(*) FROM (SELECT 1 FROM traces t JOIN observations o ON o.trace_id = t.id WHERE",
+    );
+    q.push_trace_filters(&mut count_builder, scope.project_id());
+    count_builder.push(" AND o.model IS NOT NULL GROUP BY o.model, o.provider) m");
+    let total_items: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&state.pool)
+        .await?;
+
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
+        r#"
+SELECT
+  o.model,
+  o.provider,
+  COUNT(*)::BIGINT AS count_requests,
+  COUNT(DISTINCT t.id)::BIGINT AS count_traces,
+  COALESCE(SUM(o.prompt_tokens), 0)::BIGINT AS input_usage,
+  COALESCE(SUM(o.completion_tokens), 0)::BIGINT AS output_usage,
+  COALESCE(SUM(o.total_tokens), 0)::BIGINT AS total_usage,
+  AVG(EXTRACT(EPOCH FROM o.end_time - o.start_time))::DOUBLE PRECISION AS avg_latency,
+  COUNT(*) FILTER (WHERE o.level = 'ERROR')::BIGINT AS count_errors,
+  (COUNT(*) FILTER (WHERE o.level = 'ERROR'))::DOUBLE PRECISION / COUNT(*) AS error_rate,
+  COALESCE(SUM(o.calculated_total_cost), 0)::DOUBLE PRECISION AS total_cost
+FROM traces t
+JOIN observations o ON o.trace_id = t.id
+WHERE"#,
+    );
+    q.push_trace_filters(&mut builder, scope.project_id());
+    builder.push(
+        " AND o.model IS NOT NULL GROUP BY 1, 2 ORDER BY total_cost DESC, count_requests DESC, 1, 2 LIMIT ",
+    );
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+    let data: Vec<ModelUsage> = builder.build_query_as().fetch_all(&state.pool).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data,
+            meta: page_meta(page, limit, total_items),
+        }),
+    ))
+}
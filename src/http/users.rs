@@ -0,0 +1,193 @@
+//! `GET /api/public/users` and `GET /api/public/users/:userId`: the users
+//! seen in traces (`userId`) with their trace count, first and last
+//! activity, tokens and cost, shaped like the Langfuse public API.
+//!
+//! Like the other list endpoints, the list covers all traces unless
+//! `fromTimestamp` or the default query window bounds it.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::QueryBuilder;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        common::{PageMeta, PagedData},
+        error::ApiError,
+    },
+    state::AppState,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UsersQuery {
+    page: Option<i64>,
+    limit: Option<i64>,
+    from_timestamp: Option<DateTime<Utc>>,
+    to_timestamp: Option<DateTime<Utc>>,
+    environment: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    user_id: String,
+    total_traces: i64,
+    total_observations: i64,
+    prompt_tokens: i64,
+    completion_tokens: i64,
+    total_tokens: i64,
+    total_cost: f64,
+    first_trace: DateTime<Utc>,
+    /// Timestamp of the user's latest trace.
+    last_trace: DateTime<Utc>,
+}
+
+/// Appends the project, range, environment and user filters, for traces
+/// aliased `t`.
+fn push_trace_filters(
+    builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    project_id: &str,
+    q: &UsersQuery,
+    user_id: Option<&str>,
+) {
+    builder.push(" t.project_id = ");
+    builder.push_bind(project_id.to_string());
+    builder.push(" AND t.user_id IS NOT NULL");
+    if let Some(from_ts) = q.from_timestamp {
+        builder.push(" AND t.\"timestamp\" >= ");
+        builder.push_bind(from_ts);
+    }
+    if let Some(to_ts) = q.to_timestamp {
+        builder.push(" AND t.\"timestamp\" <= ");
+        builder.push_bind(to_ts);
+    }
+    if let Some(environment) = &q.environment {
+        builder.push(" AND t.environment = ");
+        builder.push_bind(environment.clone());
+    }
+    if let Some(user_id) = user_id {
+        builder.push(" AND t.user_id = ");
+        builder.push_bind(user_id.to_string());
+    }
+}
+
+/// Aggregates per user, most recently active first; `user_id` narrows it to
+/// one user.
+async fn fetch_users(
+    state: &AppState,
+    project_id: &str,
+    q: &UsersQuery,
+    user_id: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<User>, sqlx::Error> {
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
+        "WITH filtered_traces AS (SELECT t.id, t.user_id, t.total_cost, t.\"timestamp\" FROM traces t WHERE",
+    );
+    push_trace_filters(&mut builder, project_id, q, user_id);
+    builder.push(
+        r#")
+, users AS (
+  SELECT
+    ft.user_id,
+    COUNT(*)::BIGINT AS total_traces,
+    COALESCE(SUM(ft.total_cost), 0)::DOUBLE PRECISION AS total_cost,
+    MIN(ft."timestamp") AS first_trace,
+    MAX(ft."timestamp") AS last_trace
+  FROM filtered_traces ft
+  GROUP BY 1
+  ORDER BY last_trace DESC, ft.user_id
+  LIMIT "#,
+    );
+    builder.push_bind(limit);
+    builder.push(" OFFSET ");
+    builder.push_bind(offset);
+    builder.push(
+        r#"
+)
+, usage AS (
+  SELECT
+    ft.user_id,
+    COUNT(o.id)::BIGINT AS total_observations,
+    COALESCE(SUM(o.prompt_tokens), 0)::BIGINT AS prompt_tokens,
+    COALESCE(SUM(o.completion_tokens), 0)::BIGINT AS completion_tokens,
+    COALESCE(SUM(o.total_tokens), 0)::BIGINT AS total_tokens
+  FROM filtered_traces ft
+  JOIN users u ON u.user_id = ft.user_id
+  JOIN observations o ON o.trace_id = ft.id
+  GROUP BY 1
+)
+SELECT
+  u.user_id,
+  u.total_traces,
+  COALESCE(us.total_observations, 0) AS total_observations,
+  COALESCE(us.prompt_tokens, 0) AS prompt_tokens,
+  COALESCE(us.completion_tokens, 0) AS completion_tokens,
+  COALESCE(us.total_tokens, 0) AS total_tokens,
+  u.total_cost,
+  u.first_trace,
+  u.last_trace
+FROM users u
+LEFT JOIN usage us ON us.user_id = u.user_id
+ORDER BY u.last_trace DESC, u.user_id"#,
+    );
+    builder.build_query_as().fetch_all(&state.pool).await
+}
+
+/// Users with at least one trace, paginated (`page`, `limit` 1–200).
+pub(crate) async fn get_users(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(mut q): Query<UsersQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    q.from_timestamp = state
+        .query_windows
+        .list_from(q.from_timestamp, q.to_timestamp);
+    let page = q.page.unwrap_or(1).max(1);
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * limit;
+
+    let mut count_builder: QueryBuilder<'_, sqlx::Postgres> =
+        QueryBuilder::new("SELECT COUNT(DISTINCT t.user_id) FROM traces t WHERE");
+    push_trace_filters(&mut count_builder, scope.project_id(), &q, None);
+    let total_items: i64 = count_builder
+        .build_query_scalar()
+        .fetch_one(&state.pool)
+        .await?;
+    let data = fetch_users(&state, scope.project_id(), &q, None, limit, offset).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PagedData {
+            data,
+            meta: PageMeta {
+                page,
+                limit,
+                totalItems: total_items,
+                totalPages: (total_items + limit - 1) / limit,
+            },
+        }),
+    ))
+}
+
+/// One user over all of its traces (or the requested range); `404` when it
+/// has none.
+pub(crate) async fn get_user(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(user_id): Path<String>,
+    Query(q): Query<UsersQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = fetch_users(&state, scope.project_id(), &q, Some(&user_id), 1, 0)
+        .await?
+        .pop()
+        .ok_or(ApiError::NotFound)?;
+    Ok((StatusCode::OK, Json(user)))
+}
@@ -0,0 +1,140 @@
+//! Public API versioning.
+//!
+//! The public endpoints are served under `/api/public/*` and the explicit
+//! alias `/api/v1/*`. A client may pin a version with the
+//! `X-Xtrace-Api-Version` header; the negotiated version is echoed back and
+//! stored in the request extensions so handlers can branch on it once a
+//! breaking DTO change ships as a new version. When a sunset is scheduled for
+//! a version, its responses carry `Deprecation` / `Sunset` headers.
+
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+
+use crate::{http::error::ApiError, state::AppState};
+
+pub(crate) const API_VERSION_HEADER: HeaderName = HeaderName::from_static("x-xtrace-api-version");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    pub const LATEST: ApiVersion = ApiVersion::V1;
+    const SUPPORTED: &'static [ApiVersion] = &[ApiVersion::V1];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "1",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix(['v', 'V']).unwrap_or(s);
+        Self::SUPPORTED.iter().copied().find(|v| v.as_str() == s)
+    }
+
+    /// Version implied by an explicit path prefix such as `/api/v1/...`.
+    fn from_path(path: &str) -> Option<Self> {
+        let rest = path.strip_prefix("/api/")?;
+        let segment = rest.split('/').next()?;
+        segment.strip_prefix('v').and_then(Self::parse)
+    }
+}
+
+/// Deprecation schedule for a single API version.
+#[derive(Debug, Clone, Default)]
+pub struct VersionLifecycle {
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ApiVersionPolicy {
+    pub v1: VersionLifecycle,
+}
+
+impl ApiVersionPolicy {
+    fn lifecycle(&self, version: ApiVersion) -> &VersionLifecycle {
+        match version {
+            ApiVersion::V1 => &self.v1,
+        }
+    }
+}
+
+/// Resolves the API version from the path prefix and/or the version header.
+pub(crate) async fn api_version(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|u| u.0.path().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let from_path = ApiVersion::from_path(&path);
+
+    let from_header = match req.headers().get(&API_VERSION_HEADER) {
+        None => None,
+        Some(raw) => match raw.to_str().ok().and_then(ApiVersion::parse) {
+            Some(v) => Some(v),
+            None => {
+                let supported = ApiVersion::SUPPORTED
+                    .iter()
+                    .map(|v| v.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return ApiError::BadRequest(format!(
+                    "unsupported {API_VERSION_HEADER}: {:?} (supported: {supported})",
+                    raw
+                ))
+                .into_response();
+            }
+        },
+    };
+
+    let version = match (from_path, from_header) {
+        (Some(p), Some(h)) if p != h => {
+            return ApiError::BadRequest(format!(
+                "{API_VERSION_HEADER} {} conflicts with path version {}",
+                h.as_str(),
+                p.as_str()
+            ))
+            .into_response();
+        }
+        (Some(v), _) | (None, Some(v)) => v,
+        (None, None) => ApiVersion::LATEST,
+    };
+
+    req.extensions_mut().insert(version);
+    let mut res = next.run(req).await;
+
+    let headers = res.headers_mut();
+    headers.insert(
+        API_VERSION_HEADER,
+        HeaderValue::from_static(version.as_str()),
+    );
+
+    let lifecycle = state.api_versions.lifecycle(version);
+    if let Some(at) = lifecycle.deprecated_at {
+        // RFC 9745 structured date.
+        if let Ok(v) = HeaderValue::from_str(&format!("@{}", at.timestamp())) {
+            headers.insert(HeaderName::from_static("deprecation"), v);
+        }
+    }
+    if let Some(at) = lifecycle.sunset_at {
+        // RFC 8594 HTTP-date.
+        let date = at.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(v) = HeaderValue::from_str(&date) {
+            headers.insert(HeaderName::from_static("sunset"), v);
+        }
+    }
+    res
+}
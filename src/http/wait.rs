@@ -0,0 +1,171 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::{
+    http::{auth::ProjectScope, error::ApiError},
+    state::AppState,
+};
+
+const MAX_TIMEOUT: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TraceWaitQuery {
+    /// Maximum time to hold the request, e.g. `30s`, `500ms`, `2m` (default `30s`, max `120s`).
+    #[serde(default)]
+    timeout: Option<String>,
+    /// A trace counts as complete once it has received no writes for this long (default `5s`).
+    #[serde(default, rename = "quietPeriod")]
+    quiet_period: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TraceActivityRow {
+    observation_count: i64,
+    completed_at: Option<DateTime<Utc>>,
+    last_activity_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceWaitResponse {
+    trace_id: Uuid,
+    completed: bool,
+    /// `completed` when the trace was explicitly completed, `quiet` when it went
+    /// idle, `timeout` when it was still receiving writes.
+    reason: &'static str,
+    observation_count: i64,
+    last_activity_at: DateTime<Utc>,
+    waited_ms: u64,
+}
+
+/// Parses `30s` / `500ms` / `2m` / bare seconds.
+fn parse_duration(raw: &str, name: &str) -> Result<Duration, ApiError> {
+    let s = raw.trim();
+    let parsed = if let Some(v) = s.strip_suffix("ms") {
+        v.trim().parse::<u64>().ok().map(Duration::from_millis)
+    } else if let Some(v) = s.strip_suffix('s') {
+        v.trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(|v| Duration::try_from_secs_f64(v).ok())
+    } else if let Some(v) = s.strip_suffix('m') {
+        v.trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|m| m.checked_mul(60))
+            .map(Duration::from_secs)
+    } else {
+        s.parse::<f64>()
+            .ok()
+            .and_then(|v| Duration::try_from_secs_f64(v).ok())
+    };
+    parsed.ok_or_else(|| ApiError::BadRequest(format!("invalid {name}: {raw}")))
+}
+
+async fn fetch_activity(
+    state: &AppState,
+    project_id: &str,
+    trace_id: Uuid,
+) -> Result<Option<TraceActivityRow>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT
+  (SELECT COUNT(*) FROM observations o WHERE o.trace_id = t.id)::BIGINT AS observation_count,
+  t.completed_at,
+  GREATEST(
+    t.updated_at,
+    (SELECT MAX(o.updated_at) FROM observations o WHERE o.trace_id = t.id)
+  ) AS last_activity_at
+FROM traces t
+WHERE t.id = $1 AND t.project_id = $2
+        "#,
+    )
+    .bind(trace_id)
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await
+}
+
+/// Long-polls until the trace is explicitly completed, goes quiet, or `timeout`
+/// elapses. Returns `404` if the trace never shows up within the timeout.
+pub(crate) async fn wait_for_trace(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(trace_id): Path<Uuid>,
+    Query(q): Query<TraceWaitQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let timeout = match q.timeout.as_deref() {
+        Some(raw) => parse_duration(raw, "timeout")?,
+        None => Duration::from_secs(30),
+    }
+    .min(MAX_TIMEOUT);
+    let quiet_period = match q.quiet_period.as_deref() {
+        Some(raw) => parse_duration(raw, "quietPeriod")?,
+        None => Duration::from_secs(5),
+    };
+    let quiet = chrono::Duration::from_std(quiet_period)
+        .map_err(|_| ApiError::BadRequest("quietPeriod is too large".to_string()))?;
+
+    let started = Instant::now();
+    let deadline = started + timeout;
+    loop {
+        let activity = fetch_activity(&state, scope.project_id(), trace_id).await?;
+        let now = Instant::now();
+
+        if let Some(a) = &activity {
+            let is_quiet = Utc::now() - a.last_activity_at >= quiet;
+            let reason = if a.completed_at.is_some() {
+                Some("completed")
+            } else if is_quiet {
+                Some("quiet")
+            } else if now >= deadline {
+                Some("timeout")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Ok((
+                    StatusCode::OK,
+                    Json(TraceWaitResponse {
+                        trace_id,
+                        completed: reason != "timeout",
+                        reason,
+                        observation_count: a.observation_count,
+                        last_activity_at: a.last_activity_at,
+                        waited_ms: started.elapsed().as_millis() as u64,
+                    }),
+                ));
+            }
+        } else if now >= deadline {
+            return Err(ApiError::NotFound);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_and_rejects_overflow() {
+        let parse = |raw: &str| parse_duration(raw, "timeout").ok();
+        assert_eq!(parse("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse("30"), Some(Duration::from_secs(30)));
+        let overflow = format!("{}m", u64::MAX / 60 + 1);
+        assert_eq!(parse(&overflow), None);
+        assert_eq!(parse("soon"), None);
+    }
+}
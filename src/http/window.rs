@@ -0,0 +1,71 @@
+//! Time window guardrails of the query endpoints.
+//!
+//! List endpoints without a lower time bound get a default window ending at
+//! their upper bound (or now), so that an unfiltered request does not scan
+//! the whole table. Aggregate endpoints reject ranges longer than a maximum
+//! unless an instance-wide key passes `ignoreRangeLimit=true`. Both are off
+//! unless configured.
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::http::{auth::ProjectScope, error::ApiError};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct QueryWindows {
+    /// Window of list queries without `fromTimestamp`.
+    pub(crate) default_window: Option<Duration>,
+    /// Longest range of aggregate queries.
+    pub(crate) max_range: Option<Duration>,
+}
+
+impl QueryWindows {
+    /// Builds the windows from seconds; 0 disables either.
+    pub(crate) fn from_secs(default_window_secs: u64, max_range_secs: u64) -> Self {
+        let duration = |secs: u64| {
+            (secs > 0).then(|| Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX / 1000)))
+        };
+        Self {
+            default_window: duration(default_window_secs),
+            max_range: duration(max_range_secs),
+        }
+    }
+
+    /// Lower bound of a list query: `from`, else the default window before
+    /// `to` (or now).
+    pub(crate) fn list_from(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        from.or_else(|| {
+            self.default_window
+                .map(|window| to.unwrap_or_else(Utc::now) - window)
+        })
+    }
+
+    /// Rejects an aggregate range longer than the maximum. `ignore_limit`
+    /// lifts it for instance-wide keys.
+    pub(crate) fn check_range(
+        &self,
+        scope: &ProjectScope,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        ignore_limit: bool,
+    ) -> Result<(), ApiError> {
+        if ignore_limit {
+            if scope.is_bound() {
+                return Err(ApiError::Forbidden(
+                    "ignoreRangeLimit requires an instance-wide key".to_string(),
+                ));
+            }
+            return Ok(());
+        }
+        match self.max_range {
+            Some(max) if to - from > max => Err(ApiError::BadRequest(format!(
+                "time range must not exceed {} seconds (instance-wide keys may pass ignoreRangeLimit=true)",
+                max.num_seconds()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
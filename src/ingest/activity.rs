@@ -0,0 +1,119 @@
+//! The `trace_activity` table: trace counts per project, 15-minute bucket,
+//! environment and name, for activity heatmaps.
+//!
+//! Writers read the keys of the traces they change before and after the
+//! change and collect the difference in an [`ActivityDelta`], which is
+//! applied once just before their transaction commits, so the rows of the
+//! current bucket, which every ingest worker updates, stay locked briefly.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Bucket of a trace row, as stored in `trace_activity.bucket`.
+pub(crate) const BUCKET: &str = r#"date_bin('15 minutes', "timestamp", TIMESTAMPTZ 'epoch')"#;
+
+/// Length of a bucket.
+pub(crate) const BUCKET_SECS: i64 = 15 * 60;
+
+/// `(project_id, bucket, environment, name)`; unnamed traces have name `''`.
+pub(crate) type ActivityKey = (String, DateTime<Utc>, String, String);
+
+/// Pending changes to `trace_activity` counts.
+#[derive(Debug, Default)]
+pub(crate) struct ActivityDelta(BTreeMap<ActivityKey, i64>);
+
+impl ActivityDelta {
+    /// Counts the traces of `after` and uncounts those of `before`.
+    pub(crate) fn record(&mut self, before: Vec<ActivityKey>, after: Vec<ActivityKey>) {
+        for key in before {
+            *self.0.entry(key).or_default() -= 1;
+        }
+        for key in after {
+            *self.0.entry(key).or_default() += 1;
+        }
+    }
+
+    /// Uncounts `count` traces of `key`.
+    pub(crate) fn remove(&mut self, key: ActivityKey, count: i64) {
+        *self.0.entry(key).or_default() -= count;
+    }
+}
+
+/// The keys of `trace_ids` as they are now, one per existing trace.
+pub(crate) async fn keys(
+    conn: &mut PgConnection,
+    trace_ids: &[Uuid],
+) -> Result<Vec<ActivityKey>, sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    sqlx::query_as(&format!(
+        "SELECT project_id, {BUCKET}, environment, COALESCE(name, '') FROM traces WHERE id = ANY($1)"
+    ))
+    .bind(trace_ids)
+    .fetch_all(conn)
+    .await
+}
+
+/// Applies `delta`, deleting rows that reach zero. Rows are locked in key
+/// order, so concurrent writers do not deadlock.
+pub(crate) async fn apply(
+    conn: &mut PgConnection,
+    delta: ActivityDelta,
+) -> Result<(), sqlx::Error> {
+    let mut projects = Vec::new();
+    let mut buckets = Vec::new();
+    let mut environments = Vec::new();
+    let mut names = Vec::new();
+    let mut counts = Vec::new();
+    for ((project_id, bucket, environment, name), count) in delta.0 {
+        if count != 0 {
+            projects.push(project_id);
+            buckets.push(bucket);
+            environments.push(environment);
+            names.push(name);
+            counts.push(count);
+        }
+    }
+    if counts.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+INSERT INTO trace_activity (project_id, bucket, environment, name, trace_count)
+SELECT d.project_id, d.bucket, d.environment, d.name, d.trace_count
+FROM UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::TEXT[], $4::TEXT[], $5::BIGINT[])
+  WITH ORDINALITY AS d(project_id, bucket, environment, name, trace_count, n)
+ORDER BY d.n
+ON CONFLICT (project_id, bucket, environment, name) DO UPDATE
+SET trace_count = trace_activity.trace_count + EXCLUDED.trace_count
+        "#,
+    )
+    .bind(&projects)
+    .bind(&buckets)
+    .bind(&environments)
+    .bind(&names)
+    .bind(&counts)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        r#"
+DELETE FROM trace_activity a
+USING UNNEST($1::TEXT[], $2::TIMESTAMPTZ[], $3::TEXT[], $4::TEXT[])
+  AS d(project_id, bucket, environment, name)
+WHERE a.project_id = d.project_id AND a.bucket = d.bucket
+  AND a.environment = d.environment AND a.name = d.name
+  AND a.trace_count <= 0
+        "#,
+    )
+    .bind(projects)
+    .bind(buckets)
+    .bind(environments)
+    .bind(names)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -1,26 +1,258 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
-use serde_json::Value as JsonValue;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
 use sqlx::PgPool;
-use std::sync::Arc;
-use tokio::{sync::mpsc, time::Duration};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 use uuid::Uuid;
 
 use crate::{
-    http::{common::ApiResponse, error::ApiError},
-    state::AppState,
+    http::{
+        auth::ProjectScope,
+        common::ApiResponse,
+        error::ApiError,
+        ingest_limit::IngestCost,
+        projects,
+        quotas::{self, UsageDelta},
+        telemetry::Telemetry,
+    },
+    ingest::{
+        activity::{self, ActivityDelta},
+        completion::{self, LateWriteMode},
+        conflicts::{self, ConflictPolicies, ConflictPolicy, ObservationRows},
+        dead_letter, dedup,
+        encoding::decode_body,
+        environments,
+        models::ModelAliases,
+        ownership,
+        pricing::{PriceRegistry, PriceTable},
+        providers,
+        raw::RawSource,
+        resources,
+        sampling::SamplingPolicy,
+        secrets::SecretScanner,
+        sessions::{self, SessionKey},
+        wal::Wal,
+    },
+    offload::Offloader,
+    state::{AppState, WriteGate},
+    webhooks::{
+        outbox::{self, OutboxConfig},
+        rules,
+    },
 };
 
-#[derive(Debug, Deserialize)]
+/// Settings shared by every write performed by the ingest worker.
+#[derive(Clone)]
+pub(crate) struct IngestSettings {
+    pub default_project_id: Arc<str>,
+    pub outbox: OutboxConfig,
+    pub secret_scanner: Option<Arc<SecretScanner>>,
+    pub offloader: Option<Arc<Offloader>>,
+    pub model_aliases: Option<Arc<ModelAliases>>,
+    pub prices: Arc<PriceRegistry>,
+    pub late_write_mode: LateWriteMode,
+    pub conflict_policies: Arc<ConflictPolicies>,
+    pub sampling: Arc<SamplingPolicy>,
+    /// Write-ahead log whose payloads are acknowledged once written.
+    pub wal: Option<Arc<Wal>>,
+    /// How long event ids are remembered; `None` turns deduplication off.
+    pub dedup_ttl: Option<Duration>,
+    pub telemetry: Arc<Telemetry>,
+}
+
+/// Attempts per payload once its batch failed, before it is dead-lettered.
+const WRITE_ATTEMPTS: u32 = 3;
+/// Delay before the second attempt; doubled for every further one.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// How an ingest worker groups queued payloads into one transaction.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IngestBatching {
+    /// Payloads written per transaction at most.
+    pub max_batches: usize,
+    /// How long to wait for more payloads after the first one.
+    pub window: Duration,
+}
+
+/// Queues of the ingest workers. Payloads are routed by trace id, so writes
+/// to one trace are applied in order by a single worker.
+#[derive(Clone)]
+pub struct IngestQueue {
+    senders: Arc<[mpsc::Sender<BatchIngestRequest>]>,
+}
+
+impl IngestQueue {
+    /// Queues for `workers` workers holding `capacity` payloads in total.
+    pub(crate) fn new(
+        workers: usize,
+        capacity: usize,
+    ) -> (Self, Vec<mpsc::Receiver<BatchIngestRequest>>) {
+        let workers = workers.max(1);
+        let per_worker = capacity.div_ceil(workers).max(1);
+        let (senders, receivers) = (0..workers).map(|_| mpsc::channel(per_worker)).unzip();
+        let senders: Vec<_> = senders;
+        (
+            Self {
+                senders: senders.into(),
+            },
+            receivers,
+        )
+    }
+
+    /// Index of the worker that writes `payload`.
+    pub(crate) fn shard(&self, payload: &BatchIngestRequest) -> usize {
+        let trace_id = payload
+            .trace
+            .as_ref()
+            .map(|t| t.id)
+            .or_else(|| payload.observations.first().map(|o| o.traceId));
+        trace_id.map_or(0, |id| (id.as_u128() % self.senders.len() as u128) as usize)
+    }
+
+    /// The queue of worker `shard`.
+    pub(crate) fn shard_sender(&self, shard: usize) -> &mpsc::Sender<BatchIngestRequest> {
+        &self.senders[shard]
+    }
+
+    /// Payloads one worker's queue holds.
+    pub(crate) fn shard_capacity(&self) -> usize {
+        self.senders[0].max_capacity()
+    }
+
+    /// The queue of the worker that writes `payload`.
+    pub(crate) fn sender(&self, payload: &BatchIngestRequest) -> &mpsc::Sender<BatchIngestRequest> {
+        self.shard_sender(self.shard(payload))
+    }
+
+    /// Queues `payload`, waiting while its worker's queue is full.
+    pub(crate) async fn send(&self, payload: BatchIngestRequest) -> Result<(), ApiError> {
+        self.sender(&payload)
+            .send(payload)
+            .await
+            .map_err(|_| ApiError::ServiceUnavailable)
+    }
+
+    /// Payloads queued per worker.
+    pub(crate) fn depths(&self) -> Vec<usize> {
+        self.senders
+            .iter()
+            .map(|s| s.max_capacity() - s.capacity())
+            .collect()
+    }
+
+    /// Payloads the queues hold in total.
+    pub(crate) fn capacity(&self) -> usize {
+        self.senders.iter().map(|s| s.max_capacity()).sum()
+    }
+}
+
+struct SecretFinding {
+    trace_id: Uuid,
+    observation_id: Option<Uuid>,
+    field: &'static str,
+    kind: &'static str,
+    count: usize,
+}
+
+fn scan_field(
+    scanner: &SecretScanner,
+    value: &mut Option<JsonValue>,
+    trace_id: Uuid,
+    observation_id: Option<Uuid>,
+    field: &'static str,
+    out: &mut Vec<SecretFinding>,
+) {
+    let Some(v) = value.as_mut() else {
+        return;
+    };
+    for (kind, count) in scanner.scan(v) {
+        out.push(SecretFinding {
+            trace_id,
+            observation_id,
+            field,
+            kind,
+            count,
+        });
+    }
+}
+
+/// Header naming the idempotency key of a `/v1/l/batch` payload without
+/// `eventId`.
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Metadata key holding ingested fields the server does not recognize.
+pub(crate) const RAW_EXTRA_METADATA_KEY: &str = "raw_extra";
+/// Unknown fields above this serialized size are recorded by name only.
+const MAX_RAW_EXTRA_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub(crate) struct BatchIngestRequest {
     #[serde(default)]
     pub trace: Option<TraceIngest>,
     #[serde(default)]
     pub observations: Vec<ObservationIngest>,
+    /// Idempotency keys of the payload (see [`dedup`]); one id or a list.
+    #[serde(
+        default,
+        rename = "eventId",
+        deserialize_with = "dedup::one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub event_ids: Vec<String>,
+    #[serde(flatten)]
+    pub extra: JsonMap<String, JsonValue>,
+    /// Traces to finalize once this payload is written (completion endpoint).
+    #[serde(skip)]
+    pub complete: Vec<Uuid>,
+    /// Sequence number in the write-ahead log, when enabled.
+    #[serde(skip)]
+    pub wal_seq: Option<u64>,
+    /// When the payload was queued, for the age of the oldest pending one.
+    #[serde(skip)]
+    pub accepted_at: Option<std::time::Instant>,
 }
 
-#[derive(Debug, Deserialize)]
+impl BatchIngestRequest {
+    /// The project the payload is written to, when it names one.
+    pub(crate) fn project_id(&self) -> Option<&str> {
+        self.trace
+            .as_ref()
+            .and_then(|t| t.projectId.as_deref())
+            .or_else(|| {
+                self.observations
+                    .iter()
+                    .find_map(|o| o.projectId.as_deref())
+            })
+    }
+
+    /// Rejects payloads that cannot be written as sent.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self
+            .observations
+            .iter()
+            .position(|o| o.outputDelta.is_some() && o.outputSeq.is_none())
+        {
+            Some(i) => Err(format!("observations[{i}].outputDelta requires outputSeq")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[allow(non_snake_case)]
 pub(crate) struct TraceIngest {
     pub id: Uuid,
@@ -59,11 +291,37 @@ pub(crate) struct TraceIngest {
     #[serde(default)]
     pub totalCost: Option<f64>,
 
+    #[serde(default)]
+    pub serviceName: Option<String>,
+    #[serde(default)]
+    pub serviceVersion: Option<String>,
+    #[serde(default)]
+    pub sdkLanguage: Option<String>,
+    #[serde(default)]
+    pub hostName: Option<String>,
+
     #[serde(default)]
     pub projectId: Option<String>,
+
+    /// Finalize the trace after this payload is written.
+    #[serde(default)]
+    pub complete: Option<bool>,
+
+    /// When the SDK recorded this state; orders partial updates under the
+    /// `latest` conflict policy. Defaults to the time of the write.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eventTime: Option<DateTime<Utc>>,
+
+    /// Apply as a partial update: fields left out keep their stored values
+    /// whatever the conflict policy, and the timestamp only moves earlier.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<bool>,
+
+    #[serde(flatten)]
+    pub extra: JsonMap<String, JsonValue>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[allow(non_snake_case)]
 pub(crate) struct ObservationIngest {
     pub id: Uuid,
@@ -145,42 +403,225 @@ pub(crate) struct ObservationIngest {
     #[serde(default)]
     pub environment: Option<String>,
 
+    #[serde(default)]
+    pub serviceName: Option<String>,
+    #[serde(default)]
+    pub serviceVersion: Option<String>,
+    #[serde(default)]
+    pub sdkLanguage: Option<String>,
+    #[serde(default)]
+    pub hostName: Option<String>,
+
+    /// Inferred at ingest when omitted.
+    #[serde(default)]
+    pub provider: Option<String>,
+
+    /// Object keys of an offloaded `input` / `output`; set at write time.
+    #[serde(skip)]
+    pub input_offload: Option<String>,
+    #[serde(skip)]
+    pub output_offload: Option<String>,
+
     #[serde(default)]
     pub projectId: Option<String>,
+
+    /// See [`TraceIngest::eventTime`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eventTime: Option<DateTime<Utc>>,
+
+    /// Apply as a partial update: fields left out keep their stored values
+    /// whatever the conflict policy, and the write is not a conflict.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<bool>,
+    /// Text appended to the stored `output` (e.g. a streamed chunk); implies
+    /// `update`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputDelta: Option<String>,
+    /// Position of `outputDelta` in the stream, required with it. A delta is
+    /// appended only when it is past the last one applied, so a retried or
+    /// replayed chunk is not appended twice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputSeq: Option<i64>,
+
+    #[serde(flatten)]
+    pub extra: JsonMap<String, JsonValue>,
+}
+
+impl ObservationIngest {
+    /// Whether the observation is a partial update of a stored one.
+    pub(crate) fn is_update(&self) -> bool {
+        self.update == Some(true) || self.outputDelta.is_some()
+    }
+}
+
+/// Moves unrecognized fields into `metadata.raw_extra` so they are not lost, and
+/// returns one warning per ignored field for the ingest response.
+pub(crate) fn absorb_unknown_fields(payload: &mut BatchIngestRequest) -> Vec<String> {
+    let mut warnings: Vec<String> = std::mem::take(&mut payload.extra)
+        .keys()
+        .map(|k| format!("ignored unknown field `{k}`"))
+        .collect();
+    if let Some(trace) = payload.trace.as_mut() {
+        let extra = std::mem::take(&mut trace.extra);
+        stash_extra(&mut trace.metadata, extra, "trace", &mut warnings);
+    }
+    for (i, obs) in payload.observations.iter_mut().enumerate() {
+        let extra = std::mem::take(&mut obs.extra);
+        stash_extra(
+            &mut obs.metadata,
+            extra,
+            &format!("observations[{i}]"),
+            &mut warnings,
+        );
+    }
+    warnings
+}
+
+fn stash_extra(
+    metadata: &mut Option<JsonValue>,
+    extra: JsonMap<String, JsonValue>,
+    path: &str,
+    warnings: &mut Vec<String>,
+) {
+    if extra.is_empty() {
+        return;
+    }
+    let meta = metadata.get_or_insert_with(|| JsonValue::Object(JsonMap::new()));
+    let Some(map) = meta.as_object_mut() else {
+        warnings.extend(
+            extra
+                .keys()
+                .map(|k| format!("ignored unknown field `{path}.{k}` (metadata is not an object)")),
+        );
+        return;
+    };
+    warnings.extend(extra.keys().map(|k| {
+        format!("unknown field `{path}.{k}` stored in metadata.{RAW_EXTRA_METADATA_KEY}")
+    }));
+    let size = serde_json::to_vec(&extra).map(|v| v.len()).unwrap_or(0);
+    let stored = if size <= MAX_RAW_EXTRA_BYTES {
+        JsonValue::Object(extra)
+    } else {
+        serde_json::json!({
+            "truncated": true,
+            "fields": extra.keys().collect::<Vec<_>>(),
+        })
+    };
+    map.insert(RAW_EXTRA_METADATA_KEY.to_string(), stored);
 }
 
 pub(crate) async fn post_batch(
     State(state): State<AppState>,
-    Json(payload): Json<BatchIngestRequest>,
+    Extension(scope): Extension<ProjectScope>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> Result<impl IntoResponse, ApiError> {
-    match state.ingest_tx.try_send(payload) {
-        Ok(()) => Ok((
-            StatusCode::OK,
-            Json(ApiResponse::<serde_json::Value> {
-                message: "Request Successful.".to_string(),
-                code: None,
-                data: None,
-            }),
-        )),
-        Err(mpsc::error::TrySendError::Full(_)) => Err(ApiError::TooManyRequests),
-        Err(mpsc::error::TrySendError::Closed(_)) => Err(ApiError::ServiceUnavailable),
+    let body = decode_body(&headers, body)?;
+    let mut payload: BatchIngestRequest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("invalid json: {e}")))?;
+    payload.validate().map_err(ApiError::BadRequest)?;
+    state.charge_ingest(
+        &headers,
+        IngestCost {
+            bytes: body.len(),
+            observations: payload.observations.len(),
+        },
+    )?;
+    if let Some(trace) = payload.trace.as_mut() {
+        scope.claim(&mut trace.projectId)?;
+    }
+    for obs in &mut payload.observations {
+        scope.claim(&mut obs.projectId)?;
+    }
+    state.check_quota(std::slice::from_ref(&payload))?;
+    ownership::check(
+        &state.pool,
+        std::slice::from_ref(&payload),
+        scope.project_id(),
+    )
+    .await?;
+    if payload.event_ids.is_empty() {
+        let key = headers.get(IDEMPOTENCY_KEY).and_then(|v| v.to_str().ok());
+        payload.event_ids.extend(key.map(String::from));
     }
+    if let Some(ttl) = state.dedup_ttl.filter(|_| !payload.event_ids.is_empty()) {
+        // A retry of a written payload is acknowledged without queueing it.
+        let project_id = payload.project_id().unwrap_or(scope.project_id());
+        let keys: Vec<(String, String)> = payload
+            .event_ids
+            .iter()
+            .map(|id| (project_id.to_string(), id.clone()))
+            .collect();
+        let seen = dedup::seen(&state.pool, &keys, ttl).await?;
+        if keys.iter().all(|k| seen.contains(k)) {
+            state.telemetry.record_deduplicated(payload.event_ids.len());
+            return Ok((
+                StatusCode::OK,
+                Json(ApiResponse::<serde_json::Value> {
+                    message: "Request Successful.".to_string(),
+                    code: None,
+                    data: Some(serde_json::json!({ "duplicate": true })),
+                }),
+            ));
+        }
+    }
+    let warnings = absorb_unknown_fields(&mut payload);
+    let observations: Vec<(Uuid, &str)> = payload
+        .observations
+        .iter()
+        .filter(|o| !o.is_update())
+        .map(|o| (o.id, o.projectId.as_deref().unwrap_or_default()))
+        .collect();
+    let rejected = state
+        .conflict_policies
+        .rejected(&state.pool, &observations)
+        .await?;
+    if !rejected.is_empty() {
+        let ids: Vec<String> = rejected.iter().map(Uuid::to_string).collect();
+        return Err(ApiError::Conflict(format!(
+            "observations already exist: {}",
+            ids.join(", ")
+        )));
+    }
+    state
+        .enqueue_ingest_from(
+            RawSource {
+                endpoint: "/v1/l/batch",
+                project_id: scope.project_id(),
+                headers: &headers,
+                body: &body,
+            },
+            vec![payload],
+        )
+        .await?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse::<serde_json::Value> {
+            message: "Request Successful.".to_string(),
+            code: None,
+            data: (!warnings.is_empty()).then(|| serde_json::json!({ "warnings": warnings })),
+        }),
+    ))
 }
 
 pub(crate) async fn ingest_worker(
     pool: PgPool,
-    default_project_id: Arc<str>,
+    settings: IngestSettings,
+    batching: IngestBatching,
+    gate: Arc<WriteGate>,
     mut rx: mpsc::Receiver<BatchIngestRequest>,
 ) {
-    const MAX_BATCHES: usize = 200;
-    let window = Duration::from_millis(50);
+    let IngestBatching {
+        max_batches,
+        window,
+    } = batching;
 
     while let Some(first) = rx.recv().await {
-        let mut batches = Vec::with_capacity(MAX_BATCHES);
+        let mut batches = Vec::with_capacity(max_batches);
         batches.push(first);
 
         let start = tokio::time::Instant::now();
-        while batches.len() < MAX_BATCHES {
+        while batches.len() < max_batches {
             let elapsed = start.elapsed();
             let remaining = match window.checked_sub(elapsed) {
                 Some(r) if !r.is_zero() => r,
@@ -194,201 +635,660 @@ pub(crate) async fn ingest_worker(
             }
         }
 
-        if let Err(err) = write_batches(&pool, default_project_id.as_ref(), batches).await {
-            tracing::error!(error = ?err, "failed to write batch");
+        let n = batches.len() as u64;
+        if let Err(err) = write_recorded(&pool, &settings, &batches).await {
+            tracing::warn!(error = %err, payloads = n, "failed to write batch, retrying payloads one by one");
+            write_each(&pool, &settings, &batches).await;
         }
+        if let Some(wal) = &settings.wal {
+            wal.ack(batches.iter().filter_map(|p| p.wal_seq));
+        }
+        gate.release(n);
+        settings
+            .telemetry
+            .record_settled(batches.iter().filter_map(|p| p.accepted_at));
     }
 }
 
+/// Writes payloads in transactions of their own after their batch failed,
+/// retrying each with backoff. Payloads that keep failing are dead-lettered.
+async fn write_each(pool: &PgPool, settings: &IngestSettings, payloads: &[BatchIngestRequest]) {
+    for payload in payloads {
+        let mut backoff = RETRY_BACKOFF;
+        let mut attempts = 1;
+        let err = loop {
+            match write_recorded(pool, settings, std::slice::from_ref(payload)).await {
+                Ok(()) => break None,
+                Err(err) if attempts >= WRITE_ATTEMPTS => break Some(err),
+                Err(_) => {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempts += 1;
+                }
+            }
+        };
+        let Some(err) = err else { continue };
+        let project_id = payload.project_id().unwrap_or(&settings.default_project_id);
+        settings.telemetry.record_dead_letter();
+        match dead_letter::store(pool, project_id, payload, &err.to_string(), attempts).await {
+            Ok(id) => tracing::error!(
+                error = %err,
+                failed_event_id = id,
+                "failed to write ingest payload, kept in failed_events"
+            ),
+            Err(store_err) => tracing::error!(
+                error = %err,
+                store_error = %store_err,
+                payload = %serde_json::to_string(payload).unwrap_or_default(),
+                "failed to write ingest payload and to keep it in failed_events"
+            ),
+        }
+    }
+}
+
+/// [`write_batches`], recorded in the server's telemetry.
+async fn write_recorded(
+    pool: &PgPool,
+    settings: &IngestSettings,
+    payloads: &[BatchIngestRequest],
+) -> Result<(), sqlx::Error> {
+    let start = Instant::now();
+    let result = write_batches(pool, settings, payloads).await;
+    let written = result.is_ok().then(|| {
+        payloads.iter().fold((0, 0), |(traces, observations), p| {
+            (
+                traces + usize::from(p.trace.is_some()),
+                observations + p.observations.len(),
+            )
+        })
+    });
+    settings
+        .telemetry
+        .record_ingest_write(start.elapsed(), written);
+    result
+}
+
 async fn write_batches(
     pool: &PgPool,
-    default_project_id: &str,
-    payloads: Vec<BatchIngestRequest>,
+    settings: &IngestSettings,
+    payloads: &[BatchIngestRequest],
 ) -> Result<(), sqlx::Error> {
+    let prices = settings.prices.snapshot(pool).await?;
+    let mut prepared = Vec::with_capacity(payloads.len());
+    {
+        let mut conn = pool.acquire().await?;
+        for payload in payloads {
+            prepared.push(prepare(&mut conn, settings, payload).await?);
+        }
+    }
     let mut tx = pool.begin().await?;
-    for payload in payloads {
-        write_one(&mut tx, default_project_id, payload).await?;
+    let mut touched_sessions = BTreeSet::new();
+    let mut touched_projects = BTreeSet::new();
+    let mut deltas = Deltas::default();
+    for payload in prepared {
+        write_one(
+            &mut tx,
+            settings,
+            &prices,
+            payload,
+            &mut touched_sessions,
+            &mut touched_projects,
+            &mut deltas,
+        )
+        .await?;
     }
+    sessions::refresh(&mut tx, &touched_sessions).await?;
+    let trace_ids: BTreeSet<Uuid> = payloads
+        .iter()
+        .flat_map(|p| {
+            p.trace
+                .iter()
+                .map(|t| t.id)
+                .chain(p.observations.iter().map(|o| o.traceId))
+        })
+        .collect();
+    environments::record(&mut tx, &Vec::from_iter(trace_ids)).await?;
+    let touched_projects: Vec<String> = touched_projects.into_iter().collect();
+    projects::register(&mut *tx, &touched_projects).await?;
+    // Last, so the rows every worker updates stay locked briefly.
+    activity::apply(&mut tx, deltas.activity).await?;
+    quotas::record_usage(&mut tx, deltas.usage).await?;
     tx.commit().await?;
     Ok(())
 }
 
+/// Changes to counters that an ingest transaction collects over its payloads
+/// and applies before it commits.
+#[derive(Default)]
+struct Deltas {
+    activity: ActivityDelta,
+    usage: UsageDelta,
+}
+
+/// Takes the write locks of `trace_ids` until the end of the transaction.
+/// Every writer of a trace row holds its lock, so writes to one trace take
+/// turns across instances.
+pub(crate) async fn lock_traces(
+    conn: &mut sqlx::PgConnection,
+    trace_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "SELECT pg_advisory_xact_lock(hashtextextended(id::TEXT, 0)) FROM UNNEST($1::uuid[]) AS t(id)",
+    )
+    .bind(trace_ids)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Moves the observations of `trace_ids` to their trace's partition.
+/// Observations are partitioned by `trace_timestamp`, a copy of the trace's
+/// `timestamp`; a write that changes the timestamp, or moves an observation
+/// to another trace, leaves them behind until this runs.
+async fn align_observations(
+    conn: &mut sqlx::PgConnection,
+    trace_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+UPDATE observations o SET trace_timestamp = t.timestamp
+FROM traces t
+WHERE t.id = ANY($1) AND o.trace_id = t.id AND o.trace_timestamp <> t.timestamp
+        "#,
+    )
+    .bind(trace_ids)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// A payload ready to be written: with secrets masked and large bodies
+/// offloaded.
+struct Prepared {
+    payload: BatchIngestRequest,
+    findings: Vec<SecretFinding>,
+}
+
+/// Prepares `payload` for [`write_one`]. Runs before the write transaction,
+/// so uploads to object storage hold neither a connection in a transaction
+/// nor trace locks. Objects of writes that end up not stored (a failed
+/// transaction, a late write in strict mode) stay in the bucket.
+async fn prepare(
+    conn: &mut sqlx::PgConnection,
+    settings: &IngestSettings,
+    payload: &BatchIngestRequest,
+) -> Result<Prepared, sqlx::Error> {
+    let mut payload = payload.clone();
+    let default_project_id = settings.default_project_id.as_ref();
+
+    let mut findings = Vec::new();
+    if let Some(scanner) = settings.secret_scanner.as_deref() {
+        if let Some(trace) = payload.trace.as_mut() {
+            scan_field(
+                scanner,
+                &mut trace.input,
+                trace.id,
+                None,
+                "input",
+                &mut findings,
+            );
+            scan_field(
+                scanner,
+                &mut trace.output,
+                trace.id,
+                None,
+                "output",
+                &mut findings,
+            );
+        }
+        for obs in &mut payload.observations {
+            let (tid, oid) = (obs.traceId, Some(obs.id));
+            scan_field(scanner, &mut obs.input, tid, oid, "input", &mut findings);
+            scan_field(scanner, &mut obs.output, tid, oid, "output", &mut findings);
+            if let Some(delta) = obs.outputDelta.take() {
+                let mut value = Some(JsonValue::String(delta));
+                scan_field(scanner, &mut value, tid, oid, "output", &mut findings);
+                if let Some(JsonValue::String(delta)) = value {
+                    obs.outputDelta = Some(delta);
+                }
+            }
+        }
+    }
+
+    // After masking, so stored objects hold no detected secrets. Sampling is
+    // decided again in the transaction; this pass only skips uploading the
+    // bodies of traces sampled out now, which at worst stay inline.
+    if let Some(offloader) = settings.offloader.as_deref() {
+        let mut sampled = payload.clone();
+        settings
+            .sampling
+            .apply(conn, &mut sampled, default_project_id)
+            .await?;
+        let kept: HashSet<Uuid> = sampled.observations.iter().map(|o| o.id).collect();
+        for obs in payload
+            .observations
+            .iter_mut()
+            .filter(|o| kept.contains(&o.id))
+        {
+            let project_id = obs
+                .projectId
+                .clone()
+                .unwrap_or_else(|| default_project_id.to_string());
+            offloader.offload(&project_id, obs).await;
+        }
+    }
+
+    Ok(Prepared { payload, findings })
+}
+
 async fn write_one(
     tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-    default_project_id: &str,
-    payload: BatchIngestRequest,
+    settings: &IngestSettings,
+    prices: &PriceTable,
+    prepared: Prepared,
+    touched_sessions: &mut BTreeSet<SessionKey>,
+    touched_projects: &mut BTreeSet<String>,
+    deltas: &mut Deltas,
 ) -> Result<(), sqlx::Error> {
+    let Prepared {
+        mut payload,
+        mut findings,
+    } = prepared;
+    let default_project_id = settings.default_project_id.as_ref();
+    let outbox = &settings.outbox;
     let now = Utc::now();
 
+    if let Some(ttl) = settings.dedup_ttl.filter(|_| !payload.event_ids.is_empty()) {
+        let project_id = payload.project_id().unwrap_or(default_project_id);
+        if dedup::claim(tx, project_id, &payload.event_ids, ttl).await? == 0 {
+            settings
+                .telemetry
+                .record_deduplicated(payload.event_ids.len());
+            return Ok(());
+        }
+    }
+
+    let sampled_out = settings
+        .sampling
+        .apply(tx, &mut payload, default_project_id)
+        .await?;
+    settings.telemetry.record_sampled_out(sampled_out);
+
+    let mut payload_trace_ids: Vec<Uuid> = payload
+        .trace
+        .iter()
+        .map(|t| t.id)
+        .chain(payload.observations.iter().map(|o| o.traceId))
+        .collect();
+    payload_trace_ids.sort_unstable();
+    payload_trace_ids.dedup();
+    // The partitioned tables only enforce unique `(id, timestamp)` and
+    // `(id, trace_timestamp)`. Ids stay unique because writes look up the
+    // existing rows by id under this lock before inserting.
+    lock_traces(tx, &payload_trace_ids).await?;
+    let dropped = ownership::retain_owned(tx, &mut payload, default_project_id).await?;
+    if dropped > 0 {
+        tracing::warn!(
+            dropped,
+            "dropped ingest writes to traces or observations of another project"
+        );
+    }
+    let observation_ids: Vec<Uuid> = payload.observations.iter().map(|o| o.id).collect();
+    let activity_before = activity::keys(tx, &payload_trace_ids).await?;
+    let usage_before = quotas::usage_of(tx, &payload_trace_ids, &observation_ids).await?;
+    let already_completed = completion::completed_among(tx, &payload_trace_ids).await?;
+
+    let mut late_writes: HashMap<Uuid, i32> = HashMap::new();
+    if !already_completed.is_empty() {
+        if let Some(trace) = &payload.trace {
+            if already_completed.contains(&trace.id) {
+                *late_writes.entry(trace.id).or_default() += 1;
+            }
+        }
+        for obs in &payload.observations {
+            if already_completed.contains(&obs.traceId) {
+                *late_writes.entry(obs.traceId).or_default() += 1;
+            }
+        }
+        if settings.late_write_mode == LateWriteMode::Strict {
+            if payload
+                .trace
+                .as_ref()
+                .is_some_and(|t| already_completed.contains(&t.id))
+            {
+                payload.trace = None;
+            }
+            payload
+                .observations
+                .retain(|o| !already_completed.contains(&o.traceId));
+            tracing::warn!(
+                traces = late_writes.len(),
+                "dropped writes to completed traces (strict mode)"
+            );
+        }
+    }
+    completion::record_late_writes(tx, &late_writes).await?;
+
+    let mut complete = std::mem::take(&mut payload.complete);
+    if let Some(trace) = payload.trace.as_ref().filter(|t| t.complete == Some(true)) {
+        complete.push(trace.id);
+    }
+
+    // Findings of writes dropped above are not recorded.
+    findings.retain(|f| match f.observation_id {
+        Some(id) => payload.observations.iter().any(|o| o.id == id),
+        None => payload.trace.as_ref().is_some_and(|t| t.id == f.trace_id),
+    });
+
+    for obs in &mut payload.observations {
+        // Detect before aliasing so `openai/gpt-4o`-style prefixes still count.
+        obs.provider = providers::detect(
+            obs.provider.as_deref(),
+            obs.model.as_deref(),
+            obs.metadata.as_ref(),
+        );
+        if let Some(aliases) = settings.model_aliases.as_deref() {
+            aliases.apply(&mut obs.model, &mut obs.metadata);
+        }
+        let project_id = obs.projectId.clone();
+        prices.apply(obs, project_id.as_deref().unwrap_or(default_project_id));
+    }
+
+    // (trace_id, project_id, observation_count) for outbox events.
+    let mut touched: Vec<(Uuid, String, usize)> = Vec::new();
+
     if let Some(trace) = payload.trace {
         let project_id = trace.projectId.as_deref().unwrap_or(default_project_id);
         let timestamp = trace.timestamp.unwrap_or(now);
-        let environment = trace.environment.unwrap_or_else(|| "default".to_string());
+        let environment = trace
+            .environment
+            .clone()
+            .unwrap_or_else(|| "default".to_string());
 
-        sqlx::query(
-            r#"
-INSERT INTO traces (
-  id, project_id, environment, timestamp, name, input, output, session_id, release, version, user_id,
-  metadata, tags, public, external_id, bookmarked, latency, total_cost, created_at, updated_at
-) VALUES (
-  $1, $2, $3, $4, $5, $6, $7, $8, $9, $10,
-  $11, $12, $13, $14, $15, $16, $17, $18, NOW(), NOW()
-)
-ON CONFLICT (id) DO UPDATE SET
-  project_id = EXCLUDED.project_id,
-  environment = EXCLUDED.environment,
-  timestamp = EXCLUDED.timestamp,
-  name = EXCLUDED.name,
-  input = EXCLUDED.input,
-  output = EXCLUDED.output,
-  session_id = EXCLUDED.session_id,
-  release = EXCLUDED.release,
-  version = EXCLUDED.version,
-  user_id = EXCLUDED.user_id,
-  metadata = EXCLUDED.metadata,
-  tags = EXCLUDED.tags,
-  public = EXCLUDED.public,
-  external_id = EXCLUDED.external_id,
-  bookmarked = EXCLUDED.bookmarked,
-  latency = EXCLUDED.latency,
-  total_cost = EXCLUDED.total_cost,
-  updated_at = NOW()
-            "#,
-        )
-        .bind(trace.id)
-        .bind(project_id.to_string())
-        .bind(environment.clone())
-        .bind(timestamp)
-        .bind(trace.name.clone())
-        .bind(trace.input.clone())
-        .bind(trace.output.clone())
-        .bind(trace.session_id.clone())
-        .bind(trace.release.clone())
-        .bind(trace.version.clone())
-        .bind(trace.userId.clone())
-        .bind(trace.metadata.clone())
-        .bind(trace.tags.clone())
-        .bind(trace.public.unwrap_or(false))
-        .bind(trace.externalId.clone())
-        .bind(trace.bookmarked.unwrap_or(false))
-        .bind(trace.latency)
-        .bind(trace.totalCost)
-        .execute(&mut **tx)
-        .await?;
+        // The trace may be moving out of a session; refresh the old one too.
+        sessions::collect_sessions(tx, &[trace.id], touched_sessions).await?;
+
+        let update = trace.update == Some(true);
+        let mut policy = settings.conflict_policies.for_project(project_id);
+        // Updates only change the fields they carry, whatever the policy;
+        // `latest` already does, and also orders them by event time.
+        if update && policy != ConflictPolicy::Latest {
+            policy = ConflictPolicy::Merge;
+        }
+        sqlx::query(conflicts::trace_upsert(policy, update))
+            .bind(trace.id)
+            .bind(project_id.to_string())
+            .bind(environment.clone())
+            .bind(timestamp)
+            .bind(trace.name.clone())
+            .bind(trace.input.clone())
+            .bind(trace.output.clone())
+            .bind(trace.session_id.clone())
+            .bind(trace.release.clone())
+            .bind(trace.version.clone())
+            .bind(trace.userId.clone())
+            .bind(trace.metadata.clone())
+            .bind(trace.tags.clone())
+            .bind(trace.public)
+            .bind(trace.externalId.clone())
+            .bind(trace.bookmarked)
+            .bind(trace.latency)
+            .bind(trace.totalCost)
+            .bind(trace.serviceName.clone())
+            .bind(trace.serviceVersion.clone())
+            .bind(trace.sdkLanguage.clone())
+            .bind(trace.hostName.clone())
+            .bind(trace.timestamp)
+            .bind(trace.environment.clone())
+            .bind(trace.eventTime)
+            .execute(&mut **tx)
+            .await?;
+
+        touched.push((trace.id, project_id.to_string(), 0));
     }
 
-    for obs in payload.observations {
-        let project_id = obs.projectId.as_deref().unwrap_or(default_project_id);
-        let environment = obs.environment.unwrap_or_else(|| "default".to_string());
+    // Observations are written in bulk: one statement creates their missing
+    // traces, then one upsert runs per round and conflict policy. A round
+    // holds at most one write per observation id, so repeated ids within the
+    // payload still apply in order.
+    let mut stubs: (Vec<Uuid>, Vec<String>, Vec<String>) = Default::default();
+    let mut writes_per_id: HashMap<Uuid, usize> = HashMap::new();
+    let mut groups: Vec<(usize, ConflictPolicy, ObservationRows)> = Vec::new();
+    let mut new_resources: Vec<(Uuid, JsonValue)> = Vec::new();
+    for mut obs in payload.observations {
+        let project_id = obs
+            .projectId
+            .clone()
+            .unwrap_or_else(|| default_project_id.to_string());
+        stubs.0.push(obs.traceId);
+        stubs.1.push(project_id.clone());
+        stubs.2.push(
+            obs.environment
+                .clone()
+                .unwrap_or_else(|| "default".to_string()),
+        );
+        match touched.iter_mut().find(|(id, _, _)| *id == obs.traceId) {
+            Some(entry) => entry.2 += 1,
+            None => touched.push((obs.traceId, project_id.clone(), 1)),
+        }
 
+        let writes = writes_per_id.entry(obs.id).or_default();
+        let round = *writes;
+        *writes += 1;
+        let mut policy = settings.conflict_policies.for_project(&project_id);
+        // Updates only change the fields they carry, whatever the policy;
+        // `latest` already does, and also orders them by event time.
+        if obs.is_update() && policy != ConflictPolicy::Latest {
+            policy = ConflictPolicy::Merge;
+        }
+        let group = match groups
+            .iter()
+            .position(|(r, p, _)| *r == round && *p == policy)
+        {
+            Some(i) => i,
+            None => {
+                groups.push((round, policy, ObservationRows::default()));
+                groups.len() - 1
+            }
+        };
+        let resource_id = resources::extract(&mut obs).map(|(id, attributes)| {
+            if !new_resources.iter().any(|(r, _)| *r == id) {
+                new_resources.push((id, attributes));
+            }
+            id
+        });
+        groups[group].2.push(obs, &project_id, resource_id);
+    }
+    resources::store(tx, &new_resources).await?;
+
+    if !stubs.0.is_empty() {
         sqlx::query(
             r#"
 INSERT INTO traces (id, project_id, environment, timestamp, created_at, updated_at)
-VALUES ($1, $2, $3, NOW(), NOW(), NOW())
-ON CONFLICT (id) DO NOTHING
+SELECT t.id, t.project_id, t.environment, COALESCE(e.timestamp, NOW()), NOW(), NOW()
+FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS t(id, project_id, environment)
+LEFT JOIN traces e ON e.id = t.id
+ON CONFLICT (id, timestamp) DO NOTHING
             "#,
         )
-        .bind(obs.traceId)
-        .bind(project_id.to_string())
-        .bind(environment.clone())
+        .bind(stubs.0)
+        .bind(stubs.1)
+        .bind(stubs.2)
         .execute(&mut **tx)
         .await?;
+    }
+
+    groups.sort_by_key(|(round, _, _)| *round);
+    for (_, policy, rows) in groups {
+        let conflicted: Vec<Uuid> = rows
+            .upsert(tx, policy)
+            .await?
+            .into_iter()
+            .filter_map(|(id, conflicted)| conflicted.then_some(id))
+            .collect();
+        for observation_id in conflicted {
+            if policy == ConflictPolicy::Error {
+                tracing::warn!(
+                    %observation_id,
+                    "rejected duplicate observation (conflict policy: error)"
+                );
+            } else {
+                tracing::debug!(
+                    %observation_id,
+                    policy = policy.as_str(),
+                    "duplicate observation"
+                );
+            }
+        }
+    }
+
+    align_observations(tx, &payload_trace_ids).await?;
+    deltas.activity.record(
+        activity_before,
+        activity::keys(tx, &payload_trace_ids).await?,
+    );
+    deltas.usage.record(
+        usage_before,
+        quotas::usage_of(tx, &payload_trace_ids, &observation_ids).await?,
+    );
+
+    touched_projects.extend(touched.iter().map(|(_, p, _)| p.clone()));
+    let trace_ids: Vec<Uuid> = touched.iter().map(|(id, _, _)| *id).collect();
+    rollup_traces(tx, &trace_ids).await?;
+    sessions::collect_sessions(tx, &trace_ids, touched_sessions).await?;
+
+    let mut completed = Vec::new();
+    for trace_id in complete {
+        if let Some(c) = completion::finalize(tx, trace_id, touched_sessions).await? {
+            completed.push((trace_id, c));
+        }
+    }
+
+    let project_of = |trace_id: Uuid| {
+        touched
+            .iter()
+            .find(|(id, _, _)| *id == trace_id)
+            .map(|(_, p, _)| p.clone())
+            .unwrap_or_else(|| default_project_id.to_string())
+    };
 
+    for f in &findings {
+        let project_id = project_of(f.trace_id);
         sqlx::query(
             r#"
-INSERT INTO observations (
-  id, trace_id, type, name, start_time, end_time, completion_start_time,
-  model, model_parameters, input, output, usage, level, status_message,
-  parent_observation_id, prompt_id, prompt_name, prompt_version, model_id,
-  input_price, output_price, total_price,
-  calculated_input_cost, calculated_output_cost, calculated_total_cost,
-  latency, time_to_first_token,
-  completion_tokens, prompt_tokens, total_tokens, unit,
-  metadata, environment, project_id, created_at, updated_at
-) VALUES (
-  $1, $2, $3, $4, $5, $6, $7,
-  $8, $9, $10, $11, $12, $13, $14,
-  $15, $16, $17, $18, $19,
-  $20, $21, $22,
-  $23, $24, $25,
-  $26, $27,
-  $28, $29, $30, $31,
-  $32, $33, $34, NOW(), NOW()
-)
-ON CONFLICT (id) DO UPDATE SET
-  trace_id = EXCLUDED.trace_id,
-  type = EXCLUDED.type,
-  name = EXCLUDED.name,
-  start_time = EXCLUDED.start_time,
-  end_time = EXCLUDED.end_time,
-  completion_start_time = EXCLUDED.completion_start_time,
-  model = EXCLUDED.model,
-  model_parameters = EXCLUDED.model_parameters,
-  input = EXCLUDED.input,
-  output = EXCLUDED.output,
-  usage = EXCLUDED.usage,
-  level = EXCLUDED.level,
-  status_message = EXCLUDED.status_message,
-  parent_observation_id = EXCLUDED.parent_observation_id,
-  prompt_id = EXCLUDED.prompt_id,
-  prompt_name = EXCLUDED.prompt_name,
-  prompt_version = EXCLUDED.prompt_version,
-  model_id = EXCLUDED.model_id,
-  input_price = EXCLUDED.input_price,
-  output_price = EXCLUDED.output_price,
-  total_price = EXCLUDED.total_price,
-  calculated_input_cost = EXCLUDED.calculated_input_cost,
-  calculated_output_cost = EXCLUDED.calculated_output_cost,
-  calculated_total_cost = EXCLUDED.calculated_total_cost,
-  latency = EXCLUDED.latency,
-  time_to_first_token = EXCLUDED.time_to_first_token,
-  completion_tokens = EXCLUDED.completion_tokens,
-  prompt_tokens = EXCLUDED.prompt_tokens,
-  total_tokens = EXCLUDED.total_tokens,
-  unit = EXCLUDED.unit,
-  metadata = EXCLUDED.metadata,
-  environment = EXCLUDED.environment,
-  project_id = EXCLUDED.project_id,
-  updated_at = NOW()
+INSERT INTO secret_findings (project_id, trace_id, observation_id, field, kind, match_count)
+VALUES ($1, $2, $3, $4, $5, $6)
             "#,
         )
-        .bind(obs.id)
-        .bind(obs.traceId)
-        .bind(obs.r#type.unwrap_or_else(|| "GENERATION".to_string()))
-        .bind(obs.name.clone())
-        .bind(obs.startTime)
-        .bind(obs.endTime)
-        .bind(obs.completionStartTime)
-        .bind(obs.model.clone())
-        .bind(obs.modelParameters.clone())
-        .bind(obs.input.clone())
-        .bind(obs.output.clone())
-        .bind(obs.usage.clone())
-        .bind(obs.level.clone())
-        .bind(obs.statusMessage.clone())
-        .bind(obs.parentObservationId)
-        .bind(obs.promptId.clone())
-        .bind(obs.promptName.clone())
-        .bind(obs.promptVersion.clone())
-        .bind(obs.modelId.clone())
-        .bind(obs.inputPrice)
-        .bind(obs.outputPrice)
-        .bind(obs.totalPrice)
-        .bind(obs.calculatedInputCost)
-        .bind(obs.calculatedOutputCost)
-        .bind(obs.calculatedTotalCost)
-        .bind(obs.latency)
-        .bind(obs.timeToFirstToken)
-        .bind(obs.completionTokens)
-        .bind(obs.promptTokens)
-        .bind(obs.totalTokens)
-        .bind(obs.unit.clone())
-        .bind(obs.metadata.clone())
-        .bind(environment)
-        .bind(project_id.to_string())
+        .bind(&project_id)
+        .bind(f.trace_id)
+        .bind(f.observation_id)
+        .bind(f.field)
+        .bind(f.kind)
+        .bind(f.count as i32)
         .execute(&mut **tx)
         .await?;
     }
+    if !findings.is_empty() {
+        tracing::warn!(
+            findings = findings.len(),
+            "secret scanner masked credentials in ingested payload"
+        );
+    }
 
+    if let Some(url) = outbox.webhook_url.as_deref() {
+        for f in &findings {
+            outbox::enqueue(
+                tx,
+                url,
+                &project_of(f.trace_id),
+                "secret.detected",
+                serde_json::json!({
+                    "traceId": f.trace_id,
+                    "observationId": f.observation_id,
+                    "field": f.field,
+                    "kind": f.kind,
+                    "matchCount": f.count,
+                }),
+            )
+            .await?;
+        }
+        for (trace_id, project_id, observation_count) in touched {
+            outbox::enqueue(
+                tx,
+                url,
+                &project_id,
+                "trace.ingested",
+                serde_json::json!({
+                    "traceId": trace_id,
+                    "projectId": project_id,
+                    "observationCount": observation_count,
+                }),
+            )
+            .await?;
+        }
+        for (trace_id, c) in &completed {
+            outbox::enqueue(
+                tx,
+                url,
+                &c.project_id,
+                "trace.completed",
+                serde_json::json!({
+                    "traceId": trace_id,
+                    "projectId": c.project_id,
+                    "completedAt": c.completed_at,
+                    "latency": c.latency,
+                    "totalCost": c.total_cost,
+                    "observationCount": c.observation_count,
+                }),
+            )
+            .await?;
+        }
+    }
+
+    rules::evaluate(tx, &trace_ids).await?;
+
+    Ok(())
+}
+
+/// Sets `traces.latency` to the span from the first observation start to the
+/// last observation end, and `traces.total_cost` to the sum of the calculated
+/// costs of the trace's observations. Values the client ever sent with the
+/// trace (`client_latency`, `client_total_cost`) are kept, as are the
+/// finalized values of completed traces and the stored ones when the
+/// observations give none.
+async fn rollup_traces(
+    conn: &mut sqlx::PgConnection,
+    trace_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+UPDATE traces t SET
+  latency = CASE WHEN t.client_latency THEN t.latency ELSE COALESCE(agg.latency, t.latency) END,
+  total_cost = CASE WHEN t.client_total_cost THEN t.total_cost ELSE COALESCE(agg.total_cost, t.total_cost) END,
+  updated_at = NOW()
+FROM (
+  SELECT
+    trace_id,
+    EXTRACT(EPOCH FROM MAX(COALESCE(end_time, start_time)) - MIN(start_time))::DOUBLE PRECISION AS latency,
+    SUM(calculated_total_cost)::DOUBLE PRECISION AS total_cost
+  FROM observations
+  WHERE trace_id = ANY($1)
+  GROUP BY trace_id
+) agg
+WHERE t.id = agg.trace_id
+  AND t.completed_at IS NULL
+  AND (
+    (NOT t.client_latency AND agg.latency IS NOT NULL AND t.latency IS DISTINCT FROM agg.latency)
+    OR (NOT t.client_total_cost AND agg.total_cost IS NOT NULL AND t.total_cost IS DISTINCT FROM agg.total_cost)
+  )
+        "#,
+    )
+    .bind(trace_ids)
+    .execute(conn)
+    .await?;
     Ok(())
 }
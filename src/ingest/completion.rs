@@ -0,0 +1,155 @@
+//! Explicit trace completion.
+//!
+//! A trace is finalized either by `"complete": true` on an ingested trace or by
+//! `POST /api/public/traces/:traceId/complete`. Finalizing stamps
+//! `completed_at`, fills `latency` / `total_cost` from the observations when the
+//! client did not send them, refreshes the owning session aggregate and emits a
+//! `trace.completed` outbox event (the hook for evaluators).
+//!
+//! Writes that arrive after completion are "late". In lenient mode they are
+//! stored but the finalized latency/cost stay frozen; in strict mode they are
+//! dropped. Both modes count them in `traces.late_writes`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    http::{auth::ProjectScope, common::ApiResponse, error::ApiError},
+    ingest::{
+        batch::BatchIngestRequest,
+        sessions::{self, SessionKey},
+    },
+    state::AppState,
+};
+
+/// How writes to an already completed trace are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum LateWriteMode {
+    /// Store late writes; finalized trace latency/cost are not overwritten.
+    #[default]
+    Lenient,
+    /// Drop late writes.
+    Strict,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub(crate) struct CompletedTrace {
+    pub project_id: String,
+    pub completed_at: DateTime<Utc>,
+    pub latency: Option<f64>,
+    pub total_cost: Option<f64>,
+    pub observation_count: i64,
+}
+
+/// Returns the subset of `trace_ids` that is already completed.
+pub(crate) async fn completed_among(
+    conn: &mut PgConnection,
+    trace_ids: &[Uuid],
+) -> Result<HashSet<Uuid>, sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let ids: Vec<Uuid> =
+        sqlx::query_scalar("SELECT id FROM traces WHERE id = ANY($1) AND completed_at IS NOT NULL")
+            .bind(trace_ids)
+            .fetch_all(conn)
+            .await?;
+    Ok(ids.into_iter().collect())
+}
+
+/// Adds `counts` (trace id -> rejected or accepted late writes) to `late_writes`.
+pub(crate) async fn record_late_writes(
+    conn: &mut PgConnection,
+    counts: &HashMap<Uuid, i32>,
+) -> Result<(), sqlx::Error> {
+    if counts.is_empty() {
+        return Ok(());
+    }
+    let (ids, n): (Vec<Uuid>, Vec<i32>) = counts.iter().map(|(id, n)| (*id, *n)).unzip();
+    sqlx::query(
+        r#"
+UPDATE traces t
+SET late_writes = t.late_writes + c.n
+FROM UNNEST($1::uuid[], $2::int[]) AS c(id, n)
+WHERE t.id = c.id
+        "#,
+    )
+    .bind(&ids)
+    .bind(&n)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Marks the trace completed and computes its final aggregates. Returns `None`
+/// if the trace does not exist or was already completed.
+pub(crate) async fn finalize(
+    conn: &mut PgConnection,
+    trace_id: Uuid,
+    touched_sessions: &mut BTreeSet<SessionKey>,
+) -> Result<Option<CompletedTrace>, sqlx::Error> {
+    let completed: Option<CompletedTrace> = sqlx::query_as(
+        r#"
+UPDATE traces t SET
+  completed_at = NOW(),
+  latency = COALESCE(t.latency, agg.latency),
+  total_cost = COALESCE(t.total_cost, agg.total_cost),
+  updated_at = NOW()
+FROM (
+  SELECT
+    EXTRACT(EPOCH FROM MAX(COALESCE(o.end_time, o.start_time)) - MIN(o.start_time))::DOUBLE PRECISION AS latency,
+    SUM(o.calculated_total_cost)::DOUBLE PRECISION AS total_cost,
+    COUNT(*)::BIGINT AS observation_count
+  FROM observations o
+  WHERE o.trace_id = $1
+) agg
+WHERE t.id = $1 AND t.completed_at IS NULL
+RETURNING t.project_id, t.completed_at, t.latency, t.total_cost, agg.observation_count
+        "#,
+    )
+    .bind(trace_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if completed.is_some() {
+        sessions::collect_sessions(conn, &[trace_id], touched_sessions).await?;
+    }
+    Ok(completed)
+}
+
+/// Queues completion behind any batches already accepted for the trace, so
+/// observations sent just before the signal are not treated as late writes.
+pub(crate) async fn post_trace_complete(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(trace_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    scope.check_trace(&state.pool, trace_id).await?;
+    let payload = BatchIngestRequest {
+        trace: None,
+        observations: Vec::new(),
+        event_ids: Vec::new(),
+        complete: vec![trace_id],
+        wal_seq: None,
+        accepted_at: None,
+        extra: Default::default(),
+    };
+    state.enqueue_ingest(vec![payload]).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ApiResponse::<serde_json::Value> {
+            message: "Request Successful.".to_string(),
+            code: None,
+            data: Some(serde_json::json!({ "traceId": trace_id })),
+        }),
+    ))
+}
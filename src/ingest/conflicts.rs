@@ -0,0 +1,716 @@
+//! What happens when an ingested observation id already exists.
+//!
+//! Writes to an existing observation are often partial updates (Langfuse's
+//! create-then-update flow) and sometimes SDKs replaying a batch. A conflict
+//! policy can be set globally and per project:
+//!
+//! - `latest` (default): like `merge`, but a payload whose event time
+//!   (`eventTime`, else the time it is written) is older than the stored
+//!   row's only fills in fields the row does not have yet, so updates
+//!   delivered out of order do not undo newer ones;
+//! - `overwrite`: replace every column with the new values;
+//! - `merge`: keep stored values where the new payload has none (metadata
+//!   objects are merged key by key);
+//! - `ignore`: keep the stored row;
+//! - `error`: keep the stored row and reject the write (`409` on
+//!   `/v1/l/batch`, a per-event error on `/api/public/ingestion`).
+//!
+//! Every conflict is recorded on the stored row under
+//! `metadata.xtrace_conflict` (`policy`, `outcome`, `count`, `at`), so double
+//! reporting shows up when looking at the observation. Traces follow `merge`
+//! and `latest` too; the other policies overwrite them.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::ingest::batch::ObservationIngest;
+
+pub(crate) const CONFLICT_METADATA_KEY: &str = "xtrace_conflict";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    Overwrite,
+    Merge,
+    Ignore,
+    Error,
+    #[default]
+    Latest,
+}
+
+impl ConflictPolicy {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "overwrite" => Some(Self::Overwrite),
+            "merge" => Some(Self::Merge),
+            "ignore" => Some(Self::Ignore),
+            "error" => Some(Self::Error),
+            "latest" => Some(Self::Latest),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Overwrite => "overwrite",
+            Self::Merge => "merge",
+            Self::Ignore => "ignore",
+            Self::Error => "error",
+            Self::Latest => "latest",
+        }
+    }
+
+    /// Outcome recorded in `metadata.xtrace_conflict.outcome`.
+    fn outcome(self) -> &'static str {
+        match self {
+            Self::Overwrite => "overwritten",
+            Self::Merge | Self::Latest => "merged",
+            Self::Ignore => "ignored",
+            Self::Error => "rejected",
+        }
+    }
+
+    /// Whether a conflicting write leaves the stored row's data untouched.
+    pub(crate) fn keeps_existing(self) -> bool {
+        matches!(self, Self::Ignore | Self::Error)
+    }
+}
+
+/// Global default plus per-project overrides.
+#[derive(Debug, Default)]
+pub struct ConflictPolicies {
+    default: ConflictPolicy,
+    per_project: HashMap<String, ConflictPolicy>,
+}
+
+impl ConflictPolicies {
+    /// `default` is a policy name; `projects` holds `project=policy` pairs
+    /// separated by commas or newlines.
+    pub(crate) fn parse(default: Option<&str>, projects: Option<&str>) -> Result<Self, String> {
+        let invalid = |v: &str| {
+            format!(
+                "invalid conflict policy: {v:?} (expected latest, overwrite, merge, ignore or error)"
+            )
+        };
+        let default = match default.map(str::trim).filter(|v| !v.is_empty()) {
+            Some(v) => ConflictPolicy::parse(v).ok_or_else(|| invalid(v))?,
+            None => ConflictPolicy::Latest,
+        };
+        let mut per_project = HashMap::new();
+        for entry in projects
+            .unwrap_or_default()
+            .split([',', '\n'])
+            .map(str::trim)
+        {
+            if entry.is_empty() {
+                continue;
+            }
+            let (project, policy) = entry
+                .split_once('=')
+                .map(|(p, v)| (p.trim(), v.trim()))
+                .filter(|(p, _)| !p.is_empty())
+                .ok_or_else(|| format!("invalid project conflict policy entry: {entry:?}"))?;
+            let policy = ConflictPolicy::parse(policy).ok_or_else(|| invalid(policy))?;
+            per_project.insert(project.to_string(), policy);
+        }
+        Ok(Self {
+            default,
+            per_project,
+        })
+    }
+
+    pub(crate) fn for_project(&self, project_id: &str) -> ConflictPolicy {
+        self.per_project
+            .get(project_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    fn any_error(&self) -> bool {
+        self.default == ConflictPolicy::Error
+            || self
+                .per_project
+                .values()
+                .any(|p| *p == ConflictPolicy::Error)
+    }
+
+    /// Observation ids (with their project) that already exist and whose
+    /// project uses the `error` policy. Lets ingest endpoints reject them up
+    /// front; the worker still applies the policy to races it cannot see.
+    pub(crate) async fn rejected(
+        &self,
+        pool: &PgPool,
+        observations: &[(Uuid, &str)],
+    ) -> Result<HashSet<Uuid>, sqlx::Error> {
+        if !self.any_error() {
+            return Ok(HashSet::new());
+        }
+        let ids: Vec<Uuid> = observations
+            .iter()
+            .filter(|(_, project_id)| self.for_project(project_id) == ConflictPolicy::Error)
+            .map(|(id, _)| *id)
+            .collect();
+        if ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let rows: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM observations WHERE id = ANY($1)")
+            .bind(&ids)
+            .fetch_all(pool)
+            .await?;
+        Ok(rows.into_iter().collect())
+    }
+}
+
+/// Columns of the observation upsert with their array element types, in bind
+/// order. [`ObservationRows`] pushes and binds them in the same order.
+const OBSERVATION_COLUMNS: &[(&str, &str)] = &[
+    ("id", "uuid"),
+    ("trace_id", "uuid"),
+    ("type", "text"),
+    ("name", "text"),
+    ("start_time", "timestamptz"),
+    ("end_time", "timestamptz"),
+    ("completion_start_time", "timestamptz"),
+    ("model", "text"),
+    ("model_parameters", "jsonb"),
+    ("input", "jsonb"),
+    ("output", "jsonb"),
+    ("input_offload", "text"),
+    ("output_offload", "text"),
+    ("usage", "jsonb"),
+    ("level", "text"),
+    ("status_message", "text"),
+    ("parent_observation_id", "uuid"),
+    ("prompt_id", "text"),
+    ("prompt_name", "text"),
+    ("prompt_version", "text"),
+    ("model_id", "text"),
+    ("input_price", "float8"),
+    ("output_price", "float8"),
+    ("total_price", "float8"),
+    ("calculated_input_cost", "float8"),
+    ("calculated_output_cost", "float8"),
+    ("calculated_total_cost", "float8"),
+    ("latency", "float8"),
+    ("time_to_first_token", "float8"),
+    ("completion_tokens", "int8"),
+    ("prompt_tokens", "int8"),
+    ("total_tokens", "int8"),
+    ("unit", "text"),
+    ("metadata", "jsonb"),
+    ("environment", "text"),
+    ("project_id", "text"),
+    ("service_name", "text"),
+    ("service_version", "text"),
+    ("sdk_language", "text"),
+    ("host_name", "text"),
+    ("provider", "text"),
+    ("resource_id", "uuid"),
+    ("event_time", "timestamptz"),
+    ("output_seq", "int8"),
+];
+
+/// How each row is written, bound after [`OBSERVATION_COLUMNS`]: whether it
+/// is an update (see [`ObservationIngest::is_update`]) and the text to append
+/// to its output. Updates are only grouped under `merge` or `latest`.
+const WRITE_COLUMNS: &[(&str, &str)] = &[("is_update", "bool"), ("output_delta", "text")];
+
+/// Wraps a metadata expression so it carries the conflict marker.
+fn mark(metadata: &str, policy: ConflictPolicy) -> String {
+    format!(
+        r#"CASE WHEN {metadata} IS NULL OR jsonb_typeof({metadata}) = 'object' THEN
+    COALESCE({metadata}, '{{}}'::jsonb) || jsonb_build_object('{CONFLICT_METADATA_KEY}', jsonb_build_object(
+      'policy', '{policy}', 'outcome', '{outcome}', 'at', NOW(),
+      'count', COALESCE((observations.metadata->'{CONFLICT_METADATA_KEY}'->>'count')::int, 0) + 1))
+  ELSE {metadata} END"#,
+        policy = policy.as_str(),
+        outcome = policy.outcome(),
+    )
+}
+
+/// Whether the written payload is at least as recent as the stored `table`
+/// row; rows written before event times were recorded count as older.
+fn newer(table: &str) -> String {
+    format!("({table}.event_time IS NULL OR EXCLUDED.event_time >= {table}.event_time)")
+}
+
+/// A `merge` or `latest` update of a column from its new and stored values:
+/// missing values never replace stored ones, and under `latest` stored values
+/// win over older payloads.
+fn merged(policy: ConflictPolicy, table: &str, new: &str, stored: &str) -> String {
+    match policy {
+        ConflictPolicy::Latest => format!(
+            "CASE WHEN {} THEN COALESCE({new}, {stored}) ELSE COALESCE({stored}, {new}) END",
+            newer(table)
+        ),
+        _ => format!("COALESCE({new}, {stored})"),
+    }
+}
+
+/// [`merged`] for a JSON metadata column; objects are merged key by key.
+fn merged_metadata(policy: ConflictPolicy, table: &str) -> String {
+    let objects = format!(
+        "jsonb_typeof({table}.metadata) = 'object' AND jsonb_typeof(EXCLUDED.metadata) = 'object'"
+    );
+    match policy {
+        ConflictPolicy::Latest => format!(
+            "(CASE WHEN {objects} THEN \
+             CASE WHEN {newer} THEN {table}.metadata || EXCLUDED.metadata ELSE EXCLUDED.metadata || {table}.metadata END \
+             ELSE {value} END)",
+            newer = newer(table),
+            value = merged(policy, table, "EXCLUDED.metadata", &format!("{table}.metadata")),
+        ),
+        _ => format!(
+            "(CASE WHEN {objects} THEN {table}.metadata || EXCLUDED.metadata \
+             ELSE COALESCE(EXCLUDED.metadata, {table}.metadata) END)"
+        ),
+    }
+}
+
+fn build_upsert(policy: ConflictPolicy) -> String {
+    let names: Vec<&str> = OBSERVATION_COLUMNS.iter().map(|(c, _)| *c).collect();
+    let inputs: Vec<&str> = OBSERVATION_COLUMNS
+        .iter()
+        .chain(WRITE_COLUMNS)
+        .map(|(c, _)| *c)
+        .collect();
+    let arrays: Vec<String> = OBSERVATION_COLUMNS
+        .iter()
+        .chain(WRITE_COLUMNS)
+        .enumerate()
+        .map(|(i, (_, ty))| format!("${}::{ty}[]", i + 1))
+        .collect();
+    // `type` and `environment` are unnested as given; defaults apply on insert
+    // only, so a merge does not reset them.
+    let values: Vec<String> = names
+        .iter()
+        .map(|c| match *c {
+            "type" => "COALESCE(v.type, 'GENERATION')".to_string(),
+            "environment" => "COALESCE(v.environment, 'default')".to_string(),
+            "event_time" => "COALESCE(v.event_time, NOW())".to_string(),
+            "output" => "CASE WHEN v.output_delta IS NULL THEN v.output \
+                 ELSE to_jsonb(COALESCE(v.output #>> '{}', '') || v.output_delta) END"
+                .to_string(),
+            c => format!("v.{c}"),
+        })
+        .collect();
+    let given = |c: &str| format!("(SELECT v.{c} FROM v WHERE v.id = EXCLUDED.id)");
+    // A delta extends the output sent along with it, else the stored one
+    // (as text when it is not a string). One at or below the stored sequence
+    // number was appended already.
+    let appended = |merged: String| {
+        format!(
+            "CASE WHEN {delta} IS NULL THEN {merged} \
+             WHEN EXCLUDED.output_seq <= observations.output_seq THEN observations.output \
+             WHEN {output} IS NOT NULL THEN EXCLUDED.output \
+             ELSE to_jsonb(COALESCE(observations.output #>> '{{}}', '') || {delta}) END",
+            delta = given("output_delta"),
+            output = given("output"),
+        )
+    };
+
+    let set = match policy {
+        ConflictPolicy::Overwrite | ConflictPolicy::Merge | ConflictPolicy::Latest => names[1..]
+            .iter()
+            .filter(|c| **c != "project_id")
+            .map(|c| {
+                let value = match (policy, *c) {
+                    (_, "output_seq") => {
+                        "GREATEST(observations.output_seq, EXCLUDED.output_seq)".to_string()
+                    }
+                    (ConflictPolicy::Overwrite, "metadata") => mark("EXCLUDED.metadata", policy),
+                    (ConflictPolicy::Overwrite, _) => format!("EXCLUDED.{c}"),
+                    // Updates are expected writes, not conflicts.
+                    (_, "metadata") => {
+                        let metadata = merged_metadata(policy, "observations");
+                        format!(
+                            "CASE WHEN {} THEN {metadata} ELSE {} END",
+                            given("is_update"),
+                            mark(&metadata, policy)
+                        )
+                    }
+                    (_, "output") => appended(merged(
+                        policy,
+                        "observations",
+                        "EXCLUDED.output",
+                        "observations.output",
+                    )),
+                    (_, "event_time") => {
+                        "GREATEST(observations.event_time, EXCLUDED.event_time)".to_string()
+                    }
+                    (_, c @ ("type" | "environment")) => {
+                        merged(policy, "observations", &given(c), &format!("observations.{c}"))
+                    }
+                    // An object key goes with the value it was offloaded from.
+                    (_, c @ ("input_offload" | "output_offload")) => {
+                        let field = c.trim_end_matches("_offload");
+                        let take_new = match policy {
+                            ConflictPolicy::Latest => format!(
+                                "CASE WHEN {} THEN EXCLUDED.{field} IS NOT NULL ELSE observations.{field} IS NULL END",
+                                newer("observations")
+                            ),
+                            _ => format!("EXCLUDED.{field} IS NOT NULL"),
+                        };
+                        format!(
+                            "CASE WHEN {take_new} THEN EXCLUDED.{c} ELSE observations.{c} END"
+                        )
+                    }
+                    (_, c) => merged(
+                        policy,
+                        "observations",
+                        &format!("EXCLUDED.{c}"),
+                        &format!("observations.{c}"),
+                    ),
+                };
+                format!("  {c} = {value},\n")
+            })
+            .collect::<String>(),
+        ConflictPolicy::Ignore | ConflictPolicy::Error => {
+            format!("  metadata = {},\n", mark("observations.metadata", policy))
+        }
+    };
+    let touch = if policy.keeps_existing() {
+        ""
+    } else {
+        "  updated_at = NOW()\n"
+    };
+    let set = if touch.is_empty() {
+        set.trim_end().trim_end_matches(',').to_string() + "\n"
+    } else {
+        set + touch
+    };
+
+    // Rows are unique by `(id, trace_timestamp)` (the table is partitioned by
+    // the trace's timestamp), so an existing row is written with its stored
+    // `trace_timestamp` and moved afterwards when its trace's has changed (see
+    // `batch::align_observations`). `existing` is read before the insert and
+    // tells conflicts apart.
+    format!(
+        "WITH v AS (\n  SELECT * FROM UNNEST({arrays}) AS v({inputs})\n),\n\
+         existing AS (\n  SELECT id, trace_timestamp FROM observations WHERE id IN (SELECT id FROM v)\n)\n\
+         INSERT INTO observations (\n  {columns}, trace_timestamp, created_at, updated_at\n)\n\
+         SELECT {values}, COALESCE(e.trace_timestamp, \
+           (SELECT t.timestamp FROM traces t WHERE t.id = v.trace_id), NOW()), NOW(), NOW()\n\
+         FROM v LEFT JOIN existing e ON e.id = v.id\n\
+         ON CONFLICT (id, trace_timestamp) DO UPDATE SET\n{set}\
+         WHERE observations.project_id = EXCLUDED.project_id\n\
+         RETURNING id, EXISTS (SELECT 1 FROM existing e WHERE e.id = observations.id) \
+           AND NOT (SELECT v.is_update FROM v WHERE v.id = observations.id) AS conflicted",
+        arrays = arrays.join(", "),
+        inputs = inputs.join(", "),
+        columns = names.join(", "),
+        values = values.join(", "),
+    )
+}
+
+/// The multi-row observation upsert for `policy`, binding one array per
+/// column (see [`ObservationRows`]). Returns `(id, conflicted)` for every row,
+/// `conflicted` being whether the id already existed. Ids must be distinct.
+/// A stored row of another project is left as it is and not returned.
+fn observation_upsert(policy: ConflictPolicy) -> &'static str {
+    static SQL: [OnceLock<String>; 5] = [
+        OnceLock::new(),
+        OnceLock::new(),
+        OnceLock::new(),
+        OnceLock::new(),
+        OnceLock::new(),
+    ];
+    SQL[policy as usize].get_or_init(|| build_upsert(policy))
+}
+
+fn build_trace_upsert(policy: ConflictPolicy, update: bool) -> String {
+    let merging = matches!(policy, ConflictPolicy::Merge | ConflictPolicy::Latest);
+    // `$23` and `$24` are the timestamp and environment as sent, so a merge
+    // keeps stored values rather than the defaults in `$4` and `$3`.
+    let column = |c: &str, new: &str| {
+        if merging {
+            merged(policy, "traces", new, &format!("traces.{c}"))
+        } else {
+            format!("EXCLUDED.{c}")
+        }
+    };
+    let tags = if merging {
+        let tags = merged(
+            policy,
+            "traces",
+            "NULLIF(EXCLUDED.tags, '{}')",
+            "NULLIF(traces.tags, '{}')",
+        );
+        format!("COALESCE({tags}, '{{}}')")
+    } else {
+        "EXCLUDED.tags".to_string()
+    };
+    let metadata = if merging {
+        merged_metadata(policy, "traces")
+    } else {
+        "EXCLUDED.metadata".to_string()
+    };
+    // A partial update may describe a later part of the trace (another
+    // export of its spans); the trace still starts at the earliest one.
+    let timestamp = if update {
+        "LEAST(traces.timestamp, COALESCE($23::timestamptz, traces.timestamp))".to_string()
+    } else {
+        column("timestamp", "$23::timestamptz")
+    };
+    let event_time = if merging {
+        "GREATEST(traces.event_time, EXCLUDED.event_time)"
+    } else {
+        "EXCLUDED.event_time"
+    };
+    // Traces are partitioned by `timestamp`, which an upsert cannot change
+    // (the row would move to another partition), so an existing trace is
+    // updated and a new one inserted. The trace lock keeps the two apart.
+    format!(
+        r#"
+WITH excluded AS (
+  SELECT
+    $1::uuid AS id, $2::text AS project_id, $3::text AS environment, $4::timestamptz AS timestamp,
+    $5::text AS name, $6::jsonb AS input, $7::jsonb AS output, $8::text AS session_id,
+    $9::text AS release, $10::text AS version, $11::text AS user_id, $12::jsonb AS metadata,
+    $13::text[] AS tags, COALESCE($14::boolean, FALSE) AS public, $15::text AS external_id,
+    COALESCE($16::boolean, FALSE) AS bookmarked, $17::float8 AS latency, $18::float8 AS total_cost,
+    $19::text AS service_name, $20::text AS service_version, $21::text AS sdk_language,
+    $22::text AS host_name, COALESCE($25::timestamptz, NOW()) AS event_time,
+    $17::float8 IS NOT NULL AS client_latency, $18::float8 IS NOT NULL AS client_total_cost
+),
+updated AS (
+  UPDATE traces SET
+    environment = {environment},
+    timestamp = {timestamp},
+    name = {name},
+    input = {input},
+    output = {output},
+    session_id = {session_id},
+    release = {release},
+    version = {version},
+    user_id = {user_id},
+    metadata = {metadata},
+    tags = {tags},
+    public = COALESCE($14, traces.public),
+    external_id = {external_id},
+    bookmarked = COALESCE($16, traces.bookmarked),
+    latency = CASE
+      WHEN traces.completed_at IS NOT NULL THEN traces.latency
+      WHEN traces.client_latency THEN COALESCE(EXCLUDED.latency, traces.latency)
+      ELSE EXCLUDED.latency
+    END,
+    total_cost = CASE
+      WHEN traces.completed_at IS NOT NULL THEN traces.total_cost
+      WHEN traces.client_total_cost THEN COALESCE(EXCLUDED.total_cost, traces.total_cost)
+      ELSE EXCLUDED.total_cost
+    END,
+    client_latency = traces.client_latency
+      OR (traces.completed_at IS NULL AND EXCLUDED.client_latency),
+    client_total_cost = traces.client_total_cost
+      OR (traces.completed_at IS NULL AND EXCLUDED.client_total_cost),
+    service_name = COALESCE(EXCLUDED.service_name, traces.service_name),
+    service_version = COALESCE(EXCLUDED.service_version, traces.service_version),
+    sdk_language = COALESCE(EXCLUDED.sdk_language, traces.sdk_language),
+    host_name = COALESCE(EXCLUDED.host_name, traces.host_name),
+    event_time = {event_time},
+    updated_at = NOW()
+  FROM excluded
+  WHERE traces.id = EXCLUDED.id AND traces.project_id = EXCLUDED.project_id
+)
+INSERT INTO traces (
+  id, project_id, environment, timestamp, name, input, output, session_id, release, version, user_id,
+  metadata, tags, public, external_id, bookmarked, latency, total_cost,
+  service_name, service_version, sdk_language, host_name, event_time,
+  client_latency, client_total_cost, created_at, updated_at
+)
+SELECT *, NOW(), NOW() FROM excluded
+WHERE NOT EXISTS (SELECT 1 FROM traces WHERE id = $1)
+"#,
+        environment = column("environment", "$24::text"),
+        name = column("name", "EXCLUDED.name"),
+        input = column("input", "EXCLUDED.input"),
+        output = column("output", "EXCLUDED.output"),
+        session_id = column("session_id", "EXCLUDED.session_id"),
+        release = column("release", "EXCLUDED.release"),
+        version = column("version", "EXCLUDED.version"),
+        user_id = column("user_id", "EXCLUDED.user_id"),
+        external_id = column("external_id", "EXCLUDED.external_id"),
+    )
+}
+
+/// The trace upsert for `policy`, and for partial updates when `update`.
+/// A stored trace of another project is left as it is.
+/// Binds the trace's columns in insert order (`$1`–`$22`), then the
+/// timestamp and environment as sent and the event time (`$23`–`$25`).
+pub(crate) fn trace_upsert(policy: ConflictPolicy, update: bool) -> &'static str {
+    static SQL: [[OnceLock<String>; 2]; 5] = [const { [const { OnceLock::new() }; 2] }; 5];
+    SQL[policy as usize][usize::from(update)].get_or_init(|| build_trace_upsert(policy, update))
+}
+
+/// Observations of one upsert, stored column by column.
+#[derive(Debug, Default)]
+pub(crate) struct ObservationRows {
+    id: Vec<Uuid>,
+    trace_id: Vec<Uuid>,
+    r#type: Vec<Option<String>>,
+    name: Vec<Option<String>>,
+    start_time: Vec<Option<DateTime<Utc>>>,
+    end_time: Vec<Option<DateTime<Utc>>>,
+    completion_start_time: Vec<Option<DateTime<Utc>>>,
+    model: Vec<Option<String>>,
+    model_parameters: Vec<Option<JsonValue>>,
+    input: Vec<Option<JsonValue>>,
+    output: Vec<Option<JsonValue>>,
+    input_offload: Vec<Option<String>>,
+    output_offload: Vec<Option<String>>,
+    usage: Vec<Option<JsonValue>>,
+    level: Vec<Option<String>>,
+    status_message: Vec<Option<String>>,
+    parent_observation_id: Vec<Option<Uuid>>,
+    prompt_id: Vec<Option<String>>,
+    prompt_name: Vec<Option<String>>,
+    prompt_version: Vec<Option<String>>,
+    model_id: Vec<Option<String>>,
+    input_price: Vec<Option<f64>>,
+    output_price: Vec<Option<f64>>,
+    total_price: Vec<Option<f64>>,
+    calculated_input_cost: Vec<Option<f64>>,
+    calculated_output_cost: Vec<Option<f64>>,
+    calculated_total_cost: Vec<Option<f64>>,
+    latency: Vec<Option<f64>>,
+    time_to_first_token: Vec<Option<f64>>,
+    completion_tokens: Vec<Option<i64>>,
+    prompt_tokens: Vec<Option<i64>>,
+    total_tokens: Vec<Option<i64>>,
+    unit: Vec<Option<String>>,
+    metadata: Vec<Option<JsonValue>>,
+    environment: Vec<Option<String>>,
+    project_id: Vec<String>,
+    service_name: Vec<Option<String>>,
+    service_version: Vec<Option<String>>,
+    sdk_language: Vec<Option<String>>,
+    host_name: Vec<Option<String>>,
+    provider: Vec<Option<String>>,
+    resource_id: Vec<Option<Uuid>>,
+    event_time: Vec<Option<DateTime<Utc>>>,
+    output_seq: Vec<Option<i64>>,
+    is_update: Vec<bool>,
+    output_delta: Vec<Option<String>>,
+}
+
+impl ObservationRows {
+    pub(crate) fn push(
+        &mut self,
+        obs: ObservationIngest,
+        project_id: &str,
+        resource_id: Option<Uuid>,
+    ) {
+        self.is_update.push(obs.is_update());
+        self.id.push(obs.id);
+        self.trace_id.push(obs.traceId);
+        self.r#type.push(obs.r#type);
+        self.name.push(obs.name);
+        self.start_time.push(obs.startTime);
+        self.end_time.push(obs.endTime);
+        self.completion_start_time.push(obs.completionStartTime);
+        self.model.push(obs.model);
+        self.model_parameters.push(obs.modelParameters);
+        self.input.push(obs.input);
+        self.output.push(obs.output);
+        self.input_offload.push(obs.input_offload);
+        self.output_offload.push(obs.output_offload);
+        self.usage.push(obs.usage);
+        self.level.push(obs.level);
+        self.status_message.push(obs.statusMessage);
+        self.parent_observation_id.push(obs.parentObservationId);
+        self.prompt_id.push(obs.promptId);
+        self.prompt_name.push(obs.promptName);
+        self.prompt_version.push(obs.promptVersion);
+        self.model_id.push(obs.modelId);
+        self.input_price.push(obs.inputPrice);
+        self.output_price.push(obs.outputPrice);
+        self.total_price.push(obs.totalPrice);
+        self.calculated_input_cost.push(obs.calculatedInputCost);
+        self.calculated_output_cost.push(obs.calculatedOutputCost);
+        self.calculated_total_cost.push(obs.calculatedTotalCost);
+        self.latency.push(obs.latency);
+        self.time_to_first_token.push(obs.timeToFirstToken);
+        self.completion_tokens.push(obs.completionTokens);
+        self.prompt_tokens.push(obs.promptTokens);
+        self.total_tokens.push(obs.totalTokens);
+        self.unit.push(obs.unit);
+        self.metadata.push(obs.metadata);
+        self.environment.push(obs.environment);
+        self.project_id.push(project_id.to_string());
+        self.service_name.push(obs.serviceName);
+        self.service_version.push(obs.serviceVersion);
+        self.sdk_language.push(obs.sdkLanguage);
+        self.host_name.push(obs.hostName);
+        self.provider.push(obs.provider);
+        self.resource_id.push(resource_id);
+        self.event_time.push(obs.eventTime);
+        self.output_seq.push(obs.outputSeq);
+        self.output_delta.push(obs.outputDelta);
+    }
+
+    /// Upserts the rows in one statement under `policy`.
+    pub(crate) async fn upsert(
+        self,
+        conn: &mut PgConnection,
+        policy: ConflictPolicy,
+    ) -> Result<Vec<(Uuid, bool)>, sqlx::Error> {
+        if self.id.is_empty() {
+            return Ok(Vec::new());
+        }
+        sqlx::query_as(observation_upsert(policy))
+            .bind(self.id)
+            .bind(self.trace_id)
+            .bind(self.r#type)
+            .bind(self.name)
+            .bind(self.start_time)
+            .bind(self.end_time)
+            .bind(self.completion_start_time)
+            .bind(self.model)
+            .bind(self.model_parameters)
+            .bind(self.input)
+            .bind(self.output)
+            .bind(self.input_offload)
+            .bind(self.output_offload)
+            .bind(self.usage)
+            .bind(self.level)
+            .bind(self.status_message)
+            .bind(self.parent_observation_id)
+            .bind(self.prompt_id)
+            .bind(self.prompt_name)
+            .bind(self.prompt_version)
+            .bind(self.model_id)
+            .bind(self.input_price)
+            .bind(self.output_price)
+            .bind(self.total_price)
+            .bind(self.calculated_input_cost)
+            .bind(self.calculated_output_cost)
+            .bind(self.calculated_total_cost)
+            .bind(self.latency)
+            .bind(self.time_to_first_token)
+            .bind(self.completion_tokens)
+            .bind(self.prompt_tokens)
+            .bind(self.total_tokens)
+            .bind(self.unit)
+            .bind(self.metadata)
+            .bind(self.environment)
+            .bind(self.project_id)
+            .bind(self.service_name)
+            .bind(self.service_version)
+            .bind(self.sdk_language)
+            .bind(self.host_name)
+            .bind(self.provider)
+            .bind(self.resource_id)
+            .bind(self.event_time)
+            .bind(self.output_seq)
+            .bind(self.is_update)
+            .bind(self.output_delta)
+            .fetch_all(conn)
+            .await
+    }
+}
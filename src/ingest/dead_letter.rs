@@ -0,0 +1,184 @@
+//! Dead letters of the ingest workers.
+//!
+//! A worker writes its queued payloads in one transaction. When that fails,
+//! each payload is retried in a transaction of its own with backoff, so one
+//! bad payload does not take the others down. Payloads that still fail are
+//! kept in `failed_events` with the last error, listed by
+//! `GET /api/admin/failed-events`, and replayed through the ingest queue or
+//! discarded by id.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::PgPool;
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+use crate::{
+    http::{common::ApiResponse, error::ApiError},
+    ingest::batch::BatchIngestRequest,
+    state::AppState,
+};
+
+/// Keeps a payload that could not be written. Returns the id of the row.
+pub(crate) async fn store(
+    pool: &PgPool,
+    project_id: &str,
+    payload: &BatchIngestRequest,
+    error: &str,
+    attempts: u32,
+) -> Result<i64, sqlx::Error> {
+    let trace_ids: BTreeSet<Uuid> = payload
+        .trace
+        .iter()
+        .map(|t| t.id)
+        .chain(payload.observations.iter().map(|o| o.traceId))
+        .collect();
+    let body = serde_json::to_vec(payload).map_err(|err| sqlx::Error::Encode(err.into()))?;
+    sqlx::query_scalar(
+        r#"
+INSERT INTO failed_events (project_id, trace_ids, payload, complete, error, attempts)
+VALUES ($1, $2, $3, $4, $5, $6)
+RETURNING id
+        "#,
+    )
+    .bind(project_id)
+    .bind(trace_ids.into_iter().collect::<Vec<_>>())
+    .bind(body)
+    .bind(&payload.complete)
+    .bind(error)
+    .bind(attempts as i32)
+    .fetch_one(pool)
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FailedEventListQuery {
+    #[serde(default)]
+    limit: Option<i64>,
+    #[serde(default, rename = "projectId")]
+    project_id: Option<String>,
+    #[serde(default, rename = "traceId")]
+    trace_id: Option<Uuid>,
+}
+
+#[derive(sqlx::FromRow)]
+struct FailedEventRow {
+    id: i64,
+    project_id: String,
+    trace_ids: Vec<Uuid>,
+    payload: Vec<u8>,
+    error: String,
+    attempts: i32,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FailedEventDto {
+    id: i64,
+    project_id: String,
+    trace_ids: Vec<Uuid>,
+    payload: JsonValue,
+    error: String,
+    attempts: i32,
+    created_at: DateTime<Utc>,
+}
+
+impl From<FailedEventRow> for FailedEventDto {
+    fn from(row: FailedEventRow) -> Self {
+        Self {
+            id: row.id,
+            project_id: row.project_id,
+            trace_ids: row.trace_ids,
+            payload: serde_json::from_slice(&row.payload).unwrap_or_else(|_| {
+                JsonValue::String(String::from_utf8_lossy(&row.payload).into())
+            }),
+            error: row.error,
+            attempts: row.attempts,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Most recent failed payloads first.
+pub(crate) async fn get_failed_events(
+    State(state): State<AppState>,
+    Query(q): Query<FailedEventListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let rows: Vec<FailedEventRow> = sqlx::query_as(
+        r#"
+SELECT id, project_id, trace_ids, payload, error, attempts, created_at
+FROM failed_events
+WHERE ($1::TEXT IS NULL OR project_id = $1)
+  AND ($2::UUID IS NULL OR trace_ids @> ARRAY[$2]::uuid[])
+ORDER BY created_at DESC, id DESC
+LIMIT $3
+        "#,
+    )
+    .bind(q.project_id)
+    .bind(q.trace_id)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let data: Vec<FailedEventDto> = rows.into_iter().map(Into::into).collect();
+    Ok((StatusCode::OK, Json(serde_json::json!({ "data": data }))))
+}
+
+/// Queues a failed payload for the ingest workers again and removes it. A
+/// payload that fails again is stored under a new id.
+pub(crate) async fn replay_failed_event(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut tx = state.pool.begin().await?;
+    let row: Option<(Vec<u8>, Vec<Uuid>)> =
+        sqlx::query_as("DELETE FROM failed_events WHERE id = $1 RETURNING payload, complete")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let (body, complete) = row.ok_or(ApiError::NotFound)?;
+    let mut payload: BatchIngestRequest = serde_json::from_slice(&body)
+        .map_err(|err| ApiError::Conflict(format!("stored payload cannot be decoded: {err}")))?;
+    payload.complete = complete;
+    state.enqueue_ingest(vec![payload]).await?;
+    tx.commit().await?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse::<JsonValue> {
+            message: "Request Successful.".to_string(),
+            code: None,
+            data: None,
+        }),
+    ))
+}
+
+pub(crate) async fn delete_failed_event(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let deleted = sqlx::query("DELETE FROM failed_events WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse::<JsonValue> {
+            message: "Request Successful.".to_string(),
+            code: None,
+            data: None,
+        }),
+    ))
+}
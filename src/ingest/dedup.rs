@@ -0,0 +1,106 @@
+//! Idempotent ingest: a payload carrying event ids (`eventId` or the
+//! `Idempotency-Key` header on `/v1/l/batch`, event ids on
+//! `/api/public/ingestion`) records them in `ingest_events` in its write
+//! transaction. A payload whose event ids were all recorded within
+//! `XTRACE_INGEST_DEDUP_TTL_SECS` is skipped, so an SDK retrying after a
+//! timeout does not apply older data over newer updates.
+//!
+//! Ids are scoped to the payload's project. Concurrent retries serialize on
+//! the primary key: the second waits for the first to commit and is skipped.
+
+use std::{collections::HashSet, time::Duration};
+
+use serde::{Deserialize, Deserializer};
+use sqlx::{PgConnection, PgPool};
+
+/// How often expired event ids are deleted.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Accepts one event id or a list of them.
+pub(crate) fn one_or_many<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Ids {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Option::<Ids>::deserialize(d)? {
+        Some(Ids::One(id)) => vec![id],
+        Some(Ids::Many(ids)) => ids,
+        None => Vec::new(),
+    })
+}
+
+/// Records `event_ids` for `project_id`. Returns how many were not recorded
+/// within `ttl` before, i.e. are new.
+pub(crate) async fn claim(
+    conn: &mut PgConnection,
+    project_id: &str,
+    event_ids: &[String],
+    ttl: Duration,
+) -> Result<usize, sqlx::Error> {
+    let claimed: Vec<String> = sqlx::query_scalar(
+        r#"
+INSERT INTO ingest_events (project_id, event_id)
+SELECT $1, e FROM (SELECT DISTINCT UNNEST($2::TEXT[]) AS e) AS ids
+ON CONFLICT (project_id, event_id) DO UPDATE SET created_at = NOW()
+  WHERE ingest_events.created_at < NOW() - make_interval(secs => $3)
+RETURNING event_id
+        "#,
+    )
+    .bind(project_id)
+    .bind(event_ids)
+    .bind(ttl.as_secs_f64())
+    .fetch_all(conn)
+    .await?;
+    Ok(claimed.len())
+}
+
+/// The `(project_id, event_id)` pairs of `keys` recorded within `ttl`.
+pub(crate) async fn seen(
+    pool: &PgPool,
+    keys: &[(String, String)],
+    ttl: Duration,
+) -> Result<HashSet<(String, String)>, sqlx::Error> {
+    if keys.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let (projects, ids): (Vec<&str>, Vec<&str>) =
+        keys.iter().map(|(p, e)| (p.as_str(), e.as_str())).unzip();
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        r#"
+SELECT e.project_id, e.event_id
+FROM ingest_events e
+JOIN UNNEST($1::TEXT[], $2::TEXT[]) AS k(project_id, event_id)
+  ON e.project_id = k.project_id AND e.event_id = k.event_id
+WHERE e.created_at >= NOW() - make_interval(secs => $3)
+        "#,
+    )
+    .bind(&projects)
+    .bind(&ids)
+    .bind(ttl.as_secs_f64())
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Deletes expired event ids every minute.
+pub(crate) async fn dedup_purge_worker(pool: PgPool, ttl: Duration) {
+    let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let result = sqlx::query(
+            "DELETE FROM ingest_events WHERE created_at < NOW() - make_interval(secs => $1)",
+        )
+        .bind(ttl.as_secs_f64())
+        .execute(&pool)
+        .await;
+        match result {
+            Ok(r) if r.rows_affected() > 0 => {
+                tracing::debug!(deleted = r.rows_affected(), "dedup: purged event ids")
+            }
+            Ok(_) => {}
+            Err(err) => tracing::error!(error = %err, "dedup: purge failed"),
+        }
+    }
+}
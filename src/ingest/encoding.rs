@@ -0,0 +1,103 @@
+//! Compressed request bodies of the ingest endpoints. `Content-Encoding`
+//! may be `gzip`, `zstd`, `br` or `identity`, or a list of them in the
+//! order they were applied.
+
+use std::io::Read;
+
+use axum::http::{header, HeaderMap};
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+
+use crate::http::error::ApiError;
+
+/// `body` with its `Content-Encoding` undone; `400` for encodings that are
+/// not supported or do not decode.
+pub(crate) fn decode_body(headers: &HeaderMap, mut body: Bytes) -> Result<Bytes, ApiError> {
+    let Some(value) = headers.get(header::CONTENT_ENCODING) else {
+        return Ok(body);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| ApiError::BadRequest("invalid content-encoding".to_string()))?;
+    for coding in value.rsplit(',').map(|c| c.trim().to_ascii_lowercase()) {
+        body = match coding.as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => read_all(GzDecoder::new(body.as_ref()), "gzip")?,
+            "zstd" => {
+                let decoder = zstd::Decoder::new(body.as_ref())
+                    .map_err(|e| ApiError::BadRequest(format!("zstd decode failed: {e}")))?;
+                read_all(decoder, "zstd")?
+            }
+            "br" => read_all(
+                brotli_decompressor::Decompressor::new(body.as_ref(), 4096),
+                "brotli",
+            )?,
+            other => {
+                return Err(ApiError::BadRequest(format!(
+                    "unsupported content-encoding: {other}"
+                )))
+            }
+        };
+    }
+    Ok(body)
+}
+
+fn read_all(mut decoder: impl Read, name: &str) -> Result<Bytes, ApiError> {
+    let mut out = Vec::new();
+    decoder
+        .read_to_end(&mut out)
+        .map_err(|e| ApiError::BadRequest(format!("{name} decode failed: {e}")))?;
+    Ok(out.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    const BODY: &[u8] = br#"{"trace":{"id":"x"}}"#;
+
+    fn decode(encoding: &str, body: Vec<u8>) -> Result<Bytes, ApiError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_str(encoding).unwrap(),
+        );
+        decode_body(&headers, body.into())
+    }
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decodes_each_encoding() {
+        // One uncompressed meta-block of 20 bytes, then an empty last one.
+        let brotli = [&[0x30, 0x01, 0x10][..], BODY, &[0x03]].concat();
+        for (encoding, body) in [
+            ("identity", BODY.to_vec()),
+            ("gzip", gzip(BODY)),
+            ("zstd", zstd::encode_all(BODY, 3).unwrap()),
+            ("br", brotli),
+        ] {
+            assert_eq!(decode(encoding, body).unwrap(), BODY, "{encoding}");
+        }
+    }
+
+    #[test]
+    fn undoes_stacked_encodings_in_reverse() {
+        let body = zstd::encode_all(gzip(BODY).as_slice(), 3).unwrap();
+        assert_eq!(decode("gzip, zstd", body).unwrap(), BODY);
+    }
+
+    #[test]
+    fn rejects_unknown_and_corrupt_bodies() {
+        assert!(decode("compress", BODY.to_vec()).is_err());
+        assert!(decode("zstd", BODY.to_vec()).is_err());
+    }
+}
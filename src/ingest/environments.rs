@@ -0,0 +1,34 @@
+//! The `environments` table: environments seen per project with the first
+//! and last trace timestamp, widened by every ingest transaction for the
+//! traces it wrote.
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Records the environments of `trace_ids`. Rows only change when a trace
+/// falls outside their range, and are locked in key order, so concurrent
+/// workers do not deadlock.
+pub(crate) async fn record(conn: &mut PgConnection, trace_ids: &[Uuid]) -> Result<(), sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+INSERT INTO environments (project_id, name, first_seen, last_seen)
+SELECT project_id, environment, MIN(timestamp), MAX(timestamp)
+FROM traces
+WHERE id = ANY($1)
+GROUP BY project_id, environment
+ORDER BY project_id, environment
+ON CONFLICT (project_id, name) DO UPDATE
+SET first_seen = LEAST(environments.first_seen, EXCLUDED.first_seen),
+    last_seen = GREATEST(environments.last_seen, EXCLUDED.last_seen)
+WHERE environments.first_seen > EXCLUDED.first_seen
+   OR environments.last_seen < EXCLUDED.last_seen
+        "#,
+    )
+    .bind(trace_ids)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Kafka (or Redpanda) ingest source, built with the `kafka` feature.
+//!
+//! With `XTRACE_KAFKA_BROKERS`, a consumer in group `XTRACE_KAFKA_GROUP_ID`
+//! reads `POST /v1/l/batch` payloads, one JSON object per message, from
+//! `XTRACE_KAFKA_TOPIC` and queues them for the ingest workers as the
+//! instance-wide key would: payloads without a `projectId` go to the default
+//! project.
+//!
+//! A message's offset is committed once its payload is queued (and logged, with
+//! the write-ahead log), so a restart reads the messages that were not queued yet
+//! again. While the queue is full or writes are paused, the consumer waits
+//! rather than dropping messages, leaving the backlog in the topic. Messages
+//! that are not valid payloads are logged and skipped.
+
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use rdkafka::{
+    consumer::{Consumer, StreamConsumer},
+    error::KafkaError,
+    message::BorrowedMessage,
+    ClientConfig, Message,
+};
+
+use crate::{
+    http::error::ApiError,
+    ingest::{
+        batch::{absorb_unknown_fields, BatchIngestRequest},
+        raw::RawSource,
+    },
+    state::AppState,
+};
+
+/// Wait before queueing a payload again.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub(crate) struct KafkaSettings {
+    /// Bootstrap servers, comma separated.
+    pub(crate) brokers: String,
+    pub(crate) topic: String,
+    pub(crate) group_id: String,
+}
+
+/// Connects a consumer subscribed to the ingest topic. Offsets are stored
+/// explicitly, after a payload is queued, and committed in the background.
+pub(crate) fn consumer(settings: &KafkaSettings) -> Result<StreamConsumer, KafkaError> {
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &settings.brokers)
+        .set("group.id", &settings.group_id)
+        .set("enable.auto.commit", "true")
+        .set("enable.auto.offset.store", "false")
+        .set("auto.offset.reset", "earliest")
+        .create()?;
+    consumer.subscribe(&[&settings.topic])?;
+    Ok(consumer)
+}
+
+pub(crate) async fn kafka_ingest_worker(state: AppState, consumer: StreamConsumer) {
+    let headers = HeaderMap::new();
+    loop {
+        let message = match consumer.recv().await {
+            Ok(message) => message,
+            Err(err) => {
+                tracing::error!(error = %err, "kafka: receive failed");
+                tokio::time::sleep(RETRY_BACKOFF).await;
+                continue;
+            }
+        };
+        let body = message.payload().unwrap_or_default();
+        let parsed = serde_json::from_slice::<BatchIngestRequest>(body)
+            .map_err(|err| err.to_string())
+            .and_then(|payload| payload.validate().map(|()| payload));
+        match parsed {
+            Ok(mut payload) => {
+                absorb_unknown_fields(&mut payload);
+                enqueue(&state, &headers, body, payload).await;
+            }
+            Err(err) => tracing::warn!(
+                error = %err,
+                partition = message.partition(),
+                offset = message.offset(),
+                "kafka: skipping invalid payload"
+            ),
+        }
+        store_offset(&consumer, &message);
+    }
+}
+
+/// Queues `payload`, waiting for queue space or for writes to resume.
+async fn enqueue(state: &AppState, headers: &HeaderMap, body: &[u8], payload: BatchIngestRequest) {
+    loop {
+        let source = RawSource {
+            endpoint: "kafka",
+            project_id: &state.default_project_id,
+            headers,
+            body,
+        };
+        match state
+            .enqueue_ingest_from(source, vec![payload.clone()])
+            .await
+        {
+            Ok(()) => return,
+            Err(ApiError::QueueFull(_) | ApiError::WritesPaused { .. }) => {}
+            Err(err) => tracing::error!(error = %err, "kafka: failed to queue payload"),
+        }
+        tokio::time::sleep(RETRY_BACKOFF).await;
+    }
+}
+
+fn store_offset(consumer: &StreamConsumer, message: &BorrowedMessage<'_>) {
+    if let Err(err) = consumer.store_offset_from_message(message) {
+        tracing::error!(
+            error = %err,
+            partition = message.partition(),
+            offset = message.offset(),
+            "kafka: failed to store offset"
+        );
+    }
+}
@@ -0,0 +1,571 @@
+//! Native Langfuse ingestion protocol (`POST /api/public/ingestion`).
+//!
+//! The Langfuse SDKs send a `batch` of typed events (`trace-create`,
+//! `span-create` / `span-update`, `generation-create` / `generation-update`,
+//! `event-create`, `score-create`, `sdk-log`). Trace and observation events
+//! are mapped onto [`BatchIngestRequest`]s and queued like `/v1/l/batch`;
+//! scores are written directly. The response is Langfuse's `207 Multi-Status`
+//! shape with one entry per event in `successes` or `errors`.
+//!
+//! Events for the same trace or observation within one batch are merged in
+//! order (later non-null fields win), so the SDK's create-then-update flow
+//! lands as a single row; across batches, the event timestamps order the
+//! updates (see [`conflicts`](crate::ingest::conflicts)). Observations with
+//! only update events are written as updates, whatever the conflict policy. Event ids double as idempotency keys: events
+//! already written are acknowledged without being applied again (see
+//! [`dedup`]). Langfuse allows arbitrary string ids; ids that are
+//! not UUIDs are mapped to a deterministic UUID v5 and the original is kept in
+//! `metadata.langfuse_id`.
+
+use std::collections::{BTreeMap, HashSet};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use uuid::Uuid;
+
+use crate::{
+    http::{
+        auth::ProjectScope,
+        error::ApiError,
+        ingest_limit::IngestCost,
+        scores::{insert_score, ScoreCreateRequest},
+    },
+    ingest::{
+        batch::{absorb_unknown_fields, BatchIngestRequest, ObservationIngest, TraceIngest},
+        dedup,
+        encoding::decode_body,
+        ownership,
+        raw::RawSource,
+    },
+    state::AppState,
+};
+
+/// Namespace for UUIDs derived from non-UUID Langfuse ids.
+const LANGFUSE_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6c61_6e67_6675_7365_8000_0000_0000_0001);
+
+const ID_FIELDS: &[&str] = &["id", "traceId", "parentObservationId", "observationId"];
+
+/// Usage keys accepted for each token count (Langfuse, OpenAI and Anthropic styles).
+const INPUT_TOKEN_KEYS: &[&str] = &["input", "promptTokens", "prompt_tokens", "input_tokens"];
+const OUTPUT_TOKEN_KEYS: &[&str] = &[
+    "output",
+    "completionTokens",
+    "completion_tokens",
+    "output_tokens",
+];
+const TOTAL_TOKEN_KEYS: &[&str] = &["total", "totalTokens", "total_tokens"];
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct IngestionRequest {
+    #[serde(default)]
+    batch: Vec<JsonValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IngestionEvent {
+    id: String,
+    #[serde(rename = "type")]
+    r#type: String,
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    body: JsonValue,
+}
+
+#[derive(Debug, Serialize)]
+struct EventSuccess {
+    id: String,
+    status: u16,
+}
+
+#[derive(Debug, Serialize)]
+struct EventError {
+    id: String,
+    status: u16,
+    message: String,
+    error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct IngestionResponse {
+    successes: Vec<EventSuccess>,
+    errors: Vec<EventError>,
+}
+
+impl IngestionResponse {
+    fn ok(&mut self, id: impl Into<String>) {
+        self.successes.push(EventSuccess {
+            id: id.into(),
+            status: 201,
+        });
+    }
+
+    fn fail(&mut self, id: impl Into<String>, message: impl Into<String>) {
+        self.fail_with(id, 400, message);
+    }
+
+    fn fail_all(&mut self, ids: Vec<String>, status: u16, message: impl Into<String>) {
+        let message = message.into();
+        for id in ids {
+            self.fail_with(id, status, message.clone());
+        }
+    }
+
+    fn fail_with(&mut self, id: impl Into<String>, status: u16, message: impl Into<String>) {
+        let message = message.into();
+        self.errors.push(EventError {
+            id: id.into(),
+            status,
+            error: message.clone(),
+            message,
+        });
+    }
+}
+
+/// Merged body of one trace or observation and the events it came from.
+#[derive(Default)]
+struct Entity {
+    body: JsonMap<String, JsonValue>,
+    event_ids: Vec<String>,
+    /// Timestamp of the first event, used when the body has none.
+    first_seen: Option<DateTime<Utc>>,
+    /// Timestamp of the latest event, the entity's event time.
+    last_seen: Option<DateTime<Utc>>,
+    /// Whether a create event was merged, not only updates.
+    created: bool,
+}
+
+impl Entity {
+    fn absorb(
+        &mut self,
+        event_id: String,
+        timestamp: Option<DateTime<Utc>>,
+        body: JsonMap<String, JsonValue>,
+    ) {
+        for (k, v) in body {
+            if !v.is_null() {
+                self.body.insert(k, v);
+            }
+        }
+        self.event_ids.push(event_id);
+        self.first_seen = self.first_seen.or(timestamp);
+        self.last_seen = self.last_seen.max(timestamp);
+    }
+
+    fn project_id(&self) -> Option<&str> {
+        self.body.get("projectId")?.as_str()
+    }
+
+    fn trace_id(&self) -> Option<Uuid> {
+        self.body.get("traceId")?.as_str()?.parse().ok()
+    }
+}
+
+fn langfuse_uuid(id: &str) -> Uuid {
+    Uuid::parse_str(id).unwrap_or_else(|_| Uuid::new_v5(&LANGFUSE_ID_NAMESPACE, id.as_bytes()))
+}
+
+/// Rewrites non-UUID ids to their derived UUID, recording the original `id`.
+fn normalize_ids(body: &mut JsonMap<String, JsonValue>) {
+    for field in ID_FIELDS {
+        let Some(JsonValue::String(raw)) = body.get(*field) else {
+            continue;
+        };
+        if Uuid::parse_str(raw).is_ok() {
+            continue;
+        }
+        let raw = raw.clone();
+        body.insert(
+            field.to_string(),
+            JsonValue::String(langfuse_uuid(&raw).to_string()),
+        );
+        if *field == "id" {
+            let meta = body
+                .entry("metadata")
+                .or_insert_with(|| JsonValue::Object(JsonMap::new()));
+            if let Some(meta) = meta.as_object_mut() {
+                meta.insert("langfuse_id".to_string(), JsonValue::String(raw));
+            }
+        }
+    }
+}
+
+fn entity_id(body: &JsonMap<String, JsonValue>) -> Option<Uuid> {
+    body.get("id")
+        .and_then(JsonValue::as_str)
+        .and_then(|s| Uuid::parse_str(s).ok())
+}
+
+fn observation_type(event_type: &str) -> Option<&'static str> {
+    match event_type {
+        "span-create" | "span-update" => Some("SPAN"),
+        "generation-create" | "generation-update" => Some("GENERATION"),
+        "event-create" => Some("EVENT"),
+        _ => None,
+    }
+}
+
+fn number(v: Option<&JsonValue>) -> Option<f64> {
+    v.and_then(JsonValue::as_f64)
+}
+
+fn first_number(obj: &JsonMap<String, JsonValue>, keys: &[&str]) -> Option<f64> {
+    keys.iter().find_map(|k| number(obj.get(*k)))
+}
+
+/// Maps Langfuse usage / cost fields onto the ingest token and cost columns.
+fn map_usage(body: &mut JsonMap<String, JsonValue>) {
+    let usage_details = body.remove("usageDetails");
+    let cost_details = body.remove("costDetails");
+    if !body.contains_key("usage") {
+        if let Some(details) = usage_details {
+            body.insert("usage".to_string(), details);
+        }
+    }
+
+    let usage = body.get("usage").and_then(JsonValue::as_object).cloned();
+    if let Some(usage) = &usage {
+        let tokens = [
+            ("promptTokens", INPUT_TOKEN_KEYS),
+            ("completionTokens", OUTPUT_TOKEN_KEYS),
+            ("totalTokens", TOTAL_TOKEN_KEYS),
+        ];
+        for (field, keys) in tokens {
+            if let Some(v) = first_number(usage, keys) {
+                body.entry(field).or_insert(JsonValue::from(v as i64));
+            }
+        }
+    }
+
+    let cost = cost_details.as_ref().and_then(JsonValue::as_object);
+    let costs = [
+        ("calculatedInputCost", "input", "inputCost"),
+        ("calculatedOutputCost", "output", "outputCost"),
+        ("calculatedTotalCost", "total", "totalCost"),
+    ];
+    for (field, detail_key, usage_key) in costs {
+        let v = cost
+            .and_then(|c| number(c.get(detail_key)))
+            .or_else(|| usage.as_ref().and_then(|u| number(u.get(usage_key))));
+        if let Some(v) = v {
+            body.entry(field).or_insert(JsonValue::from(v));
+        }
+    }
+}
+
+/// Fields the ingest structs have no column for are kept in metadata.
+fn stash_unmapped(body: &mut JsonMap<String, JsonValue>, keys: &[&str]) {
+    for key in keys {
+        let Some(v) = body.remove(*key) else {
+            continue;
+        };
+        let meta = body
+            .entry("metadata")
+            .or_insert_with(|| JsonValue::Object(JsonMap::new()));
+        if let Some(meta) = meta.as_object_mut() {
+            meta.insert(format!("langfuse_{key}"), v);
+        }
+    }
+}
+
+pub(crate) async fn post_ingestion(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let body = decode_body(&headers, body)?;
+    let req: IngestionRequest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("invalid json: {e}")))?;
+
+    let mut resp = IngestionResponse::default();
+    let mut traces: BTreeMap<Uuid, Entity> = BTreeMap::new();
+    let mut observations: BTreeMap<Uuid, Entity> = BTreeMap::new();
+    let mut scores: Vec<(String, JsonMap<String, JsonValue>)> = Vec::new();
+
+    // Events already written (SDK retries) are acknowledged and skipped.
+    let event_key = |raw: &JsonValue| {
+        let id = raw.get("id").and_then(JsonValue::as_str)?;
+        let project_id = raw
+            .pointer("/body/projectId")
+            .and_then(JsonValue::as_str)
+            .unwrap_or(scope.project_id());
+        Some((project_id.to_string(), id.to_string()))
+    };
+    let seen = match state.dedup_ttl {
+        Some(ttl) => {
+            let keys: Vec<(String, String)> = req.batch.iter().filter_map(event_key).collect();
+            dedup::seen(&state.pool, &keys, ttl).await?
+        }
+        None => HashSet::new(),
+    };
+
+    for raw in req.batch {
+        if event_key(&raw).is_some_and(|k| seen.contains(&k)) {
+            let id = raw["id"].as_str().unwrap_or_default().to_string();
+            state.telemetry.record_deduplicated(1);
+            resp.ok(id);
+            continue;
+        }
+        let raw_id = raw
+            .get("id")
+            .and_then(JsonValue::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let event: IngestionEvent = match serde_json::from_value(raw) {
+            Ok(e) => e,
+            Err(e) => {
+                resp.fail(raw_id, format!("invalid event: {e}"));
+                continue;
+            }
+        };
+        let JsonValue::Object(mut body) = event.body else {
+            if event.r#type == "sdk-log" {
+                resp.ok(event.id);
+            } else {
+                resp.fail(event.id, "event body must be an object");
+            }
+            continue;
+        };
+
+        match event.r#type.as_str() {
+            "trace-create" => {
+                normalize_ids(&mut body);
+                match entity_id(&body) {
+                    Some(id) => {
+                        traces
+                            .entry(id)
+                            .or_default()
+                            .absorb(event.id, event.timestamp, body)
+                    }
+                    None => resp.fail(event.id, "trace body requires an id"),
+                }
+            }
+            t @ ("span-create" | "span-update" | "generation-create" | "generation-update"
+            | "event-create" | "observation-create" | "observation-update") => {
+                normalize_ids(&mut body);
+                if let Some(ty) = observation_type(t) {
+                    body.insert("type".to_string(), JsonValue::String(ty.to_string()));
+                }
+                match entity_id(&body) {
+                    Some(id) => {
+                        let entity = observations.entry(id).or_default();
+                        entity.created |= t.ends_with("-create");
+                        entity.absorb(event.id, event.timestamp, body)
+                    }
+                    None => resp.fail(event.id, "observation body requires an id"),
+                }
+            }
+            "score-create" => {
+                normalize_ids(&mut body);
+                scores.push((event.id, body));
+            }
+            "sdk-log" => {
+                tracing::debug!(log = %JsonValue::Object(body), "langfuse sdk-log");
+                resp.ok(event.id);
+            }
+            other => resp.fail(event.id, format!("unsupported event type: {other}")),
+        }
+    }
+
+    // Updates may come without `traceId`; take it from the stored observation.
+    let missing_trace: Vec<Uuid> = observations
+        .iter()
+        .filter(|(_, e)| !e.body.contains_key("traceId"))
+        .map(|(id, _)| *id)
+        .collect();
+    if !missing_trace.is_empty() {
+        let rows: Vec<(Uuid, Uuid)> =
+            sqlx::query_as("SELECT id, trace_id FROM observations WHERE id = ANY($1)")
+                .bind(&missing_trace)
+                .fetch_all(&state.pool)
+                .await?;
+        for (id, trace_id) in rows {
+            if let Some(e) = observations.get_mut(&id) {
+                e.body.insert(
+                    "traceId".to_string(),
+                    JsonValue::String(trace_id.to_string()),
+                );
+            }
+        }
+    }
+
+    // Ids stored under another project are not written through this one.
+    let mut trace_keys: Vec<(Uuid, &str)> = traces
+        .iter()
+        .map(|(id, e)| (*id, e.project_id().unwrap_or(scope.project_id())))
+        .collect();
+    let mut observation_keys: Vec<(Uuid, &str)> = Vec::new();
+    for (id, e) in &observations {
+        let project_id = e.project_id().unwrap_or(scope.project_id());
+        trace_keys.extend(e.trace_id().map(|trace_id| (trace_id, project_id)));
+        observation_keys.push((*id, project_id));
+    }
+    let foreign = ownership::foreign(&state.pool, &trace_keys, &observation_keys).await?;
+
+    let mut per_trace: BTreeMap<Uuid, BatchIngestRequest> = BTreeMap::new();
+    let mut accepted: Vec<String> = Vec::new();
+    let new_request = || BatchIngestRequest {
+        trace: None,
+        observations: Vec::new(),
+        event_ids: Vec::new(),
+        complete: Vec::new(),
+        wal_seq: None,
+        accepted_at: None,
+        extra: Default::default(),
+    };
+
+    for (id, mut entity) in traces {
+        if foreign.traces.contains(&id) {
+            resp.fail_all(entity.event_ids, 403, "trace belongs to another project");
+            continue;
+        }
+        entity.body.entry("timestamp").or_insert_with(|| {
+            JsonValue::String(entity.first_seen.unwrap_or_else(Utc::now).to_rfc3339())
+        });
+        match serde_json::from_value::<TraceIngest>(JsonValue::Object(entity.body)) {
+            Ok(mut trace) => {
+                if let Err(ApiError::Forbidden(msg)) = scope.claim(&mut trace.projectId) {
+                    resp.fail_all(entity.event_ids, 403, msg);
+                    continue;
+                }
+                trace.eventTime = trace.eventTime.or(entity.last_seen);
+                let payload = per_trace.entry(id).or_insert_with(new_request);
+                payload.trace = Some(trace);
+                payload.event_ids.extend(entity.event_ids.iter().cloned());
+                accepted.extend(entity.event_ids);
+            }
+            Err(e) => {
+                for event_id in entity.event_ids {
+                    resp.fail(event_id, format!("invalid trace body: {e}"));
+                }
+            }
+        }
+    }
+
+    let keys: Vec<(Uuid, &str)> = observations
+        .iter()
+        .filter(|(_, e)| e.created)
+        .map(|(id, e)| {
+            let project_id = e.body.get("projectId").and_then(JsonValue::as_str);
+            (*id, project_id.unwrap_or(scope.project_id()))
+        })
+        .collect();
+    let rejected = state.conflict_policies.rejected(&state.pool, &keys).await?;
+
+    for (id, mut entity) in observations {
+        if rejected.contains(&id) {
+            resp.fail_all(
+                entity.event_ids,
+                409,
+                format!("observation {id} already exists"),
+            );
+            continue;
+        }
+        if !entity.body.contains_key("traceId") {
+            for event_id in entity.event_ids {
+                resp.fail(event_id, "observation body requires a traceId");
+            }
+            continue;
+        }
+        // An update alone must not move the start of an observation that
+        // was created earlier.
+        if let Some(ts) = entity.first_seen.filter(|_| entity.created) {
+            entity
+                .body
+                .entry("startTime")
+                .or_insert_with(|| JsonValue::String(ts.to_rfc3339()));
+        }
+        map_usage(&mut entity.body);
+        // Langfuse sends prompt versions as integers.
+        if let Some(v) = entity
+            .body
+            .get_mut("promptVersion")
+            .filter(|v| v.is_number())
+        {
+            *v = JsonValue::String(v.to_string());
+        }
+        stash_unmapped(&mut entity.body, &["version"]);
+        match serde_json::from_value::<ObservationIngest>(JsonValue::Object(entity.body)) {
+            Ok(mut obs) => {
+                if let Err(ApiError::Forbidden(msg)) = scope.claim(&mut obs.projectId) {
+                    resp.fail_all(entity.event_ids, 403, msg);
+                    continue;
+                }
+                if foreign.observations.contains(&id) || foreign.traces.contains(&obs.traceId) {
+                    resp.fail_all(
+                        entity.event_ids,
+                        403,
+                        "observation or its trace belongs to another project",
+                    );
+                    continue;
+                }
+                obs.eventTime = obs.eventTime.or(entity.last_seen);
+                if !entity.created {
+                    obs.update = Some(true);
+                }
+                let payload = per_trace.entry(obs.traceId).or_insert_with(new_request);
+                payload.observations.push(obs);
+                payload.event_ids.extend(entity.event_ids.iter().cloned());
+                accepted.extend(entity.event_ids);
+            }
+            Err(e) => {
+                for event_id in entity.event_ids {
+                    resp.fail(event_id, format!("invalid observation body: {e}"));
+                }
+            }
+        }
+    }
+
+    let mut payloads: Vec<BatchIngestRequest> = per_trace.into_values().collect();
+    for payload in &mut payloads {
+        absorb_unknown_fields(payload);
+    }
+    state.charge_ingest(
+        &headers,
+        IngestCost {
+            bytes: body.len(),
+            observations: payloads.iter().map(|p| p.observations.len()).sum(),
+        },
+    )?;
+    state.check_quota(&payloads)?;
+    if !payloads.is_empty() {
+        state
+            .enqueue_ingest_from(
+                RawSource {
+                    endpoint: "/api/public/ingestion",
+                    project_id: scope.project_id(),
+                    headers: &headers,
+                    body: &body,
+                },
+                payloads,
+            )
+            .await?;
+    }
+    for event_id in accepted {
+        resp.ok(event_id);
+    }
+
+    for (event_id, body) in scores {
+        let result = match serde_json::from_value::<ScoreCreateRequest>(JsonValue::Object(body)) {
+            Ok(req) => insert_score(&state, &scope, req).await.map(|_| ()),
+            Err(e) => Err(ApiError::BadRequest(format!("invalid score body: {e}"))),
+        };
+        match result {
+            Ok(()) => resp.ok(event_id),
+            Err(ApiError::BadRequest(msg)) => resp.fail(event_id, msg),
+            Err(ApiError::Forbidden(msg)) => resp.fail_with(event_id, 403, msg),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok((StatusCode::MULTI_STATUS, Json(resp)))
+}
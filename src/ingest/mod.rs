@@ -1,2 +1,23 @@
+pub(crate) mod activity;
 pub(crate) mod batch;
+pub(crate) mod completion;
+pub(crate) mod conflicts;
+pub(crate) mod dead_letter;
+pub(crate) mod dedup;
+pub(crate) mod encoding;
+pub(crate) mod environments;
+#[cfg(feature = "kafka")]
+pub(crate) mod kafka;
+pub(crate) mod langfuse;
+pub(crate) mod models;
 pub(crate) mod otlp;
+pub(crate) mod otlp_grpc;
+pub(crate) mod ownership;
+pub(crate) mod pricing;
+pub(crate) mod providers;
+pub(crate) mod raw;
+pub(crate) mod resources;
+pub(crate) mod sampling;
+pub(crate) mod secrets;
+pub(crate) mod sessions;
+pub(crate) mod wal;
@@ -0,0 +1,87 @@
+//! Ingest-time model name normalization.
+//!
+//! Providers report the same model under many strings (`gpt-4o-2024-08-06`,
+//! `openai/gpt-4o`, ...). An alias map rewrites them to one canonical name so
+//! cost and usage groupings are not fragmented; the raw name is kept in the
+//! observation metadata under [`RAW_MODEL_METADATA_KEY`].
+
+use std::collections::HashMap;
+
+use serde_json::Value as JsonValue;
+
+pub(crate) const RAW_MODEL_METADATA_KEY: &str = "xtrace_raw_model";
+
+/// Case-insensitive alias map. Entries are exact names, or prefixes when the
+/// alias ends with `*`; exact entries win, then the longest prefix.
+#[derive(Debug, Default)]
+pub(crate) struct ModelAliases {
+    exact: HashMap<String, String>,
+    prefixes: Vec<(String, String)>,
+}
+
+impl ModelAliases {
+    /// Parses `alias=canonical` pairs separated by commas or newlines, e.g.
+    /// `openai/gpt-4o=gpt-4o,gpt-4o-2024-*=gpt-4o`.
+    pub(crate) fn parse(spec: &str) -> Result<Self, String> {
+        let mut aliases = Self::default();
+        for entry in spec.split([',', '\n']).map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let (alias, canonical) = entry
+                .split_once('=')
+                .map(|(a, c)| (a.trim(), c.trim()))
+                .filter(|(a, c)| !a.is_empty() && !c.is_empty())
+                .ok_or_else(|| format!("invalid model alias entry: {entry:?}"))?;
+            let alias = alias.to_ascii_lowercase();
+            match alias.strip_suffix('*') {
+                Some(prefix) => aliases
+                    .prefixes
+                    .push((prefix.to_string(), canonical.to_string())),
+                None => {
+                    aliases.exact.insert(alias, canonical.to_string());
+                }
+            }
+        }
+        aliases
+            .prefixes
+            .sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Ok(aliases)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.prefixes.is_empty()
+    }
+
+    /// Canonical name for `model`, or `None` when no alias applies.
+    pub(crate) fn canonicalize(&self, model: &str) -> Option<&str> {
+        let key = model.trim().to_ascii_lowercase();
+        let canonical = self.exact.get(&key).map(String::as_str).or_else(|| {
+            self.prefixes
+                .iter()
+                .find(|(prefix, _)| key.starts_with(prefix.as_str()))
+                .map(|(_, c)| c.as_str())
+        })?;
+        (canonical != model).then_some(canonical)
+    }
+
+    /// Rewrites `model` in place, recording the raw name in `metadata`.
+    pub(crate) fn apply(&self, model: &mut Option<String>, metadata: &mut Option<JsonValue>) {
+        let Some(raw) = model.as_deref() else {
+            return;
+        };
+        let Some(canonical) = self.canonicalize(raw) else {
+            return;
+        };
+        let raw = model.replace(canonical.to_string());
+        match metadata.get_or_insert_with(|| JsonValue::Object(Default::default())) {
+            JsonValue::Object(map) => {
+                map.entry(RAW_MODEL_METADATA_KEY)
+                    .or_insert(JsonValue::from(raw));
+            }
+            _ => {
+                tracing::debug!("metadata is not an object; raw model name not preserved");
+            }
+        }
+    }
+}
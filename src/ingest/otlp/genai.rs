@@ -0,0 +1,167 @@
+//! OpenTelemetry GenAI semantic conventions (`gen_ai.*`), as written by the
+//! opentelemetry-python-contrib GenAI instrumentations and other vanilla OTel
+//! instrumentations; also used for unclaimed scopes whose spans carry
+//! `gen_ai.*` attributes.
+//!
+//! Prompts and completions are read from, in order: the
+//! `gen_ai.input.messages` / `gen_ai.output.messages` span attributes, the
+//! `gen_ai.content.prompt` / `gen_ai.content.completion` events, and the
+//! per-message events (`gen_ai.user.message`, .., `gen_ai.choice`).
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use super::{
+    extract_i64_attr, extract_string_attr,
+    mapper::{json_or_string, request_parameters, MappedSpan, SpanMapper, TraceAttributes, Usage},
+    otel_value_to_json, OtelEvent, OtelSpan,
+};
+
+/// Scope prefixes of GenAI semconv instrumentations.
+const SCOPES: &[&str] = &[
+    "opentelemetry.instrumentation.genai",
+    "opentelemetry.instrumentation.google_genai",
+    "opentelemetry.instrumentation.openai_v2",
+    "opentelemetry.instrumentation.vertexai",
+];
+
+const PROMPT_EVENT: &str = "gen_ai.content.prompt";
+const COMPLETION_EVENT: &str = "gen_ai.content.completion";
+const CHOICE_EVENT: &str = "gen_ai.choice";
+
+/// Per-message input events and the role of their message.
+const MESSAGE_EVENTS: &[(&str, &str)] = &[
+    ("gen_ai.system.message", "system"),
+    ("gen_ai.user.message", "user"),
+    ("gen_ai.assistant.message", "assistant"),
+    ("gen_ai.tool.message", "tool"),
+];
+
+/// Event attributes describing the provider rather than the message.
+const NON_MESSAGE_ATTRS: &[&str] = &["gen_ai.system", "gen_ai.provider.name"];
+
+pub(super) struct GenAiMapper;
+
+impl SpanMapper for GenAiMapper {
+    fn name(&self) -> &'static str {
+        "genai"
+    }
+
+    fn handles_scope(&self, scope: &str) -> bool {
+        SCOPES.iter().any(|prefix| scope.starts_with(prefix))
+    }
+
+    fn map_span(&self, span: &OtelSpan) -> MappedSpan {
+        let attrs = &span.attributes;
+        let request_model = extract_string_attr(attrs, "gen_ai.request.model");
+        let operation = extract_string_attr(attrs, "gen_ai.operation.name");
+        let obs_type = match operation.as_deref() {
+            Some("chat" | "text_completion" | "generate_content") => Some("GENERATION"),
+            Some("embeddings") => Some("EMBEDDING"),
+            Some("execute_tool") => Some("TOOL"),
+            Some("create_agent" | "invoke_agent") => Some("AGENT"),
+            _ if request_model.is_some() => Some("GENERATION"),
+            _ => None,
+        };
+        MappedSpan {
+            obs_type: obs_type.map(str::to_string),
+            model: extract_string_attr(attrs, "gen_ai.response.model").or(request_model),
+            model_parameters: request_parameters(attrs),
+            input: extract_string_attr(attrs, "gen_ai.input.messages")
+                .map(json_or_string)
+                .or_else(|| event_content(span, PROMPT_EVENT, "gen_ai.prompt"))
+                .or_else(|| input_messages(span)),
+            output: extract_string_attr(attrs, "gen_ai.output.messages")
+                .map(json_or_string)
+                .or_else(|| event_content(span, COMPLETION_EVENT, "gen_ai.completion"))
+                .or_else(|| choices(span)),
+            usage: Usage {
+                prompt: extract_i64_attr(attrs, "gen_ai.usage.input_tokens")
+                    .or_else(|| extract_i64_attr(attrs, "gen_ai.usage.prompt_tokens")),
+                completion: extract_i64_attr(attrs, "gen_ai.usage.output_tokens")
+                    .or_else(|| extract_i64_attr(attrs, "gen_ai.usage.completion_tokens")),
+                total: extract_i64_attr(attrs, "gen_ai.usage.total_tokens"),
+            }
+            .with_derived_total(),
+            trace: TraceAttributes {
+                user_id: extract_string_attr(attrs, "user.id"),
+                session_id: extract_string_attr(attrs, "session.id"),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn is_content_event(&self, event: &OtelEvent) -> bool {
+        matches!(
+            event.name.as_str(),
+            PROMPT_EVENT | COMPLETION_EVENT | CHOICE_EVENT
+        ) || message_role(&event.name).is_some()
+    }
+}
+
+fn message_role(event_name: &str) -> Option<&'static str> {
+    MESSAGE_EVENTS
+        .iter()
+        .find(|(name, _)| *name == event_name)
+        .map(|(_, role)| *role)
+}
+
+/// The `attr` attribute (a JSON string) of the first `event_name` event.
+fn event_content(span: &OtelSpan, event_name: &str, attr: &str) -> Option<JsonValue> {
+    span.events
+        .iter()
+        .filter(|e| e.name == event_name)
+        .find_map(|e| extract_string_attr(&e.attributes, attr))
+        .map(json_or_string)
+}
+
+/// The per-message input events as a message list, in event order.
+fn input_messages(span: &OtelSpan) -> Option<JsonValue> {
+    let messages: Vec<JsonValue> = span
+        .events
+        .iter()
+        .filter_map(|e| message_role(&e.name).map(|role| event_message(e, Some(role))))
+        .collect();
+    (!messages.is_empty()).then_some(JsonValue::Array(messages))
+}
+
+/// The `gen_ai.choice` events, one entry per choice.
+fn choices(span: &OtelSpan) -> Option<JsonValue> {
+    let choices: Vec<JsonValue> = span
+        .events
+        .iter()
+        .filter(|e| e.name == CHOICE_EVENT)
+        .map(|e| event_message(e, None))
+        .collect();
+    (!choices.is_empty()).then_some(JsonValue::Array(choices))
+}
+
+/// One message event: its `gen_ai.event.content` JSON if set, else its
+/// attributes, with `role` filled in from the event name.
+fn event_message(event: &OtelEvent, role: Option<&str>) -> JsonValue {
+    let mut message =
+        match extract_string_attr(&event.attributes, "gen_ai.event.content").map(json_or_string) {
+            Some(JsonValue::Object(obj)) => obj,
+            Some(other) => return other,
+            None => event
+                .attributes
+                .iter()
+                .filter(|kv| !NON_MESSAGE_ATTRS.contains(&kv.key.as_str()))
+                .map(|kv| (kv.key.clone(), json_attr(otel_value_to_json(&kv.value))))
+                .collect::<JsonMap<_, _>>(),
+        };
+    if let Some(role) = role {
+        message
+            .entry("role")
+            .or_insert_with(|| JsonValue::String(role.to_string()));
+    }
+    JsonValue::Object(message)
+}
+
+/// Event attributes such as `message` or `tool_calls` hold JSON strings.
+fn json_attr(value: JsonValue) -> JsonValue {
+    match value {
+        JsonValue::String(s) if s.starts_with(['{', '[']) => json_or_string(s),
+        other => other,
+    }
+}
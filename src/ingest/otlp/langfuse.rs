@@ -0,0 +1,60 @@
+//! `langfuse.*` attributes written by the Langfuse OpenTelemetry SDKs; also
+//! the mapper for scopes no other mapper claims.
+
+use serde_json::Value as JsonValue;
+
+use super::{
+    extract_array_string_attr, extract_prefixed_map, extract_string_attr,
+    mapper::{json_or_string, MappedSpan, SpanMapper, TraceAttributes, Usage},
+    OtelKeyValue, OtelSpan,
+};
+
+pub(super) struct LangfuseMapper;
+
+impl SpanMapper for LangfuseMapper {
+    fn name(&self) -> &'static str {
+        "langfuse"
+    }
+
+    fn handles_scope(&self, scope: &str) -> bool {
+        scope.starts_with("langfuse")
+    }
+
+    fn map_span(&self, span: &OtelSpan) -> MappedSpan {
+        let attrs = &span.attributes;
+        MappedSpan {
+            obs_type: extract_string_attr(attrs, "langfuse.observation.type")
+                .map(|s| s.to_uppercase()),
+            model: extract_string_attr(attrs, "langfuse.generation.model")
+                .or_else(|| extract_string_attr(attrs, "gen_ai.request.model")),
+            model_parameters: None,
+            input: extract_string_attr(attrs, "langfuse.observation.input").map(json_or_string),
+            output: extract_string_attr(attrs, "langfuse.observation.output").map(json_or_string),
+            usage: usage_details(attrs),
+            level: extract_string_attr(attrs, "langfuse.observation.level")
+                .map(|s| s.to_uppercase()),
+            status_message: extract_string_attr(attrs, "langfuse.observation.status_message"),
+            trace: TraceAttributes {
+                name: extract_string_attr(attrs, "langfuse.trace.name"),
+                user_id: extract_string_attr(attrs, "user.id"),
+                session_id: extract_string_attr(attrs, "session.id"),
+                tags: extract_array_string_attr(attrs, "langfuse.trace.tags"),
+                metadata: extract_prefixed_map(attrs, "langfuse.trace.metadata."),
+            },
+        }
+    }
+}
+
+/// `langfuse.observation.usage_details`, a JSON object string.
+fn usage_details(attrs: &[OtelKeyValue]) -> Usage {
+    let Some(v) = extract_string_attr(attrs, "langfuse.observation.usage_details")
+        .and_then(|raw| serde_json::from_str::<JsonValue>(&raw).ok())
+    else {
+        return Usage::default();
+    };
+    Usage {
+        prompt: v.get("promptTokens").and_then(|x| x.as_i64()),
+        completion: v.get("completionTokens").and_then(|x| x.as_i64()),
+        total: v.get("totalTokens").and_then(|x| x.as_i64()),
+    }
+}
@@ -0,0 +1,163 @@
+//! Span attribute conventions. A [`SpanMapper`] turns the attributes of one
+//! OTLP span into xtrace observation and trace fields; ids, timing, resource
+//! info and the raw attribute metadata are handled by
+//! [`super::map_otel_to_batches`] for every mapper alike.
+//!
+//! The mapper is chosen per instrumentation scope ([`mapper_for_scope`]), per
+//! span for scopes no mapper claims ([`fallback_mapper`]), or for the whole
+//! request with the `x-xtrace-span-mapper` header.
+
+use axum::http::HeaderMap;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use super::{
+    extract_prefixed_map, extract_string_attr,
+    genai::GenAiMapper,
+    langfuse::LangfuseMapper,
+    openinference::{OpenInferenceMapper, SPAN_KIND_ATTR},
+    traceloop::TraceloopMapper,
+    OtelEvent, OtelKeyValue, OtelSpan,
+};
+use crate::http::error::ApiError;
+
+/// Forces one mapper for every span of a request, e.g. `genai`.
+pub(super) const SPAN_MAPPER_HEADER: &str = "x-xtrace-span-mapper";
+
+/// Observation and trace fields a mapper extracted from one span.
+#[derive(Debug, Default)]
+pub(super) struct MappedSpan {
+    /// Observation type, upper case (`GENERATION`, `SPAN`, `TOOL`, ..).
+    pub obs_type: Option<String>,
+    pub model: Option<String>,
+    pub model_parameters: Option<JsonValue>,
+    pub input: Option<JsonValue>,
+    pub output: Option<JsonValue>,
+    pub usage: Usage,
+    /// `DEBUG` / `DEFAULT` / `WARNING` / `ERROR`; an error span status is used
+    /// when unset.
+    pub level: Option<String>,
+    pub status_message: Option<String>,
+    pub trace: TraceAttributes,
+}
+
+/// Token counts reported by a span.
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Usage {
+    pub prompt: Option<i64>,
+    pub completion: Option<i64>,
+    pub total: Option<i64>,
+}
+
+impl Usage {
+    /// Fills in a missing total from the prompt and completion counts.
+    pub fn with_derived_total(mut self) -> Self {
+        if self.total.is_none() && (self.prompt.is_some() || self.completion.is_some()) {
+            self.total = Some(self.prompt.unwrap_or(0) + self.completion.unwrap_or(0));
+        }
+        self
+    }
+
+    /// The `usage` object stored on the observation, if any count was reported.
+    pub fn to_json(self) -> Option<JsonValue> {
+        if self.prompt.is_none() && self.completion.is_none() && self.total.is_none() {
+            return None;
+        }
+        Some(serde_json::json!({
+            "input": self.prompt.unwrap_or(0),
+            "output": self.completion.unwrap_or(0),
+            "total": self.total.unwrap_or(0)
+        }))
+    }
+}
+
+/// Trace-level fields carried on a span; the first span of a trace that sets
+/// a field wins, metadata is merged.
+#[derive(Debug, Default)]
+pub(super) struct TraceAttributes {
+    pub name: Option<String>,
+    pub user_id: Option<String>,
+    pub session_id: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub metadata: JsonMap<String, JsonValue>,
+}
+
+pub(super) trait SpanMapper: Send + Sync {
+    /// Value of [`SPAN_MAPPER_HEADER`] selecting this mapper.
+    fn name(&self) -> &'static str;
+
+    /// Whether spans of the instrumentation scope `scope` follow this
+    /// mapper's conventions.
+    fn handles_scope(&self, scope: &str) -> bool;
+
+    fn map_span(&self, span: &OtelSpan) -> MappedSpan;
+
+    /// Whether [`Self::map_span`] moved `event` into the observation's input
+    /// or output; such events are not stored as `EVENT` observations.
+    fn is_content_event(&self, _event: &OtelEvent) -> bool {
+        false
+    }
+}
+
+/// Checked in order; the GenAI scopes are more specific than Traceloop's
+/// `opentelemetry.instrumentation.` prefix.
+static MAPPERS: &[&dyn SpanMapper] = &[
+    &GenAiMapper,
+    &OpenInferenceMapper,
+    &TraceloopMapper,
+    &LangfuseMapper,
+];
+
+/// The mapper claiming spans of `scope`, if any.
+pub(super) fn mapper_for_scope(scope: Option<&str>) -> Option<&'static dyn SpanMapper> {
+    scope
+        .and_then(|scope| MAPPERS.iter().find(|m| m.handles_scope(scope)))
+        .copied()
+}
+
+/// The mapper for a span of an unclaimed scope: OpenInference for spans with
+/// an `openinference.span.kind`, GenAI semconv for spans with `gen_ai.*` but
+/// no `langfuse.*` attributes, Langfuse otherwise.
+pub(super) fn fallback_mapper(span: &OtelSpan) -> &'static dyn SpanMapper {
+    let has_prefix = |prefix| span.attributes.iter().any(|kv| kv.key.starts_with(prefix));
+    if extract_string_attr(&span.attributes, SPAN_KIND_ATTR).is_some() {
+        &OpenInferenceMapper
+    } else if has_prefix("gen_ai.") && !has_prefix("langfuse.") {
+        &GenAiMapper
+    } else {
+        &LangfuseMapper
+    }
+}
+
+/// The mapper forced by [`SPAN_MAPPER_HEADER`], if the header is set.
+pub(super) fn mapper_override(
+    headers: &HeaderMap,
+) -> Result<Option<&'static dyn SpanMapper>, ApiError> {
+    let Some(value) = headers.get(SPAN_MAPPER_HEADER) else {
+        return Ok(None);
+    };
+    let name = value.to_str().unwrap_or("").trim().to_ascii_lowercase();
+    MAPPERS
+        .iter()
+        .find(|m| m.name() == name)
+        .copied()
+        .map(Some)
+        .ok_or_else(|| {
+            let known: Vec<_> = MAPPERS.iter().map(|m| m.name()).collect();
+            ApiError::BadRequest(format!(
+                "invalid {SPAN_MAPPER_HEADER}: {name:?} (expected one of {})",
+                known.join(", ")
+            ))
+        })
+}
+
+/// `gen_ai.request.*` sampling parameters other than the model.
+pub(super) fn request_parameters(attrs: &[OtelKeyValue]) -> Option<JsonValue> {
+    let mut params = extract_prefixed_map(attrs, "gen_ai.request.");
+    params.remove("model");
+    (!params.is_empty()).then_some(JsonValue::Object(params))
+}
+
+/// Parses a string attribute holding JSON, keeping non-JSON text as a string.
+pub(super) fn json_or_string(s: String) -> JsonValue {
+    serde_json::from_str::<JsonValue>(&s).unwrap_or(JsonValue::String(s))
+}
@@ -0,0 +1,902 @@
+mod genai;
+mod langfuse;
+mod mapper;
+mod openinference;
+#[cfg(test)]
+mod tests;
+mod traceloop;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::ExportTraceServiceRequest as PbExportTraceServiceRequest,
+    common::v1::{any_value::Value as PbValue, AnyValue as PbAnyValue, KeyValue as PbKeyValue},
+};
+use prost::Message;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use crate::{
+    http::{auth::ProjectScope, error::ApiError, ingest_limit::IngestCost},
+    ingest::{
+        batch::{BatchIngestRequest, ObservationIngest, TraceIngest},
+        encoding::decode_body,
+        ownership,
+        raw::RawSource,
+    },
+    state::AppState,
+};
+use mapper::{fallback_mapper, mapper_for_scope, mapper_override, SpanMapper};
+
+/// Endpoint names in the raw ingest history.
+const OTLP_HTTP_ENDPOINT: &str = "/api/public/otel/v1/traces";
+const OTLP_GRPC_ENDPOINT: &str = "grpc:/opentelemetry.proto.collector.trace.v1.TraceService/Export";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelExportTraceServiceRequest {
+    resource_spans: Vec<OtelResourceSpan>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelResourceSpan {
+    #[serde(default)]
+    resource: Option<OtelResource>,
+    #[serde(default)]
+    scope_spans: Vec<OtelScopeSpan>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelResource {
+    #[serde(default)]
+    attributes: Vec<OtelKeyValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelScopeSpan {
+    #[serde(default)]
+    scope: Option<OtelInstrumentationScope>,
+    #[serde(default)]
+    spans: Vec<OtelSpan>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelInstrumentationScope {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(default)]
+    parent_span_id: Option<String>,
+    name: String,
+    #[serde(default)]
+    start_time_unix_nano: Option<String>,
+    #[serde(default)]
+    end_time_unix_nano: Option<String>,
+    #[serde(default)]
+    attributes: Vec<OtelKeyValue>,
+    #[serde(default)]
+    events: Vec<OtelEvent>,
+    #[serde(default)]
+    links: Vec<OtelLink>,
+    #[serde(default)]
+    status: Option<OtelStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelEvent {
+    #[serde(default)]
+    time_unix_nano: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    attributes: Vec<OtelKeyValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelLink {
+    #[serde(default)]
+    trace_id: String,
+    #[serde(default)]
+    span_id: String,
+    #[serde(default)]
+    attributes: Vec<OtelKeyValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelStatus {
+    /// `2` / `"STATUS_CODE_ERROR"` for errors; OTLP/JSON writers use either.
+    #[serde(default)]
+    code: Option<JsonValue>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+impl OtelStatus {
+    fn is_error(&self) -> bool {
+        match &self.code {
+            Some(JsonValue::Number(n)) => n.as_i64() == Some(2),
+            Some(JsonValue::String(s)) => s.eq_ignore_ascii_case("STATUS_CODE_ERROR"),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelKeyValue {
+    key: String,
+    #[serde(default)]
+    value: Option<OtelAnyValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelAnyValue {
+    #[serde(default)]
+    string_value: Option<String>,
+    #[serde(default)]
+    int_value: Option<String>,
+    #[serde(default)]
+    double_value: Option<f64>,
+    #[serde(default)]
+    bool_value: Option<bool>,
+    #[serde(default)]
+    array_value: Option<OtelArrayValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OtelArrayValue {
+    #[serde(default)]
+    values: Vec<OtelAnyValue>,
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(s.len() / 2);
+    let bytes = s.as_bytes();
+    let hex_val = |c: u8| -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    };
+    for i in (0..bytes.len()).step_by(2) {
+        let hi = hex_val(bytes[i])?;
+        let lo = hex_val(bytes[i + 1])?;
+        out.push((hi << 4) | lo);
+    }
+    Some(out)
+}
+
+fn otel_trace_id_to_uuid(trace_id: &str) -> Option<Uuid> {
+    let raw = decode_hex(trace_id)?;
+    if raw.len() != 16 {
+        return None;
+    }
+    Uuid::from_slice(&raw).ok()
+}
+
+fn otel_span_id_to_uuid(span_id: &str) -> Option<Uuid> {
+    let raw = decode_hex(span_id)?;
+    if raw.len() != 8 {
+        return None;
+    }
+    let mut padded = [0u8; 16];
+    padded[8..].copy_from_slice(&raw);
+    Uuid::from_slice(&padded).ok()
+}
+
+fn unix_nano_to_datetime(v: &Option<String>) -> Option<DateTime<Utc>> {
+    let s = v.as_deref()?.trim();
+    let nanos: i128 = s.parse().ok()?;
+    if nanos <= 0 {
+        return None;
+    }
+    let secs = (nanos / 1_000_000_000) as i64;
+    let sub_nanos = (nanos % 1_000_000_000) as u32;
+    Utc.timestamp_opt(secs, sub_nanos).single()
+}
+
+fn otel_any_value_to_json(v: &OtelAnyValue) -> JsonValue {
+    if let Some(s) = &v.string_value {
+        return JsonValue::String(s.clone());
+    }
+    if let Some(s) = &v.int_value {
+        if let Ok(i) = s.parse::<i64>() {
+            return JsonValue::Number(i.into());
+        }
+        return JsonValue::String(s.clone());
+    }
+    if let Some(f) = v.double_value {
+        if let Some(n) = serde_json::Number::from_f64(f) {
+            return JsonValue::Number(n);
+        }
+    }
+    if let Some(b) = v.bool_value {
+        return JsonValue::Bool(b);
+    }
+    if let Some(arr) = &v.array_value {
+        return JsonValue::Array(arr.values.iter().map(otel_any_value_to_json).collect());
+    }
+    JsonValue::Null
+}
+
+fn otel_value_to_json(v: &Option<OtelAnyValue>) -> JsonValue {
+    v.as_ref()
+        .map(otel_any_value_to_json)
+        .unwrap_or(JsonValue::Null)
+}
+
+fn attributes_to_map(attrs: &[OtelKeyValue]) -> serde_json::Map<String, JsonValue> {
+    let mut m = serde_json::Map::new();
+    for kv in attrs {
+        m.insert(kv.key.clone(), otel_value_to_json(&kv.value));
+    }
+    m
+}
+
+fn extract_string_attr(attrs: &[OtelKeyValue], key: &str) -> Option<String> {
+    attrs
+        .iter()
+        .find(|kv| kv.key == key)
+        .and_then(|kv| kv.value.as_ref())
+        .and_then(|v| v.string_value.clone())
+}
+
+/// An integer attribute; tolerates doubles and numeric strings.
+fn extract_i64_attr(attrs: &[OtelKeyValue], key: &str) -> Option<i64> {
+    let v = attrs
+        .iter()
+        .find(|kv| kv.key == key)
+        .and_then(|kv| kv.value.as_ref())?;
+    v.int_value
+        .as_deref()
+        .or(v.string_value.as_deref())
+        .and_then(|s| s.trim().parse().ok())
+        .or_else(|| v.double_value.map(|f| f as i64))
+}
+
+fn extract_array_string_attr(attrs: &[OtelKeyValue], key: &str) -> Option<Vec<String>> {
+    let v = attrs
+        .iter()
+        .find(|kv| kv.key == key)
+        .and_then(|kv| kv.value.as_ref())?
+        .array_value
+        .as_ref()?;
+
+    let mut out = Vec::with_capacity(v.values.len());
+    for item in &v.values {
+        if let Some(s) = &item.string_value {
+            out.push(s.clone());
+        }
+    }
+    Some(out)
+}
+
+fn extract_prefixed_map(
+    attrs: &[OtelKeyValue],
+    prefix: &str,
+) -> serde_json::Map<String, JsonValue> {
+    let mut out = serde_json::Map::new();
+    for kv in attrs {
+        if let Some(rest) = kv.key.strip_prefix(prefix) {
+            if !rest.is_empty() {
+                if let Some(v) = &kv.value {
+                    out.insert(rest.to_string(), otel_any_value_to_json(v));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Resource attributes promoted to dedicated trace/observation columns.
+#[derive(Clone, Default)]
+struct ResourceInfo {
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
+}
+
+impl ResourceInfo {
+    fn from_attrs(attrs: Option<&Vec<OtelKeyValue>>) -> Self {
+        let Some(attrs) = attrs else {
+            return Self::default();
+        };
+        Self {
+            service_name: extract_string_attr(attrs, "service.name"),
+            service_version: extract_string_attr(attrs, "service.version"),
+            sdk_language: extract_string_attr(attrs, "telemetry.sdk.language"),
+            host_name: extract_string_attr(attrs, "host.name"),
+        }
+    }
+}
+
+fn content_type(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| {
+            s.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase()
+        })
+}
+
+/// Maps an export into per-trace batches. Span attributes are read by the
+/// scope's [`SpanMapper`] unless `forced` overrides it for every span.
+fn map_otel_to_batches(
+    project_id: &str,
+    payload: OtelExportTraceServiceRequest,
+    forced: Option<&dyn SpanMapper>,
+) -> Result<Vec<BatchIngestRequest>, ApiError> {
+    let project_id = project_id.to_string();
+    let mut per_trace: std::collections::BTreeMap<Uuid, Vec<ObservationIngest>> =
+        std::collections::BTreeMap::new();
+    let mut trace_first_ts: std::collections::BTreeMap<Uuid, DateTime<Utc>> =
+        std::collections::BTreeMap::new();
+    let mut trace_acc: std::collections::BTreeMap<Uuid, TraceIngest> =
+        std::collections::BTreeMap::new();
+
+    for rs in payload.resource_spans {
+        let resource_attrs = rs.resource.as_ref().map(|r| &r.attributes);
+        let resource_info = ResourceInfo::from_attrs(resource_attrs);
+        for ss in rs.scope_spans {
+            let scope_name = ss.scope.as_ref().map(|s| s.name.as_str());
+            let scope_mapper = forced.or_else(|| mapper_for_scope(scope_name));
+            let scope_meta = ss
+                .scope
+                .as_ref()
+                .map(|s| serde_json::json!({"name": s.name, "version": s.version}));
+            for mut span in ss.spans {
+                let trace_id = match otel_trace_id_to_uuid(&span.trace_id) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                let span_uuid = match otel_span_id_to_uuid(&span.span_id) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                let parent_uuid = match span.parent_span_id.as_deref() {
+                    Some(p) if !p.is_empty() && p != "0000000000000000" => otel_span_id_to_uuid(p),
+                    _ => None,
+                };
+
+                let start_time = unix_nano_to_datetime(&span.start_time_unix_nano);
+                let end_time = unix_nano_to_datetime(&span.end_time_unix_nano);
+
+                if let Some(st) = start_time {
+                    trace_first_ts
+                        .entry(trace_id)
+                        .and_modify(|cur| {
+                            if st < *cur {
+                                *cur = st
+                            }
+                        })
+                        .or_insert(st);
+                }
+
+                let mapper = scope_mapper.unwrap_or_else(|| fallback_mapper(&span));
+                let mapped = mapper.map_span(&span);
+                let trace_attrs = mapped.trace;
+                let trace_name = trace_attrs.name;
+                let user_id = trace_attrs.user_id;
+                let session_id = trace_attrs.session_id;
+                let tags = trace_attrs.tags;
+                let trace_meta = trace_attrs.metadata;
+
+                // An export may hold only some spans of a trace, so the trace
+                // is a partial update: what earlier exports promoted is kept.
+                trace_acc
+                    .entry(trace_id)
+                    .and_modify(|t| {
+                        if t.name.is_none() {
+                            t.name = trace_name.clone();
+                        }
+                        if t.userId.is_none() {
+                            t.userId = user_id.clone();
+                        }
+                        if t.session_id.is_none() {
+                            t.session_id = session_id.clone();
+                        }
+                        if t.tags.is_empty() {
+                            if let Some(tags) = &tags {
+                                t.tags = tags.clone();
+                            }
+                        }
+                        if let Some(m) = &mut t.metadata {
+                            if let JsonValue::Object(obj) = m {
+                                for (k, v) in trace_meta.clone() {
+                                    obj.insert(k, v);
+                                }
+                            }
+                        } else if !trace_meta.is_empty() {
+                            t.metadata = Some(JsonValue::Object(trace_meta.clone()));
+                        }
+                        if t.serviceName.is_none() {
+                            t.serviceName = resource_info.service_name.clone();
+                            t.serviceVersion = resource_info.service_version.clone();
+                            t.sdkLanguage = resource_info.sdk_language.clone();
+                            t.hostName = resource_info.host_name.clone();
+                        }
+                    })
+                    .or_insert_with(|| TraceIngest {
+                        id: trace_id,
+                        timestamp: None,
+                        name: trace_name.clone(),
+                        input: None,
+                        output: None,
+                        session_id: session_id.clone(),
+                        release: None,
+                        version: None,
+                        userId: user_id.clone(),
+                        metadata: if trace_meta.is_empty() {
+                            None
+                        } else {
+                            Some(JsonValue::Object(trace_meta.clone()))
+                        },
+                        tags: tags.unwrap_or_default(),
+                        public: None,
+                        environment: None,
+                        externalId: None,
+                        bookmarked: None,
+                        latency: None,
+                        totalCost: None,
+                        serviceName: resource_info.service_name.clone(),
+                        serviceVersion: resource_info.service_version.clone(),
+                        sdkLanguage: resource_info.sdk_language.clone(),
+                        hostName: resource_info.host_name.clone(),
+                        projectId: Some(project_id.clone()),
+                        complete: None,
+                        eventTime: None,
+                        update: Some(true),
+                        extra: Default::default(),
+                    });
+
+                let mut meta = attributes_to_map(&span.attributes);
+                if let Some(rattrs) = resource_attrs {
+                    meta.insert(
+                        "otel.resource".to_string(),
+                        JsonValue::Object(attributes_to_map(rattrs)),
+                    );
+                }
+                if let Some(scope) = &scope_meta {
+                    meta.insert("otel.scope".to_string(), scope.clone());
+                }
+                if !span.links.is_empty() {
+                    let links = span
+                        .links
+                        .iter()
+                        .map(|l| {
+                            serde_json::json!({
+                                "traceId": l.trace_id,
+                                "spanId": l.span_id,
+                                "attributes": attributes_to_map(&l.attributes),
+                            })
+                        })
+                        .collect();
+                    meta.insert("otel.links".to_string(), JsonValue::Array(links));
+                }
+
+                // A mapper's explicit level wins over the OTel span status.
+                let (level, status_message) = match (&mapped.level, &span.status) {
+                    (Some(_), _) => (mapped.level, mapped.status_message),
+                    (None, Some(status)) if status.is_error() => (
+                        Some("ERROR".to_string()),
+                        mapped.status_message.or_else(|| status.message.clone()),
+                    ),
+                    _ => (None, mapped.status_message),
+                };
+
+                let events = std::mem::take(&mut span.events);
+
+                let obs = ObservationIngest {
+                    id: span_uuid,
+                    traceId: trace_id,
+                    r#type: mapped.obs_type,
+                    name: Some(span.name),
+                    startTime: start_time,
+                    endTime: end_time,
+                    completionStartTime: None,
+                    model: mapped.model,
+                    modelParameters: mapped.model_parameters,
+                    input: mapped.input,
+                    output: mapped.output,
+                    usage: mapped.usage.to_json(),
+                    level,
+                    statusMessage: status_message,
+                    parentObservationId: parent_uuid,
+                    promptId: None,
+                    promptName: None,
+                    promptVersion: None,
+                    modelId: None,
+                    inputPrice: None,
+                    outputPrice: None,
+                    totalPrice: None,
+                    calculatedInputCost: None,
+                    calculatedOutputCost: None,
+                    calculatedTotalCost: None,
+                    latency: None,
+                    timeToFirstToken: None,
+                    completionTokens: mapped.usage.completion,
+                    promptTokens: mapped.usage.prompt,
+                    totalTokens: mapped.usage.total,
+                    unit: None,
+                    metadata: Some(JsonValue::Object(meta)),
+                    environment: None,
+                    serviceName: resource_info.service_name.clone(),
+                    serviceVersion: resource_info.service_version.clone(),
+                    sdkLanguage: resource_info.sdk_language.clone(),
+                    hostName: resource_info.host_name.clone(),
+                    provider: None,
+                    input_offload: None,
+                    output_offload: None,
+                    projectId: Some(project_id.clone()),
+                    eventTime: None,
+                    update: None,
+                    outputDelta: None,
+                    outputSeq: None,
+                    extra: Default::default(),
+                };
+
+                let trace_obs = per_trace.entry(trace_id).or_default();
+                trace_obs.push(obs);
+                // Indexes stay those of the span's event list, so ids do not
+                // depend on which events a mapper consumed.
+                trace_obs.extend(
+                    events
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(_, event)| !mapper.is_content_event(event))
+                        .map(|(index, event)| {
+                            event_observation(
+                                event,
+                                index,
+                                trace_id,
+                                span_uuid,
+                                start_time,
+                                &resource_info,
+                                &project_id,
+                            )
+                        }),
+                );
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(per_trace.len());
+    for (trace_id, observations) in per_trace {
+        let timestamp = trace_first_ts.get(&trace_id).cloned();
+        let mut trace = trace_acc.remove(&trace_id).unwrap_or(TraceIngest {
+            id: trace_id,
+            timestamp: None,
+            name: None,
+            input: None,
+            output: None,
+            session_id: None,
+            release: None,
+            version: None,
+            userId: None,
+            metadata: None,
+            tags: vec![],
+            public: None,
+            environment: None,
+            externalId: None,
+            bookmarked: None,
+            latency: None,
+            totalCost: None,
+            serviceName: None,
+            serviceVersion: None,
+            sdkLanguage: None,
+            hostName: None,
+            projectId: Some(project_id.clone()),
+            complete: None,
+            eventTime: None,
+            update: Some(true),
+            extra: Default::default(),
+        });
+        trace.timestamp = timestamp;
+        out.push(BatchIngestRequest {
+            trace: Some(trace),
+            observations,
+            event_ids: Vec::new(),
+            complete: Vec::new(),
+            wal_seq: None,
+            accepted_at: None,
+            extra: Default::default(),
+        });
+    }
+    Ok(out)
+}
+
+/// A span event as a child `EVENT` observation. Its id is derived from the
+/// span id and the event's position, so re-sent spans upsert the same rows;
+/// `exception` events are logged at `ERROR` level.
+fn event_observation(
+    event: OtelEvent,
+    index: usize,
+    trace_id: Uuid,
+    span_uuid: Uuid,
+    span_start: Option<DateTime<Utc>>,
+    resource_info: &ResourceInfo,
+    project_id: &str,
+) -> ObservationIngest {
+    let is_exception = event.name == "exception";
+    let status_message = if is_exception {
+        extract_string_attr(&event.attributes, "exception.message")
+            .or_else(|| extract_string_attr(&event.attributes, "exception.type"))
+    } else {
+        None
+    };
+    ObservationIngest {
+        id: Uuid::new_v5(&span_uuid, format!("event/{index}").as_bytes()),
+        traceId: trace_id,
+        r#type: Some("EVENT".to_string()),
+        startTime: unix_nano_to_datetime(&event.time_unix_nano).or(span_start),
+        endTime: None,
+        completionStartTime: None,
+        model: None,
+        modelParameters: None,
+        input: None,
+        output: None,
+        usage: None,
+        level: is_exception.then(|| "ERROR".to_string()),
+        statusMessage: status_message,
+        parentObservationId: Some(span_uuid),
+        promptId: None,
+        promptName: None,
+        promptVersion: None,
+        modelId: None,
+        inputPrice: None,
+        outputPrice: None,
+        totalPrice: None,
+        calculatedInputCost: None,
+        calculatedOutputCost: None,
+        calculatedTotalCost: None,
+        latency: None,
+        timeToFirstToken: None,
+        completionTokens: None,
+        promptTokens: None,
+        totalTokens: None,
+        unit: None,
+        metadata: Some(JsonValue::Object(attributes_to_map(&event.attributes))),
+        name: Some(event.name),
+        environment: None,
+        serviceName: resource_info.service_name.clone(),
+        serviceVersion: resource_info.service_version.clone(),
+        sdkLanguage: resource_info.sdk_language.clone(),
+        hostName: resource_info.host_name.clone(),
+        provider: None,
+        input_offload: None,
+        output_offload: None,
+        projectId: Some(project_id.to_string()),
+        eventTime: None,
+        update: None,
+        outputDelta: None,
+        outputSeq: None,
+        extra: Default::default(),
+    }
+}
+
+fn pb_any_value(value: PbAnyValue) -> OtelAnyValue {
+    let mut out = OtelAnyValue {
+        string_value: None,
+        int_value: None,
+        double_value: None,
+        bool_value: None,
+        array_value: None,
+    };
+    match value.value {
+        Some(PbValue::StringValue(s)) => out.string_value = Some(s),
+        Some(PbValue::IntValue(i)) => out.int_value = Some(i.to_string()),
+        Some(PbValue::DoubleValue(f)) => out.double_value = Some(f),
+        Some(PbValue::BoolValue(b)) => out.bool_value = Some(b),
+        Some(PbValue::ArrayValue(arr)) => {
+            out.array_value = Some(OtelArrayValue {
+                values: arr.values.into_iter().map(pb_any_value).collect(),
+            })
+        }
+        _ => {}
+    }
+    out
+}
+
+fn pb_attributes(attrs: Vec<PbKeyValue>) -> Vec<OtelKeyValue> {
+    attrs
+        .into_iter()
+        .map(|kv| OtelKeyValue {
+            key: kv.key,
+            value: kv.value.map(pb_any_value),
+        })
+        .collect()
+}
+
+fn pb_unix_nano(nanos: u64) -> Option<String> {
+    (nanos != 0).then(|| nanos.to_string())
+}
+
+fn pb_to_otel_json(payload: PbExportTraceServiceRequest) -> OtelExportTraceServiceRequest {
+    let resource_spans = payload
+        .resource_spans
+        .into_iter()
+        .map(|rs| OtelResourceSpan {
+            resource: rs.resource.map(|r| OtelResource {
+                attributes: pb_attributes(r.attributes),
+            }),
+            scope_spans: rs
+                .scope_spans
+                .into_iter()
+                .map(|ss| OtelScopeSpan {
+                    scope: ss.scope.map(|scope| OtelInstrumentationScope {
+                        name: scope.name,
+                        version: (!scope.version.is_empty()).then_some(scope.version),
+                    }),
+                    spans: ss
+                        .spans
+                        .into_iter()
+                        .map(|s| OtelSpan {
+                            trace_id: hex::encode(s.trace_id),
+                            span_id: hex::encode(s.span_id),
+                            parent_span_id: (!s.parent_span_id.is_empty())
+                                .then(|| hex::encode(s.parent_span_id)),
+                            name: s.name,
+                            start_time_unix_nano: pb_unix_nano(s.start_time_unix_nano),
+                            end_time_unix_nano: pb_unix_nano(s.end_time_unix_nano),
+                            attributes: pb_attributes(s.attributes),
+                            events: s
+                                .events
+                                .into_iter()
+                                .map(|e| OtelEvent {
+                                    time_unix_nano: pb_unix_nano(e.time_unix_nano),
+                                    name: e.name,
+                                    attributes: pb_attributes(e.attributes),
+                                })
+                                .collect(),
+                            links: s
+                                .links
+                                .into_iter()
+                                .map(|l| OtelLink {
+                                    trace_id: hex::encode(l.trace_id),
+                                    span_id: hex::encode(l.span_id),
+                                    attributes: pb_attributes(l.attributes),
+                                })
+                                .collect(),
+                            status: s.status.map(|st| OtelStatus {
+                                code: Some(JsonValue::from(st.code)),
+                                message: (!st.message.is_empty()).then_some(st.message),
+                            }),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    OtelExportTraceServiceRequest { resource_spans }
+}
+
+pub(crate) async fn post_otel_traces(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let raw = decode_body(&headers, body)?;
+    let ct = content_type(&headers).unwrap_or_else(|| "application/json".to_string());
+
+    let otel: OtelExportTraceServiceRequest = if ct == "application/json" {
+        serde_json::from_slice(&raw)
+            .map_err(|e| ApiError::BadRequest(format!("invalid json: {e}")))?
+    } else if ct == "application/x-protobuf" {
+        let pb = PbExportTraceServiceRequest::decode(raw.as_ref())
+            .map_err(|e| ApiError::BadRequest(format!("invalid protobuf: {e}")))?;
+        pb_to_otel_json(pb)
+    } else {
+        return Err(ApiError::BadRequest(format!(
+            "unsupported content-type: {ct}"
+        )));
+    };
+
+    enqueue_otel(
+        &state,
+        &scope,
+        &headers,
+        otel,
+        OTLP_HTTP_ENDPOINT,
+        raw.len(),
+        &raw,
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({}))))
+}
+
+/// Ingests a decoded OTLP/gRPC export; see [`crate::ingest::otlp_grpc`].
+pub(crate) async fn enqueue_protobuf(
+    state: &AppState,
+    scope: &ProjectScope,
+    headers: &HeaderMap,
+    payload: PbExportTraceServiceRequest,
+) -> Result<(), ApiError> {
+    let bytes = payload.encoded_len();
+    // Re-encoded for the raw ingest history only.
+    let raw = match &state.raw_ingest {
+        Some(_) => payload.encode_to_vec(),
+        None => Vec::new(),
+    };
+    enqueue_otel(
+        state,
+        scope,
+        headers,
+        pb_to_otel_json(payload),
+        OTLP_GRPC_ENDPOINT,
+        bytes,
+        &raw,
+    )
+    .await
+}
+
+/// Maps an export into the key's project, charges it and queues it.
+/// `body` is the decoded request as kept in the raw ingest history.
+async fn enqueue_otel(
+    state: &AppState,
+    scope: &ProjectScope,
+    headers: &HeaderMap,
+    otel: OtelExportTraceServiceRequest,
+    endpoint: &'static str,
+    bytes: usize,
+    body: &[u8],
+) -> Result<(), ApiError> {
+    let batches = map_otel_to_batches(scope.project_id(), otel, mapper_override(headers)?)?;
+    state.charge_ingest(
+        headers,
+        IngestCost {
+            bytes,
+            observations: batches.iter().map(|b| b.observations.len()).sum(),
+        },
+    )?;
+    state.check_quota(&batches)?;
+    ownership::check(&state.pool, &batches, scope.project_id()).await?;
+    state
+        .enqueue_ingest_from(
+            RawSource {
+                endpoint,
+                project_id: scope.project_id(),
+                headers,
+                body,
+            },
+            batches,
+        )
+        .await
+}
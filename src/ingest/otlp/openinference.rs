@@ -0,0 +1,104 @@
+//! OpenInference conventions (`openinference.span.kind`, `llm.*`,
+//! `input.value` / `output.value`), as written by the Arize / Phoenix
+//! instrumentations for LlamaIndex, LangChain, OpenAI and others.
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use std::collections::BTreeMap;
+
+use super::{
+    extract_array_string_attr, extract_i64_attr, extract_string_attr,
+    mapper::{json_or_string, MappedSpan, SpanMapper, TraceAttributes, Usage},
+    otel_value_to_json, OtelKeyValue, OtelSpan,
+};
+
+/// Set on every OpenInference span; also selects this mapper for spans of
+/// unclaimed scopes.
+pub(super) const SPAN_KIND_ATTR: &str = "openinference.span.kind";
+
+pub(super) struct OpenInferenceMapper;
+
+impl SpanMapper for OpenInferenceMapper {
+    fn name(&self) -> &'static str {
+        "openinference"
+    }
+
+    fn handles_scope(&self, scope: &str) -> bool {
+        scope.starts_with("openinference.")
+    }
+
+    fn map_span(&self, span: &OtelSpan) -> MappedSpan {
+        let attrs = &span.attributes;
+        let kind = extract_string_attr(attrs, SPAN_KIND_ATTR).map(|s| s.to_ascii_uppercase());
+        let obs_type = match kind.as_deref() {
+            Some("LLM") => Some("GENERATION"),
+            Some(
+                kind @ ("EMBEDDING" | "TOOL" | "AGENT" | "CHAIN" | "RETRIEVER" | "EVALUATOR"
+                | "GUARDRAIL"),
+            ) => Some(kind),
+            Some("RERANKER") => Some("RETRIEVER"),
+            _ => None,
+        };
+        MappedSpan {
+            obs_type: obs_type.map(str::to_string),
+            model: extract_string_attr(attrs, "llm.model_name")
+                .or_else(|| extract_string_attr(attrs, "embedding.model_name")),
+            model_parameters: extract_string_attr(attrs, "llm.invocation_parameters")
+                .map(json_or_string)
+                .filter(|v| v.as_object().is_some_and(|o| !o.is_empty())),
+            input: io_value(attrs, "input")
+                .or_else(|| indexed_messages(attrs, "llm.input_messages.")),
+            output: io_value(attrs, "output")
+                .or_else(|| indexed_messages(attrs, "llm.output_messages.")),
+            usage: Usage {
+                prompt: extract_i64_attr(attrs, "llm.token_count.prompt"),
+                completion: extract_i64_attr(attrs, "llm.token_count.completion"),
+                total: extract_i64_attr(attrs, "llm.token_count.total"),
+            }
+            .with_derived_total(),
+            trace: TraceAttributes {
+                user_id: extract_string_attr(attrs, "user.id"),
+                session_id: extract_string_attr(attrs, "session.id"),
+                tags: extract_array_string_attr(attrs, "tag.tags"),
+                metadata: match extract_string_attr(attrs, "metadata").map(json_or_string) {
+                    Some(JsonValue::Object(obj)) => obj,
+                    _ => JsonMap::new(),
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// `<prefix>.value`, parsed as JSON when `<prefix>.mime_type` says so.
+fn io_value(attrs: &[OtelKeyValue], prefix: &str) -> Option<JsonValue> {
+    let value = extract_string_attr(attrs, &format!("{prefix}.value"))?;
+    match extract_string_attr(attrs, &format!("{prefix}.mime_type")).as_deref() {
+        Some("application/json") => Some(json_or_string(value)),
+        _ => Some(JsonValue::String(value)),
+    }
+}
+
+/// Messages flattened as `<prefix><index>.message.<field>`, e.g.
+/// `llm.input_messages.0.message.role`, in index order.
+fn indexed_messages(attrs: &[OtelKeyValue], prefix: &str) -> Option<JsonValue> {
+    let mut messages: BTreeMap<usize, JsonMap<String, JsonValue>> = BTreeMap::new();
+    for kv in attrs {
+        let Some((index, field)) = kv
+            .key
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.split_once(".message."))
+        else {
+            continue;
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            continue;
+        };
+        messages
+            .entry(index)
+            .or_default()
+            .insert(field.to_string(), otel_value_to_json(&kv.value));
+    }
+    (!messages.is_empty())
+        .then(|| JsonValue::Array(messages.into_values().map(JsonValue::Object).collect()))
+}
@@ -0,0 +1,97 @@
+//! Golden-file tests of the span mappers on recorded OTLP/JSON exports in
+//! `tests/fixtures/otlp`. Run with `XTRACE_UPDATE_GOLDEN=1` to rewrite the
+//! `*.golden.json` files after an intended mapping change.
+
+use axum::http::{HeaderMap, HeaderValue};
+use std::path::PathBuf;
+
+use super::{
+    map_otel_to_batches,
+    mapper::{mapper_for_scope, mapper_override, SPAN_MAPPER_HEADER},
+    OtelExportTraceServiceRequest,
+};
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/otlp")
+}
+
+fn assert_golden(fixture: &str) {
+    let dir = fixture_dir();
+    let raw = std::fs::read(dir.join(format!("{fixture}.json"))).expect("read fixture");
+    let payload: OtelExportTraceServiceRequest =
+        serde_json::from_slice(&raw).expect("parse fixture");
+    let batches = map_otel_to_batches("golden", payload, None).expect("map fixture");
+    let actual = serde_json::to_string_pretty(&batches).unwrap() + "\n";
+
+    let golden = dir.join(format!("{fixture}.golden.json"));
+    if std::env::var_os("XTRACE_UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden, &actual).expect("write golden file");
+        return;
+    }
+    let expected = std::fs::read_to_string(&golden).unwrap_or_else(|e| {
+        panic!(
+            "{}: {e} (run with XTRACE_UPDATE_GOLDEN=1)",
+            golden.display()
+        )
+    });
+    assert_eq!(
+        actual,
+        expected,
+        "{fixture}: mapping differs from {}",
+        golden.display()
+    );
+}
+
+#[test]
+fn langfuse_golden() {
+    assert_golden("langfuse");
+}
+
+#[test]
+fn genai_golden() {
+    assert_golden("genai");
+}
+
+#[test]
+fn openinference_golden() {
+    assert_golden("openinference");
+}
+
+#[test]
+fn traceloop_golden() {
+    assert_golden("traceloop");
+}
+
+#[test]
+fn mapper_selection_by_scope() {
+    let name = |scope| mapper_for_scope(scope).map(|m| m.name());
+    assert_eq!(name(Some("langfuse-sdk")), Some("langfuse"));
+    assert_eq!(
+        name(Some("opentelemetry.instrumentation.openai_v2")),
+        Some("genai")
+    );
+    assert_eq!(
+        name(Some("opentelemetry.instrumentation.openai.v1")),
+        Some("traceloop")
+    );
+    assert_eq!(name(Some("traceloop.tracer")), Some("traceloop"));
+    assert_eq!(
+        name(Some("openinference.instrumentation.llama_index")),
+        Some("openinference")
+    );
+    // Unclaimed scopes are mapped by `fallback_mapper` (see the genai fixture).
+    assert_eq!(name(Some("my-app")), None);
+    assert_eq!(name(None), None);
+}
+
+#[test]
+fn mapper_override_header() {
+    let mut headers = HeaderMap::new();
+    assert!(mapper_override(&headers).unwrap().is_none());
+
+    headers.insert(SPAN_MAPPER_HEADER, HeaderValue::from_static("GenAI"));
+    assert_eq!(mapper_override(&headers).unwrap().unwrap().name(), "genai");
+
+    headers.insert(SPAN_MAPPER_HEADER, HeaderValue::from_static("nope"));
+    assert!(mapper_override(&headers).is_err());
+}
@@ -0,0 +1,102 @@
+//! Traceloop / OpenLLMetry attributes (`traceloop.*`, `llm.*` and the indexed
+//! `gen_ai.prompt.N.*` / `gen_ai.completion.N.*` messages).
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use std::collections::BTreeMap;
+
+use super::{
+    extract_i64_attr, extract_prefixed_map, extract_string_attr,
+    mapper::{json_or_string, request_parameters, MappedSpan, SpanMapper, TraceAttributes, Usage},
+    otel_value_to_json, OtelKeyValue, OtelSpan,
+};
+
+const ASSOCIATION_PREFIX: &str = "traceloop.association.properties.";
+
+pub(super) struct TraceloopMapper;
+
+impl SpanMapper for TraceloopMapper {
+    fn name(&self) -> &'static str {
+        "traceloop"
+    }
+
+    fn handles_scope(&self, scope: &str) -> bool {
+        scope.starts_with("traceloop") || scope.starts_with("opentelemetry.instrumentation.")
+    }
+
+    fn map_span(&self, span: &OtelSpan) -> MappedSpan {
+        let attrs = &span.attributes;
+        let obs_type = match extract_string_attr(attrs, "llm.request.type").as_deref() {
+            Some("chat" | "completion") => Some("GENERATION"),
+            Some("embedding") => Some("EMBEDDING"),
+            _ => match extract_string_attr(attrs, "traceloop.span.kind").as_deref() {
+                Some("agent") => Some("AGENT"),
+                Some("tool") => Some("TOOL"),
+                Some("workflow" | "task") => Some("SPAN"),
+                _ => None,
+            },
+        };
+
+        let mut association = extract_prefixed_map(attrs, ASSOCIATION_PREFIX);
+        let mut take = |key: &str| match association.remove(key) {
+            Some(JsonValue::String(s)) => Some(s),
+            Some(other) => Some(other.to_string()),
+            None => None,
+        };
+        let user_id = take("user_id");
+        let session_id = take("session_id");
+
+        MappedSpan {
+            obs_type: obs_type.map(str::to_string),
+            model: extract_string_attr(attrs, "gen_ai.response.model")
+                .or_else(|| extract_string_attr(attrs, "gen_ai.request.model")),
+            model_parameters: request_parameters(attrs),
+            input: extract_string_attr(attrs, "traceloop.entity.input")
+                .map(json_or_string)
+                .or_else(|| indexed_messages(attrs, "gen_ai.prompt.")),
+            output: extract_string_attr(attrs, "traceloop.entity.output")
+                .map(json_or_string)
+                .or_else(|| indexed_messages(attrs, "gen_ai.completion.")),
+            usage: Usage {
+                prompt: extract_i64_attr(attrs, "gen_ai.usage.prompt_tokens")
+                    .or_else(|| extract_i64_attr(attrs, "gen_ai.usage.input_tokens")),
+                completion: extract_i64_attr(attrs, "gen_ai.usage.completion_tokens")
+                    .or_else(|| extract_i64_attr(attrs, "gen_ai.usage.output_tokens")),
+                total: extract_i64_attr(attrs, "llm.usage.total_tokens"),
+            }
+            .with_derived_total(),
+            level: None,
+            status_message: None,
+            trace: TraceAttributes {
+                name: extract_string_attr(attrs, "traceloop.workflow.name"),
+                user_id,
+                session_id,
+                tags: None,
+                metadata: association,
+            },
+        }
+    }
+}
+
+/// Messages flattened as `<prefix><index>.<field>`, e.g.
+/// `gen_ai.prompt.0.role` / `gen_ai.prompt.0.content`, in index order.
+fn indexed_messages(attrs: &[OtelKeyValue], prefix: &str) -> Option<JsonValue> {
+    let mut messages: BTreeMap<usize, JsonMap<String, JsonValue>> = BTreeMap::new();
+    for kv in attrs {
+        let Some((index, field)) = kv
+            .key
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.split_once('.'))
+        else {
+            continue;
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            continue;
+        };
+        messages
+            .entry(index)
+            .or_default()
+            .insert(field.to_string(), otel_value_to_json(&kv.value));
+    }
+    (!messages.is_empty())
+        .then(|| JsonValue::Array(messages.into_values().map(JsonValue::Object).collect()))
+}
@@ -0,0 +1,89 @@
+//! OTLP/gRPC trace receiver (`opentelemetry.proto.collector.trace.v1.TraceService/Export`)
+//! for SDKs exporting with `OTEL_EXPORTER_OTLP_PROTOCOL=grpc`.
+//!
+//! Requests carry the same credentials as OTLP/HTTP in the `authorization`
+//! metadata entry and go through the same ingest network policy, mapping, cost
+//! limit and write mode.
+
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    trace_service_server::{TraceService, TraceServiceServer},
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use std::sync::Arc;
+use tonic::{codec::CompressionEncoding, Request, Response, Status};
+
+use crate::{
+    http::{auth::authenticate, error::ApiError, network::GroupPolicy},
+    ingest::otlp::enqueue_protobuf,
+    state::AppState,
+};
+
+pub(crate) struct OtlpTraceService {
+    state: AppState,
+    /// The ingest group's IP allowlist and client certificate mode.
+    policy: Arc<GroupPolicy>,
+}
+
+impl OtlpTraceService {
+    /// gRPC service accepting gzip- or zstd-compressed requests up to
+    /// `max_message_bytes`.
+    pub(crate) fn server(
+        state: AppState,
+        policy: Arc<GroupPolicy>,
+        max_message_bytes: usize,
+    ) -> TraceServiceServer<Self> {
+        TraceServiceServer::new(Self { state, policy })
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd)
+            .max_decoding_message_size(max_message_bytes)
+    }
+}
+
+#[tonic::async_trait]
+impl TraceService for OtlpTraceService {
+    async fn export(
+        &self,
+        request: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        let headers = request.metadata().clone().into_headers();
+        let has_cert = request.peer_certs().is_some_and(|certs| !certs.is_empty());
+        let cert_authenticated = self
+            .policy
+            .check(request.remote_addr().map(|a| a.ip()), &headers, has_cert)
+            .map_err(to_status)?;
+        let scope = authenticate(&self.state, &headers, true, cert_authenticated)
+            .ok_or_else(|| Status::unauthenticated("Unauthorized"))?;
+        scope.check_write().map_err(to_status)?;
+
+        enqueue_protobuf(&self.state, &scope, &headers, request.into_inner())
+            .await
+            .map_err(to_status)?;
+
+        Ok(Response::new(ExportTraceServiceResponse {
+            partial_success: None,
+        }))
+    }
+}
+
+/// OTLP exporters retry `RESOURCE_EXHAUSTED` and `UNAVAILABLE` only.
+fn to_status(err: ApiError) -> Status {
+    match err {
+        ApiError::BadRequest(m) => Status::invalid_argument(m),
+        ApiError::Forbidden(m) => Status::permission_denied(m),
+        ApiError::NotFound => Status::not_found("Not Found"),
+        ApiError::Conflict(m) => Status::already_exists(m),
+        ApiError::QueueFull(_) | ApiError::RateLimited { .. } => {
+            Status::resource_exhausted("Too Many Requests")
+        }
+        ApiError::ServiceUnavailable => Status::unavailable("Service Unavailable"),
+        ApiError::WritesPaused { mode, .. } => {
+            Status::unavailable(format!("Writes are paused ({mode})"))
+        }
+        // Not retried: the quota lasts until the end of the month.
+        ApiError::QuotaExceeded(quota) => Status::failed_precondition(quota.message()),
+        ApiError::Sqlx(err) => {
+            tracing::error!(error = %err, "sqlx error");
+            Status::internal("Internal Server Error")
+        }
+    }
+}
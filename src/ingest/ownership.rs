@@ -0,0 +1,135 @@
+//! Ingest writes stay in their project: a trace or observation id stored
+//! under one project is never written through another. Requests naming such
+//! an id are rejected with `403`, and the ingest worker drops the writes that
+//! get past that check (queued before the other project stored the id, or
+//! read from Kafka or the write-ahead log). A payload can thus neither move a
+//! trace into its own project nor add observations to another project's
+//! trace.
+
+use std::collections::HashSet;
+
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::{http::error::ApiError, ingest::batch::BatchIngestRequest};
+
+/// Ids stored under another project than the one they are written to.
+#[derive(Debug, Default)]
+pub(crate) struct Foreign {
+    pub traces: HashSet<Uuid>,
+    pub observations: HashSet<Uuid>,
+}
+
+/// The ids among `traces` and `observations`, each paired with the project
+/// it is written to, that are stored under another project.
+pub(crate) async fn foreign(
+    executor: impl PgExecutor<'_>,
+    traces: &[(Uuid, &str)],
+    observations: &[(Uuid, &str)],
+) -> Result<Foreign, sqlx::Error> {
+    if traces.is_empty() && observations.is_empty() {
+        return Ok(Foreign::default());
+    }
+    let (trace_ids, trace_projects): (Vec<Uuid>, Vec<&str>) = traces.iter().copied().unzip();
+    let (observation_ids, observation_projects): (Vec<Uuid>, Vec<&str>) =
+        observations.iter().copied().unzip();
+    let rows: Vec<(Uuid, bool)> = sqlx::query_as(
+        r#"
+SELECT k.id, TRUE
+FROM UNNEST($1::uuid[], $2::text[]) AS k(id, project_id)
+JOIN traces t ON t.id = k.id AND t.project_id <> k.project_id
+UNION
+SELECT k.id, FALSE
+FROM UNNEST($3::uuid[], $4::text[]) AS k(id, project_id)
+JOIN observations o ON o.id = k.id AND o.project_id <> k.project_id
+        "#,
+    )
+    .bind(&trace_ids)
+    .bind(&trace_projects)
+    .bind(&observation_ids)
+    .bind(&observation_projects)
+    .fetch_all(executor)
+    .await?;
+    let mut foreign = Foreign::default();
+    for (id, is_trace) in rows {
+        if is_trace {
+            foreign.traces.insert(id);
+        } else {
+            foreign.observations.insert(id);
+        }
+    }
+    Ok(foreign)
+}
+
+/// The trace and observation ids `payloads` write, with their projects.
+#[allow(clippy::type_complexity)]
+fn keys<'a>(
+    payloads: &'a [BatchIngestRequest],
+    default_project_id: &'a str,
+) -> (Vec<(Uuid, &'a str)>, Vec<(Uuid, &'a str)>) {
+    let mut traces = Vec::new();
+    let mut observations = Vec::new();
+    for payload in payloads {
+        if let Some(trace) = &payload.trace {
+            let project_id = trace.projectId.as_deref().unwrap_or(default_project_id);
+            traces.push((trace.id, project_id));
+        }
+        for obs in &payload.observations {
+            let project_id = obs.projectId.as_deref().unwrap_or(default_project_id);
+            traces.push((obs.traceId, project_id));
+            observations.push((obs.id, project_id));
+        }
+    }
+    (traces, observations)
+}
+
+/// Rejects `payloads` when they write an id of another project.
+pub(crate) async fn check(
+    pool: &PgPool,
+    payloads: &[BatchIngestRequest],
+    default_project_id: &str,
+) -> Result<(), ApiError> {
+    let (traces, observations) = keys(payloads, default_project_id);
+    let foreign = foreign(pool, &traces, &observations).await?;
+    if let Some(id) = foreign.traces.iter().next() {
+        return Err(ApiError::Forbidden(format!(
+            "trace {id} belongs to another project"
+        )));
+    }
+    if let Some(id) = foreign.observations.iter().next() {
+        return Err(ApiError::Forbidden(format!(
+            "observation {id} belongs to another project"
+        )));
+    }
+    Ok(())
+}
+
+/// Drops the writes of `payload` to ids of another project: the trace, and
+/// observations whose id or trace is stored elsewhere. Run under the traces'
+/// write locks. Returns how many were dropped.
+pub(crate) async fn retain_owned(
+    conn: &mut PgConnection,
+    payload: &mut BatchIngestRequest,
+    default_project_id: &str,
+) -> Result<usize, sqlx::Error> {
+    let (traces, observations) = keys(std::slice::from_ref(payload), default_project_id);
+    let foreign = foreign(conn, &traces, &observations).await?;
+    if foreign.traces.is_empty() && foreign.observations.is_empty() {
+        return Ok(0);
+    }
+    let mut dropped = 0;
+    if payload
+        .trace
+        .as_ref()
+        .is_some_and(|t| foreign.traces.contains(&t.id))
+    {
+        payload.trace = None;
+        dropped += 1;
+    }
+    let before = payload.observations.len();
+    payload
+        .observations
+        .retain(|o| !foreign.observations.contains(&o.id) && !foreign.traces.contains(&o.traceId));
+    dropped += before - payload.observations.len();
+    Ok(dropped)
+}
@@ -0,0 +1,184 @@
+//! Model pricing registry and ingest-time cost calculation.
+//!
+//! Rows of the `models` table match observation model names by regular
+//! expression. An observation that reports token counts but no calculated
+//! cost is priced by the most specific matching model: one of its own project
+//! before a shared one, then the latest `start_date` not after the
+//! observation's start time. Prices sent with the observation take precedence
+//! over the registry.
+//!
+//! The ingest worker keeps a snapshot of the table and re-reads it every
+//! [`RELOAD_INTERVAL`], or right after a change through `/api/public/models`
+//! on the same instance.
+
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::ingest::batch::ObservationIngest;
+
+/// How long a snapshot of `models` is used before it is re-read.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, sqlx::FromRow)]
+struct ModelRow {
+    id: Uuid,
+    project_id: Option<String>,
+    match_pattern: String,
+    start_date: Option<DateTime<Utc>>,
+    unit: String,
+    input_price: Option<f64>,
+    output_price: Option<f64>,
+    total_price: Option<f64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ModelPrice {
+    id: Uuid,
+    project_id: Option<String>,
+    pattern: Regex,
+    start_date: Option<DateTime<Utc>>,
+    unit: String,
+    input_price: Option<f64>,
+    output_price: Option<f64>,
+    total_price: Option<f64>,
+}
+
+/// One snapshot of the `models` table.
+#[derive(Debug, Default)]
+pub(crate) struct PriceTable {
+    models: Vec<ModelPrice>,
+}
+
+impl PriceTable {
+    fn from_rows(rows: Vec<ModelRow>) -> Self {
+        let models = rows
+            .into_iter()
+            .filter_map(|r| match Regex::new(&r.match_pattern) {
+                Ok(pattern) => Some(ModelPrice {
+                    id: r.id,
+                    project_id: r.project_id,
+                    pattern,
+                    start_date: r.start_date,
+                    unit: r.unit,
+                    input_price: r.input_price,
+                    output_price: r.output_price,
+                    total_price: r.total_price,
+                }),
+                Err(err) => {
+                    tracing::warn!(model_id = %r.id, error = %err, "skipping model with invalid match pattern");
+                    None
+                }
+            })
+            .collect();
+        Self { models }
+    }
+
+    /// The model pricing `model` in `project_id` at `at`.
+    pub(crate) fn find(
+        &self,
+        project_id: &str,
+        model: &str,
+        at: DateTime<Utc>,
+    ) -> Option<&ModelPrice> {
+        self.models
+            .iter()
+            .filter(|m| m.project_id.as_deref().is_none_or(|p| p == project_id))
+            .filter(|m| m.start_date.is_none_or(|d| d <= at))
+            .filter(|m| m.pattern.is_match(model))
+            .max_by_key(|m| (m.project_id.is_some(), m.start_date))
+    }
+
+    /// Fills in prices and calculated costs of an observation that reports
+    /// token counts but no cost.
+    pub(crate) fn apply(&self, obs: &mut ObservationIngest, project_id: &str) {
+        if obs.calculatedInputCost.is_some()
+            || obs.calculatedOutputCost.is_some()
+            || obs.calculatedTotalCost.is_some()
+        {
+            return;
+        }
+        if obs.promptTokens.is_none() && obs.completionTokens.is_none() && obs.totalTokens.is_none()
+        {
+            return;
+        }
+        if obs.inputPrice.is_none() && obs.outputPrice.is_none() && obs.totalPrice.is_none() {
+            let Some(model) = obs.model.as_deref() else {
+                return;
+            };
+            let at = obs.startTime.unwrap_or_else(Utc::now);
+            let Some(price) = self.find(project_id, model, at) else {
+                return;
+            };
+            if obs
+                .unit
+                .as_deref()
+                .is_some_and(|u| !u.eq_ignore_ascii_case(&price.unit))
+            {
+                return;
+            }
+            obs.inputPrice = price.input_price;
+            obs.outputPrice = price.output_price;
+            obs.totalPrice = price.total_price;
+            obs.modelId.get_or_insert_with(|| price.id.to_string());
+        }
+
+        let input = obs
+            .promptTokens
+            .zip(obs.inputPrice)
+            .map(|(n, p)| n as f64 * p);
+        let output = obs
+            .completionTokens
+            .zip(obs.outputPrice)
+            .map(|(n, p)| n as f64 * p);
+        let total = match (input, output) {
+            (None, None) => obs
+                .totalTokens
+                .zip(obs.totalPrice)
+                .map(|(n, p)| n as f64 * p),
+            (i, o) => Some(i.unwrap_or(0.0) + o.unwrap_or(0.0)),
+        };
+        obs.calculatedInputCost = input;
+        obs.calculatedOutputCost = output;
+        obs.calculatedTotalCost = total;
+    }
+}
+
+/// Cached [`PriceTable`], shared by the ingest worker and the models API.
+#[derive(Debug, Default)]
+pub(crate) struct PriceRegistry {
+    cached: RwLock<Option<(Instant, Arc<PriceTable>)>>,
+}
+
+impl PriceRegistry {
+    /// The current snapshot, re-read from the database when stale.
+    pub(crate) async fn snapshot(&self, pool: &PgPool) -> Result<Arc<PriceTable>, sqlx::Error> {
+        if let Some((loaded_at, table)) = self.cached.read().expect("price cache lock").as_ref() {
+            if loaded_at.elapsed() < RELOAD_INTERVAL {
+                return Ok(table.clone());
+            }
+        }
+        let rows: Vec<ModelRow> = sqlx::query_as(
+            r#"
+SELECT id, project_id, match_pattern, start_date, unit, input_price, output_price, total_price
+FROM models
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+        let table = Arc::new(PriceTable::from_rows(rows));
+        *self.cached.write().expect("price cache lock") = Some((Instant::now(), table.clone()));
+        Ok(table)
+    }
+
+    /// Forces a re-read on the next [`PriceRegistry::snapshot`].
+    pub(crate) fn invalidate(&self) {
+        *self.cached.write().expect("price cache lock") = None;
+    }
+}
@@ -0,0 +1,159 @@
+//! Provider inference for observations.
+//!
+//! Resolution order: the explicit `provider` field, provider-like metadata
+//! attributes (`gen_ai.system`, `ls_provider`, ...), the request endpoint host,
+//! and finally well-known model name prefixes.
+
+use serde_json::Value as JsonValue;
+
+const PROVIDER_ATTRS: &[&str] = &[
+    "provider",
+    "gen_ai.system",
+    "gen_ai.provider.name",
+    "llm.provider",
+    "llm.system",
+    "ls_provider",
+    "model_provider",
+];
+
+const ENDPOINT_ATTRS: &[&str] = &[
+    "endpoint",
+    "base_url",
+    "api_base",
+    "server.address",
+    "url.full",
+    "http.url",
+];
+
+/// Infers the provider of an observation from the raw (pre-alias) model name.
+pub(crate) fn detect(
+    explicit: Option<&str>,
+    model: Option<&str>,
+    metadata: Option<&JsonValue>,
+) -> Option<String> {
+    if let Some(p) = explicit.map(str::trim).filter(|p| !p.is_empty()) {
+        return Some(normalize(p));
+    }
+    let meta = metadata.and_then(JsonValue::as_object);
+    let meta_str = |key: &str| meta.and_then(|m| m.get(key)).and_then(JsonValue::as_str);
+
+    if let Some(p) = PROVIDER_ATTRS.iter().find_map(|k| meta_str(k)) {
+        if !p.trim().is_empty() {
+            return Some(normalize(p));
+        }
+    }
+    if let Some(p) = ENDPOINT_ATTRS
+        .iter()
+        .find_map(|k| meta_str(k))
+        .and_then(from_endpoint)
+    {
+        return Some(p.to_string());
+    }
+    model.and_then(from_model).map(str::to_string)
+}
+
+/// Maps the many spellings providers go by onto one name.
+fn normalize(raw: &str) -> String {
+    let p = raw.trim().to_ascii_lowercase();
+    match p.as_str() {
+        "openai" | "open_ai" | "openai_chat" => "openai",
+        "azure" | "azure_openai" | "azure-openai" | "az.ai.openai" => "azure",
+        "anthropic" | "claude" => "anthropic",
+        "google" | "gemini" | "vertex_ai" | "vertexai" | "gcp.gemini" | "gcp.vertex_ai"
+        | "google_genai" => "google",
+        "aws.bedrock" | "bedrock" | "aws_bedrock" => "bedrock",
+        "mistral" | "mistral_ai" | "mistralai" => "mistral",
+        "cohere" => "cohere",
+        "deepseek" => "deepseek",
+        "vllm" | "ollama" | "local" | "llama.cpp" | "lmstudio" | "tgi" => "local",
+        _ => return p,
+    }
+    .to_string()
+}
+
+fn from_endpoint(url: &str) -> Option<&'static str> {
+    let url = url.to_ascii_lowercase();
+    let host = url
+        .split_once("://")
+        .map_or(url.as_str(), |(_, rest)| rest)
+        .split(['/', '?'])
+        .next()
+        .unwrap_or_default();
+    let hostname = host.rsplit_once(':').map_or(host, |(h, _)| h);
+
+    if hostname.ends_with(".openai.azure.com") {
+        Some("azure")
+    } else if hostname.ends_with("openai.com") {
+        Some("openai")
+    } else if hostname.ends_with("anthropic.com") {
+        Some("anthropic")
+    } else if hostname.ends_with("googleapis.com") {
+        Some("google")
+    } else if hostname.ends_with("mistral.ai") {
+        Some("mistral")
+    } else if hostname.ends_with("cohere.com") || hostname.ends_with("cohere.ai") {
+        Some("cohere")
+    } else if hostname.ends_with("deepseek.com") {
+        Some("deepseek")
+    } else if hostname.contains("bedrock") && hostname.ends_with("amazonaws.com") {
+        Some("bedrock")
+    } else if matches!(hostname, "localhost" | "127.0.0.1" | "0.0.0.0" | "[::1]")
+        || hostname.ends_with(".local")
+        || host.ends_with(":11434")
+    {
+        Some("local")
+    } else {
+        None
+    }
+}
+
+fn from_model(model: &str) -> Option<&'static str> {
+    let m = model.trim().to_ascii_lowercase();
+    if let Some((prefix, _)) = m.split_once('/') {
+        let p = normalize(prefix);
+        if let Some(known) = KNOWN.iter().find(|k| **k == p) {
+            return Some(known);
+        }
+    }
+    let starts = |prefixes: &[&str]| prefixes.iter().any(|p| m.starts_with(p));
+    if starts(&[
+        "gpt-",
+        "gpt4",
+        "chatgpt",
+        "o1",
+        "o3",
+        "o4",
+        "text-embedding-",
+        "dall-e",
+        "whisper",
+        "tts-",
+        "davinci",
+        "babbage",
+    ]) {
+        Some("openai")
+    } else if starts(&["claude"]) {
+        Some("anthropic")
+    } else if starts(&["gemini", "gemma", "palm", "text-bison", "chat-bison"]) {
+        Some("google")
+    } else if starts(&["mistral", "mixtral", "codestral", "pixtral", "ministral"]) {
+        Some("mistral")
+    } else if starts(&["command", "embed-english", "embed-multilingual"]) {
+        Some("cohere")
+    } else if starts(&["deepseek"]) {
+        Some("deepseek")
+    } else {
+        None
+    }
+}
+
+const KNOWN: &[&str] = &[
+    "openai",
+    "azure",
+    "anthropic",
+    "google",
+    "bedrock",
+    "mistral",
+    "cohere",
+    "deepseek",
+    "local",
+];
@@ -0,0 +1,292 @@
+//! Raw ingest history: with `XTRACE_RAW_INGEST_TTL_SECS`, every accepted ingest
+//! payload (decompressed body and redacted headers) is kept for the TTL with
+//! the traces it contributed to, and served by `GET /api/admin/traces/{id}/raw`
+//! to debug SDK and mapping issues.
+//!
+//! Recording never fails or slows down ingest: payloads go through a bounded
+//! channel to [`raw_ingest_worker`] and are dropped when it is full.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use sqlx::{PgPool, QueryBuilder};
+use std::{collections::BTreeSet, time::Duration};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{http::error::ApiError, ingest::batch::BatchIngestRequest, state::AppState};
+
+/// Headers stored as `[redacted]`.
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "x-api-key",
+];
+
+/// How often expired payloads are deleted.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Payloads inserted per statement.
+const INSERT_BATCH: usize = 100;
+
+/// Payloads on the way to the database; further ones are dropped.
+pub(crate) const RAW_INGEST_QUEUE: usize = 1000;
+
+pub(crate) struct RawIngest {
+    project_id: String,
+    trace_ids: Vec<Uuid>,
+    endpoint: &'static str,
+    content_type: Option<String>,
+    headers: JsonValue,
+    body: Vec<u8>,
+    body_truncated: bool,
+    received_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Where an ingest payload came from.
+pub(crate) struct RawSource<'a> {
+    pub endpoint: &'static str,
+    pub project_id: &'a str,
+    pub headers: &'a HeaderMap,
+    /// Decompressed request body.
+    pub body: &'a [u8],
+}
+
+/// Hands accepted payloads to [`raw_ingest_worker`].
+pub(crate) struct RawIngestRecorder {
+    tx: mpsc::Sender<RawIngest>,
+    ttl: chrono::Duration,
+    max_body_bytes: usize,
+}
+
+impl RawIngestRecorder {
+    pub(crate) fn new(tx: mpsc::Sender<RawIngest>, ttl: Duration, max_body_bytes: usize) -> Self {
+        Self {
+            tx,
+            ttl: chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+            max_body_bytes,
+        }
+    }
+
+    /// Captures `source` for the traces in `payloads`; [`Self::send`] it once
+    /// the payloads are accepted.
+    pub(crate) fn capture(
+        &self,
+        source: &RawSource<'_>,
+        payloads: &[BatchIngestRequest],
+    ) -> Option<RawIngest> {
+        let RawSource {
+            endpoint,
+            project_id,
+            headers,
+            body,
+        } = *source;
+        let trace_ids: BTreeSet<Uuid> = payloads
+            .iter()
+            .flat_map(|p| {
+                p.trace
+                    .as_ref()
+                    .map(|t| t.id)
+                    .into_iter()
+                    .chain(p.observations.iter().map(|o| o.traceId))
+            })
+            .collect();
+        if trace_ids.is_empty() {
+            return None;
+        }
+        let received_at = Utc::now();
+        Some(RawIngest {
+            project_id: project_id.to_string(),
+            trace_ids: trace_ids.into_iter().collect(),
+            endpoint,
+            content_type: headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            headers: redacted_headers(headers),
+            body: body[..body.len().min(self.max_body_bytes)].to_vec(),
+            body_truncated: body.len() > self.max_body_bytes,
+            received_at,
+            expires_at: received_at
+                .checked_add_signed(self.ttl)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        })
+    }
+
+    pub(crate) fn send(&self, raw: RawIngest) {
+        if let Err(err) = self.tx.try_send(raw) {
+            let raw = err.into_inner();
+            tracing::debug!(
+                endpoint = raw.endpoint,
+                "raw ingest queue full, payload not recorded"
+            );
+        }
+    }
+}
+
+fn redacted_headers(headers: &HeaderMap) -> JsonValue {
+    let mut out = JsonMap::new();
+    for (name, value) in headers {
+        let name = name.as_str();
+        let value = if REDACTED_HEADERS.contains(&name) {
+            "[redacted]".to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        match out.get_mut(name) {
+            Some(JsonValue::String(prev)) => {
+                prev.push_str(", ");
+                prev.push_str(&value);
+            }
+            _ => {
+                out.insert(name.to_string(), JsonValue::String(value));
+            }
+        }
+    }
+    JsonValue::Object(out)
+}
+
+/// Writes recorded payloads and deletes expired ones. Keeps purging when
+/// recording is off (no [`RawIngestRecorder`]), so payloads stored by an
+/// earlier run or another instance still expire.
+pub(crate) async fn raw_ingest_worker(pool: PgPool, mut rx: mpsc::Receiver<RawIngest>) {
+    let mut purge = tokio::time::interval(PURGE_INTERVAL);
+    let mut buf = Vec::with_capacity(INSERT_BATCH);
+    let mut recording = true;
+    loop {
+        tokio::select! {
+            n = rx.recv_many(&mut buf, INSERT_BATCH), if recording => {
+                if n == 0 {
+                    recording = false;
+                    continue;
+                }
+                if let Err(err) = insert_raw(&pool, buf.drain(..)).await {
+                    tracing::error!(error = %err, "raw ingest: insert failed");
+                }
+            }
+            _ = purge.tick() => {
+                let result = sqlx::query("DELETE FROM raw_ingest WHERE expires_at < NOW()")
+                    .execute(&pool)
+                    .await;
+                match result {
+                    Ok(r) if r.rows_affected() > 0 => {
+                        tracing::debug!(deleted = r.rows_affected(), "raw ingest: purged expired payloads")
+                    }
+                    Ok(_) => {}
+                    Err(err) => tracing::error!(error = %err, "raw ingest: purge failed"),
+                }
+            }
+        }
+    }
+}
+
+async fn insert_raw(
+    pool: &PgPool,
+    rows: impl Iterator<Item = RawIngest>,
+) -> Result<(), sqlx::Error> {
+    let mut builder: QueryBuilder<'_, sqlx::Postgres> = QueryBuilder::new(
+        "INSERT INTO raw_ingest (project_id, trace_ids, endpoint, content_type, headers, body, body_truncated, received_at, expires_at) ",
+    );
+    builder.push_values(rows, |mut b, raw| {
+        b.push_bind(raw.project_id)
+            .push_bind(raw.trace_ids)
+            .push_bind(raw.endpoint)
+            .push_bind(raw.content_type)
+            .push_bind(raw.headers)
+            .push_bind(raw.body)
+            .push_bind(raw.body_truncated)
+            .push_bind(raw.received_at)
+            .push_bind(raw.expires_at);
+    });
+    builder.build().execute(pool).await?;
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct RawIngestRow {
+    project_id: String,
+    endpoint: String,
+    content_type: Option<String>,
+    headers: JsonValue,
+    body: Vec<u8>,
+    body_truncated: bool,
+    received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RawIngestDto {
+    received_at: DateTime<Utc>,
+    project_id: String,
+    endpoint: String,
+    content_type: Option<String>,
+    headers: JsonValue,
+    /// `json`, `text` or `base64` (e.g. OTLP protobuf).
+    body_encoding: &'static str,
+    body: JsonValue,
+    body_truncated: bool,
+}
+
+impl From<RawIngestRow> for RawIngestDto {
+    fn from(row: RawIngestRow) -> Self {
+        let (body_encoding, body) = match serde_json::from_slice::<JsonValue>(&row.body) {
+            Ok(json) => ("json", json),
+            Err(_) => match String::from_utf8(row.body) {
+                Ok(text) => ("text", JsonValue::String(text)),
+                Err(err) => (
+                    "base64",
+                    JsonValue::String(
+                        base64::engine::general_purpose::STANDARD.encode(err.as_bytes()),
+                    ),
+                ),
+            },
+        };
+        Self {
+            received_at: row.received_at,
+            project_id: row.project_id,
+            endpoint: row.endpoint,
+            content_type: row.content_type,
+            headers: row.headers,
+            body_encoding,
+            body,
+            body_truncated: row.body_truncated,
+        }
+    }
+}
+
+/// Retained payloads that contributed to a trace, oldest first.
+pub(crate) async fn get_trace_raw(
+    State(state): State<AppState>,
+    Path(trace_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows: Vec<RawIngestRow> = sqlx::query_as(
+        r#"
+SELECT project_id, endpoint, content_type, headers, body, body_truncated, received_at
+FROM raw_ingest
+WHERE trace_ids @> ARRAY[$1]::uuid[] AND expires_at >= NOW()
+ORDER BY received_at, id
+"#,
+    )
+    .bind(trace_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let data: Vec<RawIngestDto> = rows.into_iter().map(Into::into).collect();
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "traceId": trace_id,
+            "enabled": state.raw_ingest.is_some(),
+            "data": data,
+        })),
+    ))
+}
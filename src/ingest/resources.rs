@@ -0,0 +1,115 @@
+//! Resource attribute dictionary.
+//!
+//! OTLP spans carry the attributes of the resource that emitted them (service,
+//! host, SDK, ...), and the OTLP mapping keeps them in each observation's
+//! `metadata` under `otel.resource`. Since they repeat on every span of a
+//! process, the ingest worker moves them to `otel_resources`, keyed by a
+//! UUIDv5 of their canonical JSON, and stores only `observations.resource_id`.
+//! Reads put them back (see `OBSERVATION_COLUMNS` in `http::traces`), so the
+//! API is unchanged.
+//!
+//! Rows written before the dictionary existed are moved by the
+//! `compact_resources` admin job.
+
+use serde_json::Value as JsonValue;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::ingest::batch::ObservationIngest;
+
+/// Metadata key holding the resource attributes.
+pub(crate) const RESOURCE_METADATA_KEY: &str = "otel.resource";
+
+/// Namespace of resource ids.
+const RESOURCE_NAMESPACE: Uuid = Uuid::from_u128(0x6a0c_9d52_51e7_4f4b_9a8e_0f3c_2d7b_1e45);
+
+/// Id of a set of resource attributes; equal attributes get equal ids
+/// regardless of key order.
+pub(crate) fn resource_id(attributes: &JsonValue) -> Uuid {
+    let mut canonical = String::new();
+    write_canonical(attributes, &mut canonical);
+    Uuid::new_v5(&RESOURCE_NAMESPACE, canonical.as_bytes())
+}
+
+fn write_canonical(value: &JsonValue, out: &mut String) {
+    match value {
+        JsonValue::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&JsonValue::String(k.clone()).to_string());
+                out.push(':');
+                write_canonical(v, out);
+            }
+            out.push('}');
+        }
+        JsonValue::Array(items) => {
+            out.push('[');
+            for (i, v) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(v, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// Takes the resource attributes out of an observation's metadata.
+pub(crate) fn extract(obs: &mut ObservationIngest) -> Option<(Uuid, JsonValue)> {
+    let JsonValue::Object(meta) = obs.metadata.as_mut()? else {
+        return None;
+    };
+    if !meta.get(RESOURCE_METADATA_KEY)?.is_object() {
+        return None;
+    }
+    let attributes = meta.remove(RESOURCE_METADATA_KEY)?;
+    Some((resource_id(&attributes), attributes))
+}
+
+/// Adds resources to the dictionary; known ids are skipped. Returns the
+/// number of resources added.
+pub(crate) async fn store(
+    conn: &mut PgConnection,
+    resources: &[(Uuid, JsonValue)],
+) -> Result<u64, sqlx::Error> {
+    if resources.is_empty() {
+        return Ok(0);
+    }
+    let (ids, attributes): (Vec<Uuid>, Vec<JsonValue>) = resources.iter().cloned().unzip();
+    let result = sqlx::query(
+        r#"
+INSERT INTO otel_resources (id, attributes)
+SELECT * FROM UNNEST($1::uuid[], $2::jsonb[])
+ON CONFLICT (id) DO NOTHING
+        "#,
+    )
+    .bind(ids)
+    .bind(attributes)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn resource_id_ignores_key_order() {
+        let a = json!({"service.name": "api", "host": {"name": "h1", "arch": "amd64"}});
+        let b = json!({"host": {"arch": "amd64", "name": "h1"}, "service.name": "api"});
+        assert_eq!(resource_id(&a), resource_id(&b));
+        assert_ne!(
+            resource_id(&a),
+            resource_id(&json!({"service.name": "worker"}))
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Server-side trace sampling.
+//!
+//! `XTRACE_SAMPLE_RATE` and per project `XTRACE_PROJECT_SAMPLE_RATES` set the
+//! share of traces the ingest worker stores, between 0 and 1. The decision
+//! is derived from the trace id, so every payload of a trace (and every
+//! instance) decides the same way. A sampled-out trace is still stored when
+//! one of its payloads has an `ERROR` observation (unless
+//! `XTRACE_SAMPLE_KEEP_ERRORS=false`), and from then on like any stored
+//! trace; its earlier payloads are lost.
+//!
+//! Sampling applies to traces and observations of every ingest endpoint, but
+//! not to scores and metrics.
+
+use std::collections::{HashMap, HashSet};
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::ingest::batch::BatchIngestRequest;
+
+const ERROR_LEVEL: &str = "ERROR";
+
+/// Global sample rate plus per-project overrides.
+#[derive(Debug)]
+pub(crate) struct SamplingPolicy {
+    default: f64,
+    per_project: HashMap<String, f64>,
+    keep_errors: bool,
+}
+
+fn parse_rate(v: &str) -> Result<f64, String> {
+    v.parse()
+        .ok()
+        .filter(|r: &f64| (0.0..=1.0).contains(r))
+        .ok_or_else(|| format!("invalid sample rate: {v:?} (expected a number from 0 to 1)"))
+}
+
+impl SamplingPolicy {
+    /// `projects` holds `project=rate` pairs separated by commas or newlines.
+    pub(crate) fn parse(
+        default: f64,
+        projects: Option<&str>,
+        keep_errors: bool,
+    ) -> Result<Self, String> {
+        if !(0.0..=1.0).contains(&default) {
+            return Err(format!(
+                "invalid sample rate: {default} (expected a number from 0 to 1)"
+            ));
+        }
+        let mut per_project = HashMap::new();
+        for entry in projects
+            .unwrap_or_default()
+            .split([',', '\n'])
+            .map(str::trim)
+        {
+            if entry.is_empty() {
+                continue;
+            }
+            let (project, rate) = entry
+                .split_once('=')
+                .map(|(p, v)| (p.trim(), v.trim()))
+                .filter(|(p, _)| !p.is_empty())
+                .ok_or_else(|| format!("invalid project sample rate entry: {entry:?}"))?;
+            per_project.insert(project.to_string(), parse_rate(rate)?);
+        }
+        Ok(Self {
+            default,
+            per_project,
+            keep_errors,
+        })
+    }
+
+    pub(crate) fn for_project(&self, project_id: &str) -> f64 {
+        self.per_project
+            .get(project_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Whether any trace can be sampled out.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.default < 1.0 || self.per_project.values().any(|r| *r < 1.0)
+    }
+
+    /// Whether `trace_id` is in the sampled share of its project.
+    fn samples(&self, project_id: &str, trace_id: Uuid) -> bool {
+        let rate = self.for_project(project_id);
+        rate >= 1.0 || unit_interval(trace_id) < rate
+    }
+
+    /// Removes the trace and observations of sampled-out traces from
+    /// `payload`, keeping traces with errors and traces already stored.
+    /// Returns the traces dropped.
+    pub(crate) async fn apply(
+        &self,
+        conn: &mut PgConnection,
+        payload: &mut BatchIngestRequest,
+        default_project_id: &str,
+    ) -> Result<usize, sqlx::Error> {
+        if !self.is_enabled() {
+            return Ok(0);
+        }
+        let mut dropped: HashSet<Uuid> = HashSet::new();
+        if let Some(trace) = &payload.trace {
+            let project_id = trace.projectId.as_deref().unwrap_or(default_project_id);
+            if !self.samples(project_id, trace.id) {
+                dropped.insert(trace.id);
+            }
+        }
+        for obs in &payload.observations {
+            let project_id = obs.projectId.as_deref().unwrap_or(default_project_id);
+            if !self.samples(project_id, obs.traceId) {
+                dropped.insert(obs.traceId);
+            }
+        }
+        if self.keep_errors {
+            for obs in &payload.observations {
+                if obs.level.as_deref() == Some(ERROR_LEVEL) {
+                    dropped.remove(&obs.traceId);
+                }
+            }
+        }
+        if dropped.is_empty() {
+            return Ok(0);
+        }
+        let ids: Vec<Uuid> = dropped.iter().copied().collect();
+        let stored: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM traces WHERE id = ANY($1)")
+            .bind(&ids)
+            .fetch_all(&mut *conn)
+            .await?;
+        for id in stored {
+            dropped.remove(&id);
+        }
+
+        if payload
+            .trace
+            .as_ref()
+            .is_some_and(|t| dropped.contains(&t.id))
+        {
+            payload.trace = None;
+        }
+        payload
+            .observations
+            .retain(|o| !dropped.contains(&o.traceId));
+        payload.complete.retain(|id| !dropped.contains(id));
+        Ok(dropped.len())
+    }
+}
+
+/// Maps a trace id to `[0, 1)`, uniformly for random and time-ordered ids.
+fn unit_interval(trace_id: Uuid) -> f64 {
+    let (high, low) = trace_id.as_u64_pair();
+    // splitmix64 finalizer, so ids differing in few bits spread out.
+    let mut x = high ^ low.rotate_left(32);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_keep_their_share_of_traces() {
+        let policy = SamplingPolicy::parse(0.1, Some("all=1, none=0"), true).unwrap();
+        let ids: Vec<Uuid> = (0..10_000).map(|_| Uuid::new_v4()).collect();
+        let kept = |project: &str| {
+            ids.iter()
+                .filter(|id| policy.samples(project, **id))
+                .count()
+        };
+        assert_eq!(kept("all"), ids.len());
+        assert_eq!(kept("none"), 0);
+        assert!((800..1200).contains(&kept("other")), "{}", kept("other"));
+        // Stable across calls.
+        assert_eq!(kept("other"), kept("other"));
+
+        assert!(SamplingPolicy::parse(1.5, None, true).is_err());
+        assert!(SamplingPolicy::parse(1.0, Some("p=abc"), true).is_err());
+    }
+}
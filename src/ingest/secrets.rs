@@ -0,0 +1,68 @@
+use regex::Regex;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+
+/// Detects likely credentials in ingested I/O and replaces them with `[REDACTED:<kind>]`.
+pub(crate) struct SecretScanner {
+    patterns: Vec<(&'static str, Regex)>,
+}
+
+impl SecretScanner {
+    pub fn new() -> Self {
+        let patterns = [
+            (
+                "private_key",
+                r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+            ),
+            ("aws_access_key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+            (
+                "aws_secret_key",
+                r#"(?i)aws_secret_access_key["']?\s*[:=]\s*["']?[A-Za-z0-9/+=]{40}"#,
+            ),
+            ("bearer_token", r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]{20,}=*"),
+            ("openai_key", r"\bsk-(?:proj-)?[A-Za-z0-9_\-]{20,}"),
+            ("github_token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+            ("slack_token", r"\bxox[abprs]-[A-Za-z0-9\-]{10,}"),
+        ];
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|(kind, re)| (kind, Regex::new(re).expect("valid secret pattern")))
+                .collect(),
+        }
+    }
+
+    /// Masks secrets inside `value` in place; returns match counts per kind.
+    pub fn scan(&self, value: &mut JsonValue) -> BTreeMap<&'static str, usize> {
+        let mut found = BTreeMap::new();
+        self.scan_into(value, &mut found);
+        found
+    }
+
+    fn scan_into(&self, value: &mut JsonValue, found: &mut BTreeMap<&'static str, usize>) {
+        match value {
+            JsonValue::String(s) => {
+                for (kind, re) in &self.patterns {
+                    let n = re.find_iter(s).count();
+                    if n > 0 {
+                        *found.entry(*kind).or_insert(0) += n;
+                        *s = re
+                            .replace_all(s, format!("[REDACTED:{kind}]").as_str())
+                            .into_owned();
+                    }
+                }
+            }
+            JsonValue::Array(items) => {
+                for item in items {
+                    self.scan_into(item, found);
+                }
+            }
+            JsonValue::Object(map) => {
+                for (_, v) in map.iter_mut() {
+                    self.scan_into(v, found);
+                }
+            }
+            _ => {}
+        }
+    }
+}
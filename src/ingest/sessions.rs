@@ -0,0 +1,176 @@
+//! Incrementally maintained per-session aggregates.
+//!
+//! The ingest worker collects the `(project_id, session_id)` pairs touched by a
+//! batch and recomputes just those rows inside the same transaction, so the
+//! sessions API never has to aggregate over `traces` on the fly.
+
+use std::collections::BTreeSet;
+
+use sqlx::{PgConnection, PgPool};
+use tokio::time::Duration;
+use uuid::Uuid;
+
+/// `(project_id, session_id)`.
+pub(crate) type SessionKey = (String, String);
+
+/// Adds the sessions currently owning `trace_ids` to `out`.
+pub(crate) async fn collect_sessions(
+    conn: &mut PgConnection,
+    trace_ids: &[Uuid],
+    out: &mut BTreeSet<SessionKey>,
+) -> Result<(), sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<SessionKey> = sqlx::query_as(
+        r#"
+SELECT DISTINCT project_id, session_id
+FROM traces
+WHERE id = ANY($1) AND session_id IS NOT NULL
+        "#,
+    )
+    .bind(trace_ids)
+    .fetch_all(conn)
+    .await?;
+    out.extend(rows);
+    Ok(())
+}
+
+/// Recomputes the aggregate rows for `keys`, deleting rows whose session no
+/// longer has any traces. Run inside a transaction: the session locks it
+/// takes are held until the transaction ends.
+pub(crate) async fn refresh(
+    conn: &mut PgConnection,
+    keys: &BTreeSet<SessionKey>,
+) -> Result<(), sqlx::Error> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let (projects, sessions): (Vec<&str>, Vec<&str>) =
+        keys.iter().map(|(p, s)| (p.as_str(), s.as_str())).unzip();
+
+    // Concurrent transactions (other workers or instances) may add traces to
+    // the same session; each recomputes after the other committed, so
+    // neither count misses the other's traces. Keys are sorted, so the locks
+    // are taken in the same order everywhere.
+    sqlx::query(
+        "SELECT pg_advisory_xact_lock(hashtextextended(k.project_id || '/' || k.session_id, 1)) \
+         FROM UNNEST($1::TEXT[], $2::TEXT[]) WITH ORDINALITY AS k(project_id, session_id, n) ORDER BY k.n",
+    )
+    .bind(&projects)
+    .bind(&sessions)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+WITH keys AS (
+  SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[]) AS k(project_id, session_id)
+),
+per_trace AS (
+  SELECT
+    t.project_id,
+    t.session_id,
+    COALESCE(t.total_cost, o.cost) AS cost,
+    LEAST(t."timestamp", o.first_start) AS started_at,
+    GREATEST(t."timestamp", o.last_end) AS ended_at
+  FROM traces t
+  JOIN keys k ON k.project_id = t.project_id AND k.session_id = t.session_id
+  LEFT JOIN LATERAL (
+    SELECT
+      SUM(calculated_total_cost) AS cost,
+      MIN(start_time) AS first_start,
+      MAX(COALESCE(end_time, start_time)) AS last_end
+    FROM observations
+    WHERE trace_id = t.id
+  ) o ON TRUE
+),
+upserted AS (
+  INSERT INTO session_aggregates (
+    project_id, session_id, trace_count, total_cost,
+    first_trace_at, last_activity_at, duration_seconds, updated_at
+  )
+  SELECT
+    project_id,
+    session_id,
+    COUNT(*),
+    COALESCE(SUM(cost), 0),
+    MIN(started_at),
+    MAX(ended_at),
+    EXTRACT(EPOCH FROM MAX(ended_at) - MIN(started_at))::DOUBLE PRECISION,
+    NOW()
+  FROM per_trace
+  GROUP BY project_id, session_id
+  ON CONFLICT (project_id, session_id) DO UPDATE SET
+    trace_count = EXCLUDED.trace_count,
+    total_cost = EXCLUDED.total_cost,
+    first_trace_at = EXCLUDED.first_trace_at,
+    last_activity_at = EXCLUDED.last_activity_at,
+    duration_seconds = EXCLUDED.duration_seconds,
+    updated_at = NOW()
+  RETURNING project_id, session_id
+)
+DELETE FROM session_aggregates s
+USING keys k
+WHERE s.project_id = k.project_id
+  AND s.session_id = k.session_id
+  AND NOT EXISTS (
+    SELECT 1 FROM upserted u
+    WHERE u.project_id = k.project_id AND u.session_id = k.session_id
+  )
+        "#,
+    )
+    .bind(projects)
+    .bind(sessions)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Finds sessions active in the last day whose aggregate row is missing or
+/// whose trace count drifted from `traces`, and recomputes them.
+async fn reconcile_recent(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let drifted: Vec<SessionKey> = sqlx::query_as(
+        r#"
+SELECT live.project_id, live.session_id
+FROM (
+  SELECT project_id, session_id, COUNT(*) AS trace_count
+  FROM traces
+  WHERE session_id IS NOT NULL
+    AND (project_id, session_id) IN (
+      SELECT DISTINCT project_id, session_id
+      FROM traces
+      WHERE session_id IS NOT NULL AND "timestamp" > NOW() - INTERVAL '1 day'
+    )
+  GROUP BY project_id, session_id
+) live
+LEFT JOIN session_aggregates s
+  ON s.project_id = live.project_id AND s.session_id = live.session_id
+WHERE s.trace_count IS DISTINCT FROM live.trace_count
+LIMIT 1000
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if drifted.is_empty() {
+        return Ok(0);
+    }
+    let keys: BTreeSet<SessionKey> = drifted.into_iter().collect();
+    let mut tx = pool.begin().await?;
+    refresh(&mut tx, &keys).await?;
+    tx.commit().await?;
+    Ok(keys.len())
+}
+
+pub(crate) async fn session_reconcile_worker(pool: PgPool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(300));
+    loop {
+        interval.tick().await;
+        match reconcile_recent(&pool).await {
+            Ok(0) => {}
+            Ok(n) => tracing::warn!(sessions = n, "repaired drifted session aggregates"),
+            Err(err) => tracing::error!(error = ?err, "session aggregate reconciliation failed"),
+        }
+    }
+}
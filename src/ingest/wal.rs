@@ -0,0 +1,452 @@
+//! Write-ahead log of accepted ingest payloads.
+//!
+//! With `XTRACE_WAL_DIR`, every payload is appended to a segment file in that
+//! directory and synced to disk before it is queued and acknowledged, so a
+//! crash between the `200` and the database write loses nothing: payloads
+//! still in the log are queued again at the next start.
+//!
+//! Appends are written by a thread of their own. It takes every append
+//! waiting when it is free and syncs them together, so concurrent requests
+//! share one `fsync` and request handlers never block on the disk.
+//!
+//! Segments are JSON lines named after the sequence number of their first
+//! record. A segment is deleted once it is no longer appended to and the
+//! ingest workers have written (or dead-lettered) all of its payloads.
+//! Progress inside a segment is not persisted, so a restart replays the
+//! already written payloads of surviving segments too. Writes are upserts
+//! and output deltas carry sequence numbers (`outputSeq`), so writing a
+//! payload again leaves traces and observations as they were.
+//!
+//! The log belongs to one instance: a lock on `LOCK` in the directory keeps
+//! a second one from replaying and deleting its segments.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::ingest::batch::BatchIngestRequest;
+
+/// Size after which a new segment is started.
+const SEGMENT_BYTES: u64 = 16 * 1024 * 1024;
+
+const SEGMENT_EXTENSION: &str = "wal";
+
+/// File locked by the instance owning the directory.
+const LOCK_FILE: &str = "LOCK";
+
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    seq: u64,
+    complete: &'a [Uuid],
+    payload: &'a RawValue,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    seq: u64,
+    #[serde(default)]
+    complete: Vec<Uuid>,
+    payload: BatchIngestRequest,
+}
+
+struct Segment {
+    /// Payloads not written yet.
+    pending: usize,
+    /// Still appended to.
+    active: bool,
+}
+
+/// Segments by first sequence number, shared by the writer and [`Wal::ack`].
+type Segments = Arc<Mutex<BTreeMap<u64, Segment>>>;
+
+/// A payload serialized by the request that appends it.
+struct Entry {
+    complete: Vec<Uuid>,
+    payload: Box<RawValue>,
+}
+
+/// Payloads of one request, given consecutive sequence numbers. The first
+/// one is sent back once they are on disk.
+struct Append {
+    entries: Vec<Entry>,
+    done: oneshot::Sender<io::Result<u64>>,
+}
+
+/// State of the writer thread.
+struct Writer {
+    dir: PathBuf,
+    file: File,
+    /// First sequence number of the active segment.
+    active_start: u64,
+    active_bytes: u64,
+    next_seq: u64,
+    segments: Segments,
+}
+
+pub(crate) struct Wal {
+    dir: PathBuf,
+    segments: Segments,
+    appends: mpsc::UnboundedSender<Append>,
+    /// Holds the directory lock until the log is dropped.
+    _lock: File,
+}
+
+impl Wal {
+    /// Opens the log in `dir` and returns the payloads left by the previous
+    /// run, oldest first. They stay in the log until acknowledged.
+    pub(crate) fn open(dir: impl Into<PathBuf>) -> io::Result<(Self, Vec<BatchIngestRequest>)> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK_FILE))?;
+        lock.try_lock().map_err(|err| match err {
+            TryLockError::WouldBlock => io::Error::other(format!(
+                "{} is in use by another instance; each instance needs its own directory",
+                dir.display()
+            )),
+            TryLockError::Error(err) => err,
+        })?;
+
+        let mut starts: Vec<u64> = fs::read_dir(&dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != SEGMENT_EXTENSION {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse().ok()
+            })
+            .collect();
+        starts.sort_unstable();
+
+        let mut replay = Vec::new();
+        let mut segments = BTreeMap::new();
+        let mut next_seq = 0;
+        for start in starts {
+            let path = segment_path(&dir, start);
+            let records = read_segment(&path)?;
+            next_seq = next_seq.max(start);
+            if records.is_empty() {
+                fs::remove_file(&path)?;
+                continue;
+            }
+            segments.insert(
+                start,
+                Segment {
+                    pending: records.len(),
+                    active: false,
+                },
+            );
+            for record in records {
+                next_seq = next_seq.max(record.seq + 1);
+                let mut payload = record.payload;
+                payload.complete = record.complete;
+                payload.wal_seq = Some(record.seq);
+                replay.push(payload);
+            }
+        }
+        if !replay.is_empty() {
+            tracing::info!(
+                payloads = replay.len(),
+                "write-ahead log: replaying unwritten payloads"
+            );
+        }
+
+        let file = create_segment(&dir, next_seq)?;
+        segments.insert(
+            next_seq,
+            Segment {
+                pending: 0,
+                active: true,
+            },
+        );
+        let segments = Arc::new(Mutex::new(segments));
+        let writer = Writer {
+            dir: dir.clone(),
+            file,
+            active_start: next_seq,
+            active_bytes: 0,
+            next_seq,
+            segments: segments.clone(),
+        };
+        let (appends, rx) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("xtrace-wal".to_string())
+            .spawn(move || writer.run(rx))?;
+        let wal = Self {
+            dir,
+            segments,
+            appends,
+            _lock: lock,
+        };
+        Ok((wal, replay))
+    }
+
+    /// Appends `payloads`, waits until they are synced to disk and sets their
+    /// sequence numbers. On failure none of them counts as logged.
+    pub(crate) async fn append_all(&self, payloads: &mut [BatchIngestRequest]) -> io::Result<()> {
+        if payloads.is_empty() {
+            return Ok(());
+        }
+        let entries = payloads
+            .iter()
+            .map(|payload| {
+                Ok(Entry {
+                    complete: payload.complete.clone(),
+                    payload: serde_json::value::to_raw_value(payload)?,
+                })
+            })
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        let (done, written) = oneshot::channel();
+        let stopped = || io::Error::other("write-ahead log writer stopped");
+        self.appends
+            .send(Append { entries, done })
+            .map_err(|_| stopped())?;
+        let first = written.await.map_err(|_| stopped())??;
+        for (seq, payload) in (first..).zip(payloads.iter_mut()) {
+            payload.wal_seq = Some(seq);
+        }
+        Ok(())
+    }
+
+    /// Marks payloads as written; fully written segments are deleted.
+    pub(crate) fn ack(&self, seqs: impl IntoIterator<Item = u64>) {
+        let mut segments = self.segments.lock().expect("wal lock");
+        for seq in seqs {
+            let Some((&start, segment)) = segments.range_mut(..=seq).next_back() else {
+                continue;
+            };
+            segment.pending = segment.pending.saturating_sub(1);
+            if segment.pending == 0 && !segment.active {
+                segments.remove(&start);
+                remove_segment(&self.dir, start);
+            }
+        }
+    }
+}
+
+impl Writer {
+    fn run(mut self, mut appends: mpsc::UnboundedReceiver<Append>) {
+        while let Some(first) = appends.blocking_recv() {
+            let mut group = vec![first];
+            while let Ok(next) = appends.try_recv() {
+                group.push(next);
+            }
+            match self.write(&group) {
+                Ok(firsts) => {
+                    for (append, first) in group.into_iter().zip(firsts) {
+                        let _ = append.done.send(Ok(first));
+                    }
+                }
+                Err(err) => {
+                    tracing::error!(error = %err, "write-ahead log: append failed");
+                    for append in group {
+                        let _ = append
+                            .done
+                            .send(Err(io::Error::new(err.kind(), err.to_string())));
+                    }
+                    // A torn line must not run into the next record.
+                    if let Err(err) = self.rotate() {
+                        tracing::error!(error = %err, "write-ahead log: failed to start a segment");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes and syncs `group` at once. Returns the first sequence number of
+    /// each append.
+    fn write(&mut self, group: &[Append]) -> io::Result<Vec<u64>> {
+        let mut buf = Vec::new();
+        let mut firsts = Vec::with_capacity(group.len());
+        let mut seq = self.next_seq;
+        let end = seq + group.iter().map(|a| a.entries.len() as u64).sum::<u64>();
+        for append in group {
+            firsts.push(seq);
+            for entry in &append.entries {
+                serde_json::to_writer(
+                    &mut buf,
+                    &RecordRef {
+                        seq,
+                        complete: &entry.complete,
+                        payload: &entry.payload,
+                    },
+                )?;
+                buf.push(b'\n');
+                seq += 1;
+            }
+        }
+        let written = self
+            .file
+            .write_all(&buf)
+            .and_then(|()| self.file.sync_data());
+        let count = (end - self.next_seq) as usize;
+        // Numbers of a failed write are not reused, so lines it left behind
+        // stay in their segment's range.
+        self.next_seq = end;
+        written?;
+        self.active_bytes += buf.len() as u64;
+        if let Some(segment) = self
+            .segments
+            .lock()
+            .expect("wal lock")
+            .get_mut(&self.active_start)
+        {
+            segment.pending += count;
+        }
+        if self.active_bytes >= SEGMENT_BYTES {
+            // The records are on disk; keep appending here if this fails.
+            if let Err(err) = self.rotate() {
+                tracing::error!(error = %err, "write-ahead log: failed to start a segment");
+            }
+        }
+        Ok(firsts)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let start = self.next_seq;
+        self.file = create_segment(&self.dir, start)?;
+        let previous = std::mem::replace(&mut self.active_start, start);
+        self.active_bytes = 0;
+        let mut segments = self.segments.lock().expect("wal lock");
+        if let Some(segment) = segments.get_mut(&previous) {
+            segment.active = false;
+            if segment.pending == 0 {
+                segments.remove(&previous);
+                remove_segment(&self.dir, previous);
+            }
+        }
+        segments.insert(
+            start,
+            Segment {
+                pending: 0,
+                active: true,
+            },
+        );
+        Ok(())
+    }
+}
+
+fn remove_segment(dir: &Path, start: u64) {
+    if let Err(err) = fs::remove_file(segment_path(dir, start)) {
+        tracing::warn!(error = %err, segment = start, "write-ahead log: failed to delete segment");
+    }
+}
+
+fn segment_path(dir: &Path, start: u64) -> PathBuf {
+    dir.join(format!("{start:020}.{SEGMENT_EXTENSION}"))
+}
+
+fn create_segment(dir: &Path, start: u64) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(dir, start))?;
+    // Make the new file itself durable.
+    File::open(dir)?.sync_all()?;
+    Ok(file)
+}
+
+/// Records of a segment. Torn lines (an append that failed or was cut by a
+/// crash, so never acknowledged) are skipped.
+fn read_segment(path: &Path) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for line in BufReader::new(File::open(path)?).split(b'\n') {
+        match serde_json::from_slice::<Record>(&line?) {
+            Ok(record) => records.push(record),
+            Err(err) => {
+                tracing::warn!(error = %err, path = %path.display(), "write-ahead log: skipping unreadable record");
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(trace_id: Uuid) -> BatchIngestRequest {
+        serde_json::from_value(serde_json::json!({"trace": {"id": trace_id}})).unwrap()
+    }
+
+    #[tokio::test]
+    async fn unacknowledged_payloads_are_replayed() {
+        let dir = std::env::temp_dir().join(format!("xtrace-wal-{}", Uuid::new_v4()));
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        {
+            let (wal, replay) = Wal::open(&dir).unwrap();
+            assert!(replay.is_empty());
+            let mut completing = payload(b);
+            completing.complete = vec![b];
+            let mut payloads = [payload(a), completing];
+            wal.append_all(&mut payloads).await.unwrap();
+            assert_eq!(payloads[1].wal_seq, payloads[0].wal_seq.map(|s| s + 1));
+            wal.ack(payloads[0].wal_seq);
+        }
+
+        let (wal, replay) = Wal::open(&dir).unwrap();
+        // The segment survived until both were acknowledged.
+        assert_eq!(replay.len(), 2);
+        assert_eq!(replay[1].complete, vec![b]);
+        let seqs: Vec<u64> = replay.iter().filter_map(|p| p.wal_seq).collect();
+        wal.ack(seqs);
+        drop(wal);
+
+        let (_, replay) = Wal::open(&dir).unwrap();
+        assert!(replay.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_appends_are_numbered_apart() {
+        let dir = std::env::temp_dir().join(format!("xtrace-wal-{}", Uuid::new_v4()));
+        let (wal, _) = Wal::open(&dir).unwrap();
+        let wal = Arc::new(wal);
+        let tasks: Vec<_> = (0..32)
+            .map(|_| {
+                let wal = wal.clone();
+                tokio::spawn(async move {
+                    let mut payloads = [payload(Uuid::new_v4()), payload(Uuid::new_v4())];
+                    wal.append_all(&mut payloads).await.unwrap();
+                    payloads.map(|p| p.wal_seq.unwrap())
+                })
+            })
+            .collect();
+        let mut seqs = Vec::new();
+        for task in tasks {
+            let [first, second] = task.await.unwrap();
+            assert_eq!(second, first + 1);
+            seqs.extend([first, second]);
+        }
+        seqs.sort_unstable();
+        seqs.dedup();
+        assert_eq!(seqs.len(), 64);
+        drop(wal);
+
+        let (_, replay) = Wal::open(&dir).unwrap();
+        assert_eq!(replay.len(), 64);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn directory_is_owned_by_one_log() {
+        let dir = std::env::temp_dir().join(format!("xtrace-wal-{}", Uuid::new_v4()));
+        let (wal, _) = Wal::open(&dir).unwrap();
+        assert!(Wal::open(&dir).is_err());
+        drop(wal);
+        assert!(Wal::open(&dir).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+//! `recompute_costs`: re-derives the calculated costs of one model's
+//! observations in a start time range from their stored token counts, e.g.
+//! after a pricing change.
+//!
+//! Unit prices come from the job, or else from each observation's stored
+//! `input_price` / `output_price` / `total_price`; observations without a
+//! price or token count keep their cost. Input and output costs sum to the
+//! total cost, `total_price` applies when neither is known. Each batch also
+//! shifts `traces.total_cost` by the change of its observations' costs and
+//! refreshes the owning session aggregates, in the same transaction.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{JobError, JobHandle};
+use crate::{http::error::ApiError, ingest::sessions, state::AppState};
+
+/// Observations updated per transaction.
+const BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RecomputeCostsParams {
+    /// Stored (canonical) model name, matched exactly.
+    model: String,
+    from_start_time: DateTime<Utc>,
+    to_start_time: DateTime<Utc>,
+    /// All projects when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_id: Option<String>,
+    /// Price per input unit (token); the stored price when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input_price: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_price: Option<f64>,
+    /// Price per total unit, for models without an input/output split.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    total_price: Option<f64>,
+}
+
+impl RecomputeCostsParams {
+    pub(super) fn validate(&self) -> Result<(), ApiError> {
+        if self.model.trim().is_empty() {
+            return Err(ApiError::BadRequest("model must not be empty".to_string()));
+        }
+        if self.from_start_time >= self.to_start_time {
+            return Err(ApiError::BadRequest(
+                "fromStartTime must be before toStartTime".to_string(),
+            ));
+        }
+        for (name, price) in [
+            ("inputPrice", self.input_price),
+            ("outputPrice", self.output_price),
+            ("totalPrice", self.total_price),
+        ] {
+            if price.is_some_and(|p| !p.is_finite() || p < 0.0) {
+                return Err(ApiError::BadRequest(format!(
+                    "{name} must be a non-negative number"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    /// Observations in scope, processed so far.
+    scanned: i64,
+    /// Observations whose costs or prices changed.
+    observations_updated: i64,
+    traces_updated: i64,
+    sessions_refreshed: usize,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct BatchResult {
+    scanned: i64,
+    last_start_time: Option<DateTime<Utc>>,
+    last_id: Option<Uuid>,
+    observations_updated: i64,
+    traces_updated: i64,
+    trace_ids: Vec<Uuid>,
+}
+
+pub(super) async fn recompute(
+    state: &AppState,
+    params: &RecomputeCostsParams,
+    job: &JobHandle,
+) -> Result<(), JobError> {
+    let total: i64 = sqlx::query_scalar(
+        r#"
+SELECT COUNT(*)
+FROM observations
+WHERE model = $1
+  AND start_time >= $2 AND start_time < $3
+  AND ($4::TEXT IS NULL OR project_id = $4)
+        "#,
+    )
+    .bind(&params.model)
+    .bind(params.from_start_time)
+    .bind(params.to_start_time)
+    .bind(params.project_id.as_deref())
+    .fetch_one(&state.pool)
+    .await?;
+    job.set_total(total).await?;
+
+    let mut progress = Progress::default();
+    let mut cursor: Option<(DateTime<Utc>, Uuid)> = None;
+    loop {
+        state
+            .write_gate
+            .try_reserve(1)
+            .map_err(|mode| JobError::WritesPaused(mode.as_str()))?;
+        let result = recompute_batch(state, params, cursor, &mut progress).await;
+        state.write_gate.release(1);
+        let batch = result?;
+        job.report(&progress, progress.scanned).await?;
+
+        match (batch.last_start_time, batch.last_id) {
+            (Some(start_time), Some(id)) if batch.scanned == BATCH_SIZE => {
+                cursor = Some((start_time, id))
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Reprices the next [`BATCH_SIZE`] observations after `cursor` in
+/// `(start_time, id)` order.
+async fn recompute_batch(
+    state: &AppState,
+    params: &RecomputeCostsParams,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+    progress: &mut Progress,
+) -> Result<BatchResult, sqlx::Error> {
+    let mut tx = state.pool.begin().await?;
+    let batch: BatchResult = sqlx::query_as(
+        r#"
+WITH batch AS (
+  SELECT
+    o.id,
+    o.start_time,
+    o.prompt_tokens * COALESCE($6, o.input_price) AS input_cost,
+    o.completion_tokens * COALESCE($7, o.output_price) AS output_cost,
+    o.total_tokens * COALESCE($8, o.total_price) AS unit_total_cost
+  FROM observations o
+  WHERE o.model = $1
+    AND o.start_time >= $2 AND o.start_time < $3
+    AND ($4::TEXT IS NULL OR o.project_id = $4)
+    AND ($5::TIMESTAMPTZ IS NULL OR (o.start_time, o.id) > ($5, $9::UUID))
+  ORDER BY o.start_time, o.id
+  LIMIT $10
+  FOR UPDATE
+),
+priced AS (
+  SELECT
+    o.id,
+    o.trace_id,
+    o.calculated_total_cost AS old_total,
+    COALESCE($6, o.input_price) AS input_price,
+    COALESCE($7, o.output_price) AS output_price,
+    COALESCE($8, o.total_price) AS total_price,
+    COALESCE(b.input_cost, o.calculated_input_cost) AS input_cost,
+    COALESCE(b.output_cost, o.calculated_output_cost) AS output_cost,
+    COALESCE(
+      CASE WHEN b.input_cost IS NULL AND b.output_cost IS NULL THEN NULL
+           ELSE COALESCE(b.input_cost, 0) + COALESCE(b.output_cost, 0) END,
+      b.unit_total_cost,
+      o.calculated_total_cost
+    ) AS total_cost
+  FROM batch b
+  JOIN observations o ON o.id = b.id
+),
+updated AS (
+  UPDATE observations o SET
+    input_price = p.input_price,
+    output_price = p.output_price,
+    total_price = p.total_price,
+    calculated_input_cost = p.input_cost,
+    calculated_output_cost = p.output_cost,
+    calculated_total_cost = p.total_cost,
+    updated_at = NOW()
+  FROM priced p
+  WHERE o.id = p.id
+    AND (o.input_price, o.output_price, o.total_price,
+         o.calculated_input_cost, o.calculated_output_cost, o.calculated_total_cost)
+      IS DISTINCT FROM
+        (p.input_price, p.output_price, p.total_price,
+         p.input_cost, p.output_cost, p.total_cost)
+  RETURNING o.trace_id, p.old_total, p.total_cost AS new_total
+),
+traces_updated AS (
+  UPDATE traces t SET
+    total_cost = t.total_cost + d.delta,
+    updated_at = NOW()
+  FROM (
+    SELECT trace_id, SUM(COALESCE(new_total, 0) - COALESCE(old_total, 0)) AS delta
+    FROM updated
+    GROUP BY trace_id
+  ) d
+  WHERE t.id = d.trace_id AND t.total_cost IS NOT NULL AND d.delta <> 0
+  RETURNING t.id
+),
+last AS (
+  SELECT start_time, id FROM batch ORDER BY start_time DESC, id DESC LIMIT 1
+)
+SELECT
+  (SELECT COUNT(*) FROM batch) AS scanned,
+  (SELECT start_time FROM last) AS last_start_time,
+  (SELECT id FROM last) AS last_id,
+  (SELECT COUNT(*) FROM updated) AS observations_updated,
+  (SELECT COUNT(*) FROM traces_updated) AS traces_updated,
+  ARRAY(SELECT DISTINCT trace_id FROM updated) AS trace_ids
+        "#,
+    )
+    .bind(&params.model)
+    .bind(params.from_start_time)
+    .bind(params.to_start_time)
+    .bind(params.project_id.as_deref())
+    .bind(cursor.map(|(start_time, _)| start_time))
+    .bind(params.input_price)
+    .bind(params.output_price)
+    .bind(params.total_price)
+    .bind(cursor.map(|(_, id)| id))
+    .bind(BATCH_SIZE)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Session totals fall back to observation costs for traces without a
+    // total, so every touched session is refreshed.
+    let mut touched = BTreeSet::new();
+    sessions::collect_sessions(&mut tx, &batch.trace_ids, &mut touched).await?;
+    sessions::refresh(&mut tx, &touched).await?;
+    tx.commit().await?;
+
+    progress.scanned += batch.scanned;
+    progress.observations_updated += batch.observations_updated;
+    progress.traces_updated += batch.traces_updated;
+    progress.sessions_refreshed += touched.len();
+    Ok(batch)
+}
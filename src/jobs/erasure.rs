@@ -0,0 +1,204 @@
+//! `erase_user`: data subject deletion. Deletes or anonymizes every trace of
+//! one `userId`, with its observations and scores.
+//!
+//! - `delete` (default) removes the traces like
+//!   [`crate::http::trace_delete`];
+//! - `anonymize` keeps them for aggregate statistics (timings, tokens, costs,
+//!   scores) but clears the user id and free-form content: trace and
+//!   observation input, output and metadata, observation status messages and
+//!   score comments and metadata.
+//!
+//! Either way the raw ingest payloads and offloaded payloads of the traces are
+//! deleted, and scores the user authored lose their `authorUserId`. Each batch
+//! is one transaction; erased traces no longer match the user, so an
+//! interrupted job can simply be started again.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{JobError, JobHandle};
+use crate::{
+    http::{error::ApiError, trace_delete},
+    state::AppState,
+};
+
+/// Traces erased per transaction.
+const BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErasureMode {
+    #[default]
+    Delete,
+    Anonymize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EraseUserParams {
+    user_id: String,
+    /// All projects when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_id: Option<String>,
+    #[serde(default)]
+    mode: ErasureMode,
+}
+
+impl EraseUserParams {
+    pub(super) fn validate(&self) -> Result<(), ApiError> {
+        if self.user_id.is_empty() {
+            return Err(ApiError::BadRequest("userId must not be empty".to_string()));
+        }
+        if self
+            .project_id
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest(
+                "projectId must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    traces_deleted: i64,
+    traces_anonymized: i64,
+    observations: i64,
+    scores: i64,
+    /// Scores whose `authorUserId` was cleared.
+    authored_scores: u64,
+}
+
+pub(super) async fn erase(
+    state: &AppState,
+    params: &EraseUserParams,
+    job: &JobHandle,
+) -> Result<(), JobError> {
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM traces WHERE user_id = $1 AND ($2::TEXT IS NULL OR project_id = $2)",
+    )
+    .bind(&params.user_id)
+    .bind(params.project_id.as_deref())
+    .fetch_one(&state.pool)
+    .await?;
+    job.set_total(total).await?;
+
+    let mut progress = Progress::default();
+    let mut processed = 0;
+    loop {
+        state
+            .write_gate
+            .try_reserve(1)
+            .map_err(|mode| JobError::WritesPaused(mode.as_str()))?;
+        let result = erase_batch(state, params, &mut progress).await;
+        state.write_gate.release(1);
+        let erased = result?;
+        processed += erased;
+        job.report(&progress, processed).await?;
+        if erased < BATCH_SIZE {
+            break;
+        }
+    }
+
+    state
+        .write_gate
+        .try_reserve(1)
+        .map_err(|mode| JobError::WritesPaused(mode.as_str()))?;
+    let result = sqlx::query(
+        r#"
+UPDATE scores SET author_user_id = NULL, updated_at = NOW()
+WHERE author_user_id = $1 AND ($2::TEXT IS NULL OR project_id = $2)
+        "#,
+    )
+    .bind(&params.user_id)
+    .bind(params.project_id.as_deref())
+    .execute(&state.pool)
+    .await;
+    state.write_gate.release(1);
+    progress.authored_scores = result?.rows_affected();
+    job.report(&progress, processed).await?;
+    Ok(())
+}
+
+/// Erases the next [`BATCH_SIZE`] traces of the user. Returns their number.
+async fn erase_batch(
+    state: &AppState,
+    params: &EraseUserParams,
+    progress: &mut Progress,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = state.pool.begin().await?;
+    let trace_ids: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+SELECT id FROM traces
+WHERE user_id = $1 AND ($2::TEXT IS NULL OR project_id = $2)
+LIMIT $3
+FOR UPDATE
+        "#,
+    )
+    .bind(&params.user_id)
+    .bind(params.project_id.as_deref())
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+    if trace_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let offloaded = match params.mode {
+        ErasureMode::Delete => {
+            let deleted = trace_delete::delete_traces(&mut tx, &trace_ids).await?;
+            progress.traces_deleted += deleted.traces;
+            progress.observations += deleted.observations;
+            progress.scores += deleted.scores;
+            deleted.offloaded
+        }
+        ErasureMode::Anonymize => {
+            let (observations, scores, keys): (i64, i64, Vec<String>) = sqlx::query_as(
+                r#"
+WITH offloaded AS (
+  SELECT k FROM observations, LATERAL (VALUES (input_offload), (output_offload)) AS o(k)
+  WHERE trace_id = ANY($1) AND k IS NOT NULL
+),
+traces_updated AS (
+  UPDATE traces SET
+    user_id = NULL, input = NULL, output = NULL, metadata = NULL, updated_at = NOW()
+  WHERE id = ANY($1)
+),
+observations_updated AS (
+  UPDATE observations SET
+    input = NULL, output = NULL, input_offload = NULL, output_offload = NULL,
+    metadata = NULL, status_message = NULL, updated_at = NOW()
+  WHERE trace_id = ANY($1)
+  RETURNING 1
+),
+scores_updated AS (
+  UPDATE scores SET comment = NULL, metadata = NULL, updated_at = NOW()
+  WHERE trace_id = ANY($1)
+  RETURNING 1
+),
+raw_deleted AS (
+  DELETE FROM raw_ingest WHERE trace_ids && $1
+)
+SELECT
+  (SELECT COUNT(*) FROM observations_updated),
+  (SELECT COUNT(*) FROM scores_updated),
+  ARRAY(SELECT k FROM offloaded)
+                "#,
+            )
+            .bind(&trace_ids)
+            .fetch_one(&mut *tx)
+            .await?;
+            progress.traces_anonymized += trace_ids.len() as i64;
+            progress.observations += observations;
+            progress.scores += scores;
+            keys
+        }
+    };
+    tx.commit().await?;
+    state.delete_offloaded(offloaded);
+    Ok(trace_ids.len() as i64)
+}
@@ -0,0 +1,1111 @@
+//! `export_traces`: writes the traces of one project that match the filters of
+//! `GET /traces`, with their observations, to a JSONL, CSV or Parquet file.
+//! Projects start and fetch their own exports:
+//!
+//! - `POST /api/public/exports` starts the job (`202`);
+//! - `GET /api/public/exports/:exportId` polls it, with a `downloadPath` once
+//!   it succeeded;
+//! - `GET /api/public/exports/:exportId/download` streams the file;
+//! - `DELETE /api/public/exports/:exportId` removes the job and its file.
+//!
+//! `jsonl` has one trace per line, shaped like `GET /traces/:traceId` with
+//! its observations nested. `csv` and `parquet` have one row per trace and
+//! per observation with the fields of [`COLUMNS`]; observation rows repeat
+//! the user, session, release and tags of their trace, and JSON values are
+//! JSON text. Parquet needs the `parquet` feature. `openai_chat` is a
+//! fine-tuning dataset: one chat example (`messages`) per generation, see
+//! [`chat_example`].
+//!
+//! Besides the trace filters, `minScore` selects traces by score, e.g. to
+//! export only conversations rated good.
+//!
+//! The file is stored in `export_chunks` as it is written, so an export of
+//! any size only holds one chunk in memory, and is kept until the export is
+//! deleted.
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::QueryBuilder;
+use uuid::Uuid;
+
+use super::{
+    start, JobDto, JobError, JobHandle, JobListQuery, JobRequest, JobRow, JOB_COLUMNS,
+    STATUS_RUNNING, STATUS_SUCCEEDED,
+};
+use crate::{
+    http::{
+        auth::ProjectScope,
+        error::ApiError,
+        traces::{
+            apply_trace_filters, observation_to_dto, ObservationRow, ObservationsViewDto,
+            TraceListQuery, OBSERVATION_COLUMNS,
+        },
+    },
+    state::{AppState, WriteMode},
+};
+
+const KIND: &str = "export_traces";
+
+/// Traces read per query.
+const BATCH_SIZE: i64 = 500;
+
+/// Size at which the buffered file is stored as a chunk.
+const CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExportFormat {
+    Jsonl,
+    Csv,
+    Parquet,
+    OpenaiChat,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl | ExportFormat::OpenaiChat => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl | ExportFormat::OpenaiChat => "jsonl",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportTracesParams {
+    project_id: String,
+    format: ExportFormat,
+    /// Filters of `GET /traces`; every trace of the project when unset.
+    #[serde(default, skip_serializing_if = "JsonValue::is_null")]
+    filter: JsonValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_score: Option<ScoreThreshold>,
+}
+
+/// Traces with a score `name` of at least `value`, on the trace or any of
+/// its observations.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ScoreThreshold {
+    name: String,
+    value: f64,
+}
+
+impl ExportTracesParams {
+    fn filter(&self) -> Result<TraceListQuery, serde_json::Error> {
+        match &self.filter {
+            JsonValue::Null => serde_json::from_value(JsonValue::Object(Default::default())),
+            filter => TraceListQuery::deserialize(filter),
+        }
+    }
+
+    pub(super) fn validate(&self) -> Result<(), ApiError> {
+        if self.project_id.trim().is_empty() {
+            return Err(ApiError::BadRequest(
+                "projectId must not be empty".to_string(),
+            ));
+        }
+        if let Err(err) = self.filter() {
+            return Err(ApiError::BadRequest(format!("invalid filter: {err}")));
+        }
+        if self.min_score.as_ref().is_some_and(|s| s.name.is_empty()) {
+            return Err(ApiError::BadRequest(
+                "minScore.name must not be empty".to_string(),
+            ));
+        }
+        if self.format == ExportFormat::Parquet && !cfg!(feature = "parquet") {
+            return Err(ApiError::BadRequest(
+                "parquet exports need xtrace built with the `parquet` feature".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Appends the conditions selecting the exported traces, aliased `t`.
+    fn push_trace_filters(
+        &self,
+        builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+        filter: &TraceListQuery,
+    ) {
+        builder.push(" t.project_id = ");
+        builder.push_bind(self.project_id.clone());
+        apply_trace_filters(builder, filter);
+        if let Some(min_score) = &self.min_score {
+            builder
+                .push(" AND EXISTS (SELECT 1 FROM scores s WHERE s.trace_id = t.id AND s.name = ");
+            builder.push_bind(min_score.name.clone());
+            builder.push(" AND s.value >= ");
+            builder.push_bind(min_score.value);
+            builder.push(")");
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    traces: i64,
+    observations: i64,
+    /// Chat examples written, for `openai_chat`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    examples: Option<i64>,
+    /// Generations without a usable prompt or completion, for `openai_chat`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped_generations: Option<i64>,
+    /// Size of the file stored so far.
+    bytes: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct ExportTraceRow {
+    id: Uuid,
+    timestamp: DateTime<Utc>,
+    name: Option<String>,
+    input: Option<JsonValue>,
+    output: Option<JsonValue>,
+    session_id: Option<String>,
+    release: Option<String>,
+    version: Option<String>,
+    user_id: Option<String>,
+    metadata: Option<JsonValue>,
+    tags: Vec<String>,
+    public: bool,
+    bookmarked: bool,
+    environment: String,
+    latency: Option<f64>,
+    total_cost: Option<f64>,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    sdk_language: Option<String>,
+    host_name: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+const TRACE_COLUMNS: &str = r#"
+  t.id, t."timestamp", t.name, t.input, t.output, t.session_id, t.release, t.version,
+  t.user_id, t.metadata, t.tags, t.public, t.bookmarked, t.environment, t.latency,
+  t.total_cost, t.service_name, t.service_version, t.sdk_language, t.host_name,
+  t.created_at, t.updated_at
+"#;
+
+#[derive(Serialize)]
+struct TraceLine<'a> {
+    #[serde(flatten)]
+    trace: &'a ExportTraceRow,
+    observations: &'a [ObservationsViewDto],
+}
+
+pub(super) async fn export(
+    state: &AppState,
+    params: &ExportTracesParams,
+    job: &JobHandle,
+) -> Result<(), JobError> {
+    let filter = params
+        .filter()
+        .map_err(|err| JobError::Export(format!("invalid filter: {err}")))?;
+
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM traces t WHERE");
+    params.push_trace_filters(&mut count, &filter);
+    let total: i64 = count.build_query_scalar().fetch_one(&state.pool).await?;
+    job.set_total(total).await?;
+
+    let mut encoder = Encoder::new(params.format)?;
+    let mut seq = 0;
+    let mut progress = Progress::default();
+    let mut after: Option<(DateTime<Utc>, Uuid)> = None;
+    loop {
+        let mut select = QueryBuilder::new(format!("SELECT {TRACE_COLUMNS} FROM traces t WHERE"));
+        params.push_trace_filters(&mut select, &filter);
+        if let Some((timestamp, id)) = after {
+            select.push(" AND (t.\"timestamp\", t.id) > (");
+            select.push_bind(timestamp);
+            select.push(", ");
+            select.push_bind(id);
+            select.push(")");
+        }
+        select.push(" ORDER BY t.\"timestamp\", t.id LIMIT ");
+        select.push_bind(BATCH_SIZE);
+        let traces: Vec<ExportTraceRow> = select.build_query_as().fetch_all(&state.pool).await?;
+        let Some(last) = traces.last() else {
+            break;
+        };
+        after = Some((last.timestamp, last.id));
+
+        let trace_ids: Vec<Uuid> = traces.iter().map(|t| t.id).collect();
+        let rows: Vec<ObservationRow> = sqlx::query_as(&format!(
+            "SELECT {OBSERVATION_COLUMNS} FROM observations WHERE trace_id = ANY($1) ORDER BY start_time NULLS LAST, created_at, id"
+        ))
+        .bind(&trace_ids)
+        .fetch_all(&state.pool)
+        .await?;
+        progress.observations += rows.len() as i64;
+        let mut observations: HashMap<Uuid, Vec<ObservationsViewDto>> = HashMap::new();
+        for row in rows {
+            observations
+                .entry(row.trace_id())
+                .or_default()
+                .push(observation_to_dto(row));
+        }
+
+        for trace in &traces {
+            let trace_observations = observations.remove(&trace.id).unwrap_or_default();
+            encoder.write(trace, &trace_observations)?;
+        }
+        progress.traces += traces.len() as i64;
+        encoder.count(&mut progress);
+        if encoder.buffered() >= CHUNK_BYTES {
+            progress.bytes += store_chunk(state, job.id, &mut seq, encoder.take()).await?;
+        }
+        job.report(&progress, progress.traces).await?;
+        if (traces.len() as i64) < BATCH_SIZE {
+            break;
+        }
+    }
+
+    let rest = encoder.finish()?;
+    progress.bytes += store_chunk(state, job.id, &mut seq, rest).await?;
+    job.report(&progress, progress.traces).await?;
+    Ok(())
+}
+
+/// Stores the next chunk of the file; returns its size.
+async fn store_chunk(
+    state: &AppState,
+    job_id: Uuid,
+    seq: &mut i32,
+    data: Vec<u8>,
+) -> Result<i64, JobError> {
+    if data.is_empty() {
+        return Ok(0);
+    }
+    let size = data.len() as i64;
+    state
+        .write_gate
+        .try_reserve(1)
+        .map_err(|mode| JobError::WritesPaused(mode.as_str()))?;
+    let result = sqlx::query("INSERT INTO export_chunks (job_id, seq, data) VALUES ($1, $2, $3)")
+        .bind(job_id)
+        .bind(*seq)
+        .bind(data)
+        .execute(&state.pool)
+        .await;
+    state.write_gate.release(1);
+    result?;
+    *seq += 1;
+    Ok(size)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnKind {
+    Text,
+    Int,
+    Float,
+    Timestamp,
+}
+
+/// A column of CSV and Parquet exports: its name and kind, and the field of
+/// the trace and of the observation JSON it is read from. Observation rows
+/// without an observation field take the trace field.
+type Column = (
+    &'static str,
+    ColumnKind,
+    Option<&'static str>,
+    Option<&'static str>,
+);
+
+/// Follows `recordType` (`trace` or `observation`), the first column.
+const COLUMNS: &[Column] = &[
+    ("traceId", ColumnKind::Text, Some("id"), Some("traceId")),
+    ("observationId", ColumnKind::Text, None, Some("id")),
+    (
+        "parentObservationId",
+        ColumnKind::Text,
+        None,
+        Some("parentObservationId"),
+    ),
+    ("type", ColumnKind::Text, None, Some("type")),
+    ("name", ColumnKind::Text, Some("name"), Some("name")),
+    (
+        "startTime",
+        ColumnKind::Timestamp,
+        Some("timestamp"),
+        Some("startTime"),
+    ),
+    ("endTime", ColumnKind::Timestamp, None, Some("endTime")),
+    ("userId", ColumnKind::Text, Some("userId"), None),
+    ("sessionId", ColumnKind::Text, Some("sessionId"), None),
+    (
+        "environment",
+        ColumnKind::Text,
+        Some("environment"),
+        Some("environment"),
+    ),
+    ("release", ColumnKind::Text, Some("release"), None),
+    (
+        "version",
+        ColumnKind::Text,
+        Some("version"),
+        Some("version"),
+    ),
+    ("tags", ColumnKind::Text, Some("tags"), None),
+    ("model", ColumnKind::Text, None, Some("model")),
+    ("level", ColumnKind::Text, None, Some("level")),
+    (
+        "statusMessage",
+        ColumnKind::Text,
+        None,
+        Some("statusMessage"),
+    ),
+    ("input", ColumnKind::Text, Some("input"), Some("input")),
+    ("output", ColumnKind::Text, Some("output"), Some("output")),
+    (
+        "metadata",
+        ColumnKind::Text,
+        Some("metadata"),
+        Some("metadata"),
+    ),
+    ("promptTokens", ColumnKind::Int, None, Some("promptTokens")),
+    (
+        "completionTokens",
+        ColumnKind::Int,
+        None,
+        Some("completionTokens"),
+    ),
+    ("totalTokens", ColumnKind::Int, None, Some("totalTokens")),
+    (
+        "totalCost",
+        ColumnKind::Float,
+        Some("totalCost"),
+        Some("calculatedTotalCost"),
+    ),
+    (
+        "latency",
+        ColumnKind::Float,
+        Some("latency"),
+        Some("latency"),
+    ),
+];
+
+/// The rows of a trace and its observations, each the `recordType` and the
+/// values of [`COLUMNS`].
+fn flatten(
+    trace: &ExportTraceRow,
+    observations: &[ObservationsViewDto],
+) -> Vec<(&'static str, Vec<JsonValue>)> {
+    let trace = serde_json::to_value(trace).unwrap_or_default();
+    let field = |value: &JsonValue, key: Option<&str>| {
+        key.and_then(|key| value.get(key))
+            .cloned()
+            .unwrap_or_default()
+    };
+    let mut rows = Vec::with_capacity(observations.len() + 1);
+    rows.push((
+        "trace",
+        COLUMNS
+            .iter()
+            .map(|(_, _, trace_key, _)| field(&trace, *trace_key))
+            .collect(),
+    ));
+    for observation in observations {
+        let observation = serde_json::to_value(observation).unwrap_or_default();
+        rows.push((
+            "observation",
+            COLUMNS
+                .iter()
+                .map(|(_, _, trace_key, observation_key)| match observation_key {
+                    Some(_) => field(&observation, *observation_key),
+                    None => field(&trace, *trace_key),
+                })
+                .collect(),
+        ));
+    }
+    rows
+}
+
+/// Strings as they are, other values as JSON text.
+fn cell_text(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::Null => None,
+        JsonValue::String(s) => Some(s.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+fn push_csv_field(buf: &mut Vec<u8>, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        buf.push(b'"');
+        buf.extend_from_slice(field.replace('"', "\"\"").as_bytes());
+        buf.push(b'"');
+    } else {
+        buf.extend_from_slice(field.as_bytes());
+    }
+}
+
+fn push_csv_row<'a>(buf: &mut Vec<u8>, fields: impl Iterator<Item = &'a str>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            buf.push(b',');
+        }
+        push_csv_field(buf, field);
+    }
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Fields of a chat message kept in fine-tuning examples.
+const CHAT_MESSAGE_FIELDS: [&str; 6] = [
+    "role",
+    "content",
+    "name",
+    "tool_calls",
+    "tool_call_id",
+    "function_call",
+];
+
+/// `message` without the fields fine-tuning does not accept (e.g. `refusal`),
+/// if it is a message with a role.
+fn chat_message(message: &JsonValue) -> Option<JsonValue> {
+    let message = message.as_object()?;
+    message.get("role")?.as_str()?;
+    Some(JsonValue::Object(
+        message
+            .iter()
+            .filter(|(k, v)| CHAT_MESSAGE_FIELDS.contains(&k.as_str()) && !v.is_null())
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+    ))
+}
+
+/// A fine-tuning example from the input and output of a generation:
+/// `{"messages": [...]}`, with `tools` when the input has them.
+///
+/// The input is a message list, an object with `messages` (and optionally
+/// `tools`), or a prompt string sent as one user message. The output is a
+/// string, an assistant message or a chat completion response, of which the
+/// first choice is used. Other shapes give no example.
+fn chat_example(input: &JsonValue, output: &JsonValue) -> Option<JsonValue> {
+    let (messages, tools) = match input {
+        JsonValue::String(prompt) if !prompt.is_empty() => (
+            vec![serde_json::json!({ "role": "user", "content": prompt })],
+            None,
+        ),
+        JsonValue::Array(messages) => (messages.clone(), None),
+        JsonValue::Object(input) => (
+            input.get("messages")?.as_array()?.clone(),
+            input.get("tools").filter(|t| t.is_array()).cloned(),
+        ),
+        _ => return None,
+    };
+    let mut messages = messages
+        .iter()
+        .map(chat_message)
+        .collect::<Option<Vec<_>>>()?;
+    if messages.is_empty() {
+        return None;
+    }
+
+    let completion = match output {
+        JsonValue::String(content) if !content.is_empty() => {
+            serde_json::json!({ "role": "assistant", "content": content })
+        }
+        JsonValue::Object(fields) => {
+            let message = match fields.get("choices") {
+                Some(choices) => choices.get(0)?.get("message")?,
+                None => output,
+            };
+            let message = chat_message(message)?;
+            if message["role"] != "assistant" {
+                return None;
+            }
+            message
+        }
+        _ => return None,
+    };
+    messages.push(completion);
+
+    let mut example = serde_json::json!({ "messages": messages });
+    if let Some(tools) = tools {
+        example["tools"] = tools;
+    }
+    Some(example)
+}
+
+/// Encodes the file in memory until [`Encoder::take`] hands the bytes
+/// written so far over.
+enum Encoder {
+    Jsonl(Vec<u8>),
+    Csv(Vec<u8>),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<parquet_file::ParquetEncoder>),
+    OpenaiChat {
+        buf: Vec<u8>,
+        examples: i64,
+        skipped: i64,
+    },
+}
+
+impl Encoder {
+    fn new(format: ExportFormat) -> Result<Self, JobError> {
+        match format {
+            ExportFormat::Jsonl => Ok(Encoder::Jsonl(Vec::new())),
+            ExportFormat::OpenaiChat => Ok(Encoder::OpenaiChat {
+                buf: Vec::new(),
+                examples: 0,
+                skipped: 0,
+            }),
+            ExportFormat::Csv => {
+                let mut buf = Vec::new();
+                push_csv_row(
+                    &mut buf,
+                    std::iter::once("recordType").chain(COLUMNS.iter().map(|c| c.0)),
+                );
+                Ok(Encoder::Csv(buf))
+            }
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => Ok(Encoder::Parquet(Box::new(
+                parquet_file::ParquetEncoder::new().map_err(|e| JobError::Export(e.to_string()))?,
+            ))),
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => Err(JobError::Export(
+                "xtrace was built without the `parquet` feature".to_string(),
+            )),
+        }
+    }
+
+    fn write(
+        &mut self,
+        trace: &ExportTraceRow,
+        observations: &[ObservationsViewDto],
+    ) -> Result<(), JobError> {
+        match self {
+            Encoder::Jsonl(buf) => {
+                serde_json::to_writer(
+                    &mut *buf,
+                    &TraceLine {
+                        trace,
+                        observations,
+                    },
+                )
+                .map_err(|e| JobError::Export(e.to_string()))?;
+                buf.push(b'\n');
+            }
+            Encoder::Csv(buf) => {
+                for (record_type, values) in flatten(trace, observations) {
+                    let cells: Vec<String> = values
+                        .iter()
+                        .map(|v| cell_text(v).unwrap_or_default())
+                        .collect();
+                    push_csv_row(
+                        buf,
+                        std::iter::once(record_type).chain(cells.iter().map(String::as_str)),
+                    );
+                }
+            }
+            #[cfg(feature = "parquet")]
+            Encoder::Parquet(encoder) => {
+                for row in flatten(trace, observations) {
+                    encoder
+                        .push(row)
+                        .map_err(|e| JobError::Export(e.to_string()))?;
+                }
+            }
+            Encoder::OpenaiChat {
+                buf,
+                examples,
+                skipped,
+            } => {
+                for observation in observations {
+                    let observation = serde_json::to_value(observation).unwrap_or_default();
+                    if observation["type"] != "GENERATION" {
+                        continue;
+                    }
+                    let Some(example) = chat_example(&observation["input"], &observation["output"])
+                    else {
+                        *skipped += 1;
+                        continue;
+                    };
+                    serde_json::to_writer(&mut *buf, &example)
+                        .map_err(|e| JobError::Export(e.to_string()))?;
+                    buf.push(b'\n');
+                    *examples += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the counters of the format in `progress`.
+    fn count(&self, progress: &mut Progress) {
+        if let Encoder::OpenaiChat {
+            examples, skipped, ..
+        } = self
+        {
+            progress.examples = Some(*examples);
+            progress.skipped_generations = Some(*skipped);
+        }
+    }
+
+    fn buffered(&self) -> usize {
+        match self {
+            Encoder::Jsonl(buf) | Encoder::Csv(buf) | Encoder::OpenaiChat { buf, .. } => buf.len(),
+            #[cfg(feature = "parquet")]
+            Encoder::Parquet(encoder) => encoder.buffered(),
+        }
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        match self {
+            Encoder::Jsonl(buf) | Encoder::Csv(buf) | Encoder::OpenaiChat { buf, .. } => {
+                std::mem::take(buf)
+            }
+            #[cfg(feature = "parquet")]
+            Encoder::Parquet(encoder) => encoder.take(),
+        }
+    }
+
+    /// The rest of the file.
+    fn finish(self) -> Result<Vec<u8>, JobError> {
+        match self {
+            Encoder::Jsonl(buf) | Encoder::Csv(buf) | Encoder::OpenaiChat { buf, .. } => Ok(buf),
+            #[cfg(feature = "parquet")]
+            Encoder::Parquet(encoder) => (*encoder)
+                .finish()
+                .map_err(|e| JobError::Export(e.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_file {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use parquet::{
+        basic::Compression,
+        data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+        errors::ParquetError,
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+    use serde_json::Value as JsonValue;
+
+    use super::{cell_text, ColumnKind, COLUMNS};
+
+    /// Rows per row group.
+    const ROW_GROUP_ROWS: usize = 10_000;
+
+    /// The file written so far, drained by [`ParquetEncoder::take`].
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    pub(super) struct ParquetEncoder {
+        writer: SerializedFileWriter<SharedBuf>,
+        buf: SharedBuf,
+        rows: Vec<(&'static str, Vec<JsonValue>)>,
+    }
+
+    fn schema() -> String {
+        let mut schema =
+            "message trace_export {\n  OPTIONAL BYTE_ARRAY recordType (UTF8);\n".to_string();
+        for (name, kind, _, _) in COLUMNS {
+            let ty = match kind {
+                ColumnKind::Text => "BYTE_ARRAY",
+                ColumnKind::Int | ColumnKind::Timestamp => "INT64",
+                ColumnKind::Float => "DOUBLE",
+            };
+            let annotation = match kind {
+                ColumnKind::Text => " (UTF8)",
+                ColumnKind::Timestamp => " (TIMESTAMP(MICROS,true))",
+                ColumnKind::Int | ColumnKind::Float => "",
+            };
+            schema.push_str(&format!("  OPTIONAL {ty} {name}{annotation};\n"));
+        }
+        schema.push('}');
+        schema
+    }
+
+    /// The present values of an optional column and the definition level of
+    /// every row.
+    fn optional<T>(values: impl Iterator<Item = Option<T>>) -> (Vec<T>, Vec<i16>) {
+        let mut levels = Vec::new();
+        let values = values
+            .filter_map(|value| {
+                levels.push(i16::from(value.is_some()));
+                value
+            })
+            .collect();
+        (values, levels)
+    }
+
+    impl ParquetEncoder {
+        pub(super) fn new() -> Result<Self, ParquetError> {
+            let schema = Arc::new(parse_message_type(&schema())?);
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let buf = SharedBuf::default();
+            let writer = SerializedFileWriter::new(buf.clone(), schema, Arc::new(properties))?;
+            Ok(Self {
+                writer,
+                buf,
+                rows: Vec::new(),
+            })
+        }
+
+        pub(super) fn push(
+            &mut self,
+            row: (&'static str, Vec<JsonValue>),
+        ) -> Result<(), ParquetError> {
+            self.rows.push(row);
+            if self.rows.len() >= ROW_GROUP_ROWS {
+                self.write_row_group()?;
+            }
+            Ok(())
+        }
+
+        fn write_row_group(&mut self) -> Result<(), ParquetError> {
+            if self.rows.is_empty() {
+                return Ok(());
+            }
+            let rows = std::mem::take(&mut self.rows);
+            let mut row_group = self.writer.next_row_group()?;
+            let mut index = 0;
+            while let Some(mut column) = row_group.next_column()? {
+                if index == 0 {
+                    let (values, levels) = optional(
+                        rows.iter()
+                            .map(|(record_type, _)| Some(ByteArray::from(*record_type))),
+                    );
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                } else {
+                    let cells = rows.iter().map(|(_, values)| &values[index - 1]);
+                    match COLUMNS[index - 1].1 {
+                        ColumnKind::Text => {
+                            let (values, levels) =
+                                optional(cells.map(|v| {
+                                    cell_text(v).map(|s| ByteArray::from(s.into_bytes()))
+                                }));
+                            column.typed::<ByteArrayType>().write_batch(
+                                &values,
+                                Some(&levels),
+                                None,
+                            )?;
+                        }
+                        ColumnKind::Int => {
+                            let (values, levels) = optional(cells.map(JsonValue::as_i64));
+                            column.typed::<Int64Type>().write_batch(
+                                &values,
+                                Some(&levels),
+                                None,
+                            )?;
+                        }
+                        ColumnKind::Float => {
+                            let (values, levels) = optional(cells.map(JsonValue::as_f64));
+                            column.typed::<DoubleType>().write_batch(
+                                &values,
+                                Some(&levels),
+                                None,
+                            )?;
+                        }
+                        ColumnKind::Timestamp => {
+                            let (values, levels) = optional(cells.map(|v| {
+                                v.as_str()
+                                    .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+                                    .map(|t| t.timestamp_micros())
+                            }));
+                            column.typed::<Int64Type>().write_batch(
+                                &values,
+                                Some(&levels),
+                                None,
+                            )?;
+                        }
+                    }
+                }
+                column.close()?;
+                index += 1;
+            }
+            row_group.close()?;
+            Ok(())
+        }
+
+        pub(super) fn buffered(&self) -> usize {
+            self.buf.0.lock().unwrap_or_else(|e| e.into_inner()).len()
+        }
+
+        pub(super) fn take(&mut self) -> Vec<u8> {
+            std::mem::take(&mut *self.buf.0.lock().unwrap_or_else(|e| e.into_inner()))
+        }
+
+        pub(super) fn finish(mut self) -> Result<Vec<u8>, ParquetError> {
+            self.write_row_group()?;
+            self.writer.close()?;
+            Ok(std::mem::take(
+                &mut *self.buf.0.lock().unwrap_or_else(|e| e.into_inner()),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct CreateExportRequest {
+    format: ExportFormat,
+    /// Filters of `GET /traces`.
+    #[serde(default)]
+    filter: JsonValue,
+    #[serde(default)]
+    min_score: Option<ScoreThreshold>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportDto {
+    #[serde(flatten)]
+    job: JobDto,
+    /// Set once the export succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    download_path: Option<String>,
+}
+
+impl From<JobRow> for ExportDto {
+    fn from(row: JobRow) -> Self {
+        let download_path = (row.status == STATUS_SUCCEEDED)
+            .then(|| format!("/api/public/exports/{}/download", row.id));
+        Self {
+            job: row.into(),
+            download_path,
+        }
+    }
+}
+
+/// The export job `export_id` of the project.
+async fn fetch_export(
+    state: &AppState,
+    scope: &ProjectScope,
+    export_id: Uuid,
+) -> Result<JobRow, ApiError> {
+    let row: Option<JobRow> = sqlx::query_as(&format!(
+        "SELECT {JOB_COLUMNS} FROM admin_jobs WHERE id = $1 AND kind = $2 AND params->>'projectId' = $3"
+    ))
+    .bind(export_id)
+    .bind(KIND)
+    .bind(scope.project_id())
+    .fetch_optional(&state.pool)
+    .await?;
+    row.ok_or(ApiError::NotFound)
+}
+
+/// Starts an export of the project's traces; responds `202` with the job.
+pub(crate) async fn post_export(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Json(req): Json<CreateExportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mode = state.write_gate.mode();
+    if mode != WriteMode::Normal {
+        return Err(state.writes_paused(mode));
+    }
+    let req = JobRequest::ExportTraces(ExportTracesParams {
+        project_id: scope.project_id().to_string(),
+        format: req.format,
+        filter: req.filter,
+        min_score: req.min_score,
+    });
+    req.validate()?;
+    let row = start(&state, req).await?;
+    Ok((StatusCode::ACCEPTED, Json(ExportDto::from(row))))
+}
+
+/// The project's exports, most recent first.
+pub(crate) async fn get_exports(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Query(q): Query<JobListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let rows: Vec<JobRow> = sqlx::query_as(&format!(
+        r#"
+SELECT {JOB_COLUMNS} FROM admin_jobs
+WHERE kind = $1 AND params->>'projectId' = $2 AND ($3::TEXT IS NULL OR status = $3)
+ORDER BY created_at DESC, id
+LIMIT $4
+        "#
+    ))
+    .bind(KIND)
+    .bind(scope.project_id())
+    .bind(q.status)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let data: Vec<ExportDto> = rows.into_iter().map(Into::into).collect();
+    Ok((StatusCode::OK, Json(serde_json::json!({ "data": data }))))
+}
+
+pub(crate) async fn get_export(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(export_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row = fetch_export(&state, &scope, export_id).await?;
+    Ok((StatusCode::OK, Json(ExportDto::from(row))))
+}
+
+/// Streams the file of a succeeded export, one stored chunk at a time.
+pub(crate) async fn get_export_download(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(export_id): Path<Uuid>,
+) -> Result<Response, ApiError> {
+    let row = fetch_export(&state, &scope, export_id).await?;
+    if row.status != STATUS_SUCCEEDED {
+        return Err(ApiError::Conflict(format!("export is {}", row.status)));
+    }
+    let format = match serde_json::from_value::<ExportTracesParams>(row.params) {
+        Ok(params) => params.format,
+        Err(_) => return Err(ApiError::NotFound),
+    };
+
+    let pool = state.pool.clone();
+    let chunks = stream::unfold(Some(0), move |seq: Option<i32>| {
+        let pool = pool.clone();
+        async move {
+            let seq = seq?;
+            let chunk: Result<Option<Vec<u8>>, sqlx::Error> =
+                sqlx::query_scalar("SELECT data FROM export_chunks WHERE job_id = $1 AND seq = $2")
+                    .bind(export_id)
+                    .bind(seq)
+                    .fetch_optional(&pool)
+                    .await;
+            match chunk {
+                Ok(Some(data)) => Some((Ok(data), Some(seq + 1))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        }
+    });
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"traces-{export_id}.{}\"",
+                    format.extension()
+                ),
+            ),
+        ],
+        Body::from_stream(chunks),
+    )
+        .into_response())
+}
+
+/// Deletes a finished export with its file.
+pub(crate) async fn delete_export(
+    State(state): State<AppState>,
+    Extension(scope): Extension<ProjectScope>,
+    Path(export_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row = fetch_export(&state, &scope, export_id).await?;
+    if row.status == STATUS_RUNNING {
+        return Err(ApiError::Conflict("export is running".to_string()));
+    }
+    state
+        .write_gate
+        .try_reserve(1)
+        .map_err(|mode| state.writes_paused(mode))?;
+    let result = sqlx::query("DELETE FROM admin_jobs WHERE id = $1")
+        .bind(export_id)
+        .execute(&state.pool)
+        .await;
+    state.write_gate.release(1);
+    result?;
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({ "id": export_id, "deleted": true })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn chat_examples_from_generation_shapes() {
+        let messages = json!([
+            {"role": "system", "content": "Be brief."},
+            {"role": "user", "content": "Hi", "cache_control": {"type": "ephemeral"}},
+        ]);
+        assert_eq!(
+            chat_example(&messages, &json!("Hello!")).unwrap(),
+            json!({"messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": "Hello!"},
+            ]})
+        );
+
+        let completion = json!({"choices": [{"message": {"role": "assistant", "content": "4", "refusal": null}}]});
+        assert_eq!(
+            chat_example(&json!("2+2?"), &completion).unwrap(),
+            json!({"messages": [
+                {"role": "user", "content": "2+2?"},
+                {"role": "assistant", "content": "4"},
+            ]})
+        );
+
+        let tools = json!([{"type": "function", "function": {"name": "search"}}]);
+        let call = json!({"role": "assistant", "tool_calls": [{"id": "c1", "type": "function"}]});
+        let example = chat_example(&json!({"messages": messages, "tools": tools}), &call).unwrap();
+        assert_eq!(example["tools"], tools);
+        assert_eq!(example["messages"][2], call);
+
+        assert!(chat_example(&messages, &JsonValue::Null).is_none());
+        assert!(chat_example(&messages, &json!({"role": "user", "content": "x"})).is_none());
+        assert!(chat_example(&json!([{"content": "no role"}]), &json!("x")).is_none());
+        assert!(chat_example(&json!([]), &json!("x")).is_none());
+        assert!(chat_example(&json!(42), &json!("x")).is_none());
+    }
+}
@@ -0,0 +1,452 @@
+//! Admin jobs: maintenance work too long for one request, started with
+//! `POST /api/admin/jobs` and polled with `GET /api/admin/jobs/:jobId`, or
+//! followed as server-sent events on `GET /api/admin/jobs/:jobId/events`.
+//!
+//! A job runs on the instance that accepted it and records its progress in
+//! `admin_jobs` after every step. It works in batches through the
+//! [`WriteGate`](crate::state::WriteGate), so read-only or drain mode stops it
+//! (as `failed`) once the current batch is written.
+//!
+//! Trace exports are jobs too, started and fetched by projects through
+//! `/api/public/exports` (see [`export`]).
+
+pub(crate) mod costs;
+pub(crate) mod erasure;
+pub(crate) mod export;
+pub(crate) mod partitions;
+pub(crate) mod resources;
+pub(crate) mod retention;
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::{PgConnection, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    http::error::ApiError,
+    state::{AppState, WriteMode},
+};
+
+const STATUS_RUNNING: &str = "running";
+const STATUS_SUCCEEDED: &str = "succeeded";
+const STATUS_FAILED: &str = "failed";
+
+/// A running job that recorded no progress for this long was interrupted,
+/// e.g. by a restart of the instance running it.
+const STALE_AFTER_SECS: f64 = 300.0;
+
+/// How often the event stream of a job checks for new progress.
+const EVENTS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Advisory lock serializing scheduled job starts across instances.
+const SCHEDULE_LOCK_KEY: i64 = 0x7874_7261_6365_0002;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "params", rename_all = "snake_case")]
+pub(crate) enum JobRequest {
+    RecomputeCosts(costs::RecomputeCostsParams),
+    CompactResources(#[serde(default)] resources::CompactResourcesParams),
+    PurgeExpired(#[serde(default)] retention::PurgeExpiredParams),
+    EraseUser(erasure::EraseUserParams),
+    ExportTraces(export::ExportTracesParams),
+}
+
+impl JobRequest {
+    fn kind(&self) -> &'static str {
+        match self {
+            JobRequest::RecomputeCosts(_) => "recompute_costs",
+            JobRequest::CompactResources(_) => "compact_resources",
+            JobRequest::PurgeExpired(_) => "purge_expired",
+            JobRequest::EraseUser(_) => "erase_user",
+            JobRequest::ExportTraces(_) => "export_traces",
+        }
+    }
+
+    fn params(&self) -> JsonValue {
+        match self {
+            JobRequest::RecomputeCosts(p) => serde_json::to_value(p),
+            JobRequest::CompactResources(p) => serde_json::to_value(p),
+            JobRequest::PurgeExpired(p) => serde_json::to_value(p),
+            JobRequest::EraseUser(p) => serde_json::to_value(p),
+            JobRequest::ExportTraces(p) => serde_json::to_value(p),
+        }
+        .unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<(), ApiError> {
+        match self {
+            JobRequest::RecomputeCosts(p) => p.validate(),
+            JobRequest::CompactResources(p) => p.validate(),
+            JobRequest::PurgeExpired(p) => p.validate(),
+            JobRequest::EraseUser(p) => p.validate(),
+            JobRequest::ExportTraces(p) => p.validate(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum JobError {
+    #[error("writes are paused ({0})")]
+    WritesPaused(&'static str),
+    #[error("export failed: {0}")]
+    Export(String),
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+}
+
+/// Handle a running job reports its progress through.
+pub(crate) struct JobHandle {
+    pool: PgPool,
+    id: Uuid,
+}
+
+impl JobHandle {
+    /// Records how many items the job is going to process.
+    pub(crate) async fn set_total(&self, total: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE admin_jobs SET total = $2, updated_at = NOW() WHERE id = $1")
+            .bind(self.id)
+            .bind(total)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Records the kind-specific counters and the items processed so far.
+    pub(crate) async fn report(
+        &self,
+        progress: &impl Serialize,
+        processed: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE admin_jobs SET progress = $2, processed = $3, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(self.id)
+        .bind(serde_json::to_value(progress).unwrap_or_default())
+        .bind(processed)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Marks jobs that stopped reporting progress as failed. Run at startup, so
+/// jobs of a crashed or restarted instance do not stay `running` forever.
+pub(crate) async fn fail_interrupted(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+UPDATE admin_jobs
+SET status = $1, error = 'interrupted', finished_at = NOW(), updated_at = NOW()
+WHERE status = $2 AND updated_at < NOW() - make_interval(secs => $3)
+        "#,
+    )
+    .bind(STATUS_FAILED)
+    .bind(STATUS_RUNNING)
+    .bind(STALE_AFTER_SECS)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+async fn run(state: AppState, id: Uuid, req: JobRequest) {
+    let job = JobHandle {
+        pool: state.pool.clone(),
+        id,
+    };
+    let result = match &req {
+        JobRequest::RecomputeCosts(p) => costs::recompute(&state, p, &job).await,
+        JobRequest::CompactResources(p) => resources::compact(&state, p, &job).await,
+        JobRequest::PurgeExpired(p) => retention::purge(&state, p, &job).await,
+        JobRequest::EraseUser(p) => erasure::erase(&state, p, &job).await,
+        JobRequest::ExportTraces(p) => export::export(&state, p, &job).await,
+    };
+    let (status, error) = match result {
+        Ok(()) => (STATUS_SUCCEEDED, None),
+        Err(err) => {
+            tracing::error!(job_id = %id, kind = req.kind(), error = %err, "admin job failed");
+            (STATUS_FAILED, Some(err.to_string()))
+        }
+    };
+    let finished = sqlx::query(
+        "UPDATE admin_jobs SET status = $2, error = $3, finished_at = NOW(), updated_at = NOW() WHERE id = $1",
+    )
+    .bind(id)
+    .bind(status)
+    .bind(error)
+    .execute(&state.pool)
+    .await;
+    if let Err(err) = finished {
+        tracing::error!(job_id = %id, error = %err, "admin job: failed to record status");
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct JobRow {
+    id: Uuid,
+    kind: String,
+    params: JsonValue,
+    status: String,
+    progress: JsonValue,
+    processed: i64,
+    total: Option<i64>,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+const JOB_COLUMNS: &str = "id, kind, params, status, progress, processed, total, error, created_at, updated_at, finished_at";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JobDto {
+    id: Uuid,
+    r#type: String,
+    params: JsonValue,
+    status: String,
+    progress: JsonValue,
+    processed: i64,
+    /// Unknown until the job has counted its items.
+    total: Option<i64>,
+    /// `processed` out of `total`, 100 once succeeded.
+    percent: Option<f64>,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<JobRow> for JobDto {
+    fn from(row: JobRow) -> Self {
+        let percent = if row.status == STATUS_SUCCEEDED {
+            Some(100.0)
+        } else {
+            row.total.map(|total| match total {
+                0 => 100.0,
+                total => (row.processed as f64 * 100.0 / total as f64).min(100.0),
+            })
+        };
+        Self {
+            id: row.id,
+            r#type: row.kind,
+            params: row.params,
+            status: row.status,
+            progress: row.progress,
+            processed: row.processed,
+            total: row.total,
+            percent,
+            error: row.error,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            finished_at: row.finished_at,
+        }
+    }
+}
+
+/// Validates and starts a job; responds `202` with the running job.
+pub(crate) async fn post_job(
+    State(state): State<AppState>,
+    Json(req): Json<JobRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mode = state.write_gate.mode();
+    if mode != WriteMode::Normal {
+        return Err(state.writes_paused(mode));
+    }
+    req.validate()?;
+    let row = start(&state, req).await?;
+    Ok((StatusCode::ACCEPTED, Json(JobDto::from(row))))
+}
+
+async fn start(state: &AppState, req: JobRequest) -> Result<JobRow, sqlx::Error> {
+    let mut conn = state.pool.acquire().await?;
+    let row = insert(&mut conn, &req).await?;
+    Ok(launch(state, row, req))
+}
+
+async fn insert(conn: &mut PgConnection, req: &JobRequest) -> Result<JobRow, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "INSERT INTO admin_jobs (id, kind, params, status) VALUES ($1, $2, $3, $4) RETURNING {JOB_COLUMNS}"
+    ))
+    .bind(Uuid::new_v4())
+    .bind(req.kind())
+    .bind(req.params())
+    .bind(STATUS_RUNNING)
+    .fetch_one(conn)
+    .await
+}
+
+/// Runs the job of an inserted row in the background.
+fn launch(state: &AppState, row: JobRow, req: JobRequest) -> JobRow {
+    tracing::info!(job_id = %row.id, kind = req.kind(), "admin job started");
+    tokio::spawn(run(state.clone(), row.id, req));
+    row
+}
+
+/// Starts a scheduled job unless one of its kind is running (and not stale)
+/// on any instance. Returns the started job.
+pub(crate) async fn start_unless_running(
+    state: &AppState,
+    req: JobRequest,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut tx = state.pool.begin().await?;
+    // Instances schedule on the same interval; the lock keeps two of them
+    // from both finding no running job and starting one each.
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(SCHEDULE_LOCK_KEY)
+        .execute(&mut *tx)
+        .await?;
+    let running: bool = sqlx::query_scalar(
+        r#"
+SELECT EXISTS (
+  SELECT 1 FROM admin_jobs
+  WHERE kind = $1 AND status = $2 AND updated_at >= NOW() - make_interval(secs => $3)
+)
+        "#,
+    )
+    .bind(req.kind())
+    .bind(STATUS_RUNNING)
+    .bind(STALE_AFTER_SECS)
+    .fetch_one(&mut *tx)
+    .await?;
+    if running {
+        return Ok(None);
+    }
+    let row = insert(&mut tx, &req).await?;
+    tx.commit().await?;
+    Ok(Some(launch(state, row, req).id))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct JobListQuery {
+    #[serde(default)]
+    limit: Option<i64>,
+    /// `running`, `succeeded` or `failed`.
+    #[serde(default)]
+    status: Option<String>,
+}
+
+/// Most recent jobs first.
+pub(crate) async fn get_jobs(
+    State(state): State<AppState>,
+    Query(q): Query<JobListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = q.limit.unwrap_or(50).clamp(1, 200);
+    let rows: Vec<JobRow> = sqlx::query_as(&format!(
+        "SELECT {JOB_COLUMNS} FROM admin_jobs WHERE ($1::TEXT IS NULL OR status = $1) ORDER BY created_at DESC, id LIMIT $2"
+    ))
+    .bind(q.status)
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let data: Vec<JobDto> = rows.into_iter().map(Into::into).collect();
+    Ok((StatusCode::OK, Json(serde_json::json!({ "data": data }))))
+}
+
+async fn fetch_job(pool: &PgPool, job_id: Uuid) -> Result<Option<JobRow>, sqlx::Error> {
+    sqlx::query_as(&format!(
+        "SELECT {JOB_COLUMNS} FROM admin_jobs WHERE id = $1"
+    ))
+    .bind(job_id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub(crate) async fn get_job(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row = fetch_job(&state.pool, job_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok((StatusCode::OK, Json(JobDto::from(row))))
+}
+
+/// Follows a job as it records progress; see [`get_job_events`].
+struct JobWatch {
+    pool: PgPool,
+    id: Uuid,
+    /// Fetched but not sent yet.
+    next: Option<JobRow>,
+    last_update: Option<DateTime<Utc>>,
+    done: bool,
+}
+
+impl JobWatch {
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let row = match self.next.take() {
+                Some(row) => row,
+                None => {
+                    tokio::time::sleep(EVENTS_POLL_INTERVAL).await;
+                    match fetch_job(&self.pool, self.id).await {
+                        Ok(Some(row)) => row,
+                        // Deleted while followed.
+                        Ok(None) => return None,
+                        Err(err) => {
+                            self.done = true;
+                            return Some(Event::default().event("error").data(err.to_string()));
+                        }
+                    }
+                }
+            };
+            let running = row.status == STATUS_RUNNING;
+            if running && self.last_update == Some(row.updated_at) {
+                continue;
+            }
+            self.last_update = Some(row.updated_at);
+            self.done = !running;
+            let name = if running {
+                "progress"
+            } else {
+                row.status.as_str()
+            };
+            let event = Event::default().event(name);
+            return Some(
+                event
+                    .json_data(JobDto::from(row))
+                    .unwrap_or_else(|_| Event::default().event("error")),
+            );
+        }
+    }
+}
+
+/// Streams a job as server-sent events: a `progress` event with the job
+/// right away and whenever it records progress, then one `succeeded` or
+/// `failed` event, after which the stream ends.
+pub(crate) async fn get_job_events(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row = fetch_job(&state.pool, job_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let watch = JobWatch {
+        pool: state.pool.clone(),
+        id: job_id,
+        next: Some(row),
+        last_update: None,
+        done: false,
+    };
+    let events = stream::unfold(watch, |mut watch| async move {
+        let event = watch.next_event().await?;
+        Some((Ok::<_, Infallible>(event), watch))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,341 @@
+//! Time partitions of `traces` and `observations`: traces are range
+//! partitioned by `timestamp`, observations by `trace_timestamp` (their
+//! trace's timestamp, see [`batch`](crate::ingest::batch)), one partition per
+//! `XTRACE_PARTITION_INTERVAL` (UTC days, ISO weeks or calendar months).
+//!
+//! [`ensure`] creates the partitions up to [`AHEAD`] intervals past the
+//! current one, at startup and every hour from [`partition_worker`], splitting
+//! them off the `_future` partitions that hold the rows past the newest one.
+//! Rows from before partitioning, and backfills older than the oldest
+//! partition, stay in the `DEFAULT` partitions, where retention deletes them
+//! row by row. Partitions whose whole range is past the retention of every
+//! project are dropped by `purge_expired` jobs ([`drop_partition`]).
+
+use std::{collections::BTreeSet, time::Duration};
+
+use chrono::{DateTime, Datelike, Days, Months, TimeZone, Utc};
+use sqlx::{PgConnection, PgPool};
+
+use crate::ingest::{
+    activity::{self, ActivityDelta},
+    sessions,
+};
+
+/// Intervals created ahead of the current one.
+const AHEAD: usize = 2;
+
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Partitioned tables and their partition keys.
+const TABLES: [(&str, &str); 2] = [
+    ("traces", r#""timestamp""#),
+    ("observations", "trace_timestamp"),
+];
+
+/// Advisory lock serializing partition maintenance across instances.
+const LOCK_KEY: i64 = 0x7874_7261_6365_0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PartitionInterval {
+    Day,
+    Week,
+    Month,
+}
+
+impl PartitionInterval {
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(format!(
+                "invalid partition interval {s:?} (expected day, week or month)"
+            )),
+        }
+    }
+
+    /// First interval boundary after `from`.
+    fn next_boundary(self, from: DateTime<Utc>) -> DateTime<Utc> {
+        let date = from.date_naive();
+        let next = match self {
+            Self::Day => date + Days::new(1),
+            Self::Week => {
+                date - Days::new(date.weekday().num_days_from_monday().into()) + Days::new(7)
+            }
+            Self::Month => date
+                .with_day(1)
+                .and_then(|d| d.checked_add_months(Months::new(1)))
+                .expect("valid date"),
+        };
+        Utc.from_utc_datetime(&next.and_hms_opt(0, 0, 0).expect("midnight"))
+    }
+}
+
+/// A range partition, holding rows before `to_ts` (and from the end of the
+/// previous partition).
+#[derive(Debug, sqlx::FromRow)]
+pub(crate) struct Partition {
+    pub(crate) name: String,
+    pub(crate) to_ts: DateTime<Utc>,
+}
+
+/// The bounded range partitions of `table`, oldest first (not `_future`).
+async fn partitions(conn: &mut PgConnection, table: &str) -> Result<Vec<Partition>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT c.relname::TEXT AS name, b[2]::TIMESTAMPTZ AS to_ts
+FROM pg_inherits i
+JOIN pg_class c ON c.oid = i.inhrelid
+CROSS JOIN LATERAL regexp_match(
+  pg_get_expr(c.relpartbound, c.oid), 'FROM \(''([^'']+)''\) TO \(''([^'']+)''\)'
+) AS b
+WHERE i.inhparent = $1::TEXT::REGCLASS AND b IS NOT NULL
+ORDER BY 2
+        "#,
+    )
+    .bind(table)
+    .fetch_all(conn)
+    .await
+}
+
+/// Creates the missing partitions up to [`AHEAD`] intervals past the current
+/// one, continuing from the newest partition. Rows of the `_future` partition
+/// in their ranges move to them. Returns how many it created.
+pub(crate) async fn ensure(
+    pool: &PgPool,
+    interval: PartitionInterval,
+) -> Result<usize, sqlx::Error> {
+    let horizon = (0..=AHEAD).fold(Utc::now(), |t, _| interval.next_boundary(t));
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(LOCK_KEY)
+        .execute(&mut *tx)
+        .await?;
+    let mut created = 0;
+    for (table, key) in TABLES {
+        let mut from = partitions(&mut tx, table)
+            .await?
+            .last()
+            .map_or_else(Utc::now, |p| p.to_ts);
+        if from >= horizon {
+            continue;
+        }
+        let future = format!("{table}_future");
+        let has_future: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(&future)
+            .fetch_one(&mut *tx)
+            .await?;
+        if has_future {
+            sqlx::query(&format!("ALTER TABLE {table} DETACH PARTITION {future}"))
+                .execute(&mut *tx)
+                .await?;
+        }
+        while from < horizon {
+            let to = interval.next_boundary(from);
+            sqlx::query(&format!(
+                "CREATE TABLE {table}_p{} PARTITION OF {table} FOR VALUES FROM ('{}') TO ('{}')",
+                from.format("%Y%m%d"),
+                from.to_rfc3339(),
+                to.to_rfc3339(),
+            ))
+            .execute(&mut *tx)
+            .await?;
+            created += 1;
+            from = to;
+        }
+        if has_future {
+            sqlx::query(&format!(
+                "WITH moved AS (DELETE FROM {future} WHERE {key} < $1 RETURNING *) \
+                 INSERT INTO {table} SELECT * FROM moved"
+            ))
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(&format!(
+                "ALTER TABLE {table} ATTACH PARTITION {future} FOR VALUES FROM ('{}') TO (MAXVALUE)",
+                from.to_rfc3339(),
+            ))
+            .execute(&mut *tx)
+            .await?;
+        } else {
+            sqlx::query(&format!(
+                "CREATE TABLE {future} PARTITION OF {table} FOR VALUES FROM ('{}') TO (MAXVALUE)",
+                from.to_rfc3339(),
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(created)
+}
+
+pub(crate) async fn partition_worker(pool: PgPool, interval: PartitionInterval) {
+    let mut ticker = tokio::time::interval(MAINTENANCE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        match ensure(&pool, interval).await {
+            Ok(0) => {}
+            Ok(created) => tracing::info!(created, "partitions: created"),
+            Err(err) => tracing::error!(error = %err, "partitions: maintenance failed"),
+        }
+    }
+}
+
+/// Trace partitions holding only traces before `cutoff`.
+pub(crate) async fn expired(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<Partition>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    let mut all = partitions(&mut conn, "traces").await?;
+    all.retain(|p| p.to_ts <= cutoff);
+    Ok(all)
+}
+
+/// Rows removed by [`drop_partition`].
+#[derive(Debug, Default)]
+pub(crate) struct DroppedPartition {
+    pub(crate) traces: i64,
+    pub(crate) observations: i64,
+    pub(crate) scores: i64,
+    pub(crate) sessions_refreshed: usize,
+    /// Keys of offloaded payloads, to delete once the transaction commits.
+    pub(crate) offloaded: Vec<String>,
+}
+
+/// Drops a trace partition and the observation partition of the same range.
+/// What is attached to its traces elsewhere (observations stored in another
+/// partition, scores, secret findings, webhook matches) is deleted first, and
+/// their sessions and activity counts are refreshed. The caller owns the
+/// transaction and deletes the offloaded payloads after it commits.
+pub(crate) async fn drop_partition(
+    conn: &mut PgConnection,
+    partition: &Partition,
+) -> Result<DroppedPartition, sqlx::Error> {
+    let traces = &partition.name;
+    let observations = format!("observations_{}", traces.trim_start_matches("traces_"));
+    let observations_exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+        .bind(&observations)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let keys: Vec<sessions::SessionKey> = sqlx::query_as(&format!(
+        "SELECT DISTINCT project_id, session_id FROM {traces} WHERE session_id IS NOT NULL"
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+    let touched: BTreeSet<sessions::SessionKey> = keys.into_iter().collect();
+    let counts: Vec<(String, DateTime<Utc>, String, String, i64)> = sqlx::query_as(&format!(
+        "SELECT project_id, {}, environment, COALESCE(name, ''), COUNT(*) FROM {traces} \
+         GROUP BY 1, 2, 3, 4",
+        activity::BUCKET
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut dropped_activity = ActivityDelta::default();
+    for (project_id, bucket, environment, name, count) in counts {
+        dropped_activity.remove((project_id, bucket, environment, name), count);
+    }
+
+    let (trace_count, other_observations, scores, mut offloaded): (i64, i64, i64, Vec<String>) =
+        sqlx::query_as(&format!(
+            r#"
+WITH doomed AS (
+  SELECT id FROM {traces}
+),
+observations_deleted AS (
+  DELETE FROM observations
+  WHERE trace_id IN (SELECT id FROM doomed) AND tableoid IS DISTINCT FROM to_regclass($1)
+  RETURNING input_offload, output_offload
+),
+scores_deleted AS (
+  DELETE FROM scores WHERE trace_id IN (SELECT id FROM doomed) RETURNING 1
+),
+findings_deleted AS (
+  DELETE FROM secret_findings WHERE trace_id IN (SELECT id FROM doomed)
+),
+matches_deleted AS (
+  DELETE FROM webhook_rule_matches WHERE trace_id IN (SELECT id FROM doomed)
+)
+SELECT
+  (SELECT COUNT(*) FROM doomed),
+  (SELECT COUNT(*) FROM observations_deleted),
+  (SELECT COUNT(*) FROM scores_deleted),
+  ARRAY(
+    SELECT k FROM observations_deleted, LATERAL (VALUES (input_offload), (output_offload)) AS o(k)
+    WHERE k IS NOT NULL
+  )
+        "#
+        ))
+        .bind(&observations)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let mut observation_count = 0;
+    if observations_exists {
+        let (count, keys): (i64, Vec<String>) = sqlx::query_as(&format!(
+            r#"
+SELECT
+  (SELECT COUNT(*) FROM {observations}),
+  ARRAY(
+    SELECT k FROM {observations}, LATERAL (VALUES (input_offload), (output_offload)) AS o(k)
+    WHERE k IS NOT NULL
+  )
+            "#
+        ))
+        .fetch_one(&mut *conn)
+        .await?;
+        observation_count = count;
+        offloaded.extend(keys);
+        sqlx::query(&format!("DROP TABLE {observations}"))
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query(&format!("DROP TABLE {traces}"))
+        .execute(&mut *conn)
+        .await?;
+    sessions::refresh(conn, &touched).await?;
+    activity::apply(conn, dropped_activity).await?;
+
+    Ok(DroppedPartition {
+        traces: trace_count,
+        observations: observation_count + other_observations,
+        scores,
+        sessions_refreshed: touched.len(),
+        offloaded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundaries_follow_the_interval() {
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let from = at("2026-10-15T05:24:00Z");
+        assert_eq!(
+            PartitionInterval::Day.next_boundary(from),
+            at("2026-10-16T00:00:00Z")
+        );
+        assert_eq!(
+            PartitionInterval::Week.next_boundary(from),
+            at("2026-10-19T00:00:00Z")
+        );
+        assert_eq!(
+            PartitionInterval::Month.next_boundary(from),
+            at("2026-11-01T00:00:00Z")
+        );
+        // A boundary starts the next interval.
+        assert_eq!(
+            PartitionInterval::Week.next_boundary(at("2026-10-19T00:00:00Z")),
+            at("2026-10-26T00:00:00Z")
+        );
+        assert_eq!(
+            PartitionInterval::Month.next_boundary(at("2026-12-01T00:00:00Z")),
+            at("2027-01-01T00:00:00Z")
+        );
+        assert!(PartitionInterval::parse("year").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! `compact_resources`: moves the resource attributes that observations
+//! written before the resource dictionary still carry in
+//! `metadata.otel.resource` to `otel_resources` (see
+//! [`crate::ingest::resources`]). Reads return the same metadata before and
+//! after; `updatedAt` is not touched.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use super::{JobError, JobHandle};
+use crate::{http::error::ApiError, ingest::resources, state::AppState};
+
+/// Observations scanned per transaction.
+const BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CompactResourcesParams {
+    /// All projects when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_id: Option<String>,
+}
+
+impl CompactResourcesParams {
+    pub(super) fn validate(&self) -> Result<(), ApiError> {
+        if self
+            .project_id
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest(
+                "projectId must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    observations_compacted: i64,
+    /// Resources added to the dictionary.
+    resources_created: u64,
+}
+
+pub(super) async fn compact(
+    state: &AppState,
+    params: &CompactResourcesParams,
+    job: &JobHandle,
+) -> Result<(), JobError> {
+    let total: i64 = sqlx::query_scalar(
+        r#"
+SELECT COUNT(*)
+FROM observations
+WHERE resource_id IS NULL
+  AND jsonb_typeof(metadata->'otel.resource') = 'object'
+  AND ($1::TEXT IS NULL OR project_id = $1)
+        "#,
+    )
+    .bind(params.project_id.as_deref())
+    .fetch_one(&state.pool)
+    .await?;
+    job.set_total(total).await?;
+
+    let mut progress = Progress::default();
+    let mut cursor: Option<Uuid> = None;
+    loop {
+        state
+            .write_gate
+            .try_reserve(1)
+            .map_err(|mode| JobError::WritesPaused(mode.as_str()))?;
+        let result = compact_batch(state, params, cursor, &mut progress).await;
+        state.write_gate.release(1);
+        let last_id = result?;
+        job.report(&progress, progress.observations_compacted)
+            .await?;
+
+        match last_id {
+            Some(id) => cursor = Some(id),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Compacts the next [`BATCH_SIZE`] observations with resource attributes
+/// after `cursor` in id order; returns the last id, `None` when done.
+async fn compact_batch(
+    state: &AppState,
+    params: &CompactResourcesParams,
+    cursor: Option<Uuid>,
+    progress: &mut Progress,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut tx = state.pool.begin().await?;
+    let rows: Vec<(Uuid, JsonValue)> = sqlx::query_as(
+        r#"
+SELECT id, metadata->'otel.resource'
+FROM observations
+WHERE resource_id IS NULL
+  AND jsonb_typeof(metadata->'otel.resource') = 'object'
+  AND ($1::TEXT IS NULL OR project_id = $1)
+  AND ($2::UUID IS NULL OR id > $2)
+ORDER BY id
+LIMIT $3
+FOR UPDATE
+        "#,
+    )
+    .bind(params.project_id.as_deref())
+    .bind(cursor)
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut new_resources: Vec<(Uuid, JsonValue)> = Vec::new();
+    let (mut ids, mut resource_ids) = (Vec::new(), Vec::new());
+    for (id, attributes) in rows {
+        let resource_id = resources::resource_id(&attributes);
+        if !new_resources.iter().any(|(r, _)| *r == resource_id) {
+            new_resources.push((resource_id, attributes));
+        }
+        ids.push(id);
+        resource_ids.push(resource_id);
+    }
+    let Some(last_id) = ids.last().copied() else {
+        return Ok(None);
+    };
+
+    progress.resources_created += resources::store(&mut tx, &new_resources).await?;
+    let updated = sqlx::query(
+        r#"
+UPDATE observations o SET
+  metadata = o.metadata - 'otel.resource',
+  resource_id = v.resource_id
+FROM UNNEST($1::uuid[], $2::uuid[]) AS v(id, resource_id)
+WHERE o.id = v.id
+        "#,
+    )
+    .bind(&ids)
+    .bind(&resource_ids)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    progress.observations_compacted += updated.rows_affected() as i64;
+    Ok((ids.len() as i64 == BATCH_SIZE).then_some(last_id))
+}
@@ -0,0 +1,404 @@
+//! `purge_expired`: deletes traces (with their observations, scores and
+//! secret findings), scores and metric points older than the retention of
+//! their project.
+//!
+//! Retention is set in days with `XTRACE_RETENTION_DAYS` and per project with
+//! `XTRACE_PROJECT_RETENTION_DAYS`; 0 keeps data forever. Traces expire by
+//! their `timestamp`, scores by theirs, metric rollup buckets by their start.
+//! Each batch deletes up to [`BATCH_SIZE`] rows and refreshes the aggregates
+//! of the sessions it touched, in one transaction. Before that, trace
+//! partitions created before the longest retention are dropped whole (see
+//! [`partitions`](super::partitions)).
+//!
+//! When retention is configured, [`retention_worker`] starts the job every
+//! `XTRACE_RETENTION_INTERVAL_SECS` unless one is still running; it can also
+//! be started by hand, for one project or with another age.
+
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{partitions, JobError, JobHandle, JobRequest};
+use crate::{
+    http::{error::ApiError, trace_delete},
+    state::{AppState, WriteMode},
+};
+
+/// Rows deleted per transaction.
+const BATCH_SIZE: i64 = 500;
+
+/// Tables purged by their own timestamp column, after traces.
+const EXPIRING_TABLES: [(&str, &str); 4] = [
+    ("scores", r#""timestamp""#),
+    ("metrics", "timestamp"),
+    ("metrics_1m", "bucket"),
+    ("metrics_1h", "bucket"),
+];
+
+/// Global retention plus per-project overrides, in days (0: forever).
+#[derive(Debug, Default)]
+pub(crate) struct RetentionPolicy {
+    default_days: u32,
+    per_project: HashMap<String, u32>,
+}
+
+impl RetentionPolicy {
+    /// `projects` holds `project=days` pairs separated by commas or newlines.
+    pub(crate) fn parse(default_days: u32, projects: Option<&str>) -> Result<Self, String> {
+        let mut per_project = HashMap::new();
+        for entry in projects
+            .unwrap_or_default()
+            .split([',', '\n'])
+            .map(str::trim)
+        {
+            if entry.is_empty() {
+                continue;
+            }
+            let (project, days) = entry
+                .split_once('=')
+                .map(|(p, v)| (p.trim(), v.trim()))
+                .filter(|(p, _)| !p.is_empty())
+                .ok_or_else(|| format!("invalid project retention entry: {entry:?}"))?;
+            let days = days
+                .parse()
+                .map_err(|_| format!("invalid retention for project {project:?}: {days:?}"))?;
+            per_project.insert(project.to_string(), days);
+        }
+        Ok(Self {
+            default_days,
+            per_project,
+        })
+    }
+
+    pub(crate) fn for_project(&self, project_id: &str) -> u32 {
+        self.per_project
+            .get(project_id)
+            .copied()
+            .unwrap_or(self.default_days)
+    }
+
+    /// Whether any data expires.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.default_days > 0 || self.per_project.values().any(|d| *d > 0)
+    }
+
+    /// Days after which the data of every project has expired; `None` when
+    /// some project keeps its data forever.
+    fn longest(&self) -> Option<u32> {
+        if self.default_days == 0 || self.per_project.values().any(|d| *d == 0) {
+            return None;
+        }
+        self.per_project
+            .values()
+            .copied()
+            .chain([self.default_days])
+            .max()
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        let mut scopes: Vec<Scope> = self
+            .per_project
+            .iter()
+            .filter(|(_, days)| **days > 0)
+            .map(|(project, days)| Scope {
+                project_id: Some(project.clone()),
+                except: Vec::new(),
+                days: *days,
+            })
+            .collect();
+        if self.default_days > 0 {
+            scopes.push(Scope {
+                project_id: None,
+                except: self.per_project.keys().cloned().collect(),
+                days: self.default_days,
+            });
+        }
+        scopes
+    }
+}
+
+/// Projects sharing a retention.
+#[derive(Debug)]
+struct Scope {
+    /// All projects when unset.
+    project_id: Option<String>,
+    /// Projects with their own retention.
+    except: Vec<String>,
+    days: u32,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PurgeExpiredParams {
+    /// All projects when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_id: Option<String>,
+    /// Age in days instead of the configured retention.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    older_than_days: Option<u32>,
+}
+
+impl PurgeExpiredParams {
+    pub(super) fn validate(&self) -> Result<(), ApiError> {
+        if self
+            .project_id
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest(
+                "projectId must not be empty".to_string(),
+            ));
+        }
+        if self.older_than_days == Some(0) {
+            return Err(ApiError::BadRequest(
+                "olderThanDays must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn scopes(&self, policy: &RetentionPolicy) -> Vec<Scope> {
+        match (&self.project_id, self.older_than_days) {
+            (Some(project_id), days) => {
+                let days = days.unwrap_or_else(|| policy.for_project(project_id));
+                (days > 0)
+                    .then(|| Scope {
+                        project_id: Some(project_id.clone()),
+                        except: Vec::new(),
+                        days,
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            (None, Some(days)) => vec![Scope {
+                project_id: None,
+                except: Vec::new(),
+                days,
+            }],
+            (None, None) => policy.scopes(),
+        }
+    }
+
+    /// Age in days past which whole partitions can be dropped: only when all
+    /// projects are purged.
+    fn partition_days(&self, policy: &RetentionPolicy) -> Option<u32> {
+        match (&self.project_id, self.older_than_days) {
+            (Some(_), _) => None,
+            (None, Some(days)) => Some(days),
+            (None, None) => policy.longest(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    traces_deleted: i64,
+    observations_deleted: i64,
+    scores_deleted: i64,
+    /// Raw points and rollup buckets.
+    metric_rows_deleted: i64,
+    sessions_refreshed: usize,
+    partitions_dropped: usize,
+}
+
+pub(super) async fn purge(
+    state: &AppState,
+    params: &PurgeExpiredParams,
+    job: &JobHandle,
+) -> Result<(), JobError> {
+    let now = Utc::now();
+    let scopes: Vec<(Scope, DateTime<Utc>)> = params
+        .scopes(&state.retention)
+        .into_iter()
+        .map(|scope| {
+            let cutoff = now - chrono::Duration::days(scope.days.into());
+            (scope, cutoff)
+        })
+        .collect();
+
+    let mut total = 0;
+    for (scope, cutoff) in &scopes {
+        total += count_expired(state, "traces", r#""timestamp""#, scope, *cutoff).await?;
+        for (table, column) in EXPIRING_TABLES {
+            total += count_expired(state, table, column, scope, *cutoff).await?;
+        }
+    }
+    job.set_total(total).await?;
+
+    let mut progress = Progress::default();
+    let mut processed = 0;
+    if let Some(days) = params.partition_days(&state.retention) {
+        let cutoff = now - chrono::Duration::days(days.into());
+        for partition in partitions::expired(&state.pool, cutoff).await? {
+            let dropped = gated(state, drop_partition(state, &partition, &mut progress)).await?;
+            processed += dropped;
+            job.report(&progress, processed).await?;
+        }
+    }
+    for (scope, cutoff) in &scopes {
+        loop {
+            let deleted = gated(
+                state,
+                purge_traces_batch(state, scope, *cutoff, &mut progress),
+            )
+            .await?;
+            processed += deleted;
+            job.report(&progress, processed).await?;
+            if deleted < BATCH_SIZE {
+                break;
+            }
+        }
+        for (table, column) in EXPIRING_TABLES {
+            loop {
+                let deleted =
+                    gated(state, purge_batch(state, table, column, scope, *cutoff)).await?;
+                if table == "scores" {
+                    progress.scores_deleted += deleted;
+                } else {
+                    progress.metric_rows_deleted += deleted;
+                }
+                processed += deleted;
+                job.report(&progress, processed).await?;
+                if deleted < BATCH_SIZE {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs one batch through the write gate.
+async fn gated(
+    state: &AppState,
+    batch: impl std::future::Future<Output = Result<i64, sqlx::Error>>,
+) -> Result<i64, JobError> {
+    state
+        .write_gate
+        .try_reserve(1)
+        .map_err(|mode| JobError::WritesPaused(mode.as_str()))?;
+    let result = batch.await;
+    state.write_gate.release(1);
+    Ok(result?)
+}
+
+/// `WHERE` clause of the rows of `scope` whose `column` is before `$1`.
+fn scope_filter(column: &str) -> String {
+    format!("{column} < $1 AND ($2::TEXT IS NULL OR project_id = $2) AND project_id <> ALL($3)")
+}
+
+async fn count_expired(
+    state: &AppState,
+    table: &str,
+    column: &str,
+    scope: &Scope,
+    cutoff: DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM {table} WHERE {}",
+        scope_filter(column)
+    ))
+    .bind(cutoff)
+    .bind(scope.project_id.as_deref())
+    .bind(&scope.except)
+    .fetch_one(&state.pool)
+    .await
+}
+
+/// Deletes the next [`BATCH_SIZE`] expired traces with everything attached
+/// to them. Returns the traces deleted.
+async fn purge_traces_batch(
+    state: &AppState,
+    scope: &Scope,
+    cutoff: DateTime<Utc>,
+    progress: &mut Progress,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = state.pool.begin().await?;
+    let trace_ids: Vec<Uuid> = sqlx::query_scalar(&format!(
+        r#"SELECT id FROM traces WHERE {} ORDER BY "timestamp" LIMIT $4 FOR UPDATE SKIP LOCKED"#,
+        scope_filter(r#""timestamp""#)
+    ))
+    .bind(cutoff)
+    .bind(scope.project_id.as_deref())
+    .bind(&scope.except)
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+    if trace_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let mut deleted = trace_delete::delete_traces(&mut tx, &trace_ids).await?;
+    tx.commit().await?;
+    state.delete_offloaded(std::mem::take(&mut deleted.offloaded));
+
+    progress.traces_deleted += deleted.traces;
+    progress.observations_deleted += deleted.observations;
+    progress.scores_deleted += deleted.scores;
+    progress.sessions_refreshed += deleted.sessions_refreshed;
+    Ok(trace_ids.len() as i64)
+}
+
+/// Drops an expired trace partition. Returns the traces deleted.
+async fn drop_partition(
+    state: &AppState,
+    partition: &partitions::Partition,
+    progress: &mut Progress,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = state.pool.begin().await?;
+    let mut dropped = partitions::drop_partition(&mut tx, partition).await?;
+    tx.commit().await?;
+    state.delete_offloaded(std::mem::take(&mut dropped.offloaded));
+    tracing::info!(
+        partition = %partition.name,
+        traces = dropped.traces,
+        "retention: dropped partition"
+    );
+
+    progress.traces_deleted += dropped.traces;
+    progress.observations_deleted += dropped.observations;
+    progress.scores_deleted += dropped.scores;
+    progress.sessions_refreshed += dropped.sessions_refreshed;
+    progress.partitions_dropped += 1;
+    Ok(dropped.traces)
+}
+
+/// Deletes the next [`BATCH_SIZE`] expired rows of `table`.
+async fn purge_batch(
+    state: &AppState,
+    table: &str,
+    column: &str,
+    scope: &Scope,
+    cutoff: DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(&format!(
+        "DELETE FROM {table} WHERE id IN (SELECT id FROM {table} WHERE {} LIMIT $4 FOR UPDATE SKIP LOCKED)",
+        scope_filter(column)
+    ))
+    .bind(cutoff)
+    .bind(scope.project_id.as_deref())
+    .bind(&scope.except)
+    .bind(BATCH_SIZE)
+    .execute(&state.pool)
+    .await?;
+    Ok(result.rows_affected() as i64)
+}
+
+/// Starts a `purge_expired` job every `interval` while writes are enabled,
+/// unless the previous one is still running.
+pub(crate) async fn retention_worker(state: AppState, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if state.write_gate.mode() != WriteMode::Normal {
+            continue;
+        }
+        let req = JobRequest::PurgeExpired(PurgeExpiredParams::default());
+        if let Err(err) = super::start_unless_running(&state, req).await {
+            tracing::error!(error = %err, "retention: failed to start purge");
+        }
+    }
+}
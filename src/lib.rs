@@ -1,7 +1,15 @@
+mod alerts;
 mod app;
 mod http;
 mod ingest;
+mod jobs;
+mod offload;
+mod settings;
 mod state;
+#[cfg(feature = "ui")]
+mod ui;
+mod webhooks;
 
-pub use app::run_server;
+pub use app::{run_server, serve};
+pub use settings::{load_config, usage};
 pub use state::ServerConfig;
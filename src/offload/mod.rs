@@ -0,0 +1,180 @@
+//! Offloading of large observation payloads to object storage (S3 or a
+//! compatible store such as MinIO), enabled by `XTRACE_OFFLOAD_S3_BUCKET`.
+//!
+//! When the JSON of an observation's `input` or `output` is larger than
+//! `XTRACE_OFFLOAD_THRESHOLD_BYTES`, the ingest worker stores it as an object
+//! and keeps the object key (`input_offload` / `output_offload`) and a
+//! preview in Postgres: the start of the JSON text, as a string. Trace
+//! details and single observations read the bodies back; lists, search,
+//! exports and analytics see the preview. When an upload fails, the payload
+//! is stored inline.
+//!
+//! Objects are keyed by project, trace, observation and a hash of the body,
+//! so retried writes put the same object. Deleting or erasing traces deletes
+//! their objects.
+
+pub(crate) mod s3;
+
+use std::sync::Arc;
+
+use serde_json::Value as JsonValue;
+use tokio::task::JoinSet;
+
+use crate::ingest::batch::ObservationIngest;
+use s3::{sha256_hex, S3Client};
+
+/// Objects fetched or deleted at once.
+const MAX_CONCURRENT_REQUESTS: usize = 16;
+
+pub(crate) struct Offloader {
+    client: S3Client,
+    /// Prepended to every object key.
+    prefix: String,
+    threshold_bytes: usize,
+    preview_bytes: usize,
+}
+
+/// The first `max` bytes of `text` (cut at a character boundary), marked as
+/// cut.
+fn preview(text: &str, max: usize) -> String {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
+
+impl Offloader {
+    pub(crate) fn new(
+        client: S3Client,
+        prefix: String,
+        threshold_bytes: usize,
+        preview_bytes: usize,
+    ) -> Self {
+        Self {
+            client,
+            prefix,
+            threshold_bytes,
+            preview_bytes,
+        }
+    }
+
+    /// Moves the `input` and `output` of `obs` that exceed the threshold to
+    /// object storage, leaving previews.
+    pub(crate) async fn offload(&self, project_id: &str, obs: &mut ObservationIngest) {
+        let (trace_id, observation_id) = (obs.traceId, obs.id);
+        for (field, value, key) in [
+            ("input", &mut obs.input, &mut obs.input_offload),
+            ("output", &mut obs.output, &mut obs.output_offload),
+        ] {
+            let Some(body) = value.as_ref().map(|v| v.to_string()) else {
+                continue;
+            };
+            if body.len() <= self.threshold_bytes {
+                continue;
+            }
+            let object = format!(
+                "{}{project_id}/{trace_id}/{observation_id}/{field}-{}.json",
+                self.prefix,
+                &sha256_hex(body.as_bytes())[..16]
+            );
+            let shown = preview(&body, self.preview_bytes);
+            match self
+                .client
+                .put(&object, body.into_bytes(), "application/json")
+                .await
+            {
+                Ok(()) => {
+                    *value = Some(JsonValue::String(shown));
+                    *key = Some(object);
+                }
+                Err(err) => tracing::warn!(
+                    error = %err,
+                    %observation_id,
+                    field,
+                    "offload: upload failed, storing the payload inline"
+                ),
+            }
+        }
+    }
+
+    async fn fetch(&self, key: &str) -> Result<JsonValue, String> {
+        let body = self.client.get(key).await?;
+        serde_json::from_slice(&body).map_err(|e| format!("invalid JSON in {key}: {e}"))
+    }
+
+    /// Puts the bodies of `targets` (a value and the key of its object) in
+    /// place of their previews. A body that cannot be read keeps its preview.
+    pub(crate) async fn rehydrate(
+        self: &Arc<Self>,
+        targets: Vec<(&mut Option<JsonValue>, String)>,
+    ) {
+        let mut values = Vec::with_capacity(targets.len());
+        let mut fetches = JoinSet::new();
+        for (i, (value, key)) in targets.into_iter().enumerate() {
+            values.push(value);
+            if fetches.len() >= MAX_CONCURRENT_REQUESTS {
+                apply(fetches.join_next().await, &mut values);
+            }
+            let offloader = self.clone();
+            fetches.spawn(async move {
+                let result = offloader.fetch(&key).await;
+                (i, key, result)
+            });
+        }
+        while let Some(joined) = fetches.join_next().await {
+            apply(Some(joined), &mut values);
+        }
+    }
+
+    /// Deletes objects in the background, e.g. of deleted traces.
+    pub(crate) fn delete(self: &Arc<Self>, keys: Vec<String>) {
+        if keys.is_empty() {
+            return;
+        }
+        let offloader = self.clone();
+        tokio::spawn(async move {
+            let mut deletes = JoinSet::new();
+            for key in keys {
+                if deletes.len() >= MAX_CONCURRENT_REQUESTS {
+                    deletes.join_next().await;
+                }
+                let offloader = offloader.clone();
+                deletes.spawn(async move {
+                    if let Err(err) = offloader.client.delete(&key).await {
+                        tracing::warn!(error = %err, key, "offload: delete failed");
+                    }
+                });
+            }
+            while deletes.join_next().await.is_some() {}
+        });
+    }
+}
+
+type Fetched = (usize, String, Result<JsonValue, String>);
+
+fn apply(
+    joined: Option<Result<Fetched, tokio::task::JoinError>>,
+    values: &mut [&mut Option<JsonValue>],
+) {
+    match joined {
+        Some(Ok((i, _, Ok(body)))) => *values[i] = Some(body),
+        Some(Ok((_, key, Err(err)))) => {
+            tracing::warn!(error = %err, key, "offload: fetch failed, returning the preview")
+        }
+        Some(Err(err)) => tracing::error!(error = %err, "offload: fetch task failed"),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_cut_at_character_boundaries() {
+        assert_eq!(preview("\"héllo\"", 3), "\"h…");
+        assert_eq!(preview("\"héllo\"", 4), "\"hé…");
+        assert_eq!(preview("ab", 10), "ab…");
+    }
+}
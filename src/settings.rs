@@ -0,0 +1,556 @@
+//! Layered server configuration: defaults, then a TOML file (`--config` or
+//! `XTRACE_CONFIG`), then environment variables, then command line flags.
+//!
+//! Every setting has a key in the file (`section.name`), one environment
+//! variable (a few also accept a legacy name) and, unless it holds a secret
+//! (see [`SECRETS`]), a flag (`--section-name`):
+//!
+//! ```toml
+//! [database]
+//! url = "postgres://xtrace@db/xtrace"
+//! max_connections = 40
+//!
+//! [ingest]
+//! workers = 4
+//!
+//! [sampling]
+//! rate = 0.25
+//! project_rates = { checkout = 1.0 }
+//! ```
+//!
+//! Lists (`["10.0.0.0/8", "127.0.0.1"]`) and tables of per-project values
+//! are turned into the comma separated forms the environment variables take.
+//! An empty environment variable unsets its setting, also over the file.
+//! Unknown keys, unknown flags and values that do not parse fail startup
+//! with the setting and where its value came from.
+
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+
+use crate::{jobs::partitions::PartitionInterval, state::ServerConfig};
+
+type Apply = fn(&mut ServerConfig, &str) -> Result<(), String>;
+
+struct Setting {
+    /// Key in the config file; the flag is derived from it.
+    key: &'static str,
+    /// Environment variables, in order of precedence.
+    env: &'static [&'static str],
+    apply: Apply,
+}
+
+fn text(v: &str) -> Result<String, String> {
+    Ok(v.to_string())
+}
+
+/// `None` for an empty value.
+fn optional(v: &str) -> Result<Option<String>, String> {
+    Ok(Some(v.trim()).filter(|v| !v.is_empty()).map(String::from))
+}
+
+fn number<T: FromStr>(v: &str) -> Result<T, String> {
+    v.trim()
+        .parse()
+        .map_err(|_| format!("expected a number, got {v:?}"))
+}
+
+fn positive<T: FromStr + PartialOrd + Default>(v: &str) -> Result<T, String> {
+    number(v)
+        .ok()
+        .filter(|n| *n > T::default())
+        .ok_or_else(|| format!("expected a number greater than 0, got {v:?}"))
+}
+
+fn flag(v: &str) -> Result<bool, String> {
+    match v.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => Err(format!("expected true or false, got {v:?}")),
+    }
+}
+
+fn timestamp(v: &str) -> Result<Option<DateTime<Utc>>, String> {
+    if v.trim().is_empty() {
+        return Ok(None);
+    }
+    DateTime::parse_from_rfc3339(v.trim())
+        .map(|t| Some(t.with_timezone(&Utc)))
+        .map_err(|_| format!("expected an RFC 3339 date, got {v:?}"))
+}
+
+fn completion_mode(v: &str) -> Result<bool, String> {
+    match v.trim().to_ascii_lowercase().as_str() {
+        "strict" => Ok(true),
+        "lenient" | "" => Ok(false),
+        _ => Err(format!("expected lenient or strict, got {v:?}")),
+    }
+}
+
+fn partition_interval(v: &str) -> Result<String, String> {
+    PartitionInterval::parse(v).map(|_| v.trim().to_ascii_lowercase())
+}
+
+#[rustfmt::skip]
+const SETTINGS: &[Setting] = &[
+    // server
+    Setting { key: "server.bind_addr", env: &["BIND_ADDR"], apply: |c, v| text(v).map(|v| c.bind_addr = v) },
+    Setting { key: "server.otlp_grpc_addr", env: &["XTRACE_OTLP_GRPC_ADDR"], apply: |c, v| optional(v).map(|v| c.otlp_grpc_addr = v) },
+    Setting { key: "server.default_project_id", env: &["DEFAULT_PROJECT_ID"], apply: |c, v| text(v).map(|v| c.default_project_id = v) },
+    Setting { key: "server.max_request_body_bytes", env: &["XTRACE_MAX_REQUEST_BODY_BYTES"], apply: |c, v| positive(v).map(|v| c.max_request_body_bytes = v) },
+    Setting { key: "server.read_only", env: &["XTRACE_READ_ONLY"], apply: |c, v| flag(v).map(|v| c.read_only = v) },
+    Setting { key: "server.maintenance_retry_after_secs", env: &["XTRACE_MAINTENANCE_RETRY_AFTER_SECS"], apply: |c, v| number(v).map(|v| c.maintenance_retry_after_secs = v) },
+    // database
+    Setting { key: "database.url", env: &["DATABASE_URL"], apply: |c, v| text(v).map(|v| c.database_url = v) },
+    Setting { key: "database.max_connections", env: &["XTRACE_DB_MAX_CONNECTIONS"], apply: |c, v| positive(v).map(|v| c.db_max_connections = Some(v)) },
+    Setting { key: "database.min_connections", env: &["XTRACE_DB_MIN_CONNECTIONS"], apply: |c, v| number(v).map(|v| c.db_min_connections = v) },
+    Setting { key: "database.acquire_timeout_secs", env: &["XTRACE_DB_ACQUIRE_TIMEOUT_SECS"], apply: |c, v| positive(v).map(|v| c.db_acquire_timeout_secs = v) },
+    // tls
+    Setting { key: "tls.cert", env: &["XTRACE_TLS_CERT"], apply: |c, v| optional(v).map(|v| c.tls_cert = v) },
+    Setting { key: "tls.key", env: &["XTRACE_TLS_KEY"], apply: |c, v| optional(v).map(|v| c.tls_key = v) },
+    Setting { key: "tls.client_ca", env: &["XTRACE_TLS_CLIENT_CA"], apply: |c, v| optional(v).map(|v| c.tls_client_ca = v) },
+    Setting { key: "tls.ingest_client_cert", env: &["XTRACE_INGEST_CLIENT_CERT"], apply: |c, v| optional(v).map(|v| c.ingest_client_cert = v) },
+    Setting { key: "tls.query_client_cert", env: &["XTRACE_QUERY_CLIENT_CERT"], apply: |c, v| optional(v).map(|v| c.query_client_cert = v) },
+    Setting { key: "tls.admin_client_cert", env: &["XTRACE_ADMIN_CLIENT_CERT"], apply: |c, v| optional(v).map(|v| c.admin_client_cert = v) },
+    // network
+    Setting { key: "network.query_allowed_ips", env: &["XTRACE_QUERY_ALLOWED_IPS"], apply: |c, v| optional(v).map(|v| c.query_allowed_ips = v) },
+    Setting { key: "network.ingest_allowed_ips", env: &["XTRACE_INGEST_ALLOWED_IPS"], apply: |c, v| optional(v).map(|v| c.ingest_allowed_ips = v) },
+    Setting { key: "network.admin_allowed_ips", env: &["XTRACE_ADMIN_ALLOWED_IPS"], apply: |c, v| optional(v).map(|v| c.admin_allowed_ips = v) },
+    Setting { key: "network.trusted_proxies", env: &["XTRACE_TRUSTED_PROXIES"], apply: |c, v| optional(v).map(|v| c.trusted_proxies = v) },
+    // auth
+    Setting { key: "auth.bearer_token", env: &["API_BEARER_TOKEN"], apply: |c, v| text(v).map(|v| c.api_bearer_token = v) },
+    Setting { key: "auth.public_key", env: &["XTRACE_PUBLIC_KEY", "LANGFUSE_PUBLIC_KEY"], apply: |c, v| optional(v).map(|v| c.langfuse_public_key = v) },
+    Setting { key: "auth.secret_key", env: &["XTRACE_SECRET_KEY", "LANGFUSE_SECRET_KEY"], apply: |c, v| optional(v).map(|v| c.langfuse_secret_key = v) },
+    Setting { key: "auth.ingest_token", env: &["XTRACE_INGEST_TOKEN"], apply: |c, v| optional(v).map(|v| c.ingest_token = v) },
+    Setting { key: "auth.read_token", env: &["XTRACE_READ_TOKEN"], apply: |c, v| optional(v).map(|v| c.read_token = v) },
+    Setting { key: "auth.keys_file", env: &["XTRACE_KEYS_FILE"], apply: |c, v| optional(v).map(|v| c.keys_file = v) },
+    Setting { key: "auth.keys_reload_secs", env: &["XTRACE_KEYS_RELOAD_SECS"], apply: |c, v| positive(v).map(|v| c.keys_reload_secs = v) },
+    Setting { key: "auth.allow_unauthenticated_compat", env: &["XTRACE_ALLOW_UNAUTHENTICATED_COMPAT"], apply: |c, v| flag(v).map(|v| c.allow_unauthenticated_compat = v) },
+    // limits
+    Setting { key: "limits.rate_limit_qps", env: &["RATE_LIMIT_QPS"], apply: |c, v| positive(v).map(|v| c.rate_limit_qps = v) },
+    Setting { key: "limits.rate_limit_burst", env: &["RATE_LIMIT_BURST"], apply: |c, v| positive(v).map(|v| c.rate_limit_burst = v) },
+    Setting { key: "limits.ingest_rate_limit_qps", env: &["XTRACE_INGEST_RATE_LIMIT_QPS"], apply: |c, v| number(v).map(|v| c.ingest_rate_limit_qps = v) },
+    Setting { key: "limits.ingest_rate_limit_burst", env: &["XTRACE_INGEST_RATE_LIMIT_BURST"], apply: |c, v| number(v).map(|v| c.ingest_rate_limit_burst = v) },
+    Setting { key: "limits.ingest_cost_per_sec", env: &["XTRACE_INGEST_COST_PER_SEC"], apply: |c, v| number(v).map(|v| c.ingest_cost_per_sec = v) },
+    Setting { key: "limits.ingest_cost_burst", env: &["XTRACE_INGEST_COST_BURST"], apply: |c, v| number(v).map(|v| c.ingest_cost_burst = v) },
+    Setting { key: "limits.ingest_cost_bytes_per_unit", env: &["XTRACE_INGEST_COST_BYTES_PER_UNIT"], apply: |c, v| positive(v).map(|v| c.ingest_cost_bytes_per_unit = v) },
+    Setting { key: "limits.default_query_window_secs", env: &["XTRACE_DEFAULT_QUERY_WINDOW_SECS"], apply: |c, v| number(v).map(|v| c.default_query_window_secs = v) },
+    Setting { key: "limits.max_query_range_secs", env: &["XTRACE_MAX_QUERY_RANGE_SECS"], apply: |c, v| number(v).map(|v| c.max_query_range_secs = v) },
+    Setting { key: "limits.batch_get_max_traces", env: &["XTRACE_BATCH_GET_MAX_TRACES"], apply: |c, v| positive(v).map(|v| c.batch_get_max_traces = v) },
+    Setting { key: "limits.quota_interval_secs", env: &["XTRACE_QUOTA_INTERVAL_SECS"], apply: |c, v| positive(v).map(|v| c.quota_interval_secs = v) },
+    // ingest
+    Setting { key: "ingest.queue_capacity", env: &["XTRACE_INGEST_QUEUE_CAPACITY"], apply: |c, v| positive(v).map(|v| c.ingest_queue_capacity = v) },
+    Setting { key: "ingest.enqueue_timeout_ms", env: &["XTRACE_INGEST_ENQUEUE_TIMEOUT_MS"], apply: |c, v| number(v).map(|v| c.ingest_enqueue_timeout_ms = v) },
+    Setting { key: "ingest.retry_after_secs", env: &["XTRACE_INGEST_RETRY_AFTER_SECS"], apply: |c, v| positive(v).map(|v| c.ingest_retry_after_secs = v) },
+    Setting { key: "ingest.workers", env: &["XTRACE_INGEST_WORKERS"], apply: |c, v| positive(v).map(|v| c.ingest_workers = v) },
+    Setting { key: "ingest.max_batches", env: &["XTRACE_INGEST_MAX_BATCHES"], apply: |c, v| positive(v).map(|v| c.ingest_max_batches = v) },
+    Setting { key: "ingest.batch_window_ms", env: &["XTRACE_INGEST_BATCH_WINDOW_MS"], apply: |c, v| number(v).map(|v| c.ingest_batch_window_ms = v) },
+    Setting { key: "ingest.dedup_ttl_secs", env: &["XTRACE_INGEST_DEDUP_TTL_SECS"], apply: |c, v| number(v).map(|v| c.ingest_dedup_ttl_secs = v) },
+    Setting { key: "ingest.wal_dir", env: &["XTRACE_WAL_DIR"], apply: |c, v| optional(v).map(|v| c.wal_dir = v) },
+    Setting { key: "ingest.trace_completion_mode", env: &["XTRACE_TRACE_COMPLETION_MODE"], apply: |c, v| completion_mode(v).map(|v| c.strict_trace_completion = v) },
+    Setting { key: "ingest.conflict_policy", env: &["XTRACE_CONFLICT_POLICY"], apply: |c, v| optional(v).map(|v| c.conflict_policy = v) },
+    Setting { key: "ingest.project_conflict_policies", env: &["XTRACE_PROJECT_CONFLICT_POLICIES"], apply: |c, v| optional(v).map(|v| c.project_conflict_policies = v) },
+    Setting { key: "ingest.model_aliases", env: &["XTRACE_MODEL_ALIASES"], apply: |c, v| optional(v).map(|v| c.model_aliases = v) },
+    Setting { key: "ingest.secret_scanning", env: &["XTRACE_SECRET_SCANNING"], apply: |c, v| flag(v).map(|v| c.secret_scanning = v) },
+    Setting { key: "ingest.raw_ttl_secs", env: &["XTRACE_RAW_INGEST_TTL_SECS"], apply: |c, v| number(v).map(|v| c.raw_ingest_ttl_secs = v) },
+    Setting { key: "ingest.raw_max_body_bytes", env: &["XTRACE_RAW_INGEST_MAX_BODY_BYTES"], apply: |c, v| positive(v).map(|v| c.raw_ingest_max_body_bytes = v) },
+    // sampling
+    Setting { key: "sampling.rate", env: &["XTRACE_SAMPLE_RATE"], apply: |c, v| number(v).map(|v| c.sample_rate = v) },
+    Setting { key: "sampling.project_rates", env: &["XTRACE_PROJECT_SAMPLE_RATES"], apply: |c, v| optional(v).map(|v| c.project_sample_rates = v) },
+    Setting { key: "sampling.keep_errors", env: &["XTRACE_SAMPLE_KEEP_ERRORS"], apply: |c, v| flag(v).map(|v| c.sample_keep_errors = v) },
+    // kafka
+    Setting { key: "kafka.brokers", env: &["XTRACE_KAFKA_BROKERS"], apply: |c, v| optional(v).map(|v| c.kafka_brokers = v) },
+    Setting { key: "kafka.topic", env: &["XTRACE_KAFKA_TOPIC"], apply: |c, v| text(v).map(|v| c.kafka_topic = v) },
+    Setting { key: "kafka.group_id", env: &["XTRACE_KAFKA_GROUP_ID"], apply: |c, v| text(v).map(|v| c.kafka_group_id = v) },
+    // retention
+    Setting { key: "retention.days", env: &["XTRACE_RETENTION_DAYS"], apply: |c, v| number(v).map(|v| c.retention_days = v) },
+    Setting { key: "retention.project_days", env: &["XTRACE_PROJECT_RETENTION_DAYS"], apply: |c, v| optional(v).map(|v| c.project_retention_days = v) },
+    Setting { key: "retention.interval_secs", env: &["XTRACE_RETENTION_INTERVAL_SECS"], apply: |c, v| positive(v).map(|v| c.retention_interval_secs = v) },
+    Setting { key: "retention.partition_interval", env: &["XTRACE_PARTITION_INTERVAL"], apply: |c, v| partition_interval(v).map(|v| c.partition_interval = v) },
+    Setting { key: "retention.metrics_rollup_after_secs", env: &["XTRACE_METRICS_ROLLUP_AFTER_SECS"], apply: |c, v| number(v).map(|v| c.metrics_rollup_after_secs = v) },
+    Setting { key: "retention.metrics_hourly_rollup_after_secs", env: &["XTRACE_METRICS_HOURLY_ROLLUP_AFTER_SECS"], apply: |c, v| number(v).map(|v| c.metrics_hourly_rollup_after_secs = v) },
+    Setting { key: "retention.metrics_rollup_interval_secs", env: &["XTRACE_METRICS_ROLLUP_INTERVAL_SECS"], apply: |c, v| positive(v).map(|v| c.metrics_rollup_interval_secs = v) },
+    // offload
+    Setting { key: "offload.s3_bucket", env: &["XTRACE_OFFLOAD_S3_BUCKET"], apply: |c, v| optional(v).map(|v| c.offload_s3_bucket = v) },
+    Setting { key: "offload.s3_endpoint", env: &["XTRACE_OFFLOAD_S3_ENDPOINT"], apply: |c, v| optional(v).map(|v| c.offload_s3_endpoint = v) },
+    Setting { key: "offload.s3_region", env: &["XTRACE_OFFLOAD_S3_REGION", "AWS_REGION"], apply: |c, v| text(v).map(|v| c.offload_s3_region = v) },
+    Setting { key: "offload.s3_access_key", env: &["XTRACE_OFFLOAD_S3_ACCESS_KEY", "AWS_ACCESS_KEY_ID"], apply: |c, v| optional(v).map(|v| c.offload_s3_access_key = v) },
+    Setting { key: "offload.s3_secret_key", env: &["XTRACE_OFFLOAD_S3_SECRET_KEY", "AWS_SECRET_ACCESS_KEY"], apply: |c, v| optional(v).map(|v| c.offload_s3_secret_key = v) },
+    Setting { key: "offload.s3_prefix", env: &["XTRACE_OFFLOAD_S3_PREFIX"], apply: |c, v| text(v).map(|v| c.offload_s3_prefix = v) },
+    Setting { key: "offload.threshold_bytes", env: &["XTRACE_OFFLOAD_THRESHOLD_BYTES"], apply: |c, v| positive(v).map(|v| c.offload_threshold_bytes = v) },
+    Setting { key: "offload.preview_bytes", env: &["XTRACE_OFFLOAD_PREVIEW_BYTES"], apply: |c, v| number(v).map(|v| c.offload_preview_bytes = v) },
+    // webhooks and alerts
+    Setting { key: "webhooks.url", env: &["XTRACE_WEBHOOK_URL"], apply: |c, v| optional(v).map(|v| c.webhook_url = v) },
+    Setting { key: "webhooks.max_age_secs", env: &["XTRACE_WEBHOOK_MAX_AGE_SECS"], apply: |c, v| number(v).map(|v| c.webhook_max_age_secs = v) },
+    Setting { key: "alerts.interval_secs", env: &["XTRACE_ALERT_INTERVAL_SECS"], apply: |c, v| number(v).map(|v| c.alert_interval_secs = v) },
+    Setting { key: "alerts.smtp_url", env: &["XTRACE_SMTP_URL"], apply: |c, v| optional(v).map(|v| c.smtp_url = v) },
+    Setting { key: "alerts.smtp_from", env: &["XTRACE_SMTP_FROM"], apply: |c, v| text(v).map(|v| c.smtp_from = v) },
+    // api versions
+    Setting { key: "api.v1_deprecated_at", env: &["XTRACE_API_V1_DEPRECATED_AT"], apply: |c, v| timestamp(v).map(|v| c.api_v1_deprecated_at = v) },
+    Setting { key: "api.v1_sunset_at", env: &["XTRACE_API_V1_SUNSET_AT"], apply: |c, v| timestamp(v).map(|v| c.api_v1_sunset_at = v) },
+];
+
+/// Settings that hold credentials. They have no flag, since the command line
+/// shows in the process list; they come from the environment or the file.
+const SECRETS: &[&str] = &[
+    "database.url",
+    "auth.bearer_token",
+    "auth.secret_key",
+    "auth.ingest_token",
+    "auth.read_token",
+    "offload.s3_secret_key",
+    "webhooks.url",
+    "alerts.smtp_url",
+];
+
+fn flag_name(key: &str) -> String {
+    format!("--{}", key.replace(['.', '_'], "-"))
+}
+
+/// `--help` text: the flags with their file keys and environment variables.
+pub fn usage() -> String {
+    let mut out = String::from(
+        "Usage: xtrace [--config <file>] [--<setting> <value>]...\n\n\
+         Settings are read from the TOML config file (also XTRACE_CONFIG), then\n\
+         the environment, then flags; later sources win. Secrets have no flag.\n\n",
+    );
+    for setting in SETTINGS {
+        let flag = if SECRETS.contains(&setting.key) {
+            "-".to_string()
+        } else {
+            flag_name(setting.key)
+        };
+        out.push_str(&format!(
+            "  {:<48} {:<40} {}\n",
+            flag,
+            setting.key,
+            setting.env.join(", ")
+        ));
+    }
+    out
+}
+
+/// A setting's value and where it came from, for error messages.
+struct Value {
+    value: String,
+    source: String,
+}
+
+/// Builds the configuration from `args` (without the program name) and the
+/// process environment.
+pub fn load_config(args: impl IntoIterator<Item = String>) -> anyhow::Result<ServerConfig> {
+    load(args, |name| std::env::var(name).ok())
+}
+
+fn load(
+    args: impl IntoIterator<Item = String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<ServerConfig> {
+    let mut values: Vec<Option<Value>> = SETTINGS.iter().map(|_| None).collect();
+    let flags = parse_args(args)?;
+
+    let config_file = flags
+        .iter()
+        .find(|(name, _)| name == "--config")
+        .map(|(_, v)| v.clone())
+        .or_else(|| env("XTRACE_CONFIG").filter(|v| !v.trim().is_empty()));
+    if let Some(path) = &config_file {
+        for (i, value) in read_file(Path::new(path))? {
+            values[i] = Some(Value {
+                value,
+                source: format!("{} in {path}", SETTINGS[i].key),
+            });
+        }
+    }
+
+    for (i, setting) in SETTINGS.iter().enumerate() {
+        let vars: Vec<(&str, String)> = setting
+            .env
+            .iter()
+            .filter_map(|name| env(name).map(|v| (*name, v)))
+            .collect();
+        if let Some((name, value)) = vars.iter().find(|(_, v)| !v.is_empty()) {
+            values[i] = Some(Value {
+                value: value.clone(),
+                source: format!("environment variable {name}"),
+            });
+        } else if !vars.is_empty() {
+            // Set but empty: back to the default.
+            values[i] = None;
+        }
+    }
+
+    for (name, value) in flags {
+        if name == "--config" {
+            continue;
+        }
+        let i = SETTINGS
+            .iter()
+            .position(|s| flag_name(s.key) == name)
+            .ok_or_else(|| anyhow!("unknown flag {name} (see --help)"))?;
+        if SECRETS.contains(&SETTINGS[i].key) {
+            bail!(
+                "{name} is not accepted, flags show in the process list: set {} or {} in the config file",
+                SETTINGS[i].env[0],
+                SETTINGS[i].key
+            );
+        }
+        values[i] = Some(Value {
+            value,
+            source: format!("flag {name}"),
+        });
+    }
+
+    let mut config = ServerConfig::new("", "");
+    for (setting, value) in SETTINGS.iter().zip(values) {
+        if let Some(Value { value, source }) = value {
+            (setting.apply)(&mut config, &value)
+                .map_err(|e| anyhow!("invalid {} (from {source}): {e}", setting.key))?;
+        }
+    }
+    if config.database_url.is_empty() {
+        bail!("database.url is required (DATABASE_URL or the config file)");
+    }
+    if config.api_bearer_token.is_empty() {
+        bail!("auth.bearer_token is required (API_BEARER_TOKEN or the config file)");
+    }
+    Ok(config)
+}
+
+/// `--name value` and `--name=value` pairs.
+fn parse_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Vec<(String, String)>> {
+    let mut args = args.into_iter();
+    let mut flags = Vec::new();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            bail!("unexpected argument {arg:?} (see --help)");
+        }
+        match arg.split_once('=') {
+            Some((name, value)) => flags.push((name.to_string(), value.to_string())),
+            None => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for {arg}"))?;
+                flags.push((arg, value));
+            }
+        }
+    }
+    Ok(flags)
+}
+
+/// Settings in a config file, as indexes into [`SETTINGS`] and values.
+fn read_file(path: &Path) -> anyhow::Result<Vec<(usize, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    let doc: JsonValue = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))?,
+        _ => bail!("config file {} must be TOML (.toml)", path.display()),
+    };
+    let mut out = Vec::new();
+    collect(&doc, "", &mut out).with_context(|| format!("config file {}", path.display()))?;
+    Ok(out)
+}
+
+fn collect(node: &JsonValue, prefix: &str, out: &mut Vec<(usize, String)>) -> anyhow::Result<()> {
+    let JsonValue::Object(map) = node else {
+        bail!("expected a table at {prefix:?}");
+    };
+    for (name, value) in map {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}.{name}")
+        };
+        if let Some(i) = SETTINGS.iter().position(|s| s.key == key) {
+            out.push((i, flatten(value).map_err(|e| anyhow!("{key}: {e}"))?));
+        } else if value.is_object() && prefix.is_empty() {
+            collect(value, &key, out)?;
+        } else {
+            bail!("unknown setting {key}");
+        }
+    }
+    Ok(())
+}
+
+/// The environment variable form of a file value.
+fn flatten(value: &JsonValue) -> Result<String, String> {
+    let scalar = |v: &JsonValue| match v {
+        JsonValue::String(s) => Ok(s.clone()),
+        JsonValue::Number(n) => Ok(n.to_string()),
+        JsonValue::Bool(b) => Ok(b.to_string()),
+        JsonValue::Null => Ok(String::new()),
+        _ => Err("expected a value, list or table of values".to_string()),
+    };
+    match value {
+        JsonValue::Array(items) => Ok(items
+            .iter()
+            .map(scalar)
+            .collect::<Result<Vec<_>, _>>()?
+            .join(",")),
+        JsonValue::Object(map) => Ok(map
+            .iter()
+            .map(|(k, v)| scalar(v).map(|v| format!("{k}={v}")))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(",")),
+        v => scalar(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    const REQUIRED: &[(&str, &str)] =
+        &[("DATABASE_URL", "postgres://db"), ("API_BEARER_TOKEN", "t")];
+
+    #[test]
+    fn layers_override_in_order() {
+        let dir = std::env::temp_dir().join(format!("xtrace-settings-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("xtrace.toml");
+        std::fs::write(
+            &file,
+            r#"
+[server]
+bind_addr = "0.0.0.0:9000"
+
+[ingest]
+workers = 4
+queue_capacity = 5000
+
+[network]
+query_allowed_ips = ["10.0.0.0/8", "127.0.0.1"]
+
+[sampling]
+rate = 0.25
+project_rates = { checkout = 1.0 }
+"#,
+        )
+        .unwrap();
+
+        let config = load(
+            args(&["--config", file.to_str().unwrap(), "--ingest-workers=8"]),
+            env(&[
+                ("DATABASE_URL", "postgres://db"),
+                ("API_BEARER_TOKEN", "t"),
+                ("XTRACE_INGEST_QUEUE_CAPACITY", "7000"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.bind_addr, "0.0.0.0:9000");
+        assert_eq!(config.ingest_workers, 8);
+        assert_eq!(config.ingest_queue_capacity, 7000);
+        assert_eq!(
+            config.query_allowed_ips.as_deref(),
+            Some("10.0.0.0/8,127.0.0.1")
+        );
+        assert_eq!(config.sample_rate, 0.25);
+        assert_eq!(config.project_sample_rates.as_deref(), Some("checkout=1.0"));
+        assert_eq!(config.rate_limit_qps, 20);
+
+        // An empty variable unsets the file's value.
+        let config = load(
+            args(&["--config", file.to_str().unwrap()]),
+            env(&[
+                ("DATABASE_URL", "postgres://db"),
+                ("API_BEARER_TOKEN", "t"),
+                ("BIND_ADDR", ""),
+                ("XTRACE_QUERY_ALLOWED_IPS", ""),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.bind_addr, ServerConfig::new("", "").bind_addr);
+        assert_eq!(config.query_allowed_ips, None);
+        assert_eq!(config.ingest_workers, 4);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn secrets_have_no_flags() {
+        let message = error(load(
+            args(&["--database-url", "postgres://user:pass@db"]),
+            env(&[("API_BEARER_TOKEN", "t")]),
+        ));
+        assert!(
+            message.contains("--database-url is not accepted")
+                && message.contains("DATABASE_URL")
+                && !message.contains("pass"),
+            "{message}"
+        );
+        assert!(error(load(args(&["--auth-bearer-token=t"]), env(REQUIRED)))
+            .contains("--auth-bearer-token is not accepted"));
+
+        let usage = usage();
+        assert!(usage.contains("--ingest-workers"));
+        for key in SECRETS {
+            assert!(SETTINGS.iter().any(|s| s.key == *key), "{key}");
+            assert!(!usage.contains(&flag_name(key)), "{key}");
+        }
+    }
+
+    fn error(result: anyhow::Result<ServerConfig>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => format!("{err:#}"),
+        }
+    }
+
+    #[test]
+    fn invalid_settings_fail() {
+        let err = |args_: &[&str], vars: &'static [(&'static str, &'static str)]| {
+            error(load(args(args_), env(vars)))
+        };
+        assert!(err(&[], &[]).contains("database.url is required"));
+        assert!(err(&["--ingest-wokers", "2"], REQUIRED).contains("unknown flag --ingest-wokers"));
+
+        let vars: &'static [(&str, &str)] = &[
+            ("DATABASE_URL", "postgres://db"),
+            ("API_BEARER_TOKEN", "t"),
+            ("XTRACE_INGEST_WORKERS", "0"),
+        ];
+        let message = err(&[], vars);
+        assert!(
+            message.contains(
+                "invalid ingest.workers (from environment variable XTRACE_INGEST_WORKERS)"
+            ),
+            "{message}"
+        );
+        assert!(err(&["--server-read-only", "maybe"], REQUIRED).contains("expected true or false"));
+
+        let file = std::env::temp_dir().join(format!("xtrace-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&file, "[ingest]\nworkerz = 2\n").unwrap();
+        let message = error(load(
+            args(&["--config", file.to_str().unwrap()]),
+            env(REQUIRED),
+        ));
+        assert!(
+            message.contains("unknown setting ingest.workerz"),
+            "{message}"
+        );
+        std::fs::remove_file(file).ok();
+
+        let file = std::env::temp_dir().join(format!("xtrace-{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::write(&file, "ingest:\n  workers: 2\n").unwrap();
+        let message = error(load(
+            args(&["--config", file.to_str().unwrap()]),
+            env(REQUIRED),
+        ));
+        assert!(message.contains("must be TOML"), "{message}");
+        std::fs::remove_file(file).ok();
+    }
+}
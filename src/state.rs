@@ -1,11 +1,35 @@
 use governor::{clock::DefaultClock, Quota};
 use sqlx::PgPool;
+use std::collections::BTreeMap;
 use std::num::NonZeroU32;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 
-use crate::{http::metrics::MetricsBatchRequest, ingest::batch::BatchIngestRequest};
+use crate::{
+    alerts::sinks::Mailer,
+    http::{
+        error::{ApiError, QueueFull},
+        ingest_limit::{IngestCost, IngestCostLimiter},
+        keys::KeyStore,
+        metrics::MetricsBatchRequest,
+        quotas::QuotaStore,
+        telemetry::Telemetry,
+        version::ApiVersionPolicy,
+        window::QueryWindows,
+    },
+    ingest::{
+        batch::{BatchIngestRequest, IngestQueue},
+        conflicts::ConflictPolicies,
+        pricing::PriceRegistry,
+        raw::{RawIngestRecorder, RawSource},
+        wal::Wal,
+    },
+    jobs::retention::RetentionPolicy,
+    offload::Offloader,
+};
 
 pub type KeyedRateLimiter =
     governor::RateLimiter<String, governor::state::keyed::DashMapStateStore<String>, DefaultClock>;
@@ -38,6 +62,150 @@ impl RateLimitStats {
     }
 }
 
+/// Whether the server accepts writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    Normal,
+    /// Writes are rejected with `503`; queries keep working.
+    ReadOnly,
+    /// Like read-only, and `/readyz` reports not ready so load balancers move
+    /// traffic away while the ingest queues are flushed.
+    Draining,
+}
+
+impl WriteMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WriteMode::Normal => "normal",
+            WriteMode::ReadOnly => "read_only",
+            WriteMode::Draining => "draining",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "normal" => Some(WriteMode::Normal),
+            "read_only" | "readonly" | "read-only" => Some(WriteMode::ReadOnly),
+            "drain" | "draining" => Some(WriteMode::Draining),
+            _ => None,
+        }
+    }
+}
+
+/// How writes behave when their queue is full.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Backpressure {
+    /// How long a request waits for queue space before `429`; zero rejects
+    /// it at once.
+    pub enqueue_timeout: Duration,
+    /// `Retry-After` sent with the `429`.
+    pub retry_after_secs: u64,
+}
+
+/// Why a payload was not queued.
+enum Unsent {
+    Full,
+    Closed,
+}
+
+/// Queues `payload` on `tx`, waiting until `deadline` at most while it is
+/// full.
+async fn send_until<T>(tx: &mpsc::Sender<T>, payload: T, deadline: Instant) -> Result<(), Unsent> {
+    let wait = deadline.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        return tx.try_send(payload).map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => Unsent::Full,
+            mpsc::error::TrySendError::Closed(_) => Unsent::Closed,
+        });
+    }
+    tx.send_timeout(payload, wait)
+        .await
+        .map_err(|err| match err {
+            mpsc::error::SendTimeoutError::Timeout(_) => Unsent::Full,
+            mpsc::error::SendTimeoutError::Closed(_) => Unsent::Closed,
+        })
+}
+
+/// Reserves room for `n` payloads on `tx` at once, waiting until `deadline` at
+/// most while it is full.
+async fn reserve_many_until<T>(
+    tx: &mpsc::Sender<T>,
+    n: usize,
+    deadline: Instant,
+) -> Result<mpsc::PermitIterator<'_, T>, Unsent> {
+    let wait = deadline.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        return tx.try_reserve_many(n).map_err(|err| match err {
+            mpsc::error::TrySendError::Full(()) => Unsent::Full,
+            mpsc::error::TrySendError::Closed(()) => Unsent::Closed,
+        });
+    }
+    match tokio::time::timeout(wait, tx.reserve_many(n)).await {
+        Ok(Ok(permit)) => Ok(permit),
+        Ok(Err(_)) => Err(Unsent::Closed),
+        Err(_) => Err(Unsent::Full),
+    }
+}
+
+/// Gate in front of the ingest and metrics queues: holds the current
+/// [`WriteMode`] and counts payloads accepted but not yet written.
+pub struct WriteGate {
+    mode: AtomicU8,
+    pending: AtomicU64,
+    pub retry_after_secs: u64,
+}
+
+impl WriteGate {
+    pub fn new(mode: WriteMode, retry_after_secs: u64) -> Self {
+        Self {
+            mode: AtomicU8::new(mode as u8),
+            pending: AtomicU64::new(0),
+            retry_after_secs,
+        }
+    }
+
+    pub fn mode(&self) -> WriteMode {
+        match self.mode.load(Ordering::SeqCst) {
+            1 => WriteMode::ReadOnly,
+            2 => WriteMode::Draining,
+            _ => WriteMode::Normal,
+        }
+    }
+
+    pub fn set_mode(&self, mode: WriteMode) {
+        self.mode.store(mode as u8, Ordering::SeqCst);
+    }
+
+    /// Reserves `n` pending payloads if writes are accepted. The reservation is
+    /// taken before the mode check, so once a drain starts every accepted
+    /// payload is visible in [`WriteGate::pending`].
+    pub fn try_reserve(&self, n: u64) -> Result<(), WriteMode> {
+        self.pending.fetch_add(n, Ordering::SeqCst);
+        match self.mode() {
+            WriteMode::Normal => Ok(()),
+            mode => {
+                self.release(n);
+                Err(mode)
+            }
+        }
+    }
+
+    /// Counts `n` payloads accepted by an earlier run (write-ahead log replay)
+    /// as pending, whatever the mode.
+    pub fn reserve_replayed(&self, n: u64) {
+        self.pending.fetch_add(n, Ordering::SeqCst);
+    }
+
+    /// Marks `n` pending payloads as written (or dropped).
+    pub fn release(&self, n: u64) {
+        self.pending.fetch_sub(n, Ordering::SeqCst);
+    }
+
+    pub fn pending(&self) -> u64 {
+        self.pending.load(Ordering::SeqCst)
+    }
+}
+
 pub fn mask_client_key(key: &str) -> String {
     if let Some(rest) = key.strip_prefix("bearer:") {
         if rest.len() > 8 {
@@ -52,35 +220,308 @@ pub fn mask_client_key(key: &str) -> String {
 
 pub struct ServerConfig {
     pub database_url: String,
+    /// Connections in the pool at most; by default 20 plus one per ingest worker.
+    pub db_max_connections: Option<u32>,
+    /// Connections the pool keeps open when idle.
+    pub db_min_connections: u32,
+    /// How long a request waits for a free connection before it fails.
+    pub db_acquire_timeout_secs: u64,
     pub api_bearer_token: String,
     pub bind_addr: String,
+    /// Listen address of the OTLP/gRPC trace receiver; disabled when unset.
+    pub otlp_grpc_addr: Option<String>,
+    /// PEM certificate chain; with `tls_key`, both listeners serve TLS.
+    pub tls_cert: Option<String>,
+    /// PEM private key of `tls_cert`.
+    pub tls_key: Option<String>,
+    /// PEM CA bundle client certificates are verified against.
+    pub tls_client_ca: Option<String>,
+    /// What a verified client certificate means for ingest endpoints
+    /// (`off`, `required` or `sufficient`).
+    pub ingest_client_cert: Option<String>,
+    /// Same for query endpoints, including shared traces.
+    pub query_client_cert: Option<String>,
+    /// Same for admin endpoints and `GET /metrics`.
+    pub admin_client_cert: Option<String>,
+    /// Source IP allowlist (CIDRs, comma separated) of the query endpoints.
+    pub query_allowed_ips: Option<String>,
+    /// Source IP allowlist of the ingest endpoints (HTTP and OTLP/gRPC).
+    pub ingest_allowed_ips: Option<String>,
+    /// Source IP allowlist of `/api/admin/*`.
+    pub admin_allowed_ips: Option<String>,
+    /// Proxies whose `X-Forwarded-For` entries are trusted for the allowlists.
+    pub trusted_proxies: Option<String>,
     pub default_project_id: String,
     pub langfuse_public_key: Option<String>,
     pub langfuse_secret_key: Option<String>,
+    /// Instance-wide bearer token that may only ingest.
+    pub ingest_token: Option<String>,
+    /// Instance-wide bearer token that may only query.
+    pub read_token: Option<String>,
     pub rate_limit_qps: u32,
     pub rate_limit_burst: u32,
-    /// When true, allows unauthenticated access to `GET /api/public/projects` and
-    /// `POST /api/public/otel/v1/traces` if Langfuse public/secret keys are not configured.
+    /// Per-key request rate of the write routes; 0 disables the limit.
+    pub ingest_rate_limit_qps: u32,
+    /// Per-key burst of the write routes; 0 means twice the rate.
+    pub ingest_rate_limit_burst: u32,
+    /// Per-key ingest budget in cost units per second; 0 disables cost limiting.
+    pub ingest_cost_per_sec: u32,
+    /// Per-key ingest burst credit in cost units.
+    pub ingest_cost_burst: u32,
+    /// Payload bytes that count as one cost unit.
+    pub ingest_cost_bytes_per_unit: u32,
+    /// When true, allows unauthenticated access to `GET /api/public/projects`,
+    /// `POST /api/public/otel/v1/traces` and `POST /api/public/ingestion` if Langfuse
+    /// public/secret keys are not configured.
     /// **Must stay false in production** (default).
     pub allow_unauthenticated_compat: bool,
     /// Maximum HTTP request body size in bytes (ingest endpoints).
     pub max_request_body_bytes: usize,
+    /// Webhook endpoint receiving outbox events. Outbox writes are skipped when unset.
+    pub webhook_url: Option<String>,
+    /// Undelivered outbox events older than this are moved to the dead-letter state.
+    pub webhook_max_age_secs: i64,
+    /// Mask likely credentials in ingested input/output and record findings.
+    pub secret_scanning: bool,
+    /// Model alias map (`alias=canonical`, comma separated; `prefix*` aliases allowed).
+    pub model_aliases: Option<String>,
+    /// Keys file with additional bearer tokens / Langfuse key pairs, reloaded at runtime.
+    pub keys_file: Option<String>,
+    /// How often the keys file and `api_keys` table are re-read (also on `SIGHUP`).
+    pub keys_reload_secs: u64,
+    /// Start in read-only mode (writes rejected until switched via `/api/admin/mode`).
+    pub read_only: bool,
+    /// `Retry-After` sent with `503` responses while writes are paused.
+    pub maintenance_retry_after_secs: u64,
+    /// Drop (instead of store) writes to traces that were explicitly completed.
+    pub strict_trace_completion: bool,
+    /// Default policy for observation ids that already exist (`latest`,
+    /// `overwrite`, `merge`, `ignore` or `error`).
+    pub conflict_policy: Option<String>,
+    /// Per-project conflict policies (`project=policy`, comma separated).
+    pub project_conflict_policies: Option<String>,
+    /// Share of traces stored, from 0 to 1.
+    pub sample_rate: f64,
+    /// Per-project sample rates (`project=rate`, comma separated).
+    pub project_sample_rates: Option<String>,
+    /// Store sampled-out traces that contain `ERROR` observations anyway.
+    pub sample_keep_errors: bool,
+    /// When set, v1 responses carry a `Deprecation` header with this date.
+    pub api_v1_deprecated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When set, v1 responses carry a `Sunset` header with this date.
+    pub api_v1_sunset_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Keep raw ingest payloads this long for `GET /api/admin/traces/{id}/raw`;
+    /// 0 disables the raw ingest history.
+    pub raw_ingest_ttl_secs: u64,
+    /// Stored bytes per raw payload; longer bodies are cut.
+    pub raw_ingest_max_body_bytes: usize,
+    /// Ingest payloads queued across all workers before `429` is returned.
+    pub ingest_queue_capacity: usize,
+    /// How long a write waits for space in a full queue before `429`; 0
+    /// rejects it at once.
+    pub ingest_enqueue_timeout_ms: u64,
+    /// `Retry-After` sent with `429` responses while a queue is full.
+    pub ingest_retry_after_secs: u64,
+    /// Parallel ingest workers, each writing through its own connection.
+    pub ingest_workers: usize,
+    /// Payloads an ingest worker writes in one transaction at most.
+    pub ingest_max_batches: usize,
+    /// How long an ingest worker waits for more payloads before writing.
+    pub ingest_batch_window_ms: u64,
+    /// Directory of the ingest write-ahead log; disabled when unset.
+    pub wal_dir: Option<String>,
+    /// How long ingest event ids are remembered to skip retried payloads;
+    /// 0 turns deduplication off.
+    pub ingest_dedup_ttl_secs: u64,
+    /// Kafka bootstrap servers to consume ingest payloads from (`kafka`
+    /// feature); disabled when unset.
+    pub kafka_brokers: Option<String>,
+    /// Topic of the Kafka ingest source.
+    pub kafka_topic: String,
+    /// Consumer group of the Kafka ingest source.
+    pub kafka_group_id: String,
+    /// Window of list queries without `fromTimestamp`; 0 leaves them unbounded.
+    pub default_query_window_secs: u64,
+    /// Longest time range of aggregate queries; 0 disables the limit.
+    pub max_query_range_secs: u64,
+    /// Most trace ids one `POST /traces/batch-get` request may ask for.
+    pub batch_get_max_traces: usize,
+    /// Age after which metric points are rolled up into 1-minute buckets;
+    /// 0 keeps raw points.
+    pub metrics_rollup_after_secs: u64,
+    /// Age after which 1-minute buckets are rolled up into 1-hour buckets;
+    /// 0 keeps minute buckets.
+    pub metrics_hourly_rollup_after_secs: u64,
+    /// How often metric rollups run.
+    pub metrics_rollup_interval_secs: u64,
+    /// Days after which traces, scores and metrics are deleted; 0 keeps them.
+    pub retention_days: u32,
+    /// Per-project retention in days (`project=days`, comma separated).
+    pub project_retention_days: Option<String>,
+    /// How often expired data is purged.
+    pub retention_interval_secs: u64,
+    /// Range of a trace and observation partition: `day`, `week` or `month`.
+    pub partition_interval: String,
+    /// SMTP server for alert emails (`smtp://`, `smtps://`, with optional
+    /// credentials); alert rules cannot email without it.
+    pub smtp_url: Option<String>,
+    /// Sender of alert emails.
+    pub smtp_from: String,
+    /// How often alert rules are evaluated; 0 disables evaluation.
+    pub alert_interval_secs: u64,
+    /// How often usage of projects with a quota is checked against their limits.
+    pub quota_interval_secs: u64,
+    /// Bucket that large observation inputs and outputs are offloaded to;
+    /// offloading is off without it.
+    pub offload_s3_bucket: Option<String>,
+    /// S3 endpoint; AWS S3 in `offload_s3_region` by default.
+    pub offload_s3_endpoint: Option<String>,
+    pub offload_s3_region: String,
+    pub offload_s3_access_key: Option<String>,
+    pub offload_s3_secret_key: Option<String>,
+    /// Prepended to the keys of offloaded objects.
+    pub offload_s3_prefix: String,
+    /// Size of the JSON of an input or output above which it is offloaded.
+    pub offload_threshold_bytes: usize,
+    /// Bytes of an offloaded payload kept in Postgres as its preview.
+    pub offload_preview_bytes: usize,
+}
+
+impl ServerConfig {
+    /// Configuration with the documented defaults (see the README).
+    pub fn new(database_url: impl Into<String>, api_bearer_token: impl Into<String>) -> Self {
+        Self {
+            database_url: database_url.into(),
+            db_max_connections: None,
+            db_min_connections: 0,
+            db_acquire_timeout_secs: 30,
+            api_bearer_token: api_bearer_token.into(),
+            bind_addr: "127.0.0.1:8742".to_string(),
+            otlp_grpc_addr: None,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            ingest_client_cert: None,
+            query_client_cert: None,
+            admin_client_cert: None,
+            query_allowed_ips: None,
+            ingest_allowed_ips: None,
+            admin_allowed_ips: None,
+            trusted_proxies: None,
+            default_project_id: "default".to_string(),
+            langfuse_public_key: None,
+            langfuse_secret_key: None,
+            ingest_token: None,
+            read_token: None,
+            rate_limit_qps: 20,
+            rate_limit_burst: 40,
+            ingest_rate_limit_qps: 0,
+            ingest_rate_limit_burst: 0,
+            ingest_cost_per_sec: 0,
+            ingest_cost_burst: 0,
+            ingest_cost_bytes_per_unit: 4096,
+            allow_unauthenticated_compat: false,
+            max_request_body_bytes: 20 * 1024 * 1024,
+            webhook_url: None,
+            webhook_max_age_secs: 24 * 3600,
+            secret_scanning: false,
+            model_aliases: None,
+            keys_file: None,
+            keys_reload_secs: 30,
+            read_only: false,
+            maintenance_retry_after_secs: 30,
+            strict_trace_completion: false,
+            conflict_policy: None,
+            project_conflict_policies: None,
+            sample_rate: 1.0,
+            project_sample_rates: None,
+            sample_keep_errors: true,
+            api_v1_deprecated_at: None,
+            api_v1_sunset_at: None,
+            raw_ingest_ttl_secs: 0,
+            raw_ingest_max_body_bytes: 1024 * 1024,
+            ingest_queue_capacity: 1000,
+            ingest_enqueue_timeout_ms: 0,
+            ingest_retry_after_secs: 1,
+            ingest_workers: 1,
+            ingest_max_batches: 200,
+            ingest_batch_window_ms: 50,
+            wal_dir: None,
+            ingest_dedup_ttl_secs: 600,
+            kafka_brokers: None,
+            kafka_topic: "xtrace-ingest".to_string(),
+            kafka_group_id: "xtrace".to_string(),
+            default_query_window_secs: 0,
+            max_query_range_secs: 0,
+            batch_get_max_traces: 100,
+            metrics_rollup_after_secs: 0,
+            metrics_hourly_rollup_after_secs: 0,
+            metrics_rollup_interval_secs: 60,
+            retention_days: 0,
+            project_retention_days: None,
+            retention_interval_secs: 3600,
+            partition_interval: "week".to_string(),
+            smtp_url: None,
+            smtp_from: "xtrace@localhost".to_string(),
+            alert_interval_secs: 60,
+            quota_interval_secs: 60,
+            offload_s3_bucket: None,
+            offload_s3_endpoint: None,
+            offload_s3_region: "us-east-1".to_string(),
+            offload_s3_access_key: None,
+            offload_s3_secret_key: None,
+            offload_s3_prefix: String::new(),
+            offload_threshold_bytes: 256 * 1024,
+            offload_preview_bytes: 1024,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
-    pub api_bearer_token: Arc<str>,
-    pub langfuse_public_key: Option<Arc<str>>,
-    pub langfuse_secret_key: Option<Arc<str>>,
+    pub keys: Arc<KeyStore>,
     pub default_project_id: Arc<str>,
-    pub ingest_tx: mpsc::Sender<BatchIngestRequest>,
+    pub ingest_queue: IngestQueue,
     pub metrics_tx: mpsc::Sender<MetricsBatchRequest>,
     pub query_limiter: Arc<KeyedRateLimiter>,
     pub rate_limit_stats: Arc<RateLimitStats>,
     pub rate_limit_qps: u32,
     pub rate_limit_burst: u32,
+    /// Set when write routes are rate limited (`XTRACE_INGEST_RATE_LIMIT_QPS`).
+    pub(crate) ingest_rate_limiter: Option<Arc<KeyedRateLimiter>>,
+    pub ingest_limiter: Option<Arc<IngestCostLimiter>>,
+    pub conflict_policies: Arc<ConflictPolicies>,
     pub allow_unauthenticated_compat: bool,
+    pub api_versions: Arc<ApiVersionPolicy>,
+    pub write_gate: Arc<WriteGate>,
+    /// Waiting for and rejecting writes while a queue is full.
+    pub(crate) backpressure: Backpressure,
+    /// Set when raw ingest history is enabled (`XTRACE_RAW_INGEST_TTL_SECS`).
+    pub(crate) raw_ingest: Option<Arc<RawIngestRecorder>>,
+    /// Model prices, shared with the ingest worker.
+    pub(crate) prices: Arc<PriceRegistry>,
+    /// Set when the ingest write-ahead log is enabled (`XTRACE_WAL_DIR`).
+    pub(crate) wal: Option<Arc<Wal>>,
+    /// How long ingest event ids are remembered; `None` when deduplication
+    /// is off (`XTRACE_INGEST_DEDUP_TTL_SECS=0`).
+    pub(crate) dedup_ttl: Option<std::time::Duration>,
+    /// Default window and maximum range of queries.
+    pub(crate) query_windows: QueryWindows,
+    /// Most trace ids per `POST /traces/batch-get` request.
+    pub(crate) batch_get_max_traces: usize,
+    /// Counters served by `GET /metrics`.
+    pub(crate) telemetry: Arc<Telemetry>,
+    /// Redacted startup configuration served by `GET /api/admin/config`.
+    pub(crate) effective_config: Arc<serde_json::Value>,
+    /// Retention applied by `purge_expired` jobs.
+    pub(crate) retention: Arc<RetentionPolicy>,
+    /// Sends alert emails; set with `XTRACE_SMTP_URL`.
+    pub(crate) mailer: Option<Arc<Mailer>>,
+    /// Projects over their monthly quota, refreshed by the quota worker.
+    pub(crate) quotas: Arc<QuotaStore>,
+    /// Set when large payloads are offloaded (`XTRACE_OFFLOAD_S3_BUCKET`).
+    pub(crate) offloader: Option<Arc<Offloader>>,
 }
 
 impl AppState {
@@ -89,4 +530,166 @@ impl AppState {
             .allow_burst(NonZeroU32::new(burst).expect("rate_limit_burst must be > 0"));
         Arc::new(KeyedRateLimiter::keyed(quota))
     }
+
+    /// Deletes offloaded payloads of deleted observations in the background.
+    pub(crate) fn delete_offloaded(&self, keys: Vec<String>) {
+        if let Some(offloader) = &self.offloader {
+            offloader.delete(keys);
+        }
+    }
+
+    pub(crate) fn writes_paused(&self, mode: WriteMode) -> ApiError {
+        ApiError::WritesPaused {
+            mode: mode.as_str(),
+            retry_after_secs: self.write_gate.retry_after_secs,
+        }
+    }
+
+    /// The `429` of a write that found its queue full.
+    fn queue_full(&self, queue_depth: usize, queue_capacity: usize) -> ApiError {
+        ApiError::QueueFull(QueueFull {
+            queue_depth,
+            queue_capacity,
+            retry_after_secs: self.backpressure.retry_after_secs,
+        })
+    }
+
+    /// Charges an ingest request against its key's cost budget, if enabled.
+    pub(crate) fn charge_ingest(
+        &self,
+        headers: &axum::http::HeaderMap,
+        cost: IngestCost,
+    ) -> Result<(), ApiError> {
+        match &self.ingest_limiter {
+            Some(limiter) => limiter.charge(headers, cost),
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects ingest payloads of projects over their monthly quota.
+    pub(crate) fn check_quota(&self, payloads: &[BatchIngestRequest]) -> Result<(), ApiError> {
+        self.quotas.check(
+            payloads
+                .iter()
+                .map(|p| p.project_id().unwrap_or(&self.default_project_id)),
+        )
+    }
+
+    /// Queues ingest payloads for the ingest worker, honoring the write mode.
+    /// With the write-ahead log, payloads are logged before they are queued.
+    /// While a queue is full, waits up to the enqueue timeout for space.
+    ///
+    /// Queue space is reserved for every payload before any is logged or
+    /// queued, so the payloads are accepted or rejected together and a
+    /// retried request does not write the first ones twice. Each worker's
+    /// share is reserved at once and the queues in worker order, so
+    /// concurrent requests never hold part of the space another waits for.
+    pub(crate) async fn enqueue_ingest(
+        &self,
+        mut payloads: Vec<BatchIngestRequest>,
+    ) -> Result<(), ApiError> {
+        let shards: Vec<usize> = payloads
+            .iter()
+            .map(|p| self.ingest_queue.shard(p))
+            .collect();
+        let mut per_shard = BTreeMap::<usize, usize>::new();
+        for &shard in &shards {
+            *per_shard.entry(shard).or_default() += 1;
+        }
+        let shard_capacity = self.ingest_queue.shard_capacity();
+        if let Some(most) = per_shard
+            .values()
+            .copied()
+            .filter(|&c| c > shard_capacity)
+            .max()
+        {
+            return Err(ApiError::BadRequest(format!(
+                "request needs {most} slots in one ingest queue, which holds {shard_capacity}; \
+                 split it into smaller requests"
+            )));
+        }
+
+        let n = payloads.len() as u64;
+        self.write_gate
+            .try_reserve(n)
+            .map_err(|mode| self.writes_paused(mode))?;
+        let deadline = Instant::now() + self.backpressure.enqueue_timeout;
+        let mut permits = BTreeMap::new();
+        for (&shard, &count) in &per_shard {
+            let tx = self.ingest_queue.shard_sender(shard);
+            match reserve_many_until(tx, count, deadline).await {
+                Ok(reserved) => {
+                    permits.insert(shard, reserved);
+                }
+                Err(unsent) => {
+                    self.write_gate.release(n);
+                    return Err(match unsent {
+                        Unsent::Full => self.queue_full(
+                            self.ingest_queue.depths().into_iter().sum(),
+                            self.ingest_queue.capacity(),
+                        ),
+                        Unsent::Closed => ApiError::ServiceUnavailable,
+                    });
+                }
+            }
+        }
+        if let Some(wal) = &self.wal {
+            if let Err(err) = wal.append_all(&mut payloads).await {
+                tracing::error!(error = %err, "write-ahead log: append failed");
+                self.write_gate.release(n);
+                return Err(ApiError::ServiceUnavailable);
+            }
+        }
+        let accepted_at = std::time::Instant::now();
+        self.telemetry.record_accepted(accepted_at, n);
+        for (shard, mut payload) in shards.into_iter().zip(payloads) {
+            payload.accepted_at = Some(accepted_at);
+            if let Some(permit) = permits.get_mut(&shard).and_then(Iterator::next) {
+                permit.send(payload);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`AppState::enqueue_ingest`], and keeps `source` in the raw ingest
+    /// history once the payloads are accepted.
+    pub(crate) async fn enqueue_ingest_from(
+        &self,
+        source: RawSource<'_>,
+        payloads: Vec<BatchIngestRequest>,
+    ) -> Result<(), ApiError> {
+        let raw = self
+            .raw_ingest
+            .as_ref()
+            .and_then(|recorder| recorder.capture(&source, &payloads));
+        self.enqueue_ingest(payloads).await?;
+        if let (Some(recorder), Some(raw)) = (&self.raw_ingest, raw) {
+            recorder.send(raw);
+        }
+        Ok(())
+    }
+
+    /// Queues a metrics payload for the metrics worker, honoring the write
+    /// mode and waiting like [`AppState::enqueue_ingest`] while it is full.
+    pub(crate) async fn enqueue_metrics(
+        &self,
+        payload: MetricsBatchRequest,
+    ) -> Result<(), ApiError> {
+        self.write_gate
+            .try_reserve(1)
+            .map_err(|mode| self.writes_paused(mode))?;
+        let deadline = Instant::now() + self.backpressure.enqueue_timeout;
+        send_until(&self.metrics_tx, payload, deadline)
+            .await
+            .map_err(|unsent| {
+                self.write_gate.release(1);
+                match unsent {
+                    Unsent::Full => {
+                        let capacity = self.metrics_tx.max_capacity();
+                        self.queue_full(capacity - self.metrics_tx.capacity(), capacity)
+                    }
+                    Unsent::Closed => ApiError::ServiceUnavailable,
+                }
+            })
+    }
 }
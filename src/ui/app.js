@@ -0,0 +1,488 @@
+// The embedded xtrace UI: traces, trace trees and daily metrics, read from
+// the public query API with the key entered at sign-in.
+"use strict";
+
+const KEY_STORAGE = "xtrace.apiKey";
+const API = "../api/public";
+const PAGE_SIZE = 50;
+
+const view = document.getElementById("view");
+const signOut = document.getElementById("sign-out");
+
+// ---- helpers ---------------------------------------------------------------
+
+/** Creates an element; strings among `children` become text nodes. */
+function el(tag, attrs, ...children) {
+  const node = document.createElement(tag);
+  for (const [name, value] of Object.entries(attrs || {})) {
+    if (value === null || value === undefined || value === false) continue;
+    if (name.startsWith("on")) node.addEventListener(name.slice(2), value);
+    else if (name === "className") node.className = value;
+    // Set through the CSSOM: the content security policy forbids inline styles.
+    else if (name === "style") Object.assign(node.style, value);
+    else node.setAttribute(name, value === true ? "" : value);
+  }
+  for (const child of children.flat()) {
+    if (child === null || child === undefined || child === false) continue;
+    node.append(child instanceof Node ? child : String(child));
+  }
+  return node;
+}
+
+function svg(tag, attrs, ...children) {
+  const node = document.createElementNS("http://www.w3.org/2000/svg", tag);
+  for (const [name, value] of Object.entries(attrs || {})) node.setAttribute(name, value);
+  node.append(...children);
+  return node;
+}
+
+const numberFormat = new Intl.NumberFormat();
+
+function formatNumber(n) {
+  return n === null || n === undefined ? "–" : numberFormat.format(n);
+}
+
+function formatCost(n) {
+  if (n === null || n === undefined) return "–";
+  if (n === 0) return "$0";
+  return n < 0.01 ? "$" + n.toPrecision(2) : "$" + n.toFixed(2);
+}
+
+function formatSeconds(s) {
+  if (s === null || s === undefined) return "–";
+  return s < 1 ? Math.round(s * 1000) + " ms" : s.toFixed(2) + " s";
+}
+
+function formatTime(ts) {
+  return ts ? new Date(ts).toLocaleString() : "–";
+}
+
+function formatJson(value) {
+  if (value === null || value === undefined) return "–";
+  return typeof value === "string" ? value : JSON.stringify(value, null, 2);
+}
+
+function tokens(o) {
+  if (o.totalTokens === null || o.totalTokens === undefined) return "–";
+  if (o.promptTokens === null || o.promptTokens === undefined) return formatNumber(o.totalTokens);
+  return `${formatNumber(o.promptTokens)} → ${formatNumber(o.completionTokens)} (${formatNumber(o.totalTokens)})`;
+}
+
+function message(text, className) {
+  view.replaceChildren(el("p", { className: className || "muted" }, text));
+}
+
+// ---- API -------------------------------------------------------------------
+
+class SignedOut extends Error {}
+
+function authorization(key) {
+  return key.includes(":") ? "Basic " + btoa(key) : "Bearer " + key;
+}
+
+async function api(path, params, key) {
+  key = key || localStorage.getItem(KEY_STORAGE);
+  if (!key) throw new SignedOut();
+  const query = new URLSearchParams();
+  for (const [name, value] of Object.entries(params || {})) {
+    if (value !== null && value !== undefined && value !== "") query.set(name, value);
+  }
+  const url = API + path + ([...query].length ? "?" + query : "");
+  const res = await fetch(url, { headers: { Authorization: authorization(key) } });
+  if (res.status === 401) throw new SignedOut();
+  const body = await res.json().catch(() => null);
+  if (!res.ok) throw new Error((body && body.message) || `${res.status} ${res.statusText}`);
+  return body;
+}
+
+// ---- sign-in -----------------------------------------------------------------
+
+function showSignIn(error) {
+  localStorage.removeItem(KEY_STORAGE);
+  signOut.hidden = true;
+  const form = document.getElementById("sign-in").content.firstElementChild.cloneNode(true);
+  const errorText = form.querySelector(".error");
+  if (error) {
+    errorText.textContent = error;
+    errorText.hidden = false;
+  }
+  form.addEventListener("submit", async (event) => {
+    event.preventDefault();
+    const key = form.elements.key.value.trim();
+    try {
+      await api("/traces", { limit: 1, fields: "metrics" }, key);
+      localStorage.setItem(KEY_STORAGE, key);
+      route();
+    } catch (err) {
+      errorText.textContent = err instanceof SignedOut ? "The key was not accepted." : err.message;
+      errorText.hidden = false;
+    }
+  });
+  view.replaceChildren(form);
+  form.elements.key.focus();
+}
+
+signOut.addEventListener("click", () => showSignIn());
+
+// ---- traces ----------------------------------------------------------------
+
+async function showTraces(params) {
+  const page = Math.max(1, parseInt(params.get("page"), 10) || 1);
+  const q = params.get("q") || "";
+  const search = el(
+    "form",
+    { className: "toolbar" },
+    el("input", { name: "q", type: "search", placeholder: "Search names, input, output, metadata", value: q }),
+    el("button", { type: "submit" }, "Search"),
+  );
+  search.addEventListener("submit", (event) => {
+    event.preventDefault();
+    const next = new URLSearchParams();
+    if (search.elements.q.value) next.set("q", search.elements.q.value);
+    location.hash = "#/?" + next;
+  });
+  message("Loading traces…");
+
+  const res = await api("/traces", { page, limit: PAGE_SIZE, q, fields: "metrics" });
+  const rows = res.data.map((t) =>
+    el(
+      "tr",
+      { className: "link", onclick: () => (location.hash = "#/traces/" + t.id) },
+      el("td", {}, formatTime(t.timestamp)),
+      el(
+        "td",
+        {},
+        el("a", { href: "#/traces/" + t.id }, t.name || t.id),
+        t.errorCount > 0 ? el("span", { className: "badge error" }, `${t.errorCount} error${t.errorCount > 1 ? "s" : ""}`) : null,
+      ),
+      el("td", {}, t.userId || "–"),
+      el("td", { className: "num" }, formatNumber(t.observationCount)),
+      el("td", { className: "num" }, formatNumber(t.totalTokens)),
+      el("td", { className: "num" }, formatCost(t.totalCost)),
+      el("td", { className: "num" }, formatSeconds(t.latency)),
+    ),
+  );
+  const pages = Math.max(1, res.meta.totalPages || 1);
+  const goTo = (p) => {
+    const next = new URLSearchParams(params);
+    next.set("page", p);
+    return "#/?" + next;
+  };
+  view.replaceChildren(
+    search,
+    rows.length
+      ? el(
+          "table",
+          {},
+          el(
+            "thead",
+            {},
+            el(
+              "tr",
+              {},
+              el("th", {}, "Time"),
+              el("th", {}, "Name"),
+              el("th", {}, "User"),
+              el("th", { className: "num" }, "Observations"),
+              el("th", { className: "num" }, "Tokens"),
+              el("th", { className: "num" }, "Cost"),
+              el("th", { className: "num" }, "Latency"),
+            ),
+          ),
+          el("tbody", {}, rows),
+        )
+      : el("p", { className: "muted" }, "No traces found."),
+    el(
+      "div",
+      { className: "pager" },
+      page > 1 ? el("a", { href: goTo(page - 1) }, "← Newer") : el("span"),
+      el("span", { className: "muted" }, `Page ${page} of ${pages} · ${formatNumber(res.meta.totalItems)} traces`),
+      page < pages ? el("a", { href: goTo(page + 1) }, "Older →") : el("span"),
+    ),
+  );
+}
+
+// ---- trace detail ------------------------------------------------------------
+
+function details(title, fields) {
+  return el(
+    "section",
+    { className: "details" },
+    el("h2", {}, title),
+    el(
+      "dl",
+      {},
+      fields
+        .filter(([, value]) => value !== null && value !== undefined && value !== "")
+        .map(([name, value]) => [el("dt", {}, name), el("dd", {}, value)]),
+    ),
+  );
+}
+
+function payloads(item) {
+  return ["input", "output", "metadata"]
+    .filter((field) => item[field] !== null && item[field] !== undefined)
+    .map((field) => el("section", {}, el("h3", {}, field), el("pre", {}, formatJson(item[field]))));
+}
+
+async function showTrace(traceId) {
+  message("Loading trace…");
+  const [trace, tree] = await Promise.all([
+    api("/traces/" + encodeURIComponent(traceId)),
+    api("/traces/" + encodeURIComponent(traceId) + "/tree"),
+  ]);
+
+  // The waterfall spans from the earliest start to the latest end.
+  const nodes = [];
+  const collect = (node, depth) => {
+    nodes.push([node, depth]);
+    node.children.forEach((child) => collect(child, depth + 1));
+  };
+  tree.roots.forEach((root) => collect(root, 0));
+  const starts = nodes.map(([n]) => Date.parse(n.startTime)).filter((t) => !isNaN(t));
+  const begin = starts.length ? Math.min(...starts) : 0;
+  const end = Math.max(begin + 1, ...nodes.map(([n]) => Date.parse(n.startTime) + (n.duration || 0) * 1000).filter((t) => !isNaN(t)));
+
+  const panel = el("aside", { className: "panel" });
+  const select = (row, title, item, fields) => {
+    view.querySelectorAll("tr.selected").forEach((r) => r.classList.remove("selected"));
+    if (row) row.classList.add("selected");
+    panel.replaceChildren(details(title, fields), ...payloads(item));
+  };
+  const traceFields = [
+    ["ID", trace.id],
+    ["Time", formatTime(trace.timestamp)],
+    ["User", trace.userId],
+    ["Session", trace.sessionId],
+    ["Release", trace.release],
+    ["Version", trace.version],
+    ["Environment", trace.environment],
+    ["Tags", (trace.tags || []).join(", ")],
+    ["Latency", formatSeconds(trace.latency)],
+    ["Tokens", formatNumber(nodes.reduce((sum, [n]) => sum + (n.totalTokens || 0), 0))],
+    ["Cost", formatCost(tree.totalCost)],
+  ];
+
+  const rows = nodes.map(([node, depth]) => {
+    const start = Date.parse(node.startTime);
+    const offset = isNaN(start) ? 0 : ((start - begin) / (end - begin)) * 100;
+    const width = Math.max(0.5, (((node.duration || 0) * 1000) / (end - begin)) * 100);
+    const row = el(
+      "tr",
+      { className: "link" + (node.level === "ERROR" ? " failed" : "") },
+      el(
+        "td",
+        { style: { paddingLeft: `${0.5 + depth * 1.25}rem` } },
+        el("span", { className: "badge type-" + String(node.type).toLowerCase() }, node.type),
+        " ",
+        node.name || node.id,
+        node.model ? el("span", { className: "muted" }, " · " + node.model) : null,
+      ),
+      el("td", { className: "num" }, tokens(node)),
+      el("td", { className: "num" }, formatCost(node.cost), node.children.length ? el("div", { className: "muted" }, "Σ " + formatCost(node.subtreeCost)) : null),
+      el("td", { className: "num" }, formatSeconds(node.duration)),
+      el("td", { className: "timeline" }, el("div", { className: "bar", style: { marginLeft: `${offset}%`, width: `${Math.min(width, 100 - offset)}%` } })),
+    );
+    row.addEventListener("click", () =>
+      select(row, node.name || node.type, node, [
+        ["ID", node.id],
+        ["Type", node.type],
+        ["Model", node.model],
+        ["Level", node.level],
+        ["Status", node.statusMessage],
+        ["Start", formatTime(node.startTime)],
+        ["Duration", formatSeconds(node.duration)],
+        ["Time to first token", formatSeconds(node.timeToFirstToken)],
+        ["Tokens", tokens(node)],
+        ["Cost", formatCost(node.calculatedTotalCost)],
+        ["Subtree cost", formatCost(node.subtreeCost)],
+      ]),
+    );
+    return row;
+  });
+
+  view.replaceChildren(
+    el(
+      "div",
+      { className: "title" },
+      el("a", { href: "#/" }, "← Traces"),
+      el("h1", { className: "link", onclick: () => select(null, "Trace", trace, traceFields) }, trace.name || trace.id),
+    ),
+    el(
+      "div",
+      { className: "split" },
+      el(
+        "div",
+        { className: "tree" },
+        rows.length
+          ? el(
+              "table",
+              {},
+              el(
+                "thead",
+                {},
+                el(
+                  "tr",
+                  {},
+                  el("th", {}, `Observations (${tree.observationCount})`),
+                  el("th", { className: "num" }, "Tokens"),
+                  el("th", { className: "num" }, "Cost"),
+                  el("th", { className: "num" }, "Duration"),
+                  el("th", { className: "timeline" }, "Timeline"),
+                ),
+              ),
+              el("tbody", {}, rows),
+            )
+          : el("p", { className: "muted" }, "This trace has no observations."),
+      ),
+      panel,
+    ),
+  );
+  select(null, "Trace", trace, traceFields);
+}
+
+// ---- metrics -----------------------------------------------------------------
+
+function barChart(title, days, value, format) {
+  const width = 720;
+  const height = 160;
+  const peak = Math.max(...days.map(value), 0);
+  const max = peak || 1;
+  const step = width / days.length;
+  const bars = days.map((day, i) => {
+    const h = (value(day) / max) * (height - 4);
+    return svg(
+      "rect",
+      { x: i * step + 1, y: height - h, width: Math.max(1, step - 2), height: h, class: "bar" },
+      svg("title", {}, `${day.date}: ${format(value(day))}`),
+    );
+  });
+  const total = days.reduce((sum, day) => sum + value(day), 0);
+  return el(
+    "section",
+    { className: "chart" },
+    el("h2", {}, title, el("span", { className: "muted" }, " · " + format(total))),
+    svg(
+      "svg",
+      { viewBox: `0 0 ${width} ${height}`, preserveAspectRatio: "none", role: "img" },
+      ...bars,
+    ),
+    el(
+      "div",
+      { className: "axis-labels muted" },
+      el("span", {}, days[0].date),
+      el("span", {}, "peak " + format(peak)),
+      el("span", {}, days[days.length - 1].date),
+    ),
+  );
+}
+
+async function showMetrics(params) {
+  const range = [7, 30, 90].includes(Number(params.get("days"))) ? Number(params.get("days")) : 30;
+  message("Loading metrics…");
+  const to = new Date();
+  const from = new Date(to.getTime() - (range - 1) * 86400000);
+  from.setHours(0, 0, 0, 0);
+  const timezone = Intl.DateTimeFormat().resolvedOptions().timeZone || "UTC";
+  const res = await api("/metrics/daily", {
+    fromTimestamp: from.toISOString(),
+    toTimestamp: to.toISOString(),
+    timezone,
+    limit: 200,
+  });
+
+  // Days without traces are not returned; chart them as zero.
+  const byDate = new Map(res.data.map((day) => [day.date, day]));
+  const days = [];
+  for (let d = new Date(from); d <= to; d.setDate(d.getDate() + 1)) {
+    const date = [d.getFullYear(), String(d.getMonth() + 1).padStart(2, "0"), String(d.getDate()).padStart(2, "0")].join("-");
+    days.push(byDate.get(date) || { date, countTraces: 0, countObservations: 0, totalCost: 0, usage: [] });
+  }
+  const dayTokens = (day) => day.usage.reduce((sum, u) => sum + (u.totalUsage || 0), 0);
+
+  const models = new Map();
+  for (const day of days) {
+    for (const u of day.usage) {
+      const m = models.get(u.model) || { model: u.model, provider: u.provider, observations: 0, input: 0, output: 0, total: 0, cost: 0 };
+      m.observations += u.countObservations || 0;
+      m.input += u.inputUsage || 0;
+      m.output += u.outputUsage || 0;
+      m.total += u.totalUsage || 0;
+      m.cost += u.totalCost || 0;
+      models.set(u.model, m);
+    }
+  }
+  const modelRows = [...models.values()]
+    .sort((a, b) => b.cost - a.cost || b.total - a.total)
+    .map((m) =>
+      el(
+        "tr",
+        {},
+        el("td", {}, m.model || "–", m.provider ? el("span", { className: "muted" }, " · " + m.provider) : null),
+        el("td", { className: "num" }, formatNumber(m.observations)),
+        el("td", { className: "num" }, formatNumber(m.input)),
+        el("td", { className: "num" }, formatNumber(m.output)),
+        el("td", { className: "num" }, formatNumber(m.total)),
+        el("td", { className: "num" }, formatCost(m.cost)),
+      ),
+    );
+
+  view.replaceChildren(
+    el(
+      "div",
+      { className: "toolbar" },
+      el("h1", {}, "Daily metrics"),
+      el("span", { className: "muted" }, timezone),
+      ...[7, 30, 90].map((n) => el("a", { href: "#/metrics?days=" + n, className: n === range ? "active" : null }, `${n} days`)),
+    ),
+    barChart("Traces", days, (day) => day.countTraces, formatNumber),
+    barChart("Cost", days, (day) => day.totalCost, formatCost),
+    barChart("Tokens", days, dayTokens, formatNumber),
+    el("h2", {}, "Models"),
+    modelRows.length
+      ? el(
+          "table",
+          {},
+          el(
+            "thead",
+            {},
+            el(
+              "tr",
+              {},
+              el("th", {}, "Model"),
+              el("th", { className: "num" }, "Observations"),
+              el("th", { className: "num" }, "Input tokens"),
+              el("th", { className: "num" }, "Output tokens"),
+              el("th", { className: "num" }, "Total tokens"),
+              el("th", { className: "num" }, "Cost"),
+            ),
+          ),
+          el("tbody", {}, modelRows),
+        )
+      : el("p", { className: "muted" }, "No model usage in this range."),
+  );
+}
+
+// ---- routing -----------------------------------------------------------------
+
+async function route() {
+  const [path, query] = location.hash.replace(/^#/, "").split("?");
+  const params = new URLSearchParams(query || "");
+  if (!localStorage.getItem(KEY_STORAGE)) return showSignIn();
+  signOut.hidden = false;
+  const trace = /^\/traces\/([^/]+)$/.exec(path || "");
+  document.querySelectorAll("[data-nav]").forEach((a) => {
+    a.classList.toggle("active", a.dataset.nav === (path === "/metrics" ? "metrics" : "traces"));
+  });
+  try {
+    if (trace) await showTrace(decodeURIComponent(trace[1]));
+    else if (path === "/metrics") await showMetrics(params);
+    else await showTraces(params);
+  } catch (err) {
+    if (err instanceof SignedOut) showSignIn("Your key is no longer accepted.");
+    else message(err.message, "error");
+  }
+}
+
+window.addEventListener("hashchange", route);
+route();
@@ -0,0 +1,66 @@
+//! The embedded web UI (`ui` feature): a single page served under `/ui` that
+//! lists traces, renders the observation tree of a trace with tokens and
+//! cost, and charts daily metrics.
+//!
+//! The page is static and needs no credentials to load; it asks for an API
+//! key, keeps it in the browser's local storage and calls the public query
+//! API with it, so it sees exactly what the key may read. The assets are
+//! compiled into the binary and have no external dependencies.
+
+use axum::{
+    http::{header, HeaderName, HeaderValue},
+    response::{IntoResponse, Redirect},
+    routing::get,
+    Router,
+};
+
+use crate::state::AppState;
+
+const INDEX_HTML: &str = include_str!("index.html");
+const APP_JS: &str = include_str!("app.js");
+const APP_CSS: &str = include_str!("app.css");
+
+/// Everything is served from the binary, and the page only talks to this
+/// server.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; img-src 'self' data:; \
+     frame-ancestors 'none'; base-uri 'none'; form-action 'self'";
+
+fn asset(content_type: &'static str, body: &'static str) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            // Assets change with the binary, which has no version in their URLs.
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                HeaderValue::from_static(CONTENT_SECURITY_POLICY),
+            ),
+            (
+                HeaderName::from_static("x-content-type-options"),
+                HeaderValue::from_static("nosniff"),
+            ),
+        ],
+        body,
+    )
+}
+
+async fn index() -> impl IntoResponse {
+    asset("text/html; charset=utf-8", INDEX_HTML)
+}
+
+async fn app_js() -> impl IntoResponse {
+    asset("text/javascript; charset=utf-8", APP_JS)
+}
+
+async fn app_css() -> impl IntoResponse {
+    asset("text/css; charset=utf-8", APP_CSS)
+}
+
+/// `/ui/` and its assets; pages are addressed by the URL fragment.
+pub(crate) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/ui", get(|| async { Redirect::permanent("ui/") }))
+        .route("/ui/", get(index))
+        .route("/ui/app.js", get(app_js))
+        .route("/ui/app.css", get(app_css))
+}
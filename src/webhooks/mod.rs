@@ -0,0 +1,2 @@
+pub(crate) mod outbox;
+pub(crate) mod rules;
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+/// Outbox delivery settings shared by the ingest path and the delivery worker.
+#[derive(Clone)]
+pub(crate) struct OutboxConfig {
+    pub webhook_url: Option<Arc<str>>,
+    pub max_age: chrono::Duration,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct OutboxRow {
+    id: i64,
+    event_type: String,
+    target_url: String,
+    payload: JsonValue,
+    attempts: i32,
+    created_at: DateTime<Utc>,
+}
+
+/// Records an event in the outbox using the caller's transaction, so the event is
+/// committed (or rolled back) together with the ingested data that produced it.
+pub(crate) async fn enqueue(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    target_url: &str,
+    project_id: &str,
+    event_type: &str,
+    payload: JsonValue,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+INSERT INTO event_outbox (project_id, event_type, target_url, payload)
+VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(project_id)
+    .bind(event_type)
+    .bind(target_url)
+    .bind(payload)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+fn backoff(attempts: i32) -> chrono::Duration {
+    let secs = 2i64.saturating_pow(attempts.clamp(0, 12) as u32).min(3600);
+    chrono::Duration::seconds(secs)
+}
+
+/// Drains the outbox with at-least-once semantics: rows are claimed with
+/// `FOR UPDATE SKIP LOCKED`, retried with exponential backoff, and moved to
+/// `dead` once they exceed `max_age`.
+pub(crate) async fn outbox_worker(pool: PgPool, config: OutboxConfig) {
+    const BATCH_SIZE: i64 = 50;
+    let idle = Duration::from_secs(1);
+
+    let http = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(c) => c,
+        Err(err) => {
+            tracing::error!(error = %err, "outbox: failed to build http client");
+            return;
+        }
+    };
+
+    loop {
+        match deliver_pending(&pool, &http, &config, BATCH_SIZE).await {
+            Ok(n) if n > 0 => continue,
+            Ok(_) => {}
+            Err(err) => tracing::error!(error = ?err, "outbox: delivery pass failed"),
+        }
+        tokio::time::sleep(idle).await;
+    }
+}
+
+async fn deliver_pending(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    config: &OutboxConfig,
+    batch_size: i64,
+) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let rows: Vec<OutboxRow> = sqlx::query_as(
+        r#"
+SELECT id, event_type, target_url, payload, attempts, created_at
+FROM event_outbox
+WHERE status = 'pending' AND next_attempt_at <= NOW()
+ORDER BY id
+LIMIT $1
+FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .bind(batch_size)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let n = rows.len();
+    for row in rows {
+        let body = serde_json::json!({
+            "id": row.id,
+            "type": row.event_type,
+            "createdAt": row.created_at.to_rfc3339(),
+            "data": row.payload,
+        });
+
+        let result = http
+            .post(&row.target_url)
+            .header("X-Xtrace-Event", row.event_type.as_str())
+            .header("X-Xtrace-Delivery", row.id.to_string())
+            .json(&body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+
+        match result {
+            Ok(_) => {
+                sqlx::query(
+                    "UPDATE event_outbox SET status = 'delivered', attempts = attempts + 1, delivered_at = NOW(), last_error = NULL WHERE id = $1",
+                )
+                .bind(row.id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            Err(err) => {
+                let attempts = row.attempts + 1;
+                let expired = Utc::now() - row.created_at > config.max_age;
+                let status = if expired { "dead" } else { "pending" };
+                if expired {
+                    tracing::warn!(id = row.id, attempts, error = %err, "outbox: event moved to dead-letter");
+                }
+                sqlx::query(
+                    "UPDATE event_outbox SET status = $2, attempts = $3, next_attempt_at = $4, last_error = $5 WHERE id = $1",
+                )
+                .bind(row.id)
+                .bind(status)
+                .bind(attempts)
+                .bind(Utc::now() + backoff(attempts))
+                .bind(err.to_string())
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(n)
+}
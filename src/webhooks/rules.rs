@@ -0,0 +1,337 @@
+//! Webhook rules: per-project conditions on ingested traces, managed through
+//! `/api/admin/webhooks`. A trace matches a rule once it meets every
+//! condition set: an observation at or above `minLevel` (e.g. any `ERROR`),
+//! a total cost of at least `minCost`, a latency of at least `minLatency`
+//! seconds, and the `environment`.
+//!
+//! Rules are evaluated in the ingest transaction after the trace rollups, so
+//! a trace that crosses a threshold with a later batch matches then. Each
+//! rule fires once per trace: the match is recorded in
+//! `webhook_rule_matches` and a `rule.matched` event for the rule's `url` is
+//! added to the [outbox](super::outbox), which delivers it with retries.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::outbox;
+use crate::{
+    http::{error::ApiError, projects, traces::LEVELS},
+    state::AppState,
+};
+
+const RULE_COLUMNS: &str = "id, project_id, name, url, min_level, min_cost, min_latency, \
+  environment, enabled, created_at, updated_at";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebhookRuleRequest {
+    name: String,
+    /// Receives the `rule.matched` events.
+    url: String,
+    /// The default project when unset.
+    #[serde(default)]
+    project_id: Option<String>,
+    #[serde(default)]
+    min_level: Option<String>,
+    #[serde(default)]
+    min_cost: Option<f64>,
+    /// Seconds.
+    #[serde(default)]
+    min_latency: Option<f64>,
+    #[serde(default)]
+    environment: Option<String>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookRuleRequest {
+    fn validate(&mut self) -> Result<(), ApiError> {
+        if self.name.trim().is_empty() {
+            return Err(ApiError::BadRequest("name must not be empty".to_string()));
+        }
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => {
+                return Err(ApiError::BadRequest(
+                    "url must be an http(s) URL".to_string(),
+                ))
+            }
+        }
+        if self
+            .project_id
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest(
+                "projectId must not be empty".to_string(),
+            ));
+        }
+        if let Some(level) = &mut self.min_level {
+            *level = level.to_ascii_uppercase();
+            if !LEVELS.contains(&level.as_str()) {
+                return Err(ApiError::BadRequest(format!(
+                    "invalid minLevel: {level} (expected DEBUG, DEFAULT, WARNING or ERROR)"
+                )));
+            }
+        }
+        for (name, value) in [("minCost", self.min_cost), ("minLatency", self.min_latency)] {
+            if value.is_some_and(|v| !v.is_finite() || v < 0.0) {
+                return Err(ApiError::BadRequest(format!(
+                    "{name} must be a non-negative number"
+                )));
+            }
+        }
+        if self.min_level.is_none() && self.min_cost.is_none() && self.min_latency.is_none() {
+            return Err(ApiError::BadRequest(
+                "one of minLevel, minCost or minLatency is required".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn project_id(&self, state: &AppState) -> String {
+        self.project_id
+            .clone()
+            .unwrap_or_else(|| state.default_project_id.to_string())
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct WebhookRuleDto {
+    id: Uuid,
+    project_id: String,
+    name: String,
+    url: String,
+    min_level: Option<String>,
+    min_cost: Option<f64>,
+    min_latency: Option<f64>,
+    environment: Option<String>,
+    enabled: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebhookRuleListQuery {
+    #[serde(default)]
+    project_id: Option<String>,
+}
+
+pub(crate) async fn get_webhook_rules(
+    State(state): State<AppState>,
+    Query(q): Query<WebhookRuleListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let data: Vec<WebhookRuleDto> = sqlx::query_as(&format!(
+        "SELECT {RULE_COLUMNS} FROM webhook_rules WHERE ($1::TEXT IS NULL OR project_id = $1) ORDER BY project_id, name, id"
+    ))
+    .bind(q.project_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(serde_json::json!({ "data": data }))))
+}
+
+pub(crate) async fn get_webhook_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row: Option<WebhookRuleDto> = sqlx::query_as(&format!(
+        "SELECT {RULE_COLUMNS} FROM webhook_rules WHERE id = $1"
+    ))
+    .bind(rule_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(row.ok_or(ApiError::NotFound)?)))
+}
+
+pub(crate) async fn post_webhook_rule(
+    State(state): State<AppState>,
+    Json(mut req): Json<WebhookRuleRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    req.validate()?;
+    let project_id = req.project_id(&state);
+    projects::register(&state.pool, std::slice::from_ref(&project_id)).await?;
+    let row: WebhookRuleDto = sqlx::query_as(&format!(
+        r#"
+INSERT INTO webhook_rules (
+  id, project_id, name, url, min_level, min_cost, min_latency, environment, enabled
+) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+RETURNING {RULE_COLUMNS}
+        "#
+    ))
+    .bind(Uuid::new_v4())
+    .bind(project_id)
+    .bind(req.name.trim())
+    .bind(&req.url)
+    .bind(&req.min_level)
+    .bind(req.min_cost)
+    .bind(req.min_latency)
+    .bind(&req.environment)
+    .bind(req.enabled)
+    .fetch_one(&state.pool)
+    .await?;
+    Ok((StatusCode::OK, Json(row)))
+}
+
+/// Replaces every field of a rule. Traces it already fired for do not fire
+/// again.
+pub(crate) async fn put_webhook_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+    Json(mut req): Json<WebhookRuleRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    req.validate()?;
+    let project_id = req.project_id(&state);
+    projects::register(&state.pool, std::slice::from_ref(&project_id)).await?;
+    let row: WebhookRuleDto = sqlx::query_as(&format!(
+        r#"
+UPDATE webhook_rules SET
+  project_id = $2,
+  name = $3,
+  url = $4,
+  min_level = $5,
+  min_cost = $6,
+  min_latency = $7,
+  environment = $8,
+  enabled = $9,
+  updated_at = NOW()
+WHERE id = $1
+RETURNING {RULE_COLUMNS}
+        "#
+    ))
+    .bind(rule_id)
+    .bind(project_id)
+    .bind(req.name.trim())
+    .bind(&req.url)
+    .bind(&req.min_level)
+    .bind(req.min_cost)
+    .bind(req.min_latency)
+    .bind(&req.environment)
+    .bind(req.enabled)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    Ok((StatusCode::OK, Json(row)))
+}
+
+/// Deletes a rule. Events it already queued are still delivered.
+pub(crate) async fn delete_webhook_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let deleted = sqlx::query("DELETE FROM webhook_rules WHERE id = $1")
+        .bind(rule_id)
+        .execute(&state.pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok((StatusCode::OK, Json(serde_json::json!({ "id": rule_id }))))
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RuleMatch {
+    rule_id: Uuid,
+    rule_name: String,
+    url: String,
+    trace_id: Uuid,
+    project_id: String,
+    trace_name: Option<String>,
+    environment: String,
+    total_cost: Option<f64>,
+    latency: Option<f64>,
+    /// Observations at or above the rule's `minLevel`.
+    level_count: Option<i64>,
+}
+
+/// Records the rules `trace_ids` newly match and queues their events, in the
+/// ingest transaction.
+pub(crate) async fn evaluate(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    trace_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    if trace_ids.is_empty() {
+        return Ok(());
+    }
+    // Levels rank by their position in LEVELS; observations without a level
+    // are DEFAULT.
+    let matches: Vec<RuleMatch> = sqlx::query_as(
+        r#"
+WITH candidates AS (
+  SELECT
+    r.id AS rule_id, r.name AS rule_name, r.url, r.min_level,
+    t.id AS trace_id, t.project_id, t.name AS trace_name, t.environment, t.total_cost, t.latency,
+    (
+      SELECT COUNT(*) FROM observations o
+      WHERE o.trace_id = t.id
+        AND array_position($2::TEXT[], COALESCE(o.level, 'DEFAULT'))
+          >= array_position($2::TEXT[], r.min_level)
+    ) AS level_count
+  FROM webhook_rules r
+  JOIN traces t ON t.project_id = r.project_id
+  WHERE r.enabled AND t.id = ANY($1)
+    AND (r.environment IS NULL OR t.environment = r.environment)
+    AND (r.min_cost IS NULL OR t.total_cost >= r.min_cost)
+    AND (r.min_latency IS NULL OR t.latency >= r.min_latency)
+    AND NOT EXISTS (
+      SELECT 1 FROM webhook_rule_matches m WHERE m.rule_id = r.id AND m.trace_id = t.id
+    )
+),
+matched AS (
+  SELECT * FROM candidates WHERE min_level IS NULL OR level_count > 0
+),
+inserted AS (
+  INSERT INTO webhook_rule_matches (rule_id, trace_id)
+  SELECT rule_id, trace_id FROM matched
+  ON CONFLICT DO NOTHING
+  RETURNING rule_id, trace_id
+)
+SELECT
+  m.rule_id, m.rule_name, m.url, m.trace_id, m.project_id, m.trace_name, m.environment,
+  m.total_cost, m.latency,
+  CASE WHEN m.min_level IS NULL THEN NULL ELSE m.level_count END AS level_count
+FROM matched m
+JOIN inserted i ON i.rule_id = m.rule_id AND i.trace_id = m.trace_id
+ORDER BY m.trace_id, m.rule_name
+        "#,
+    )
+    .bind(trace_ids)
+    .bind(LEVELS.map(String::from).to_vec())
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for m in matches {
+        outbox::enqueue(
+            tx,
+            &m.url,
+            &m.project_id,
+            "rule.matched",
+            serde_json::json!({
+                "ruleId": m.rule_id,
+                "ruleName": m.rule_name,
+                "traceId": m.trace_id,
+                "projectId": m.project_id,
+                "traceName": m.trace_name,
+                "environment": m.environment,
+                "totalCost": m.total_cost,
+                "latency": m.latency,
+                "levelCount": m.level_count,
+            }),
+        )
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Admin introspection endpoints.
+
+mod common;
+
+use common::TestServer;
+
+#[tokio::test]
+async fn config_is_effective_and_redacted() {
+    let server = TestServer::start_with(|c| {
+        c.ingest_workers = 0;
+        c.ingest_cost_per_sec = 100;
+        c.webhook_url = Some("https://hooks.example.com/in?token=abc".to_string());
+    })
+    .await;
+
+    let config = server.get_json("/api/admin/config").await;
+    // Derived values, not the raw settings.
+    assert_eq!(config["ingest"]["workers"], 1);
+    assert_eq!(config["limits"]["ingestCost"]["burst"], 1000);
+    assert_eq!(
+        config["webhooks"]["url"],
+        "https://hooks.example.com/in?token=***"
+    );
+    assert_eq!(config["auth"]["apiBearerToken"], "***");
+    assert!(!config.to_string().contains(&server.token));
+    assert_eq!(config["runtime"]["writeMode"], "normal");
+
+    let res = server
+        .http
+        .put(server.url("/api/admin/mode"))
+        .bearer_auth(&server.token)
+        .json(&serde_json::json!({"mode": "read_only"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let config = server.get_json("/api/admin/config").await;
+    assert_eq!(config["runtime"]["writeMode"], "read_only");
+}
+
+#[tokio::test]
+async fn prometheus_metrics() {
+    let server = TestServer::start().await;
+    let trace_id = uuid::Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &serde_json::json!({"trace": {"id": trace_id}, "observations": [
+                {"id": uuid::Uuid::new_v4(), "traceId": trace_id, "type": "SPAN"}
+            ]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 1).await;
+
+    // No key needed, like the health checks.
+    let res = server
+        .http
+        .get(server.url("/metrics"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert!(res.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = res.text().await.unwrap();
+    let value = |series: &str| -> f64 {
+        body.lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+            .unwrap_or_else(|| panic!("{series} missing:\n{body}"))
+            .parse()
+            .unwrap()
+    };
+    assert!(value("xtrace_ingest_batches_written_total") >= 1.0);
+    assert!(value("xtrace_rows_written_total{table=\"observations\"}") >= 1.0);
+    assert!(value("xtrace_ingest_write_duration_seconds_count") >= 1.0);
+    assert_eq!(
+        value("xtrace_http_requests_total{method=\"POST\",route=\"/v1/l/batch\",status=\"200\"}"),
+        1.0
+    );
+    assert_eq!(value("xtrace_ingest_queue_depth{worker=\"0\"}"), 0.0);
+    assert_eq!(value("xtrace_write_mode{mode=\"normal\"}"), 1.0);
+    assert!(value("xtrace_db_pool_max_connections") > 0.0);
+}
+
+#[tokio::test]
+async fn stats_report_tables_and_throughput() {
+    let server = TestServer::start().await;
+    let trace_id = uuid::Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &serde_json::json!({"trace": {"id": trace_id}, "observations": [
+                {"id": uuid::Uuid::new_v4(), "traceId": trace_id, "type": "SPAN"},
+                {"id": uuid::Uuid::new_v4(), "traceId": trace_id, "type": "GENERATION"}
+            ]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 2).await;
+
+    // The worker records the write right after it commits, and settles the
+    // payload last.
+    let mut stats = server.get_json("/api/admin/stats").await;
+    for _ in 0..50 {
+        if stats["ingest"]["oldestPendingSecs"].is_null() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        stats = server.get_json("/api/admin/stats").await;
+    }
+    assert!(stats["database"]["sizeBytes"].as_i64().unwrap() > 0);
+    let tables = stats["database"]["tables"].as_array().unwrap();
+    for name in ["traces", "observations"] {
+        let table = tables
+            .iter()
+            .find(|t| t["name"] == name)
+            .unwrap_or_else(|| panic!("{name} missing: {stats}"));
+        assert!(table["bytes"].as_i64().unwrap() > 0);
+    }
+    // Partitions are rolled up into their table.
+    assert!(!tables
+        .iter()
+        .any(|t| t["name"].as_str().unwrap().starts_with("observations_p")));
+
+    let ingest = &stats["ingest"];
+    assert_eq!(ingest["queueDepth"], 0);
+    assert!(ingest["oldestPendingSecs"].is_null());
+    assert_eq!(ingest["throughput"]["windowMinutes"], 15);
+    assert_eq!(
+        ingest["throughput"]["perMinute"].as_array().unwrap().len(),
+        15
+    );
+    assert_eq!(ingest["throughput"]["traces"], 1);
+    assert_eq!(ingest["throughput"]["observations"], 2);
+    assert_eq!(stats["writeMode"], "normal");
+}
@@ -0,0 +1,300 @@
+//! Alert rules and their notifications.
+
+mod common;
+
+use chrono::Utc;
+use common::{receiver, TestServer};
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+async fn start() -> TestServer {
+    TestServer::start_with(|c| c.alert_interval_secs = 1).await
+}
+
+#[tokio::test]
+async fn metric_rules_fire_and_resolve() {
+    let server = start().await;
+    let (url, received) = receiver().await;
+
+    let now = Utc::now();
+    let res = server
+        .post_json(
+            "/v1/metrics/batch",
+            &json!({"metrics": [
+                {"name": "queue_depth", "labels": {"queue": "a"}, "value": 4.0, "timestamp": now - chrono::Duration::seconds(30)},
+                {"name": "queue_depth", "labels": {"queue": "a"}, "value": 10.0, "timestamp": now},
+                {"name": "queue_depth", "labels": {"queue": "b"}, "value": 100.0, "timestamp": now},
+            ]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let mut rule = json!({
+        "name": "queue a backlog",
+        "metric": "queue_depth",
+        "labels": {"queue": "a"},
+        "agg": "max",
+        "comparator": ">",
+        "threshold": 5,
+        "windowSecs": 300,
+        "slackWebhookUrl": url,
+    });
+    let res = server.post_json("/api/admin/alerts", &rule).await;
+    assert_eq!(res.status(), 200);
+    let created: JsonValue = res.json().await.unwrap();
+    assert_eq!(created["state"], "ok");
+    let path = format!("/api/admin/alerts/{}", created["id"].as_str().unwrap());
+
+    let messages = received.wait_for(1).await;
+    let text = messages[0]["text"].as_str().unwrap();
+    assert!(
+        text.starts_with("[xtrace] FIRING: queue a backlog"),
+        "{text}"
+    );
+    assert!(
+        text.contains("max(queue_depth) over the last 300s is 10"),
+        "{text}"
+    );
+    let firing = server.get_json(&path).await;
+    assert_eq!(firing["state"], "firing");
+    assert_eq!(firing["lastValue"], 10.0);
+    assert!(firing["lastNotifiedAt"].is_string());
+    assert_eq!(firing["lastError"], JsonValue::Null);
+
+    // Still breached: no second notification.
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    assert_eq!(received.bodies().len(), 1);
+
+    rule["threshold"] = json!(50);
+    let res = server
+        .http
+        .put(server.url(&path))
+        .bearer_auth(&server.token)
+        .json(&rule)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+
+    let messages = received.wait_for(2).await;
+    let text = messages[1]["text"].as_str().unwrap();
+    assert!(
+        text.starts_with("[xtrace] RESOLVED: queue a backlog"),
+        "{text}"
+    );
+    assert_eq!(server.get_json(&path).await["state"], "ok");
+}
+
+#[tokio::test]
+async fn trace_metrics_count_errors() {
+    let server = start().await;
+    let (url, received) = receiver().await;
+
+    let res = server
+        .post_json(
+            "/api/admin/alerts",
+            &json!({
+                "name": "errors",
+                "metric": "xtrace.errors",
+                "comparator": ">=",
+                "threshold": 2,
+                "windowSecs": 600,
+                "slackWebhookUrl": url,
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let rule: JsonValue = res.json().await.unwrap();
+    assert_eq!(rule["agg"], "sum");
+
+    let trace_id = Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {"id": trace_id, "timestamp": Utc::now()}, "observations": [
+                {"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN", "level": "ERROR"},
+                {"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN", "level": "ERROR"},
+                {"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN", "level": "WARNING"},
+            ]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 3).await;
+
+    let messages = received.wait_for(1).await;
+    let text = messages[0]["text"].as_str().unwrap();
+    assert!(
+        text.contains("sum(xtrace.errors) over the last 600s is 2"),
+        "{text}"
+    );
+}
+
+#[tokio::test]
+async fn rules_are_managed_through_the_admin_api() {
+    let server = start().await;
+    let project_id = format!("alerts-{}", Uuid::new_v4());
+    let rule = |overrides: JsonValue| {
+        let mut rule = json!({
+            "name": "cost",
+            "projectId": project_id,
+            "metric": "xtrace.cost",
+            "comparator": ">",
+            "threshold": 10,
+            "windowSecs": 3600,
+            "slackWebhookUrl": "https://hooks.slack.com/services/T/B/X",
+        });
+        for (k, v) in overrides.as_object().unwrap() {
+            rule[k] = v.clone();
+        }
+        rule
+    };
+
+    for body in [
+        rule(json!({"comparator": "=="})),
+        rule(json!({"agg": "avg"})),
+        rule(json!({"labels": {"queue": "a"}})),
+        rule(json!({"metric": "queue_depth", "agg": "median"})),
+        rule(json!({"metric": "queue_depth", "labels": {"queue": 1}})),
+        rule(json!({"windowSecs": 0})),
+        rule(json!({"slackWebhookUrl": null})),
+        rule(json!({"slackWebhookUrl": "hooks.slack.com"})),
+        // Needs XTRACE_SMTP_URL.
+        rule(json!({"emailTo": ["oncall@example.com"]})),
+    ] {
+        let res = server.post_json("/api/admin/alerts", &body).await;
+        assert_eq!(res.status(), 400, "{body}");
+    }
+
+    let res = server
+        .post_json("/api/admin/alerts", &rule(json!({})))
+        .await;
+    assert_eq!(res.status(), 200);
+    let created: JsonValue = res.json().await.unwrap();
+    assert_eq!(created["agg"], "sum");
+    assert_eq!(created["emailTo"], json!([]));
+    let path = format!("/api/admin/alerts/{}", created["id"].as_str().unwrap());
+
+    let res = server
+        .http
+        .put(server.url(&path))
+        .bearer_auth(&server.token)
+        .json(&rule(
+            json!({"metric": "latency_ms", "agg": "p99", "enabled": false}),
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let updated = server.get_json(&path).await;
+    assert_eq!(updated["metric"], "latency_ms");
+    assert_eq!(updated["agg"], "p99");
+    assert_eq!(updated["enabled"], false);
+
+    let list = server
+        .get_json(&format!("/api/admin/alerts?projectId={project_id}"))
+        .await;
+    assert_eq!(list["data"].as_array().unwrap().len(), 1);
+
+    let res = server
+        .http
+        .delete(server.url(&path))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let res = server
+        .http
+        .get(server.url(&path))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+/// A minimal SMTP server accepting any message; returns its URL and the
+/// received messages (headers and body).
+async fn smtp_server() -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("smtp://{}", listener.local_addr().unwrap());
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                write.write_all(b"220 localhost\r\n").await.unwrap();
+                let mut data: Option<String> = None;
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(message) = data.as_mut() {
+                        if line == "." {
+                            tx.send(data.take().unwrap()).unwrap();
+                            write.write_all(b"250 queued\r\n").await.unwrap();
+                        } else {
+                            message.push_str(&line);
+                            message.push('\n');
+                        }
+                        continue;
+                    }
+                    let reply: &[u8] = match line.get(..4).map(str::to_ascii_uppercase).as_deref() {
+                        Some("DATA") => {
+                            data = Some(String::new());
+                            b"354 go ahead\r\n"
+                        }
+                        Some("QUIT") => b"221 bye\r\n",
+                        _ => b"250 ok\r\n",
+                    };
+                    write.write_all(reply).await.unwrap();
+                }
+            });
+        }
+    });
+    (url, rx)
+}
+
+#[tokio::test]
+async fn rules_send_email() {
+    let (smtp_url, mut messages) = smtp_server().await;
+    let server = TestServer::start_with(|c| {
+        c.alert_interval_secs = 1;
+        c.smtp_url = Some(smtp_url);
+        c.smtp_from = "alerts@xtrace.example".to_string();
+    })
+    .await;
+
+    // No traces yet: fewer than one trace in the window.
+    let res = server
+        .post_json(
+            "/api/admin/alerts",
+            &json!({
+                "name": "no traffic",
+                "metric": "xtrace.traces",
+                "comparator": "<",
+                "threshold": 1,
+                "windowSecs": 60,
+                "emailTo": ["oncall@example.com"],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let message = tokio::time::timeout(std::time::Duration::from_secs(15), messages.recv())
+        .await
+        .expect("no email sent")
+        .unwrap();
+    assert!(message.contains("From: alerts@xtrace.example"), "{message}");
+    assert!(message.contains("To: oncall@example.com"), "{message}");
+    assert!(
+        message.contains("Subject: [xtrace] FIRING: no traffic"),
+        "{message}"
+    );
+    assert!(
+        message.contains("sum(xtrace.traces) over the last 60s is 0"),
+        "{message}"
+    );
+}
@@ -0,0 +1,1184 @@
+//! Ingest (`/v1/l/batch`, Langfuse `/api/public/ingestion`) to query flows.
+
+mod common;
+
+use common::{observation, TestServer};
+use serde_json::json;
+use uuid::Uuid;
+use xtrace_client::{BatchIngestRequest, GenerationRecorder, TraceListQuery};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batch_ingest_is_queryable() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let span_id = Uuid::new_v4();
+    let generation_id = Uuid::new_v4();
+
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({
+                "trace": {
+                    "id": trace_id,
+                    "timestamp": "2026-01-01T00:00:00Z",
+                    "name": "chat",
+                    "userId": "alice",
+                    "sessionId": "s-1",
+                    "tags": ["prod", "beta"],
+                    "input": {"question": "hi"},
+                },
+                "observations": [
+                    {
+                        "id": span_id,
+                        "traceId": trace_id,
+                        "type": "SPAN",
+                        "name": "agent",
+                        "startTime": "2026-01-01T00:00:00Z",
+                        "endTime": "2026-01-01T00:00:02Z",
+                    },
+                    {
+                        "id": generation_id,
+                        "traceId": trace_id,
+                        "type": "GENERATION",
+                        "name": "llm",
+                        "parentObservationId": span_id,
+                        "startTime": "2026-01-01T00:00:00.500Z",
+                        "endTime": "2026-01-01T00:00:01.500Z",
+                        "model": "gpt-4o",
+                        "promptTokens": 12,
+                        "completionTokens": 30,
+                        "totalTokens": 42,
+                        "output": "hello",
+                    },
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+
+    let trace = server.wait_for_trace(trace_id, 2).await;
+    assert_eq!(trace["name"], "chat");
+    assert_eq!(trace["userId"], "alice");
+    assert_eq!(trace["sessionId"], "s-1");
+    assert_eq!(trace["tags"], json!(["prod", "beta"]));
+    assert_eq!(trace["input"], json!({"question": "hi"}));
+    assert_eq!(trace["projectId"], server.project_id);
+
+    let generation = observation(&trace, generation_id);
+    assert_eq!(generation["type"], "GENERATION");
+    assert_eq!(generation["parentObservationId"], span_id.to_string());
+    assert_eq!(generation["model"], "gpt-4o");
+    assert_eq!(generation["promptTokens"], 12);
+    assert_eq!(generation["completionTokens"], 30);
+    assert_eq!(generation["totalTokens"], 42);
+    assert_eq!(generation["output"], "hello");
+    assert_eq!(observation(&trace, span_id)["type"], "SPAN");
+
+    // The typed client reads the same trace.
+    let detail = server
+        .client()
+        .get_trace(trace_id)
+        .await
+        .expect("get_trace");
+    assert_eq!(detail.observations.len(), 2);
+
+    let list = server
+        .client()
+        .list_traces(&TraceListQuery {
+            user_id: Some("alice".to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("list_traces");
+    assert_eq!(list.data.len(), 1);
+    assert_eq!(list.data[0].id, trace_id);
+
+    let observations = server
+        .get_json(&format!(
+            "/api/public/observations?traceId={trace_id}&type=GENERATION"
+        ))
+        .await;
+    assert_eq!(observations["data"].as_array().unwrap().len(), 1);
+
+    let count = server
+        .get_json("/api/public/observations/count?groupBy=model")
+        .await;
+    assert_eq!(count["total"], 2);
+    let groups = count["groups"].as_array().unwrap();
+    assert_eq!(groups.len(), 2);
+    assert!(groups.contains(&json!({"value": "gpt-4o", "count": 1})));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batch_upsert_updates_observation() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let generation_id = Uuid::new_v4();
+
+    let generation = |extra: serde_json::Value| {
+        let mut obs = json!({
+            "id": generation_id,
+            "traceId": trace_id,
+            "type": "GENERATION",
+            "name": "llm",
+            "startTime": "2026-01-01T00:00:00Z",
+            "model": "gpt-4o",
+        });
+        obs.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        obs
+    };
+
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {"id": trace_id, "name": "stream"}, "observations": [generation(json!({}))]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 1).await;
+
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"observations": [generation(json!({
+                "endTime": "2026-01-01T00:00:03Z",
+                "output": "done",
+                "totalTokens": 7,
+            }))]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    loop {
+        let trace = server.wait_for_trace(trace_id, 1).await;
+        let obs = observation(&trace, generation_id);
+        if obs["output"] == "done" {
+            assert_eq!(trace["observations"].as_array().unwrap().len(), 1);
+            assert_eq!(obs["totalTokens"], 7);
+            assert_eq!(obs["endTime"], "2026-01-01T00:00:03Z");
+            assert_eq!(trace["latency"], 3.0);
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "update not applied");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trace_latency_rolls_up_unless_sent() {
+    let server = TestServer::start().await;
+    let (derived, reported) = (Uuid::new_v4(), Uuid::new_v4());
+    let span = |trace_id: Uuid, start: &str, end: &str| {
+        json!({
+            "id": Uuid::new_v4(),
+            "traceId": trace_id,
+            "type": "SPAN",
+            "startTime": start,
+            "endTime": end,
+        })
+    };
+    for (trace, latency) in [(derived, None), (reported, Some(9.5))] {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace, "latency": latency},
+                    "observations": [
+                        span(trace, "2026-01-01T00:00:00Z", "2026-01-01T00:00:02Z"),
+                        span(trace, "2026-01-01T00:00:01Z", "2026-01-01T00:00:04.5Z"),
+                    ],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+    }
+
+    assert_eq!(server.wait_for_trace(derived, 2).await["latency"], 4.5);
+    assert_eq!(server.wait_for_trace(reported, 2).await["latency"], 9.5);
+}
+
+#[tokio::test]
+async fn client_trace_metrics_survive_later_writes() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let span = |start: &str, end: &str| {
+        json!({
+            "id": Uuid::new_v4(),
+            "traceId": trace_id,
+            "type": "SPAN",
+            "startTime": start,
+            "endTime": end,
+            "calculatedTotalCost": 1.0,
+        })
+    };
+    for (body, observations) in [
+        (
+            json!({
+                "trace": {"id": trace_id, "latency": 9.5, "totalCost": 0.25},
+                "observations": [span("2026-01-01T00:00:00Z", "2026-01-01T00:00:02Z")],
+            }),
+            1,
+        ),
+        // Neither the trace update nor the new observation replaces them.
+        (
+            json!({
+                "trace": {"id": trace_id, "name": "renamed"},
+                "observations": [span("2026-01-01T00:00:01Z", "2026-01-01T00:00:04.5Z")],
+            }),
+            2,
+        ),
+    ] {
+        let res = server.post_json("/v1/l/batch", &body).await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, observations).await;
+    }
+
+    let trace = server.wait_for_trace(trace_id, 2).await;
+    assert_eq!(trace["name"], "renamed");
+    assert_eq!(trace["latency"], 9.5);
+    assert_eq!(trace["totalCost"], 0.25);
+}
+
+#[tokio::test]
+async fn trace_detail_breaks_down_latency_by_type() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let observation = |kind: &str, start: &str, end: Option<&str>, latency: Option<f64>| {
+        json!({
+            "id": Uuid::new_v4(),
+            "traceId": trace_id,
+            "type": kind,
+            "startTime": start,
+            "endTime": end,
+            "latency": latency,
+        })
+    };
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({
+                "trace": {"id": trace_id},
+                "observations": [
+                    observation("SPAN", "2026-01-01T00:00:00Z", Some("2026-01-01T00:00:04Z"), None),
+                    observation("GENERATION", "2026-01-01T00:00:00.5Z", Some("2026-01-01T00:00:02.5Z"), None),
+                    observation("TOOL", "2026-01-01T00:00:02Z", None, Some(1.0)),
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let expected = json!({"SPAN": 1.5, "GENERATION": 1.5, "TOOL": 1.0});
+    assert_eq!(
+        server.wait_for_trace(trace_id, 3).await["latencyBreakdown"],
+        expected
+    );
+    // Paginated details still cover every observation.
+    let page = server
+        .get_json(&format!(
+            "/api/public/traces/{trace_id}?observationsLimit=1"
+        ))
+        .await;
+    assert_eq!(page["latencyBreakdown"], expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bulk_write_applies_repeated_ids_in_order() {
+    let server = TestServer::start_with(|c| c.conflict_policy = Some("merge".to_string())).await;
+    let trace_id = Uuid::new_v4();
+    let repeated = Uuid::new_v4();
+    let mut observations: Vec<_> = (0..50)
+        .map(|i| json!({"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN", "name": format!("span-{i}")}))
+        .collect();
+    observations
+        .push(json!({"id": repeated, "traceId": trace_id, "name": "llm", "model": "gpt-4o"}));
+    observations.push(json!({"id": repeated, "traceId": trace_id, "output": "done"}));
+
+    let res = server
+        .post_json("/v1/l/batch", &json!({"observations": observations}))
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let trace = server.wait_for_trace(trace_id, 51).await;
+    let obs = observation(&trace, repeated);
+    assert_eq!(obs["type"], "GENERATION");
+    assert_eq!(obs["name"], "llm");
+    assert_eq!(obs["model"], "gpt-4o");
+    assert_eq!(obs["output"], "done");
+    assert_eq!(obs["metadata"]["xtrace_conflict"]["outcome"], "merged");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn partial_updates_keep_newer_fields() {
+    let server = TestServer::start().await;
+    let (trace_id, obs_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let write = |trace: serde_json::Value, obs: serde_json::Value| {
+        let mut trace = trace;
+        let mut obs = obs;
+        trace["id"] = json!(trace_id);
+        obs["id"] = json!(obs_id);
+        obs["traceId"] = json!(trace_id);
+        let body = json!({"trace": trace, "observations": [obs]});
+        let server = &server;
+        async move {
+            let res = server.post_json("/v1/l/batch", &body).await;
+            assert_eq!(res.status(), 200);
+        }
+    };
+
+    write(
+        json!({"name": "chat", "userId": "alice", "tags": ["prod"], "eventTime": "2026-01-01T00:00:00Z"}),
+        json!({"type": "GENERATION", "name": "llm", "input": "hi", "eventTime": "2026-01-01T00:00:00Z"}),
+    )
+    .await;
+    write(
+        json!({"output": "bye", "eventTime": "2026-01-01T00:00:05Z"}),
+        json!({"output": "new", "eventTime": "2026-01-01T00:00:05Z"}),
+    )
+    .await;
+    // Delivered late: fills in `level` and `release` but changes nothing else.
+    write(
+        json!({"name": "stale", "release": "v1", "eventTime": "2026-01-01T00:00:03Z"}),
+        json!({"output": "old", "level": "WARNING", "eventTime": "2026-01-01T00:00:03Z"}),
+    )
+    .await;
+
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    let trace = loop {
+        let trace = server.wait_for_trace(trace_id, 1).await;
+        if observation(&trace, obs_id)["level"] == "WARNING" {
+            break trace;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "update not applied");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    };
+    assert_eq!(trace["name"], "chat");
+    assert_eq!(trace["userId"], "alice");
+    assert_eq!(trace["tags"], json!(["prod"]));
+    assert_eq!(trace["output"], "bye");
+    assert_eq!(trace["release"], "v1");
+    let obs = observation(&trace, obs_id);
+    assert_eq!(obs["type"], "GENERATION");
+    assert_eq!(obs["name"], "llm");
+    assert_eq!(obs["input"], "hi");
+    assert_eq!(obs["output"], "new");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn parallel_workers_write_every_trace() {
+    let server = TestServer::start_with(|c| {
+        c.ingest_workers = 4;
+        c.ingest_batch_window_ms = 5;
+    })
+    .await;
+    let trace_ids: Vec<Uuid> = (0..16).map(|_| Uuid::new_v4()).collect();
+    for trace_id in &trace_ids {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id},
+                    "observations": [{"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN"}],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+    }
+    for trace_id in trace_ids {
+        server.wait_for_trace(trace_id, 1).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ids_stay_unique_across_partitions() {
+    let server = TestServer::start_with(|c| {
+        c.ingest_workers = 4;
+        c.ingest_batch_window_ms = 5;
+    })
+    .await;
+    let (trace_id, shared_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let now = chrono::Utc::now();
+    // The default, current and future partitions.
+    let timestamps = [
+        now - chrono::TimeDelta::days(400),
+        now,
+        now + chrono::TimeDelta::days(5 * 365),
+    ];
+
+    // Concurrent writes move the trace and its observations between
+    // partitions without ever storing an id twice.
+    let mut writes = tokio::task::JoinSet::new();
+    for i in 0..24 {
+        writes.spawn(
+            server
+                .http
+                .post(server.url("/v1/l/batch"))
+                .bearer_auth(&server.token)
+                .json(&json!({
+                    "trace": {"id": trace_id, "timestamp": timestamps[i % 3]},
+                    "observations": [
+                        {"id": shared_id, "traceId": trace_id, "type": "SPAN"},
+                        {"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN"},
+                    ],
+                }))
+                .send(),
+        );
+    }
+    while let Some(res) = writes.join_next().await {
+        assert_eq!(res.unwrap().unwrap().status(), 200);
+    }
+    server.wait_for_trace(trace_id, 25).await;
+
+    let pool = sqlx::PgPool::connect(&server.database_url).await.unwrap();
+    let rows =
+        |sql: &'static str, id: Uuid| sqlx::query_scalar::<_, i64>(sql).bind(id).fetch_one(&pool);
+    let traces = rows("SELECT COUNT(*) FROM traces WHERE id = $1", trace_id);
+    assert_eq!(traces.await.unwrap(), 1);
+    let observations = rows("SELECT COUNT(*) FROM observations WHERE id = $1", shared_id);
+    assert_eq!(observations.await.unwrap(), 1);
+    let misplaced: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM observations o JOIN traces t ON t.id = o.trace_id \
+         WHERE t.id = $1 AND o.trace_timestamp <> t.timestamp",
+    )
+    .bind(trace_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(misplaced, 0);
+
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {"id": trace_id, "timestamp": timestamps[2], "name": "moved"}}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    while server.wait_for_trace(trace_id, 25).await["name"] != "moved" {
+        assert!(tokio::time::Instant::now() < deadline, "trace not moved");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let partitions: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT tableoid::regclass::TEXT FROM traces WHERE id = $1 \
+         UNION SELECT DISTINCT tableoid::regclass::TEXT FROM observations WHERE trace_id = $1",
+    )
+    .bind(trace_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        partitions
+            .iter()
+            .map(String::as_str)
+            .collect::<std::collections::BTreeSet<_>>(),
+        ["observations_future", "traces_future"].into()
+    );
+
+    // Time-bounded trace queries only read the partitions in range.
+    let plan: Vec<String> = sqlx::query_scalar(
+        "EXPLAIN SELECT id FROM traces WHERE timestamp >= NOW() - INTERVAL '1 hour' AND timestamp < NOW()",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let plan = plan.join("\n");
+    assert!(!plan.contains("traces_future"), "{plan}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recorded_stream_is_ingested() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let mut rec = GenerationRecorder::start(trace_id, "chat", "gpt-4o")
+        .with_input(json!([{"role": "user", "content": "hi"}]));
+    rec.record_chunk("");
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    for chunk in ["Hel", "lo", "!"] {
+        rec.record_chunk(chunk);
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+    rec.record_usage(Some(3), Some(2));
+    let id = rec.id();
+    let generation = rec.finish();
+    let ttft = generation.time_to_first_token.unwrap();
+    assert!(ttft >= 0.02 && ttft <= generation.latency.unwrap());
+
+    server
+        .client()
+        .ingest_batch(&BatchIngestRequest {
+            observations: vec![generation],
+            ..Default::default()
+        })
+        .await
+        .expect("ingest_batch");
+
+    let trace = server.wait_for_trace(trace_id, 1).await;
+    let obs = observation(&trace, id);
+    assert_eq!(obs["type"], "GENERATION");
+    assert_eq!(obs["output"], "Hello!");
+    assert_eq!(obs["totalTokens"], 5);
+    assert!(obs["completionStartTime"].is_string());
+    assert_eq!(obs["metadata"]["stream_chunks"], 3);
+    assert!(
+        obs["metadata"]["stream_inter_chunk_max_ms"]
+            .as_f64()
+            .unwrap()
+            >= 5.0
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streamed_updates_append_output() {
+    // Updates are not duplicates, so even the strictest policy accepts them.
+    let server = TestServer::start_with(|c| c.conflict_policy = Some("error".to_string())).await;
+    let trace_id = Uuid::new_v4();
+    let mut rec = GenerationRecorder::start(trace_id, "chat", "gpt-4o").with_input("hi");
+    let id = rec.id();
+    let send = |obs| {
+        let client = server.client();
+        async move {
+            client
+                .ingest_batch(&BatchIngestRequest {
+                    observations: vec![obs],
+                    ..Default::default()
+                })
+                .await
+                .expect("ingest_batch");
+        }
+    };
+    let wait_for = |expected: &'static str| {
+        let server = &server;
+        async move {
+            let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+            loop {
+                let trace = server.wait_for_trace(trace_id, 1).await;
+                let obs = observation(&trace, id).clone();
+                if obs["output"] == expected {
+                    return obs;
+                }
+                assert!(
+                    tokio::time::Instant::now() < deadline,
+                    "{expected} not written"
+                );
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        }
+    };
+
+    rec.record_chunk("Hel");
+    send(rec.flush().unwrap()).await;
+    wait_for("Hel").await;
+    rec.record_chunk("lo");
+    send(rec.flush().unwrap()).await;
+    rec.record_chunk(" world");
+    send(rec.flush().unwrap()).await;
+    assert!(rec.flush().is_none());
+    let obs = wait_for("Hello world").await;
+    assert_eq!(obs["name"], "chat");
+    assert_eq!(obs["input"], "hi");
+    assert!(obs["endTime"].is_null());
+    assert!(obs["metadata"]["xtrace_conflict"].is_null());
+
+    send(rec.finish()).await;
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    while wait_for("Hello world").await["endTime"].is_null() {
+        assert!(tokio::time::Instant::now() < deadline, "finish not written");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    // A Langfuse update alone is an update too.
+    let res = server
+        .post_json(
+            "/api/public/ingestion",
+            &json!({"batch": [{
+                "id": "evt-level",
+                "type": "generation-update",
+                "timestamp": chrono::Utc::now(),
+                "body": {"id": id, "traceId": trace_id, "level": "WARNING"},
+            }]}),
+        )
+        .await;
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["successes"][0]["id"], "evt-level", "{body}");
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    loop {
+        let obs = wait_for("Hello world").await;
+        if obs["level"] == "WARNING" {
+            assert_eq!(obs["name"], "chat");
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "update not written");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resent_deltas_are_appended_once() {
+    let server = TestServer::start().await;
+    let (trace_id, obs_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let delta = |seq: i64, text: &str| {
+        json!({"observations": [{
+            "id": obs_id, "traceId": trace_id, "outputDelta": text, "outputSeq": seq,
+        }]})
+    };
+
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"observations": [{"id": obs_id, "traceId": trace_id, "outputDelta": "a"}]}),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+
+    // Retries without event ids, as after a timeout or a replay.
+    for (seq, text) in [(1, "a"), (1, "a"), (2, "b"), (1, "a"), (2, "b"), (3, "c")] {
+        let res = server.post_json("/v1/l/batch", &delta(seq, text)).await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    }
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    let output = loop {
+        let trace = server.wait_for_trace(trace_id, 1).await;
+        let output = observation(&trace, obs_id)["output"].clone();
+        if output.as_str().is_some_and(|o| o.ends_with('c')) {
+            break output;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "deltas not written");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    };
+    assert_eq!(output, "abc");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retried_batches_are_applied_once() {
+    let server = TestServer::start().await;
+    let (trace_id, obs_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let batch = |event_id: &str, output: &str| {
+        json!({
+            "eventId": event_id,
+            "trace": {"id": trace_id},
+            "observations": [{"id": obs_id, "traceId": trace_id, "output": output}],
+        })
+    };
+    let output = || async {
+        let trace = server
+            .get_json(&format!("/api/public/traces/{trace_id}"))
+            .await;
+        observation(&trace, obs_id)["output"].clone()
+    };
+    let wait_for_output = |expected: &'static str| async move {
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+        while output().await != expected {
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "{expected} not written"
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    };
+
+    // The second copy is skipped when written, or acknowledged right away.
+    for _ in 0..2 {
+        let res = server.post_json("/v1/l/batch", &batch("a", "first")).await;
+        assert_eq!(res.status(), 200);
+    }
+    server.wait_for_trace(trace_id, 1).await;
+    wait_for_output("first").await;
+    let res = server.post_json("/v1/l/batch", &batch("b", "second")).await;
+    assert_eq!(res.status(), 200);
+    wait_for_output("second").await;
+
+    let res = server.post_json("/v1/l/batch", &batch("a", "first")).await;
+    assert_eq!(res.status(), 200);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["data"]["duplicate"], true);
+
+    let res = server
+        .http
+        .post(server.url("/v1/l/batch"))
+        .bearer_auth(&server.token)
+        .header("idempotency-key", "b")
+        .json(&json!({"observations": [{"id": obs_id, "traceId": trace_id, "output": "stale"}]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        res.json::<serde_json::Value>().await.unwrap()["data"]["duplicate"],
+        true
+    );
+
+    let event = |id: &str, output: &str| {
+        json!({"batch": [{
+            "id": id,
+            "type": "span-update",
+            "timestamp": chrono::Utc::now(),
+            "body": {"id": obs_id, "traceId": trace_id, "output": output},
+        }]})
+    };
+    let res = server
+        .post_json("/api/public/ingestion", &event("evt-third", "third"))
+        .await;
+    assert_eq!(res.status(), 207);
+    wait_for_output("third").await;
+    let res = server
+        .post_json("/api/public/ingestion", &event("evt-third", "stale"))
+        .await;
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["successes"][0]["id"], "evt-third");
+
+    // Writes are queued in order: once a later write landed, a queued retry
+    // would have been applied.
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"eventId": "c", "trace": {"id": trace_id, "name": "done"}}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    loop {
+        let trace = server
+            .get_json(&format!("/api/public/traces/{trace_id}"))
+            .await;
+        if trace["name"] == "done" {
+            assert_eq!(observation(&trace, obs_id)["output"], "third");
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "update not applied");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let metrics = server
+        .http
+        .get(server.url("/metrics"))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        !metrics.contains("xtrace_ingest_events_deduplicated_total 0\n"),
+        "{metrics}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn langfuse_ingestion_batch() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let generation_id = Uuid::new_v4();
+
+    let res = server
+        .post_json(
+            "/api/public/ingestion",
+            &json!({"batch": [
+                {
+                    "id": "evt-1",
+                    "type": "trace-create",
+                    "timestamp": "2026-01-01T00:00:00Z",
+                    "body": {"id": trace_id, "name": "langfuse-trace", "userId": "bob"},
+                },
+                {
+                    "id": "evt-2",
+                    "type": "generation-create",
+                    "timestamp": "2026-01-01T00:00:00Z",
+                    "body": {
+                        "id": generation_id,
+                        "traceId": trace_id,
+                        "name": "completion",
+                        "model": "gpt-4o-mini",
+                        "startTime": "2026-01-01T00:00:00Z",
+                        "usage": {"input": 3, "output": 4, "total": 7},
+                    },
+                },
+                {"id": "evt-3", "type": "no-such-event", "timestamp": "2026-01-01T00:00:00Z", "body": {}},
+            ]}),
+        )
+        .await;
+    assert_eq!(res.status(), 207);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let successes: Vec<_> = body["successes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["id"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(successes, ["evt-1", "evt-2"]);
+    assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+    assert_eq!(body["errors"][0]["id"], "evt-3");
+
+    let trace = server.wait_for_trace(trace_id, 1).await;
+    assert_eq!(trace["name"], "langfuse-trace");
+    assert_eq!(trace["userId"], "bob");
+    let generation = observation(&trace, generation_id);
+    assert_eq!(generation["type"], "GENERATION");
+    assert_eq!(generation["model"], "gpt-4o-mini");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn raw_ingest_history() {
+    let server = TestServer::start_with(|config| config.raw_ingest_ttl_secs = 3600).await;
+    let trace_id = Uuid::new_v4();
+    let body = json!({"trace": {"id": trace_id, "name": "raw"}, "observations": []});
+
+    let res = server.post_json("/v1/l/batch", &body).await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 0).await;
+
+    let path = format!("/api/admin/traces/{trace_id}/raw");
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    let raw = loop {
+        let raw = server.get_json(&path).await;
+        if !raw["data"].as_array().unwrap().is_empty() {
+            break raw;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "raw payload not recorded"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    };
+    assert_eq!(raw["enabled"], true);
+    let entry = &raw["data"][0];
+    assert_eq!(entry["endpoint"], "/v1/l/batch");
+    assert_eq!(entry["projectId"], server.project_id);
+    assert_eq!(entry["bodyEncoding"], "json");
+    assert_eq!(entry["body"], body);
+    assert_eq!(entry["headers"]["authorization"], "[redacted]");
+
+    let other = server
+        .get_json(&format!("/api/admin/traces/{}/raw", Uuid::new_v4()))
+        .await;
+    assert_eq!(other["data"], json!([]));
+}
+
+#[tokio::test]
+async fn failed_payloads_are_dead_lettered() {
+    let server = TestServer::start().await;
+    let (good, poison) = (Uuid::new_v4(), Uuid::new_v4());
+    // PostgreSQL rejects NUL characters in text, so this payload cannot be written.
+    for (id, name) in [(good, "good"), (poison, "bad\u{0}name")] {
+        let res = server
+            .post_json("/v1/l/batch", &json!({"trace": {"id": id, "name": name}}))
+            .await;
+        assert_eq!(res.status(), 200);
+    }
+    server.wait_for_trace(good, 0).await;
+
+    let path = format!("/api/admin/failed-events?traceId={poison}");
+    let wait_for_event = |previous: Option<i64>| {
+        let path = path.clone();
+        let server = &server;
+        async move {
+            let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+            loop {
+                let events = server.get_json(&path).await;
+                if let Some(event) = events["data"].as_array().unwrap().first() {
+                    if event["id"].as_i64() != previous {
+                        break event.clone();
+                    }
+                }
+                assert!(
+                    tokio::time::Instant::now() < deadline,
+                    "payload not dead-lettered"
+                );
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        }
+    };
+    let event = wait_for_event(None).await;
+    assert_eq!(event["projectId"], server.project_id);
+    assert_eq!(event["attempts"], 3);
+    assert_eq!(event["payload"]["trace"]["name"], "bad\u{0}name");
+    let id = event["id"].as_i64().unwrap();
+
+    // A replayed payload that fails again comes back under a new id.
+    let res = server
+        .post_json(&format!("/api/admin/failed-events/{id}/replay"), &json!({}))
+        .await;
+    assert_eq!(res.status(), 200);
+    let replayed = wait_for_event(Some(id)).await;
+    let replayed_id = replayed["id"].as_i64().unwrap();
+
+    let delete = |id: i64| {
+        server
+            .http
+            .delete(server.url(&format!("/api/admin/failed-events/{id}")))
+            .bearer_auth(&server.token)
+            .send()
+    };
+    assert_eq!(delete(replayed_id).await.unwrap().status(), 200);
+    assert_eq!(delete(replayed_id).await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn write_ahead_log_is_replayed_at_startup() {
+    let dir = std::env::temp_dir().join(format!("xtrace-wal-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // A payload acknowledged by a previous run that crashed before writing it.
+    let trace_id = Uuid::new_v4();
+    let segment = dir.join(format!("{:020}.wal", 0));
+    let record = json!({"seq": 0, "complete": [], "payload": {"trace": {"id": trace_id, "name": "replayed"}}});
+    std::fs::write(&segment, format!("{record}\n")).unwrap();
+
+    let server = TestServer::start_with(|c| c.wal_dir = Some(dir.display().to_string())).await;
+    assert_eq!(server.wait_for_trace(trace_id, 0).await["name"], "replayed");
+
+    // New payloads go through the log as well; written segments are deleted.
+    let live = Uuid::new_v4();
+    let res = server
+        .post_json("/v1/l/batch", &json!({"trace": {"id": live}}))
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(live, 0).await;
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    while segment.exists() {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "replayed segment not deleted"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn sampled_out_traces_are_dropped_unless_they_fail() {
+    let server = TestServer::start_with(|c| {
+        c.project_sample_rates = Some(format!("{}=0", c.default_project_id));
+    })
+    .await;
+    let (dropped, failed) = (Uuid::new_v4(), Uuid::new_v4());
+    let span = |trace_id: Uuid, level: &str| json!({"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN", "level": level});
+    for body in [
+        json!({"trace": {"id": dropped}, "observations": [span(dropped, "DEFAULT")]}),
+        json!({"trace": {"id": failed}, "observations": [span(failed, "ERROR")]}),
+        // Stored traces keep receiving writes.
+        json!({"observations": [span(failed, "DEFAULT")]}),
+    ] {
+        let res = server.post_json("/v1/l/batch", &body).await;
+        assert_eq!(res.status(), 200);
+    }
+    server.wait_for_trace(failed, 2).await;
+
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/traces/{dropped}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+    let metrics = server
+        .http
+        .get(server.url("/metrics"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        metrics.contains("xtrace_traces_sampled_out_total 1\n"),
+        "{metrics}"
+    );
+}
+
+#[tokio::test]
+async fn ingest_is_rate_limited_per_key() {
+    let server = TestServer::start_with(|c| {
+        c.ingest_rate_limit_qps = 1;
+        c.ingest_rate_limit_burst = 2;
+    })
+    .await;
+    let batch = json!({"trace": {"id": Uuid::new_v4()}});
+    for _ in 0..2 {
+        let res = server.post_json("/v1/l/batch", &batch).await;
+        assert_eq!(res.status(), 200);
+    }
+    let res = server.post_json("/v1/l/batch", &batch).await;
+    assert_eq!(res.status(), 429);
+    assert!(res.headers().contains_key("retry-after"));
+    let res = server
+        .post_json("/api/public/ingestion", &json!({"batch": []}))
+        .await;
+    assert_eq!(res.status(), 429);
+
+    // Read routes have their own bucket.
+    let res = server
+        .http
+        .get(server.url("/api/public/traces"))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn full_queue_waits_then_signals_retry_after() {
+    let server = TestServer::start_with(|c| {
+        c.ingest_queue_capacity = 1;
+        c.ingest_max_batches = 1;
+        c.ingest_enqueue_timeout_ms = 500;
+        c.ingest_retry_after_secs = 3;
+    })
+    .await;
+    // Holding the trace's write lock stalls the worker on its first payload.
+    let trace_id = Uuid::new_v4();
+    let mut lock: sqlx::PgConnection = sqlx::Connection::connect(&server.database_url)
+        .await
+        .unwrap();
+    sqlx::query("SELECT pg_advisory_lock(hashtextextended($1, 0))")
+        .bind(trace_id.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let batch = || {
+        json!({
+            "trace": {"id": trace_id},
+            "observations": [{"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN"}],
+        })
+    };
+
+    let mut accepted = 0;
+    let rejected = loop {
+        let started = std::time::Instant::now();
+        let res = server.post_json("/v1/l/batch", &batch()).await;
+        if res.status() == 429 {
+            assert!(started.elapsed() >= std::time::Duration::from_millis(500));
+            break res;
+        }
+        assert_eq!(res.status(), 200);
+        accepted += 1;
+        assert!(accepted < 5, "queue never filled up");
+    };
+    assert_eq!(rejected.headers()["retry-after"], "3");
+    let body: serde_json::Value = rejected.json().await.unwrap();
+    assert_eq!(body["code"], "TOO_MANY_REQUESTS");
+    assert_eq!(body["data"]["queueDepth"], 1);
+    assert_eq!(body["data"]["queueCapacity"], 1);
+    assert_eq!(body["data"]["retryAfterSecs"], 3);
+
+    // A request that finds the queue full is accepted once it drains in time.
+    let last = batch();
+    let (res, ()) = tokio::join!(server.post_json("/v1/l/batch", &last), async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+            .bind(trace_id.to_string())
+            .execute(&mut lock)
+            .await
+            .unwrap();
+    });
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, accepted + 1).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn requests_are_queued_whole_or_not_at_all() {
+    let server = TestServer::start_with(|c| {
+        c.ingest_queue_capacity = 2;
+        c.ingest_max_batches = 1;
+    })
+    .await;
+    let stalled = Uuid::new_v4();
+    let mut lock: sqlx::PgConnection = sqlx::Connection::connect(&server.database_url)
+        .await
+        .unwrap();
+    sqlx::query("SELECT pg_advisory_lock(hashtextextended($1, 0))")
+        .bind(stalled.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let queue_depth = || async {
+        server.get_json("/api/admin/stats").await["ingest"]["queueDepth"]
+            .as_u64()
+            .unwrap()
+    };
+
+    // The worker stalls on the first payload; the second leaves one slot.
+    let batch = json!({"trace": {"id": stalled}});
+    assert_eq!(server.post_json("/v1/l/batch", &batch).await.status(), 200);
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    while queue_depth().await > 0 {
+        assert!(tokio::time::Instant::now() < deadline, "worker idle");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(server.post_json("/v1/l/batch", &batch).await.status(), 200);
+
+    // Two traces make two payloads, which do not fit.
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+    let event = |id: Uuid| {
+        json!({
+            "id": Uuid::new_v4().to_string(),
+            "type": "trace-create",
+            "timestamp": chrono::Utc::now(),
+            "body": {"id": id},
+        })
+    };
+    let res = server
+        .post_json(
+            "/api/public/ingestion",
+            &json!({"batch": [event(a), event(b)]}),
+        )
+        .await;
+    assert_eq!(res.status(), 429);
+    assert_eq!(queue_depth().await, 1);
+
+    sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+        .bind(stalled.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    server.wait_for_trace(stalled, 0).await;
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/traces/{a}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn requests_larger_than_a_queue_are_rejected_at_once() {
+    let server = TestServer::start_with(|c| {
+        c.ingest_workers = 1;
+        c.ingest_queue_capacity = 1;
+        c.ingest_enqueue_timeout_ms = 5000;
+    })
+    .await;
+    let event = |id: Uuid| {
+        json!({
+            "id": Uuid::new_v4().to_string(),
+            "type": "trace-create",
+            "timestamp": chrono::Utc::now(),
+            "body": {"id": id},
+        })
+    };
+
+    // Two payloads for a queue of one could never be accepted: no waiting, no 429.
+    let started = std::time::Instant::now();
+    let res = server
+        .post_json(
+            "/api/public/ingestion",
+            &json!({"batch": [event(Uuid::new_v4()), event(Uuid::new_v4())]}),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+
+    let trace_id = Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/api/public/ingestion",
+            &json!({"batch": [event(trace_id)]}),
+        )
+        .await;
+    assert_eq!(res.status(), 207);
+    server.wait_for_trace(trace_id, 0).await;
+}
@@ -0,0 +1,724 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{observation, TestServer};
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+/// Polls an admin job until it is no longer running.
+async fn wait_for_job(server: &TestServer, job_id: &str) -> JsonValue {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+    loop {
+        let job = server.get_json(&format!("/api/admin/jobs/{job_id}")).await;
+        if job["status"] != "running" {
+            return job;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "job {job_id} still running"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test]
+async fn recompute_costs_job() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let (priced, unpriced, other_model) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let model = format!("recompute-{}", Uuid::new_v4());
+    let session = format!("s-{trace_id}");
+    let generation = |id: Uuid, model: &str, cost: Option<f64>, tokens: Option<(i64, i64)>| {
+        json!({
+            "id": id,
+            "traceId": trace_id,
+            "type": "GENERATION",
+            "startTime": "2026-01-01T00:00:00Z",
+            "model": model,
+            "promptTokens": tokens.map(|t| t.0),
+            "completionTokens": tokens.map(|t| t.1),
+            "totalTokens": tokens.map(|t| t.0 + t.1),
+            "calculatedTotalCost": cost,
+        })
+    };
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({
+                "trace": {"id": trace_id, "sessionId": session, "complete": true},
+                "observations": [
+                    generation(priced, &model, Some(1.0), Some((100, 10))),
+                    generation(unpriced, &model, Some(0.5), None),
+                    generation(other_model, "other", Some(2.0), Some((100, 10))),
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    let trace = server.wait_for_trace(trace_id, 3).await;
+    assert_eq!(trace["totalCost"], 3.5);
+
+    let res = server
+        .post_json(
+            "/api/admin/jobs",
+            &json!({
+                "type": "recompute_costs",
+                "params": {
+                    "model": model,
+                    "fromStartTime": "2026-01-01T00:00:00Z",
+                    "toStartTime": "2026-01-02T00:00:00Z",
+                    "projectId": server.project_id,
+                    "inputPrice": 0.01,
+                    "outputPrice": 0.02,
+                },
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 202, "{}", res.text().await.unwrap());
+    let job: JsonValue = res.json().await.unwrap();
+    assert_eq!(job["type"], "recompute_costs");
+
+    let job = wait_for_job(&server, job["id"].as_str().unwrap()).await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+    assert_eq!(job["progress"]["scanned"], 2);
+    assert_eq!(job["progress"]["observationsUpdated"], 2);
+    assert_eq!(job["progress"]["tracesUpdated"], 1);
+    assert_eq!(job["progress"]["sessionsRefreshed"], 1);
+
+    let trace = server
+        .get_json(&format!("/api/public/traces/{trace_id}"))
+        .await;
+    let cost = |id| {
+        observation(&trace, id)["calculatedTotalCost"]
+            .as_f64()
+            .unwrap()
+    };
+    assert!((cost(priced) - 1.2).abs() < 1e-9);
+    assert_eq!(observation(&trace, priced)["inputPrice"], 0.01);
+    // No token counts: the stored cost is kept.
+    assert_eq!(cost(unpriced), 0.5);
+    assert_eq!(cost(other_model), 2.0);
+    assert!((trace["totalCost"].as_f64().unwrap() - 3.7).abs() < 1e-9);
+
+    let session = server
+        .get_json(&format!("/api/public/sessions/{session}"))
+        .await;
+    assert!((session["totalCost"].as_f64().unwrap() - 3.7).abs() < 1e-9);
+
+    let jobs = server.get_json("/api/admin/jobs?status=succeeded").await;
+    assert!(jobs["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|j| j["id"] == job["id"]));
+}
+
+#[tokio::test]
+async fn recompute_costs_job_rejects_bad_params() {
+    let server = TestServer::start().await;
+    let res = server
+        .post_json(
+            "/api/admin/jobs",
+            &json!({
+                "type": "recompute_costs",
+                "params": {
+                    "model": "gpt-4o",
+                    "fromStartTime": "2026-01-02T00:00:00Z",
+                    "toStartTime": "2026-01-01T00:00:00Z",
+                },
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+
+    let res = server
+        .post_json("/api/admin/jobs", &json!({"type": "vacuum", "params": {}}))
+        .await;
+    assert_eq!(res.status(), 422);
+}
+
+#[tokio::test]
+async fn compact_resources_job() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let (span, other) = (Uuid::new_v4(), Uuid::new_v4());
+    let resource = json!({"service.name": format!("svc-{trace_id}"), "host.name": "h1"});
+    let with_resource = |id: Uuid| {
+        json!({
+            "id": id,
+            "traceId": trace_id,
+            "type": "SPAN",
+            "metadata": {"http.route": "/chat", "otel.resource": resource},
+        })
+    };
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"observations": [with_resource(span), with_resource(other)]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let before = server.wait_for_trace(trace_id, 2).await;
+    assert_eq!(
+        observation(&before, span)["metadata"]["otel.resource"],
+        resource
+    );
+
+    // The ingest worker stored the attributes once; turn one observation
+    // back into a row written before the dictionary existed.
+    let pool = sqlx::PgPool::connect(&server.database_url).await.unwrap();
+    let stored: Vec<(Option<Uuid>, JsonValue)> =
+        sqlx::query_as("SELECT resource_id, metadata FROM observations WHERE trace_id = $1")
+            .bind(trace_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert!(stored[0].0.is_some() && stored[0].0 == stored[1].0);
+    assert!(stored[0].1.get("otel.resource").is_none());
+    sqlx::query(
+        r#"
+UPDATE observations o
+SET metadata = o.metadata || jsonb_build_object('otel.resource', r.attributes), resource_id = NULL
+FROM otel_resources r
+WHERE o.id = $1 AND r.id = o.resource_id
+        "#,
+    )
+    .bind(span)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let res = server
+        .post_json(
+            "/api/admin/jobs",
+            &json!({"type": "compact_resources", "params": {"projectId": server.project_id}}),
+        )
+        .await;
+    assert_eq!(res.status(), 202, "{}", res.text().await.unwrap());
+    let job: JsonValue = res.json().await.unwrap();
+    let job_id = job["id"].as_str().unwrap();
+
+    // The event stream ends with the finished job.
+    let res = server
+        .http
+        .get(server.url(&format!("/api/admin/jobs/{job_id}/events")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/event-stream");
+    let body = tokio::time::timeout(Duration::from_secs(15), res.text())
+        .await
+        .expect("event stream did not end")
+        .unwrap();
+    let last = body.trim_end().rsplit("\n\n").next().unwrap();
+    assert!(last.starts_with("event: succeeded\n"), "{body}");
+    let finished: JsonValue = serde_json::from_str(last.split_once("data: ").unwrap().1).unwrap();
+    assert_eq!(finished["processed"], 1);
+    assert_eq!(finished["total"], 1);
+    assert_eq!(finished["percent"], 100.0);
+
+    let job = wait_for_job(&server, job_id).await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+    assert_eq!(job["progress"]["observationsCompacted"], 1);
+    assert_eq!(job["progress"]["resourcesCreated"], 0);
+
+    let (resource_id, metadata): (Option<Uuid>, JsonValue) =
+        sqlx::query_as("SELECT resource_id, metadata FROM observations WHERE id = $1")
+            .bind(span)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(resource_id, stored[0].0);
+    assert!(metadata.get("otel.resource").is_none());
+    let after = server.wait_for_trace(trace_id, 2).await;
+    assert_eq!(
+        observation(&after, span)["metadata"],
+        observation(&before, span)["metadata"]
+    );
+}
+
+#[tokio::test]
+async fn purge_expired_job() {
+    let server = TestServer::start_with(|c| {
+        c.project_retention_days = Some(format!("{}=1", c.default_project_id));
+    })
+    .await;
+    let (old, fresh) = (Uuid::new_v4(), Uuid::new_v4());
+    let session = format!("s-{old}");
+    let three_days_ago = chrono::Utc::now() - chrono::TimeDelta::days(3);
+    for (trace_id, timestamp) in [(old, three_days_ago), (fresh, chrono::Utc::now())] {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "timestamp": timestamp, "sessionId": session},
+                    "observations": [{"id": Uuid::new_v4(), "traceId": trace_id, "startTime": timestamp}],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+        server.wait_for_trace(trace_id, 1).await;
+    }
+    for trace_id in [old, fresh] {
+        let res = server
+            .post_json(
+                "/api/public/scores",
+                &json!({"traceId": trace_id, "name": "quality", "value": 1.0}),
+            )
+            .await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    }
+    let metric = format!("retention-{}", Uuid::new_v4());
+    let res = server
+        .post_json(
+            "/v1/metrics/batch",
+            &json!({"metrics": [
+                {"name": metric, "value": 1.0, "timestamp": three_days_ago},
+                {"name": metric, "value": 2.0, "timestamp": chrono::Utc::now()},
+            ]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let pool = sqlx::PgPool::connect(&server.database_url).await.unwrap();
+    // A score of the fresh trace that expired on its own.
+    sqlx::query(
+        r#"INSERT INTO scores (id, project_id, trace_id, name, value, "timestamp") VALUES ($1, $2, $3, 'stale', 0, $4)"#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(&server.project_id)
+    .bind(fresh)
+    .bind(three_days_ago)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let metric_points = || {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM metrics WHERE name = $1")
+            .bind(&metric)
+            .fetch_one(&pool)
+    };
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+    while metric_points().await.unwrap() < 2 {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "metrics not written"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let res = server
+        .post_json(
+            "/api/admin/jobs",
+            &json!({"type": "purge_expired", "params": {"projectId": server.project_id}}),
+        )
+        .await;
+    assert_eq!(res.status(), 202, "{}", res.text().await.unwrap());
+    let job: JsonValue = res.json().await.unwrap();
+    let job = wait_for_job(&server, job["id"].as_str().unwrap()).await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+    assert_eq!(job["progress"]["tracesDeleted"], 1);
+    assert_eq!(job["progress"]["observationsDeleted"], 1);
+    assert_eq!(job["progress"]["scoresDeleted"], 2);
+    assert_eq!(job["progress"]["metricRowsDeleted"], 1);
+    assert_eq!(job["progress"]["sessionsRefreshed"], 1);
+    assert_eq!(job["percent"], 100.0);
+
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/traces/{old}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+    server.wait_for_trace(fresh, 1).await;
+    let scores = server
+        .get_json(&format!("/api/public/scores?traceId={fresh}"))
+        .await;
+    assert_eq!(scores["data"].as_array().unwrap().len(), 1);
+    assert_eq!(metric_points().await.unwrap(), 1);
+    let session = server
+        .get_json(&format!("/api/public/sessions/{session}"))
+        .await;
+    assert_eq!(session["traceCount"], 1, "{session}");
+
+    let res = server
+        .post_json(
+            "/api/admin/jobs",
+            &json!({"type": "purge_expired", "params": {"olderThanDays": 0}}),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test]
+async fn erase_user_job() {
+    let server = TestServer::start().await;
+    let (deleted_user, anonymized_user) = (
+        format!("erase-{}", Uuid::new_v4()),
+        format!("anon-{}", Uuid::new_v4()),
+    );
+    let (deleted, anonymized, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    for (trace_id, user_id) in [
+        (deleted, deleted_user.as_str()),
+        (anonymized, anonymized_user.as_str()),
+        (other, "someone-else"),
+    ] {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "userId": user_id, "input": "my address is ..."},
+                    "observations": [{"id": Uuid::new_v4(), "traceId": trace_id, "output": "noted"}],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+        server.wait_for_trace(trace_id, 1).await;
+    }
+    for (trace_id, author) in [(anonymized, None), (other, Some(&anonymized_user))] {
+        let res = server
+            .post_json(
+                "/api/public/scores",
+                &json!({"traceId": trace_id, "name": "quality", "value": 1.0, "comment": "personal", "authorUserId": author}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+    }
+
+    for (user_id, mode) in [(&deleted_user, "delete"), (&anonymized_user, "anonymize")] {
+        let res = server
+            .post_json(
+                "/api/admin/jobs",
+                &json!({"type": "erase_user", "params": {"userId": user_id, "projectId": server.project_id, "mode": mode}}),
+            )
+            .await;
+        assert_eq!(res.status(), 202, "{}", res.text().await.unwrap());
+        let job: JsonValue = res.json().await.unwrap();
+        let job = wait_for_job(&server, job["id"].as_str().unwrap()).await;
+        assert_eq!(job["status"], "succeeded", "{job}");
+        assert_eq!(job["total"], 1);
+        assert_eq!(job["progress"]["observations"], 1);
+    }
+
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/traces/{deleted}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+
+    let trace = server.wait_for_trace(anonymized, 1).await;
+    assert_eq!(trace["userId"], JsonValue::Null);
+    assert_eq!(trace["input"], JsonValue::Null);
+    assert_eq!(trace["observations"][0]["output"], JsonValue::Null);
+    assert_eq!(trace["scores"][0]["value"], 1.0);
+    assert_eq!(trace["scores"][0]["comment"], JsonValue::Null);
+    let trace = server.wait_for_trace(other, 1).await;
+    assert_eq!(trace["input"], "my address is ...");
+    assert_eq!(trace["scores"][0]["authorUserId"], JsonValue::Null);
+    assert_eq!(trace["scores"][0]["comment"], "personal");
+
+    let res = server
+        .post_json(
+            "/api/admin/jobs",
+            &json!({"type": "erase_user", "params": {"userId": ""}}),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test]
+async fn traces_are_exported_to_a_file() {
+    let server = TestServer::start().await;
+    let (first, second, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let session = format!("s-{first}");
+    for (trace_id, session, observations) in [
+        (first, Some(&session), 2),
+        (second, Some(&session), 1),
+        (other, None, 1),
+    ] {
+        let observations: Vec<JsonValue> = (0..observations)
+            .map(|i| {
+                json!({
+                    "id": Uuid::new_v4(),
+                    "traceId": trace_id,
+                    "type": "GENERATION",
+                    "startTime": format!("2026-01-01T00:00:0{i}Z"),
+                    "model": "gpt-4o",
+                    "promptTokens": 10,
+                })
+            })
+            .collect();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {
+                        "id": trace_id,
+                        "sessionId": session,
+                        "timestamp": if trace_id == first { "2026-01-01T00:00:00Z" } else { "2026-01-02T00:00:00Z" },
+                        "input": "a, \"quoted\"\nline",
+                    },
+                    "observations": observations,
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+        server.wait_for_trace(trace_id, observations.len()).await;
+    }
+
+    let export = |format: &'static str| {
+        let server = &server;
+        let body = json!({"format": format, "filter": {"sessionId": session}});
+        async move {
+            let res = server.post_json("/api/public/exports", &body).await;
+            assert_eq!(res.status(), 202, "{}", res.text().await.unwrap());
+            let job: JsonValue = res.json().await.unwrap();
+            assert_eq!(job["type"], "export_traces");
+            let id = job["id"].as_str().unwrap().to_string();
+            let job = wait_for_job(server, &id).await;
+            assert_eq!(job["status"], "succeeded", "{job}");
+            assert_eq!(job["total"], 2);
+            assert_eq!(job["progress"]["traces"], 2);
+            assert_eq!(job["progress"]["observations"], 3);
+
+            let export = server.get_json(&format!("/api/public/exports/{id}")).await;
+            let path = export["downloadPath"].as_str().unwrap().to_string();
+            assert_eq!(path, format!("/api/public/exports/{id}/download"));
+            let res = server
+                .http
+                .get(server.url(&path))
+                .bearer_auth(&server.token)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.status(), 200);
+            let disposition = res.headers()["content-disposition"].to_str().unwrap();
+            assert!(disposition.contains(&format!("traces-{id}.{format}")));
+            (id, res.bytes().await.unwrap().to_vec())
+        }
+    };
+
+    let (jsonl_id, jsonl) = export("jsonl").await;
+    let lines: Vec<JsonValue> = String::from_utf8(jsonl)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["id"], json!(first));
+    assert_eq!(lines[0]["sessionId"], json!(session));
+    assert_eq!(lines[0]["observations"].as_array().unwrap().len(), 2);
+    assert_eq!(lines[0]["observations"][0]["model"], "gpt-4o");
+    assert_eq!(lines[1]["id"], json!(second));
+
+    let (_, csv) = export("csv").await;
+    let csv = String::from_utf8(csv).unwrap();
+    let rows: Vec<&str> = csv.split("\r\n").filter(|r| !r.is_empty()).collect();
+    assert!(rows[0].starts_with("recordType,traceId,observationId,"));
+    assert!(rows[1].starts_with(&format!("trace,{first},,")), "{csv}");
+    assert!(rows[1].contains("\"a, \"\"quoted\"\"\nline\""), "{csv}");
+    assert!(
+        rows[2].starts_with(&format!("observation,{first},")),
+        "{csv}"
+    );
+    // Header, two traces and three observations; quoted newlines are not row breaks.
+    assert_eq!(csv.matches("\r\n").count(), 6);
+
+    #[cfg(feature = "parquet")]
+    {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let (_, parquet) = export("parquet").await;
+        let reader = SerializedFileReader::new(bytes::Bytes::from(parquet)).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 5);
+        let fields = metadata.file_metadata().schema().get_fields();
+        assert_eq!(fields[0].name(), "recordType");
+        assert_eq!(fields[1].name(), "traceId");
+    }
+    #[cfg(not(feature = "parquet"))]
+    {
+        let res = server
+            .post_json("/api/public/exports", &json!({"format": "parquet"}))
+            .await;
+        assert_eq!(res.status(), 400);
+    }
+    let res = server
+        .post_json("/api/public/exports", &json!({"format": "xml"}))
+        .await;
+    assert_eq!(res.status(), 422);
+
+    let listed = server.get_json("/api/public/exports").await;
+    assert!(listed["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|e| e["id"] == json!(jsonl_id)));
+    let res = server
+        .http
+        .delete(server.url(&format!("/api/public/exports/{jsonl_id}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/exports/{jsonl_id}/download")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn generations_are_exported_for_fine_tuning() {
+    let server = TestServer::start().await;
+    let session = format!("s-{}", Uuid::new_v4());
+    let tag = format!("golden-{}", Uuid::new_v4());
+    let (golden, rated, poor) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let generation = |trace_id: Uuid, second: u32, input: JsonValue, output: JsonValue| {
+        json!({
+            "id": Uuid::new_v4(),
+            "traceId": trace_id,
+            "type": "GENERATION",
+            "startTime": format!("2026-01-01T00:00:0{second}Z"),
+            "input": input,
+            "output": output,
+        })
+    };
+    let messages = json!([
+        {"role": "system", "content": "Answer briefly."},
+        {"role": "user", "content": "Capital of France?"},
+    ]);
+    for (trace_id, tags, observations) in [
+        (
+            golden,
+            vec![tag.clone()],
+            vec![
+                generation(golden, 0, messages.clone(), json!("Paris.")),
+                generation(
+                    golden,
+                    1,
+                    json!("2+2?"),
+                    json!({"choices": [{"message": {"role": "assistant", "content": "4"}}]}),
+                ),
+                generation(golden, 2, json!("no answer"), JsonValue::Null),
+                json!({"id": Uuid::new_v4(), "traceId": golden, "type": "SPAN", "input": "x", "output": "y"}),
+            ],
+        ),
+        (
+            rated,
+            vec![],
+            vec![generation(rated, 0, json!("Hi"), json!("Hello!"))],
+        ),
+        (
+            poor,
+            vec![],
+            vec![generation(poor, 0, json!("Hi"), json!("Go away."))],
+        ),
+    ] {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "sessionId": session, "tags": tags},
+                    "observations": observations,
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+        server.wait_for_trace(trace_id, observations.len()).await;
+    }
+    for (trace_id, value) in [(rated, 0.9), (poor, 0.2)] {
+        let res = server
+            .post_json(
+                "/api/public/scores",
+                &json!({"traceId": trace_id, "name": "quality", "value": value}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+    }
+
+    let export = |body: JsonValue| {
+        let server = &server;
+        async move {
+            let res = server.post_json("/api/public/exports", &body).await;
+            assert_eq!(res.status(), 202, "{}", res.text().await.unwrap());
+            let job: JsonValue = res.json().await.unwrap();
+            let id = job["id"].as_str().unwrap().to_string();
+            let job = wait_for_job(server, &id).await;
+            assert_eq!(job["status"], "succeeded", "{job}");
+            let res = server
+                .http
+                .get(server.url(&format!("/api/public/exports/{id}/download")))
+                .bearer_auth(&server.token)
+                .send()
+                .await
+                .unwrap();
+            assert!(res.headers()["content-disposition"]
+                .to_str()
+                .unwrap()
+                .ends_with(".jsonl\""));
+            let lines: Vec<JsonValue> = res
+                .text()
+                .await
+                .unwrap()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect();
+            (job, lines)
+        }
+    };
+
+    let (job, examples) = export(json!({"format": "openai_chat", "filter": {"tags": [tag]}})).await;
+    assert_eq!(job["progress"]["examples"], 2);
+    assert_eq!(job["progress"]["skippedGenerations"], 1);
+    assert_eq!(examples.len(), 2);
+    assert_eq!(
+        examples[0]["messages"],
+        json!([
+            {"role": "system", "content": "Answer briefly."},
+            {"role": "user", "content": "Capital of France?"},
+            {"role": "assistant", "content": "Paris."},
+        ])
+    );
+    assert_eq!(
+        examples[1]["messages"],
+        json!([
+            {"role": "user", "content": "2+2?"},
+            {"role": "assistant", "content": "4"},
+        ])
+    );
+
+    let (job, examples) = export(json!({
+        "format": "openai_chat",
+        "filter": {"sessionId": session},
+        "minScore": {"name": "quality", "value": 0.5},
+    }))
+    .await;
+    assert_eq!(job["total"], 1);
+    assert_eq!(
+        examples,
+        vec![json!({"messages": [
+            {"role": "user", "content": "Hi"},
+            {"role": "assistant", "content": "Hello!"},
+        ]})]
+    );
+
+    let res = server
+        .post_json(
+            "/api/public/exports",
+            &json!({"format": "openai_chat", "minScore": {"name": "", "value": 1}}),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+}
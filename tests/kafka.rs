@@ -0,0 +1,140 @@
+//! Kafka ingest source (`kafka` feature). The broker is started with
+//! testcontainers (Docker required) unless `XTRACE_TEST_KAFKA_BROKERS` points
+//! at an existing one.
+
+#![cfg(feature = "kafka")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::TestServer;
+use rdkafka::{
+    admin::{AdminClient, AdminOptions, NewTopic, TopicReplication},
+    client::DefaultClientContext,
+    consumer::{BaseConsumer, Consumer},
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig, Offset, TopicPartitionList,
+};
+use serde_json::json;
+use testcontainers_modules::{
+    kafka::apache::{Kafka, KAFKA_PORT},
+    testcontainers::{runners::AsyncRunner, ContainerAsync},
+};
+use uuid::Uuid;
+
+async fn brokers() -> (String, Option<ContainerAsync<Kafka>>) {
+    if let Ok(brokers) = std::env::var("XTRACE_TEST_KAFKA_BROKERS") {
+        return (brokers, None);
+    }
+    let container = Kafka::default()
+        .start()
+        .await
+        .expect("failed to start Kafka; is Docker running? (or set XTRACE_TEST_KAFKA_BROKERS)");
+    let port = container
+        .get_host_port_ipv4(KAFKA_PORT)
+        .await
+        .expect("kafka port");
+    (format!("127.0.0.1:{port}"), Some(container))
+}
+
+/// Creates a single-partition topic and writes `messages` to it in order.
+async fn produce(brokers: &str, topic: &str, messages: &[Vec<u8>]) {
+    let admin: AdminClient<DefaultClientContext> = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .create()
+        .expect("admin client");
+    let results = admin
+        .create_topics(
+            &[NewTopic::new(topic, 1, TopicReplication::Fixed(1))],
+            &AdminOptions::new(),
+        )
+        .await
+        .expect("create topic");
+    for result in results {
+        result.expect("create topic");
+    }
+
+    let producer: FutureProducer = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .create()
+        .expect("producer");
+    for message in messages {
+        producer
+            .send(
+                FutureRecord::<(), _>::to(topic).payload(message),
+                Duration::from_secs(10),
+            )
+            .await
+            .map_err(|(err, _)| err)
+            .expect("produce");
+    }
+}
+
+/// The offset committed for `group` on partition 0 of `topic`.
+fn committed_offset(brokers: &str, group: &str, topic: &str) -> Offset {
+    let consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("group.id", group)
+        .create()
+        .expect("consumer");
+    let mut partitions = TopicPartitionList::new();
+    partitions.add_partition(topic, 0);
+    consumer
+        .committed_offsets(partitions, Duration::from_secs(10))
+        .expect("committed offsets")
+        .find_partition(topic, 0)
+        .expect("partition")
+        .offset()
+}
+
+#[tokio::test]
+async fn payloads_are_consumed_and_poison_messages_skipped() {
+    let (brokers, _kafka) = brokers().await;
+    let topic = format!("xtrace-test-{}", Uuid::new_v4());
+    let group = format!("xtrace-test-{}", Uuid::new_v4());
+    let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+    let span = |trace_id: Uuid| json!({"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN"});
+    let messages = [
+        json!({"trace": {"id": first}, "observations": [span(first)]}).to_string(),
+        // Not JSON.
+        "{\"trace\":".to_string(),
+        // JSON, but not a valid payload.
+        json!({"observations": [{"id": Uuid::new_v4(), "traceId": first, "outputDelta": "x"}]})
+            .to_string(),
+        json!({"trace": {"id": second}, "observations": [span(second), span(second)]}).to_string(),
+    ];
+    let messages: Vec<Vec<u8>> = messages.into_iter().map(String::into_bytes).collect();
+    produce(&brokers, &topic, &messages).await;
+
+    let server = TestServer::start_with(|c| {
+        c.kafka_brokers = Some(brokers.clone());
+        c.kafka_topic = topic.clone();
+        c.kafka_group_id = group.clone();
+    })
+    .await;
+    // Payloads without a `projectId` go to the default project; the invalid
+    // messages in between do not hold up the one after them.
+    server.wait_for_trace(first, 1).await;
+    server.wait_for_trace(second, 2).await;
+
+    // Every message is committed, the skipped ones included, so a restart
+    // does not read them again.
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(30);
+    loop {
+        let (brokers, group, topic) = (brokers.clone(), group.clone(), topic.clone());
+        let offset =
+            tokio::task::spawn_blocking(move || committed_offset(&brokers, &group, &topic))
+                .await
+                .unwrap();
+        if offset == Offset::Offset(messages.len() as i64) {
+            break;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "offset {offset:?} was committed, not {}",
+            messages.len()
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
@@ -0,0 +1,256 @@
+//! Ingest-only, read-only and project-bound keys.
+
+mod common;
+
+use common::TestServer;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn ingest_and_read_keys_are_limited() {
+    let file_read_token = format!("read-{}", Uuid::new_v4());
+    let db_ingest_token = format!("ingest-{}", Uuid::new_v4());
+    let keys_file = std::env::temp_dir().join(format!("xtrace-keys-{}", Uuid::new_v4()));
+    std::fs::write(&keys_file, format!("read bearer {file_read_token}\n")).unwrap();
+    let ingest_token = format!("ingest-{}", Uuid::new_v4());
+    let read_token = format!("read-{}", Uuid::new_v4());
+    let server = TestServer::start_with(|c| {
+        c.ingest_token = Some(ingest_token.clone());
+        c.read_token = Some(read_token.clone());
+        c.keys_file = Some(keys_file.display().to_string());
+        c.keys_reload_secs = 1;
+    })
+    .await;
+
+    let pool = sqlx::PgPool::connect(&server.database_url).await.unwrap();
+    sqlx::query("INSERT INTO api_keys (kind, key, access) VALUES ('bearer', $1, 'ingest')")
+        .bind(&db_ingest_token)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let ingest = |token: String| {
+        let trace_id = Uuid::new_v4();
+        let req = server
+            .http
+            .post(server.url("/v1/l/batch"))
+            .bearer_auth(token)
+            .json(&json!({"trace": {"id": trace_id}}))
+            .send();
+        async move { (trace_id, req.await.unwrap().status()) }
+    };
+    let query =
+        |token: String, path: &str| server.http.get(server.url(path)).bearer_auth(token).send();
+
+    // Ingest-only keys write but cannot read.
+    let (trace_id, status) = ingest(ingest_token.clone()).await;
+    assert_eq!(status, 200);
+    server.wait_for_trace(trace_id, 0).await;
+    let trace_path = format!("/api/public/traces/{trace_id}");
+    for path in [
+        trace_path.as_str(),
+        "/api/public/traces",
+        "/api/admin/config",
+    ] {
+        let res = query(ingest_token.clone(), path).await.unwrap();
+        assert_eq!(res.status(), 403, "{path}");
+    }
+    let res = query(ingest_token.clone(), "/api/public/projects")
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+
+    // Read-only keys read but cannot write.
+    for token in [&read_token, &file_read_token] {
+        let res = query(token.clone(), &trace_path).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(ingest(token.clone()).await.1, 403);
+        let res = server
+            .http
+            .post(server.url("/api/public/scores"))
+            .bearer_auth(token)
+            .json(&json!({"traceId": trace_id, "name": "quality", "value": 1}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 403);
+        let res = query(token.clone(), "/api/admin/config").await.unwrap();
+        assert_eq!(res.status(), 403);
+    }
+
+    // `api_keys.access`, picked up at the next reload.
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    while ingest(db_ingest_token.clone()).await.1 != 200 {
+        assert!(tokio::time::Instant::now() < deadline, "key not reloaded");
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+    let res = query(db_ingest_token.clone(), &trace_path).await.unwrap();
+    assert_eq!(res.status(), 403);
+
+    // Full keys do both.
+    let res = query(server.token.clone(), &trace_path).await.unwrap();
+    assert_eq!(res.status(), 200);
+
+    std::fs::remove_file(keys_file).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bound_keys_cannot_write_to_other_projects() {
+    let (token_a, token_b) = (
+        format!("a-{}", Uuid::new_v4()),
+        format!("b-{}", Uuid::new_v4()),
+    );
+    let (project_a, project_b) = (
+        format!("a-{}", Uuid::new_v4()),
+        format!("b-{}", Uuid::new_v4()),
+    );
+    let keys_file = std::env::temp_dir().join(format!("xtrace-keys-{}", Uuid::new_v4()));
+    std::fs::write(
+        &keys_file,
+        format!("bearer {token_a} {project_a}\nbearer {token_b} {project_b}\n"),
+    )
+    .unwrap();
+    let server = TestServer::start_with(|c| {
+        c.keys_file = Some(keys_file.display().to_string());
+    })
+    .await;
+
+    let post = |token: &str, path: &str, body: serde_json::Value| {
+        server
+            .http
+            .post(server.url(path))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+    };
+    let get_trace = |token: &str, trace_id: Uuid| {
+        server
+            .http
+            .get(server.url(&format!("/api/public/traces/{trace_id}")))
+            .bearer_auth(token)
+            .send()
+    };
+    let (trace_id, obs_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let res = post(
+        &token_b,
+        "/v1/l/batch",
+        json!({
+            "trace": {"id": trace_id, "name": "mine", "input": "b"},
+            "observations": [{"id": obs_id, "traceId": trace_id, "type": "SPAN", "name": "b"}],
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.status(), 200);
+    wait_for(&server, &token_b, trace_id, 1).await;
+
+    // The trace, its observation, and new observations in it are out of reach.
+    for body in [
+        json!({"trace": {"id": trace_id, "name": "taken", "input": "a"}}),
+        json!({"observations": [{"id": obs_id, "traceId": Uuid::new_v4(), "name": "taken"}]}),
+        json!({"observations": [{"id": Uuid::new_v4(), "traceId": trace_id, "name": "added"}]}),
+    ] {
+        let res = post(&token_a, "/v1/l/batch", body.clone()).await.unwrap();
+        assert_eq!(res.status(), 403, "{body}");
+    }
+    let res = post(
+        &token_a,
+        "/api/public/ingestion",
+        json!({"batch": [{
+            "id": "evt-taken",
+            "type": "trace-create",
+            "timestamp": chrono::Utc::now(),
+            "body": {"id": trace_id, "name": "taken"},
+        }]}),
+    )
+    .await
+    .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["errors"][0]["id"], "evt-taken", "{body}");
+    assert_eq!(body["errors"][0]["status"], 403, "{body}");
+
+    // A write queued before the other project stored the trace is dropped.
+    let late_id = Uuid::new_v4();
+    let mut lock: sqlx::PgConnection = sqlx::Connection::connect(&server.database_url)
+        .await
+        .unwrap();
+    sqlx::query("SELECT pg_advisory_lock(hashtextextended($1, 0))")
+        .bind(late_id.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let res = post(
+        &token_a,
+        "/v1/l/batch",
+        json!({"trace": {"id": late_id, "name": "taken"}}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.status(), 200);
+    sqlx::query(
+        "INSERT INTO traces (id, project_id, timestamp, name) VALUES ($1, $2, NOW(), 'mine')",
+    )
+    .bind(late_id)
+    .bind(&project_b)
+    .execute(&mut lock)
+    .await
+    .unwrap();
+    sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+        .bind(late_id.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let after_id = Uuid::new_v4();
+    let res = post(&token_a, "/v1/l/batch", json!({"trace": {"id": after_id}}))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    wait_for(&server, &token_a, after_id, 0).await;
+
+    let trace = wait_for(&server, &token_b, trace_id, 1).await;
+    assert_eq!(trace["name"], "mine", "{trace}");
+    assert_eq!(trace["input"], "b", "{trace}");
+    assert_eq!(
+        trace["observations"].as_array().unwrap().len(),
+        1,
+        "{trace}"
+    );
+    assert_eq!(trace["observations"][0]["name"], "b", "{trace}");
+    let late = wait_for(&server, &token_b, late_id, 0).await;
+    assert_eq!(late["name"], "mine", "{late}");
+    for id in [trace_id, late_id] {
+        assert_eq!(get_trace(&token_a, id).await.unwrap().status(), 404);
+    }
+
+    std::fs::remove_file(keys_file).ok();
+}
+
+/// Polls the trace detail as `token` until it has `observations`.
+async fn wait_for(
+    server: &TestServer,
+    token: &str,
+    trace_id: Uuid,
+    observations: usize,
+) -> serde_json::Value {
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    loop {
+        let res = server
+            .http
+            .get(server.url(&format!("/api/public/traces/{trace_id}")))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        if res.status() == 200 {
+            let trace: serde_json::Value = res.json().await.unwrap();
+            if trace["observations"].as_array().map_or(0, Vec::len) >= observations {
+                return trace;
+            }
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "{trace_id} not written"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
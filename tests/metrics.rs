@@ -0,0 +1,201 @@
+//! Metric rollups and the trace activity heatmap.
+
+mod common;
+
+use std::time::Duration;
+
+use chrono::{DurationRound, SecondsFormat, TimeDelta, Utc};
+use common::TestServer;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn old_points_are_rolled_up() {
+    let server = TestServer::start_with(|c| {
+        c.metrics_rollup_after_secs = 3600;
+        c.metrics_hourly_rollup_after_secs = 24 * 3600;
+        c.metrics_rollup_interval_secs = 1;
+    })
+    .await;
+    let name = format!("rollup-{}", Uuid::new_v4());
+    let now = Utc::now();
+    let two_days_ago = (now - TimeDelta::days(2))
+        .duration_trunc(TimeDelta::hours(1))
+        .unwrap();
+    let two_hours_ago = (now - TimeDelta::hours(2))
+        .duration_trunc(TimeDelta::minutes(1))
+        .unwrap();
+    let point = |ts: chrono::DateTime<Utc>, value: f64| json!({"name": name, "labels": {"host": "a"}, "value": value, "timestamp": ts});
+    let res = server
+        .post_json(
+            "/v1/metrics/batch",
+            &json!({"metrics": [
+                point(two_days_ago + TimeDelta::minutes(10), 1.0),
+                point(two_days_ago + TimeDelta::minutes(20), 3.0),
+                point(two_hours_ago + TimeDelta::seconds(10), 4.0),
+                point(two_hours_ago + TimeDelta::seconds(20), 2.0),
+                point(now, 5.0),
+            ]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let pool = sqlx::PgPool::connect(&server.database_url).await.unwrap();
+    let count = |table: &'static str| {
+        let (pool, name) = (&pool, &name);
+        async move {
+            sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {table} WHERE name = $1"))
+                .bind(name)
+                .fetch_one(pool)
+                .await
+                .unwrap()
+        }
+    };
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+    while (
+        count("metrics").await,
+        count("metrics_1m").await,
+        count("metrics_1h").await,
+    ) != (1, 1, 1)
+    {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "points not rolled up"
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    let query = |agg: &str| {
+        format!(
+            "/api/public/metrics/query?name={name}&step=1h&agg={agg}&from={}&to={}",
+            (now - TimeDelta::days(3)).to_rfc3339_opts(SecondsFormat::Secs, true),
+            (now + TimeDelta::minutes(1)).to_rfc3339_opts(SecondsFormat::Secs, true),
+        )
+    };
+    let values = |body: serde_json::Value| -> Vec<f64> {
+        body["data"][0]["values"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["value"].as_f64().unwrap())
+            .collect()
+    };
+    assert_eq!(
+        values(server.get_json(&query("sum")).await),
+        [4.0, 6.0, 5.0]
+    );
+    assert_eq!(
+        values(server.get_json(&query("avg")).await),
+        [2.0, 3.0, 5.0]
+    );
+    assert_eq!(
+        values(server.get_json(&query("max")).await),
+        [3.0, 4.0, 5.0]
+    );
+    assert_eq!(
+        values(server.get_json(&query("last")).await),
+        [3.0, 2.0, 5.0]
+    );
+
+    let names = server.get_json("/api/public/metrics/names").await;
+    assert!(names["data"].as_array().unwrap().contains(&json!(name)));
+}
+
+#[tokio::test]
+async fn activity_is_counted_from_the_rollup() {
+    let server = TestServer::start().await;
+    let environment = format!("activity-{}", Uuid::new_v4().simple());
+    let (t1, t2, t3) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let trace = |id: Uuid, name: &str, timestamp: &str| json!({"trace": {"id": id, "name": name, "timestamp": timestamp, "environment": environment}});
+    for (id, name, timestamp) in [
+        (t1, "a", "2026-03-02T10:05:00Z"),
+        (t2, "b", "2026-03-02T10:20:00Z"),
+        (t3, "a", "2026-03-02T11:50:00Z"),
+    ] {
+        let res = server
+            .post_json("/v1/l/batch", &trace(id, name, timestamp))
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(id, 0).await;
+    }
+
+    let activity = |query: &str| {
+        let path = format!("/api/public/metrics/activity?{query}");
+        let server = &server;
+        async move {
+            let body = server.get_json(&path).await;
+            body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|d| {
+                    (
+                        d["hour"].as_str().unwrap().to_string(),
+                        d["group"].as_str().unwrap_or_default().to_string(),
+                        d["count"].as_i64().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+    let row = |hour: &str, group: &str, count: i64| (hour.to_string(), group.to_string(), count);
+    let whole = "fromTimestamp=2026-03-02T10:00:00Z&toTimestamp=2026-03-02T12:00:00Z";
+
+    // Partial buckets at the ends are counted exactly.
+    assert_eq!(
+        activity(
+            "fromTimestamp=2026-03-02T10:10:00Z&toTimestamp=2026-03-02T11:50:00Z&groupBy=name"
+        )
+        .await,
+        [
+            row("2026-03-02T10:00:00", "b", 1),
+            row("2026-03-02T11:00:00", "a", 1)
+        ]
+    );
+    // Half-hour offsets fall into whole local hours.
+    assert_eq!(
+        activity(&format!("{whole}&timezone=Asia/Kolkata")).await,
+        [
+            row("2026-03-02T15:00:00", "", 2),
+            row("2026-03-02T17:00:00", "", 1)
+        ]
+    );
+
+    // Renames and deletes move the counts.
+    let mut renamed = trace(t1, "c", "2026-03-02T10:05:00Z");
+    renamed["trace"]["update"] = json!(true);
+    assert_eq!(
+        server.post_json("/v1/l/batch", &renamed).await.status(),
+        200
+    );
+    let res = server
+        .http
+        .delete(server.url(&format!("/api/public/traces/{t2}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let expected = [
+        row("2026-03-02T10:00:00", "c", 1),
+        row("2026-03-02T11:00:00", "a", 1),
+    ];
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+    while activity(&format!("{whole}&groupBy=name")).await != expected {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "activity not updated"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let pool = sqlx::PgPool::connect(&server.database_url).await.unwrap();
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT name, trace_count FROM trace_activity WHERE environment = $1 ORDER BY name",
+    )
+    .bind(&environment)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(rows, [("a".to_string(), 1), ("c".to_string(), 1)]);
+}
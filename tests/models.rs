@@ -0,0 +1,151 @@
+//! Model pricing registry (`/api/public/models`) and ingest-time costs.
+
+mod common;
+
+use common::{observation, TestServer};
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn ingest_prices_observations_from_models() {
+    let server = TestServer::start().await;
+    let model = format!("priced-{}", Uuid::new_v4());
+    // Project-scoped so that tests sharing a database do not see the model.
+    let res = server
+        .post_json(
+            "/api/public/models",
+            &json!({
+                "modelName": model,
+                "matchPattern": format!("^{model}(-\\d+)?$"),
+                "inputPrice": 0.01,
+                "outputPrice": 0.02,
+                "projectId": server.project_id,
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    let created: JsonValue = res.json().await.unwrap();
+    assert_eq!(created["unit"], "TOKENS");
+
+    let trace_id = Uuid::new_v4();
+    let (priced, reported, unknown) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let generation = |id: Uuid, model: &str, cost: Option<f64>| {
+        json!({
+            "id": id,
+            "traceId": trace_id,
+            "type": "GENERATION",
+            "startTime": "2026-01-01T00:00:00Z",
+            "model": model,
+            "promptTokens": 100,
+            "completionTokens": 10,
+            "calculatedTotalCost": cost,
+        })
+    };
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({
+                "trace": {"id": trace_id},
+                "observations": [
+                    generation(priced, &format!("{model}-0613"), None),
+                    generation(reported, &model, Some(0.5)),
+                    generation(unknown, "unpriced", None),
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+
+    let trace = server.wait_for_trace(trace_id, 3).await;
+    let priced = observation(&trace, priced);
+    assert!((priced["calculatedInputCost"].as_f64().unwrap() - 1.0).abs() < 1e-9);
+    assert!((priced["calculatedOutputCost"].as_f64().unwrap() - 0.2).abs() < 1e-9);
+    assert!((priced["calculatedTotalCost"].as_f64().unwrap() - 1.2).abs() < 1e-9);
+    assert_eq!(priced["inputPrice"], 0.01);
+    assert_eq!(priced["modelId"], created["id"]);
+    // Reported costs are kept; unknown models stay unpriced.
+    assert_eq!(observation(&trace, reported)["calculatedTotalCost"], 0.5);
+    assert!(observation(&trace, unknown)["calculatedTotalCost"].is_null());
+    assert!((trace["totalCost"].as_f64().unwrap() - 1.7).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn models_crud() {
+    let server = TestServer::start().await;
+    let res = server
+        .post_json(
+            "/api/public/models",
+            &json!({
+                "modelName": "crud",
+                "matchPattern": "^crud$",
+                "totalPrice": 0.001,
+                "unit": "characters",
+                "projectId": server.project_id,
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    let created: JsonValue = res.json().await.unwrap();
+    let id = created["id"].as_str().unwrap();
+    assert_eq!(created["unit"], "CHARACTERS");
+    assert_eq!(created["projectId"], server.project_id);
+
+    let fetched = server.get_json(&format!("/api/public/models/{id}")).await;
+    assert_eq!(fetched["matchPattern"], "^crud$");
+    let list = server.get_json("/api/public/models").await;
+    assert!(list["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|m| m["id"] == id));
+
+    let res = server
+        .http
+        .put(server.url(&format!("/api/public/models/{id}")))
+        .bearer_auth(&server.token)
+        .json(&json!({
+            "modelName": "crud",
+            "matchPattern": "^crud-v2$",
+            "totalPrice": 0.002,
+            "projectId": server.project_id,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let updated: JsonValue = res.json().await.unwrap();
+    assert_eq!(updated["matchPattern"], "^crud-v2$");
+    assert_eq!(updated["totalPrice"], 0.002);
+
+    let res = server
+        .http
+        .delete(server.url(&format!("/api/public/models/{id}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/models/{id}")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+
+    let res = server
+        .post_json(
+            "/api/public/models",
+            &json!({"modelName": "bad", "matchPattern": "(", "inputPrice": 1.0}),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+    let res = server
+        .post_json(
+            "/api/public/models",
+            &json!({"modelName": "free", "matchPattern": "^free$"}),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+}
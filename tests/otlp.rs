@@ -0,0 +1,329 @@
+//! OTLP/HTTP (`/api/public/otel/v1/traces`) JSON, protobuf and gzip payloads.
+
+mod common;
+
+use common::{observation, TestServer};
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::ExportTraceServiceRequest,
+    common::v1::{any_value, AnyValue, KeyValue},
+    resource::v1::Resource,
+    trace::v1::{span::Event, ResourceSpans, ScopeSpans, Span, Status},
+};
+use prost::Message;
+use serde_json::json;
+use uuid::Uuid;
+
+const OTLP_PATH: &str = "/api/public/otel/v1/traces";
+
+/// 2026-01-01T00:00:00Z.
+const START_NANOS: u64 = 1_767_225_600_000_000_000;
+
+/// The observation id xtrace derives from an OTLP span id.
+fn span_uuid(span_id: [u8; 8]) -> Uuid {
+    let mut padded = [0u8; 16];
+    padded[8..].copy_from_slice(&span_id);
+    Uuid::from_bytes(padded)
+}
+
+/// Fresh root and child span ids. Span ids are stored as observation ids,
+/// which stay with the project that first wrote them.
+fn span_ids() -> ([u8; 8], [u8; 8]) {
+    let raw = *Uuid::new_v4().as_bytes();
+    (raw[..8].try_into().unwrap(), raw[8..].try_into().unwrap())
+}
+
+fn attr(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value.to_string())),
+        }),
+    }
+}
+
+/// A root agent span and a child LLM generation in one trace.
+fn export_request(trace_id: Uuid, root: [u8; 8], child: [u8; 8]) -> ExportTraceServiceRequest {
+    let span = |span_id: [u8; 8], parent: Option<[u8; 8]>, name: &str, attributes| Span {
+        trace_id: trace_id.as_bytes().to_vec(),
+        span_id: span_id.to_vec(),
+        parent_span_id: parent.map(|p| p.to_vec()).unwrap_or_default(),
+        name: name.to_string(),
+        start_time_unix_nano: START_NANOS,
+        end_time_unix_nano: START_NANOS + 1_000_000_000,
+        attributes,
+        ..Default::default()
+    };
+    ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: Some(Resource {
+                attributes: vec![attr("service.name", "checkout-agent")],
+                ..Default::default()
+            }),
+            scope_spans: vec![ScopeSpans {
+                spans: vec![
+                    span(
+                        root,
+                        None,
+                        "agent",
+                        vec![
+                            attr("langfuse.trace.name", "otlp-trace"),
+                            attr("user.id", "carol"),
+                        ],
+                    ),
+                    Span {
+                        status: Some(Status {
+                            code: 2,
+                            message: "boom".to_string(),
+                        }),
+                        events: vec![Event {
+                            time_unix_nano: START_NANOS + 500_000_000,
+                            name: "exception".to_string(),
+                            attributes: vec![attr("exception.message", "boom")],
+                            ..Default::default()
+                        }],
+                        ..span(
+                            child,
+                            Some(root),
+                            "llm",
+                            vec![
+                                attr("langfuse.observation.type", "generation"),
+                                attr("gen_ai.request.model", "gpt-4o"),
+                                attr("langfuse.observation.output", r#"{"text":"hi"}"#),
+                                attr(
+                                    "langfuse.observation.usage_details",
+                                    r#"{"promptTokens":5,"completionTokens":8,"totalTokens":13}"#,
+                                ),
+                            ],
+                        )
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    }
+}
+
+async fn assert_mapped(server: &TestServer, trace_id: Uuid, root: [u8; 8], child: [u8; 8]) {
+    let trace = server.wait_for_trace(trace_id, 3).await;
+    assert_eq!(trace["name"], "otlp-trace");
+    assert_eq!(trace["userId"], "carol");
+    assert_eq!(trace["serviceName"], "checkout-agent");
+
+    let root_id = span_uuid(root);
+    assert!(observation(&trace, root_id)["parentObservationId"].is_null());
+
+    let generation = observation(&trace, span_uuid(child));
+    assert_eq!(generation["type"], "GENERATION");
+    assert_eq!(generation["parentObservationId"], root_id.to_string());
+    assert_eq!(generation["model"], "gpt-4o");
+    assert_eq!(generation["output"], json!({"text": "hi"}));
+    assert_eq!(generation["promptTokens"], 5);
+    assert_eq!(generation["completionTokens"], 8);
+    assert_eq!(generation["totalTokens"], 13);
+    assert_eq!(generation["startTime"], "2026-01-01T00:00:00Z");
+    assert_eq!(generation["endTime"], "2026-01-01T00:00:01Z");
+    assert_eq!(generation["level"], "ERROR");
+    assert_eq!(generation["statusMessage"], "boom");
+
+    let event = trace["observations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|o| o["type"] == "EVENT")
+        .expect("exception event");
+    assert_eq!(event["name"], "exception");
+    assert_eq!(event["parentObservationId"], span_uuid(child).to_string());
+    assert_eq!(event["startTime"], "2026-01-01T00:00:00.500Z");
+    assert_eq!(event["level"], "ERROR");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn otlp_protobuf_gzip_and_zstd() {
+    let server = TestServer::start().await;
+
+    for gzip in [false, true] {
+        let trace_id = Uuid::new_v4();
+        let (root, child) = span_ids();
+        let body = export_request(trace_id, root, child).encode_to_vec();
+
+        let res = server
+            .post_bytes(OTLP_PATH, "application/x-protobuf", gzip, body)
+            .await;
+        assert_eq!(
+            res.status(),
+            200,
+            "gzip={gzip}: {}",
+            res.text().await.unwrap()
+        );
+        assert_mapped(&server, trace_id, root, child).await;
+    }
+
+    // The OTel Rust and Go SDKs compress large exports with zstd.
+    let trace_id = Uuid::new_v4();
+    let (root, child) = span_ids();
+    let body = export_request(trace_id, root, child).encode_to_vec();
+    let res = server
+        .http
+        .post(server.url(OTLP_PATH))
+        .bearer_auth(&server.token)
+        .header("content-type", "application/x-protobuf")
+        .header("content-encoding", "zstd")
+        .body(zstd::encode_all(body.as_slice(), 3).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200, "zstd: {}", res.text().await.unwrap());
+    assert_mapped(&server, trace_id, root, child).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn otlp_json() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let (root, child) = span_ids();
+    let string_attr = |key: &str, value: &str| json!({"key": key, "value": {"stringValue": value}});
+    let span = |span_id: [u8; 8], parent: Option<[u8; 8]>, name: &str, attributes| {
+        json!({
+            "traceId": hex::encode(trace_id.as_bytes()),
+            "spanId": hex::encode(span_id),
+            "parentSpanId": parent.map(hex::encode),
+            "name": name,
+            "startTimeUnixNano": START_NANOS.to_string(),
+            "endTimeUnixNano": (START_NANOS + 1_000_000_000).to_string(),
+            "attributes": attributes,
+        })
+    };
+    let mut generation = span(
+        child,
+        Some(root),
+        "llm",
+        json!([
+            string_attr("langfuse.observation.type", "generation"),
+            string_attr("gen_ai.request.model", "gpt-4o"),
+            string_attr("langfuse.observation.output", r#"{"text":"hi"}"#),
+            string_attr(
+                "langfuse.observation.usage_details",
+                r#"{"promptTokens":5,"completionTokens":8,"totalTokens":13}"#,
+            ),
+        ]),
+    );
+    generation["status"] = json!({"code": "STATUS_CODE_ERROR", "message": "boom"});
+    generation["events"] = json!([{
+        "timeUnixNano": (START_NANOS + 500_000_000).to_string(),
+        "name": "exception",
+        "attributes": [string_attr("exception.message", "boom")],
+    }]);
+    let body = json!({"resourceSpans": [{
+        "resource": {"attributes": [string_attr("service.name", "checkout-agent")]},
+        "scopeSpans": [{"spans": [
+            span(root, None, "agent", json!([
+                string_attr("langfuse.trace.name", "otlp-trace"),
+                string_attr("user.id", "carol"),
+            ])),
+            generation,
+        ]}],
+    }]});
+
+    let res = server.post_json(OTLP_PATH, &body).await;
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    assert_mapped(&server, trace_id, root, child).await;
+}
+
+#[tokio::test]
+async fn partial_exports_merge_into_the_trace() {
+    // Even a policy that overwrites traces keeps what earlier exports set.
+    assert_partial_exports_merge("overwrite").await;
+}
+
+#[tokio::test]
+async fn partial_exports_merge_into_the_trace_under_latest() {
+    assert_partial_exports_merge("latest").await;
+}
+
+/// Writes a trace through `/v1/l/batch`, then two OTLP exports of its spans
+/// under the conflict `policy`, and checks that each kept what the others set.
+async fn assert_partial_exports_merge(policy: &str) {
+    let server = TestServer::start_with(|c| c.conflict_policy = Some(policy.to_string())).await;
+    let trace_id = Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {"id": trace_id, "environment": "production"}}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 0).await;
+
+    let string_attr = |key: &str, value: &str| json!({"key": key, "value": {"stringValue": value}});
+    let export = |span_id: [u8; 8], parent: Option<[u8; 8]>, offset_secs: u64, attributes| {
+        let start = START_NANOS + offset_secs * 1_000_000_000;
+        json!({"resourceSpans": [{"scopeSpans": [{"spans": [{
+            "traceId": hex::encode(trace_id.as_bytes()),
+            "spanId": hex::encode(span_id),
+            "parentSpanId": parent.map(hex::encode),
+            "name": "step",
+            "startTimeUnixNano": start.to_string(),
+            "endTimeUnixNano": (start + 1_000_000_000).to_string(),
+            "attributes": attributes,
+        }]}]}]})
+    };
+    let (root, child) = span_ids();
+    let res = server
+        .post_json(
+            OTLP_PATH,
+            &export(
+                root,
+                None,
+                10,
+                json!([
+                    string_attr("langfuse.trace.name", "long-run"),
+                    string_attr("user.id", "dave"),
+                ]),
+            ),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 1).await;
+    // A later export with a span that carries no trace attributes.
+    let res = server
+        .post_json(OTLP_PATH, &export(child, Some(root), 20, json!([])))
+        .await;
+    assert_eq!(res.status(), 200);
+    let trace = server.wait_for_trace(trace_id, 2).await;
+
+    assert_eq!(trace["environment"], "production");
+    assert_eq!(trace["name"], "long-run");
+    assert_eq!(trace["userId"], "dave");
+    assert_eq!(trace["timestamp"], "2026-01-01T00:00:10Z");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn otlp_rejects_bad_payloads() {
+    let server = TestServer::start().await;
+
+    let res = server
+        .post_bytes(
+            OTLP_PATH,
+            "application/x-protobuf",
+            false,
+            b"\xff\xff\xff".to_vec(),
+        )
+        .await;
+    assert_eq!(res.status(), 400);
+
+    let res = server
+        .post_bytes(OTLP_PATH, "text/plain", false, b"hello".to_vec())
+        .await;
+    assert_eq!(res.status(), 400);
+
+    let res = server
+        .http
+        .post(server.url(OTLP_PATH))
+        .header("content-type", "application/x-protobuf")
+        .body(ExportTraceServiceRequest::default().encode_to_vec())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 401);
+}
@@ -0,0 +1,809 @@
+//! Query time window guardrails, trace list search, filters and ordering,
+//! and the aggregate, environment, tag and user views.
+
+mod common;
+
+use common::TestServer;
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn default_window_and_max_range() {
+    let project_token = format!("project-{}", Uuid::new_v4());
+    let keys_file = std::env::temp_dir().join(format!("xtrace-keys-{}", Uuid::new_v4()));
+    std::fs::write(
+        &keys_file,
+        format!("bearer {project_token} bound-project\n"),
+    )
+    .unwrap();
+    let server = TestServer::start_with(|c| {
+        c.default_query_window_secs = 7 * 24 * 3600;
+        c.max_query_range_secs = 30 * 24 * 3600;
+        c.keys_file = Some(keys_file.display().to_string());
+    })
+    .await;
+
+    let trace_id = Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {"id": trace_id, "timestamp": "2020-01-01T00:00:00Z"}}),
+        )
+        .await;
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    server.wait_for_trace(trace_id, 0).await;
+
+    // Outside the default window unless a lower bound is given.
+    let recent = server.get_json("/api/public/traces").await;
+    assert_eq!(recent["meta"]["totalItems"], 0);
+    let old = server
+        .get_json("/api/public/traces?fromTimestamp=2019-12-01T00:00:00Z")
+        .await;
+    assert_eq!(old["data"][0]["id"], trace_id.to_string());
+
+    let year = "/api/public/metrics/daily?fromTimestamp=2020-01-01T00:00:00Z&toTimestamp=2021-01-01T00:00:00Z";
+    let get =
+        |path: String, token: &str| server.http.get(server.url(&path)).bearer_auth(token).send();
+    assert_eq!(
+        get(year.to_string(), &server.token).await.unwrap().status(),
+        400
+    );
+    let res = get(format!("{year}&ignoreRangeLimit=true"), &server.token)
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    let res = get(format!("{year}&ignoreRangeLimit=true"), &project_token)
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 403);
+
+    std::fs::remove_file(keys_file).ok();
+}
+
+#[tokio::test]
+async fn traces_and_observations_are_searchable() {
+    let server = TestServer::start().await;
+    let word = format!("w{}", Uuid::new_v4().simple());
+    let (in_trace, in_observation, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let generation = Uuid::new_v4();
+    for body in [
+        json!({
+            "trace": {"id": in_trace, "input": {"messages": [{"role": "user", "content": format!("Tell me about {word}")}]}},
+        }),
+        json!({
+            "trace": {"id": in_observation},
+            "observations": [{
+                "id": generation,
+                "traceId": in_observation,
+                "type": "GENERATION",
+                "output": format!("The answer is {word} indeed"),
+            }],
+        }),
+        json!({"trace": {"id": other, "input": "nothing to see"}}),
+    ] {
+        let res = server.post_json("/v1/l/batch", &body).await;
+        assert_eq!(res.status(), 200);
+    }
+    server.wait_for_trace(other, 0).await;
+    server.wait_for_trace(in_observation, 1).await;
+
+    let traces = server
+        .get_json(&format!("/api/public/traces?q={word}"))
+        .await;
+    let mut ids: Vec<String> = traces["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["id"].as_str().unwrap().to_string())
+        .collect();
+    ids.sort();
+    let mut expected = vec![in_trace.to_string(), in_observation.to_string()];
+    expected.sort();
+    assert_eq!(ids, expected);
+    assert_eq!(traces["meta"]["totalItems"], 2);
+
+    let traces = server
+        .get_json(&format!(
+            "/api/public/traces?searchQuery=%22answer%20is%20{word}%22"
+        ))
+        .await;
+    assert_eq!(traces["data"].as_array().unwrap().len(), 1);
+    let traces = server
+        .get_json(&format!("/api/public/traces?q={word}%20-answer"))
+        .await;
+    assert_eq!(traces["data"][0]["id"], json!(in_trace));
+
+    let observations = server
+        .get_json(&format!("/api/public/observations?q={word}"))
+        .await;
+    assert_eq!(observations["data"].as_array().unwrap().len(), 1);
+    assert_eq!(observations["data"][0]["id"], json!(generation));
+}
+
+#[tokio::test]
+async fn traces_match_structured_filters() {
+    let server = TestServer::start().await;
+    let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    for (id, name, tags, metadata) in [
+        (
+            a,
+            "chat-support",
+            vec!["x", "y"],
+            json!({"user": {"plan": "pro"}, "turns": 3}),
+        ),
+        (
+            b,
+            "chat-sales",
+            vec!["y"],
+            json!({"user": {"plan": "free"}, "turns": 12}),
+        ),
+        (c, "summarize", vec![], json!({})),
+    ] {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({"trace": {"id": id, "name": name, "tags": tags, "metadata": metadata}}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(id, 0).await;
+    }
+
+    let matching = |filter: JsonValue| {
+        let server = &server;
+        async move {
+            let res = server
+                .http
+                .get(server.url("/api/public/traces"))
+                .query(&[("filter", filter.to_string())])
+                .bearer_auth(&server.token)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.status(), 200, "{filter}");
+            let body: JsonValue = res.json().await.unwrap();
+            let mut ids: Vec<String> = body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|t| t["id"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        }
+    };
+    let ids = |traces: &[Uuid]| {
+        let mut ids: Vec<String> = traces.iter().map(Uuid::to_string).collect();
+        ids.sort();
+        ids
+    };
+
+    assert_eq!(
+        matching(json!([{"type": "string", "column": "name", "operator": "starts with", "value": "CHAT"}])).await,
+        ids(&[a, b])
+    );
+    assert_eq!(
+        matching(json!([
+            {"type": "string", "column": "name", "operator": "contains", "value": "chat"},
+            {"type": "arrayOptions", "column": "tags", "operator": "all of", "value": ["x", "y"]},
+        ]))
+        .await,
+        ids(&[a])
+    );
+    assert_eq!(
+        matching(json!([{"type": "arrayOptions", "column": "tags", "operator": "none of", "value": ["y"]}])).await,
+        ids(&[c])
+    );
+    assert_eq!(
+        matching(json!([{"type": "stringObject", "column": "metadata", "key": "user.plan", "operator": "=", "value": "free"}])).await,
+        ids(&[b])
+    );
+    assert_eq!(
+        matching(json!([{"type": "numberObject", "column": "metadata", "key": "turns", "operator": ">=", "value": 3}])).await,
+        ids(&[a, b])
+    );
+    assert_eq!(
+        matching(json!([{"type": "stringOptions", "column": "name", "operator": "none of", "value": ["chat-support", "chat-sales"]}])).await,
+        ids(&[c])
+    );
+
+    for filter in [
+        json!([{"type": "number", "column": "name", "operator": ">", "value": 1}]),
+        json!([{"type": "string", "column": "nope", "operator": "=", "value": "x"}]),
+        json!([{"type": "string", "column": "name", "operator": "~", "value": "x"}]),
+        json!([{"type": "stringObject", "column": "metadata", "operator": "=", "value": "x"}]),
+    ] {
+        let res = server
+            .http
+            .get(server.url("/api/public/traces"))
+            .query(&[("filter", filter.to_string())])
+            .bearer_auth(&server.token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 400, "{filter}");
+    }
+}
+
+#[tokio::test]
+async fn traces_filter_by_minimum_level() {
+    let server = TestServer::start().await;
+    let (failed, warned, fine) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    for (trace_id, levels) in [
+        (failed, vec![json!("DEFAULT"), json!("ERROR")]),
+        (warned, vec![json!("WARNING")]),
+        (fine, vec![json!(null), json!("DEBUG")]),
+    ] {
+        let observations: Vec<JsonValue> = levels
+            .iter()
+            .map(|level| json!({"id": Uuid::new_v4(), "traceId": trace_id, "level": level}))
+            .collect();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({"trace": {"id": trace_id}, "observations": observations}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, levels.len()).await;
+    }
+
+    let ids = |body: JsonValue| {
+        let mut ids: Vec<String> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+    let expected = |traces: &[Uuid]| {
+        let mut ids: Vec<String> = traces.iter().map(Uuid::to_string).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(
+        ids(server.get_json("/api/public/traces?minLevel=ERROR").await),
+        expected(&[failed])
+    );
+    assert_eq!(
+        ids(server.get_json("/api/public/traces?minLevel=warning").await),
+        expected(&[failed, warned])
+    );
+    assert_eq!(
+        ids(server.get_json("/api/public/traces?minLevel=DEFAULT").await),
+        expected(&[failed, warned, fine])
+    );
+    let count = server
+        .get_json("/api/public/traces/count?minLevel=ERROR")
+        .await;
+    assert_eq!(count["total"], 1, "{count}");
+
+    let res = server
+        .http
+        .get(server.url("/api/public/traces?minLevel=FATAL"))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test]
+async fn traces_list_observation_aggregates() {
+    let server = TestServer::start().await;
+    let (chatty, costly, failing) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    for (trace_id, observations) in [
+        (
+            chatty,
+            vec![(10, "DEFAULT"), (10, "DEFAULT"), (10, "WARNING")],
+        ),
+        (costly, vec![(5000, "DEFAULT")]),
+        (failing, vec![(1, "ERROR"), (1, "ERROR")]),
+    ] {
+        let observations: Vec<JsonValue> = observations
+            .iter()
+            .map(|(tokens, level)| {
+                json!({
+                    "id": Uuid::new_v4(),
+                    "traceId": trace_id,
+                    "type": "GENERATION",
+                    "startTime": "2026-01-01T00:00:01Z",
+                    "completionStartTime": "2026-01-01T00:00:01.500Z",
+                    "totalTokens": tokens,
+                    "level": level,
+                })
+            })
+            .collect();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "timestamp": "2026-01-01T00:00:00Z"},
+                    "observations": observations,
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, observations.len()).await;
+    }
+
+    let window = "fromTimestamp=2025-12-31T00:00:00Z&toTimestamp=2026-01-02T00:00:00Z";
+    let ordered = |order_by: &str| {
+        let server = &server;
+        let path = format!("/api/public/traces?{window}&orderBy={order_by}");
+        async move {
+            server.get_json(&path).await["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|t| t["id"].as_str().unwrap().parse::<Uuid>().unwrap())
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(ordered("totalTokens").await, vec![costly, chatty, failing]);
+    assert_eq!(
+        ordered("observationCount.desc").await,
+        vec![chatty, failing, costly]
+    );
+    assert_eq!(ordered("errorCount").await[0], failing);
+    assert_eq!(ordered("totalTokens.asc").await[0], failing);
+
+    let traces = server
+        .get_json(&format!("/api/public/traces?{window}&orderBy=errorCount"))
+        .await;
+    let failing = &traces["data"][0];
+    assert_eq!(failing["observationCount"], 2);
+    assert_eq!(failing["errorCount"], 2);
+    assert_eq!(failing["totalTokens"], 2);
+    assert_eq!(failing["timeToFirstToken"], 1.5);
+}
+
+#[tokio::test]
+async fn daily_metrics_are_grouped_by_environment() {
+    let server = TestServer::start().await;
+    for (environment, cost) in [("production", 2.0), ("production", 1.0), ("staging", 0.5)] {
+        let trace_id = Uuid::new_v4();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {
+                        "id": trace_id,
+                        "timestamp": "2026-03-02T12:00:00Z",
+                        "environment": environment,
+                    },
+                    "observations": [{
+                        "id": Uuid::new_v4(),
+                        "traceId": trace_id,
+                        "type": "GENERATION",
+                        "model": "gpt-4o-mini",
+                        "totalTokens": 10,
+                        "calculatedTotalCost": cost,
+                    }],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 1).await;
+    }
+
+    let day = "fromTimestamp=2026-03-02T00:00:00Z&toTimestamp=2026-03-03T00:00:00Z";
+    let daily = server
+        .get_json(&format!(
+            "/api/public/metrics/daily?{day}&groupBy=environment"
+        ))
+        .await;
+    let data = daily["data"].as_array().unwrap();
+    assert_eq!(daily["meta"]["totalItems"], 2, "{daily}");
+    assert_eq!(data[0]["date"], "2026-03-02");
+    assert_eq!(data[0]["group"], "production");
+    assert_eq!(data[0]["countTraces"], 2);
+    assert_eq!(data[0]["usage"][0]["totalUsage"], 20);
+    assert_eq!(data[1]["group"], "staging");
+    assert_eq!(data[1]["countObservations"], 1);
+
+    let ungrouped = server
+        .get_json(&format!("/api/public/metrics/daily?{day}"))
+        .await;
+    assert_eq!(ungrouped["data"][0]["countTraces"], 3);
+    assert!(ungrouped["data"][0].get("group").is_none());
+
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/metrics/daily?{day}&groupBy=model")))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test]
+async fn environments_are_listed_with_first_and_last_seen() {
+    let server = TestServer::start().await;
+    for (environment, timestamp) in [
+        ("production", "2026-03-02T12:00:00Z"),
+        ("staging", "2026-03-03T12:00:00Z"),
+        ("production", "2026-03-01T08:00:00Z"),
+        ("production", "2026-03-04T18:00:00Z"),
+    ] {
+        let trace_id = Uuid::new_v4();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({"trace": {
+                    "id": trace_id,
+                    "timestamp": timestamp,
+                    "environment": environment,
+                }}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 0).await;
+    }
+
+    let environments = server.get_json("/api/public/environments").await;
+    assert_eq!(
+        environments["data"],
+        json!([
+            {
+                "name": "production",
+                "firstSeen": "2026-03-01T08:00:00Z",
+                "lastSeen": "2026-03-04T18:00:00Z",
+            },
+            {
+                "name": "staging",
+                "firstSeen": "2026-03-03T12:00:00Z",
+                "lastSeen": "2026-03-03T12:00:00Z",
+            },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn tags_are_counted_in_a_time_range() {
+    let server = TestServer::start().await;
+    for (tags, environment, timestamp) in [
+        (
+            json!(["checkout", "beta"]),
+            "production",
+            "2026-03-02T10:00:00Z",
+        ),
+        (
+            json!(["checkout", "beta", "Billing"]),
+            "production",
+            "2026-03-02T11:00:00Z",
+        ),
+        (json!(["checkout"]), "staging", "2026-03-02T12:00:00Z"),
+        (json!(["old"]), "production", "2026-02-01T12:00:00Z"),
+    ] {
+        let trace_id = Uuid::new_v4();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({"trace": {
+                    "id": trace_id,
+                    "timestamp": timestamp,
+                    "environment": environment,
+                    "tags": tags,
+                }}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 0).await;
+    }
+
+    let day = "fromTimestamp=2026-03-02T00:00:00Z&toTimestamp=2026-03-03T00:00:00Z";
+    let tags = server.get_json(&format!("/api/public/tags?{day}")).await;
+    assert_eq!(
+        tags["data"],
+        json!([
+            {"tag": "checkout", "count": 3},
+            {"tag": "beta", "count": 2},
+            {"tag": "Billing", "count": 1},
+        ])
+    );
+    assert_eq!(tags["fromTimestamp"], "2026-03-02T00:00:00Z");
+
+    let tags = server
+        .get_json(&format!("/api/public/tags?{day}&prefix=BI"))
+        .await;
+    assert_eq!(tags["data"], json!([{"tag": "Billing", "count": 1}]));
+    let tags = server
+        .get_json(&format!("/api/public/tags?{day}&limit=1"))
+        .await;
+    assert_eq!(tags["data"], json!([{"tag": "checkout", "count": 3}]));
+    let tags = server
+        .get_json(&format!("/api/public/tags?{day}&environment=staging"))
+        .await;
+    assert_eq!(tags["data"], json!([{"tag": "checkout", "count": 1}]));
+}
+
+#[tokio::test]
+async fn analytics_queries_aggregate_views() {
+    let server = TestServer::start().await;
+    for (model, cost, start) in [
+        ("gpt-4o", 2.0, "2026-04-01T10:00:00Z"),
+        ("gpt-4o", 1.0, "2026-04-02T10:00:00Z"),
+        ("claude", 0.5, "2026-04-02T11:00:00Z"),
+    ] {
+        let trace_id = Uuid::new_v4();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "name": "chat", "timestamp": start},
+                    "observations": [
+                        {
+                            "id": Uuid::new_v4(),
+                            "traceId": trace_id,
+                            "type": "GENERATION",
+                            "model": model,
+                            "startTime": start,
+                            "calculatedTotalCost": cost,
+                        },
+                        {"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN", "startTime": start},
+                    ],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 2).await;
+        let res = server
+            .post_json(
+                "/api/public/scores",
+                &json!({"traceId": trace_id, "name": "quality", "value": cost}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+    }
+
+    let analytics = |query: JsonValue| {
+        server
+            .http
+            .get(server.url("/api/public/metrics"))
+            .bearer_auth(&server.token)
+            .query(&[("query", query.to_string())])
+            .send()
+    };
+    let window = json!({
+        "fromTimestamp": "2026-04-01T00:00:00Z",
+        "toTimestamp": "2026-04-03T00:00:00Z",
+    });
+    let with_window = |mut query: JsonValue| {
+        query
+            .as_object_mut()
+            .unwrap()
+            .extend(window.as_object().unwrap().clone());
+        query
+    };
+
+    let res = analytics(with_window(json!({
+        "view": "observations",
+        "dimensions": [{"field": "model"}],
+        "metrics": [
+            {"measure": "count", "aggregation": "count"},
+            {"measure": "totalCost", "aggregation": "sum"},
+        ],
+        "filters": [{"type": "string", "column": "type", "operator": "=", "value": "GENERATION"}],
+    })))
+    .await
+    .unwrap();
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(
+        body["data"],
+        json!([
+            {"model": "gpt-4o", "count_count": 2, "sum_totalCost": 3},
+            {"model": "claude", "count_count": 1, "sum_totalCost": 0.5},
+        ])
+    );
+
+    let res = analytics(with_window(json!({
+        "view": "traces",
+        "metrics": [{"measure": "count", "aggregation": "count"}],
+        "timeDimension": {"granularity": "day"},
+    })))
+    .await
+    .unwrap();
+    let body: JsonValue = res.json().await.unwrap();
+    let counts: Vec<&JsonValue> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| &row["count_count"])
+        .collect();
+    assert_eq!(counts, vec![&json!(1), &json!(2)], "{body}");
+    assert!(body["data"][0]["time_dimension"]
+        .as_str()
+        .unwrap()
+        .starts_with("2026-04-01T00:00:00"));
+
+    let res = analytics(json!({
+        "view": "scores",
+        "dimensions": [{"field": "name"}],
+        "metrics": [{"measure": "value", "aggregation": "max"}],
+        "fromTimestamp": "2020-01-01T00:00:00Z",
+        "toTimestamp": "2100-01-01T00:00:00Z",
+    }))
+    .await
+    .unwrap();
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["data"], json!([{"name": "quality", "max_value": 2}]));
+
+    for query in [
+        json!({"view": "sessions", "metrics": [{"measure": "count", "aggregation": "count"}]}),
+        json!({"view": "traces", "metrics": [{"measure": "tokens", "aggregation": "sum"}]}),
+        json!({"view": "traces", "metrics": [{"measure": "count", "aggregation": "p95"}]}),
+        json!({"view": "traces", "dimensions": [{"field": "model"}], "metrics": [{"measure": "count", "aggregation": "count"}]}),
+        json!({"view": "traces", "metrics": [{"measure": "count", "aggregation": "count"}], "orderBy": [{"field": "name"}]}),
+    ] {
+        let res = analytics(with_window(query)).await.unwrap();
+        assert_eq!(res.status(), 400);
+    }
+}
+
+#[tokio::test]
+async fn usage_is_summed_per_user() {
+    let server = TestServer::start().await;
+    let (alice, bob) = (
+        format!("alice-{}", Uuid::new_v4()),
+        format!("bob-{}", Uuid::new_v4()),
+    );
+    for (user, tokens, cost) in [(&alice, 100, 1.0), (&alice, 50, 0.5), (&bob, 10, 2.0)] {
+        let trace_id = Uuid::new_v4();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "userId": user, "timestamp": "2026-05-01T12:00:00Z"},
+                    "observations": [{
+                        "id": Uuid::new_v4(),
+                        "traceId": trace_id,
+                        "type": "GENERATION",
+                        "promptTokens": tokens,
+                        "completionTokens": tokens,
+                        "totalTokens": 2 * tokens,
+                        "calculatedTotalCost": cost,
+                    }],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 1).await;
+    }
+
+    let range = "fromTimestamp=2026-05-01T00:00:00Z&toTimestamp=2026-05-02T00:00:00Z";
+    let users = server
+        .get_json(&format!("/api/public/metrics/users?{range}"))
+        .await;
+    assert_eq!(users["meta"]["totalItems"], 2, "{users}");
+    let data = users["data"].as_array().unwrap();
+    assert_eq!(data[0]["userId"], json!(bob));
+    assert_eq!(data[1]["userId"], json!(alice));
+    assert_eq!(data[1]["countTraces"], 2);
+    assert_eq!(data[1]["inputUsage"], 150);
+    assert_eq!(data[1]["totalUsage"], 300);
+    assert_eq!(data[1]["totalCost"], 1.5);
+
+    let page = server
+        .get_json(&format!("/api/public/metrics/users?{range}&limit=1&page=2"))
+        .await;
+    assert_eq!(page["data"][0]["userId"], json!(alice));
+    assert_eq!(page["meta"]["totalPages"], 2);
+}
+
+#[tokio::test]
+async fn users_are_listed_with_their_activity() {
+    let server = TestServer::start().await;
+    for (user, tokens, cost, timestamp) in [
+        ("alice", 100, 1.0, "2026-05-01T12:00:00Z"),
+        ("alice", 50, 0.5, "2026-05-03T12:00:00Z"),
+        ("bob", 10, 2.0, "2026-05-02T12:00:00Z"),
+    ] {
+        let trace_id = Uuid::new_v4();
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "userId": user, "timestamp": timestamp},
+                    "observations": [{
+                        "id": Uuid::new_v4(),
+                        "traceId": trace_id,
+                        "type": "GENERATION",
+                        "promptTokens": tokens,
+                        "completionTokens": tokens,
+                        "totalTokens": 2 * tokens,
+                        "calculatedTotalCost": cost,
+                    }],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 1).await;
+    }
+
+    let users = server.get_json("/api/public/users").await;
+    assert_eq!(users["meta"]["totalItems"], 2, "{users}");
+    let data = users["data"].as_array().unwrap();
+    assert_eq!(data[0]["userId"], "alice");
+    assert_eq!(data[0]["totalTraces"], 2);
+    assert_eq!(data[0]["totalObservations"], 2);
+    assert_eq!(data[0]["promptTokens"], 150);
+    assert_eq!(data[0]["totalTokens"], 300);
+    assert_eq!(data[0]["totalCost"], 1.5);
+    assert_eq!(data[0]["firstTrace"], "2026-05-01T12:00:00Z");
+    assert_eq!(data[0]["lastTrace"], "2026-05-03T12:00:00Z");
+    assert_eq!(data[1]["userId"], "bob");
+
+    let page = server.get_json("/api/public/users?limit=1&page=2").await;
+    assert_eq!(page["data"][0]["userId"], "bob");
+    assert_eq!(page["meta"]["totalPages"], 2);
+
+    let bob = server.get_json("/api/public/users/bob").await;
+    assert_eq!(bob["totalTraces"], 1);
+    assert_eq!(bob["totalTokens"], 20);
+    assert_eq!(bob["totalCost"], 2.0);
+    let res = server
+        .http
+        .get(server.url("/api/public/users/carol"))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn usage_is_summed_per_model() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let generation = |model: &str, level: &str, tokens: i64, cost: f64| {
+        json!({
+            "id": Uuid::new_v4(),
+            "traceId": trace_id,
+            "type": "GENERATION",
+            "model": model,
+            "provider": "openai",
+            "level": level,
+            "startTime": "2026-06-01T12:00:00Z",
+            "endTime": "2026-06-01T12:00:02Z",
+            "totalTokens": tokens,
+            "calculatedTotalCost": cost,
+        })
+    };
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({
+                "trace": {"id": trace_id, "timestamp": "2026-06-01T12:00:00Z"},
+                "observations": [
+                    generation("gpt-4o", "DEFAULT", 100, 1.0),
+                    generation("gpt-4o", "ERROR", 0, 0.0),
+                    generation("gpt-4o-mini", "DEFAULT", 30, 0.1),
+                    {"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN"},
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 4).await;
+
+    let models = server
+        .get_json("/api/public/metrics/models?fromTimestamp=2026-06-01T00:00:00Z&toTimestamp=2026-06-02T00:00:00Z")
+        .await;
+    assert_eq!(models["meta"]["totalItems"], 2, "{models}");
+    let top = &models["data"][0];
+    assert_eq!(top["model"], "gpt-4o");
+    assert_eq!(top["provider"], "openai");
+    assert_eq!(top["countRequests"], 2);
+    assert_eq!(top["countTraces"], 1);
+    assert_eq!(top["totalUsage"], 100);
+    assert_eq!(top["avgLatency"], 2.0);
+    assert_eq!(top["countErrors"], 1);
+    assert_eq!(top["errorRate"], 0.5);
+    assert_eq!(top["totalCost"], 1.0);
+    assert_eq!(models["data"][1]["model"], "gpt-4o-mini");
+}
@@ -0,0 +1,201 @@
+//! Monthly ingest quotas per project.
+
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::TestServer;
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+fn batch(trace_id: Uuid, tokens: i64) -> JsonValue {
+    json!({
+        "trace": {"id": trace_id, "timestamp": Utc::now()},
+        "observations": [{
+            "id": Uuid::new_v4(),
+            "traceId": trace_id,
+            "type": "GENERATION",
+            "totalTokens": tokens,
+        }],
+    })
+}
+
+async fn put_quota(server: &TestServer, body: &JsonValue) -> reqwest::Response {
+    server
+        .http
+        .put(server.url(&format!("/api/admin/quotas/{}", server.project_id)))
+        .bearer_auth(&server.token)
+        .json(body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn ingest_is_rejected_over_the_traces_quota() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let res = server.post_json("/v1/l/batch", &batch(trace_id, 10)).await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 1).await;
+
+    let res = put_quota(&server, &json!({"monthlyTraces": 1})).await;
+    assert_eq!(res.status(), 200);
+    let quota: JsonValue = res.json().await.unwrap();
+    assert_eq!(quota["monthlyTraces"], 1);
+    assert_eq!(quota["monthlyTokens"], JsonValue::Null);
+    assert_eq!(quota["traces"], 1);
+    assert_eq!(quota["tokens"], 10);
+    assert_eq!(quota["exceeded"], "traces");
+    assert!(quota["accountedAt"].is_string());
+
+    let res = server
+        .post_json("/v1/l/batch", &batch(Uuid::new_v4(), 10))
+        .await;
+    assert_eq!(res.status(), 402);
+    let headers = res.headers();
+    assert_eq!(headers["x-xtrace-quota-metric"], "traces");
+    assert_eq!(headers["x-xtrace-quota-limit"], "1");
+    assert_eq!(headers["x-xtrace-quota-used"], "1");
+    let reset: DateTime<Utc> = headers["x-xtrace-quota-reset"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(reset > Utc::now());
+    let retry_after: i64 = headers["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!(retry_after > 0);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["code"], "QUOTA_EXCEEDED");
+
+    let list = server.get_json("/api/admin/quotas").await;
+    assert!(list["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|q| q["projectId"] == server.project_id.as_str()));
+
+    // Raising the limit takes effect right away.
+    let res = put_quota(&server, &json!({"monthlyTraces": 10})).await;
+    assert_eq!(res.status(), 200);
+    let res = server
+        .post_json("/v1/l/batch", &batch(Uuid::new_v4(), 10))
+        .await;
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test]
+async fn ingest_is_rejected_over_the_tokens_quota() {
+    let server = TestServer::start().await;
+    let res = put_quota(&server, &json!({"monthlyTokens": 100})).await;
+    assert_eq!(res.status(), 200);
+    let quota: JsonValue = res.json().await.unwrap();
+    assert_eq!(quota["exceeded"], JsonValue::Null);
+
+    let trace_id = Uuid::new_v4();
+    let res = server.post_json("/v1/l/batch", &batch(trace_id, 150)).await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 1).await;
+
+    // Check again by setting the same limit.
+    let res = put_quota(&server, &json!({"monthlyTokens": 100})).await;
+    assert_eq!(res.json::<JsonValue>().await.unwrap()["exceeded"], "tokens");
+
+    let res = server
+        .post_json(
+            "/api/public/otel/v1/traces",
+            &json!({"resourceSpans": [{"scopeSpans": [{"spans": [{
+                "traceId": "0af7651916cd43dd8448eb211c80319c",
+                "spanId": "b7ad6b7169203331",
+                "name": "llm",
+                "startTimeUnixNano": "1700000000000000000",
+                "endTimeUnixNano": "1700000001000000000",
+            }]}]}]}),
+        )
+        .await;
+    assert_eq!(res.status(), 402);
+    assert_eq!(res.headers()["x-xtrace-quota-metric"], "tokens");
+
+    let path = format!("/api/admin/quotas/{}", server.project_id);
+    let res = server
+        .http
+        .delete(server.url(&path))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let res = server
+        .post_json("/v1/l/batch", &batch(Uuid::new_v4(), 10))
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = server
+        .http
+        .get(server.url(&path))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn deleting_traces_does_not_lower_usage() {
+    let server = TestServer::start().await;
+    let trace_ids = [Uuid::new_v4(), Uuid::new_v4()];
+    for trace_id in trace_ids {
+        let res = server.post_json("/v1/l/batch", &batch(trace_id, 10)).await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 1).await;
+    }
+    // Writing a trace again does not count it twice.
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {"id": trace_ids[0], "name": "again"}}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    while server.wait_for_trace(trace_ids[0], 1).await["name"] != "again" {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "trace not rewritten"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    for trace_id in trace_ids {
+        let res = server
+            .http
+            .delete(server.url(&format!("/api/public/traces/{trace_id}")))
+            .bearer_auth(&server.token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+    }
+
+    let res = put_quota(&server, &json!({"monthlyTraces": 2})).await;
+    let quota: JsonValue = res.json().await.unwrap();
+    assert_eq!(quota["traces"], 2);
+    assert_eq!(quota["tokens"], 20);
+    assert_eq!(quota["exceeded"], "traces");
+    let res = server
+        .post_json("/v1/l/batch", &batch(Uuid::new_v4(), 10))
+        .await;
+    assert_eq!(res.status(), 402);
+}
+
+#[tokio::test]
+async fn quotas_are_validated() {
+    let server = TestServer::start().await;
+    for body in [
+        json!({}),
+        json!({"monthlyTraces": -1}),
+        json!({"monthlyTraces": 10, "monthlyTokens": -5}),
+    ] {
+        let res = put_quota(&server, &body).await;
+        assert_eq!(res.status(), 400, "{body}");
+    }
+}
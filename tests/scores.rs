@@ -0,0 +1,71 @@
+//! Scores API (`/api/public/scores`).
+
+mod common;
+
+use common::TestServer;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn scores_record_source_and_key() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let score = |name: &str, source: &str, author: Option<&str>| {
+        json!({
+            "traceId": trace_id,
+            "name": name,
+            "value": 0.5,
+            "source": source,
+            "authorUserId": author,
+        })
+    };
+    for body in [
+        score("judge", "eval", Some("llm-judge-v2")),
+        score("review", "ANNOTATION", Some("alice")),
+        score("client", "API", None),
+    ] {
+        let res = server.post_json("/api/public/scores", &body).await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+    }
+
+    let key_id = format!("bearer:{}***", &server.token[..8]);
+    let evals = server
+        .get_json(&format!(
+            "/api/public/scores?traceId={trace_id}&source=eval"
+        ))
+        .await;
+    let data = evals["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["source"], "EVAL");
+    assert_eq!(data[0]["authorUserId"], "llm-judge-v2");
+    assert_eq!(data[0]["apiKeyId"], key_id);
+
+    let by_author = server
+        .get_json(&format!(
+            "/api/public/scores?traceId={trace_id}&authorUserId=alice"
+        ))
+        .await;
+    assert_eq!(by_author["meta"]["totalItems"], 1);
+    assert_eq!(by_author["data"][0]["name"], "review");
+
+    let by_key = server
+        .get_json(&format!(
+            "/api/public/scores?traceId={trace_id}&apiKeyId={}",
+            urlencode(&key_id)
+        ))
+        .await;
+    assert_eq!(by_key["meta"]["totalItems"], 3);
+
+    let res = server
+        .http
+        .get(server.url("/api/public/scores?source=human"))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 400);
+}
+
+fn urlencode(s: &str) -> String {
+    s.replace(':', "%3A").replace('*', "%2A")
+}
@@ -0,0 +1,103 @@
+//! HTTPS listener and client certificates (mutual TLS), with the test
+//! certificates in `tests/fixtures/tls`.
+
+mod common;
+
+use std::path::PathBuf;
+
+use common::TestServer;
+use serde_json::json;
+use uuid::Uuid;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+fn fixture_path(name: &str) -> String {
+    fixture(name).to_string_lossy().into_owned()
+}
+
+/// A client trusting the test CA, presenting the certificate `identity`
+/// (`client` or `untrusted-client`) if any.
+fn client(identity: Option<&str>) -> reqwest::Client {
+    let ca = std::fs::read(fixture("ca.pem")).expect("ca");
+    let mut builder = reqwest::Client::builder()
+        .use_rustls_tls()
+        .add_root_certificate(reqwest::Certificate::from_pem(&ca).expect("ca"));
+    if let Some(name) = identity {
+        let mut pem = std::fs::read(fixture(&format!("{name}.pem"))).expect("cert");
+        pem.extend(std::fs::read(fixture(&format!("{name}-key.pem"))).expect("key"));
+        builder = builder.identity(reqwest::Identity::from_pem(&pem).expect("identity"));
+    }
+    builder.build().expect("client")
+}
+
+async fn start(configure: impl FnOnce(&mut xtrace::ServerConfig)) -> TestServer {
+    TestServer::start_with(|c| {
+        c.tls_cert = Some(fixture_path("server.pem"));
+        c.tls_key = Some(fixture_path("server-key.pem"));
+        c.tls_client_ca = Some(fixture_path("ca.pem"));
+        configure(c);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn query_and_admin_routes_can_require_client_certificates() {
+    let server = start(|c| {
+        c.query_client_cert = Some("required".to_string());
+        c.admin_client_cert = Some("required".to_string());
+    })
+    .await;
+    let get = |client: reqwest::Client, path: &str| {
+        client
+            .get(server.url(path))
+            .bearer_auth(&server.token)
+            .send()
+    };
+
+    for path in ["/api/public/traces", "/api/admin/config"] {
+        let res = get(client(Some("client")), path).await.expect("request");
+        assert_eq!(res.status(), 200, "{path}");
+
+        let res = get(client(None), path).await.expect("request");
+        assert_eq!(res.status(), 403, "{path}");
+        let body: serde_json::Value = res.json().await.unwrap();
+        assert!(
+            body.to_string().contains("client certificate required"),
+            "{body}"
+        );
+
+        // A certificate the client CA did not issue fails the handshake.
+        assert!(
+            get(client(Some("untrusted-client")), path).await.is_err(),
+            "{path}"
+        );
+    }
+
+    // Ingest keeps its own mode (off): the token alone still writes.
+    let res = client(None)
+        .post(server.url("/v1/l/batch"))
+        .bearer_auth(&server.token)
+        .json(&json!({"trace": {"id": Uuid::new_v4()}}))
+        .send()
+        .await
+        .expect("request");
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test]
+async fn client_certificates_are_optional_by_default() {
+    let server = start(|_| {}).await;
+    for identity in [None, Some("client")] {
+        let res = client(identity)
+            .get(server.url("/api/public/traces"))
+            .bearer_auth(&server.token)
+            .send()
+            .await
+            .expect("request");
+        assert_eq!(res.status(), 200);
+    }
+}
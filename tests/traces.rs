@@ -0,0 +1,524 @@
+//! Trace endpoints: deletion (`DELETE /api/public/traces/:traceId`,
+//! `POST /api/public/traces/delete`), batched details, existence checks,
+//! sharing, the observation tree and offloaded payloads.
+
+mod common;
+
+use common::{object_store, observation, TestServer};
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+async fn delete(server: &TestServer, path: &str) -> reqwest::Response {
+    server
+        .http
+        .delete(server.url(path))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn traces_are_deleted_with_their_data() {
+    let server = TestServer::start().await;
+    let (single, listed, tagged, kept) = (
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+    );
+    let session = format!("s-{single}");
+    let tag = format!("drop-{single}");
+    for (trace_id, tags) in [
+        (single, vec![]),
+        (listed, vec![]),
+        (tagged, vec![tag.clone()]),
+        (kept, vec![]),
+    ] {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({
+                    "trace": {"id": trace_id, "sessionId": session, "tags": tags},
+                    "observations": [{"id": Uuid::new_v4(), "traceId": trace_id}],
+                }),
+            )
+            .await;
+        assert_eq!(res.status(), 200, "{}", res.text().await.unwrap());
+        server.wait_for_trace(trace_id, 1).await;
+    }
+    let res = server
+        .post_json(
+            "/api/public/scores",
+            &json!({"traceId": single, "name": "quality", "value": 1.0}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = delete(&server, &format!("/api/public/traces/{single}")).await;
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["observations"], 1);
+    assert_eq!(body["scores"], 1);
+    let res = delete(&server, &format!("/api/public/traces/{single}")).await;
+    assert_eq!(res.status(), 404);
+    let scores = server
+        .get_json(&format!("/api/public/scores?traceId={single}"))
+        .await;
+    assert_eq!(scores["data"], json!([]));
+
+    let unknown = Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/api/public/traces/delete",
+            &json!({"traceIds": [listed, unknown]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["deleted"]["traces"], 1);
+    assert_eq!(body["missing"], json!([unknown]));
+
+    let res = server
+        .post_json(
+            "/api/public/traces/delete",
+            &json!({"filter": {"tags": [tag]}}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["deleted"]["traces"], 1);
+    assert_eq!(body["deleted"]["observations"], 1);
+
+    for body in [json!({"filter": {}}), json!({})] {
+        let res = server.post_json("/api/public/traces/delete", &body).await;
+        assert_eq!(res.status(), 400);
+    }
+
+    let traces = server
+        .get_json(&format!("/api/public/traces?sessionId={session}"))
+        .await;
+    let ids: Vec<&JsonValue> = traces["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| &t["id"])
+        .collect();
+    assert_eq!(ids, vec![&json!(kept)]);
+    let session = server
+        .get_json(&format!("/api/public/sessions/{session}"))
+        .await;
+    assert_eq!(session["traceCount"], 1, "{session}");
+}
+
+#[tokio::test]
+async fn deletes_are_rejected_while_read_only() {
+    let server = TestServer::start_with(|c| c.read_only = true).await;
+    let res = delete(&server, &format!("/api/public/traces/{}", Uuid::new_v4())).await;
+    assert_eq!(res.status(), 503);
+}
+
+#[tokio::test]
+async fn traces_are_fetched_in_one_batch() {
+    let server = TestServer::start_with(|c| c.batch_get_max_traces = 3).await;
+    let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+    for (trace_id, name) in [(first, "first"), (second, "second")] {
+        let res = server
+            .post_json(
+                "/v1/l/batch",
+                &json!({"trace": {"id": trace_id, "name": name}, "observations": [
+                    {"id": Uuid::new_v4(), "traceId": trace_id, "type": "GENERATION"}
+                ]}),
+            )
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 1).await;
+    }
+
+    let unknown = Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/api/public/traces/batch-get",
+            &json!({"traceIds": [second, unknown, first, second]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    let names: Vec<&JsonValue> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| &t["name"])
+        .collect();
+    assert_eq!(names, vec![&json!("second"), &json!("first")]);
+    assert_eq!(body["data"][0]["observations"].as_array().unwrap().len(), 1);
+    assert_eq!(body["missing"], json!([unknown]));
+
+    // The older name answers the same way.
+    let res = server
+        .post_json("/api/public/traces/get-many", &json!({"traceIds": [first]}))
+        .await;
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["data"][0]["id"], json!(first));
+
+    let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+    let res = server
+        .post_json("/api/public/traces/batch-get", &json!({"traceIds": ids}))
+        .await;
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test]
+async fn delivery_is_checked_without_trace_bodies() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let name = format!("delivery-{trace_id}");
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {"id": trace_id, "name": name}}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 0).await;
+
+    let head = |id: Uuid| {
+        server
+            .http
+            .head(server.url(&format!("/api/public/traces/{id}")))
+            .bearer_auth(&server.token)
+            .send()
+    };
+    let res = head(trace_id).await.unwrap();
+    assert_eq!(res.status(), 200);
+    let last_modified = res.headers()["last-modified"].to_str().unwrap();
+    assert!(last_modified.ends_with(" GMT"), "{last_modified}");
+    assert!(res.bytes().await.unwrap().is_empty());
+    assert_eq!(head(Uuid::new_v4()).await.unwrap().status(), 404);
+
+    let count = server
+        .get_json(&format!("/api/public/traces/count?name={name}"))
+        .await;
+    assert_eq!(count["total"], 1);
+}
+
+#[tokio::test]
+async fn observations_are_nested_into_a_tree() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let (root, first, second, leaf, orphan) = (
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+    );
+    let observation = |id: Uuid, parent: Option<Uuid>, start: &str, end: &str, cost: f64| {
+        json!({
+            "id": id,
+            "traceId": trace_id,
+            "parentObservationId": parent,
+            "type": "SPAN",
+            "startTime": format!("2026-01-01T00:00:{start}Z"),
+            "endTime": format!("2026-01-01T00:00:{end}Z"),
+            "calculatedTotalCost": cost,
+        })
+    };
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({
+                "trace": {"id": trace_id},
+                "observations": [
+                    observation(leaf, Some(second), "03", "04", 0.25),
+                    observation(second, Some(root), "02", "05", 0.5),
+                    observation(first, Some(root), "01", "02", 1.0),
+                    observation(root, None, "00", "10", 0.0),
+                    observation(orphan, Some(Uuid::new_v4()), "06", "07", 2.0),
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 5).await;
+
+    let tree = server
+        .get_json(&format!("/api/public/traces/{trace_id}/tree"))
+        .await;
+    assert_eq!(tree["observationCount"], 5);
+    assert_eq!(tree["totalCost"], 3.75);
+    let roots = tree["roots"].as_array().unwrap();
+    assert_eq!(roots.len(), 2);
+    assert_eq!(roots[0]["id"], json!(root));
+    assert_eq!(roots[0]["duration"], 10.0);
+    assert_eq!(roots[0]["subtreeCost"], 1.75);
+    let children = roots[0]["children"].as_array().unwrap();
+    assert_eq!(children[0]["id"], json!(first));
+    assert_eq!(children[1]["id"], json!(second));
+    assert_eq!(children[1]["cost"], 0.5);
+    assert_eq!(children[1]["subtreeCost"], 0.75);
+    assert_eq!(children[1]["children"][0]["id"], json!(leaf));
+    assert_eq!(children[1]["children"][0]["children"], json!([]));
+    assert_eq!(roots[1]["id"], json!(orphan));
+
+    let res = server
+        .http
+        .get(server.url(&format!("/api/public/traces/{}/tree", Uuid::new_v4())))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn public_traces_are_shared_without_credentials() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let ingest = |trace: JsonValue| {
+        let server = &server;
+        async move {
+            let res = server
+                .post_json(
+                    "/v1/l/batch",
+                    &json!({"trace": trace, "observations": [{"id": Uuid::new_v4(), "traceId": trace_id}]}),
+                )
+                .await;
+            assert_eq!(res.status(), 200);
+        }
+    };
+    ingest(json!({"id": trace_id, "name": "shared"})).await;
+    server.wait_for_trace(trace_id, 1).await;
+
+    let shared_path = format!("/api/public/shared/traces/{trace_id}");
+    let shared = |path: String| server.http.get(server.url(&path)).send();
+    assert_eq!(shared(shared_path.clone()).await.unwrap().status(), 404);
+
+    let patch = |public: bool| {
+        server
+            .http
+            .patch(server.url(&format!("/api/public/traces/{trace_id}")))
+            .bearer_auth(&server.token)
+            .json(&json!({ "public": public }))
+            .send()
+    };
+    let res = patch(true).await.unwrap();
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["public"], true);
+    assert_eq!(body["sharePath"], json!(shared_path));
+
+    // Payloads without `public` keep the flag.
+    ingest(json!({"id": trace_id, "name": "renamed"})).await;
+    server.wait_for_trace(trace_id, 2).await;
+    let res = shared(shared_path.clone()).await.unwrap();
+    assert_eq!(res.status(), 200);
+    let trace: JsonValue = res.json().await.unwrap();
+    assert_eq!(trace["name"], "renamed");
+    assert_eq!(trace["public"], true);
+    assert_eq!(trace["observations"].as_array().unwrap().len(), 2);
+
+    assert_eq!(patch(false).await.unwrap().status(), 200);
+    assert_eq!(shared(shared_path).await.unwrap().status(), 404);
+
+    let res = server
+        .http
+        .patch(server.url(&format!("/api/public/traces/{}", Uuid::new_v4())))
+        .bearer_auth(&server.token)
+        .json(&json!({"public": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn traces_are_updated_after_ingest() {
+    let server = TestServer::start().await;
+    let trace_id = Uuid::new_v4();
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"trace": {
+                "id": trace_id,
+                "tags": ["a", "b"],
+                "metadata": {"team": "search", "draft": true},
+            }}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(trace_id, 0).await;
+
+    let patch = |body: JsonValue| {
+        server
+            .http
+            .patch(server.url(&format!("/api/public/traces/{trace_id}")))
+            .bearer_auth(&server.token)
+            .json(&body)
+            .send()
+    };
+    let res = patch(json!({
+        "tags": {"add": ["c", "a"], "remove": ["b"]},
+        "bookmarked": true,
+        "metadata": {"reviewed": "yes", "draft": null},
+    }))
+    .await
+    .unwrap();
+    assert_eq!(res.status(), 200);
+    let body: JsonValue = res.json().await.unwrap();
+    assert_eq!(body["tags"], json!(["a", "c"]));
+    assert_eq!(body["bookmarked"], true);
+    assert_eq!(body["public"], false);
+    assert_eq!(
+        body["metadata"],
+        json!({"team": "search", "reviewed": "yes"})
+    );
+    assert!(body.get("sharePath").is_none());
+
+    let trace = server
+        .get_json(&format!("/api/public/traces/{trace_id}"))
+        .await;
+    assert_eq!(trace["tags"], json!(["a", "c"]));
+    assert_eq!(trace["bookmarked"], true);
+    let filter = json!([
+        {"type": "boolean", "column": "bookmarked", "operator": "=", "value": true},
+        {"type": "arrayOptions", "column": "tags", "operator": "any of", "value": ["c"]},
+    ]);
+    let listed: JsonValue = server
+        .http
+        .get(server.url("/api/public/traces"))
+        .bearer_auth(&server.token)
+        .query(&[("filter", filter.to_string())])
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed["data"][0]["id"], json!(trace_id), "{listed}");
+
+    // A patch waits for the trace's write lock, as ingest does.
+    let mut lock: sqlx::PgConnection = sqlx::Connection::connect(&server.database_url)
+        .await
+        .unwrap();
+    sqlx::query("SELECT pg_advisory_lock(hashtextextended($1, 0))")
+        .bind(trace_id.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let pending = tokio::spawn(patch(json!({"bookmarked": false})));
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(!pending.is_finished());
+    sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+        .bind(trace_id.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    assert_eq!(pending.await.unwrap().unwrap().status(), 200);
+
+    assert_eq!(patch(json!({})).await.unwrap().status(), 400);
+    for body in [json!({"metadata": "x"}), json!({"name": "x"})] {
+        assert_eq!(patch(body).await.unwrap().status(), 422);
+    }
+}
+
+#[tokio::test]
+async fn large_payloads_are_offloaded_and_read_back() {
+    let (endpoint, objects) = object_store().await;
+    let server = TestServer::start_with(|c| {
+        c.offload_s3_bucket = Some("traces".to_string());
+        c.offload_s3_endpoint = Some(endpoint);
+        c.offload_s3_access_key = Some("minio".to_string());
+        c.offload_s3_secret_key = Some("minio-secret".to_string());
+        c.offload_threshold_bytes = 1024;
+        c.offload_preview_bytes = 16;
+        c.conflict_policy = Some("merge".to_string());
+    })
+    .await;
+    let (trace_id, large, small) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let input = json!({"messages": [{"role": "user", "content": "é".repeat(2000)}]});
+    // Uploads happen before the write takes the trace's lock.
+    let mut lock: sqlx::PgConnection = sqlx::Connection::connect(&server.database_url)
+        .await
+        .unwrap();
+    sqlx::query("SELECT pg_advisory_lock(hashtextextended($1, 0))")
+        .bind(trace_id.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({
+                "trace": {"id": trace_id},
+                "observations": [
+                    {"id": large, "traceId": trace_id, "input": input, "output": "ok"},
+                    {"id": small, "traceId": trace_id, "input": "hi"},
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let keys = objects.wait_for(1).await;
+    sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+        .bind(trace_id.to_string())
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let trace = server.wait_for_trace(trace_id, 2).await;
+
+    assert!(
+        keys[0].starts_with(&format!(
+            "traces/{}/{trace_id}/{large}/input-",
+            server.project_id
+        )),
+        "{keys:?}"
+    );
+    assert_eq!(observation(&trace, large)["input"], input);
+    assert_eq!(observation(&trace, large)["output"], "ok");
+    assert_eq!(observation(&trace, small)["input"], "hi");
+    let single = server
+        .get_json(&format!("/api/public/observations/{large}"))
+        .await;
+    assert_eq!(single["input"], input);
+
+    // Postgres keeps the key and a preview.
+    let pool = sqlx::PgPool::connect(&server.database_url).await.unwrap();
+    let (stored, key): (JsonValue, Option<String>) =
+        sqlx::query_as("SELECT input, input_offload FROM observations WHERE id = $1")
+            .bind(large)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored, json!("{\"messages\":[{\"c…"));
+    assert_eq!(
+        Some(format!("traces/{}", key.unwrap())),
+        Some(keys[0].clone())
+    );
+
+    // A merged update without an input keeps the object.
+    let res = server
+        .post_json(
+            "/v1/l/batch",
+            &json!({"observations": [{"id": large, "traceId": trace_id, "level": "WARNING"}]}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(15);
+    loop {
+        let trace = server
+            .get_json(&format!("/api/public/traces/{trace_id}"))
+            .await;
+        if observation(&trace, large)["level"] == "WARNING" {
+            assert_eq!(observation(&trace, large)["input"], input);
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "update not applied");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    let res = delete(&server, &format!("/api/public/traces/{trace_id}")).await;
+    assert_eq!(res.status(), 200);
+    objects.wait_for(0).await;
+}
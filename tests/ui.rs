@@ -0,0 +1,34 @@
+//! The embedded web UI (`ui` feature).
+#![cfg(feature = "ui")]
+
+mod common;
+
+use common::TestServer;
+
+#[tokio::test]
+async fn ui_is_served_without_credentials() {
+    let server = TestServer::start().await;
+    for (path, content_type) in [
+        ("/ui/", "text/html"),
+        ("/ui/app.js", "text/javascript"),
+        ("/ui/app.css", "text/css"),
+    ] {
+        let res = server.http.get(server.url(path)).send().await.unwrap();
+        assert_eq!(res.status(), 200, "{path}");
+        let headers = res.headers();
+        assert!(headers["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with(content_type));
+        assert!(headers.contains_key("content-security-policy"));
+    }
+
+    // The page's API calls still need a key.
+    let res = server
+        .http
+        .get(server.url("/api/public/traces"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 401);
+}
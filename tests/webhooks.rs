@@ -0,0 +1,133 @@
+//! Webhook rules and their deliveries.
+
+mod common;
+
+use common::{receiver, TestServer};
+use serde_json::{json, Value as JsonValue};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn rules_fire_once_per_matching_trace() {
+    let server = TestServer::start().await;
+    let (url, received) = receiver().await;
+
+    let res = server
+        .post_json(
+            "/api/admin/webhooks",
+            &json!({"name": "errors", "url": url, "minLevel": "error"}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let rule: JsonValue = res.json().await.unwrap();
+    assert_eq!(rule["minLevel"], "ERROR");
+    assert_eq!(rule["projectId"], server.project_id);
+    assert_eq!(rule["enabled"], true);
+    // A rule for another project does not see these traces.
+    let res = server
+        .post_json(
+            "/api/admin/webhooks",
+            &json!({"name": "other", "url": url, "minLevel": "DEBUG", "projectId": format!("other-{}", Uuid::new_v4())}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let failing = Uuid::new_v4();
+    let passing = Uuid::new_v4();
+    let batch = |trace_id: Uuid, level: &'static str| {
+        json!({"trace": {"id": trace_id, "name": "checkout"}, "observations": [
+            {"id": Uuid::new_v4(), "traceId": trace_id, "type": "SPAN", "level": level}
+        ]})
+    };
+    for (trace_id, level) in [(failing, "ERROR"), (passing, "WARNING")] {
+        let res = server
+            .post_json("/v1/l/batch", &batch(trace_id, level))
+            .await;
+        assert_eq!(res.status(), 200);
+        server.wait_for_trace(trace_id, 1).await;
+    }
+
+    received.wait_for(1).await;
+
+    // More observations on a trace that already fired do not fire again.
+    let res = server
+        .post_json("/v1/l/batch", &batch(failing, "ERROR"))
+        .await;
+    assert_eq!(res.status(), 200);
+    server.wait_for_trace(failing, 2).await;
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+
+    let received = received.bodies();
+    assert_eq!(received.len(), 1, "{received:?}");
+    let event = &received[0];
+    assert_eq!(event["type"], "rule.matched");
+    assert_eq!(event["data"]["ruleId"], rule["id"]);
+    assert_eq!(event["data"]["ruleName"], "errors");
+    assert_eq!(event["data"]["traceId"], failing.to_string());
+    assert_eq!(event["data"]["traceName"], "checkout");
+    assert_eq!(event["data"]["levelCount"], 1);
+}
+
+#[tokio::test]
+async fn rules_are_managed_through_the_admin_api() {
+    let server = TestServer::start().await;
+    let project_id = format!("hooks-{}", Uuid::new_v4());
+
+    for body in [
+        json!({"name": "", "url": "https://example.com/hook", "minLevel": "ERROR"}),
+        json!({"name": "bad url", "url": "ftp://example.com/hook", "minLevel": "ERROR"}),
+        json!({"name": "no condition", "url": "https://example.com/hook"}),
+        json!({"name": "bad level", "url": "https://example.com/hook", "minLevel": "FATAL"}),
+        json!({"name": "bad cost", "url": "https://example.com/hook", "minCost": -1}),
+    ] {
+        let res = server.post_json("/api/admin/webhooks", &body).await;
+        assert_eq!(res.status(), 400, "{body}");
+    }
+
+    let res = server
+        .post_json(
+            "/api/admin/webhooks",
+            &json!({"name": "expensive", "url": "https://example.com/hook", "minCost": 0.5, "projectId": project_id}),
+        )
+        .await;
+    assert_eq!(res.status(), 200);
+    let rule: JsonValue = res.json().await.unwrap();
+    let path = format!("/api/admin/webhooks/{}", rule["id"].as_str().unwrap());
+
+    let res = server
+        .http
+        .put(server.url(&path))
+        .bearer_auth(&server.token)
+        .json(&json!({"name": "slow", "url": "https://example.com/hook", "minLatency": 30, "environment": "production", "enabled": false, "projectId": project_id}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let updated = server.get_json(&path).await;
+    assert_eq!(updated["name"], "slow");
+    assert_eq!(updated["minCost"], JsonValue::Null);
+    assert_eq!(updated["minLatency"], 30.0);
+    assert_eq!(updated["environment"], "production");
+    assert_eq!(updated["enabled"], false);
+
+    let list = server
+        .get_json(&format!("/api/admin/webhooks?projectId={project_id}"))
+        .await;
+    assert_eq!(list["data"].as_array().unwrap().len(), 1);
+
+    let res = server
+        .http
+        .delete(server.url(&path))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let res = server
+        .http
+        .get(server.url(&path))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 404);
+}